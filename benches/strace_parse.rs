@@ -1,55 +1,145 @@
-use criterion::{criterion_group, criterion_main, Benchmark, Criterion, Throughput};
+use bstr::ByteSlice;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 use parser::parser::parse_line;
-use parser::syscall_data::build_syscall_data;
+use parser::syscall_data::{build_syscall_data, build_syscall_data_with_hint, SizeHint};
 use rayon::prelude::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-fn build_strace_data(buffer: &str) {
+// Counts allocations and reallocations so map growth can be compared
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn count_allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn build_strace_data(buffer: &[u8]) {
     let _syscall_data = build_syscall_data(buffer);
 }
 
-fn parse_strace_st(buffer: &str) {
+fn build_strace_data_unsized(buffer: &[u8]) {
+    let _syscall_data = build_syscall_data_with_hint(buffer, SizeHint::default());
+}
+
+fn parse_strace_st(buffer: &[u8]) {
     let mut parsed_data = Vec::new();
     for line in buffer.lines() {
         parsed_data.push(parse_line(line));
     }
 }
 
-fn parse_strace_mt(buffer: &str) {
-    let _data: Vec<_> = buffer.par_lines().map(|l| parse_line(l)).collect();
+fn parse_strace_mt(buffer: &[u8]) {
+    let _data: Vec<_> = buffer.par_split(|c| *c == b'\n').map(parse_line).collect();
 }
 
 fn data_benchmark(c: &mut Criterion) {
-    c.bench(
-        "Full Pipeline",
-        Benchmark::new("Throughput -- Multi-Threaded", |b| {
-            b.iter(|| build_strace_data(DATA))
-        })
-        .throughput(Throughput::Bytes(DATA.len() as u64)),
-    );
+    let mut group = c.benchmark_group("Full Pipeline");
+    group.throughput(Throughput::Bytes(DATA.len() as u64));
+    group.bench_function("Throughput -- Multi-Threaded", |b| {
+        b.iter(|| build_strace_data(DATA))
+    });
+    group.finish();
 }
 
-fn throughput_bench(c: &mut Criterion) {
-    c.bench(
-        "Parser-Only",
-        Benchmark::new("Throughput -- Single-Threaded", |b| {
-            b.iter(|| parse_strace_st(DATA))
-        })
-        .throughput(Throughput::Bytes(DATA.len() as u64)),
-    );
+// PIDs take turns making bursts of syscalls, so maps keep growing throughout
+fn many_pids_trace() -> Vec<u8> {
+    let syscalls = [
+        "read",
+        "write",
+        "open",
+        "close",
+        "fstat",
+        "lseek",
+        "mmap",
+        "munmap",
+        "poll",
+        "ioctl",
+        "futex",
+        "sendto",
+        "recvfrom",
+        "epoll_wait",
+        "getpid",
+        "stat",
+        "lstat",
+        "access",
+    ];
+    let mut trace = Vec::new();
+
+    for round in 0..4 {
+        for pid in 1000..3000 {
+            for syscall in syscalls.iter() {
+                trace.extend_from_slice(
+                    format!(
+                        "{} 00:09:{:02}.{:06} {}(3</dev/null>, \"a\", 1) = 1 <0.000010>\n",
+                        pid, round, pid, syscall
+                    )
+                    .as_bytes(),
+                );
+            }
+        }
+    }
 
-    c.bench(
-        "Parser-Only",
-        Benchmark::new("Throughput -- Multi-Threaded", |b| {
-            b.iter(|| parse_strace_mt(DATA))
-        })
-        .throughput(Throughput::Bytes(DATA.len() as u64)),
+    trace
+}
+
+fn map_sizing_bench(c: &mut Criterion) {
+    let trace = many_pids_trace();
+    let sized = count_allocations(|| build_strace_data(&trace));
+    let unsized_ct = count_allocations(|| build_strace_data_unsized(&trace));
+    println!(
+        "Map Sizing: {} allocations with size hint, {} without",
+        sized, unsized_ct
     );
+
+    let mut group = c.benchmark_group("Map Sizing");
+    group.throughput(Throughput::Bytes(trace.len() as u64));
+    group.bench_function("Estimate Only", |b| b.iter(|| SizeHint::estimate(&trace)));
+    group.bench_function("With Size Hint", |b| b.iter(|| build_strace_data(&trace)));
+    group.bench_function("Without Size Hint", |b| {
+        b.iter(|| build_strace_data_unsized(&trace))
+    });
+    group.finish();
+}
+
+fn throughput_bench(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Parser-Only");
+    group.throughput(Throughput::Bytes(DATA.len() as u64));
+    group.bench_function("Throughput -- Single-Threaded", |b| {
+        b.iter(|| parse_strace_st(DATA))
+    });
+    group.bench_function("Throughput -- Multi-Threaded", |b| {
+        b.iter(|| parse_strace_mt(DATA))
+    });
+    group.finish();
 }
 
-criterion_group!(benches, data_benchmark, throughput_bench);
+criterion_group!(benches, data_benchmark, map_sizing_bench, throughput_bench);
 criterion_main!(benches);
 
-static DATA: &'static str = r##"
+static DATA: &[u8] = br##"
 7387  00:09:47.789648 wait4(-1,  <unfinished ...>
 826   00:09:47.789757 restart_syscall(<... resuming interrupted poll ...> <unfinished ...>
 7112  00:09:47.789777 epoll_wait(39<anon_inode:[eventpoll]>,  <unfinished ...>
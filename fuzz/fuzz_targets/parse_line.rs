@@ -0,0 +1,33 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use parser::parser::{parse_line, LineData};
+use parser::syscall_data::build_syscall_data;
+
+// Checks that a slice returned by the parser points into the line it was given
+fn borrowed_from(line: &[u8], part: &[u8]) -> bool {
+    let line_range = line.as_ptr_range();
+    let part_range = part.as_ptr_range();
+    part.is_empty() || (line_range.start <= part_range.start && part_range.end <= line_range.end)
+}
+
+fuzz_target!(|data: &[u8]| {
+    for line in data.split(|c| *c == b'\n') {
+        if let Some(LineData::Syscall(raw_data)) = parse_line(line) {
+            assert!(raw_data
+                .syscall
+                .first()
+                .is_some_and(|c| c.is_ascii_alphabetic() || *c == b'_'));
+            assert!(borrowed_from(line, raw_data.time));
+            assert!(borrowed_from(line, raw_data.syscall));
+            if let Some(error) = raw_data.error {
+                assert!(borrowed_from(line, error));
+            }
+            if let Some(file) = raw_data.file() {
+                assert!(borrowed_from(line, file));
+            }
+        }
+    }
+
+    // Aggregation must also cope with whatever the parser accepts
+    let _pid_data = build_syscall_data(data);
+});
@@ -0,0 +1,33 @@
+#ifndef STRACE_PARSER_H
+#define STRACE_PARSER_H
+
+/* Generated by cbindgen from src/capi.rs, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Summarizes the trace in `buf`, which need not be NUL terminated, as a JSON
+// object in the same format as `strace-parser <INPUT> baseline save`:
+// each syscall's calls, errors, and total time, the PIDs, the traced time,
+// and the files opened.
+//
+// Returns NULL if `buf` is NULL or the trace can't be summarized. The string
+// must be released with `strace_parser_free_string`.
+//
+// # Safety
+//
+// `buf` must point to `len` readable bytes, which are not modified during
+// the call.
+char *strace_parser_summary_json(const uint8_t *buf, size_t len);
+
+// Releases a string returned by this library. Passing NULL does nothing.
+//
+// # Safety
+//
+// `s` must have been returned by this library and not already released.
+void strace_parser_free_string(char *s);
+
+#endif /* STRACE_PARSER_H */
@@ -0,0 +1,212 @@
+use crate::io_data;
+use crate::output;
+use crate::parser::{self, LineData};
+use crate::path_calls;
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::io::Error;
+
+/// Files read this many times over are flagged as likely missing a cache
+pub const REREAD_FACTOR: f64 = 10.0;
+
+/// The bytes read from a file compared with its size
+#[derive(Clone, Debug, PartialEq)]
+pub struct Amplification<'a> {
+    pub file: &'a [u8],
+    pub reads: usize,
+    pub bytes_read: u64,
+    /// The largest size a 'stat' call reported for the file
+    pub size: u64,
+}
+
+impl<'a> Amplification<'a> {
+    /// How many times over the file was read, e.g. 12.0 when 1.2 MB were
+    /// read from a 100 KB file
+    pub fn factor(&self) -> f64 {
+        self.bytes_read as f64 / self.size as f64
+    }
+
+    pub fn is_reread(&self) -> bool {
+        self.factor() >= REREAD_FACTOR
+    }
+}
+
+/// The size of each regular file as reported by the 'stat' family of calls
+/// in the trace, 'fstat' on a fd named by '-y' included. A file that grew
+/// during the trace is given its largest size.
+pub fn file_sizes(buffer: &[u8]) -> HashMap<&[u8], u64> {
+    buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(|line| match parser::parse_line(line)? {
+            LineData::Syscall(raw_data) if raw_data.error.is_none() => {
+                match raw_data.syscall {
+                    b"fstat" | b"fstat64" | b"fstatat64" | b"lstat" | b"lstat64"
+                    | b"newfstatat" | b"stat" | b"stat64" | b"statx" => {}
+                    _ => return None,
+                }
+                let size = stat_size(line)?;
+                Some((path_calls::call_path(line, &raw_data)?, size))
+            }
+            _ => None,
+        })
+        .fold(HashMap::default, |mut sizes, (path, size)| {
+            let entry = sizes.entry(path).or_insert(0);
+            *entry = size.max(*entry);
+            sizes
+        })
+        .reduce(HashMap::default, |mut sizes, other| {
+            for (path, size) in other {
+                let entry = sizes.entry(path).or_insert(0);
+                *entry = size.max(*entry);
+            }
+            sizes
+        })
+}
+
+// 17819 13:43:39.889240 fstat(3</etc/ld.so.cache>, {st_mode=S_IFREG|0644, st_size=69296, ...}) = 0 <0.000010>
+//                                                                         ^^^^^
+fn stat_size(line: &[u8]) -> Option<u64> {
+    // Sockets, pipes, and devices have no meaningful size
+    if !line.contains_str("S_IFREG") {
+        return None;
+    }
+
+    let start = line
+        .find("st_size=")
+        .map(|pos| pos + "st_size=".len())
+        .or_else(|| line.find("stx_size=").map(|pos| pos + "stx_size=".len()))?;
+    let digits = &line[start..];
+    let end = digits
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+
+    digits[..end].to_str().ok()?.parse().ok()
+}
+
+/// How many times over `pids` read each file with a known, non-zero size,
+/// the most amplified first
+pub fn amplification<'a>(
+    buffer: &'a [u8],
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData<'a>>,
+) -> Vec<Amplification<'a>> {
+    let sizes = file_sizes(buffer);
+    let mut files: HashMap<&[u8], Amplification> = HashMap::default();
+
+    for io_call in io_data::io_calls(pids, raw_data).values().flatten() {
+        if !io_call.is_read() || io_call.error.is_some() {
+            continue;
+        }
+        let size = match sizes.get(io_call.fd) {
+            Some(&size) if size > 0 => size,
+            _ => continue,
+        };
+
+        let entry = files.entry(io_call.fd).or_insert_with(|| Amplification {
+            file: io_call.fd,
+            reads: 0,
+            bytes_read: 0,
+            size,
+        });
+        entry.reads += 1;
+        entry.bytes_read += io_call.bytes.max(0) as u64;
+    }
+
+    let mut files: Vec<_> = files.into_values().collect();
+    files.sort_by(|x, y| {
+        y.factor()
+            .total_cmp(&x.factor())
+            .then_with(|| x.file.cmp(y.file))
+    });
+
+    files
+}
+
+/// Prints how many times over each file read by `pids` was read, flagging
+/// those read at least `REREAD_FACTOR` times
+pub fn print_amplification(
+    buffer: &[u8],
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let files = amplification(buffer, pids, raw_data);
+
+    output::print_heading("\nRead Amplification by File\n-----------\n")?;
+    if files.is_empty() {
+        output::print_note(
+            "  No file sizes found for the files read, they're taken from 'stat' and 'fstat' calls traced with '-y'\n",
+        )?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("reads", 8, Align::Right, 2),
+        Column::new("bytes read", 12, Align::Right, 1),
+        Column::new("file size", 12, Align::Right, 1),
+        Column::new("times read", 10, Align::Right, 0),
+        Column::new("flag", 7, Align::Left, 3),
+        Column::new("file name", 9, Align::Left, 0),
+    ]);
+
+    for file in &files {
+        table.add_row(vec![
+            file.reads.to_string(),
+            file.bytes_read.to_string(),
+            file.size.to_string(),
+            format!("{:.2}x", file.factor()),
+            if file.is_reread() { "re-read" } else { "" }.to_string(),
+            file.file.to_str_lossy().to_string(),
+        ]);
+    }
+    table.print(width)?;
+
+    let reread = files.iter().filter(|f| f.is_reread()).count();
+    let (files_were, them) = match reread {
+        1 => ("file was", "it"),
+        _ => ("files were", "them"),
+    };
+    if reread > 0 {
+        output::print_note(&format!(
+            "\n  {} {} read {:.0} or more times over, consider caching {}\n",
+            reread, files_were, REREAD_FACTOR, them,
+        ))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn amplification_compares_reads_to_size() {
+        let input = br##"477   10:00:00.000000 openat(AT_FDCWD, "/etc/app.conf", O_RDONLY) = 3</etc/app.conf> <0.000010>
+477   10:00:00.100000 fstat(3</etc/app.conf>, {st_mode=S_IFREG|0644, st_size=10, ...}) = 0 <0.000010>
+477   10:00:00.200000 read(3</etc/app.conf>, "0123456789", 4096) = 10 <0.000010>
+477   10:00:00.300000 pread64(3</etc/app.conf>, "0123456789", 4096, 0) = 10 <0.000010>
+478   10:00:00.350000 read(3</etc/app.conf>,  <unfinished ...>
+477   10:00:00.400000 fstat(4<pipe:[300]>, {st_mode=S_IFIFO|0600, st_size=0, ...}) = 0 <0.000010>
+478   10:00:00.450000 <... read resumed>"0123456789", 4096) = 10 <0.000010>
+477   10:00:00.500000 stat("/var/big.db", {st_mode=S_IFREG|0644, st_size=1000, ...}) = 0 <0.000010>
+477   10:00:00.600000 read(5</var/big.db>, "01234", 5) = 5 <0.000010>
+477   10:00:00.700000 read(4<pipe:[300]>, "01234", 5) = 5 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let files = amplification(input, &[477, 478], &pid_data_map);
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].file, b"/etc/app.conf");
+        assert_eq!(files[0].reads, 3);
+        assert_eq!(files[0].factor(), 3.0);
+        assert!(!files[0].is_reread());
+        assert_eq!(files[1].size, 1000);
+        assert_eq!(files[1].factor(), 0.005);
+    }
+}
@@ -0,0 +1,548 @@
+use crate::count_summary::CountSummary;
+use crate::output;
+use crate::syscall_data::PidData;
+use crate::syscall_stats::SyscallStats;
+use crate::table::{Align, Column, Table};
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error;
+use std::fmt;
+use std::io::{prelude::*, stdout, Error};
+
+const PROFILE_VERSION: u32 = 1;
+// Syscalls made fewer times than this in both traces are too noisy to compare
+const MIN_CALLS: u64 = 10;
+// Files listed for each side of a changed file set, the rest are counted
+const FILES_LISTED: usize = 10;
+
+// Syscall times are summed from f32s, whose widening error is rounded away to
+// the µs strace prints
+fn round_to_us(ms: f64) -> f64 {
+    (ms * 1000.0).round() / 1000.0
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyscallProfile {
+    pub calls: u64,
+    pub errors: u64,
+    pub time_ms: f64,
+    /// Times of the syscall's first and last calls, to find them in the trace.
+    /// Profiles saved before ranges were kept have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last: Option<String>,
+}
+
+impl SyscallProfile {
+    fn avg_ms(&self) -> f64 {
+        if self.calls > 0 {
+            self.time_ms / self.calls as f64
+        } else {
+            0.0
+        }
+    }
+
+    fn error_pct(&self) -> f64 {
+        if self.calls > 0 {
+            self.errors as f64 / self.calls as f64 * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The metrics of a trace kept to compare later traces against, small
+/// enough to store in place of the trace itself
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub traced_secs: f64,
+    pub pids: usize,
+    pub syscalls: BTreeMap<String, SyscallProfile>,
+    /// Paths passed to successful 'open', 'openat', and 'creat' calls
+    pub files: BTreeSet<String>,
+    /// Saved from the totals printed by 'strace -c', which have no traced
+    /// time, PIDs, or files
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub counts_only: bool,
+    /// The '--title' given when the profile was saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl Profile {
+    pub fn from_trace(
+        session_stats: &HashMap<Pid, Vec<SyscallStats>>,
+        raw_data: &HashMap<Pid, PidData>,
+        traced_secs: f64,
+    ) -> Profile {
+        let mut syscalls: BTreeMap<String, SyscallProfile> = BTreeMap::new();
+        for stats in session_stats.values().flatten() {
+            let entry = syscalls
+                .entry(stats.name.to_str_lossy().to_string())
+                .or_default();
+            entry.calls += stats.count as u64;
+            entry.errors += stats.error_count() as u64;
+            entry.time_ms += stats.total as f64;
+        }
+        for entry in syscalls.values_mut() {
+            entry.time_ms = round_to_us(entry.time_ms);
+        }
+
+        let mut ranges: HashMap<&[u8], (&[u8], &[u8])> = HashMap::default();
+        for (name, data) in raw_data
+            .values()
+            .flat_map(|pid_data| &pid_data.syscall_data)
+        {
+            if let (Some(first), Some(last)) = (data.first_time, data.last_time) {
+                let range = ranges.entry(name).or_insert((first, last));
+                range.0 = range.0.min(first);
+                range.1 = range.1.max(last);
+            }
+        }
+        for (name, (first, last)) in ranges {
+            if let Some(entry) = syscalls.get_mut(&*name.to_str_lossy()) {
+                entry.first = Some(output::timestamp(first));
+                entry.last = Some(output::timestamp(last));
+            }
+        }
+
+        let files = raw_data
+            .values()
+            .flat_map(|pid_data| pid_data.open_events.iter().chain(&pid_data.dir_events))
+            .filter(|e| matches!(e.syscall, b"creat" | b"open" | b"openat") && e.error.is_none())
+            .filter_map(|e| e.file())
+            .map(|f| f.to_str_lossy().to_string())
+            .collect();
+
+        Profile {
+            traced_secs,
+            pids: raw_data.len(),
+            syscalls,
+            files,
+            counts_only: false,
+            title: output::title().map(String::from),
+        }
+    }
+
+    /// A profile of the totals printed by 'strace -c'
+    pub fn from_counts(counts: &CountSummary) -> Profile {
+        let mut syscalls: BTreeMap<String, SyscallProfile> = BTreeMap::new();
+        for count in &counts.syscalls {
+            let entry = syscalls
+                .entry(count.name.to_str_lossy().to_string())
+                .or_default();
+            entry.calls += count.calls.max(0) as u64;
+            entry.errors += count.errors.max(0) as u64;
+            entry.time_ms += count.total as f64;
+        }
+        for entry in syscalls.values_mut() {
+            entry.time_ms = round_to_us(entry.time_ms);
+        }
+
+        Profile {
+            traced_secs: 0.0,
+            pids: 0,
+            syscalls,
+            files: BTreeSet::new(),
+            counts_only: true,
+            title: output::title().map(String::from),
+        }
+    }
+
+    // The heading line of the profile in a comparison
+    fn describe(&self) -> String {
+        let described = if self.counts_only {
+            format!("'strace -c' totals, {} syscalls", self.syscalls.len())
+        } else {
+            format!(
+                "{:.3}s traced, {} PIDs, {} syscalls, {} files",
+                self.traced_secs,
+                self.pids,
+                self.syscalls.len(),
+                self.files.len()
+            )
+        };
+        match &self.title {
+            Some(title) => format!("{} ({})", title, described),
+            None => described,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let saved = SavedProfile {
+            version: PROFILE_VERSION,
+            profile: self,
+        };
+        let mut json = serde_json::to_string(&saved).expect("profiles have only string keys");
+        json.push('\n');
+        json
+    }
+
+    pub fn from_json(text: &str) -> Result<Profile, ProfileError> {
+        let invalid = |e: serde_json::Error| ProfileError(e.to_string());
+
+        // The version is checked first, a newer profile may not parse at all
+        let version: ProfileVersion = serde_json::from_str(text).map_err(invalid)?;
+        if version.version != PROFILE_VERSION {
+            return Err(ProfileError("Unsupported profile version".into()));
+        }
+        let saved: SavedProfile<Profile> = serde_json::from_str(text).map_err(invalid)?;
+
+        Ok(saved.profile)
+    }
+
+    // Traces are compared by rate, so captures of different lengths line up
+    fn per_sec(&self, calls: u64) -> f64 {
+        if self.traced_secs > 0.0 {
+            calls as f64 / self.traced_secs
+        } else {
+            calls as f64
+        }
+    }
+}
+
+// A profile as saved, with the version of its format
+#[derive(Serialize, Deserialize)]
+struct SavedProfile<P> {
+    version: u32,
+    #[serde(flatten)]
+    profile: P,
+}
+
+#[derive(Deserialize)]
+struct ProfileVersion {
+    version: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileError(String);
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid baseline profile: {}", self.0)
+    }
+}
+
+impl error::Error for ProfileError {}
+
+/// A metric of one syscall that moved by more than the tolerance
+#[derive(Clone, Debug, PartialEq)]
+pub struct Deviation {
+    pub syscall: String,
+    pub metric: &'static str,
+    pub baseline: f64,
+    pub current: f64,
+}
+
+impl Deviation {
+    /// Relative change from the baseline, `None` when the baseline was zero
+    pub fn change(&self) -> Option<f64> {
+        if self.baseline > 0.0 {
+            Some((self.current - self.baseline) / self.baseline)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Comparison {
+    pub deviations: Vec<Deviation>,
+    pub new_files: Vec<String>,
+    pub missing_files: Vec<String>,
+}
+
+impl Comparison {
+    pub fn is_empty(&self) -> bool {
+        self.deviations.is_empty() && self.new_files.is_empty() && self.missing_files.is_empty()
+    }
+}
+
+/// Compares each syscall's call rate, average time, and error rate, flagging
+/// those that changed by more than `tolerance`, e.g. 0.2 for 20%, along with
+/// files opened in only one of the traces. 'strace -c' totals have no traced
+/// time or files, so against a full trace only their average time and error
+/// rate are compared, and two of them compare their total calls.
+pub fn compare(baseline: &Profile, current: &Profile, tolerance: f64) -> Comparison {
+    let none = SyscallProfile::default();
+    let same_kind = baseline.counts_only == current.counts_only;
+    let names: BTreeSet<_> = baseline
+        .syscalls
+        .keys()
+        .chain(current.syscalls.keys())
+        .collect();
+
+    let mut deviations = Vec::new();
+    for name in names {
+        let base = baseline.syscalls.get(name).unwrap_or(&none);
+        let cur = current.syscalls.get(name).unwrap_or(&none);
+        if base.calls < MIN_CALLS && cur.calls < MIN_CALLS {
+            continue;
+        }
+
+        let metrics = [
+            (
+                "calls /s",
+                baseline.per_sec(base.calls),
+                current.per_sec(cur.calls),
+            ),
+            ("avg ms", base.avg_ms(), cur.avg_ms()),
+            ("errors %", base.error_pct(), cur.error_pct()),
+        ];
+        for (metric, base_value, cur_value) in metrics {
+            // A syscall missing from one trace only changes its rate
+            if metric != "calls /s" && (base.calls == 0 || cur.calls == 0) {
+                continue;
+            }
+            if metric == "calls /s" && !same_kind {
+                continue;
+            }
+
+            let exceeded = if base_value > 0.0 {
+                ((cur_value - base_value) / base_value).abs() > tolerance
+            } else {
+                cur_value > 0.0
+            };
+            if exceeded {
+                deviations.push(Deviation {
+                    syscall: name.clone(),
+                    metric,
+                    baseline: base_value,
+                    current: cur_value,
+                });
+            }
+        }
+    }
+
+    if baseline.counts_only || current.counts_only {
+        return Comparison {
+            deviations,
+            new_files: Vec::new(),
+            missing_files: Vec::new(),
+        };
+    }
+
+    Comparison {
+        deviations,
+        new_files: current.files.difference(&baseline.files).cloned().collect(),
+        missing_files: baseline.files.difference(&current.files).cloned().collect(),
+    }
+}
+
+pub fn print_comparison(
+    baseline: &Profile,
+    current: &Profile,
+    comparison: &Comparison,
+    tolerance: f64,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    output::print_heading("\nBaseline Comparison\n-----------\n")?;
+    output::print_heading(&format!(
+        "  Baseline: {}\n  Current:  {}\n",
+        baseline.describe(),
+        current.describe(),
+    ))?;
+
+    if !comparison.deviations.is_empty() {
+        let mut table = Table::new(vec![
+            Column::new("syscall", 17, Align::Left, 0),
+            Column::new("metric", 8, Align::Left, 0),
+            Column::new("baseline", 10, Align::Right, 2),
+            Column::new("current", 10, Align::Right, 1),
+            Column::new("change", 8, Align::Right, 0),
+        ]);
+        for deviation in &comparison.deviations {
+            table.add_row(vec![
+                deviation.syscall.clone(),
+                deviation.metric.to_string(),
+                format!("{:.3}", deviation.baseline),
+                format!("{:.3}", deviation.current),
+                match deviation.change() {
+                    Some(change) => format!("{:+.0}%", change * 100.0),
+                    None => "new".to_string(),
+                },
+            ]);
+        }
+        table.print(width)?;
+    }
+
+    print_files(
+        "Files opened that weren't in the baseline",
+        &comparison.new_files,
+    )?;
+    print_files(
+        "Files in the baseline that weren't opened",
+        &comparison.missing_files,
+    )?;
+
+    let changes = comparison.deviations.len()
+        + usize::from(!comparison.new_files.is_empty())
+        + usize::from(!comparison.missing_files.is_empty());
+    let summary = match changes {
+        0 => format!("  No changes beyond {:.0}%", tolerance * 100.0),
+        1 => format!("  1 change beyond {:.0}%", tolerance * 100.0),
+        n => format!("  {} changes beyond {:.0}%", n, tolerance * 100.0),
+    };
+    // Follows the heading directly when there is nothing else to show
+    let gap = if comparison.is_empty() { "" } else { "\n" };
+    writeln!(stdout(), "{}{}\n", gap, summary)?;
+
+    Ok(())
+}
+
+fn print_files(title: &str, files: &[String]) -> Result<(), Error> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let mut out = stdout().lock();
+    writeln!(out, "\n  {} ({}):", title, files.len())?;
+    for file in files.iter().take(FILES_LISTED) {
+        writeln!(out, "    {}", file)?;
+    }
+    if files.len() > FILES_LISTED {
+        writeln!(out, "    ...and {} more", files.len() - FILES_LISTED)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+    use crate::syscall_stats::build_syscall_stats;
+
+    fn profile(calls: u64, errors: u64, time_ms: f64, files: &[&str]) -> Profile {
+        let mut syscalls = BTreeMap::new();
+        syscalls.insert(
+            "read".to_string(),
+            SyscallProfile {
+                calls,
+                errors,
+                time_ms,
+                ..SyscallProfile::default()
+            },
+        );
+        Profile {
+            traced_secs: 10.0,
+            pids: 1,
+            syscalls,
+            files: files.iter().map(|f| f.to_string()).collect(),
+            counts_only: false,
+            title: None,
+        }
+    }
+
+    #[test]
+    fn baseline_round_trips_profile() {
+        let input = br##"477   10:00:00.000000 openat(AT_FDCWD, "/etc/\"hosts\"", O_RDONLY) = 3</etc/"hosts"> <0.000010>
+477   10:00:00.100000 openat(AT_FDCWD, "/missing", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000010>
+477   10:00:01.000000 read(3</etc/"hosts">, "a", 4096) = 1 <0.000020>"##;
+        let pid_data_map = build_syscall_data(input);
+        let stats = build_syscall_stats(&pid_data_map);
+        let profile = Profile::from_trace(&stats, &pid_data_map, 1.0);
+
+        assert_eq!(profile.syscalls["openat"].calls, 2);
+        assert_eq!(profile.syscalls["openat"].errors, 1);
+        assert_eq!(profile.syscalls["openat"].time_ms, 0.02);
+        assert!(profile.to_json().contains("\"time_ms\":0.02,"));
+        assert_eq!(
+            profile.syscalls["openat"].first.as_deref(),
+            Some("10:00:00.000000")
+        );
+        assert_eq!(
+            profile.syscalls["openat"].last.as_deref(),
+            Some("10:00:00.100000")
+        );
+        assert_eq!(
+            profile.files,
+            vec!["/etc/\\\"hosts\\\"".to_string()].into_iter().collect()
+        );
+        assert_eq!(Profile::from_json(&profile.to_json()), Ok(profile));
+        assert!(Profile::from_json("{\"version\":2}").is_err());
+    }
+
+    #[test]
+    fn baseline_keeps_title() {
+        let mut titled = profile(100, 0, 10.0, &[]);
+        titled.title = Some("nightly build".to_string());
+
+        let json = titled.to_json();
+        assert!(json.contains("\"title\":\"nightly build\""));
+        assert_eq!(Profile::from_json(&json), Ok(titled.clone()));
+        assert!(titled
+            .describe()
+            .starts_with("nightly build (10.000s traced"));
+        assert!(!profile(100, 0, 10.0, &[]).to_json().contains("title"));
+    }
+
+    #[test]
+    fn baseline_flags_changes_beyond_tolerance() {
+        let baseline = profile(100, 0, 10.0, &["/etc/hosts", "/etc/passwd"]);
+
+        let same = compare(
+            &baseline,
+            &profile(110, 0, 11.5, &["/etc/hosts", "/etc/passwd"]),
+            0.2,
+        );
+        assert!(same.is_empty());
+
+        let changed = compare(
+            &baseline,
+            &profile(300, 30, 30.0, &["/etc/hosts", "/tmp/new"]),
+            0.2,
+        );
+        assert_eq!(
+            changed
+                .deviations
+                .iter()
+                .map(|d| d.metric)
+                .collect::<Vec<_>>(),
+            vec!["calls /s", "errors %"]
+        );
+        assert_eq!(changed.deviations[0].change(), Some(2.0));
+        assert_eq!(changed.deviations[1].change(), None);
+        assert_eq!(changed.new_files, vec!["/tmp/new".to_string()]);
+        assert_eq!(changed.missing_files, vec!["/etc/passwd".to_string()]);
+    }
+
+    #[test]
+    fn baseline_compares_count_totals() {
+        let input = b"% time     seconds  usecs/call     calls    errors syscall
+------ ----------- ----------- --------- --------- ----------------
+ 90.00    0.009000          30       300        30 read
+------ ----------- ----------- --------- --------- ----------------
+100.00    0.009000          30       300        30 total
+";
+        let counts = Profile::from_counts(&CountSummary::parse(input).unwrap());
+        assert!(counts.counts_only);
+        assert_eq!(counts.syscalls["read"].calls, 300);
+        assert_eq!(Profile::from_json(&counts.to_json()).unwrap(), counts);
+
+        // Against a full trace only the average time and error rate are compared
+        let baseline = profile(100, 0, 10.0, &["/etc/hosts"]);
+        let comparison = compare(&baseline, &counts, 0.2);
+        assert_eq!(
+            comparison
+                .deviations
+                .iter()
+                .map(|d| d.metric)
+                .collect::<Vec<_>>(),
+            vec!["avg ms", "errors %"]
+        );
+        assert!(comparison.missing_files.is_empty());
+
+        // Two sets of totals compare their calls too
+        let mut fewer = counts.clone();
+        fewer.syscalls.get_mut("read").unwrap().calls = 100;
+        assert_eq!(
+            compare(&counts, &fewer, 0.2).deviations[0].metric,
+            "calls /s"
+        );
+    }
+}
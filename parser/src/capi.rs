@@ -0,0 +1,116 @@
+//! A C ABI for tools that would rather call the parser in-process than run
+//! `strace-parser` and parse its text output. Built with the `capi` feature
+//! and the `capi` profile, its header 'include/strace_parser.h' is generated
+//! with cbindgen, see the README.
+
+// A panic is caught and returned to the caller as NULL, which can't be done
+// when panics abort, as they do in the release profile
+#[cfg(panic = "abort")]
+compile_error!("the 'capi' feature needs panic = \"unwind\", build it with '--profile capi'");
+
+use crate::baseline::Profile;
+use crate::estimate;
+use crate::parser;
+use crate::syscall_data;
+use crate::syscall_stats;
+use crate::time;
+
+use std::borrow::Cow;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic;
+use std::ptr;
+use std::slice;
+
+/// Summarizes the trace in `buf`, which need not be NUL terminated, as a JSON
+/// object in the same format as `strace-parser <INPUT> baseline save`:
+/// each syscall's calls, errors, and total time, the PIDs, the traced time,
+/// and the files opened.
+///
+/// Returns NULL if `buf` is NULL or the trace can't be summarized. The string
+/// must be released with `strace_parser_free_string`.
+///
+/// # Safety
+///
+/// `buf` must point to `len` readable bytes, which are not modified during
+/// the call.
+#[no_mangle]
+pub unsafe extern "C" fn strace_parser_summary_json(buf: *const u8, len: usize) -> *mut c_char {
+    if buf.is_null() {
+        return ptr::null_mut();
+    }
+    let buffer = slice::from_raw_parts(buf, len);
+
+    // A panic must not unwind into the caller's frames
+    match panic::catch_unwind(|| summary_json(buffer)) {
+        Ok(Some(json)) => json.into_raw(),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Releases a string returned by this library. Passing NULL does nothing.
+///
+/// # Safety
+///
+/// `s` must have been returned by this library and not already released.
+#[no_mangle]
+pub unsafe extern "C" fn strace_parser_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+fn summary_json(buffer: &[u8]) -> Option<CString> {
+    // Calls wrapped onto several lines are joined, and traces run without
+    // '-T' are summarized with estimated durations, as by the command line
+    let buffer = match parser::has_continued_lines(buffer) {
+        true => Cow::Owned(parser::join_continued_lines(buffer)),
+        false => Cow::Borrowed(buffer),
+    };
+    let buffer = match estimate::has_durations(&buffer) {
+        true => buffer,
+        false => Cow::Owned(estimate::add_estimated_durations(&buffer)),
+    };
+
+    let mut raw_data = syscall_data::build_syscall_data(&buffer);
+    syscall_data::quarantine_corrupt_pids(&mut raw_data);
+    let stats = syscall_stats::build_syscall_stats(&raw_data);
+    let traced_secs = time::traced_time(
+        raw_data
+            .values()
+            .map(|pid_data| (pid_data.start_time, pid_data.end_time)),
+    )
+    .map_or(0.0, |t| {
+        t.num_microseconds().unwrap_or_default() as f64 / 1_000_000.0
+    });
+
+    let profile = Profile::from_trace(&stats, &raw_data, traced_secs);
+    // JSON strings escape control characters, so there is no interior NUL
+    CString::new(profile.to_json()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn capi_summarizes_trace() {
+        let input = br##"477   00:09:56.000000 openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = 3</etc/hosts> <0.000100>
+477   00:09:56.100000 read(3</etc/hosts>, "a", 1) = 1 <0.000200>
+477   00:09:56.200000 stat("/tmp/missing", 0x7ffc6d3c2df0) = -1 ENOENT (No such file or directory) <0.000300>"##;
+
+        unsafe {
+            let json = strace_parser_summary_json(input.as_ptr(), input.len());
+            assert!(!json.is_null());
+
+            let profile = Profile::from_json(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+            assert_eq!(profile.pids, 1);
+            assert_eq!(profile.syscalls["stat"].errors, 1);
+            assert!(profile.files.contains("/etc/hosts"));
+
+            strace_parser_free_string(json);
+            assert!(strace_parser_summary_json(ptr::null(), 0).is_null());
+        }
+    }
+}
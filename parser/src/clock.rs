@@ -0,0 +1,328 @@
+use crate::output;
+use crate::time::{format_timestamp_us, parse_timestamp_us};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::io::Error;
+use std::ops::Range;
+
+// Lines from different threads can be written slightly out of order, only
+// larger steps back are from the clock itself
+const MIN_JUMP_US: i64 = 1_000_000;
+// A wall clock time going back further than this is the trace running past midnight
+const MIDNIGHT_ROLLOVER_US: i64 = 12 * 60 * 60 * 1_000_000;
+// The trace is scanned in pieces of about this size in parallel
+const CHUNK_BYTES: usize = 16 * 1024 * 1024;
+const MAX_JUMPS_PRINTED: usize = 10;
+
+/// A step backwards in the trace's timestamps, e.g. from NTP stepping the
+/// host's clock. Durations and intervals measured across it are wrong.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClockJump {
+    /// Line number of the first line after the jump, starting from 1
+    pub line: usize,
+    /// Byte offset of that line in the trace
+    pub offset: usize,
+    pub before: String,
+    pub after: String,
+    /// How far back the clock went, in microseconds
+    pub jump_us: i64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Stamp<'a> {
+    // Line within its chunk, starting from 0
+    line: usize,
+    offset: usize,
+    us: i64,
+    token: &'a [u8],
+}
+
+#[derive(Clone, Debug, Default)]
+struct ChunkScan<'a> {
+    lines: usize,
+    first: Option<Stamp<'a>>,
+    last: Option<Stamp<'a>>,
+    jumps: Vec<(Stamp<'a>, Stamp<'a>, i64)>,
+}
+
+/// Finds each place where a line's timestamp is at least a second earlier
+/// than the line before it
+pub fn find_clock_jumps(buffer: &[u8]) -> Vec<ClockJump> {
+    find_jumps_in_chunks(buffer, CHUNK_BYTES)
+}
+
+fn find_jumps_in_chunks(buffer: &[u8], chunk_bytes: usize) -> Vec<ClockJump> {
+    let scans: Vec<_> = chunk_ranges(buffer, chunk_bytes)
+        .into_par_iter()
+        .map(|range| scan_chunk(buffer, range))
+        .collect();
+
+    let mut jumps = Vec::new();
+    let mut base_line = 0;
+    let mut prev: Option<Stamp> = None;
+
+    for scan in scans {
+        // A jump may fall between the last line of one chunk and the first of the next
+        if let (Some(before), Some(after)) = (prev, scan.first) {
+            if let Some(back) = jump_between(&before, &after) {
+                jumps.push(clock_jump(base_line, &before, &after, back));
+            }
+        }
+        for (before, after, back) in &scan.jumps {
+            jumps.push(clock_jump(base_line, before, after, *back));
+        }
+
+        prev = scan.last.or(prev);
+        base_line += scan.lines;
+    }
+
+    jumps
+}
+
+fn clock_jump(base_line: usize, before: &Stamp, after: &Stamp, back: i64) -> ClockJump {
+    ClockJump {
+        line: base_line + after.line + 1,
+        offset: after.offset,
+        before: before.token.to_str_lossy().to_string(),
+        after: after.token.to_str_lossy().to_string(),
+        jump_us: back,
+    }
+}
+
+// Ranges of about `chunk_bytes`, each ending after a newline
+fn chunk_ranges(buffer: &[u8], chunk_bytes: usize) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    while start < buffer.len() {
+        let end = match buffer
+            .get(start + chunk_bytes..)
+            .and_then(|rest| rest.find_byte(b'\n'))
+        {
+            Some(pos) => start + chunk_bytes + pos + 1,
+            None => buffer.len(),
+        };
+        ranges.push(start..end);
+        start = end;
+    }
+
+    ranges
+}
+
+fn scan_chunk(buffer: &[u8], range: Range<usize>) -> ChunkScan<'_> {
+    let mut offset = range.start;
+    let chunk = &buffer[range];
+    let chunk = chunk.strip_suffix(b"\n").unwrap_or(chunk);
+    let mut scan = ChunkScan::default();
+
+    for (idx, line) in chunk.split(|c| *c == b'\n').enumerate() {
+        let stamp = timestamp_range(line).and_then(|r| {
+            let token = &line[r];
+            Some(Stamp {
+                line: idx,
+                offset,
+                us: parse_timestamp_us(token)?,
+                token,
+            })
+        });
+
+        if let Some(stamp) = stamp {
+            if let Some(prev) = scan.last {
+                if let Some(back) = jump_between(&prev, &stamp) {
+                    scan.jumps.push((prev, stamp, back));
+                }
+            }
+            scan.first = scan.first.or(Some(stamp));
+            scan.last = Some(stamp);
+        }
+
+        offset += line.len() + 1;
+        scan.lines += 1;
+    }
+
+    scan
+}
+
+fn jump_between(before: &Stamp, after: &Stamp) -> Option<i64> {
+    let back = before.us - after.us;
+    let is_wall_clock = after.token.contains(&b':');
+
+    if back < MIN_JUMP_US || (is_wall_clock && back > MIDNIGHT_ROLLOVER_US) {
+        None
+    } else {
+        Some(back)
+    }
+}
+
+// 477   00:09:56.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+//       ^^^^^^^^^^^^^^^
+pub(crate) fn timestamp_range(line: &[u8]) -> Option<Range<usize>> {
+    let pid_end = line.find_byteset(b" \t")?;
+    let start = pid_end
+        + line[pid_end..]
+            .iter()
+            .position(|c| !c.is_ascii_whitespace())?;
+    let end = line[start..]
+        .find_byteset(b" \t")
+        .map_or(line.len(), |pos| start + pos);
+
+    Some(start..end)
+}
+
+/// Shifts the timestamp of every line after each jump forward by the total of
+/// the jumps before it, so timestamps through the trace no longer go back.
+/// Timestamps are rewritten in place, and are left alone if shifting one
+/// would change its width.
+pub fn correct_clock_jumps(buffer: &mut [u8], jumps: &[ClockJump]) {
+    let first = match jumps.first() {
+        Some(jump) => jump.offset,
+        None => return,
+    };
+
+    let mut chunks = Vec::new();
+    let mut offset = first;
+    let mut rest = &mut buffer[first..];
+    for range in chunk_ranges(rest, CHUNK_BYTES) {
+        let (chunk, tail) = std::mem::take(&mut rest).split_at_mut(range.len());
+        chunks.push((offset, chunk));
+        offset += range.len();
+        rest = tail;
+    }
+
+    chunks
+        .into_par_iter()
+        .for_each(|(offset, chunk)| correct_chunk(chunk, offset, jumps));
+}
+
+fn correct_chunk(chunk: &mut [u8], offset: usize, jumps: &[ClockJump]) {
+    let mut start = 0;
+
+    while start < chunk.len() {
+        let end = chunk[start..]
+            .find_byte(b'\n')
+            .map_or(chunk.len(), |pos| start + pos);
+
+        let shift: i64 = jumps
+            .iter()
+            .take_while(|j| j.offset <= offset + start)
+            .map(|j| j.jump_us)
+            .sum();
+        if shift > 0 {
+            shift_timestamp(&mut chunk[start..end], shift);
+        }
+
+        start = end + 1;
+    }
+}
+
+fn shift_timestamp(line: &mut [u8], shift_us: i64) {
+    let range = match timestamp_range(line) {
+        Some(r) => r,
+        None => return,
+    };
+
+    let token = &line[range.clone()];
+    if let Some(us) = parse_timestamp_us(token) {
+        let shifted = format_timestamp_us(us + shift_us, token);
+        if shifted.len() == token.len() {
+            line[range].copy_from_slice(shifted.as_bytes());
+        }
+    }
+}
+
+pub fn print_clock_jumps(jumps: &[ClockJump], corrected: bool) -> Result<(), Error> {
+    for jump in jumps.iter().take(MAX_JUMPS_PRINTED) {
+        output::print_note(&format!(
+            "  Warning: the clock went back {:.3}s at line {}, from {} to {}",
+            jump.jump_us as f64 / 1_000_000.0,
+            jump.line,
+            output::timestamp(jump.before.as_bytes()),
+            output::timestamp(jump.after.as_bytes())
+        ))?;
+    }
+    if jumps.len() > MAX_JUMPS_PRINTED {
+        output::print_note(&format!(
+            "  ... and {} more",
+            jumps.len() - MAX_JUMPS_PRINTED
+        ))?;
+    }
+
+    if corrected {
+        output::print_note("  Timestamps after each jump were shifted forward to compensate")
+    } else {
+        output::print_note(
+            "  Times measured across a jump are wrong, pass '--fix-clock' to compensate",
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_finds_backwards_jump() {
+        let input = br##"477   10:00:00.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+495   10:00:05.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   10:00:01.500000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   10:00:02.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>"##;
+        let jumps = find_clock_jumps(input);
+
+        assert_eq!(jumps.len(), 1);
+        assert_eq!(jumps[0].line, 3);
+        assert_eq!(jumps[0].before, "10:00:05.000000");
+        assert_eq!(jumps[0].after, "10:00:01.500000");
+        assert_eq!(jumps[0].jump_us, 3_500_000);
+    }
+
+    #[test]
+    fn clock_ignores_reordering_and_midnight() {
+        let input = br##"477   23:59:59.900000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+495   23:59:59.800000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   00:00:00.100000 write(1</dev/null>, "a", 1) = 1 <0.000010>"##;
+        assert!(find_clock_jumps(input).is_empty());
+    }
+
+    #[test]
+    fn clock_finds_jumps_across_chunks() {
+        let input = br##"477   1588000005.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   1588000001.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   1588000002.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   1587999000.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+"##;
+        // Chunks of one line each
+        let jumps = find_jumps_in_chunks(input, 1);
+
+        assert_eq!(jumps.len(), 2);
+        assert_eq!(jumps[0].line, 2);
+        assert_eq!(jumps[1].line, 4);
+        assert_eq!(jumps[1].jump_us, 1_002_000_000);
+    }
+
+    #[test]
+    fn clock_correction_shifts_later_lines() {
+        let mut input = br##"477   10:00:00.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+495   10:00:05.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   10:00:01.500000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   10:00:02.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>"##
+            .to_vec();
+        let jumps = find_clock_jumps(&input);
+        correct_clock_jumps(&mut input, &jumps);
+
+        let times: Vec<_> = input
+            .lines()
+            .filter_map(|line| timestamp_range(line).map(|r| line[r].to_str_lossy().to_string()))
+            .collect();
+        assert_eq!(
+            times,
+            vec![
+                "10:00:00.000000",
+                "10:00:05.000000",
+                "10:00:05.000000",
+                "10:00:05.500000"
+            ]
+        );
+        assert!(find_clock_jumps(&input).is_empty());
+    }
+}
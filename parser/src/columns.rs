@@ -0,0 +1,68 @@
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PidColumn {
+    Children,
+    Threads,
+    Exec,
+    FirstActivity,
+    LastActivity,
+    Elapsed,
+    Errors,
+    Uid,
+}
+
+impl PidColumn {
+    pub fn header(self) -> &'static str {
+        match self {
+            PidColumn::Children => "children",
+            PidColumn::Threads => "threads",
+            PidColumn::Exec => "program",
+            PidColumn::FirstActivity => "first time",
+            PidColumn::LastActivity => "last time",
+            PidColumn::Elapsed => "elapsed (ms)",
+            PidColumn::Errors => "errors",
+            PidColumn::Uid => "uid",
+        }
+    }
+
+    pub fn width(self) -> usize {
+        match self {
+            PidColumn::Children | PidColumn::Threads | PidColumn::Errors | PidColumn::Uid => 9,
+            PidColumn::FirstActivity | PidColumn::LastActivity => 15,
+            PidColumn::Elapsed => 12,
+            PidColumn::Exec => 30,
+        }
+    }
+}
+
+impl FromStr for PidColumn {
+    type Err = ParseColumnError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "children" => Ok(PidColumn::Children),
+            "threads" => Ok(PidColumn::Threads),
+            "exec" => Ok(PidColumn::Exec),
+            "first" => Ok(PidColumn::FirstActivity),
+            "last" => Ok(PidColumn::LastActivity),
+            "elapsed" => Ok(PidColumn::Elapsed),
+            "errors" => Ok(PidColumn::Errors),
+            "uid" => Ok(PidColumn::Uid),
+            _ => Err(ParseColumnError),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ParseColumnError;
+
+impl fmt::Display for ParseColumnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid 'columns' value")
+    }
+}
+
+impl error::Error for ParseColumnError {}
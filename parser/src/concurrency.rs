@@ -0,0 +1,454 @@
+use crate::events::{self, Event};
+use crate::output;
+use crate::parser::{self, CallStatus, LineData};
+use crate::table::{Align, Column, Table};
+use crate::time::{format_timestamp_us, parse_timestamp_us};
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::io::{prelude::*, stdout, Error};
+
+// Calls shorter than this are treated as the PID still running rather than waiting
+const MIN_BLOCKED_US: i64 = 1000;
+const SPARK_CHARS: [char; 8] = [
+    '\u{2581}', '\u{2582}', '\u{2583}', '\u{2584}', '\u{2585}', '\u{2586}', '\u{2587}', '\u{2588}',
+];
+// Width used for sparklines when output is not fit to a terminal
+const DEFAULT_SPARK_WIDTH: usize = 80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Interval {
+    pub start_us: i64,
+    /// PIDs that started or finished a syscall during the interval
+    pub active_pids: usize,
+    /// The most PIDs waiting in slow syscalls at the same moment
+    pub peak_blocked: usize,
+}
+
+// When a call started and returned, in microseconds
+type Span = (i64, i64);
+
+#[derive(Clone, Copy, Debug)]
+struct CallSpan {
+    pid: Pid,
+    start: i64,
+    finish: i64,
+}
+
+/// Splits the trace into intervals of `interval_us` and counts how many of
+/// `pids` were making syscalls in each, and how many were blocked at once.
+/// A timestamp from the trace is returned to format interval starts.
+pub fn concurrency_series<'a>(
+    buffer: &'a [u8],
+    pids: &[Pid],
+    interval_us: i64,
+) -> (Vec<Interval>, Option<&'a [u8]>) {
+    let pids: HashSet<Pid> = pids.iter().copied().collect();
+
+    let mut spans: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(parser::parse_line)
+        .filter_map(|line| match line {
+            LineData::Syscall(raw_data) if pids.contains(&raw_data.pid) => Some(raw_data),
+            _ => None,
+        })
+        .filter_map(|raw_data| {
+            let time = parse_timestamp_us(raw_data.time)?;
+            let duration = raw_data
+                .duration
+                .map_or(0, |d| (d as f64 * 1_000_000.0) as i64);
+
+            // The resumed line is printed as the call returns, the started
+            // line is skipped as the resumed one covers the whole call
+            let (start, finish) = match raw_data.call_status {
+                CallStatus::Complete => (time, time + duration),
+                CallStatus::Resumed => (time - duration, time),
+                CallStatus::Started => return None,
+            };
+
+            Some((
+                raw_data.time,
+                CallSpan {
+                    pid: raw_data.pid,
+                    start,
+                    finish,
+                },
+            ))
+        })
+        .collect();
+
+    spans.par_sort_by_key(|(_, span)| span.start);
+
+    let first_time = spans.first().map(|(time, _)| *time);
+    let spans: Vec<_> = spans.into_iter().map(|(_, span)| span).collect();
+
+    (build_intervals(&spans, interval_us), first_time)
+}
+
+// Spans must be sorted by start time
+fn build_intervals(spans: &[CallSpan], interval_us: i64) -> Vec<Interval> {
+    let (first, last) = match (spans.first(), spans.iter().map(|s| s.finish).max()) {
+        (Some(first), Some(last)) => (first.start, last),
+        _ => return Vec::new(),
+    };
+    let interval_ct = ((last - first) / interval_us + 1) as usize;
+    let interval_idx = |t: i64| (((t - first) / interval_us) as usize).min(interval_ct - 1);
+
+    let mut active: Vec<HashSet<Pid>> = vec![HashSet::default(); interval_ct];
+    let mut edges = Vec::new();
+
+    for span in spans {
+        active[interval_idx(span.start)].insert(span.pid);
+        active[interval_idx(span.finish)].insert(span.pid);
+
+        if span.finish - span.start >= MIN_BLOCKED_US {
+            edges.push((span.start, 1));
+            edges.push((span.finish, -1));
+        }
+    }
+    // A call ending as another begins is not counted as overlapping
+    edges.sort_unstable();
+
+    let mut intervals = Vec::with_capacity(interval_ct);
+    let mut edge_iter = edges.iter().peekable();
+    let mut blocked: i64 = 0;
+
+    for (idx, pids) in active.iter().enumerate() {
+        let start_us = first + idx as i64 * interval_us;
+        let end_us = start_us + interval_us;
+        let mut peak = blocked;
+
+        while let Some((_, delta)) = edge_iter.next_if(|(t, _)| *t < end_us) {
+            blocked += delta;
+            peak = peak.max(blocked);
+        }
+
+        intervals.push(Interval {
+            start_us,
+            active_pids: pids.len(),
+            peak_blocked: peak as usize,
+        });
+    }
+
+    intervals
+}
+
+/// A line of block characters, one per interval, scaled to the largest value.
+/// Neighbouring intervals are merged, keeping their maximum, to fit `width`.
+pub fn sparkline(values: &[usize], width: usize) -> String {
+    let width = width.max(1);
+    let per_char = values.len().div_ceil(width).max(1);
+    let buckets: Vec<_> = values
+        .chunks(per_char)
+        .map(|chunk| chunk.iter().copied().max().unwrap_or_default())
+        .collect();
+    let max = buckets.iter().copied().max().unwrap_or_default();
+
+    buckets
+        .iter()
+        .map(|&v| {
+            let level = (v * (SPARK_CHARS.len() - 1))
+                .checked_div(max)
+                .unwrap_or_default();
+            SPARK_CHARS[level]
+        })
+        .collect()
+}
+
+pub fn print_concurrency(
+    buffer: &[u8],
+    pids: &[Pid],
+    interval_us: i64,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let (intervals, first_time) = concurrency_series(buffer, pids, interval_us);
+
+    output::print_heading("\nConcurrency\n-----------\n")?;
+
+    let time_layout = match first_time {
+        Some(t) if !intervals.is_empty() => t,
+        _ => {
+            output::print_note(
+                "  No timestamped syscalls found, '-tt' must be passed to strace\n",
+            )?;
+            return Ok(());
+        }
+    };
+
+    let active: Vec<_> = intervals.iter().map(|i| i.active_pids).collect();
+    let blocked: Vec<_> = intervals.iter().map(|i| i.peak_blocked).collect();
+
+    let starts: Vec<_> = intervals
+        .iter()
+        .map(|i| output::timestamp(format_timestamp_us(i.start_us, time_layout).as_bytes()))
+        .collect();
+    let label_width = 24;
+    let spark_width = width
+        .unwrap_or(DEFAULT_SPARK_WIDTH)
+        .saturating_sub(label_width);
+
+    if output::headers() {
+        writeln!(
+            stdout(),
+            "  {} intervals of {:.3}s starting at {}\n",
+            intervals.len(),
+            interval_us as f64 / 1_000_000.0,
+            starts[0]
+        )?;
+        writeln!(
+            stdout(),
+            "  active     max {: >6}  {}",
+            active.iter().max().unwrap_or(&0),
+            sparkline(&active, spark_width)
+        )?;
+        writeln!(
+            stdout(),
+            "  blocked    max {: >6}  {}\n",
+            blocked.iter().max().unwrap_or(&0),
+            sparkline(&blocked, spark_width)
+        )?;
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("interval start", 15, Align::Center, 0),
+        Column::new("active pids", 11, Align::Right, 0),
+        Column::new("peak blocked", 12, Align::Right, 1),
+    ]);
+    for (interval, start) in intervals.iter().zip(starts) {
+        table.add_row(vec![
+            start,
+            interval.active_pids.to_string(),
+            interval.peak_blocked.to_string(),
+        ]);
+    }
+    table.print(width)?;
+    writeln!(stdout())?;
+
+    Ok(())
+}
+
+/// The most calls of one syscall in flight at once across every PID
+#[derive(Clone, Debug, PartialEq)]
+pub struct InFlight<'a> {
+    pub syscall: &'a [u8],
+    pub calls: usize,
+    pub peak: usize,
+    /// When the peak was first reached
+    pub peak_us: i64,
+    /// The file or fd with the most of the syscall's calls in flight at once,
+    /// and how many
+    pub busiest_path: Option<(&'a [u8], usize)>,
+}
+
+/// For each syscall in `events`, the most calls in flight at once, counting
+/// from when each call started until it returned. Unfinished calls are joined
+/// with their resumed line by `events::events`. Only syscalls with calls that
+/// overlapped are returned, the most in flight first.
+pub fn in_flight<'a>(events: &[Event<'a>]) -> Vec<InFlight<'a>> {
+    let mut by_syscall: HashMap<&[u8], Vec<Span>> = HashMap::default();
+    let mut by_path: HashMap<(&[u8], &[u8]), Vec<Span>> = HashMap::default();
+    let mut calls: HashMap<&[u8], usize> = HashMap::default();
+
+    for event in events {
+        *calls.entry(event.syscall).or_default() += event.count;
+        let start = event.start_us();
+        let finish = start + (f64::from(event.duration) * 1000.0).round() as i64;
+        // Calls that took no time can't overlap another
+        if finish <= start {
+            continue;
+        }
+        by_syscall
+            .entry(event.syscall)
+            .or_default()
+            .push((start, finish));
+        if let Some(path) = event.path {
+            by_path
+                .entry((event.syscall, path))
+                .or_default()
+                .push((start, finish));
+        }
+    }
+
+    let mut busiest_paths: HashMap<&[u8], (&[u8], usize)> = HashMap::default();
+    for ((syscall, path), spans) in &by_path {
+        let (peak, _) = peak_overlap(spans);
+        let busiest = busiest_paths.entry(syscall).or_insert((path, peak));
+        if (peak, Reverse(*path)) > (busiest.1, Reverse(busiest.0)) {
+            *busiest = (path, peak);
+        }
+    }
+
+    let mut in_flight: Vec<_> = by_syscall
+        .iter()
+        .map(|(&syscall, spans)| {
+            let (peak, peak_us) = peak_overlap(spans);
+            InFlight {
+                syscall,
+                calls: calls[syscall],
+                peak,
+                peak_us,
+                busiest_path: busiest_paths
+                    .get(syscall)
+                    .copied()
+                    .filter(|(_, ct)| *ct > 1),
+            }
+        })
+        .filter(|in_flight| in_flight.peak > 1)
+        .collect();
+    in_flight.sort_by(|x, y| y.peak.cmp(&x.peak).then(x.syscall.cmp(y.syscall)));
+
+    in_flight
+}
+
+// The most spans overlapping at once and when that was first reached. A span
+// ending as another starts doesn't overlap it.
+fn peak_overlap(spans: &[Span]) -> (usize, i64) {
+    let mut edges: Vec<_> = spans
+        .iter()
+        .flat_map(|&(start, finish)| [(start, 1), (finish, -1)])
+        .collect();
+    edges.sort_unstable();
+
+    let (mut level, mut peak, mut peak_us) = (0i64, 0i64, 0);
+    for (time, delta) in edges {
+        level += delta;
+        if level > peak {
+            peak = level;
+            peak_us = time;
+        }
+    }
+
+    (peak as usize, peak_us)
+}
+
+/// The syscalls with the most calls in flight at once, printed after the
+/// summary
+pub fn print_in_flight(
+    buffer: &[u8],
+    pids: &[Pid],
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let events = events::events(buffer, pids, 0);
+    let in_flight = in_flight(&events);
+    let time_layout = match events.first() {
+        Some(event) if !in_flight.is_empty() => event.time,
+        _ => return Ok(()),
+    };
+
+    output::print_heading("\nMost Calls In Flight at Once\n-----------\n")?;
+
+    let mut table = Table::new(vec![
+        Column::new("syscall", 17, Align::Left, 0),
+        Column::new("calls", 8, Align::Right, 3),
+        Column::new("in flight", 9, Align::Right, 0),
+        Column::new("first at", 15, Align::Center, 1),
+        Column::new("on one path", 11, Align::Right, 2),
+        Column::new("path", 30, Align::Left, 2),
+    ]);
+
+    for in_flight in in_flight.iter().take(count) {
+        let (path, path_peak) = match in_flight.busiest_path {
+            Some((path, peak)) => (path.to_str_lossy().to_string(), peak.to_string()),
+            None => (String::from("-"), String::from("-")),
+        };
+        table.add_row(vec![
+            in_flight.syscall.to_str_lossy().to_string(),
+            in_flight.calls.to_string(),
+            in_flight.peak.to_string(),
+            output::timestamp(format_timestamp_us(in_flight.peak_us, time_layout).as_bytes()),
+            path_peak,
+            path,
+        ]);
+    }
+
+    table.print(width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrency_counts_active_pids() {
+        let input = br##"477   00:09:56.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+495   00:09:56.500000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   00:09:57.100000 write(1</dev/null>, "a", 1) = 1 <0.000010>"##;
+        let (intervals, first_time) = concurrency_series(input, &[477, 495], 1_000_000);
+
+        assert_eq!(first_time, Some(b"00:09:56.000000".as_ref()));
+        assert_eq!(intervals.len(), 2);
+        assert_eq!(intervals[0].active_pids, 2);
+        assert_eq!(intervals[1].active_pids, 1);
+        assert_eq!(intervals[0].peak_blocked, 0);
+    }
+
+    #[test]
+    fn concurrency_counts_overlapping_blocked_calls() {
+        let input = br##"477   00:09:56.000000 read(0</dev/null>, "", 10) = 0 <1.500000>
+495   00:09:56.200000 epoll_wait(4<anon_inode:[eventpoll]>, [], 1, 500) = 0 <0.500000>
+567   00:09:56.900000 read(0</dev/null>, "", 10) = 0 <0.500000>
+567   00:09:57.900000 wait4(-1 <unfinished ...>
+567   00:09:58.100000 <... wait4 resumed>[{WIFEXITED(s) && WEXITSTATUS(s) == 0}], 0, NULL) = 600 <0.200000>"##;
+        let (intervals, _) = concurrency_series(input, &[477, 495, 567], 1_000_000);
+
+        assert_eq!(intervals.len(), 3);
+        assert_eq!(intervals[0].peak_blocked, 2);
+        assert_eq!(intervals[1].peak_blocked, 2);
+        assert_eq!(intervals[2].peak_blocked, 1);
+    }
+
+    #[test]
+    fn concurrency_filters_pids() {
+        let input = br##"477   00:09:56.000000 read(0</dev/null>, "", 10) = 0 <1.500000>
+495   00:09:56.200000 read(0</dev/null>, "", 10) = 0 <1.500000>"##;
+        let (intervals, _) = concurrency_series(input, &[495], 1_000_000);
+
+        assert_eq!(intervals[0].active_pids, 1);
+        assert_eq!(intervals[0].peak_blocked, 1);
+    }
+
+    #[test]
+    fn concurrency_sparkline_merges_to_width() {
+        assert_eq!(sparkline(&[0, 4, 8], 10), "\u{2581}\u{2584}\u{2588}");
+        assert_eq!(sparkline(&[0, 4, 8, 2], 2), "\u{2584}\u{2588}");
+        assert_eq!(sparkline(&[0, 0], 2), "\u{2581}\u{2581}");
+    }
+
+    #[test]
+    fn in_flight_finds_overlapping_calls_of_each_syscall() {
+        let input =
+            br##"477   00:09:56.000000 futex(0x7f00, FUTEX_WAIT_PRIVATE, 0, NULL <unfinished ...>
+478   00:09:56.100000 futex(0x7f00, FUTEX_WAIT_PRIVATE, 0, NULL <unfinished ...>
+479   00:09:56.200000 write(5</var/log/app.log>, "a", 1 <unfinished ...>
+480   00:09:56.300000 write(5</var/log/app.log>, "b", 1) = 1 <0.100000>
+481   00:09:56.350000 write(6</tmp/other>, "c", 1) = 1 <0.100000>
+479   00:09:56.500000 <... write resumed>) = 1 <0.300000>
+477   00:09:57.000000 <... futex resumed>) = 0 <1.000000>
+478   00:09:57.000000 <... futex resumed>) = 0 <0.900000>
+482   00:09:57.000000 futex(0x7f00, FUTEX_WAKE_PRIVATE, 2) = 2 <0.000010>
+477   00:09:57.100000 write(5</var/log/app.log>, "d", 1) = 1 <0.000000>"##;
+        let events = events::events(input, &[477, 478, 479, 480, 481, 482], 0);
+        let in_flight = in_flight(&events);
+
+        assert_eq!(in_flight.len(), 2);
+        let write = &in_flight[0];
+        assert_eq!(
+            (write.syscall, write.calls, write.peak),
+            (b"write".as_ref(), 4, 3)
+        );
+        assert_eq!(
+            write.peak_us,
+            parse_timestamp_us(b"00:09:56.350000").unwrap()
+        );
+        assert_eq!(write.busiest_path, Some((b"/var/log/app.log".as_ref(), 2)));
+        let futex = &in_flight[1];
+        assert_eq!(
+            (futex.syscall, futex.calls, futex.peak),
+            (b"futex".as_ref(), 3, 2)
+        );
+        assert_eq!(futex.busiest_path, None);
+    }
+}
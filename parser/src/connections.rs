@@ -0,0 +1,837 @@
+use crate::io_data;
+use crate::output;
+use crate::parser::{CallStatus, OtherFields};
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::time::elapsed_ms;
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::fmt;
+use std::io::Error;
+
+static OUTBOUND: &[u8] = b"(outbound connect)";
+static UNKNOWN_LISTENER: &[u8] = b"(unknown listener)";
+
+// The time of an I/O call on a socket, whether it read, and the bytes moved
+type Transfer<'a> = (&'a [u8], bool, u64);
+
+// When a connection was made, the listener it was accepted on, and the PID
+// that made it
+type ConnStart<'a> = (SocketTime<'a>, &'a [u8], Pid);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SocketTime<'a> {
+    time: &'a [u8],
+    offset: f32,
+}
+
+impl<'a> SocketTime<'a> {
+    // Lines for complete calls are timestamped at entry,
+    // so the call's duration is needed to find when it returned
+    fn new(time: &'a [u8], duration: Option<f32>) -> SocketTime<'a> {
+        SocketTime {
+            time,
+            offset: duration.unwrap_or_default() * 1000.0,
+        }
+    }
+
+    fn until(&self, end: &SocketTime) -> Option<f32> {
+        elapsed_ms(self.time, end.time).map(|t| t + end.offset - self.offset)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListenerStats<'a> {
+    pub listener: &'a [u8],
+    pub service_times: Vec<f32>,
+    pub unanswered: usize,
+    /// Bytes read from and written to the connections, across all of them
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+impl<'a> ListenerStats<'a> {
+    fn new(listener: &'a [u8]) -> ListenerStats<'a> {
+        ListenerStats {
+            listener,
+            service_times: Vec::new(),
+            unanswered: 0,
+            bytes_read: 0,
+            bytes_written: 0,
+        }
+    }
+
+    pub fn conn_count(&self) -> usize {
+        self.service_times.len() + self.unanswered
+    }
+
+    pub fn average(&self) -> f32 {
+        if self.service_times.is_empty() {
+            0.0
+        } else {
+            self.service_times.iter().sum::<f32>() / self.service_times.len() as f32
+        }
+    }
+
+    /// Average bytes read and written per connection
+    pub fn average_bytes(&self) -> (u64, u64) {
+        match self.conn_count() as u64 {
+            0 => (0, 0),
+            conns => (self.bytes_read / conns, self.bytes_written / conns),
+        }
+    }
+
+    /// The socket path of a UNIX domain listener traced with '-yy', e.g.
+    /// 'UNIX:[3578806,"/var/opt/gitlab/gitlab-rails/sockets/gitlab.socket"]'
+    pub fn unix_path(&self) -> Option<&'a [u8]> {
+        if !self.listener.starts_with(b"UNIX") {
+            return None;
+        }
+        let start = self.listener.find(b",\"")? + 2;
+        let end = self.listener.rfind(b"\"]")?;
+        self.listener.get(start..end)
+    }
+
+    /// Nearest-rank percentile of the service times in ms, `service_times` must be sorted
+    pub fn percentile(&self, pct: f32) -> f32 {
+        if self.service_times.is_empty() {
+            return 0.0;
+        }
+
+        let rank = (pct / 100.0 * self.service_times.len() as f32).ceil() as usize;
+        self.service_times[rank.clamp(1, self.service_times.len()) - 1]
+    }
+}
+
+impl<'a> fmt::Display for ListenerStats<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "  {: <30}    {: >7}    {: >8}    {: >10.3}    {: >10.3}    {: >10.3}    {: >10.3}    {: >10.3}",
+            self.listener.to_str_lossy(),
+            self.conn_count(),
+            self.unanswered,
+            self.percentile(0.0),
+            self.average(),
+            self.percentile(50.0),
+            self.percentile(99.0),
+            self.service_times.last().copied().unwrap_or_default(),
+        )
+    }
+}
+
+/// Per-connection service time, from the return of 'accept'/'accept4' to the
+/// return of the final write or 'shutdown' on the connection, and the bytes
+/// moved on it, grouped by the listening socket the connection was accepted
+/// on. Outbound connections are timed from the return of 'connect' to the
+/// first read of a reply by the PID that connected.
+pub fn service_times<'a>(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData<'a>>,
+) -> Vec<ListenerStats<'a>> {
+    let mut starts: HashMap<&[u8], Vec<ConnStart>> = HashMap::default();
+    let mut ends: HashMap<&[u8], Vec<SocketTime>> = HashMap::default();
+    // Reads returning data, by the PID that made them
+    let mut replies: HashMap<(&[u8], Pid), Vec<SocketTime>> = HashMap::default();
+
+    for pid in pids {
+        let mut socket_events = raw_data[pid].socket_events.clone();
+        socket_events.par_sort_unstable_by(|x, y| (x.time).cmp(y.time));
+
+        let mut pending_listener = None;
+        for event in &socket_events {
+            match (event.syscall, event.call_status, &event.other) {
+                (b"accept" | b"accept4", CallStatus::Started, Some(OtherFields::File(f))) => {
+                    pending_listener = Some(*f);
+                }
+                (b"accept" | b"accept4", _, Some(OtherFields::Accept { listener, conn })) => {
+                    let listener = listener
+                        .or_else(|| pending_listener.take())
+                        .unwrap_or(UNKNOWN_LISTENER);
+                    let offset = match event.call_status {
+                        CallStatus::Complete => event.duration,
+                        _ => None,
+                    };
+                    starts.entry(conn).or_default().push((
+                        SocketTime::new(event.time, offset),
+                        listener,
+                        *pid,
+                    ));
+                }
+                (
+                    b"connect",
+                    CallStatus::Complete | CallStatus::Started,
+                    Some(OtherFields::Connect { file: Some(f), .. }),
+                ) if matches!(event.error, None | Some(b"EINPROGRESS")) => {
+                    starts.entry(f).or_default().push((
+                        SocketTime::new(event.time, event.duration),
+                        OUTBOUND,
+                        *pid,
+                    ));
+                }
+                (
+                    b"shutdown",
+                    CallStatus::Complete | CallStatus::Started,
+                    Some(OtherFields::File(f)),
+                ) => {
+                    ends.entry(f)
+                        .or_default()
+                        .push(SocketTime::new(event.time, event.duration));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut transfers: HashMap<&[u8], Vec<Transfer>> = HashMap::default();
+
+    for io_call in io_data::io_calls(pids, raw_data).values().flatten() {
+        if io_call.bytes > 0 {
+            transfers.entry(io_call.fd).or_default().push((
+                io_call.time,
+                io_call.is_read(),
+                io_call.bytes as u64,
+            ));
+        }
+        if io_call.bytes > 0 && io_call.is_read() {
+            replies
+                .entry((io_call.fd, io_call.pid))
+                .or_default()
+                .push(SocketTime::new(io_call.time, Some(io_call.duration)));
+        }
+        if let b"send" | b"sendmsg" | b"sendto" | b"write" | b"writev" = io_call.syscall {
+            ends.entry(io_call.fd)
+                .or_default()
+                .push(SocketTime::new(io_call.time, Some(io_call.duration)));
+        }
+    }
+
+    let mut listeners: HashMap<&[u8], ListenerStats> = HashMap::default();
+
+    for (conn, conn_starts) in starts.iter_mut() {
+        conn_starts.sort_by(|(x, ..), (y, ..)| (x.time).cmp(y.time));
+        let mut conn_ends = ends.remove(conn).unwrap_or_default();
+        conn_ends.sort_by(|x, y| (x.time).cmp(y.time));
+        let conn_transfers = transfers.remove(conn).unwrap_or_default();
+
+        for (idx, (start, listener, pid)) in conn_starts.iter().enumerate() {
+            // A later accept/connect returning the same socket ends this connection's window
+            let window_end = conn_starts.get(idx + 1).map(|(next, ..)| next.time);
+
+            let in_window = |time: &[u8]| time >= start.time && window_end.is_none_or(|w| time < w);
+
+            let end = if *listener == OUTBOUND {
+                replies
+                    .get(&(*conn, *pid))
+                    .and_then(|r| r.iter().find(|e| in_window(e.time)))
+            } else {
+                conn_ends.iter().rev().find(|e| in_window(e.time))
+            };
+
+            let stats = listeners
+                .entry(listener)
+                .or_insert_with(|| ListenerStats::new(listener));
+
+            for (_, is_read, bytes) in conn_transfers.iter().filter(|(t, ..)| in_window(t)) {
+                if *is_read {
+                    stats.bytes_read += bytes;
+                } else {
+                    stats.bytes_written += bytes;
+                }
+            }
+
+            match end.and_then(|e| start.until(e)) {
+                Some(service_time) => stats.service_times.push(service_time),
+                None => stats.unanswered += 1,
+            }
+        }
+    }
+
+    let mut listener_stats: Vec<_> = listeners.into_values().collect();
+    for stats in listener_stats.iter_mut() {
+        stats.service_times.sort_by(|x, y| {
+            x.partial_cmp(y)
+                .expect("Invalid comparison when sorting service times")
+        });
+    }
+
+    listener_stats.sort_by(|x, y| {
+        (y.conn_count())
+            .cmp(&x.conn_count())
+            .then_with(|| (x.listener).cmp(y.listener))
+    });
+
+    listener_stats
+}
+
+/// Connections accepted on UNIX domain sockets, merged by socket path so a
+/// server that recreated its listener is counted once
+pub fn unix_socket_stats<'a>(listener_stats: &[ListenerStats<'a>]) -> Vec<ListenerStats<'a>> {
+    let mut paths: HashMap<&[u8], ListenerStats> = HashMap::default();
+
+    for stats in listener_stats {
+        if let Some(path) = stats.unix_path() {
+            let entry = paths
+                .entry(path)
+                .or_insert_with(|| ListenerStats::new(path));
+            entry.service_times.extend(&stats.service_times);
+            entry.unanswered += stats.unanswered;
+            entry.bytes_read += stats.bytes_read;
+            entry.bytes_written += stats.bytes_written;
+        }
+    }
+
+    let mut unix_stats: Vec<_> = paths.into_values().collect();
+    for stats in unix_stats.iter_mut() {
+        stats.service_times.sort_by(|x, y| {
+            x.partial_cmp(y)
+                .expect("Invalid comparison when sorting service times")
+        });
+    }
+
+    unix_stats.sort_by(|x, y| {
+        (y.conn_count())
+            .cmp(&x.conn_count())
+            .then_with(|| (x.listener).cmp(y.listener))
+    });
+
+    unix_stats
+}
+
+/// Outcomes of the 'connect' calls made to one destination address
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectStats {
+    pub endpoint: String,
+    pub succeeded: usize,
+    pub refused: usize,
+    pub timed_out: usize,
+    /// Non-blocking connects, the result is only known to a later poll or getsockopt
+    pub in_progress: usize,
+    pub other_errors: usize,
+    /// Time spent in each 'connect' call in ms, sorted
+    pub latencies: Vec<f32>,
+}
+
+impl ConnectStats {
+    fn new(endpoint: String) -> ConnectStats {
+        ConnectStats {
+            endpoint,
+            ..ConnectStats::default()
+        }
+    }
+
+    pub fn attempts(&self) -> usize {
+        self.succeeded + self.refused + self.timed_out + self.in_progress + self.other_errors
+    }
+
+    /// No attempt succeeded, and none were left in progress that may have later
+    pub fn never_succeeded(&self) -> bool {
+        self.succeeded == 0 && self.in_progress == 0
+    }
+
+    pub fn average(&self) -> f32 {
+        if self.latencies.is_empty() {
+            0.0
+        } else {
+            self.latencies.iter().sum::<f32>() / self.latencies.len() as f32
+        }
+    }
+
+    fn add(&mut self, error: Option<&[u8]>, duration: Option<f32>) {
+        match error {
+            None => self.succeeded += 1,
+            Some(b"ECONNREFUSED") => self.refused += 1,
+            Some(b"ETIMEDOUT") => self.timed_out += 1,
+            Some(b"EINPROGRESS") => self.in_progress += 1,
+            Some(_) => self.other_errors += 1,
+        }
+        if let Some(duration) = duration {
+            self.latencies.push(duration * 1000.0);
+        }
+    }
+}
+
+/// Formats the address argument of 'connect' as 'ip:port', '[ipv6]:port', or
+/// the socket path, falling back to the address as printed by strace
+pub fn endpoint(addr: &[u8]) -> String {
+    let port = field(addr, b"sin6_port=htons(")
+        .or_else(|| field(addr, b"sin_port=htons("))
+        .map(|p| p.trim_end_with(|c| c == ')'));
+
+    if let Some(path) = field(addr, b"sun_path=") {
+        return path.trim_with(|c| c == '"').to_str_lossy().to_string();
+    }
+    if let (Some(ip), Some(port)) = (field(addr, b"inet_addr(\""), port) {
+        return format!("{}:{}", ip.to_str_lossy(), port.to_str_lossy());
+    }
+    if let (Some(ip), Some(port)) = (field(addr, b"inet_pton(AF_INET6, \""), port) {
+        return format!("[{}]:{}", ip.to_str_lossy(), port.to_str_lossy());
+    }
+
+    addr.to_str_lossy().to_string()
+}
+
+// The text following `key`, up to the next '"', ',' or '}'
+fn field<'a>(addr: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    let start = addr.find(key)? + key.len();
+    let rest = &addr[start..];
+    let end = if key.ends_with(b"\"") {
+        rest.find_byte(b'"')
+    } else {
+        rest.find_byteset(b",}")
+    };
+
+    Some(&rest[..end.unwrap_or(rest.len())])
+}
+
+/// Names for the sockets strace printed with only an inode, e.g.
+/// 'socket:[61612]', as with '-y' rather than '-yy'. Each is named from the
+/// address it was bound or connected to, and sockets accepted on a named
+/// listener are named after it:
+///
+/// * 'socket:[100] on 0.0.0.0:8080' for a bound socket
+/// * 'socket:[300] -> 10.0.0.2:5432' for a connected one
+/// * 'socket:[200] accepted on 0.0.0.0:8080' for an accepted one
+///
+/// The whole trace is searched, as servers often bind in a parent and accept
+/// in its workers.
+pub fn socket_names<'a>(raw_data: &HashMap<Pid, PidData<'a>>) -> HashMap<&'a [u8], String> {
+    let mut names = HashMap::default();
+    // The addresses sockets were bound to, to name the connections accepted on them
+    let mut bound: HashMap<&[u8], String> = HashMap::default();
+    let mut accepted = Vec::new();
+
+    for pid_data in raw_data.values() {
+        let mut socket_events = pid_data.socket_events.clone();
+        socket_events.par_sort_unstable_by(|x, y| (x.time).cmp(y.time));
+
+        let mut pending_listener = None;
+        for event in &socket_events {
+            match (event.syscall, &event.other) {
+                (b"accept" | b"accept4", Some(OtherFields::File(f))) => {
+                    pending_listener = Some(*f);
+                }
+                (b"accept" | b"accept4", Some(OtherFields::Accept { listener, conn })) => {
+                    if let Some(listener) = listener.or_else(|| pending_listener.take()) {
+                        accepted.push((listener, *conn));
+                    }
+                }
+                (
+                    syscall @ (b"bind" | b"connect"),
+                    Some(OtherFields::Connect {
+                        file: Some(f),
+                        addr: Some(addr),
+                    }),
+                ) if is_anonymous(f) && matches!(event.error, None | Some(b"EINPROGRESS")) => {
+                    let endpoint = endpoint(addr);
+                    let name = match syscall {
+                        b"bind" => format!("{} on {}", f.to_str_lossy(), endpoint),
+                        _ => format!("{} -> {}", f.to_str_lossy(), endpoint),
+                    };
+                    names.insert(*f, name);
+                    if syscall == b"bind" {
+                        bound.insert(*f, endpoint);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for (listener, conn) in accepted {
+        if !is_anonymous(conn) {
+            continue;
+        }
+        if let Some(endpoint) = bound.get(listener) {
+            let name = format!("{} accepted on {}", conn.to_str_lossy(), endpoint);
+            names.insert(conn, name);
+        }
+    }
+
+    names
+}
+
+// Sockets traced with '-yy' are already named by protocol and address
+fn is_anonymous(file: &[u8]) -> bool {
+    file.starts_with(b"socket:[")
+}
+
+/// Groups the 'connect' calls of `pids` by the address connected to. Calls
+/// left unfinished take their result from the line they are resumed on.
+pub fn connect_stats(pids: &[Pid], raw_data: &HashMap<Pid, PidData>) -> Vec<ConnectStats> {
+    let mut endpoints: HashMap<String, ConnectStats> = HashMap::default();
+
+    for pid in pids {
+        let mut socket_events = raw_data[pid].socket_events.clone();
+        socket_events.par_sort_unstable_by(|x, y| (x.time).cmp(y.time));
+
+        let mut pending_endpoint = None;
+        for event in socket_events.iter().filter(|e| e.syscall == b"connect") {
+            let addr = match event.other {
+                Some(OtherFields::Connect { addr, .. }) => addr,
+                _ => None,
+            };
+
+            let endpoint = match event.call_status {
+                CallStatus::Started => {
+                    pending_endpoint = addr.map(endpoint);
+                    continue;
+                }
+                CallStatus::Resumed => pending_endpoint.take(),
+                CallStatus::Complete => addr.map(endpoint),
+            };
+
+            if let Some(endpoint) = endpoint {
+                endpoints
+                    .entry(endpoint.clone())
+                    .or_insert_with(|| ConnectStats::new(endpoint))
+                    .add(event.error, event.duration);
+            }
+        }
+    }
+
+    let mut connect_stats: Vec<_> = endpoints.into_values().collect();
+    for stats in connect_stats.iter_mut() {
+        stats.latencies.sort_by(|x, y| {
+            x.partial_cmp(y)
+                .expect("Invalid comparison when sorting latencies")
+        });
+    }
+
+    connect_stats.sort_by(|x, y| {
+        (y.never_succeeded())
+            .cmp(&x.never_succeeded())
+            .then_with(|| y.attempts().cmp(&x.attempts()))
+            .then_with(|| (x.endpoint).cmp(&y.endpoint))
+    });
+
+    connect_stats
+}
+
+pub fn print_connections(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let listener_stats = service_times(pids, raw_data);
+    let connect_stats = connect_stats(pids, raw_data);
+
+    if listener_stats.is_empty() && connect_stats.is_empty() {
+        output::print_note("No connections found, '-y' must be passed to strace to match sockets")?;
+        return Ok(());
+    }
+
+    if !listener_stats.is_empty() {
+        let unix_stats = unix_socket_stats(&listener_stats);
+        print_service_times(listener_stats, &socket_names(raw_data), width)?;
+        if !unix_stats.is_empty() {
+            print_unix_socket_stats(unix_stats, width)?;
+        }
+    }
+    if !connect_stats.is_empty() {
+        print_connect_stats(connect_stats, width)?;
+    }
+
+    Ok(())
+}
+
+fn print_service_times(
+    listener_stats: Vec<ListenerStats>,
+    socket_names: &HashMap<&[u8], String>,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    output::print_heading("\nConnection service time by listening socket\n-----------\n")?;
+
+    let mut table = Table::new(vec![
+        Column::new("listener", 30, Align::Left, 0),
+        Column::new("conns", 7, Align::Right, 1),
+        Column::new("no reply", 8, Align::Right, 5),
+        Column::new("min (ms)", 10, Align::Right, 6),
+        Column::new("avg (ms)", 10, Align::Right, 3),
+        Column::new("p50 (ms)", 10, Align::Right, 4),
+        Column::new("p99 (ms)", 10, Align::Right, 2),
+        Column::new("max (ms)", 10, Align::Right, 7),
+    ]);
+
+    for stats in listener_stats {
+        table.add_row(vec![
+            socket_names
+                .get(stats.listener)
+                .cloned()
+                .unwrap_or_else(|| stats.listener.to_str_lossy().to_string()),
+            stats.conn_count().to_string(),
+            stats.unanswered.to_string(),
+            format!("{:.3}", stats.percentile(0.0)),
+            format!("{:.3}", stats.average()),
+            format!("{:.3}", stats.percentile(50.0)),
+            format!("{:.3}", stats.percentile(99.0)),
+            format!(
+                "{:.3}",
+                stats.service_times.last().copied().unwrap_or_default()
+            ),
+        ]);
+    }
+    table.print(width)?;
+
+    Ok(())
+}
+
+fn print_unix_socket_stats(
+    unix_stats: Vec<ListenerStats>,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    output::print_heading("\nRequests by UNIX socket\n-----------\n")?;
+
+    let mut table = Table::new(vec![
+        Column::new("socket path", 30, Align::Left, 0),
+        Column::new("requests", 8, Align::Right, 0),
+        Column::new("no reply", 8, Align::Right, 4),
+        Column::new("avg bytes in", 12, Align::Right, 1),
+        Column::new("avg bytes out", 13, Align::Right, 1),
+        Column::new("avg (ms)", 10, Align::Right, 2),
+        Column::new("p99 (ms)", 10, Align::Right, 3),
+    ]);
+
+    for stats in unix_stats {
+        let (avg_read, avg_written) = stats.average_bytes();
+        table.add_row(vec![
+            stats.listener.to_str_lossy().to_string(),
+            stats.conn_count().to_string(),
+            stats.unanswered.to_string(),
+            avg_read.to_string(),
+            avg_written.to_string(),
+            format!("{:.3}", stats.average()),
+            format!("{:.3}", stats.percentile(99.0)),
+        ]);
+    }
+    table.print(width)?;
+
+    Ok(())
+}
+
+fn print_connect_stats(
+    connect_stats: Vec<ConnectStats>,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    output::print_heading("\nConnect attempts by destination\n-----------\n")?;
+
+    let mut table = Table::new(vec![
+        Column::new("destination", 30, Align::Left, 0),
+        Column::new("attempts", 8, Align::Right, 0),
+        Column::new("ok", 6, Align::Right, 1),
+        Column::new("refused", 7, Align::Right, 2),
+        Column::new("timeout", 7, Align::Right, 2),
+        Column::new("pending", 7, Align::Right, 3),
+        Column::new("other", 6, Align::Right, 4),
+        Column::new("avg (ms)", 10, Align::Right, 5),
+        Column::new("max (ms)", 10, Align::Right, 6),
+        Column::new("status", 15, Align::Left, 1),
+    ]);
+
+    for stats in &connect_stats {
+        table.add_row(vec![
+            stats.endpoint.clone(),
+            stats.attempts().to_string(),
+            stats.succeeded.to_string(),
+            stats.refused.to_string(),
+            stats.timed_out.to_string(),
+            stats.in_progress.to_string(),
+            stats.other_errors.to_string(),
+            format!("{:.3}", stats.average()),
+            format!("{:.3}", stats.latencies.last().copied().unwrap_or_default()),
+            if stats.never_succeeded() {
+                String::from("never connected")
+            } else {
+                String::new()
+            },
+        ]);
+    }
+    table.print(width)?;
+
+    let failing = connect_stats.iter().filter(|s| s.never_succeeded()).count();
+    if failing > 0 {
+        output::print_note(&format!(
+            "\n  {} of {} destinations were never connected to",
+            failing,
+            connect_stats.len()
+        ))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn connections_service_time_until_last_write() {
+        let input = br##"9071  10:14:21.000000 accept4(3<socket:[100]>, NULL, NULL, SOCK_CLOEXEC) = 7<socket:[200]> <0.000010>
+9071  10:14:21.001000 read(7<socket:[200]>, "GET / HTTP/1.1\r\n", 1024) = 16 <0.000010>
+9071  10:14:21.003000 write(7<socket:[200]>, "HTTP/1.1 200 OK\r\n", 17) = 17 <0.000010>
+9071  10:14:21.005000 write(7<socket:[200]>, "hello", 5) = 5 <0.000010>
+9071  10:14:21.006000 close(7<socket:[200]>) = 0 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let stats = service_times(&[9071], &pid_data_map);
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].listener, b"socket:[100]");
+        assert_eq!(stats[0].unanswered, 0);
+        assert_relative_eq!(stats[0].service_times[0], 5.0, epsilon = 0.001);
+    }
+
+    #[test]
+    fn connections_resumed_accept_uses_started_listener() {
+        let input = br##"9071  10:14:21.000000 accept(3<socket:[100]>,  <unfinished ...>
+9071  10:14:22.000000 <... accept resumed>NULL, NULL) = 7<socket:[200]> <1.000000>
+9072  10:14:22.002000 shutdown(7<socket:[200]>, SHUT_WR) = 0 <0.000000>
+9071  10:14:22.003000 accept4(3<socket:[100]>, NULL, NULL, SOCK_CLOEXEC) = 8<socket:[201]> <0.000000>"##;
+        let pid_data_map = build_syscall_data(input);
+        let stats = service_times(&[9071, 9072], &pid_data_map);
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].listener, b"socket:[100]");
+        assert_eq!(stats[0].conn_count(), 2);
+        assert_eq!(stats[0].unanswered, 1);
+        assert_relative_eq!(stats[0].service_times[0], 2.0, epsilon = 0.001);
+    }
+
+    #[test]
+    fn connections_unix_socket_bytes_by_path() {
+        let input = br##"823   00:09:51.241574 accept4(25<UNIX:[3578806,"/run/app.socket"]>, NULL, NULL, SOCK_CLOEXEC) = 26<UNIX:[3605141->3605140,"/run/app.socket"]> <0.000010>
+823   00:09:51.241641 recvfrom(26<UNIX:[3605141->3605140,"/run/app.socket"]>, "GET /-/readiness HTTP/1.1\r\n", 16384, MSG_DONTWAIT, NULL, NULL) = 28 <0.000010>
+823   00:09:51.251641 write(26<UNIX:[3605141->3605140,"/run/app.socket"]>, "HTTP/1.1 200 OK\r\n\r\nok", 21) = 21 <0.000010>
+823   00:09:51.254859 close(26<UNIX:[3605141,"/run/app.socket"]>) = 0 <0.000017>
+817   00:09:51.641824 accept4(25<UNIX:[3578806,"/run/app.socket"]>, NULL, NULL, SOCK_CLOEXEC) = 26<UNIX:[3605148->3605147,"/run/app.socket"]> <0.000010>
+817   00:09:51.642000 read(26<UNIX:[3605148->3605147,"/run/app.socket"]>, "GET / HTTP/1.1\r\n", 16384) = 16 <0.000010>
+817   00:09:51.672000 write(26<UNIX:[3605148->3605147,"/run/app.socket"]>, "HTTP/1.1 302 Found\r\n\r\nx", 23) = 23 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let stats = service_times(&[817, 823], &pid_data_map);
+        let unix_stats = unix_socket_stats(&stats);
+
+        assert_eq!(unix_stats.len(), 1);
+        assert_eq!(unix_stats[0].listener, b"/run/app.socket");
+        assert_eq!(unix_stats[0].conn_count(), 2);
+        assert_eq!(unix_stats[0].bytes_read, 44);
+        assert_eq!(unix_stats[0].bytes_written, 44);
+        assert_eq!(unix_stats[0].average_bytes(), (22, 22));
+        assert_relative_eq!(unix_stats[0].average(), 20.122, epsilon = 0.001);
+    }
+
+    #[test]
+    fn connections_outbound_connect_tracked() {
+        let input = br##"9080  10:14:21.000000 connect(5<socket:[300]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = -1 EINPROGRESS (Operation now in progress) <0.000000>
+9080  10:14:21.010000 sendto(5<socket:[300]>, "ping", 4, 0, NULL, 0) = 4 <0.000000>
+9080  10:14:21.020000 connect(6<socket:[301]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = -1 ECONNREFUSED (Connection refused) <0.000000>
+9081  10:14:21.030000 read(5<socket:[300]>, "pong", 4096) = 4 <0.000000>
+9080  10:14:21.040000 recvfrom(5<socket:[300]>, "pong", 4096, 0, NULL, NULL) = 4 <0.002000>
+9080  10:14:21.050000 read(5<socket:[300]>, "more", 4096) = 4 <0.000000>
+9080  10:14:21.060000 connect(7<socket:[302]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = 0 <0.000000>
+9080  10:14:21.070000 write(7<socket:[302]>, "ping", 4) = 4 <0.000000>"##;
+        let pid_data_map = build_syscall_data(input);
+        let stats = service_times(&[9080, 9081], &pid_data_map);
+
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].listener, OUTBOUND);
+        assert_eq!(stats[0].conn_count(), 2);
+        assert_eq!(stats[0].unanswered, 1);
+        assert_relative_eq!(stats[0].service_times[0], 42.0, epsilon = 0.001);
+    }
+
+    #[test]
+    fn connections_endpoint_formats_addresses() {
+        assert_eq!(
+            endpoint(
+                br#"{sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}"#
+            ),
+            "10.0.0.2:5432"
+        );
+        assert_eq!(
+            endpoint(br#"{sa_family=AF_INET6, sin6_port=htons(443), sin6_flowinfo=htonl(0), inet_pton(AF_INET6, "::1", &sin6_addr), sin6_scope_id=0}"#),
+            "[::1]:443"
+        );
+        assert_eq!(
+            endpoint(br#"{sa_family=AF_UNIX, sun_path="/var/run/nscd/socket"}"#),
+            "/var/run/nscd/socket"
+        );
+        assert_eq!(
+            endpoint(b"{sa_family=AF_NETLINK, nl_pid=0, nl_groups=00000000}"),
+            "{sa_family=AF_NETLINK, nl_pid=0, nl_groups=00000000}"
+        );
+    }
+
+    #[test]
+    fn connections_connect_stats_by_destination() {
+        let input = br##"9080  10:14:21.000000 connect(5<socket:[300]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = -1 EINPROGRESS (Operation now in progress) <0.000100>
+9080  10:14:21.010000 connect(6<socket:[301]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = 0 <0.000300>
+9080  10:14:21.020000 connect(7<socket:[302]>, {sa_family=AF_INET, sin_port=htons(6379), sin_addr=inet_addr("10.0.0.3")}, 16) = -1 ECONNREFUSED (Connection refused) <0.000200>
+9081  10:14:21.030000 connect(8<socket:[303]>, {sa_family=AF_INET, sin_port=htons(6379), sin_addr=inet_addr("10.0.0.3")}, 16 <unfinished ...>
+9081  10:14:23.030000 <... connect resumed>) = -1 ETIMEDOUT (Connection timed out) <2.000000>
+9081  10:14:23.040000 connect(9, {sa_family=AF_UNIX, sun_path="/run/app.sock"}, 110) = -1 ENOENT (No such file or directory) <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let stats = connect_stats(&[9080, 9081], &pid_data_map);
+
+        assert_eq!(stats.len(), 3);
+
+        assert_eq!(stats[0].endpoint, "10.0.0.3:6379");
+        assert!(stats[0].never_succeeded());
+        assert_eq!(stats[0].refused, 1);
+        assert_eq!(stats[0].timed_out, 1);
+        assert_relative_eq!(stats[0].latencies[1], 2000.0, epsilon = 0.001);
+
+        assert_eq!(stats[1].endpoint, "/run/app.sock");
+        assert!(stats[1].never_succeeded());
+        assert_eq!(stats[1].other_errors, 1);
+
+        assert_eq!(stats[2].endpoint, "10.0.0.2:5432");
+        assert!(!stats[2].never_succeeded());
+        assert_eq!(stats[2].succeeded, 1);
+        assert_eq!(stats[2].in_progress, 1);
+        assert_relative_eq!(stats[2].average(), 0.2, epsilon = 0.001);
+    }
+
+    #[test]
+    fn connections_percentile_nearest_rank() {
+        let stats = ListenerStats {
+            listener: b"socket:[1]",
+            service_times: vec![1.0, 2.0, 3.0, 4.0],
+            unanswered: 0,
+            bytes_read: 0,
+            bytes_written: 0,
+        };
+
+        assert_relative_eq!(stats.percentile(0.0), 1.0);
+        assert_relative_eq!(stats.percentile(50.0), 2.0);
+        assert_relative_eq!(stats.percentile(99.0), 4.0);
+    }
+
+    #[test]
+    fn connections_sockets_named_from_bind_and_connect() {
+        let input = br##"9070  10:14:20.000000 bind(3<socket:[100]>, {sa_family=AF_INET, sin_port=htons(8080), sin_addr=inet_addr("0.0.0.0")}, 16) = 0 <0.000010>
+9071  10:14:21.000000 accept(3<socket:[100]>,  <unfinished ...>
+9071  10:14:22.000000 <... accept resumed>NULL, NULL) = 7<socket:[200]> <1.000000>
+9071  10:14:22.100000 connect(8<socket:[300]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = 0 <0.000120>
+9071  10:14:22.200000 bind(9<socket:[400]>, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr("0.0.0.0")}, 16) = -1 EACCES (Permission denied) <0.000010>
+9071  10:14:22.300000 connect(10<TCP:[10.0.0.1:4000->10.0.0.3:80]>, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr("10.0.0.3")}, 16) = 0 <0.000120>"##;
+        let pid_data_map = build_syscall_data(input);
+        let names = socket_names(&pid_data_map);
+
+        assert_eq!(
+            names[b"socket:[100]".as_ref()],
+            "socket:[100] on 0.0.0.0:8080"
+        );
+        assert_eq!(
+            names[b"socket:[200]".as_ref()],
+            "socket:[200] accepted on 0.0.0.0:8080"
+        );
+        assert_eq!(
+            names[b"socket:[300]".as_ref()],
+            "socket:[300] -> 10.0.0.2:5432"
+        );
+        assert_eq!(names.len(), 3);
+    }
+}
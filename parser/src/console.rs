@@ -0,0 +1,358 @@
+use crate::output;
+use crate::parser::{self, CallStatus, LineData};
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, prelude::*, Error};
+
+/// Where a write was sent, by fd number and the name '-y' gave the fd
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+    Tty,
+    Log,
+    /// Output thrown away by writing it to '/dev/null'
+    Null,
+}
+
+impl Stream {
+    /// The stream a write to `fd` went to, e.g. `2<pipe:[3008]>`. fds 1 and 2
+    /// are taken to be stdout and stderr unless sent to '/dev/null'. Writes to
+    /// other fds are only console output when sent to a terminal or a log file.
+    pub fn of(fd: &[u8]) -> Option<Stream> {
+        let (num, name) = match fd.find_byte(b'<') {
+            Some(idx) => (&fd[..idx], fd[idx + 1..].strip_suffix(b">")),
+            None => (fd, None),
+        };
+
+        match (num, name) {
+            (_, Some(b"/dev/null")) => Some(Stream::Null),
+            (b"1", _) => Some(Stream::Stdout),
+            (b"2", _) => Some(Stream::Stderr),
+            (_, Some(name))
+                if name.starts_with(b"/dev/pts/")
+                    || name.starts_with(b"/dev/tty")
+                    || name == b"/dev/console" =>
+            {
+                Some(Stream::Tty)
+            }
+            (_, Some(name)) if name.ends_with(b".log") || name.starts_with(b"/var/log/") => {
+                Some(Stream::Log)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+            Stream::Tty => "tty",
+            Stream::Log => "log",
+            Stream::Null => "/dev/null",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The payload of a write to the console, decoded from the string strace
+/// printed
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsoleWrite<'a> {
+    pub time: &'a [u8],
+    pub stream: Stream,
+    pub fd: &'a [u8],
+    pub text: Vec<u8>,
+    /// Whether strace cut the string short, it prints at most '-s' bytes
+    pub truncated: bool,
+}
+
+/// The console writes `pids` made, in the order made. Failed writes are left
+/// out, and partial writes are cut to the bytes written.
+pub fn console_writes<'a>(buffer: &'a [u8], pids: &[Pid]) -> BTreeMap<Pid, Vec<ConsoleWrite<'a>>> {
+    let pids: HashSet<Pid> = pids.iter().copied().collect();
+
+    let mut calls: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(|line| match parser::parse_line(line)? {
+            LineData::Syscall(raw_data)
+                if pids.contains(&raw_data.pid)
+                    && matches!(raw_data.syscall, b"pwrite64" | b"write" | b"writev") =>
+            {
+                Some((line, raw_data))
+            }
+            _ => None,
+        })
+        .collect();
+    calls.par_sort_by(|(_, x), (_, y)| x.time.cmp(y.time));
+
+    let mut writes: BTreeMap<Pid, Vec<ConsoleWrite>> = BTreeMap::new();
+    // The write each PID started, finished by the next resumed line
+    let mut started: HashMap<Pid, ConsoleWrite> = HashMap::default();
+
+    for (line, raw_data) in calls {
+        let write = match raw_data.call_status {
+            CallStatus::Started => {
+                if let Some(write) = console_write(line, raw_data.time) {
+                    started.insert(raw_data.pid, write);
+                }
+                continue;
+            }
+            CallStatus::Complete => console_write(line, raw_data.time),
+            CallStatus::Resumed => started.remove(&raw_data.pid),
+        };
+
+        if let (Some(mut write), None) = (write, raw_data.error) {
+            if let Some(written) = raw_data.rtn_cd.and_then(|b| usize::try_from(b).ok()) {
+                write.text.truncate(written);
+            }
+            writes.entry(raw_data.pid).or_default().push(write);
+        }
+    }
+
+    writes
+}
+
+// 477   00:09:56.954448 write(2<pipe:[3008]>, "error: no such file\n", 20) = 20 <0.000010>
+//                             ^^^^^^^^^^^^^^^  ^^^^^^^^^^^^^^^^^^^^^^^^
+fn console_write<'a>(line: &'a [u8], time: &'a [u8]) -> Option<ConsoleWrite<'a>> {
+    let args = parser::line_args(line);
+    let fd = *args.first()?;
+    let stream = Stream::of(fd)?;
+
+    // 'writev' prints each iovec's 'iov_base' as a string in turn
+    let mut text = Vec::new();
+    let mut truncated = false;
+    let mut rest = *args.get(1)?;
+    while let Some(start) = rest.find_byte(b'"') {
+        let (bytes, len) = unescape(&rest[start + 1..]);
+        text.extend(bytes);
+        rest = &rest[start + 1 + len..];
+        truncated |= rest.starts_with(b"...");
+    }
+
+    Some(ConsoleWrite {
+        time,
+        stream,
+        fd,
+        text,
+        truncated,
+    })
+}
+
+// Decodes a string printed by strace, 'rest' starts just after the opening
+// quote. Returns the bytes and the length of the string with its closing quote.
+fn unescape(rest: &[u8]) -> (Vec<u8>, usize) {
+    let mut bytes = Vec::new();
+    let mut idx = 0;
+
+    while let Some(&c) = rest.get(idx) {
+        idx += 1;
+        let escaped = match c {
+            b'"' => return (bytes, idx),
+            b'\\' => match rest.get(idx) {
+                Some(&c) => c,
+                None => break,
+            },
+            _ => {
+                bytes.push(c);
+                continue;
+            }
+        };
+        idx += 1;
+
+        match escaped {
+            b'n' => bytes.push(b'\n'),
+            b't' => bytes.push(b'\t'),
+            b'r' => bytes.push(b'\r'),
+            b'v' => bytes.push(0x0b),
+            b'f' => bytes.push(0x0c),
+            b'x' => {
+                let hex = rest.get(idx..idx + 2).and_then(|h| h.to_str().ok());
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        bytes.push(b);
+                        idx += 2;
+                    }
+                    None => bytes.push(b'x'),
+                }
+            }
+            // Octal escapes of one to three digits, e.g. '\0' or '\33'
+            b'0'..=b'7' => {
+                let mut value = u32::from(escaped - b'0');
+                for _ in 0..2 {
+                    match rest.get(idx) {
+                        Some(&d @ b'0'..=b'7') => {
+                            value = value * 8 + u32::from(d - b'0');
+                            idx += 1;
+                        }
+                        _ => break,
+                    }
+                }
+                bytes.push(value as u8);
+            }
+            c => bytes.push(c),
+        }
+    }
+
+    (bytes, idx)
+}
+
+/// Prints what each of `pids` wrote to stdout, stderr, terminals, log files,
+/// and '/dev/null', grouped by PID and fd
+pub fn print_console(buffer: &[u8], pids: &[Pid]) -> Result<(), Error> {
+    let writes = console_writes(buffer, pids);
+
+    output::print_heading("\nConsole Output by PID\n-----------\n")?;
+    if writes.is_empty() {
+        output::print_note(
+            "  No writes to stdout, stderr, terminals, or log files found, fds are only named when traced with '-y'\n",
+        )?;
+        return Ok(());
+    }
+
+    let mut out = io::stdout();
+    let mut first = true;
+    for (pid, writes) in &writes {
+        // Grouped by fd, in the order each fd was first written to
+        let mut fds: Vec<(&[u8], Stream, Vec<&ConsoleWrite>)> = Vec::new();
+        for write in writes {
+            match fds.iter_mut().find(|(fd, ..)| *fd == write.fd) {
+                Some((.., fd_writes)) => fd_writes.push(write),
+                None => fds.push((write.fd, write.stream, vec![write])),
+            }
+        }
+
+        for (fd, stream, fd_writes) in fds {
+            let bytes: usize = fd_writes.iter().map(|w| w.text.len()).sum();
+            if !first {
+                writeln!(out)?;
+            }
+            first = false;
+            writeln!(
+                out,
+                "  PID {} {} ({}), {} {}, {} bytes",
+                output::pid_label(*pid),
+                stream,
+                fd.to_str_lossy(),
+                fd_writes.len(),
+                if fd_writes.len() == 1 {
+                    "write"
+                } else {
+                    "writes"
+                },
+                bytes
+            )?;
+
+            let mut text = String::new();
+            for write in fd_writes {
+                text += &printable(&write.text);
+                if write.truncated {
+                    text += "...\n";
+                }
+            }
+            for line in text.lines() {
+                writeln!(out, "    | {}", line)?;
+            }
+        }
+    }
+
+    if writes.values().flatten().any(|w| w.truncated) {
+        output::print_note(
+            "\n  Writes ending in '...' were cut short by strace, trace with '-s 4096' or larger to capture them whole\n",
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes the bytes `pids` sent to stdout and stderr, exactly as written and
+/// in the order written, recovering the programs' output
+pub fn write_raw_console<W: Write>(out: &mut W, buffer: &[u8], pids: &[Pid]) -> Result<(), Error> {
+    let mut writes: Vec<_> = console_writes(buffer, pids)
+        .into_values()
+        .flatten()
+        .filter(|w| matches!(w.stream, Stream::Stdout | Stream::Stderr))
+        .collect();
+    writes.sort_by(|x, y| x.time.cmp(y.time));
+
+    for write in writes {
+        out.write_all(&write.text)?;
+    }
+
+    Ok(())
+}
+
+// Control characters other than tabs and newlines are escaped as strace
+// escapes them, e.g. '\0' or '\x1b', so escape sequences written to a
+// terminal don't act on this one
+fn printable(text: &[u8]) -> String {
+    let mut printable = String::with_capacity(text.len());
+    for (start, end, c) in text.char_indices() {
+        match c {
+            '\t' | '\n' => printable.push(c),
+            '\0' => printable += "\\0",
+            '\r' => printable += "\\r",
+            '\x0b' => printable += "\\v",
+            '\x0c' => printable += "\\f",
+            c if c.is_control() => {
+                for b in &text[start..end] {
+                    printable += &format!("\\x{:02x}", b);
+                }
+            }
+            // Bytes that aren't UTF-8 are shown as U+FFFD
+            c => printable.push(c),
+        }
+    }
+
+    printable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn console_writes_decoded_by_stream() {
+        let input = br##"477   00:09:56.000000 write(1</dev/pts/0>, "hello\n\33[0m\tw\303\266rld\n", 18) = 18 <0.000010>
+477   00:09:56.100000 write(2<pipe:[3008]>, "error: "..., 32 <unfinished ...>
+495   00:09:56.150000 write(5</var/log/app.log>, "\x6f\x6b\n", 3) = 3 <0.000010>
+477   00:09:56.200000 <... write resumed>) = 4 <0.000010>
+477   00:09:56.300000 writev(1</dev/null>, [{iov_base="a\"b", iov_len=3}, {iov_base="\\c\0", iov_len=3}], 2) = 6 <0.000010>
+477   00:09:56.400000 write(1</dev/pts/0>, "lost", 4) = -1 EPIPE (Broken pipe) <0.000010>
+477   00:09:56.500000 write(7<socket:[300]>, "GET / HTTP/1.1", 14) = 14 <0.000010>
+495   00:09:56.600000 write(2, "partial", 7) = 4 <0.000010>"##;
+        let writes = console_writes(input, &[477, 495]);
+
+        let pid_writes = &writes[&477];
+        assert_eq!(pid_writes.len(), 3);
+        assert_eq!(pid_writes[0].stream, Stream::Stdout);
+        assert_eq!(pid_writes[0].text, "hello\n\x1b[0m\twörld\n".as_bytes());
+        assert_eq!(pid_writes[1].stream, Stream::Stderr);
+        assert_eq!(pid_writes[1].text, b"erro");
+        assert!(pid_writes[1].truncated);
+        assert_eq!(pid_writes[2].stream, Stream::Null);
+        assert_eq!(pid_writes[2].text, b"a\"b\\c\0");
+
+        let pid_writes = &writes[&495];
+        assert_eq!(pid_writes[0].stream, Stream::Log);
+        assert_eq!(pid_writes[0].text, b"ok\n");
+        assert_eq!(pid_writes[1].text, b"part");
+
+        let mut out = Vec::new();
+        write_raw_console(&mut out, input, &[477, 495]).unwrap();
+        assert_eq!(out, "hello\n\x1b[0m\twörld\nerropart".as_bytes());
+        assert_eq!(printable(b"\x1b[0m\tok"), "\\x1b[0m\tok");
+        assert_eq!(
+            printable(b"a\0b\x01\r\n\xc2\x85"),
+            "a\\0b\\x01\\r\n\\xc2\\x85"
+        );
+    }
+}
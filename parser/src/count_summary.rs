@@ -0,0 +1,391 @@
+use crate::json::{self, rounded, Lossy};
+use crate::output;
+use crate::syscall_groups::{GroupMode, SyscallGroups};
+use crate::table::{Align, Column, Table};
+
+use bstr::ByteSlice;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::io::{prelude::*, stdout, Error};
+
+// The table is printed as strace exits, but may follow output from the traced program
+const MAX_HEADER_LINE: usize = 20;
+
+/// A row of the table printed by 'strace -c'
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SyscallCount<'a> {
+    pub name: &'a [u8],
+    pub calls: i32,
+    pub errors: i32,
+    /// Total time in syscall, in ms
+    pub total: f32,
+}
+
+impl<'a> SyscallCount<'a> {
+    pub fn avg(&self) -> f32 {
+        if self.calls > 0 {
+            self.total / self.calls as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The per-syscall totals of a trace recorded with 'strace -c', which has
+/// no individual calls or PIDs
+#[derive(Clone, Debug, PartialEq)]
+pub struct CountSummary<'a> {
+    pub syscalls: Vec<SyscallCount<'a>>,
+    pub total: Option<SyscallCount<'a>>,
+}
+
+impl<'a> CountSummary<'a> {
+    /// Finds and parses a 'strace -c' table near the start of `buffer`
+    pub fn parse(buffer: &'a [u8]) -> Option<CountSummary<'a>> {
+        // % time     seconds  usecs/call     calls    errors syscall
+        // ------ ----------- ----------- --------- --------- ----------------
+        //  45.27    0.000297           8        35           mmap
+        //   7.01    0.000046           6         7         2 openat
+        // ------ ----------- ----------- --------- --------- ----------------
+        // 100.00    0.000656           5       122         6 total
+        let mut lines = ByteSlice::lines(buffer);
+        lines
+            .by_ref()
+            .take(MAX_HEADER_LINE)
+            .find(|line| line.trim_start().starts_with(b"% time"))?;
+
+        // Empty and right-aligned cells are sliced from the spans of the dashes
+        let divider = lines.next()?;
+        let spans = column_spans(divider);
+        if spans.is_empty() {
+            return None;
+        }
+
+        let mut syscalls = Vec::new();
+        let mut total = None;
+
+        for line in lines {
+            if line.starts_with(b"---") {
+                continue;
+            }
+            let row = match parse_row(line, &spans) {
+                Some(row) => row,
+                None => break,
+            };
+
+            if row.name == b"total" {
+                total = Some(row);
+                break;
+            }
+            syscalls.push(row);
+        }
+
+        if syscalls.is_empty() {
+            None
+        } else {
+            Some(CountSummary { syscalls, total })
+        }
+    }
+
+    /// Adds a row totalling each group with members present. With
+    /// `GroupMode::Instead` the member rows are removed.
+    pub fn group_syscalls(&mut self, groups: &SyscallGroups<'a>, mode: GroupMode) {
+        let mut group_rows = Vec::new();
+
+        for (name, members) in groups.iter() {
+            let member_rows: Vec<_> = self
+                .syscalls
+                .iter()
+                .filter(|s| members.contains(s.name))
+                .collect();
+
+            if !member_rows.is_empty() {
+                group_rows.push(SyscallCount {
+                    name,
+                    calls: member_rows.iter().map(|s| s.calls).sum(),
+                    errors: member_rows.iter().map(|s| s.errors).sum(),
+                    total: member_rows.iter().map(|s| s.total).sum(),
+                });
+            }
+        }
+
+        if mode == GroupMode::Instead {
+            self.syscalls.retain(|s| !groups.is_grouped(s.name));
+        }
+        self.syscalls.extend(group_rows);
+    }
+
+    pub fn print(&self, count: usize, width: Option<usize>) -> Result<(), Error> {
+        let total_time = self.total_time();
+
+        output::print_heading(&format!(
+            "\nTop {} Syscalls by Time\n-----------\n",
+            count.min(self.syscalls.len())
+        ))?;
+        output::print_note(
+            "  Only per-syscall counts were found, as printed by 'strace -c'.\n  Only 'summary' and 'baseline' work with these, other subcommands need a full trace.\n",
+        )?;
+
+        let mut table = Table::new(vec![
+            Column::new("syscall", 17, Align::Left, 0),
+            Column::new("calls", 9, Align::Right, 0),
+            Column::new("errors", 9, Align::Right, 1),
+            Column::new("total (ms)", 10, Align::Right, 0),
+            Column::new("avg (ms)", 10, Align::Right, 2),
+            Column::new("% time", 7, Align::Right, 3),
+        ]);
+
+        for syscall in self.sorted().iter().take(count) {
+            table.add_row(vec![
+                syscall.name.to_str_lossy().to_string(),
+                syscall.calls.to_string(),
+                syscall.errors.to_string(),
+                format!("{:.3}", syscall.total),
+                format!("{:.3}", syscall.avg()),
+                self.time_pct(syscall)
+                    .map_or_else(|| String::from("-"), |pct| format!("{:.2}%", pct)),
+            ]);
+        }
+        table.print(width)?;
+
+        let (calls, errors) = self.totals();
+        writeln!(stdout(), "\nsyscalls {}", calls)?;
+        writeln!(stdout(), "errors   {}", errors)?;
+        writeln!(stdout(), "time     {:.3} ms\n", total_time)?;
+
+        Ok(())
+    }
+
+    /// Writes what `print` does as one JSON object: the top `count` syscalls
+    /// by time, and the totals
+    pub fn write_json<W: Write>(&self, out: &mut W, count: usize) -> Result<(), Error> {
+        let syscalls = self
+            .sorted()
+            .iter()
+            .take(count)
+            .map(|syscall| SyscallCountJson {
+                syscall: Lossy(syscall.name),
+                calls: syscall.calls,
+                errors: syscall.errors,
+                total_ms: rounded(syscall.total, 3),
+                avg_ms: rounded(syscall.avg(), 3),
+                time_pct: self.time_pct(syscall).map(|pct| rounded(pct, 2)),
+            })
+            .collect();
+        let (calls, errors) = self.totals();
+
+        json::write_report(
+            out,
+            &CountSummaryReport {
+                syscalls,
+                calls,
+                errors,
+                total_ms: rounded(self.total_time(), 3),
+            },
+        )
+    }
+
+    // Rows slowest first
+    fn sorted(&self) -> Vec<&SyscallCount<'a>> {
+        let mut syscalls: Vec<_> = self.syscalls.iter().collect();
+        syscalls.sort_by(|x, y| y.total.total_cmp(&x.total));
+        syscalls
+    }
+
+    // The calls and errors of the 'total' row, summed from the others without one
+    fn totals(&self) -> (i32, i32) {
+        match self.total {
+            Some(total) => (total.calls, total.errors),
+            None => (
+                self.syscalls.iter().map(|s| s.calls).sum(),
+                self.syscalls.iter().map(|s| s.errors).sum(),
+            ),
+        }
+    }
+
+    fn total_time(&self) -> f32 {
+        match self.total {
+            Some(total) => total.total,
+            None => self.syscalls.iter().map(|s| s.total).sum(),
+        }
+    }
+
+    // Percent of the total time spent in `syscall`, `None` when no time was spent
+    fn time_pct(&self, syscall: &SyscallCount) -> Option<f32> {
+        let total_time = self.total_time();
+        if total_time > 0.0 {
+            Some(syscall.total / total_time * 100.0)
+        } else {
+            None
+        }
+    }
+}
+
+// Byte ranges of each run of '-' in the divider, the last runs to the end of the line
+fn column_spans(divider: &[u8]) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+
+    for (idx, c) in divider.iter().enumerate() {
+        match (c, start) {
+            (b'-', None) => start = Some(idx),
+            (b'-', Some(_)) => {}
+            (_, Some(s)) => {
+                spans.push((s, idx));
+                start = None;
+            }
+            (_, None) => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, usize::MAX));
+    }
+
+    spans
+}
+
+// Columns are '% time', 'seconds', 'usecs/call', 'calls', 'errors', then 'syscall'
+fn parse_row<'a>(line: &'a [u8], spans: &[(usize, usize)]) -> Option<SyscallCount<'a>> {
+    if spans.len() < 6 {
+        return None;
+    }
+
+    let cell = |idx: usize| -> &'a [u8] {
+        let (start, end) = spans[idx];
+        let start = start.min(line.len());
+        line[start..end.min(line.len())].trim()
+    };
+    let number = |idx: usize| cell(idx).to_str().ok().filter(|c| !c.is_empty());
+
+    let name = cell(5);
+    if name.is_empty() {
+        return None;
+    }
+
+    let seconds = number(1)?.parse::<f32>().ok()?;
+    let calls = number(3)?.parse::<i32>().ok()?;
+    let errors = match number(4) {
+        Some(errors) => errors.parse::<i32>().ok()?,
+        None => 0,
+    };
+
+    Some(SyscallCount {
+        name,
+        calls,
+        errors,
+        total: seconds * 1000.0,
+    })
+}
+
+// A row of the 'strace -c' table as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct SyscallCountJson<'a> {
+    syscall: Lossy<'a>,
+    calls: i32,
+    errors: i32,
+    total_ms: f64,
+    avg_ms: f64,
+    time_pct: Option<f64>,
+}
+
+// The 'strace -c' table as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct CountSummaryReport<'a> {
+    syscalls: Vec<SyscallCountJson<'a>>,
+    calls: i32,
+    errors: i32,
+    total_ms: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn count_summary_parses_table() {
+        let input = br##"hello
+% time     seconds  usecs/call     calls    errors syscall
+------ ----------- ----------- --------- --------- ----------------
+ 45.27    0.000297           8        35           mmap
+  7.01    0.000046           6         7         2 openat
+  0.00    0.000000           0         1           rt_sigreturn
+------ ----------- ----------- --------- --------- ----------------
+100.00    0.000656           5       122         6 total
+"##;
+        let summary = CountSummary::parse(input).unwrap();
+
+        assert_eq!(summary.syscalls.len(), 3);
+        assert_eq!(summary.syscalls[0].name, b"mmap");
+        assert_eq!(summary.syscalls[0].calls, 35);
+        assert_eq!(summary.syscalls[0].errors, 0);
+        assert_relative_eq!(summary.syscalls[0].total, 0.297);
+        assert_eq!(summary.syscalls[1].errors, 2);
+
+        let total = summary.total.unwrap();
+        assert_eq!(total.calls, 122);
+        assert_eq!(total.errors, 6);
+    }
+
+    #[test]
+    fn count_summary_allows_empty_usecs_per_call() {
+        let input = br##"% time     seconds  usecs/call     calls    errors syscall
+------ ----------- ----------- --------- --------- ----------------
+100.00    0.000070                    22         1 execve
+------ ----------- ----------- --------- --------- ----------------
+100.00    0.000070                    22         1 total"##;
+        let summary = CountSummary::parse(input).unwrap();
+
+        assert_eq!(summary.syscalls[0].calls, 22);
+        assert_eq!(summary.syscalls[0].errors, 1);
+    }
+
+    #[test]
+    fn count_summary_writes_json() {
+        let input = br##"% time     seconds  usecs/call     calls    errors syscall
+------ ----------- ----------- --------- --------- ----------------
+ 25.00    0.000100          10        10           mmap
+ 75.00    0.000300          30        10         4 stat
+------ ----------- ----------- --------- --------- ----------------
+100.00    0.000400          20        20         4 total"##;
+        let summary = CountSummary::parse(input).unwrap();
+
+        let mut out = Vec::new();
+        summary.write_json(&mut out, 1).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+
+        let syscalls = value["syscalls"].as_array().unwrap();
+        assert_eq!(syscalls.len(), 1);
+        assert_eq!(syscalls[0]["syscall"], "stat");
+        assert_eq!(syscalls[0]["errors"], 4);
+        assert_eq!(syscalls[0]["time_pct"], 75.0);
+        assert_eq!(value["calls"], 20);
+        assert_eq!(value["total_ms"], 0.4);
+    }
+
+    #[test]
+    fn count_summary_groups_syscalls() {
+        let input = br##"% time     seconds  usecs/call     calls    errors syscall
+------ ----------- ----------- --------- --------- ----------------
+ 60.00    0.000600          20        30           openat
+ 30.00    0.000300          30        10         4 stat
+ 10.00    0.000100          10        10           mmap"##;
+        let config = "file_meta = [openat, stat]";
+        let groups = SyscallGroups::parse(config).unwrap();
+
+        let mut summary = CountSummary::parse(input).unwrap();
+        summary.group_syscalls(&groups, GroupMode::Instead);
+
+        assert_eq!(summary.syscalls.len(), 2);
+        assert_eq!(summary.syscalls[1].name, b"file_meta");
+        assert_eq!(summary.syscalls[1].calls, 40);
+        assert_eq!(summary.syscalls[1].errors, 4);
+        assert_relative_eq!(summary.syscalls[1].total, 0.9);
+    }
+
+    #[test]
+    fn count_summary_ignores_full_traces() {
+        let input = br##"477   00:09:56.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>"##;
+        assert_eq!(CountSummary::parse(input), None);
+    }
+}
@@ -0,0 +1,365 @@
+use crate::output;
+use crate::syscall_stats::SyscallStats;
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::Error;
+
+/// Classes of syscalls, as named by strace's `-e trace=%<class>`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SyscallClass {
+    Creds,
+    Desc,
+    File,
+    Ipc,
+    Memory,
+    Network,
+    Process,
+    Signal,
+}
+
+const CLASSES: [SyscallClass; 8] = [
+    SyscallClass::Creds,
+    SyscallClass::Desc,
+    SyscallClass::File,
+    SyscallClass::Ipc,
+    SyscallClass::Memory,
+    SyscallClass::Network,
+    SyscallClass::Process,
+    SyscallClass::Signal,
+];
+
+impl SyscallClass {
+    pub fn contains(self, syscall: &[u8]) -> bool {
+        match self {
+            SyscallClass::Creds => matches!(
+                syscall,
+                b"capget"
+                    | b"capset"
+                    | b"getegid"
+                    | b"geteuid"
+                    | b"getgid"
+                    | b"getgroups"
+                    | b"getresgid"
+                    | b"getresuid"
+                    | b"getuid"
+                    | b"setgid"
+                    | b"setgroups"
+                    | b"setregid"
+                    | b"setresgid"
+                    | b"setresuid"
+                    | b"setreuid"
+                    | b"setuid"
+            ),
+            SyscallClass::Desc => matches!(
+                syscall,
+                b"close"
+                    | b"dup"
+                    | b"dup2"
+                    | b"dup3"
+                    | b"epoll_create"
+                    | b"epoll_create1"
+                    | b"epoll_ctl"
+                    | b"epoll_pwait"
+                    | b"epoll_wait"
+                    | b"eventfd"
+                    | b"eventfd2"
+                    | b"fcntl"
+                    | b"fdatasync"
+                    | b"flock"
+                    | b"fstat"
+                    | b"fsync"
+                    | b"ftruncate"
+                    | b"getdents"
+                    | b"getdents64"
+                    | b"ioctl"
+                    | b"lseek"
+                    | b"pipe"
+                    | b"pipe2"
+                    | b"poll"
+                    | b"ppoll"
+                    | b"pread64"
+                    | b"preadv"
+                    | b"preadv2"
+                    | b"pselect6"
+                    | b"pwrite64"
+                    | b"pwritev"
+                    | b"pwritev2"
+                    | b"read"
+                    | b"readv"
+                    | b"select"
+                    | b"sendfile"
+                    | b"splice"
+                    | b"write"
+                    | b"writev"
+            ),
+            SyscallClass::File => matches!(
+                syscall,
+                b"access"
+                    | b"chdir"
+                    | b"chmod"
+                    | b"chown"
+                    | b"creat"
+                    | b"execve"
+                    | b"execveat"
+                    | b"faccessat"
+                    | b"fchmodat"
+                    | b"fchownat"
+                    | b"lchown"
+                    | b"link"
+                    | b"linkat"
+                    | b"lstat"
+                    | b"mkdir"
+                    | b"mkdirat"
+                    | b"newfstatat"
+                    | b"open"
+                    | b"openat"
+                    | b"openat2"
+                    | b"readlink"
+                    | b"readlinkat"
+                    | b"rename"
+                    | b"renameat"
+                    | b"renameat2"
+                    | b"rmdir"
+                    | b"stat"
+                    | b"statfs"
+                    | b"statx"
+                    | b"symlink"
+                    | b"symlinkat"
+                    | b"truncate"
+                    | b"unlink"
+                    | b"unlinkat"
+                    | b"utimensat"
+            ),
+            SyscallClass::Ipc => matches!(
+                syscall,
+                b"msgctl"
+                    | b"msgget"
+                    | b"msgrcv"
+                    | b"msgsnd"
+                    | b"semctl"
+                    | b"semget"
+                    | b"semop"
+                    | b"semtimedop"
+                    | b"shmat"
+                    | b"shmctl"
+                    | b"shmdt"
+                    | b"shmget"
+            ),
+            SyscallClass::Memory => matches!(
+                syscall,
+                b"brk"
+                    | b"madvise"
+                    | b"mlock"
+                    | b"mmap"
+                    | b"mprotect"
+                    | b"mremap"
+                    | b"msync"
+                    | b"munlock"
+                    | b"munmap"
+            ),
+            SyscallClass::Network => matches!(
+                syscall,
+                b"accept"
+                    | b"accept4"
+                    | b"bind"
+                    | b"connect"
+                    | b"getpeername"
+                    | b"getsockname"
+                    | b"getsockopt"
+                    | b"listen"
+                    | b"recv"
+                    | b"recvfrom"
+                    | b"recvmmsg"
+                    | b"recvmsg"
+                    | b"send"
+                    | b"sendmmsg"
+                    | b"sendmsg"
+                    | b"sendto"
+                    | b"setsockopt"
+                    | b"shutdown"
+                    | b"socket"
+                    | b"socketpair"
+            ),
+            SyscallClass::Process => matches!(
+                syscall,
+                b"clone"
+                    | b"clone3"
+                    | b"execve"
+                    | b"execveat"
+                    | b"exit"
+                    | b"exit_group"
+                    | b"fork"
+                    | b"kill"
+                    | b"tgkill"
+                    | b"tkill"
+                    | b"vfork"
+                    | b"wait4"
+                    | b"waitid"
+            ),
+            SyscallClass::Signal => matches!(
+                syscall,
+                b"kill"
+                    | b"pause"
+                    | b"rt_sigaction"
+                    | b"rt_sigprocmask"
+                    | b"rt_sigreturn"
+                    | b"rt_sigsuspend"
+                    | b"rt_sigtimedwait"
+                    | b"sigaltstack"
+                    | b"signalfd"
+                    | b"signalfd4"
+                    | b"tgkill"
+                    | b"tkill"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SyscallClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SyscallClass::Creds => "%creds",
+            SyscallClass::Desc => "%desc",
+            SyscallClass::File => "%file",
+            SyscallClass::Ipc => "%ipc",
+            SyscallClass::Memory => "%memory",
+            SyscallClass::Network => "%network",
+            SyscallClass::Process => "%process",
+            SyscallClass::Signal => "%signal",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Which syscalls appear in the trace. A trace captured with a filter such as
+/// `-e trace=network` is missing most calls, so reports built on the rest of
+/// them would only show zeros.
+#[derive(Clone, Debug, Default)]
+pub struct Coverage<'a> {
+    syscalls: BTreeSet<&'a [u8]>,
+}
+
+impl<'a> Coverage<'a> {
+    pub fn from_syscall_stats(session_stats: &HashMap<Pid, Vec<SyscallStats<'a>>>) -> Self {
+        Coverage {
+            syscalls: session_stats
+                .values()
+                .flat_map(|stats| stats.iter().map(|s| s.name))
+                .collect(),
+        }
+    }
+
+    pub fn contains(&self, syscall: &[u8]) -> bool {
+        self.syscalls.contains(syscall)
+    }
+
+    /// Any process that runs long enough to be worth tracing reads, writes,
+    /// or maps memory, so a trace without any of these was filtered
+    pub fn is_restricted(&self) -> bool {
+        !self.syscalls.is_empty()
+            && !self
+                .syscalls
+                .iter()
+                .any(|s| SyscallClass::Desc.contains(s) || SyscallClass::Memory.contains(s))
+    }
+
+    /// The classes with at least one syscall in the trace
+    pub fn classes(&self) -> Vec<SyscallClass> {
+        CLASSES
+            .iter()
+            .copied()
+            .filter(|class| self.syscalls.iter().any(|s| class.contains(s)))
+            .collect()
+    }
+
+    /// Describes a filtered trace, noting that times between syscalls include
+    /// calls that weren't traced
+    pub fn print_coverage(&self) -> Result<(), Error> {
+        if !self.is_restricted() {
+            return Ok(());
+        }
+
+        let classes = self.classes();
+        let described = if classes.is_empty() {
+            self.syscalls
+                .iter()
+                .map(|s| s.to_str_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        } else {
+            classes
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        output::print_note(&format!(
+            "  Note: this trace appears to be limited to {} syscalls, e.g. with 'strace -e trace=...'",
+            described
+        ))?;
+        output::print_note(
+            "  Only those calls are counted, and user time includes time spent in calls that weren't traced",
+        )
+    }
+
+    /// Whether a report built on `needs` has anything to show. When none of
+    /// them were traced, prints why the report was skipped.
+    pub fn check_report(&self, report: &str, needs: &[&[u8]]) -> Result<bool, Error> {
+        if needs.is_empty() || !self.is_restricted() || needs.iter().any(|s| self.contains(s)) {
+            return Ok(true);
+        }
+
+        output::print_note(&format!(
+            "\n  Skipping '{}', it needs {} which weren't traced",
+            report,
+            needs
+                .iter()
+                .map(|s| s.to_str_lossy())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))?;
+
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+    use crate::syscall_stats::build_syscall_stats;
+
+    #[test]
+    fn coverage_detects_filtered_trace() {
+        let input = br##"9080  10:14:21.000000 socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 5<TCP:[61612]> <0.000020>
+9080  10:14:21.000100 connect(5<TCP:[61612]>, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr("10.0.0.1")}, 16) = 0 <0.000300>
+9081  10:14:21.000200 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f0) = 9082 <0.000100>"##;
+        let pid_data_map = build_syscall_data(input);
+        let coverage = Coverage::from_syscall_stats(&build_syscall_stats(&pid_data_map));
+
+        assert!(coverage.is_restricted());
+        assert_eq!(
+            coverage.classes(),
+            vec![SyscallClass::Network, SyscallClass::Process]
+        );
+        assert!(coverage.check_report("connections", &[b"connect"]).unwrap());
+        assert!(!coverage
+            .check_report("files", &[b"open", b"openat"])
+            .unwrap());
+    }
+
+    #[test]
+    fn coverage_full_trace_not_restricted() {
+        let input = br##"9080  10:14:21.000000 openat(AT_FDCWD, "/etc/hosts", O_RDONLY|O_CLOEXEC) = 3</etc/hosts> <0.000020>
+9080  10:14:21.000100 read(3</etc/hosts>, "127.0.0.1 localhost\n", 4096) = 20 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let coverage = Coverage::from_syscall_stats(&build_syscall_stats(&pid_data_map));
+
+        assert!(!coverage.is_restricted());
+        assert!(coverage.check_report("connections", &[b"connect"]).unwrap());
+    }
+}
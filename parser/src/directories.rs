@@ -1,4 +1,5 @@
-use crate::file_data::{self, FileData};
+use crate::output;
+use crate::parser::{CallStatus, OtherFields, RawData};
 use crate::syscall_data::PidData;
 use crate::{HashMap, Pid};
 
@@ -8,6 +9,12 @@ use rayon::prelude::*;
 use std::collections::BTreeMap;
 use std::fmt;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct DirectoryData<'a> {
     pub pid: Pid,
@@ -15,29 +22,95 @@ pub struct DirectoryData<'a> {
     pub end_time: &'a [u8],
     pub duration: f32,
     pub ct: usize,
+    pub read_ct: usize,
+    pub read_duration: f32,
+    pub write_ct: usize,
+    pub write_duration: f32,
 }
 
 impl<'a> DirectoryData<'a> {
-    fn new(pid: Pid, start_time: &'a [u8], end_time: &'a [u8], duration: f32) -> DirectoryData<'a> {
-        DirectoryData {
+    fn new(pid: Pid, event: &DirEvent<'a>) -> DirectoryData<'a> {
+        let mut data = DirectoryData {
             pid,
-            start_time,
-            end_time,
-            duration,
-            ct: 1,
+            start_time: event.time,
+            end_time: event.time,
+            duration: 0.0,
+            ct: 0,
+            read_ct: 0,
+            read_duration: 0.0,
+            write_ct: 0,
+            write_duration: 0.0,
+        };
+        data.add_event(event);
+        data
+    }
+
+    fn add_event(&mut self, event: &DirEvent<'a>) {
+        if event.time < self.start_time {
+            self.start_time = event.time;
         }
+
+        if event.time > self.end_time {
+            self.end_time = event.time;
+        }
+
+        self.duration += event.duration;
+        self.ct += 1;
+
+        match event.access {
+            Access::Read => {
+                self.read_ct += 1;
+                self.read_duration += event.duration;
+            }
+            Access::Write => {
+                self.write_ct += 1;
+                self.write_duration += event.duration;
+            }
+        }
+    }
+
+    fn merge(&mut self, other: &DirectoryData<'a>) {
+        if other.start_time < self.start_time {
+            self.start_time = other.start_time;
+        }
+
+        if other.end_time > self.end_time {
+            self.end_time = other.end_time;
+        }
+
+        self.duration += other.duration;
+        self.ct += other.ct;
+        self.read_ct += other.read_ct;
+        self.read_duration += other.read_duration;
+        self.write_ct += other.write_ct;
+        self.write_duration += other.write_duration;
     }
 }
 
+// A call that listed a directory, or opened, created, renamed, or removed an entry in it
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct DirEvent<'a> {
+    time: &'a [u8],
+    duration: f32,
+    access: Access,
+    path: &'a [u8],
+    // 'getdents' is called on the directory itself rather than an entry in it
+    is_dir: bool,
+}
+
 impl<'a> fmt::Display for DirectoryData<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{: >10.3}    {: ^15}    {: ^15}    {: >10}",
+            "{: >10.3}    {: ^15}    {: ^15}    {: >10}    {: >8}    {: >10.3}    {: >8}    {: >10.3}",
             self.duration,
-            self.start_time.to_str_lossy(),
-            self.end_time.to_str_lossy(),
+            output::timestamp(self.start_time),
+            output::timestamp(self.end_time),
             self.ct,
+            self.read_ct,
+            self.read_duration,
+            self.write_ct,
+            self.write_duration,
         )
     }
 }
@@ -63,13 +136,13 @@ impl<'a> Iterator for PathSplit<'a> {
         }
 
         if self.path == b"/" {
-            self.path = &*b"";
+            self.path = b"";
             return Some(b"/");
         }
 
         match self.path.rfind_byte(b'/') {
-            Some(idx) if idx == 0 => {
-                self.path = &*b"";
+            Some(0) => {
+                self.path = b"";
                 Some(b"/")
             }
             Some(idx) => match self.path.get(..idx) {
@@ -88,8 +161,6 @@ pub fn directories_opened<'a>(
     pids: &[Pid],
     raw_data: &HashMap<Pid, PidData<'a>>,
 ) -> HashMap<Pid, BTreeMap<&'a [u8], DirectoryData<'a>>> {
-    let open_events = file_data::files_opened(&pids, raw_data, file_data::SortFilesBy::Time);
-
     pids.par_iter()
         .map(|pid| {
             let mut directory_data = BTreeMap::new();
@@ -104,11 +175,9 @@ pub fn directories_opened<'a>(
             // OPEN_CT + PATH_LEN inserts.
             let mut directory_graph = DiGraphMap::new();
 
-            open_events.get(pid).map(|files| {
-                for event in files {
-                    process_event(event, &mut directory_data, &mut directory_graph);
-                }
-            });
+            for event in dir_events(&raw_data[pid]) {
+                process_event(*pid, &event, &mut directory_data, &mut directory_graph);
+            }
 
             walk_dir_graph(directory_graph, &mut directory_data);
 
@@ -117,36 +186,89 @@ pub fn directories_opened<'a>(
         .collect()
 }
 
+fn dir_events<'a>(pid_data: &PidData<'a>) -> Vec<DirEvent<'a>> {
+    let mut raw_events: Vec<_> = pid_data
+        .open_events
+        .iter()
+        .filter(|e| matches!(e.syscall, b"open" | b"openat"))
+        .chain(pid_data.dir_events.iter())
+        .collect();
+    raw_events.sort_by(|x, y| (x.time).cmp(y.time));
+
+    let mut events = Vec::new();
+    let mut iter = raw_events.into_iter().peekable();
+
+    while let Some(entry) = iter.next() {
+        let duration = match entry.call_status {
+            CallStatus::Complete => entry.duration,
+            // The arguments are on the started line, the duration on the resumed one
+            CallStatus::Started => match iter.peek() {
+                Some(next)
+                    if next.call_status == CallStatus::Resumed && next.syscall == entry.syscall =>
+                {
+                    iter.next().and_then(|next| next.duration)
+                }
+                _ => None,
+            },
+            CallStatus::Resumed => continue,
+        };
+        let duration = duration.map_or(0.0, |d| d * 1000.0);
+
+        let (access, is_dir) = match entry.syscall {
+            b"getdents" | b"getdents64" => (Access::Read, true),
+            b"open" | b"openat" if !entry.opened_for_write() => (Access::Read, false),
+            _ => (Access::Write, false),
+        };
+
+        for path in event_paths(entry) {
+            events.push(DirEvent {
+                time: entry.time,
+                duration,
+                access,
+                path,
+                is_dir,
+            });
+        }
+    }
+
+    events
+}
+
+// A rename changes the directory on both sides, unless it stays within one
+fn event_paths<'a>(entry: &RawData<'a>) -> Vec<&'a [u8]> {
+    match entry.other {
+        Some(OtherFields::Rename { from, to }) => {
+            if PathSplit::new(from).next() == PathSplit::new(to).next() {
+                vec![from]
+            } else {
+                vec![from, to]
+            }
+        }
+        _ => entry.file().into_iter().collect(),
+    }
+}
+
 fn process_event<'a>(
-    event: &FileData<'a>,
+    pid: Pid,
+    event: &DirEvent<'a>,
     directory_data: &mut BTreeMap<&'a [u8], DirectoryData<'a>>,
     directory_graph: &mut DiGraphMap<&'a [u8], u32>,
 ) {
-    let mut splitter = PathSplit::new(event.file);
-    if let Some(parent) = splitter.next() {
-        directory_data
-            .entry(parent)
-            .and_modify(|entry: &mut DirectoryData| {
-                if event.time < entry.start_time {
-                    entry.start_time = event.time;
-                }
+    let mut splitter = PathSplit::new(event.path);
+    let dir = if event.is_dir {
+        Some(event.path)
+    } else {
+        splitter.next()
+    };
 
-                if event.time > entry.end_time {
-                    entry.end_time = event.time;
-                }
+    if let Some(dir) = dir {
+        directory_data
+            .entry(dir)
+            .and_modify(|entry: &mut DirectoryData| entry.add_event(event))
+            .or_insert_with(|| DirectoryData::new(pid, event));
 
-                entry.duration += event.duration;
-                entry.ct += 1;
-            })
-            .or_insert(DirectoryData::new(
-                event.pid,
-                event.time,
-                event.time,
-                event.duration,
-            ));
-
-        let mut prev_path = parent;
-        for path in splitter {
+        let mut prev_path = dir;
+        for path in splitter.filter(|&p| p != dir) {
             // Stop parsing path once we find an existing edge
             if directory_graph.contains_edge(prev_path, path) {
                 break;
@@ -179,8 +301,8 @@ fn walk_dir_graph<'a>(
     for root_node in dir_graph.externals(Incoming) {
         let mut dfs = DfsPostOrder::new(&dir_graph, root_node);
         while let Some(node) = dfs.next(&dir_graph) {
-            let mut parents = dir_graph.neighbors_directed(node, Incoming);
-            while let Some(parent_node) = parents.next() {
+            let parents = dir_graph.neighbors_directed(node, Incoming);
+            for parent_node in parents {
                 process_node(node, parent_node, &dir_graph, dir_data);
             }
         }
@@ -200,18 +322,7 @@ fn process_node<'a>(
     };
 
     data.entry(graph[parent_node])
-        .and_modify(|entry: &mut DirectoryData| {
-            if node_data.start_time < entry.start_time {
-                entry.start_time = node_data.start_time;
-            }
-
-            if node_data.end_time > entry.end_time {
-                entry.end_time = node_data.end_time;
-            }
-
-            entry.duration += node_data.duration;
-            entry.ct += node_data.ct;
-        })
+        .and_modify(|entry: &mut DirectoryData| entry.merge(&node_data))
         .or_insert(node_data);
 }
 
@@ -230,6 +341,15 @@ mod tests {
         assert_eq!(vec![&1070690], dir_data.keys().collect::<Vec<_>>());
     }
 
+    #[test]
+    fn dirs_skips_non_open_fds() {
+        let input = br##"1070690 02:39:58.426334 openat(AT_FDCWD, "/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache> <0.000020>
+1070690 02:39:58.426434 dup2(3</etc/ld.so.cache>, 4) = 4</etc/ld.so.cache> <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let dir_data = directories_opened(&[1070690], &pid_data_map);
+        assert_eq!(dir_data[&1070690][B("/etc")].ct, 1);
+    }
+
     #[test]
     fn dirs_captures_path_ancestors() {
         let input = br##"1070690 02:39:58.426334 openat(AT_FDCWD, "/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache> <0.000020>"##;
@@ -307,13 +427,54 @@ mod tests {
         );
     }
 
+    #[test]
+    fn dirs_splits_reads_and_writes() {
+        let input = br##"2201  10:15:01.000000 openat(AT_FDCWD, "/var/spool/in/job.1", O_RDONLY) = 3</var/spool/in/job.1> <0.000010>
+2201  10:15:01.000100 getdents64(4</var/spool/in>, 0x55d0c0a0e2d0 /* 3 entries */, 32768) = 80 <0.000030>
+2201  10:15:01.000200 openat(AT_FDCWD, "/var/spool/in/job.2", O_WRONLY|O_CREAT|O_TRUNC, 0644) = 5</var/spool/in/job.2> <0.000050>
+2201  10:15:01.000300 unlink("/var/spool/in/job.1") = 0 <0.000020>
+2201  10:15:01.000400 mkdir("/var/spool/in/tmp", 0755) = 0 <0.000040>"##;
+        let pid_data_map = build_syscall_data(input);
+        let dir_data = directories_opened(&[2201], &pid_data_map);
+        let spool_in = &dir_data[&2201][B("/var/spool/in")];
+
+        assert_eq!(spool_in.ct, 5);
+        assert_eq!(spool_in.read_ct, 2);
+        assert_eq!(spool_in.write_ct, 3);
+        assert_ulps_eq!(spool_in.read_duration, 0.04);
+        assert_ulps_eq!(spool_in.write_duration, 0.11);
+        assert_eq!(dir_data[&2201][B("/var")].write_ct, 3);
+    }
+
+    #[test]
+    fn dirs_rename_writes_both_directories() {
+        let input = br##"2201  10:15:01.000000 rename("/var/spool/in/job.1", "/var/spool/done/job.1") = 0 <0.000020>
+2201  10:15:01.000100 rename("/var/spool/in/job.2", "/var/spool/in/job.2.tmp") = 0 <0.000020>"##;
+        let pid_data_map = build_syscall_data(input);
+        let dir_data = directories_opened(&[2201], &pid_data_map);
+
+        assert_eq!(dir_data[&2201][B("/var/spool/in")].write_ct, 2);
+        assert_eq!(dir_data[&2201][B("/var/spool/done")].write_ct, 1);
+        assert_eq!(dir_data[&2201][B("/var/spool/in")].read_ct, 0);
+    }
+
+    #[test]
+    fn dirs_resumed_call_sets_duration() {
+        let input = br##"2201  10:15:01.000000 unlink("/tmp/lock" <unfinished ...>
+2201  10:15:01.000300 <... unlink resumed>) = 0 <0.000300>"##;
+        let pid_data_map = build_syscall_data(input);
+        let dir_data = directories_opened(&[2201], &pid_data_map);
+
+        assert_eq!(dir_data[&2201][B("/tmp")].ct, 1);
+        assert_ulps_eq!(dir_data[&2201][B("/tmp")].write_duration, 0.3);
+    }
+
     #[test]
     fn dirs_path_split_handles_absolute_path() {
         let path = b"/usr/lib/locale/locale-archive".as_ref();
         assert_eq!(
             vec![B("/usr/lib/locale"), B("/usr/lib"), B("/usr"), B("/"),],
             PathSplit::new(path)
-                .into_iter()
                 .map(|p| p.as_bstr())
                 .collect::<Vec<_>>(),
         );
@@ -325,7 +486,6 @@ mod tests {
         assert_eq!(
             vec![b"target/release".as_bstr(), b"target".as_bstr(),],
             PathSplit::new(path)
-                .into_iter()
                 .map(|p| p.as_bstr())
                 .collect::<Vec<_>>(),
         );
@@ -343,7 +503,6 @@ mod tests {
                 B(".."),
             ],
             PathSplit::new(path)
-                .into_iter()
                 .map(|p| p.as_bstr())
                 .collect::<Vec<_>>(),
         );
@@ -355,7 +514,6 @@ mod tests {
         assert_eq!(
             vec![B("./target/release"), B("./target"), B("."),],
             PathSplit::new(path)
-                .into_iter()
                 .map(|p| p.as_bstr())
                 .collect::<Vec<_>>(),
         );
@@ -0,0 +1,320 @@
+use crate::output;
+use crate::parser::{self, CallStatus, LineData};
+use crate::table::{Align, Column, Table};
+use crate::time::parse_timestamp_us;
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::io::Error;
+
+// Mixes differing by at least this fraction of their calls are flagged
+const CHANGED_MIX: f64 = 0.5;
+// Syscalls listed as gaining or losing the most share of a PID's calls
+const TOP_SHIFTS: usize = 3;
+
+/// A PID's calls in one window of the trace
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WindowCalls<'a> {
+    pub calls: usize,
+    pub errors: usize,
+    /// Time in calls started in the window, in ms
+    pub time: f32,
+    pub syscalls: BTreeMap<&'a [u8], usize>,
+    pub errnos: BTreeMap<&'a [u8], usize>,
+}
+
+impl<'a> WindowCalls<'a> {
+    pub fn error_rate(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.calls as f64
+        }
+    }
+
+    fn share(&self, syscall: &[u8]) -> f64 {
+        match self.syscalls.get(syscall) {
+            Some(&ct) => ct as f64 / self.calls as f64,
+            None => 0.0,
+        }
+    }
+}
+
+/// A PID's calls at the start and the end of the trace
+#[derive(Clone, Debug, PartialEq)]
+pub struct PidDrift<'a> {
+    pub pid: Pid,
+    pub start: WindowCalls<'a>,
+    pub end: WindowCalls<'a>,
+}
+
+impl<'a> PidDrift<'a> {
+    /// The fraction of calls that would have to change syscall for the two
+    /// windows to have the same mix, from 0 when they match to 1 when they
+    /// share no syscalls
+    pub fn mix_change(&self) -> f64 {
+        let syscalls: HashSet<_> = self
+            .start
+            .syscalls
+            .keys()
+            .chain(self.end.syscalls.keys())
+            .collect();
+
+        syscalls
+            .into_iter()
+            .map(|s| (self.end.share(s) - self.start.share(s)).abs())
+            .sum::<f64>()
+            / 2.0
+    }
+
+    /// The syscalls whose share of calls changed the most, largest first,
+    /// with the change in percentage points
+    pub fn shifts(&self) -> Vec<(&'a [u8], f64)> {
+        let syscalls: HashSet<&[u8]> = self
+            .start
+            .syscalls
+            .keys()
+            .chain(self.end.syscalls.keys())
+            .copied()
+            .collect();
+
+        let mut shifts: Vec<_> = syscalls
+            .into_iter()
+            .map(|s| (s, (self.end.share(s) - self.start.share(s)) * 100.0))
+            .filter(|(_, delta)| delta.abs() >= 1.0)
+            .collect();
+        shifts.sort_by(|(x_name, x), (y_name, y)| {
+            y.abs().total_cmp(&x.abs()).then_with(|| x_name.cmp(y_name))
+        });
+        shifts.truncate(TOP_SHIFTS);
+
+        shifts
+    }
+
+    /// Errnos returned at the end of the trace but not at the start
+    pub fn new_errnos(&self) -> Vec<&'a [u8]> {
+        self.end
+            .errnos
+            .keys()
+            .filter(|e| !self.start.errnos.contains_key(*e))
+            .copied()
+            .collect()
+    }
+
+    fn is_changed(&self) -> bool {
+        self.mix_change() >= CHANGED_MIX || !self.new_errnos().is_empty()
+    }
+}
+
+/// The calls `pids` made in the first and last `pct` percent of the trace's
+/// time, with the PIDs whose mix of syscalls changed the most first. Also
+/// returns the PIDs only active in the first window, then those only active
+/// in the last. Calls are placed by the time they started.
+pub fn drift<'a>(
+    buffer: &'a [u8],
+    pids: &[Pid],
+    pct: f64,
+) -> (Vec<PidDrift<'a>>, Vec<Pid>, Vec<Pid>) {
+    let pids: HashSet<Pid> = pids.iter().copied().collect();
+
+    let calls: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(|line| match parser::parse_line(line)? {
+            LineData::Syscall(raw_data) if pids.contains(&raw_data.pid) => {
+                let time = parse_timestamp_us(raw_data.time)?;
+                Some((time, raw_data))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let first = calls.par_iter().map(|(t, _)| *t).min();
+    let last = calls.par_iter().map(|(t, _)| *t).max();
+    let (first, last) = match (first, last) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return (Vec::new(), Vec::new(), Vec::new()),
+    };
+    let window = ((last - first) as f64 * pct / 100.0) as i64;
+
+    let mut windows: HashMap<Pid, (WindowCalls, WindowCalls)> = HashMap::default();
+    // Whether each PID's unfinished call started in the first and last windows
+    let mut started: HashMap<Pid, (bool, bool)> = HashMap::default();
+
+    let mut calls = calls;
+    calls.par_sort_by_key(|(t, _)| *t);
+    for (time, raw_data) in calls {
+        let in_windows = (time <= first + window, time >= last - window);
+        let (begins, (in_start, in_end)) = match raw_data.call_status {
+            CallStatus::Started => {
+                started.insert(raw_data.pid, in_windows);
+                (true, in_windows)
+            }
+            CallStatus::Complete => (true, in_windows),
+            // The call's duration and result count towards the windows it
+            // started in
+            CallStatus::Resumed => (false, started.remove(&raw_data.pid).unwrap_or_default()),
+        };
+
+        let (start, end) = windows.entry(raw_data.pid).or_default();
+        for (counted, calls) in [(in_start, start), (in_end, end)] {
+            if !counted {
+                continue;
+            }
+            if begins {
+                calls.calls += 1;
+                *calls.syscalls.entry(raw_data.syscall).or_default() += 1;
+            }
+            if raw_data.call_status != CallStatus::Started {
+                calls.time += raw_data.duration.unwrap_or_default() * 1000.0;
+                if let Some(errno) = raw_data.error {
+                    calls.errors += 1;
+                    *calls.errnos.entry(errno).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let mut only_start = Vec::new();
+    let mut only_end = Vec::new();
+    let mut drifts = Vec::new();
+    for (pid, (start, end)) in windows {
+        match (start.calls, end.calls) {
+            (0, 0) => {}
+            (_, 0) => only_start.push(pid),
+            (0, _) => only_end.push(pid),
+            _ => drifts.push(PidDrift { pid, start, end }),
+        }
+    }
+    only_start.sort_unstable();
+    only_end.sort_unstable();
+
+    drifts.sort_by(|x, y| {
+        y.mix_change()
+            .total_cmp(&x.mix_change())
+            .then_with(|| y.end.error_rate().total_cmp(&x.end.error_rate()))
+            .then_with(|| x.pid.cmp(&y.pid))
+    });
+
+    (drifts, only_start, only_end)
+}
+
+pub fn print_drift(
+    buffer: &[u8],
+    pids: &[Pid],
+    pct: f64,
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let (drifts, only_start, only_end) = drift(buffer, pids, pct);
+
+    output::print_heading(&format!(
+        "\nSyscall Mix in the First and Last {}% of the Trace\n-----------\n",
+        pct
+    ))?;
+
+    if drifts.is_empty() && only_start.is_empty() && only_end.is_empty() {
+        output::print_note("  No timestamped syscalls found\n")?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("pid", 7, Align::Left, 0),
+        Column::new("calls", 13, Align::Right, 1),
+        Column::new("errors", 13, Align::Right, 2),
+        Column::new("mix change", 10, Align::Right, 0),
+        Column::new("flag", 7, Align::Left, 3),
+        Column::new("largest shifts", 14, Align::Left, 4),
+    ]);
+
+    for drift in drifts.iter().take(count) {
+        let new_errnos = drift.new_errnos();
+        let flag = if drift.is_changed() { "changed" } else { "" };
+        let mut shifts: Vec<_> = drift
+            .shifts()
+            .into_iter()
+            .map(|(name, delta)| format!("{:+.0}% {}", delta, name.to_str_lossy()))
+            .collect();
+        if !new_errnos.is_empty() {
+            let errnos: Vec<_> = new_errnos.iter().map(|e| e.to_str_lossy()).collect();
+            shifts.push(format!("new {}", errnos.join("/")));
+        }
+
+        table.add_row(vec![
+            output::pid_label(drift.pid),
+            format!("{} -> {}", drift.start.calls, drift.end.calls),
+            format!(
+                "{:.0}% -> {:.0}%",
+                drift.start.error_rate() * 100.0,
+                drift.end.error_rate() * 100.0
+            ),
+            format!("{:.0}%", drift.mix_change() * 100.0),
+            flag.to_string(),
+            shifts.join(", "),
+        ]);
+    }
+    table.print(width)?;
+
+    if !only_start.is_empty() {
+        output::print_note(&format!(
+            "\n  Only active at the start: {}",
+            pid_list(&only_start)
+        ))?;
+    }
+    if !only_end.is_empty() {
+        output::print_note(&format!(
+            "\n  Only active at the end: {}",
+            pid_list(&only_end)
+        ))?;
+    }
+    output::print_note(&format!(
+        "\n  'changed' PIDs moved {:.0}% or more of their calls to other syscalls, or returned new errors\n",
+        CHANGED_MIX * 100.0
+    ))
+}
+
+fn pid_list(pids: &[Pid]) -> String {
+    pids.iter()
+        .map(|pid| output::pid_label(*pid))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drift_compares_first_and_last_windows() {
+        let input = br##"477   10:00:00.000000 sendto(3<TCP:[61613]>, "a", 1, 0, NULL, 0) = 1 <0.000010>
+477   10:00:01.000000 sendto(3<TCP:[61613]>, "a", 1, 0, NULL, 0) = 1 <0.000010>
+495   10:00:01.000000 read(0</dev/null>, "", 10) = 0 <0.000010>
+477   10:00:05.000000 getpid() = 477 <0.000010>
+477   10:00:09.000000 openat(AT_FDCWD, "/data/a", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000010>
+495   10:00:09.000000 read(0</dev/null>,  <unfinished ...>
+477   10:00:09.500000 read(4</data/b>, "a", 1) = 1 <0.000010>
+495   10:00:09.600000 <... read resumed>"", 10) = 0 <0.600000>
+496   10:00:10.000000 getpid() = 496 <0.000010>"##;
+        let (drifts, only_start, only_end) = drift(input, &[477, 495, 496], 20.0);
+
+        assert_eq!(only_start, Vec::<Pid>::new());
+        assert_eq!(only_end, vec![496]);
+        assert_eq!(drifts.len(), 2);
+
+        assert_eq!(drifts[0].pid, 477);
+        assert_eq!(drifts[0].start.calls, 2);
+        assert_eq!(drifts[0].end.calls, 2);
+        assert!((drifts[0].mix_change() - 1.0).abs() < 1e-9);
+        assert_eq!(drifts[0].end.errors, 1);
+        assert_eq!(drifts[0].new_errnos(), vec![b"ENOENT".as_ref()]);
+        assert_eq!(drifts[0].shifts()[0], (b"sendto".as_ref(), -100.0));
+        assert!(drifts[0].is_changed());
+
+        assert_eq!(drifts[1].pid, 495);
+        assert_eq!(drifts[1].end.calls, 1);
+        assert!((drifts[1].end.time - 600.0).abs() < 1e-3);
+        assert_eq!(drifts[1].mix_change(), 0.0);
+        assert!(!drifts[1].is_changed());
+    }
+}
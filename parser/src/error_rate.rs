@@ -0,0 +1,353 @@
+use crate::concurrency::sparkline;
+use crate::json::{self, rounded};
+use crate::output;
+use crate::parser::{self, CallStatus, LineData};
+use crate::table::{Align, Column, Table};
+use crate::time::{format_timestamp_us, parse_timestamp_us};
+use crate::Pid;
+
+use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::io::{prelude::*, stdout, Error};
+
+// Longest sparkline printed, longer series are merged to fit
+const SPARK_WIDTH: usize = 60;
+
+// Rates are drawn in hundredths of a percent, sparklines take whole numbers
+const RATE_SCALE: f64 = 10_000.0;
+
+/// Calls started and calls that failed in each interval, by one PID or by
+/// every PID in the trace
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RateSeries {
+    /// `None` for the series of every PID
+    pub pid: Option<Pid>,
+    pub calls: Vec<usize>,
+    pub errors: Vec<usize>,
+}
+
+impl RateSeries {
+    fn new(pid: Option<Pid>, interval_ct: usize) -> RateSeries {
+        RateSeries {
+            pid,
+            calls: vec![0; interval_ct],
+            errors: vec![0; interval_ct],
+        }
+    }
+
+    /// The share of each interval's calls that failed, `None` for intervals
+    /// without calls
+    pub fn rates(&self) -> Vec<Option<f64>> {
+        self.calls
+            .iter()
+            .zip(&self.errors)
+            .map(|(&calls, &errors)| Some(errors as f64 / calls as f64).filter(|r| r.is_finite()))
+            .collect()
+    }
+
+    pub fn total_errors(&self) -> usize {
+        self.errors.iter().sum()
+    }
+
+    /// The interval with the highest error rate and its rate
+    pub fn peak(&self) -> Option<(usize, f64)> {
+        self.rates()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(idx, rate)| Some((idx, rate?)))
+            .fold(None, |peak, (idx, rate)| match peak {
+                Some((_, max)) if max >= rate => peak,
+                _ => Some((idx, rate)),
+            })
+    }
+
+    fn sparkline(&self, width: usize) -> String {
+        let scaled: Vec<_> = self
+            .rates()
+            .iter()
+            .map(|rate| (rate.unwrap_or_default() * RATE_SCALE).round() as usize)
+            .collect();
+        sparkline(&scaled, width)
+    }
+}
+
+/// Error rates over the trace, for every PID together and for chosen PIDs,
+/// each split into the same intervals
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ErrorRate<'a> {
+    pub start_us: i64,
+    pub interval_us: i64,
+    pub overall: RateSeries,
+    pub pids: Vec<RateSeries>,
+    /// A timestamp from the trace to format interval starts like
+    pub time_layout: Option<&'a [u8]>,
+}
+
+impl<'a> ErrorRate<'a> {
+    fn series(&self) -> impl Iterator<Item = &RateSeries> {
+        std::iter::once(&self.overall).chain(&self.pids)
+    }
+
+    fn interval_start(&self, idx: usize) -> String {
+        match self.time_layout {
+            Some(layout) => output::timestamp(
+                format_timestamp_us(self.start_us + idx as i64 * self.interval_us, layout)
+                    .as_bytes(),
+            ),
+            None => String::new(),
+        }
+    }
+}
+
+/// Splits the trace into intervals of `interval_us` and counts the syscalls
+/// started and failed in each, for every PID together and for each of `pids`.
+/// Calls split across lines are counted once, in the interval they started in.
+pub fn error_rate<'a>(buffer: &'a [u8], pids: &[Pid], interval_us: i64) -> ErrorRate<'a> {
+    let calls: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(parser::parse_line)
+        .filter_map(|line| match line {
+            LineData::Syscall(raw_data) => Some(raw_data),
+            _ => None,
+        })
+        .filter_map(|raw_data| {
+            let time = parse_timestamp_us(raw_data.time)?;
+            let duration = raw_data
+                .duration
+                .map_or(0, |d| (d as f64 * 1_000_000.0) as i64);
+            let start = match raw_data.call_status {
+                CallStatus::Complete => time,
+                CallStatus::Resumed => time - duration,
+                // The resumed line has the result
+                CallStatus::Started => return None,
+            };
+
+            Some((start, raw_data.pid, raw_data.error.is_some(), raw_data.time))
+        })
+        .collect();
+
+    let (first, last, time_layout) = match (
+        calls.iter().min_by_key(|(start, ..)| *start),
+        calls.iter().map(|(start, ..)| *start).max(),
+    ) {
+        (Some(&(first, _, _, time)), Some(last)) => (first, last, time),
+        _ => return ErrorRate::default(),
+    };
+    let interval_ct = ((last - first) / interval_us + 1) as usize;
+
+    let mut overall = RateSeries::new(None, interval_ct);
+    let mut by_pid: Vec<_> = pids
+        .iter()
+        .map(|&pid| RateSeries::new(Some(pid), interval_ct))
+        .collect();
+    for &(start, pid, failed, _) in &calls {
+        let idx = ((start - first) / interval_us) as usize;
+        let selected = by_pid.iter_mut().find(|series| series.pid == Some(pid));
+        for series in std::iter::once(&mut overall).chain(selected) {
+            series.calls[idx] += 1;
+            series.errors[idx] += usize::from(failed);
+        }
+    }
+
+    ErrorRate {
+        start_us: first,
+        interval_us,
+        overall,
+        pids: by_pid,
+        time_layout: Some(time_layout),
+    }
+}
+
+fn series_label(series: &RateSeries) -> String {
+    series
+        .pid
+        .map_or_else(|| String::from("all PIDs"), output::pid_label)
+}
+
+fn format_rate(rate: Option<f64>) -> String {
+    rate.map_or_else(|| String::from("-"), |r| format!("{:.2}%", r * 100.0))
+}
+
+pub fn print_error_rate(error_rate: &ErrorRate, width: Option<usize>) -> Result<(), Error> {
+    output::print_heading("\nError Rate\n-----------\n")?;
+
+    let interval_ct = error_rate.overall.calls.len();
+    if error_rate.time_layout.is_none() || interval_ct == 0 {
+        output::print_note("  No timestamped syscalls found, '-tt' must be passed to strace\n")?;
+        return Ok(());
+    }
+
+    let labels: Vec<_> = error_rate.series().map(series_label).collect();
+    let label_width = labels.iter().map(|l| l.len()).max().unwrap_or_default();
+    if output::headers() {
+        writeln!(
+            stdout(),
+            "  {} intervals of {:.3}s starting at {}\n",
+            interval_ct,
+            error_rate.interval_us as f64 / 1_000_000.0,
+            error_rate.interval_start(0)
+        )?;
+        for (series, label) in error_rate.series().zip(&labels) {
+            writeln!(
+                stdout(),
+                "  {: <3$}  peak {: >7}  {}",
+                label,
+                format_rate(series.peak().map(|(_, rate)| rate)),
+                series.sparkline(SPARK_WIDTH),
+                label_width
+            )?;
+        }
+        writeln!(stdout())?;
+    }
+
+    let mut columns = vec![
+        Column::new("interval start", 15, Align::Left, 0),
+        Column::new("calls", 8, Align::Right, 2),
+        Column::new("errors", 8, Align::Right, 1),
+        Column::new("error %", 8, Align::Right, 0),
+    ];
+    for (idx, label) in labels.iter().enumerate().skip(1) {
+        columns.push(Column::new(
+            format!("{} %", label),
+            8,
+            Align::Right,
+            idx + 2,
+        ));
+    }
+    let mut table = Table::new(columns);
+
+    let rates: Vec<_> = error_rate.series().map(RateSeries::rates).collect();
+    for idx in 0..interval_ct {
+        let overall = &error_rate.overall;
+        let mut row = vec![
+            error_rate.interval_start(idx),
+            overall.calls[idx].to_string(),
+            overall.errors[idx].to_string(),
+        ];
+        row.extend(rates.iter().map(|r| format_rate(r[idx])));
+        table.add_row(row);
+    }
+
+    table.print(width)
+}
+
+pub fn print_error_rate_json(error_rate: &ErrorRate) -> Result<(), Error> {
+    let series = error_rate
+        .series()
+        .map(|series| RateSeriesJson {
+            pid: series.pid,
+            calls: &series.calls,
+            errors: &series.errors,
+            error_rate: series
+                .rates()
+                .into_iter()
+                .map(|rate| rate.map(|r| rounded(r, 4)))
+                .collect(),
+        })
+        .collect();
+
+    json::write_report(
+        &mut stdout(),
+        &ErrorRateReport {
+            start: error_rate.time_layout.map(|_| error_rate.interval_start(0)),
+            interval_ms: error_rate.interval_us as f64 / 1000.0,
+            series,
+        },
+    )
+}
+
+/// One row per interval and series, the series of every PID has no PID
+pub fn print_error_rate_csv(error_rate: &ErrorRate) -> Result<(), Error> {
+    let mut out = stdout();
+    writeln!(out, "start,pid,calls,errors,error_rate")?;
+    for idx in 0..error_rate.overall.calls.len() {
+        let start = error_rate.interval_start(idx);
+        for series in error_rate.series() {
+            writeln!(
+                out,
+                "{},{},{},{},{}",
+                start,
+                series.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+                series.calls[idx],
+                series.errors[idx],
+                series.rates()[idx].map_or_else(String::new, |r| format!("{:.4}", r))
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The error rate of every PID over the trace as one sparkline, printed
+/// after the summary when any calls failed
+pub fn print_error_trend(buffer: &[u8], interval_us: i64) -> Result<(), Error> {
+    let error_rate = error_rate(buffer, &[], interval_us);
+    let overall = &error_rate.overall;
+    let (peak_idx, peak_rate) = match overall.peak() {
+        Some(peak) if overall.total_errors() > 0 => peak,
+        _ => return Ok(()),
+    };
+
+    output::print_heading("\nError Rate Over Time\n-----------\n")?;
+    writeln!(
+        stdout(),
+        "  {}  peak {} at {}",
+        overall.sparkline(SPARK_WIDTH),
+        format_rate(Some(peak_rate)),
+        error_rate.interval_start(peak_idx)
+    )?;
+    output::print_note(&format!(
+        "\n  {} intervals of {:.3}s, 'error-rate' breaks the rate down by PID\n",
+        overall.calls.len(),
+        interval_us as f64 / 1_000_000.0
+    ))
+}
+
+// A series of 'error-rate' as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct RateSeriesJson<'a> {
+    pid: Option<Pid>,
+    calls: &'a [usize],
+    errors: &'a [usize],
+    error_rate: Vec<Option<f64>>,
+}
+
+// 'error-rate' as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct ErrorRateReport<'a> {
+    start: Option<String>,
+    interval_ms: f64,
+    series: Vec<RateSeriesJson<'a>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_rate_counts_failed_calls_per_interval() {
+        let input = br##"477   00:09:56.000000 stat("/a", 0x7ffd4c3b8d70) = 0 <0.000100>
+495   00:09:56.200000 stat("/b", 0x7ffd4c3b8d70) = 0 <0.000100>
+477   00:09:57.100000 stat("/c", 0x7ffd4c3b8d70) = -1 ENOENT (No such file or directory) <0.000100>
+495   00:09:57.300000 read(0</dev/null>,  <unfinished ...>
+495   00:09:58.500000 <... read resumed>0x7ffd4c3b8d70, 10) = -1 EAGAIN (Resource temporarily unavailable) <1.200000>
+477   00:09:58.600000 stat("/d", 0x7ffd4c3b8d70) = -1 ENOENT (No such file or directory) <0.000100>"##;
+        let error_rate = error_rate(input, &[495], 1_000_000);
+
+        assert_eq!(error_rate.time_layout, Some(b"00:09:56.000000".as_ref()));
+        assert_eq!(error_rate.overall.calls, vec![2, 2, 1]);
+        assert_eq!(error_rate.overall.errors, vec![0, 2, 1]);
+        assert_eq!(
+            error_rate.overall.rates(),
+            vec![Some(0.0), Some(1.0), Some(1.0)]
+        );
+        assert_eq!(error_rate.overall.peak(), Some((1, 1.0)));
+
+        let pid = &error_rate.pids[0];
+        assert_eq!(pid.pid, Some(495));
+        assert_eq!(pid.calls, vec![1, 1, 0]);
+        assert_eq!(pid.rates(), vec![Some(0.0), Some(1.0), None]);
+        assert_eq!(error_rate.overall.sparkline(10), "\u{2581}\u{2588}\u{2588}");
+    }
+}
@@ -0,0 +1,348 @@
+use crate::output;
+use crate::parser::{CallStatus, RawData};
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use std::collections::BTreeSet;
+use std::io::Error;
+
+const WHITESPACE: &[u8] = b" \t";
+
+/// Failed calls sharing a syscall, path, and errno
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorPattern<'a> {
+    pub syscall: &'a [u8],
+    /// The path or fd the call acted on, not known for fds without '-y'
+    pub path: Option<&'a [u8]>,
+    pub errno: &'a [u8],
+    pub count: usize,
+    pub pids: BTreeSet<Pid>,
+}
+
+// Syscall, path, and errno
+type PatternKey<'a> = (&'a [u8], Option<&'a [u8]>, &'a [u8]);
+
+// A failed call in a PID's 'error_events', with the line it started on if
+// it was split across lines
+struct FailedCall<'a> {
+    idx: usize,
+    start_idx: Option<usize>,
+    syscall: &'a [u8],
+    path: Option<&'a [u8]>,
+    errno: &'a [u8],
+    duration: Option<f32>,
+}
+
+// `events` must be sorted by time
+fn failed_calls<'a>(events: &[RawData<'a>]) -> Vec<FailedCall<'a>> {
+    let mut calls = Vec::new();
+    let mut pending: Option<usize> = None;
+
+    for (idx, event) in events.iter().enumerate() {
+        let start = match event.call_status {
+            CallStatus::Started => {
+                pending = Some(idx);
+                continue;
+            }
+            CallStatus::Resumed => pending
+                .take()
+                .filter(|&start| events[start].syscall == event.syscall),
+            CallStatus::Complete => None,
+        };
+
+        if let Some(errno) = event.error {
+            calls.push(FailedCall {
+                idx,
+                start_idx: start,
+                syscall: event.syscall,
+                path: event
+                    .path()
+                    .or_else(|| start.and_then(|s| events[s].path())),
+                errno,
+                duration: event.duration,
+            });
+        }
+    }
+
+    calls
+}
+
+/// Failed calls made by `pids`, grouped by syscall, path, and errno with the
+/// most frequent first
+pub fn error_patterns<'a>(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData<'a>>,
+) -> Vec<ErrorPattern<'a>> {
+    let mut patterns: HashMap<PatternKey, ErrorPattern> = HashMap::default();
+
+    for pid in pids {
+        let mut events = raw_data[pid].error_events.clone();
+        events.sort_by(|x, y| (x.time).cmp(y.time));
+
+        for call in failed_calls(&events) {
+            let pattern = patterns
+                .entry((call.syscall, call.path, call.errno))
+                .or_insert_with(|| ErrorPattern {
+                    syscall: call.syscall,
+                    path: call.path,
+                    errno: call.errno,
+                    count: 0,
+                    pids: BTreeSet::new(),
+                });
+            pattern.count += 1;
+            pattern.pids.insert(*pid);
+        }
+    }
+
+    let mut patterns: Vec<_> = patterns.into_values().collect();
+    patterns.sort_by(|x, y| {
+        (y.count)
+            .cmp(&x.count)
+            .then_with(|| (x.syscall).cmp(y.syscall))
+            .then_with(|| (x.path).cmp(&y.path))
+            .then_with(|| (x.errno).cmp(y.errno))
+    });
+
+    patterns
+}
+
+/// Errors to leave out of every report, such as a library probing for files
+/// that are expected to be missing
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IgnoredErrors {
+    patterns: Vec<(Vec<u8>, Vec<u8>, Vec<u8>)>,
+}
+
+impl IgnoredErrors {
+    /// Parses patterns of the form 'SYSCALL PATH ERRNO', one per line, as
+    /// printed by the 'errors' report. Any field may be '*' to match
+    /// anything, and a path ending in '*' matches paths starting with the
+    /// rest. Blank lines and anything after a '#' are ignored. Paths are
+    /// compared as bytes, so may be in any encoding.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(text: &T) -> Result<IgnoredErrors, String> {
+        let mut patterns = Vec::new();
+
+        for (idx, line) in text.as_ref().lines().enumerate() {
+            let line = match line.find_byte(b'#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            }
+            .trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            // The path is whatever is between the syscall and errno, so it may hold spaces
+            let fields = line.find_byteset(WHITESPACE).and_then(|end| {
+                let (syscall, rest) = (&line[..end], line[end..].trim());
+                let start = rest.rfind_byteset(WHITESPACE)?;
+                Some((syscall, rest[..start].trim(), &rest[start + 1..]))
+            });
+
+            match fields {
+                Some((syscall, path, errno)) => {
+                    patterns.push((syscall.to_vec(), path.to_vec(), errno.to_vec()))
+                }
+                None => {
+                    return Err(format!(
+                        "Invalid error pattern '{}' on line {}",
+                        line.to_str_lossy(),
+                        idx + 1
+                    ))
+                }
+            }
+        }
+
+        Ok(IgnoredErrors { patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    fn matches(&self, syscall: &[u8], path: Option<&[u8]>, errno: &[u8]) -> bool {
+        let field = |pattern: &[u8], value: &[u8]| pattern == b"*" || pattern == value;
+        let path = path.unwrap_or(b"-");
+
+        self.patterns.iter().any(|(p_syscall, p_path, p_errno)| {
+            field(p_syscall, syscall)
+                && field(p_errno, errno)
+                && match p_path.strip_suffix(b"*") {
+                    Some(prefix) => path.starts_with(prefix),
+                    None => p_path == path,
+                }
+        })
+    }
+}
+
+/// Removes the failed calls matching `ignored` from the error counts of each
+/// PID, so that other reports treat them as successful. Returns the number of
+/// calls removed.
+pub fn remove_ignored_errors(
+    raw_data: &mut HashMap<Pid, PidData>,
+    ignored: &IgnoredErrors,
+) -> usize {
+    if ignored.is_empty() {
+        return 0;
+    }
+
+    let mut removed = 0;
+    for pid_data in raw_data.values_mut() {
+        pid_data.error_events.sort_by(|x, y| (x.time).cmp(y.time));
+
+        let mut dropped = HashSet::default();
+        for call in failed_calls(&pid_data.error_events) {
+            if !ignored.matches(call.syscall, call.path, call.errno) {
+                continue;
+            }
+
+            if let Some(syscall_data) = pid_data.syscall_data.get_mut(call.syscall) {
+                if let Some(count) = syscall_data.errors.get_mut(call.errno) {
+                    *count -= 1;
+                    if *count <= 0 {
+                        syscall_data.errors.remove(call.errno);
+                    }
+                }
+                if let (Some(lengths), Some(duration)) = (
+                    syscall_data.error_lengths.get_mut(call.errno),
+                    call.duration,
+                ) {
+                    if let Some(pos) = lengths.iter().position(|l| *l == duration) {
+                        lengths.swap_remove(pos);
+                    }
+                    if lengths.is_empty() {
+                        syscall_data.error_lengths.remove(call.errno);
+                    }
+                }
+            }
+
+            dropped.insert(call.idx);
+            dropped.extend(call.start_idx);
+            removed += 1;
+        }
+
+        if !dropped.is_empty() {
+            let mut idx = 0;
+            pid_data.error_events.retain(|_| {
+                idx += 1;
+                !dropped.contains(&(idx - 1))
+            });
+        }
+    }
+
+    removed
+}
+
+pub fn print_error_patterns(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let patterns = error_patterns(pids, raw_data);
+
+    output::print_heading("\nTop Errors by Syscall, Path, and Errno\n-----------\n")?;
+
+    if patterns.is_empty() {
+        output::print_note("  No failed calls found\n")?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("count", 8, Align::Right, 0),
+        Column::new("pids", 5, Align::Right, 2),
+        Column::new("syscall", 12, Align::Left, 0),
+        Column::new("errno", 15, Align::Left, 1),
+        Column::new("path", 4, Align::Left, 0),
+    ]);
+
+    for pattern in patterns.iter().take(count) {
+        table.add_row(vec![
+            pattern.count.to_string(),
+            pattern.pids.len().to_string(),
+            pattern.syscall.to_str_lossy().to_string(),
+            pattern.errno.to_str_lossy().to_string(),
+            pattern.path.unwrap_or(b"-").to_str_lossy().to_string(),
+        ]);
+    }
+    table.print(width)?;
+
+    output::print_note(
+        "\n  To leave a pattern out of every report, add a 'SYSCALL PATH ERRNO' line for it to a \
+         file passed with '--ignore-errors'\n",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    static INPUT: &[u8] = br##"567   00:09:47.836504 access("/etc/ld.so.nohwcap", F_OK) = -1 ENOENT (No such file or directory) <0.000020>
+567   00:09:47.836704 openat(AT_FDCWD, "/etc/ld.so.nohwcap", O_RDONLY <unfinished ...>
+567   00:09:47.836904 <... openat resumed>) = -1 ENOENT (No such file or directory) <0.000030>
+568   00:09:47.837004 access("/etc/ld.so.nohwcap", F_OK) = -1 ENOENT (No such file or directory) <0.000020>
+568   00:09:47.837104 stat("/opt/app/config.yml", 0x7ffd4c3b8d70) = -1 EACCES (Permission denied) <0.000011>
+568   00:09:47.837204 openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = 3</etc/hosts> <0.000020>"##;
+
+    #[test]
+    fn errors_grouped_by_syscall_path_and_errno() {
+        let pid_data_map = build_syscall_data(INPUT);
+        let patterns = error_patterns(&[567, 568], &pid_data_map);
+
+        assert_eq!(patterns.len(), 3);
+        assert_eq!(patterns[0].syscall, b"access");
+        assert_eq!(patterns[0].path, Some(b"/etc/ld.so.nohwcap".as_ref()));
+        assert_eq!(patterns[0].count, 2);
+        assert_eq!(patterns[0].pids.len(), 2);
+        assert_eq!(patterns[1].syscall, b"openat");
+        assert_eq!(patterns[1].path, Some(b"/etc/ld.so.nohwcap".as_ref()));
+        assert_eq!(patterns[2].errno, b"EACCES");
+    }
+
+    #[test]
+    fn errors_ignored_patterns_removed() {
+        let mut pid_data_map = build_syscall_data(INPUT);
+        let ignored = IgnoredErrors::parse("# ld.so probes\n* /etc/ld.so.* ENOENT\n").unwrap();
+
+        assert_eq!(remove_ignored_errors(&mut pid_data_map, &ignored), 3);
+        assert!(pid_data_map[&567].syscall_data[b"access".as_ref()]
+            .errors
+            .is_empty());
+        assert!(pid_data_map[&567].syscall_data[b"openat".as_ref()]
+            .error_lengths
+            .is_empty());
+        assert!(pid_data_map[&567].error_events.is_empty());
+
+        let patterns = error_patterns(&[567, 568], &pid_data_map);
+        assert_eq!(patterns.len(), 1);
+        assert_eq!(patterns[0].path, Some(b"/opt/app/config.yml".as_ref()));
+
+        assert_eq!(
+            IgnoredErrors::parse("access ENOENT\n"),
+            Err(String::from(
+                "Invalid error pattern 'access ENOENT' on line 1"
+            ))
+        );
+    }
+
+    #[test]
+    fn errors_match_non_utf8_paths() {
+        // Latin-1 names, 'caf\xe9' and 'na\xefve', which aren't valid UTF-8
+        let input = b"477   00:09:56.000000 stat(\"/srv/caf\xe9\", 0x7ffc6d3c2df0) = -1 ENOENT (No such file or directory) <0.000100>
+477   00:09:56.100000 stat(\"/srv/na\xefve\", 0x7ffc6d3c2df0) = -1 ENOENT (No such file or directory) <0.000100>";
+        let mut pid_data_map = build_syscall_data(input);
+
+        let patterns = error_patterns(&[477], &pid_data_map);
+        assert_eq!(patterns.len(), 2);
+        assert_eq!(patterns[0].path, Some(b"/srv/caf\xe9".as_ref()));
+
+        let ignored = IgnoredErrors::parse(b"stat /srv/caf\xe9 ENOENT\n").unwrap();
+        assert_eq!(remove_ignored_errors(&mut pid_data_map, &ignored), 1);
+        let patterns = error_patterns(&[477], &pid_data_map);
+        assert_eq!(patterns[0].path, Some(b"/srv/na\xefve".as_ref()));
+    }
+}
@@ -0,0 +1,147 @@
+use crate::clock::timestamp_range;
+use crate::output;
+use crate::time::parse_timestamp_us;
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::io::Error;
+
+// Lines checked for a '-T' duration before deciding the trace has none
+const LINES_CHECKED: usize = 100;
+
+/// Whether the trace was run with '-T', found from the first lines that would
+/// end with a duration
+pub fn has_durations(buffer: &[u8]) -> bool {
+    buffer
+        .lines()
+        .filter(|line| !line.ends_with(b"<unfinished ...>") && !line.ends_with(b"+++"))
+        .filter(|line| !line.ends_with(b"---"))
+        .take(LINES_CHECKED)
+        .any(|line| duration_token(line).is_some())
+}
+
+// 477   00:09:56.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+//                                                      ^^^^^^^^
+fn duration_token(line: &[u8]) -> Option<&[u8]> {
+    let token = line.strip_suffix(b">")?;
+    let start = token.rfind_byte(b'<')? + 1;
+    let token = &token[start..];
+    if !token.is_empty() && token.iter().all(|c| c.is_ascii_digit() || *c == b'.') {
+        Some(token)
+    } else {
+        None
+    }
+}
+
+/// Copies a trace run without '-T', appending to each call a duration
+/// estimated as the time from the call's start until its PID's next line, or
+/// the end of the trace for a PID's last call. A PID may have been running
+/// rather than in the call for part of that gap, so the estimate is an upper
+/// bound. Lines for signals and exits are left unchanged.
+pub fn add_estimated_durations(buffer: &[u8]) -> Vec<u8> {
+    let lines: Vec<&[u8]> = buffer.lines().collect();
+    let mut durations: Vec<Option<i64>> = vec![None; lines.len()];
+
+    // Per PID, the last call waiting on the next line to end its estimate,
+    // and the start of a call split across lines
+    let mut pending: HashMap<Pid, (usize, i64)> = HashMap::default();
+    let mut started: HashMap<Pid, i64> = HashMap::default();
+    let mut last_us = None;
+
+    for (idx, line) in lines.iter().enumerate() {
+        let (pid, us) = match pid_and_time(line) {
+            Some(stamp) => stamp,
+            None => continue,
+        };
+        last_us = Some(us);
+
+        if let Some((call, start)) = pending.remove(&pid) {
+            durations[call] = Some(us - start);
+        }
+
+        if line.ends_with(b"<unfinished ...>") {
+            started.insert(pid, us);
+        } else if line.contains_str(" resumed>") {
+            let start = started.remove(&pid).unwrap_or(us);
+            pending.insert(pid, (idx, start));
+        } else if !line.ends_with(b"+++") && !line.ends_with(b"---") {
+            pending.insert(pid, (idx, us));
+        }
+    }
+
+    if let Some(end) = last_us {
+        for (call, start) in pending.into_values() {
+            durations[call] = Some(end - start);
+        }
+    }
+
+    let mut estimated = Vec::with_capacity(buffer.len() + lines.len() * 11);
+    for (line, duration) in lines.iter().zip(durations) {
+        estimated.extend_from_slice(line);
+        if let Some(us) = duration {
+            let us = us.max(0);
+            estimated.extend_from_slice(
+                format!(" <{}.{:06}>", us / 1_000_000, us % 1_000_000).as_bytes(),
+            );
+        }
+        estimated.push(b'\n');
+    }
+
+    estimated
+}
+
+fn pid_and_time(line: &[u8]) -> Option<(Pid, i64)> {
+    let range = timestamp_range(line)?;
+    let pid = line[..range.start]
+        .trim()
+        .to_str()
+        .ok()?
+        .parse::<Pid>()
+        .ok()?;
+    let us = parse_timestamp_us(&line[range])?;
+    Some((pid, us))
+}
+
+pub fn print_estimate_note() -> Result<(), Error> {
+    output::print_note(
+        "  Note: this trace was run without '-T', so call durations are estimated as the time until \
+         each PID's next line\n  Estimates are an upper bound, and include time spent running between calls",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_detects_durations() {
+        let with_t = br##"477   10:00:00.000000 read(3</etc/hosts>,  <unfinished ...>
+477   10:00:00.000100 <... read resumed>"a", 1) = 1 <0.000100>"##;
+        let without_t = br##"477   10:00:00.000000 read(3</etc/hosts>,  <unfinished ...>
+477   10:00:00.000100 <... read resumed>"a", 1) = 1"##;
+
+        assert!(has_durations(with_t));
+        assert!(!has_durations(without_t));
+    }
+
+    #[test]
+    fn estimate_uses_gap_to_next_line() {
+        let input =
+            br##"477   10:00:00.000000 openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = 3</etc/hosts>
+495   10:00:00.000500 read(4</etc/passwd>,  <unfinished ...>
+477   10:00:00.001000 close(3</etc/hosts>) = 0
+495   10:00:00.003000 <... read resumed>"a", 1) = 1
+477   10:00:00.004000 +++ exited with 0 +++
+495   10:00:00.005000 write(1</dev/null>, "a", 1) = 1"##;
+        let estimated = add_estimated_durations(input);
+        let lines: Vec<_> = estimated.lines().collect();
+
+        assert!(lines[0].ends_with(b" = 3</etc/hosts> <0.001000>"));
+        assert!(lines[1].ends_with(b"<unfinished ...>"));
+        assert!(lines[2].ends_with(b" = 0 <0.003000>"));
+        assert!(lines[3].ends_with(b" = 1 <0.004500>"));
+        assert!(lines[4].ends_with(b"+++ exited with 0 +++"));
+        assert!(lines[5].ends_with(b" = 1 <0.000000>"));
+        assert!(has_durations(&estimated));
+    }
+}
@@ -0,0 +1,332 @@
+use crate::json::{rounded, Lossy, Time};
+use crate::parser::{self, CallStatus, LineData, RawData};
+use crate::time::parse_timestamp_us;
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::{prelude::*, stdout, BufWriter, Error};
+
+/// A call, or a run of identical calls by one PID collapsed into one record
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event<'a> {
+    pub pid: Pid,
+    pub time: &'a [u8],
+    /// When the last call of a collapsed run started
+    pub last_time: &'a [u8],
+    pub syscall: &'a [u8],
+    pub path: Option<&'a [u8]>,
+    pub errno: Option<&'a [u8]>,
+    pub count: usize,
+    /// Total time in the calls, in ms
+    pub duration: f32,
+    start_us: i64,
+}
+
+impl<'a> Event<'a> {
+    fn new(raw_data: &RawData<'a>, start_us: i64) -> Event<'a> {
+        Event {
+            pid: raw_data.pid,
+            time: raw_data.time,
+            last_time: raw_data.time,
+            syscall: raw_data.syscall,
+            path: raw_data.path(),
+            errno: raw_data.error,
+            count: 1,
+            duration: raw_data.duration.unwrap_or_default() * 1000.0,
+            start_us,
+        }
+    }
+
+    /// When the call started, in microseconds as given by
+    /// `time::parse_timestamp_us`
+    pub fn start_us(&self) -> i64 {
+        self.start_us
+    }
+
+    fn same_call(&self, other: &Event) -> bool {
+        self.syscall == other.syscall && self.path == other.path && self.errno == other.errno
+    }
+
+    fn write_json<W: Write>(&self, out: &mut W) -> Result<(), Error> {
+        let record = EventJson {
+            pid: self.pid,
+            time: Time(self.time),
+            last_time: Time(self.last_time),
+            syscall: Lossy(self.syscall),
+            path: self.path.map(Lossy),
+            errno: self.errno.map(Lossy),
+            count: self.count,
+            duration_ms: rounded(self.duration, 3),
+        };
+        serde_json::to_writer(&mut *out, &record)?;
+        writeln!(out)
+    }
+}
+
+/// Every call made by `pids`, in the order they started. Unfinished calls are
+/// joined with their resumed line. With a `dedup_window` above 0, a call with
+/// the same syscall, path, and errno as one of the PID's last `dedup_window`
+/// records is added to that record's count and duration instead of starting
+/// its own, so a PID polling in a loop of a few calls becomes a few records.
+pub fn events<'a>(buffer: &'a [u8], pids: &[Pid], dedup_window: usize) -> Vec<Event<'a>> {
+    let pids: HashSet<Pid> = pids.iter().copied().collect();
+    let start = buffer.as_ptr() as usize;
+
+    let mut calls: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter(|line| parser::line_pid(line).is_some_and(|pid| pids.contains(&pid)))
+        .filter_map(|line| match parser::parse_line(line)? {
+            LineData::Syscall(raw_data) if pids.contains(&raw_data.pid) => {
+                let time = parse_timestamp_us(raw_data.time)?;
+                // Lines with the same timestamp are kept in the order they were printed
+                let offset = line.as_ptr() as usize - start;
+                Some(((raw_data.pid, time, offset), raw_data))
+            }
+            _ => None,
+        })
+        .collect();
+    calls.par_sort_unstable_by_key(|(key, _)| *key);
+
+    let mut events: Vec<Event> = Vec::new();
+    // Indexes of each PID's last `dedup_window` records
+    let mut recent: HashMap<Pid, VecDeque<usize>> = HashMap::default();
+    // Each PID's unfinished call, waiting for its resumed line
+    let mut started: HashMap<Pid, Event> = HashMap::default();
+
+    for ((pid, time, _), raw_data) in calls {
+        let event = match raw_data.call_status {
+            CallStatus::Complete => Event::new(&raw_data, time),
+            CallStatus::Started => {
+                started.insert(pid, Event::new(&raw_data, time));
+                continue;
+            }
+            CallStatus::Resumed => match started.remove(&pid) {
+                Some(mut event) => {
+                    event.path = event.path.or_else(|| raw_data.path());
+                    event.errno = raw_data.error;
+                    event.duration = raw_data.duration.unwrap_or_default() * 1000.0;
+                    event
+                }
+                None => continue,
+            },
+        };
+
+        let pid_recent = recent.entry(pid).or_default();
+        if let Some(&idx) = pid_recent
+            .iter()
+            .find(|&&idx| events[idx].same_call(&event))
+        {
+            let record = &mut events[idx];
+            record.count += 1;
+            record.duration += event.duration;
+            record.last_time = event.time;
+            continue;
+        }
+
+        if dedup_window > 0 {
+            pid_recent.push_back(events.len());
+            if pid_recent.len() > dedup_window {
+                pid_recent.pop_front();
+            }
+        }
+        events.push(event);
+    }
+
+    // Calls still unfinished when the trace ends
+    events.extend(started.into_values());
+
+    events.par_sort_by_key(|event| (event.start_us, event.pid));
+
+    events
+}
+
+// A path split by `path_tokens`, with `None` for numbers that differ
+type PathPattern<'a> = Vec<Option<&'a [u8]>>;
+
+/// A run of back-to-back calls by one PID, or a single call
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallRun<'a> {
+    pub time: &'a [u8],
+    pub syscall: &'a [u8],
+    /// The path, with numbers that differ between the calls replaced by '*'
+    pub path: Option<String>,
+    pub errno: Option<&'a [u8]>,
+    pub count: usize,
+    /// Total time in the calls, in ms
+    pub duration: f32,
+}
+
+/// The calls made by each of `pids` in the order they started. Unless
+/// `expand` is set, back-to-back calls to the same syscall with the same errno
+/// on paths that differ at most in their numbers are collapsed into one run,
+/// such as 'lstat' of each of '/proc/self/fd/0' to '/proc/self/fd/221'.
+pub fn call_runs<'a>(
+    buffer: &'a [u8],
+    pids: &[Pid],
+    expand: bool,
+) -> HashMap<Pid, Vec<CallRun<'a>>> {
+    let mut runs: HashMap<Pid, Vec<(CallRun, PathPattern)>> = HashMap::default();
+
+    for event in events(buffer, pids, 0) {
+        let pid_runs = runs.entry(event.pid).or_default();
+        let path = event.path.map(path_tokens);
+
+        if let Some((run, pattern)) = pid_runs.last_mut().filter(|(run, pattern)| {
+            !expand
+                && run.syscall == event.syscall
+                && run.errno == event.errno
+                && same_shape(pattern, path.as_deref())
+        }) {
+            if let Some(path) = &path {
+                for (token, other) in pattern.iter_mut().zip(path) {
+                    if *token != *other {
+                        *token = None;
+                    }
+                }
+            }
+            run.count += 1;
+            run.duration += event.duration;
+            continue;
+        }
+
+        pid_runs.push((
+            CallRun {
+                time: event.time,
+                syscall: event.syscall,
+                path: None,
+                errno: event.errno,
+                count: 1,
+                duration: event.duration,
+            },
+            path.unwrap_or_default(),
+        ));
+    }
+
+    runs.into_iter()
+        .map(|(pid, pid_runs)| {
+            let pid_runs = pid_runs
+                .into_iter()
+                .map(|(mut run, pattern)| {
+                    if !pattern.is_empty() {
+                        let path: Vec<u8> = pattern
+                            .iter()
+                            .flat_map(|token| token.unwrap_or(b"*").iter().copied())
+                            .collect();
+                        run.path = Some(path.to_str_lossy().to_string());
+                    }
+                    run
+                })
+                .collect();
+            (pid, pid_runs)
+        })
+        .collect()
+}
+
+// A path split into runs of digits and the text between them
+fn path_tokens(path: &[u8]) -> PathPattern<'_> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    for idx in 1..=path.len() {
+        if idx == path.len() || path[idx].is_ascii_digit() != path[idx - 1].is_ascii_digit() {
+            tokens.push(Some(&path[start..idx]));
+            start = idx;
+        }
+    }
+    tokens
+}
+
+// Whether `path` matches `pattern` except in its numbers
+fn same_shape(pattern: &[Option<&[u8]>], path: Option<&[Option<&[u8]>]>) -> bool {
+    let path = path.unwrap_or_default();
+    pattern.len() == path.len()
+        && pattern.iter().zip(path).all(|(token, other)| {
+            let is_number = |t: &Option<&[u8]>| t.is_none_or(|t| t[0].is_ascii_digit());
+            token == other || (is_number(token) && is_number(other))
+        })
+}
+
+/// Writes each event as a JSON object on its own line
+pub fn print_events(buffer: &[u8], pids: &[Pid], dedup_window: usize) -> Result<(), Error> {
+    let stdout = stdout();
+    let mut out = BufWriter::new(stdout.lock());
+
+    for event in events(buffer, pids, dedup_window) {
+        event.write_json(&mut out)?;
+    }
+
+    out.flush()
+}
+
+// An event as written to JSON, one per line
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct EventJson<'a> {
+    pid: Pid,
+    time: Time<'a>,
+    last_time: Time<'a>,
+    syscall: Lossy<'a>,
+    path: Option<Lossy<'a>>,
+    errno: Option<Lossy<'a>>,
+    count: usize,
+    duration_ms: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_dedup_polling_loops() {
+        let input = br##"477   10:00:00.000000 epoll_wait(5<anon_inode:[eventpoll]>, [], 1024, 100) = 0 <0.100000>
+477   10:00:00.100000 read(3</proc/self/status>, "", 10) = 0 <0.000010>
+478   10:00:00.100000 openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000020>
+477   10:00:00.200000 epoll_wait(5<anon_inode:[eventpoll]>,  <unfinished ...>
+477   10:00:00.300000 <... epoll_wait resumed>[], 1024, 100) = 0 <0.100000>
+477   10:00:00.300100 read(3</proc/self/status>, "", 10) = 0 <0.000010>
+478   10:00:00.400000 openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000020>
+478   10:00:00.500000 openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = 3</etc/hosts> <0.000020>"##;
+
+        let all = events(input, &[477, 478], 0);
+        assert_eq!(all.len(), 7);
+        assert!(all.iter().all(|e| e.count == 1));
+
+        let deduped = events(input, &[477, 478], 2);
+        assert_eq!(deduped.len(), 4);
+        assert_eq!(deduped[0].syscall, b"epoll_wait");
+        assert_eq!(deduped[0].count, 2);
+        assert!((deduped[0].duration - 200.0).abs() < 1e-3);
+        assert_eq!(deduped[0].last_time, b"10:00:00.200000");
+        assert_eq!(deduped[1].path, Some(b"/proc/self/status".as_ref()));
+        assert_eq!(deduped[1].count, 2);
+        assert_eq!(deduped[2].errno, Some(b"ENOENT".as_ref()));
+        assert_eq!(deduped[2].count, 2);
+        assert_eq!(deduped[3].errno, None);
+
+        // Only consecutive calls are collapsed with a window of 1
+        assert_eq!(events(input, &[477, 478], 1).len(), 6);
+    }
+    #[test]
+    fn events_collapse_runs_of_calls() {
+        let input = br##"567   00:09:47.836504 open("/proc/self/fd", O_RDONLY|O_NONBLOCK|O_DIRECTORY|O_CLOEXEC) = 221</proc/495/fd> <0.000027>
+567   00:09:47.837002 lstat("/proc/self/fd/0", {st_mode=S_IFLNK|0500, st_size=64, ...}) = 0 <0.000016>
+567   00:09:47.837058 lstat("/proc/self/fd/1", {st_mode=S_IFLNK|0300, st_size=64, ...}) = 0 <0.000017>
+567   00:09:47.837101 lstat("/proc/self/fd/12", {st_mode=S_IFLNK|0300, st_size=64, ...}) = 0 <0.000024>
+567   00:09:47.837149 lstat("/proc/self/fd/13", 0x7ffc6d3c2df0) = -1 ENOENT (No such file or directory) <0.000013>
+567   00:09:47.837184 close(221</proc/495/fd>) = 0 <0.000019>"##;
+
+        let runs = &call_runs(input, &[567], false)[&567];
+        assert_eq!(runs.len(), 4);
+        assert_eq!(runs[0].path.as_deref(), Some("/proc/self/fd"));
+        assert_eq!(runs[1].syscall, b"lstat");
+        assert_eq!(runs[1].path.as_deref(), Some("/proc/self/fd/*"));
+        assert_eq!(runs[1].count, 3);
+        assert!((runs[1].duration - 0.057).abs() < 1e-3);
+        assert_eq!(runs[2].errno, Some(b"ENOENT".as_ref()));
+        assert_eq!(runs[2].path.as_deref(), Some("/proc/self/fd/13"));
+
+        assert_eq!(call_runs(input, &[567], true)[&567].len(), 6);
+    }
+}
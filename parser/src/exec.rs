@@ -1,4 +1,6 @@
+use crate::output;
 use crate::syscall_data::RawExec;
+use crate::{HashMap, Pid};
 
 use bstr::ByteSlice;
 use std::fmt;
@@ -9,12 +11,14 @@ use std::slice::Iter;
 pub struct Execs {
     cmds: Vec<String>,
     times: Vec<String>,
+    env_counts: Vec<Option<usize>>,
 }
 
 impl Execs {
     pub fn new(mut raw_execs: Vec<RawExec>) -> Execs {
         let mut cmds = Vec::new();
         let mut times = Vec::new();
+        let mut env_counts = Vec::new();
 
         if raw_execs.is_empty() {
             panic!("empty exec");
@@ -34,7 +38,7 @@ impl Execs {
             let mut cmd = arg_iter
                 .next()
                 .map(|s| s.to_str_lossy().to_string())
-                .map(|c| Execs::trim_arg(c))
+                .map(Execs::trim_arg)
                 .unwrap_or_default();
 
             cmd.push(' ');
@@ -43,7 +47,7 @@ impl Execs {
                 arg_iter
                     .nth(1) // skip argv[0] as this is a repeat of cmd 99% of the time
                     .map(|s| s.to_str_lossy().to_string())
-                    .map(|a| Execs::trim_arg(a))
+                    .map(Execs::trim_arg)
                     .unwrap_or_default()
                     .as_str(),
             );
@@ -52,21 +56,26 @@ impl Execs {
 
             let full_cmd = arg_iter
                 .map(|s| s.to_str_lossy().to_string())
-                .map(|a| Execs::trim_arg(a))
+                .map(Execs::trim_arg)
                 .fold(cmd, |s, arg| s + &arg + " ");
 
             cmds.push(full_cmd.trim().to_string());
-            times.push(raw_exec.time.to_str_lossy().to_string());
+            times.push(output::timestamp(raw_exec.time));
+            env_counts.push(raw_exec.env_count);
         }
 
-        Execs { cmds, times }
+        Execs {
+            cmds,
+            times,
+            env_counts,
+        }
     }
 
-    pub fn cmds(&self) -> Iter<String> {
+    pub fn cmds(&self) -> Iter<'_, String> {
         self.cmds.iter()
     }
 
-    pub fn iter(&self) -> Zip<Iter<String>, Iter<String>> {
+    pub fn iter(&self) -> Zip<Iter<'_, String>, Iter<'_, String>> {
         self.cmds.iter().zip(&self.times)
     }
 
@@ -78,9 +87,7 @@ impl Execs {
     }
 
     fn trim_arg(arg: String) -> String {
-        let initial_trim = arg
-            .trim_start_matches(|c| c == '[')
-            .trim_end_matches(|c| c == ',' || c == ']');
+        let initial_trim = arg.trim_start_matches('[').trim_end_matches([',', ']']);
 
         // Only trim quotes if arg is fully quoted and not a standalone quote
         // otherwise it's part of a quoted command, e.g. 'sh -c "ls -la"'
@@ -95,6 +102,110 @@ impl Execs {
     }
 }
 
+// Programs run fewer times than this have no baseline to compare against
+const MIN_ANOMALY_RUNS: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecAnomaly<'a> {
+    pub pid: Pid,
+    pub time: &'a str,
+    pub cmd: &'a str,
+    pub runs: usize,
+    pub args_differ: bool,
+    pub env_count: Option<usize>,
+    pub usual_env_count: Option<usize>,
+}
+
+impl<'a> ExecAnomaly<'a> {
+    pub fn reason(&self) -> String {
+        let mut reasons = Vec::new();
+        if self.args_differ {
+            reasons.push(String::from("args"));
+        }
+
+        if let (Some(env), Some(usual)) = (self.env_count, self.usual_env_count) {
+            if env != usual {
+                reasons.push(format!("env {} vs {}", env, usual));
+            }
+        }
+
+        reasons.join(", ")
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ExecRun<'a> {
+    pid: Pid,
+    time: &'a str,
+    cmd: &'a str,
+    args: &'a str,
+    env_count: Option<usize>,
+}
+
+/// Compares repeated executions of the same program, flagging those whose
+/// arguments or environment size occur only once while another value repeats
+pub fn exec_anomalies<'a>(execs: &[(Pid, &'a Execs)]) -> Vec<ExecAnomaly<'a>> {
+    let mut programs: HashMap<&str, Vec<ExecRun>> = HashMap::default();
+
+    for (pid, exec) in execs {
+        for ((cmd, time), env_count) in exec.iter().zip(&exec.env_counts) {
+            let (program, args) = cmd.split_once(' ').unwrap_or((cmd, ""));
+            programs.entry(program).or_default().push(ExecRun {
+                pid: *pid,
+                time,
+                cmd,
+                args,
+                env_count: *env_count,
+            });
+        }
+    }
+
+    let mut anomalies = Vec::new();
+
+    for runs in programs.values().filter(|r| r.len() >= MIN_ANOMALY_RUNS) {
+        let mut arg_cts: HashMap<&str, usize> = HashMap::default();
+        let mut env_cts: HashMap<usize, usize> = HashMap::default();
+        for run in runs {
+            *arg_cts.entry(run.args).or_insert(0) += 1;
+            if let Some(env) = run.env_count {
+                *env_cts.entry(env).or_insert(0) += 1;
+            }
+        }
+
+        let args_repeat = arg_cts.values().any(|&ct| ct > 1);
+        let usual_env = env_cts
+            .iter()
+            .filter(|(_, &ct)| ct > 1)
+            .max_by(|(x_env, x_ct), (y_env, y_ct)| x_ct.cmp(y_ct).then(y_env.cmp(x_env)))
+            .map(|(env, _)| *env);
+
+        for run in runs {
+            let args_differ = args_repeat && arg_cts[run.args] == 1;
+            let env_differs = usual_env.is_some()
+                && run
+                    .env_count
+                    .map(|env| env_cts[&env] == 1)
+                    .unwrap_or_default();
+
+            if args_differ || env_differs {
+                anomalies.push(ExecAnomaly {
+                    pid: run.pid,
+                    time: run.time,
+                    cmd: run.cmd,
+                    runs: runs.len(),
+                    args_differ,
+                    env_count: run.env_count,
+                    usual_env_count: usual_env,
+                });
+            }
+        }
+    }
+
+    anomalies.sort_by(|x, y| (x.time).cmp(y.time).then(x.pid.cmp(&y.pid)));
+
+    anomalies
+}
+
 impl fmt::Display for Execs {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut iter = self.iter().peekable();
@@ -147,7 +258,7 @@ mod tests {
         let execs = Execs::new(pid_data_map.remove(&12668).unwrap().execve.unwrap());
 
         let cmd = execs.cmds.first().unwrap();
-        assert_eq!(false, cmd.contains("0xae3c08"));
+        assert!(!cmd.contains("0xae3c08"));
     }
 
     #[test]
@@ -194,4 +305,31 @@ mod tests {
             r##"/bin/sh -c "/opt/gitlab/bin/gitlab-psql -d gitlabhq_production -c \"SELECT table_name\n FROM information_schema.tables\n WHERE table_catalog = 'gitlabhq_production'\n AND table_schema='public'\" -A | grep -x projects""##
         );
     }
+
+    #[test]
+    fn exec_flags_unique_args_and_env() {
+        let input = br##"100 10:00:00.000001 execve("/usr/bin/git", ["git", "status"], 0x7ffc1bafc638 /* 25 vars */) = 0 <0.000302>
+101 10:00:00.000002 execve("/usr/bin/git", ["git", "status"], 0x7ffc1bafc638 /* 25 vars */) = 0 <0.000302>
+102 10:00:00.000003 execve("/usr/bin/git", ["git", "status"], 0x7ffc1bafc638 /* 31 vars */) = 0 <0.000302>
+103 10:00:00.000004 execve("/usr/bin/git", ["git", "gc", "--aggressive"], 0x7ffc1bafc638 /* 25 vars */) = 0 <0.000302>
+104 10:00:00.000005 execve("/bin/sleep", ["sleep", "1"], 0x7ffc1bafc638 /* 25 vars */) = 0 <0.000302>"##;
+        let mut pid_data_map = build_syscall_data(input);
+        let execs: Vec<_> = (100..=104)
+            .map(|pid| {
+                (
+                    pid,
+                    Execs::new(pid_data_map.remove(&pid).unwrap().execve.unwrap()),
+                )
+            })
+            .collect();
+        let exec_refs: Vec<_> = execs.iter().map(|(pid, e)| (*pid, e)).collect();
+
+        let anomalies = exec_anomalies(&exec_refs);
+        assert_eq!(anomalies.len(), 2);
+        assert_eq!(anomalies[0].pid, 102);
+        assert_eq!(anomalies[0].reason(), "env 31 vs 25");
+        assert_eq!(anomalies[1].pid, 103);
+        assert_eq!(anomalies[1].reason(), "args");
+        assert_eq!(anomalies[1].runs, 4);
+    }
 }
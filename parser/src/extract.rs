@@ -0,0 +1,279 @@
+use crate::parser::{self, LineData};
+use crate::throughput::format_bytes;
+use crate::{HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::fmt;
+use std::io::{prelude::*, Error};
+
+/// The string strace prints for a call's data is cut to this many bytes by
+/// default, enough to recognize it, e.g. an HTTP request line
+pub const DEFAULT_STRING_LIMIT: usize = 32;
+
+// Lines are compacted in batches of about this many bytes, so memory stays
+// small however large the trace is
+const CHUNK_BYTES: usize = 64 << 20;
+
+// Syscalls whose string arguments are the data moved, rather than paths
+const PAYLOAD_SYSCALLS: &[&[u8]] = &[
+    b"pread64",
+    b"preadv",
+    b"preadv2",
+    b"pwrite64",
+    b"pwritev",
+    b"pwritev2",
+    b"read",
+    b"readv",
+    b"recv",
+    b"recvfrom",
+    b"recvmmsg",
+    b"recvmsg",
+    b"send",
+    b"sendmmsg",
+    b"sendmsg",
+    b"sendto",
+    b"write",
+    b"writev",
+];
+
+/// How much of the trace an extract kept
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ExtractStats {
+    pub lines: usize,
+    pub kept_lines: usize,
+    /// Calls with data cut to the string limit
+    pub cut_calls: usize,
+    pub bytes_in: usize,
+    pub bytes_out: usize,
+}
+
+impl fmt::Display for ExtractStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "kept {} of {} lines and cut the data of {} calls, {} down to {}",
+            self.kept_lines,
+            self.lines,
+            self.cut_calls,
+            format_bytes(self.bytes_in as f64),
+            format_bytes(self.bytes_out as f64)
+        )
+    }
+}
+
+/// Writes a compact copy of the trace in `buffer` to `out`, for reports to be
+/// run away from the host it was captured on. It's a trace itself, with every
+/// line of `pids`, or all PIDs when `None`, kept whole except the data of
+/// reads, writes, sends, and receives, which are cut to `string_limit` bytes
+/// as if traced with '-s'. Return values still give the bytes moved.
+pub fn write_extract<W: Write>(
+    out: &mut W,
+    buffer: &[u8],
+    pids: Option<&HashSet<Pid>>,
+    string_limit: usize,
+) -> Result<ExtractStats, Error> {
+    let mut stats = ExtractStats {
+        bytes_in: buffer.len(),
+        ..ExtractStats::default()
+    };
+
+    let mut rest = buffer;
+    while !rest.is_empty() {
+        let end = match rest.get(CHUNK_BYTES..).and_then(|r| r.find_byte(b'\n')) {
+            Some(idx) => CHUNK_BYTES + idx + 1,
+            None => rest.len(),
+        };
+        let (chunk, remaining) = rest.split_at(end);
+        rest = remaining;
+
+        let lines: Vec<_> = chunk
+            .strip_suffix(b"\n")
+            .unwrap_or(chunk)
+            .par_split(|c| *c == b'\n')
+            .map(|line| compact_line(line, pids, string_limit))
+            .collect();
+
+        for line in lines {
+            stats.lines += 1;
+            let (line, cut) = match line {
+                Some(line) => line,
+                None => continue,
+            };
+            stats.kept_lines += 1;
+            stats.cut_calls += usize::from(cut);
+            stats.bytes_out += line.len() + 1;
+            out.write_all(&line)?;
+            out.write_all(b"\n")?;
+        }
+    }
+
+    Ok(stats)
+}
+
+// The line to keep, if any, and whether its data was cut. Lines that don't
+// start with a PID are kept, so the extract has the same unparsed lines.
+fn compact_line(line: &[u8], pids: Option<&HashSet<Pid>>, limit: usize) -> Option<(Vec<u8>, bool)> {
+    let pid = line
+        .fields()
+        .next()
+        .and_then(|p| p.to_str().ok())
+        .and_then(|p| p.parse::<Pid>().ok());
+    if let (Some(pids), Some(pid)) = (pids, pid) {
+        if !pids.contains(&pid) {
+            return None;
+        }
+    }
+
+    match parser::parse_line(line) {
+        Some(LineData::Syscall(raw_data)) if PAYLOAD_SYSCALLS.contains(&raw_data.syscall) => {
+            Some(cut_strings(line, limit))
+        }
+        _ => Some((line.to_vec(), false)),
+    }
+}
+
+// Cuts each string in `line` longer than `limit` bytes, marking it with '...'
+// as strace does. Strings naming an fd's socket with '-yyy', e.g.
+// '3<UNIX:[5->6,"/run/app.sock"]>', and addresses, e.g.
+// 'inet_addr("10.0.0.1")', are left whole.
+fn cut_strings(line: &[u8], limit: usize) -> (Vec<u8>, bool) {
+    let mut out = Vec::with_capacity(line.len().min(256));
+    let mut cut = false;
+    let mut idx = 0;
+    // How many '<' outside of strings are yet to be closed
+    let mut fd_depth = 0usize;
+
+    while let Some(offset) = line[idx..].find_byte(b'"') {
+        let open = idx + offset;
+        for (i, &c) in line.iter().enumerate().take(open).skip(idx) {
+            match c {
+                b'<' => fd_depth += 1,
+                // Not the '->' between a socket's ends
+                b'>' if i == 0 || line[i - 1] != b'-' => fd_depth = fd_depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        out.extend_from_slice(&line[idx..=open]);
+        let before = &line[..open];
+        let address = fd_depth > 0
+            || before.ends_with(b"inet_addr(")
+            || before.ends_with(b"sun_path=")
+            || before
+                .rfind("inet_pton(")
+                .is_some_and(|i| before[i..].find_byte(b')').is_none());
+
+        // Walk the string an escape sequence at a time, counting the bytes
+        // each decodes to
+        let mut pos = open + 1;
+        let mut decoded = 0;
+        let mut keep_to = None;
+        while let Some(&c) = line.get(pos) {
+            if c == b'"' {
+                break;
+            }
+            if decoded == limit && keep_to.is_none() && !address {
+                keep_to = Some(pos);
+            }
+            pos += escape_len(&line[pos..]);
+            decoded += 1;
+        }
+
+        let close = match line.get(pos) {
+            Some(b'"') => pos,
+            // An unterminated string, e.g. a line cut off when copied
+            _ => {
+                out.extend_from_slice(&line[open + 1..]);
+                return (out, cut);
+            }
+        };
+        idx = close + 1;
+
+        match keep_to {
+            Some(end) => {
+                out.extend_from_slice(&line[open + 1..end]);
+                out.extend_from_slice(b"\"...");
+                // strace already marked it as cut short
+                if line[idx..].starts_with(b"...") {
+                    idx += 3;
+                }
+                cut = true;
+            }
+            None => out.extend_from_slice(&line[open + 1..=close]),
+        }
+    }
+    out.extend_from_slice(&line[idx..]);
+
+    (out, cut)
+}
+
+// The length of the character or escape sequence, e.g. '\x1b' or '\33', at
+// the start of `s`
+fn escape_len(s: &[u8]) -> usize {
+    match s {
+        [b'\\', b'x', rest @ ..] => {
+            2 + rest
+                .iter()
+                .take(2)
+                .take_while(|c| c.is_ascii_hexdigit())
+                .count()
+        }
+        [b'\\', b'0'..=b'7', rest @ ..] => {
+            2 + rest
+                .iter()
+                .take(2)
+                .take_while(|c| matches!(c, b'0'..=b'7'))
+                .count()
+        }
+        [b'\\', _, ..] => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_cuts_data_and_keeps_paths() {
+        let input = br##"477   00:09:56.000000 openat(AT_FDCWD, "/var/opt/gitlab/a/very/long/path/to/config.yml", O_RDONLY) = 3 <0.000010>
+477   00:09:56.100000 read(3</var/opt/gitlab/config.yml>, "production:\n  adapter: postgresql\n"..., 4096) = 4096 <0.000020>
+495   00:09:56.150000 write(1, "short", 5) = 5 <0.000010>
+477   00:09:56.200000 sendto(5<UDP:[1]>, "\33\1\2\3\x04\0", 6, 0, {sa_family=AF_INET, sin_port=htons(53), sin_addr=inet_addr("10.0.0.1")}, 16) = 6 <0.000010>
+477   00:09:56.300000 read(4<UNIX:[5->6,"/var/opt/gitlab/gitlab.socket"]>,  <unfinished ...>
+477   00:09:56.400000 <... read resumed>"abcdefgh", 8) = 8 <0.000010>
+not a trace line"##;
+
+        let mut out = Vec::new();
+        let stats = write_extract(&mut out, input, None, 4).unwrap();
+        let text = out.to_str_lossy();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], input.to_str_lossy().lines().next().unwrap());
+        assert_eq!(
+            lines[1],
+            r#"477   00:09:56.100000 read(3</var/opt/gitlab/config.yml>, "prod"..., 4096) = 4096 <0.000020>"#
+        );
+        assert_eq!(
+            lines[2],
+            r#"495   00:09:56.150000 write(1, "shor"..., 5) = 5 <0.000010>"#
+        );
+        assert!(lines[3].contains(r#"sendto(5<UDP:[1]>, "\33\1\2\3"..., 6"#));
+        assert!(lines[3].contains(r#"inet_addr("10.0.0.1")"#));
+        assert_eq!(
+            lines[5],
+            r#"477   00:09:56.400000 <... read resumed>"abcd"..., 8) = 8 <0.000010>"#
+        );
+        assert!(lines[4].contains(r#""/var/opt/gitlab/gitlab.socket"]>"#));
+        assert_eq!(lines[6], "not a trace line");
+        assert_eq!(stats.lines, 7);
+        assert_eq!(stats.kept_lines, 7);
+        assert_eq!(stats.cut_calls, 4);
+        assert_eq!(stats.bytes_out, out.len());
+
+        let pids: HashSet<Pid> = [495].iter().copied().collect();
+        let mut out = Vec::new();
+        let stats = write_extract(&mut out, input, Some(&pids), 64).unwrap();
+        assert_eq!(stats.kept_lines, 2);
+        assert!(out.starts_with(br#"495   00:09:56.150000 write(1, "short", 5) = 5 <0.000010>"#));
+    }
+}
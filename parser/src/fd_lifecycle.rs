@@ -0,0 +1,335 @@
+use crate::open_fds::{fd_events, thread_groups};
+use crate::output;
+use crate::parser::OtherFields;
+use crate::syscall_data::PidData;
+use crate::syscall_stats::FdKind;
+use crate::table::{Align, Column, Table};
+use crate::time;
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use std::collections::BTreeMap;
+use std::io::{prelude::*, stdout, Error};
+
+/// An fd held by a process, from the call that opened it to the one that
+/// closed it. Threads share their process's fds, so they are listed under the
+/// thread group leader.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FdLife<'a> {
+    pub pid: Pid,
+    pub fd: i32,
+    /// The file, socket, or pipe the fd referred to, when traced with '-y'
+    pub target: Option<&'a [u8]>,
+    /// The call that opened the fd, `None` for fds open before the trace
+    pub opened_by: Option<&'a [u8]>,
+    pub opened: Option<&'a [u8]>,
+    /// 'close', or the call that opened another file on the fd, as 'dup2' does
+    pub closed_by: Option<&'a [u8]>,
+    pub closed: Option<&'a [u8]>,
+}
+
+impl<'a> FdLife<'a> {
+    /// Opened in the trace and never closed
+    pub fn leaked(&self) -> bool {
+        self.opened.is_some() && self.closed.is_none()
+    }
+
+    /// How long the fd was held in ms, up to `end` for fds never closed.
+    /// Unknown for fds open before the trace.
+    pub fn held_ms(&self, end: &[u8]) -> Option<f32> {
+        time::elapsed_ms(self.opened?, self.closed.unwrap_or(end))
+    }
+}
+
+/// Fds never closed by one process
+#[derive(Clone, Debug, PartialEq)]
+pub struct Leaks {
+    pub pid: Pid,
+    pub kinds: BTreeMap<FdKind, usize>,
+    pub total: usize,
+    /// Whether the process exited, closing them, before the trace ended
+    pub exited: bool,
+}
+
+/// The fds of the processes of some PIDs, ordered by process and then by when
+/// each was opened
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FdLifecycle<'a> {
+    pub fds: Vec<FdLife<'a>>,
+    /// Each process's last call, when fds never closed are held until
+    pub end_times: HashMap<Pid, &'a [u8]>,
+    pub exited: Vec<Pid>,
+}
+
+impl<'a> FdLifecycle<'a> {
+    /// The fds each process never closed, by kind, in PID order
+    pub fn leaks(&self) -> Vec<Leaks> {
+        let mut leaks: BTreeMap<Pid, Leaks> = BTreeMap::new();
+        for life in self.fds.iter().filter(|life| life.leaked()) {
+            let leak = leaks.entry(life.pid).or_insert_with(|| Leaks {
+                pid: life.pid,
+                kinds: BTreeMap::new(),
+                total: 0,
+                exited: self.exited.contains(&life.pid),
+            });
+            if let Some(target) = life.target {
+                *leak.kinds.entry(FdKind::classify(target)).or_default() += 1;
+            }
+            leak.total += 1;
+        }
+
+        leaks.into_values().collect()
+    }
+
+    fn held_ms(&self, life: &FdLife) -> Option<f32> {
+        life.held_ms(self.end_times.get(&life.pid).copied().unwrap_or_default())
+    }
+}
+
+/// Replays the calls opening and closing fds in the processes of `pids`,
+/// pairing each fd with the file it referred to and the call that closed it.
+/// An fd opened again while open was replaced, as 'dup2' does, or closed in a
+/// way that wasn't traced, such as on 'execve' or by 'close_range'. Fds open
+/// before the trace are only listed once closed or replaced.
+pub fn fd_lifecycle<'a>(pids: &[Pid], raw_data: &HashMap<Pid, PidData<'a>>) -> FdLifecycle<'a> {
+    let mut lifecycle = FdLifecycle::default();
+
+    for (pid, group) in thread_groups(pids, raw_data) {
+        let first = lifecycle.fds.len();
+        // Indexes into `lifecycle.fds` of the fds open now, and every fd seen
+        let mut open: HashMap<i32, usize> = HashMap::default();
+        let mut seen: HashSet<i32> = HashSet::default();
+
+        for event in fd_events(&group, raw_data) {
+            let target = match event.other {
+                Some(OtherFields::Open { file, resolved, .. }) => Some(resolved.unwrap_or(file)),
+                _ => event.file(),
+            };
+            let fds = match event.other {
+                Some(OtherFields::Close { fd, file }) => {
+                    seen.insert(fd);
+                    match open.remove(&fd) {
+                        Some(idx) => {
+                            let life = &mut lifecycle.fds[idx];
+                            life.target = life.target.or(file);
+                            life.closed_by = Some(event.syscall);
+                            life.closed = Some(event.time);
+                        }
+                        None => lifecycle.fds.push(FdLife {
+                            pid,
+                            fd,
+                            target: file,
+                            opened_by: None,
+                            opened: None,
+                            closed_by: Some(event.syscall),
+                            closed: Some(event.time),
+                        }),
+                    }
+                    continue;
+                }
+                Some(OtherFields::FdPair { fds, .. }) => fds.to_vec(),
+                _ if event.syscall == b"close" => continue,
+                _ => match event.rtn_cd.filter(|fd| *fd >= 0) {
+                    Some(fd) => vec![fd],
+                    // Without '-T' the returned fd isn't read
+                    None => continue,
+                },
+            };
+
+            for fd in fds {
+                match open.get(&fd) {
+                    Some(&idx) => {
+                        let life = &mut lifecycle.fds[idx];
+                        life.closed_by = Some(event.syscall);
+                        life.closed = Some(event.time);
+                    }
+                    // Replacing an fd open before the trace, named by '-y'
+                    None if matches!(event.syscall, b"dup2" | b"dup3") && !seen.contains(&fd) => {
+                        let replaced = match event.other {
+                            Some(OtherFields::Dup { replaced, .. }) => replaced,
+                            _ => None,
+                        };
+                        lifecycle.fds.push(FdLife {
+                            pid,
+                            fd,
+                            target: replaced,
+                            opened_by: None,
+                            opened: None,
+                            closed_by: Some(event.syscall),
+                            closed: Some(event.time),
+                        })
+                    }
+                    None => {}
+                }
+                seen.insert(fd);
+                open.insert(fd, lifecycle.fds.len());
+                lifecycle.fds.push(FdLife {
+                    pid,
+                    fd,
+                    target,
+                    opened_by: Some(event.syscall),
+                    opened: Some(event.time),
+                    closed_by: None,
+                    closed: None,
+                });
+            }
+        }
+
+        // Fds open before the trace have no open time, and are placed by when
+        // they were closed
+        lifecycle.fds[first..].sort_by_key(|life| (life.opened.or(life.closed), life.fd));
+
+        let end_time = group.iter().map(|pid| raw_data[pid].end_time).max();
+        if let Some(end_time) = end_time {
+            lifecycle.end_times.insert(pid, end_time);
+        }
+        if raw_data[&pid].exit.is_some() {
+            lifecycle.exited.push(pid);
+        }
+    }
+
+    lifecycle
+}
+
+pub fn print_fd_lifecycle(
+    lifecycle: &FdLifecycle,
+    leaks_only: bool,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    output::print_heading("\nFile Descriptors\n-----------\n")?;
+
+    if lifecycle.fds.is_empty() {
+        output::print_note("  No fds opened or closed\n")?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("pid", 7, Align::Right, 0),
+        Column::new("fd", 5, Align::Right, 0),
+        Column::new("opened", 15, Align::Center, 2),
+        Column::new("opened by", 12, Align::Left, 3),
+        Column::new("held (ms)", 10, Align::Right, 1),
+        Column::new("closed by", 12, Align::Left, 1),
+        Column::new("target", 30, Align::Left, 0),
+    ]);
+
+    for life in &lifecycle.fds {
+        if leaks_only && !life.leaked() {
+            continue;
+        }
+        let closed_by = match (life.closed_by, life.leaked()) {
+            (Some(syscall), _) => syscall.to_str_lossy().to_string(),
+            (None, true) => String::from("never"),
+            (None, false) => String::from("-"),
+        };
+
+        table.add_row(vec![
+            output::pid_label(life.pid),
+            life.fd.to_string(),
+            life.opened
+                .map_or_else(|| String::from("before trace"), output::timestamp),
+            life.opened_by.unwrap_or(b"-").to_str_lossy().to_string(),
+            lifecycle
+                .held_ms(life)
+                .map_or_else(|| String::from("-"), |ms| format!("{:.3}", ms)),
+            closed_by,
+            life.target.unwrap_or(b"-").to_str_lossy().to_string(),
+        ]);
+    }
+    table.print(width)?;
+
+    let opened = lifecycle.fds.iter().filter(|l| l.opened.is_some()).count();
+    let closed = lifecycle.fds.iter().filter(|l| l.closed.is_some()).count();
+    let leaks = lifecycle.leaks();
+    writeln!(
+        stdout(),
+        "\n  {} fds opened and {} open before the trace, {} closed, {} never closed",
+        opened,
+        lifecycle.fds.len() - opened,
+        closed,
+        leaks.iter().map(|l| l.total).sum::<usize>()
+    )?;
+
+    if !leaks.is_empty() {
+        output::print_heading("\nNever Closed\n-----------\n")?;
+        for leak in &leaks {
+            let kinds: Vec<_> = leak
+                .kinds
+                .iter()
+                .map(|(kind, ct)| format!("{} {}", kind, ct))
+                .collect();
+            writeln!(
+                stdout(),
+                "  {: >7}  {: >5}  {}{}",
+                output::pid_label(leak.pid),
+                leak.total,
+                if leak.exited {
+                    "closed on exit"
+                } else {
+                    "still open at the end of the trace"
+                },
+                if kinds.is_empty() {
+                    String::new()
+                } else {
+                    format!(", {}", kinds.join(", "))
+                }
+            )?;
+        }
+    }
+    writeln!(stdout())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn fd_lifecycle_pairs_opens_with_closes() {
+        let input = br##"477   10:00:00.000000 openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = 3</etc/hosts> <0.000010>
+477   10:00:00.100000 socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 4<TCP:[61612]> <0.000010>
+477   10:00:00.200000 pipe2([5<pipe:[700]>, 6<pipe:[700]>], O_CLOEXEC) = 0 <0.000010>
+477   10:00:00.300000 close(3</etc/hosts>) = 0 <0.000010>
+477   10:00:00.400000 close(9</var/log/app.log>) = 0 <0.000010>
+477   10:00:00.500000 dup2(4<TCP:[61612]>, 1</dev/pts/0>) = 1<TCP:[61612]> <0.000010>
+477   10:00:00.600000 openat(AT_FDCWD, "/missing", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000010>
+477   10:00:00.700000 close(5<pipe:[700]>) = 0 <0.000010>
+477   10:00:01.000000 openat(AT_FDCWD, "/tmp/a", O_RDONLY) = 5</tmp/a> <0.000010>
+477   10:00:01.100000 openat(AT_FDCWD, "/tmp/b", O_RDONLY) = 5</tmp/b> <0.000010>
+477   10:00:02.000000 +++ exited with 0 +++"##;
+        let pid_data_map = build_syscall_data(input);
+        let lifecycle = fd_lifecycle(&[477], &pid_data_map);
+
+        let summary: Vec<_> = lifecycle
+            .fds
+            .iter()
+            .map(|l| (l.fd, l.target.unwrap_or_default(), l.closed_by))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (3, b"/etc/hosts".as_ref(), Some(b"close".as_ref())),
+                (4, b"TCP:[61612]", None),
+                (5, b"pipe:[700]", Some(b"close")),
+                (6, b"pipe:[700]", None),
+                (9, b"/var/log/app.log", Some(b"close")),
+                (1, b"/dev/pts/0", Some(b"dup2")),
+                (1, b"TCP:[61612]", None),
+                (5, b"/tmp/a", Some(b"openat")),
+                (5, b"/tmp/b", None),
+            ]
+        );
+        assert_eq!(lifecycle.held_ms(&lifecycle.fds[0]), Some(300.0));
+        assert_eq!(lifecycle.held_ms(&lifecycle.fds[4]), None);
+        // Held until the process's last call
+        let held = lifecycle.held_ms(&lifecycle.fds[1]).unwrap();
+        assert!((held - 1000.0).abs() < 0.01);
+
+        let leaks = lifecycle.leaks();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!((leaks[0].total, leaks[0].exited), (4, true));
+        assert_eq!(leaks[0].kinds.get(&FdKind::Socket), Some(&2));
+    }
+}
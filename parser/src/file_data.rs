@@ -1,9 +1,12 @@
+use crate::output;
 use crate::parser::{CallStatus, RawData};
+use crate::path_aliases::PathAliases;
 use crate::syscall_data::PidData;
 use crate::{HashMap, Pid};
 
 use bstr::ByteSlice;
 use rayon::prelude::*;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fmt;
 
@@ -11,6 +14,7 @@ use std::fmt;
 pub struct FileData<'a> {
     pub pid: Pid,
     pub time: &'a [u8],
+    pub syscall: &'a [u8],
     pub file: &'a [u8],
     pub error: Option<&'a [u8]>,
     pub duration: f32,
@@ -20,6 +24,7 @@ impl<'a> FileData<'a> {
     fn new(
         pid: Pid,
         time: &'a [u8],
+        syscall: &'a [u8],
         file_opt: Option<&'a [u8]>,
         error: Option<&'a [u8]>,
         duration_opt: Option<f32>,
@@ -27,6 +32,7 @@ impl<'a> FileData<'a> {
         FileData {
             pid,
             time,
+            syscall,
             file: file_opt.unwrap_or_default(),
             error,
             duration: duration_opt.map_or(0.0, |dur| dur * 1000.0),
@@ -34,11 +40,12 @@ impl<'a> FileData<'a> {
     }
 }
 
-impl<'a, 'b> From<&'b RawData<'a>> for FileData<'a> {
+impl<'a> From<&RawData<'a>> for FileData<'a> {
     fn from(raw_data: &RawData<'a>) -> FileData<'a> {
         FileData {
             pid: raw_data.pid,
             time: raw_data.time,
+            syscall: raw_data.syscall,
             file: raw_data.file().unwrap_or_default(),
             error: raw_data.error,
             duration: raw_data.duration.map_or(0.0, |dur| dur * 1000.0),
@@ -52,15 +59,85 @@ impl<'a> fmt::Display for FileData<'a> {
 
         write!(
             f,
-            "{: >10.3}    {: ^15}    {: ^15}    {: <30}",
+            "{: >10.3}    {: ^15}    {: <14}    {: ^15}    {: <30}",
             self.duration,
-            self.time.to_str_lossy(),
+            output::timestamp(self.time),
+            self.syscall.to_str_lossy(),
             error.to_str_lossy(),
             self.file.to_str_lossy()
         )
     }
 }
 
+/// The opens of one path by any number of PIDs, such as a shared library
+/// loaded by every child process
+#[derive(Clone, Debug, PartialEq)]
+pub struct SharedFile<'a> {
+    pub file: Cow<'a, [u8]>,
+    pub first_time: &'a [u8],
+    pub opens: usize,
+    pub errors: usize,
+    /// Total time in the opens, in ms
+    pub duration: f32,
+    /// Time each PID spent opening the path, in ms
+    pub pid_durations: BTreeMap<Pid, f32>,
+}
+
+impl<'a> SharedFile<'a> {
+    pub fn pids(&self) -> usize {
+        self.pid_durations.len()
+    }
+
+    /// The mean of each PID's total time opening the path, in ms
+    pub fn mean_per_pid(&self) -> f32 {
+        self.duration / self.pids().max(1) as f32
+    }
+
+    /// The most time one PID spent opening the path, in ms
+    pub fn max_per_pid(&self) -> f32 {
+        self.pid_durations.values().copied().fold(0.0, f32::max)
+    }
+}
+
+/// Merges `open_events` of the same path into one `SharedFile`, with paths
+/// opened through an alias merged under the path it refers to. Paths are in
+/// the order first opened.
+pub fn shared_files<'a>(
+    open_events: &[&FileData<'a>],
+    path_aliases: &PathAliases,
+) -> Vec<SharedFile<'a>> {
+    let mut files: Vec<SharedFile> = Vec::new();
+    let mut indexes: HashMap<Cow<[u8]>, usize> = HashMap::default();
+
+    for event in open_events {
+        let file = path_aliases.resolve(event.file);
+        let idx = *indexes.entry(file.clone()).or_insert_with(|| {
+            files.push(SharedFile {
+                file,
+                first_time: event.time,
+                opens: 0,
+                errors: 0,
+                duration: 0.0,
+                pid_durations: BTreeMap::new(),
+            });
+            files.len() - 1
+        });
+
+        let shared = &mut files[idx];
+        shared.first_time = shared.first_time.min(event.time);
+        shared.opens += 1;
+        if event.error.is_some() {
+            shared.errors += 1;
+        }
+        shared.duration += event.duration;
+        *shared.pid_durations.entry(event.pid).or_default() += event.duration;
+    }
+
+    files.par_sort_by(|x, y| (x.first_time).cmp(y.first_time));
+
+    files
+}
+
 pub enum SortFilesBy {
     Duration,
     Time,
@@ -74,17 +151,17 @@ pub fn files_opened<'a>(
     pids.par_iter()
         .map(|pid| {
             let mut open_events = raw_data[pid].open_events.clone();
-            open_events.par_sort_unstable_by(|x, y| (x.time).cmp(&y.time));
+            open_events.par_sort_unstable_by(|x, y| (x.time).cmp(y.time));
 
             let mut coalesced_data: Vec<_> = coalesce_file_data(open_events.as_slice());
 
             match sort_by {
                 SortFilesBy::Duration => coalesced_data.par_sort_by(|x, y| {
-                    (&y.duration)
+                    y.duration
                         .partial_cmp(&x.duration)
                         .expect("Invalid comparison when sorting file open times")
                 }),
-                SortFilesBy::Time => coalesced_data.par_sort_by(|x, y| (x.time).cmp(&y.time)),
+                SortFilesBy::Time => coalesced_data.par_sort_by(|x, y| (x.time).cmp(y.time)),
             }
             (*pid, coalesced_data)
         })
@@ -101,6 +178,7 @@ fn coalesce_file_data<'a>(file_data: &[RawData<'a>]) -> Vec<FileData<'a>> {
                 complete_entries.push(FileData::new(
                     entry.pid,
                     entry.time,
+                    entry.syscall,
                     entry.file(),
                     entry.error,
                     entry.duration,
@@ -108,10 +186,12 @@ fn coalesce_file_data<'a>(file_data: &[RawData<'a>]) -> Vec<FileData<'a>> {
             }
             CallStatus::Started => {
                 if let Some(next_entry) = iter.next() {
+                    // Calls such as 'accept' only name the new fd when they return
                     complete_entries.push(FileData::new(
                         entry.pid,
                         entry.time,
-                        entry.file(),
+                        entry.syscall,
+                        next_entry.file().or_else(|| entry.file()),
                         next_entry.error,
                         next_entry.duration,
                     ));
@@ -123,3 +203,31 @@ fn coalesce_file_data<'a>(file_data: &[RawData<'a>]) -> Vec<FileData<'a>> {
 
     complete_entries
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn shared_files_merge_pids() {
+        let input = br##"600   00:09:56.000000 openat(AT_FDCWD, "/lib/x86_64-linux-gnu/libc.so.6", O_RDONLY|O_CLOEXEC) = 3</lib/x86_64-linux-gnu/libc.so.6> <0.000100>
+601   00:09:56.100000 openat(AT_FDCWD, "/lib/x86_64-linux-gnu/libc.so.6", O_RDONLY|O_CLOEXEC) = 3</lib/x86_64-linux-gnu/libc.so.6> <0.000300>
+601   00:09:56.200000 openat(AT_FDCWD, "/lib/x86_64-linux-gnu/libc.so.6", O_RDONLY|O_CLOEXEC) = 4</lib/x86_64-linux-gnu/libc.so.6> <0.000200>
+602   00:09:56.050000 openat(AT_FDCWD, "/etc/ld.so.preload", O_RDONLY|O_CLOEXEC) = -1 ENOENT (No such file or directory) <0.000050>
+602   00:09:56.300000 openat(AT_FDCWD, "/lib/x86_64-linux-gnu/libc.so.6", O_RDONLY|O_CLOEXEC) = 3</lib/x86_64-linux-gnu/libc.so.6> <0.000600>"##;
+        let pid_data_map = build_syscall_data(input);
+        let opened = files_opened(&[600, 601, 602], &pid_data_map, SortFilesBy::Time);
+        let events: Vec<_> = opened.values().flatten().collect();
+        let files = shared_files(&events, &PathAliases::default());
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].file.as_ref(), b"/lib/x86_64-linux-gnu/libc.so.6");
+        assert_eq!(files[0].opens, 4);
+        assert_eq!(files[0].pids(), 3);
+        assert!((files[0].mean_per_pid() - 0.4).abs() < 1e-4);
+        assert!((files[0].max_per_pid() - 0.6).abs() < 1e-4);
+        assert_eq!(files[1].first_time, b"00:09:56.050000");
+        assert_eq!(files[1].errors, 1);
+    }
+}
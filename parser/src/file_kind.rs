@@ -0,0 +1,200 @@
+use crate::file_data::FileData;
+use crate::output;
+use crate::path_aliases::PathAliases;
+use crate::table::{Align, Column, Table};
+
+use bstr::ByteSlice;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Error;
+
+/// A rough category for a path, guessed from its location and name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FileKind {
+    Library,
+    Config,
+    Log,
+    Temp,
+    Device,
+    Pseudo,
+    Other,
+}
+
+impl FileKind {
+    pub const ALL: [FileKind; 7] = [
+        FileKind::Library,
+        FileKind::Config,
+        FileKind::Log,
+        FileKind::Temp,
+        FileKind::Device,
+        FileKind::Pseudo,
+        FileKind::Other,
+    ];
+
+    pub fn classify(path: &[u8]) -> FileKind {
+        let name = path.rsplit_str("/").next().unwrap_or(path);
+
+        // Checked first so the loader's /etc/ld.so.cache counts as loading libraries
+        if is_shared_library(name) || name.starts_with(b"ld.so.") {
+            FileKind::Library
+        } else if path.starts_with(b"/proc/")
+            || path.starts_with(b"/sys/")
+            || path == b"/proc"
+            || path == b"/sys"
+        {
+            FileKind::Pseudo
+        } else if path.starts_with(b"/tmp/")
+            || path.starts_with(b"/var/tmp/")
+            || path.starts_with(b"/dev/shm/")
+            || name.ends_with(b".tmp")
+            || name.ends_with(b"~")
+        {
+            FileKind::Temp
+        } else if path.starts_with(b"/dev/") {
+            FileKind::Device
+        } else if path.starts_with(b"/var/log/") || name.ends_with(b".log") {
+            FileKind::Log
+        } else if path.starts_with(b"/etc/") || name.ends_with(b".conf") {
+            FileKind::Config
+        } else {
+            FileKind::Other
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            FileKind::Library => "shared library, or the loader's cache",
+            FileKind::Config => "config under /etc, or named *.conf",
+            FileKind::Log => "log under /var/log, or named *.log",
+            FileKind::Temp => "temp file under /tmp, /var/tmp, or /dev/shm",
+            FileKind::Device => "device node under /dev",
+            FileKind::Pseudo => "/proc or /sys pseudo-file",
+            FileKind::Other => "anything else, including sockets and pipes",
+        }
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            FileKind::Library => "lib",
+            FileKind::Config => "config",
+            FileKind::Log => "log",
+            FileKind::Temp => "temp",
+            FileKind::Device => "device",
+            FileKind::Pseudo => "pseudo",
+            FileKind::Other => "other",
+        };
+        f.pad(label)
+    }
+}
+
+// 'libc.so', 'libc.so.6', or 'libruby.so.2.7'
+fn is_shared_library(name: &[u8]) -> bool {
+    match name.find(b".so") {
+        Some(idx) => name[idx + 3..]
+            .split_str(".")
+            .all(|part| part.iter().all(u8::is_ascii_digit)),
+        None => false,
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct KindTotals {
+    pub ct: usize,
+    pub duration: f32,
+    pub errors: usize,
+}
+
+/// Totals of `files` by kind, classified by the path their name is an alias of
+pub fn kind_totals(files: &[&FileData], aliases: &PathAliases) -> BTreeMap<FileKind, KindTotals> {
+    let mut totals: BTreeMap<FileKind, KindTotals> = BTreeMap::new();
+
+    for file in files {
+        let kind = FileKind::classify(&aliases.resolve(file.file));
+        let entry = totals.entry(kind).or_default();
+        entry.ct += 1;
+        entry.duration += file.duration;
+        if file.error.is_some() {
+            entry.errors += 1;
+        }
+    }
+
+    totals
+}
+
+pub fn print_kind_totals(
+    files: &[&FileData],
+    aliases: &PathAliases,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let totals = kind_totals(files, aliases);
+
+    output::print_heading("\nFiles by Kind\n")?;
+    let mut table = Table::new(vec![
+        Column::new("kind", 6, Align::Left, 0),
+        Column::new("calls", 8, Align::Right, 0),
+        Column::new("dur (ms)", 10, Align::Right, 1),
+        Column::new("errors", 8, Align::Right, 2),
+        Column::new("description", 11, Align::Left, 3),
+    ]);
+
+    for kind in FileKind::ALL {
+        let total = totals.get(&kind).copied().unwrap_or_default();
+        table.add_row(vec![
+            kind.to_string(),
+            total.ct.to_string(),
+            format!("{:.3}", total.duration),
+            total.errors.to_string(),
+            kind.description().to_string(),
+        ]);
+    }
+    table.print(width)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_kind_classifies_paths() {
+        let cases: [(&[u8], FileKind); 12] = [
+            (b"/lib64/libpthread.so.0", FileKind::Library),
+            (
+                b"/opt/gitlab/embedded/lib/libruby.so.2.7",
+                FileKind::Library,
+            ),
+            (b"/etc/ld.so.cache", FileKind::Library),
+            (b"/etc/nsswitch.conf", FileKind::Config),
+            (b"/var/log/gitlab/gitlab-rails/api_json.log", FileKind::Log),
+            (b"/tmp/ruby-uploads20200101-1-abc", FileKind::Temp),
+            (b"/dev/shm/sem.lock", FileKind::Temp),
+            (b"/dev/null", FileKind::Device),
+            (b"/proc/self/status", FileKind::Pseudo),
+            (b"/sys/fs/cgroup/memory.max", FileKind::Pseudo),
+            (
+                b"/opt/gitlab/embedded/lib/ruby/2.7.0/json.rb",
+                FileKind::Other,
+            ),
+            (b"socket:[3578440]", FileKind::Other),
+        ];
+
+        for (path, kind) in cases.iter() {
+            assert_eq!(FileKind::classify(path), *kind, "{}", path.to_str_lossy());
+        }
+    }
+
+    #[test]
+    fn file_kind_library_needs_so_suffix() {
+        assert_eq!(
+            FileKind::classify(b"/usr/share/sounds/solar.ogg"),
+            FileKind::Other
+        );
+        assert_eq!(
+            FileKind::classify(b"/usr/lib/python3/json.so.bak"),
+            FileKind::Other
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use crate::output;
+use crate::parser::{CallStatus, OtherFields};
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::collections::BTreeSet;
+use std::io::{prelude::*, stdout, Error};
+
+/// Lock names keyed by the address of the lock
+pub type LockNames = HashMap<u64, String>;
+
+/// Time spent waiting on a single private futex
+#[derive(Clone, Debug, PartialEq)]
+pub struct FutexContention<'a> {
+    pub addr: &'a [u8],
+    pub waits: usize,
+    /// Total and longest wait in seconds, only known for lines traced with '-T'
+    pub total_wait: f32,
+    pub max_wait: f32,
+    pub pids: BTreeSet<Pid>,
+}
+
+/// Waits on private futexes by `pids`, grouped by address with the most time
+/// spent waiting first. A wait split across lines takes its duration from the
+/// line where it resumed.
+pub fn futex_contention<'a>(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData<'a>>,
+) -> Vec<FutexContention<'a>> {
+    let mut contention: HashMap<&[u8], FutexContention> = HashMap::default();
+
+    for pid in pids {
+        let mut events = raw_data[pid].futex_events.clone();
+        events.sort_by(|x, y| (x.time).cmp(y.time));
+
+        let mut pending: Option<&[u8]> = None;
+        for event in &events {
+            let (addr, duration) = match (event.call_status, event.other.as_ref()) {
+                (CallStatus::Complete, Some(OtherFields::Futex { addr, wait: true })) => {
+                    (*addr, event.duration)
+                }
+                (CallStatus::Started, Some(OtherFields::Futex { addr, wait: true })) => {
+                    pending = Some(addr);
+                    continue;
+                }
+                (CallStatus::Resumed, _) => match pending.take() {
+                    Some(addr) => (addr, event.duration),
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            let entry = contention.entry(addr).or_insert_with(|| FutexContention {
+                addr,
+                waits: 0,
+                total_wait: 0.0,
+                max_wait: 0.0,
+                pids: BTreeSet::new(),
+            });
+            entry.waits += 1;
+            entry.total_wait += duration.unwrap_or_default();
+            entry.max_wait = entry.max_wait.max(duration.unwrap_or_default());
+            entry.pids.insert(*pid);
+        }
+    }
+
+    let mut contention: Vec<_> = contention.into_values().collect();
+    contention.sort_by(|x, y| {
+        (y.total_wait)
+            .total_cmp(&x.total_wait)
+            .then_with(|| y.waits.cmp(&x.waits))
+            .then_with(|| x.addr.cmp(y.addr))
+    });
+
+    contention
+}
+
+/// Parses a map of lock addresses to names, one per line. The first field is
+/// the address in hex and the last is the name, so both 'ADDR NAME' lines and
+/// 'nm' output such as '00000000004c6f40 B global_lock' are accepted. Blank
+/// lines and anything after a '#' are ignored.
+pub fn parse_lock_names(text: &str) -> Result<LockNames, String> {
+    let mut names = LockNames::default();
+
+    for (idx, line) in text.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(pos) => &line[..pos],
+            None => line,
+        }
+        .trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+        match (fields.next().and_then(parse_addr), fields.last()) {
+            (Some(addr), Some(name)) => {
+                names.insert(addr, name.to_string());
+            }
+            _ => return Err(format!("Invalid lock name '{}' on line {}", line, idx + 1)),
+        }
+    }
+
+    Ok(names)
+}
+
+fn parse_addr(addr: &str) -> Option<u64> {
+    let addr = addr
+        .strip_prefix("0x")
+        .or_else(|| addr.strip_prefix("0X"))
+        .unwrap_or(addr);
+    u64::from_str_radix(addr, 16).ok()
+}
+
+fn lock_name<'n>(addr: &[u8], lock_names: &'n LockNames) -> Option<&'n str> {
+    let addr = parse_addr(addr.to_str().ok()?)?;
+    lock_names.get(&addr).map(String::as_str)
+}
+
+pub fn print_futex_contention(
+    contention: &[FutexContention],
+    lock_names: &LockNames,
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    output::print_heading("\nFutex Contention\n-----------\n")?;
+
+    if contention.is_empty() {
+        output::print_note("  No waits on private futexes found\n")?;
+        return Ok(());
+    }
+
+    let mut columns = vec![Column::new("address", 14, Align::Left, 0)];
+    if !lock_names.is_empty() {
+        columns.push(Column::new("lock", 12, Align::Left, 0));
+    }
+    columns.extend(vec![
+        Column::new("waits", 7, Align::Right, 0),
+        Column::new("total ms", 10, Align::Right, 0),
+        Column::new("max ms", 10, Align::Right, 1),
+        Column::new("pids", 4, Align::Right, 2),
+    ]);
+    let mut table = Table::new(columns);
+
+    for futex in contention.iter().take(count) {
+        let mut row = vec![futex.addr.to_str_lossy().to_string()];
+        if !lock_names.is_empty() {
+            row.push(lock_name(futex.addr, lock_names).unwrap_or("-").to_string());
+        }
+        row.extend(vec![
+            futex.waits.to_string(),
+            format!("{:.3}", futex.total_wait * 1000.0),
+            format!("{:.3}", futex.max_wait * 1000.0),
+            futex.pids.len().to_string(),
+        ]);
+        table.add_row(row);
+    }
+    table.print(width)?;
+    writeln!(stdout())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn futex_contention_sums_waits_by_address() {
+        let input = br##"11638 11:34:25.556415 futex(0x7ffa50080ff4, FUTEX_WAIT_PRIVATE, 27, NULL <unfinished ...>
+11639 11:34:25.556500 futex(0x7ffa50080ff4, FUTEX_WAIT_BITSET_PRIVATE|FUTEX_CLOCK_REALTIME, 0, NULL, FUTEX_BITSET_MATCH_ANY) = -1 EAGAIN (Resource temporarily unavailable) <0.000010>
+11640 11:34:25.556600 futex(0x7ffa50080ff4, FUTEX_WAKE_PRIVATE, 1) = 1 <0.000020>
+11638 11:34:25.566415 <... futex resumed> ) = 0 <0.010000>
+11639 11:34:25.570000 futex(0x535c890, FUTEX_WAIT_PRIVATE, 2, NULL) = 0 <0.001000>
+11639 11:34:25.580000 futex(0xc420061548, FUTEX_WAIT, 0, NULL) = 0 <0.500000>"##;
+        let pid_data_map = build_syscall_data(input);
+        let contention = futex_contention(&[11638, 11639, 11640], &pid_data_map);
+
+        assert_eq!(contention.len(), 2);
+        assert_eq!(contention[0].addr, b"0x7ffa50080ff4");
+        assert_eq!(contention[0].waits, 2);
+        assert!((contention[0].total_wait - 0.01001).abs() < 1e-6);
+        assert!((contention[0].max_wait - 0.01).abs() < 1e-6);
+        assert_eq!(
+            contention[0].pids.iter().collect::<Vec<_>>(),
+            [&11638, &11639]
+        );
+        assert_eq!(contention[1].addr, b"0x535c890");
+    }
+
+    #[test]
+    fn futex_parses_lock_names() {
+        let input =
+            "# exported from app\n0x7ffa50080ff4 db_pool_mutex\n00000000004c6f40 B global_lock\n";
+        let names = parse_lock_names(input).unwrap();
+
+        assert_eq!(lock_name(b"0x7ffa50080ff4", &names), Some("db_pool_mutex"));
+        assert_eq!(lock_name(b"0x4c6f40", &names), Some("global_lock"));
+        assert_eq!(lock_name(b"0x535c890", &names), None);
+        assert_eq!(
+            parse_lock_names("db_pool_mutex\n"),
+            Err(String::from("Invalid lock name 'db_pool_mutex' on line 1"))
+        );
+    }
+}
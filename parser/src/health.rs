@@ -0,0 +1,172 @@
+use crate::output;
+use crate::Pid;
+
+use std::io::Error;
+
+// The share of failed calls that costs nothing, and the share costing the most
+const ERROR_RATE: (f64, f64) = (0.02, 0.3);
+// Servers spend most of their time waiting, so only near-total waiting counts
+const WAIT_SHARE: (f64, f64) = (0.95, 1.0);
+// Fds held at once by one process
+const FD_PEAK: (f64, f64) = (512.0, 4096.0);
+// Processes started per second of the trace
+const PROCESS_CHURN: (f64, f64) = (1.0, 20.0);
+
+const MAX_ERROR_PENALTY: f64 = 40.0;
+const MAX_WAIT_PENALTY: f64 = 10.0;
+const MAX_FD_PENALTY: f64 = 25.0;
+const MAX_CHURN_PENALTY: f64 = 25.0;
+
+/// Totals for a trace that hint at trouble
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Signals {
+    pub calls: i64,
+    pub errors: i64,
+    /// Time in calls that wait, such as 'futex' and 'epoll_wait', in ms
+    pub wait_time: f64,
+    /// Time in all other calls, in ms
+    pub active_time: f64,
+    /// The most fds one process held at once, and its PID
+    pub fd_peak: Option<(Pid, usize)>,
+    /// Child processes started, not counting threads
+    pub processes: usize,
+    pub traced_secs: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    /// Points taken off the score
+    pub penalty: f64,
+    pub message: String,
+}
+
+/// A score from 0 to 100 and the findings that lowered it, worst first
+#[derive(Clone, Debug, PartialEq)]
+pub struct Health {
+    pub score: u32,
+    pub findings: Vec<Finding>,
+    /// Signs the tracing itself changed what was traced, which may explain
+    /// odd behavior but don't count against the score
+    pub caveats: Vec<String>,
+}
+
+impl Health {
+    pub fn from_signals(signals: &Signals) -> Health {
+        let mut findings = Vec::new();
+
+        if signals.calls > 0 {
+            let rate = signals.errors as f64 / signals.calls as f64;
+            findings.push(Finding {
+                penalty: MAX_ERROR_PENALTY * scale(rate, ERROR_RATE),
+                message: format!("{:.0}% of calls failed", rate * 100.0),
+            });
+        }
+
+        let syscall_time = signals.wait_time + signals.active_time;
+        if syscall_time > 0.0 {
+            let share = signals.wait_time / syscall_time;
+            findings.push(Finding {
+                penalty: MAX_WAIT_PENALTY * scale(share, WAIT_SHARE),
+                message: format!(
+                    "{:.0}% of syscall time spent waiting on locks, polls, and children",
+                    share * 100.0
+                ),
+            });
+        }
+
+        if let Some((pid, peak)) = signals.fd_peak {
+            findings.push(Finding {
+                penalty: MAX_FD_PENALTY * scale(peak as f64, FD_PEAK),
+                message: format!("PID {} held {} fds at once", pid, peak),
+            });
+        }
+
+        if signals.traced_secs > 0.0 {
+            let rate = signals.processes as f64 / signals.traced_secs;
+            findings.push(Finding {
+                penalty: MAX_CHURN_PENALTY * scale(rate, PROCESS_CHURN),
+                message: format!(
+                    "{} processes started, {:.1} per second",
+                    signals.processes, rate
+                ),
+            });
+        }
+
+        findings.retain(|f| f.penalty > 0.0);
+        findings.sort_by(|x, y| y.penalty.total_cmp(&x.penalty));
+
+        let penalty: f64 = findings.iter().map(|f| f.penalty).sum();
+        Health {
+            score: (100.0 - penalty).round().max(0.0) as u32,
+            findings,
+            caveats: Vec::new(),
+        }
+    }
+
+    pub fn verdict(&self) -> &'static str {
+        match self.score {
+            80..=100 => "looks healthy",
+            50..=79 => "worth a closer look",
+            _ => "looks pathological",
+        }
+    }
+}
+
+// 0 at or below `low`, rising to 1 at `high`
+fn scale(value: f64, (low, high): (f64, f64)) -> f64 {
+    ((value - low) / (high - low)).clamp(0.0, 1.0)
+}
+
+pub fn print_verdict(health: &Health) -> Result<(), Error> {
+    let finding = match health.findings.first() {
+        Some(finding) => finding.message.as_str(),
+        None => "no warning signs",
+    };
+
+    output::print_note(&format!(
+        "  Health: {}/100, {} - {}",
+        health.score,
+        health.verdict(),
+        finding
+    ))?;
+    for caveat in &health.caveats {
+        output::print_note(&format!("  Tracing: {}", caveat))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_scores_worst_finding_first() {
+        let quiet = Signals {
+            calls: 1000,
+            errors: 5,
+            wait_time: 800.0,
+            active_time: 200.0,
+            fd_peak: Some((477, 40)),
+            processes: 2,
+            traced_secs: 10.0,
+        };
+        let health = Health::from_signals(&quiet);
+        assert_eq!(health.score, 100);
+        assert!(health.findings.is_empty());
+        assert_eq!(health.verdict(), "looks healthy");
+
+        let busy = Signals {
+            errors: 160,
+            fd_peak: Some((495, 4096)),
+            processes: 300,
+            ..quiet
+        };
+        let health = Health::from_signals(&busy);
+        assert_eq!(health.score, 30);
+        assert_eq!(health.verdict(), "looks pathological");
+        assert_eq!(health.findings.len(), 3);
+        assert_eq!(health.findings[0].message, "PID 495 held 4096 fds at once");
+        assert_eq!(health.findings[2].message, "16% of calls failed");
+    }
+}
@@ -1,10 +1,49 @@
+use crate::output;
+use crate::parser::{self, CallStatus, LineData};
 use crate::syscall_data::PidData;
-use crate::HashMap;
 use crate::Pid;
+use crate::{HashMap, HashSet};
 
 use bstr::ByteSlice;
+use rayon::prelude::*;
 use std::collections::BTreeMap;
 use std::io::{prelude::*, stdout, Error};
+use std::str::FromStr;
+
+/// What each call is bucketed by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuantizeValue {
+    Duration,
+    RetVal,
+    /// The argument at this position, counting from 1
+    Arg(usize),
+}
+
+impl FromStr for QuantizeValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "duration" => Ok(QuantizeValue::Duration),
+            "retval" => Ok(QuantizeValue::RetVal),
+            _ => match s.strip_prefix("arg:").map(|n| n.parse::<usize>()) {
+                Some(Ok(n)) if n > 0 => Ok(QuantizeValue::Arg(n)),
+                _ => Err(String::from(
+                    "VALUE must be 'duration', 'retval', or 'arg:N' with N counting from 1",
+                )),
+            },
+        }
+    }
+}
+
+/// Calls bucketed by the log\u{2082} of their return value or an argument
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValueDistribution {
+    pub distribution: BTreeMap<u32, i32>,
+    /// Calls whose value wasn't a non-negative integer, such as failed calls
+    /// or a flag argument
+    pub skipped: usize,
+}
 
 pub fn print_histogram(
     syscall: &[u8],
@@ -13,39 +52,119 @@ pub fn print_histogram(
 ) -> Result<(), Error> {
     let distribution = build_distribution(syscall, pids, syscall_data);
 
-    let pid_list = build_pid_list(pids);
+    if distribution.is_empty() {
+        output::print_note(&format!("No data found for {}", syscall.to_str_lossy()))?;
+        return Ok(());
+    }
 
-    let max = match distribution.values().max() {
-        Some(m) => *m,
-        None => {
-            writeln!(stdout(), "No data found for {}", syscall.to_str_lossy())?;
-            return Ok(());
-        }
-    };
+    print_header(syscall, pids)?;
+    print_distribution(&distribution, "\u{03BC}secs")?;
+    writeln!(stdout())?;
+
+    Ok(())
+}
+
+pub fn print_outcome_histograms(
+    syscall: &[u8],
+    pids: &[Pid],
+    syscall_data: &HashMap<Pid, PidData>,
+) -> Result<(), Error> {
+    let distributions = build_outcome_distributions(syscall, pids, syscall_data);
+
+    if distributions.is_empty() {
+        output::print_note(&format!("No data found for {}", syscall.to_str_lossy()))?;
+        return Ok(());
+    }
+
+    print_header(syscall, pids)?;
+
+    for (outcome, distribution) in distributions {
+        writeln!(
+            stdout(),
+            "  outcome: {} ({} calls)\n",
+            outcome.to_str_lossy(),
+            distribution.values().sum::<i32>()
+        )?;
+        print_distribution(&distribution, "\u{03BC}secs")?;
+        writeln!(stdout())?;
+    }
+
+    Ok(())
+}
+
+/// Histograms the return value or an argument of `syscall`, such as the
+/// bytes returned by 'read' or the count requested with 'arg:3'. Errors are
+/// never counted, as their return value is always -1.
+pub fn print_value_histogram(
+    buffer: &[u8],
+    syscall: &[u8],
+    pids: &[Pid],
+    value: QuantizeValue,
+) -> Result<(), Error> {
+    let values = build_value_distribution(buffer, syscall, pids, value);
+
+    if values.distribution.is_empty() && values.skipped == 0 {
+        output::print_note(&format!("No data found for {}", syscall.to_str_lossy()))?;
+        return Ok(());
+    }
+
+    print_header(syscall, pids)?;
+    if !values.distribution.is_empty() {
+        print_distribution(&values.distribution, "value")?;
+    }
+
+    if values.skipped > 0 {
+        let desc = match value {
+            QuantizeValue::Arg(n) => format!("argument {} wasn't", n),
+            _ => String::from("return value wasn't"),
+        };
+        output::print_note(&format!(
+            "\n  {} {} not counted, their {} a non-negative integer",
+            values.skipped,
+            if values.skipped == 1 {
+                "call was"
+            } else {
+                "calls were"
+            },
+            desc
+        ))?;
+    }
+    writeln!(stdout())?;
+
+    Ok(())
+}
 
-    writeln!(
-        stdout(),
+fn print_header(syscall: &[u8], pids: &[Pid]) -> Result<(), Error> {
+    output::print_heading(&format!(
         "\n  syscall: {}\n  pids: {}\n",
         syscall.to_str_lossy(),
-        pid_list
-    )?;
-    writeln!(
-        stdout(),
-        "       {: ^5}        {: >8}     {: <12}",
-        "\u{03BC}secs",
-        "count",
-        "distribution",
-    )?;
-    writeln!(
-        stdout(),
-        "    ------------    --------     ----------------------------------------",
-    )?;
+        build_pid_list(pids)
+    ))
+}
+
+fn print_distribution(distribution: &BTreeMap<u32, i32>, unit: &str) -> Result<(), Error> {
+    let max = distribution.values().max().copied().unwrap_or_default();
+
+    if output::headers() {
+        writeln!(
+            stdout(),
+            "       {: ^5}        {: >8}     {: <12}",
+            unit,
+            "count",
+            "distribution",
+        )?;
+        writeln!(
+            stdout(),
+            "    ------------    --------     ----------------------------------------",
+        )?;
+    }
 
     for (pow, count) in distribution.iter().skip_while(|(_, count)| **count == 0) {
         let (low, low_suffix) = pow_description(*pow);
         let (high, high_suffix) = pow_description(*pow + 1);
 
-        let low_desc = match low {
+        // The first bucket also holds anything below 1
+        let low_desc = match pow {
             0 => 0.to_string(),
             _ => format!("{}{}", low, low_suffix),
         };
@@ -65,7 +184,6 @@ pub fn print_histogram(
             build_bar((*count as f32 / max as f32) * 40.0),
         )?;
     }
-    writeln!(stdout())?;
 
     Ok(())
 }
@@ -76,37 +194,151 @@ fn build_distribution(
     syscall_data: &HashMap<Pid, PidData>,
 ) -> BTreeMap<u32, i32> {
     let mut distribution = BTreeMap::new();
-    let mut max_pow = 0;
 
     for pid in pids {
         if let Some(pid_data) = syscall_data.get(pid) {
             if let Some(data) = pid_data.syscall_data.get(syscall) {
-                for x in data.lengths.iter() {
-                    let u_secs = *x * 1000.0 * 1000.0;
-
-                    if u_secs < 1.0 {
-                        let entry = distribution.entry(0).or_insert(0);
-                        *entry += 1;
-                    } else {
-                        let pow = u_secs.log2() as u32;
-                        let entry = distribution.entry(pow).or_insert(0);
-                        *entry += 1;
-
-                        if pow > max_pow {
-                            max_pow = pow;
-                        }
-                    }
-                }
+                add_lengths(&mut distribution, &data.lengths);
             }
         }
     }
 
-    fill_empty_pows(&mut distribution, max_pow);
+    fill_empty_pows(&mut distribution);
 
     distribution
 }
 
-fn fill_empty_pows(distribution: &mut BTreeMap<u32, i32>, max_pow: u32) {
+/// Distributions for successful calls followed by each errno, in name order.
+/// Successes are whatever remains of the full distribution after errors.
+fn build_outcome_distributions<'a>(
+    syscall: &[u8],
+    pids: &[Pid],
+    syscall_data: &HashMap<Pid, PidData<'a>>,
+) -> Vec<(&'a [u8], BTreeMap<u32, i32>)> {
+    let mut success = build_distribution(syscall, pids, syscall_data);
+    let mut errors: BTreeMap<&[u8], BTreeMap<u32, i32>> = BTreeMap::new();
+
+    for pid in pids {
+        if let Some(data) = syscall_data
+            .get(pid)
+            .and_then(|pid_data| pid_data.syscall_data.get(syscall))
+        {
+            for (error, lengths) in &data.error_lengths {
+                add_lengths(errors.entry(error).or_default(), lengths);
+            }
+        }
+    }
+
+    for distribution in errors.values_mut() {
+        for (pow, count) in distribution.iter() {
+            if let Some(success_count) = success.get_mut(pow) {
+                *success_count -= count;
+            }
+        }
+        fill_empty_pows(distribution);
+    }
+
+    while let Some((&pow, 0)) = success.iter().next_back() {
+        success.remove(&pow);
+    }
+
+    let mut outcomes = Vec::new();
+    if !success.is_empty() {
+        outcomes.push((b"success".as_ref(), success));
+    }
+    outcomes.extend(errors);
+
+    outcomes
+}
+
+fn build_value_distribution(
+    buffer: &[u8],
+    syscall: &[u8],
+    pids: &[Pid],
+    value: QuantizeValue,
+) -> ValueDistribution {
+    let pids: HashSet<Pid> = pids.iter().copied().collect();
+
+    let lines: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(|line| match parser::parse_line(line)? {
+            LineData::Syscall(raw_data)
+                if raw_data.syscall == syscall && pids.contains(&raw_data.pid) =>
+            {
+                Some((line, raw_data))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut values = ValueDistribution::default();
+    // The arguments printed before each PID's unfinished call was interrupted
+    let mut started: HashMap<Pid, Vec<&[u8]>> = HashMap::default();
+
+    for (line, raw_data) in lines {
+        let val = match (value, raw_data.call_status) {
+            (_, CallStatus::Started) => {
+                if let QuantizeValue::Arg(_) = value {
+                    started.insert(raw_data.pid, parser::line_args(line));
+                }
+                continue;
+            }
+            (QuantizeValue::Arg(n), status) => {
+                let mut args = match status {
+                    CallStatus::Resumed => started.remove(&raw_data.pid).unwrap_or_default(),
+                    _ => Vec::new(),
+                };
+                args.extend(parser::line_args(line));
+                args.get(n - 1).and_then(|arg| parse_value(arg))
+            }
+            (_, _) if raw_data.error.is_some() => None,
+            _ => parser::line_return(line).and_then(parse_value),
+        };
+
+        match val {
+            Some(val) => *values.distribution.entry(value_pow(val)).or_default() += 1,
+            None => values.skipped += 1,
+        }
+    }
+
+    fill_empty_pows(&mut values.distribution);
+
+    values
+}
+
+// Decimal or hex integers, as strace prints counts, sizes, and addresses
+fn parse_value(arg: &[u8]) -> Option<u64> {
+    let arg = arg.to_str().ok()?.trim();
+    match arg.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => arg.parse::<u64>().ok(),
+    }
+}
+
+fn value_pow(val: u64) -> u32 {
+    match val {
+        0 => 0,
+        _ => val.ilog2(),
+    }
+}
+
+fn add_lengths(distribution: &mut BTreeMap<u32, i32>, lengths: &[f32]) {
+    for x in lengths.iter() {
+        let u_secs = *x * 1000.0 * 1000.0;
+
+        let pow = if u_secs < 1.0 {
+            0
+        } else {
+            u_secs.log2() as u32
+        };
+        let entry = distribution.entry(pow).or_insert(0);
+        *entry += 1;
+    }
+}
+
+fn fill_empty_pows(distribution: &mut BTreeMap<u32, i32>) {
+    let max_pow = distribution.keys().last().copied().unwrap_or_default();
+
     for pow in 0..max_pow {
         if distribution.get(&pow).is_none() {
             distribution.insert(pow, 0);
@@ -134,7 +366,7 @@ fn build_pid_list(pids: &[Pid]) -> String {
     let mut pid_list = pids
         .iter()
         .take(10)
-        .fold(String::new(), |s, p| s + &p.to_string() + " ");
+        .fold(String::new(), |s, p| s + &output::pid_label(*p) + " ");
 
     if pids.len() > 10 {
         let addendum = format!("and {} more...", pids.len() - 10);
@@ -178,7 +410,7 @@ mod tests {
 477   00:09:56.954488 fcntl(1<pipe:[3578440]>, F_GETFD) = 0 <0.000100>
 477   00:09:56.954525 fcntl(10<pipe:[3578440]>, F_SETFD, FD_CLOEXEC) = 0 <0.001500>"##;
         let pid_data_map = build_syscall_data(input);
-        let dist = build_distribution(b"fcntl", &vec![477], &pid_data_map);
+        let dist = build_distribution(b"fcntl", &[477], &pid_data_map);
         assert_eq!(dist.keys().last(), Some(&10));
     }
 
@@ -189,7 +421,61 @@ mod tests {
 477   00:09:56.954488 fcntl(1<pipe:[3578440]>, F_GETFD) = 0 <0.000100>
 477   00:09:56.954525 fcntl(10<pipe:[3578440]>, F_SETFD, FD_CLOEXEC) = 0 <0.001500>"##;
         let pid_data_map = build_syscall_data(input);
-        let dist = build_distribution(b"fcntl", &vec![477], &pid_data_map);
+        let dist = build_distribution(b"fcntl", &[477], &pid_data_map);
         assert_eq!(dist.get(&9), Some(&0));
     }
+
+    #[test]
+    fn histogram_parses_quantize_values() {
+        assert_eq!("retval".parse(), Ok(QuantizeValue::RetVal));
+        assert_eq!("arg:3".parse(), Ok(QuantizeValue::Arg(3)));
+        assert!("arg:0".parse::<QuantizeValue>().is_err());
+        assert!("bytes".parse::<QuantizeValue>().is_err());
+    }
+
+    #[test]
+    fn histogram_buckets_values() {
+        let input = br##"477   00:09:56.954410 read(3</etc/hosts>, "abc", 4096) = 3 <0.000100>
+477   00:09:56.954448 read(3</etc/hosts>, "", 4096) = 0 <0.000100>
+477   00:09:56.954488 read(5<socket:[1]>,  <unfinished ...>
+478   00:09:56.954490 read(7<socket:[2]>, 0x7ffd0000, 0x10000) = -1 EAGAIN (Resource temporarily unavailable) <0.000010>
+477   00:09:56.954525 <... read resumed>"abcdefghij", 1024) = 10 <0.000100>
+477   00:09:56.954600 write(1</dev/pts/0>, "abc", 3) = 3 <0.000100>"##;
+
+        let retvals = build_value_distribution(input, b"read", &[477, 478], QuantizeValue::RetVal);
+        assert_eq!(retvals.distribution.get(&0), Some(&1));
+        assert_eq!(retvals.distribution.get(&1), Some(&1));
+        assert_eq!(retvals.distribution.get(&2), Some(&0));
+        assert_eq!(retvals.distribution.get(&3), Some(&1));
+        assert_eq!(retvals.skipped, 1);
+
+        let counts = build_value_distribution(input, b"read", &[477, 478], QuantizeValue::Arg(3));
+        assert_eq!(counts.distribution.get(&10), Some(&1));
+        assert_eq!(counts.distribution.get(&12), Some(&2));
+        assert_eq!(counts.distribution.get(&16), Some(&1));
+        assert_eq!(counts.skipped, 0);
+
+        let fds = build_value_distribution(input, b"read", &[477], QuantizeValue::Arg(1));
+        assert_eq!(fds.skipped, 3);
+    }
+
+    #[test]
+    fn histogram_splits_outcomes() {
+        let input = br##"477   00:09:56.954410 connect(5<socket:[1]>, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr("10.0.0.2")}, 16) = 0 <0.000100>
+477   00:09:56.954448 connect(6<socket:[2]>, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr("10.0.0.2")}, 16) = 0 <0.000100>
+477   00:09:56.954488 connect(7<socket:[3]>, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr("10.0.0.2")}, 16) = -1 ETIMEDOUT (Connection timed out) <0.001500>
+477   00:09:56.954525 connect(8<socket:[4]>, {sa_family=AF_INET, sin_port=htons(80), sin_addr=inet_addr("10.0.0.2")}, 16) = -1 ECONNREFUSED (Connection refused) <0.000100>"##;
+        let pid_data_map = build_syscall_data(input);
+        let outcomes = build_outcome_distributions(b"connect", &[477], &pid_data_map);
+
+        let labels: Vec<_> = outcomes.iter().map(|(o, _)| *o).collect();
+        assert_eq!(
+            labels,
+            vec![b"success".as_ref(), b"ECONNREFUSED", b"ETIMEDOUT"]
+        );
+        assert_eq!(outcomes[0].1.keys().last(), Some(&6));
+        assert_eq!(outcomes[0].1.get(&6), Some(&2));
+        assert_eq!(outcomes[1].1.get(&6), Some(&1));
+        assert_eq!(outcomes[2].1.keys().last(), Some(&10));
+    }
 }
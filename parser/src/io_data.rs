@@ -1,12 +1,23 @@
+use crate::output;
 use crate::parser::{CallStatus, RawData};
 use crate::syscall_data::PidData;
+use crate::syscall_stats::FdKind;
+use crate::time::parse_timestamp_us;
 use crate::{HashMap, Pid};
 
 use bstr::ByteSlice;
 use rayon::prelude::*;
+use std::cmp::Reverse;
 use std::collections::BTreeMap;
 use std::fmt;
 
+// Gaps varying by less than this fraction of their mean are evenly spaced
+const STEADY_VARIATION: f32 = 0.2;
+// A mean gap this many times the median is pulled up by pauses between bursts
+const BURST_RATIO: f32 = 4.0;
+// Fewer calls than this don't show a pattern
+const MIN_CADENCE_CALLS: usize = 3;
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct IoCall<'a> {
     pub pid: Pid,
@@ -14,10 +25,124 @@ pub struct IoCall<'a> {
     pub syscall: &'a [u8],
     pub fd: &'a [u8],
     pub bytes: i32,
+    /// Bytes asked for, when strace printed the count
+    pub requested: Option<i32>,
     pub duration: f32,
     pub error: Option<&'a [u8]>,
 }
 
+impl<'a> IoCall<'a> {
+    pub fn is_read(&self) -> bool {
+        matches!(
+            self.syscall,
+            b"pread64"
+                | b"preadv"
+                | b"preadv2"
+                | b"read"
+                | b"readv"
+                | b"recv"
+                | b"recvfrom"
+                | b"recvmsg"
+        )
+    }
+
+    /// A call that succeeded but moved fewer bytes than requested. A read
+    /// returning 0 has reached the end of the file and is not counted.
+    pub fn is_short(&self) -> bool {
+        match self.requested {
+            Some(requested) if self.error.is_none() => {
+                self.bytes < requested && (self.bytes > 0 || !self.is_read())
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShortIo<'a> {
+    pub pid: Pid,
+    pub fd: &'a [u8],
+    pub calls: usize,
+    pub short: usize,
+    /// Calls in the same direction on the fd made after a short one
+    pub retries: usize,
+    pub retry_duration: f32,
+}
+
+/// I/O calls on all fds of one kind
+#[derive(Clone, Debug, PartialEq)]
+pub struct KindTotal {
+    pub kind: FdKind,
+    pub calls: usize,
+    pub bytes: i64,
+    pub duration: f32,
+}
+
+/// Bytes read and written by a PID, on one of its files or across all of them
+#[derive(Clone, Debug, PartialEq)]
+pub struct ByteTotal<'a> {
+    pub pid: Pid,
+    /// The file, `None` for the PID's total
+    pub fd: Option<&'a [u8]>,
+    pub calls: usize,
+    pub read: u64,
+    pub written: u64,
+}
+
+impl<'a> ByteTotal<'a> {
+    fn new(pid: Pid, fd: Option<&'a [u8]>) -> ByteTotal<'a> {
+        ByteTotal {
+            pid,
+            fd,
+            calls: 0,
+            read: 0,
+            written: 0,
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.read + self.written
+    }
+
+    fn add(&mut self, call: &IoCall) {
+        let bytes = call.bytes.max(0) as u64;
+        match call.is_read() {
+            true => self.read += bytes,
+            false => self.written += bytes,
+        }
+        self.calls += 1;
+    }
+}
+
+/// The time between the starts of consecutive I/O calls on one of a PID's
+/// fds, in ms
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cadence<'a> {
+    pub pid: Pid,
+    pub fd: &'a [u8],
+    pub calls: usize,
+    pub min: f32,
+    pub median: f32,
+    pub avg: f32,
+    pub max: f32,
+    pub stddev: f32,
+}
+
+impl<'a> Cadence<'a> {
+    /// 'steady' when calls are evenly spaced, as when polling on a timer,
+    /// and 'bursts' when most follow each other closely with long pauses
+    /// between groups
+    pub fn pattern(&self) -> &'static str {
+        if self.avg > 0.0 && self.stddev < self.avg * STEADY_VARIATION {
+            "steady"
+        } else if self.median * BURST_RATIO < self.avg {
+            "bursts"
+        } else {
+            "-"
+        }
+    }
+}
+
 impl<'a> fmt::Display for IoCall<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let error = self.error.unwrap_or(b"-");
@@ -29,7 +154,7 @@ impl<'a> fmt::Display for IoCall<'a> {
             "  {: >7}    {: >10.3}    {: ^15}    {: <8}    {: >8}     {: ^15}    {: <30}",
             self.pid,
             duration,
-            self.time.to_str_lossy(),
+            output::timestamp(self.time),
             self.syscall.to_str_lossy(),
             bytes,
             error.to_str_lossy(),
@@ -45,11 +170,13 @@ pub fn io_calls<'a>(
     pids.par_iter()
         .map(|pid| {
             let mut io_events = raw_data[pid].io_events.clone();
-            io_events.par_sort_unstable_by(|x, y| (x.time).cmp(&y.time));
+            // Stable, so calls made in the same microsecond keep the order they
+            // were read in and an unfinished call is followed by its resumption
+            io_events.par_sort_by(|x, y| (x.time).cmp(y.time));
 
             let mut coalesced_data: Vec<_> = coalesce_io_events(&io_events);
 
-            coalesced_data.par_sort_by(|x, y| (x.time).cmp(&y.time));
+            coalesced_data.par_sort_by(|x, y| (x.time).cmp(y.time));
             (*pid, coalesced_data)
         })
         .collect()
@@ -69,6 +196,7 @@ fn coalesce_io_events<'a>(events: &[RawData<'a>]) -> Vec<IoCall<'a>> {
                     .file()
                     .unwrap_or(b"Unavailable: '-y' flag was not passed to strace"),
                 bytes: event.rtn_cd.unwrap_or_default(),
+                requested: event.requested(),
                 duration: event.duration.unwrap_or_default(),
                 error: event.error,
             }),
@@ -82,6 +210,7 @@ fn coalesce_io_events<'a>(events: &[RawData<'a>]) -> Vec<IoCall<'a>> {
                             .file()
                             .unwrap_or(b"Unavailable: '-y' flag was not passed to strace"),
                         bytes: next_event.rtn_cd.unwrap_or_default(),
+                        requested: event.requested().or_else(|| next_event.requested()),
                         duration: next_event.duration.unwrap_or_default(),
                         error: next_event.error,
                     })
@@ -92,3 +221,285 @@ fn coalesce_io_events<'a>(events: &[RawData<'a>]) -> Vec<IoCall<'a>> {
     }
     io_calls
 }
+
+/// Partial reads and writes on each PID's fds, and the calls that followed
+/// them. Only fds with at least one short call are returned.
+pub fn short_io<'a>(io_calls: &BTreeMap<Pid, Vec<IoCall<'a>>>) -> Vec<ShortIo<'a>> {
+    let mut short_io = Vec::new();
+
+    for (pid, calls) in io_calls {
+        let mut fds: BTreeMap<&[u8], ShortIo> = BTreeMap::new();
+        // Whether a read or write on each fd was left unfinished by a short call,
+        // failed calls such as EAGAIN leave it unfinished
+        let mut unfinished: HashMap<(&[u8], bool), bool> = HashMap::default();
+
+        for call in calls {
+            let entry = fds.entry(call.fd).or_insert_with(|| ShortIo {
+                pid: *pid,
+                fd: call.fd,
+                calls: 0,
+                short: 0,
+                retries: 0,
+                retry_duration: 0.0,
+            });
+            entry.calls += 1;
+
+            let direction = (call.fd, call.is_read());
+            let retrying = unfinished.get(&direction).copied().unwrap_or_default();
+            if retrying {
+                entry.retries += 1;
+                entry.retry_duration += call.duration;
+            }
+
+            let is_short = call.is_short();
+            if is_short {
+                entry.short += 1;
+            }
+            unfinished.insert(direction, is_short || (retrying && call.error.is_some()));
+        }
+
+        short_io.extend(fds.into_values().filter(|fd| fd.short > 0));
+    }
+
+    short_io
+}
+
+/// Totals of the I/O calls on each kind of fd, the kinds taking the most time
+/// first. Fds not named with '-y' can't be classified and are left out.
+pub fn kind_totals(io_calls: &BTreeMap<Pid, Vec<IoCall>>) -> Vec<KindTotal> {
+    let mut totals: BTreeMap<FdKind, KindTotal> = BTreeMap::new();
+
+    for call in io_calls.values().flatten() {
+        if call.fd.starts_with(b"Unavailable:") {
+            continue;
+        }
+        let kind = FdKind::classify(call.fd);
+        let total = totals.entry(kind).or_insert(KindTotal {
+            kind,
+            calls: 0,
+            bytes: 0,
+            duration: 0.0,
+        });
+        total.calls += 1;
+        total.bytes += i64::from(call.bytes.max(0));
+        total.duration += call.duration;
+    }
+
+    let mut totals: Vec<_> = totals.into_values().collect();
+    totals.sort_by(|x, y| y.duration.total_cmp(&x.duration));
+
+    totals
+}
+
+/// Bytes moved on each of a PID's files, the most first. Fds not named with
+/// '-y' are left out, as calls on different files would be mixed together.
+pub fn file_byte_totals<'a>(io_calls: &BTreeMap<Pid, Vec<IoCall<'a>>>) -> Vec<ByteTotal<'a>> {
+    let mut totals: BTreeMap<(Pid, &[u8]), ByteTotal> = BTreeMap::new();
+
+    for call in io_calls.values().flatten() {
+        if call.fd.starts_with(b"Unavailable:") {
+            continue;
+        }
+        totals
+            .entry((call.pid, call.fd))
+            .or_insert_with(|| ByteTotal::new(call.pid, Some(call.fd)))
+            .add(call);
+    }
+
+    let mut totals: Vec<_> = totals.into_values().collect();
+    totals.sort_by_key(|total| Reverse(total.total()));
+
+    totals
+}
+
+/// Bytes moved by each PID across all of its files, the most first
+pub fn pid_byte_totals<'a>(io_calls: &BTreeMap<Pid, Vec<IoCall<'a>>>) -> Vec<ByteTotal<'a>> {
+    let mut totals: Vec<_> = io_calls
+        .iter()
+        .filter(|(_, calls)| !calls.is_empty())
+        .map(|(pid, calls)| {
+            calls
+                .iter()
+                .fold(ByteTotal::new(*pid, None), |mut total, call| {
+                    total.add(call);
+                    total
+                })
+        })
+        .collect();
+    totals.sort_by_key(|total| Reverse(total.total()));
+
+    totals
+}
+
+/// The spacing of calls on each PID's fds with at least 3 calls, the most
+/// used fds first. Fds not named with '-y' are left out, as calls on
+/// different files would be mixed together.
+pub fn io_cadence<'a>(io_calls: &BTreeMap<Pid, Vec<IoCall<'a>>>) -> Vec<Cadence<'a>> {
+    let mut cadence = Vec::new();
+
+    for (pid, calls) in io_calls {
+        let mut starts: BTreeMap<&[u8], Vec<i64>> = BTreeMap::new();
+        for call in calls.iter().filter(|c| !c.fd.starts_with(b"Unavailable:")) {
+            if let Some(time) = parse_timestamp_us(call.time) {
+                starts.entry(call.fd).or_default().push(time);
+            }
+        }
+
+        for (fd, mut times) in starts {
+            if times.len() < MIN_CADENCE_CALLS {
+                continue;
+            }
+            times.sort_unstable();
+
+            let mut gaps: Vec<f32> = times
+                .windows(2)
+                .map(|w| (w[1] - w[0]) as f32 / 1000.0)
+                .collect();
+            gaps.sort_by(|x, y| x.total_cmp(y));
+
+            let avg = gaps.iter().sum::<f32>() / gaps.len() as f32;
+            let variance =
+                gaps.iter().map(|g| (g - avg) * (g - avg)).sum::<f32>() / gaps.len() as f32;
+            let mid = gaps.len() / 2;
+            let median = if gaps.len().is_multiple_of(2) {
+                (gaps[mid - 1] + gaps[mid]) / 2.0
+            } else {
+                gaps[mid]
+            };
+
+            cadence.push(Cadence {
+                pid: *pid,
+                fd,
+                calls: times.len(),
+                min: gaps[0],
+                median,
+                avg,
+                max: gaps[gaps.len() - 1],
+                stddev: variance.sqrt(),
+            });
+        }
+    }
+
+    cadence.sort_by(|x, y| {
+        (y.calls)
+            .cmp(&x.calls)
+            .then_with(|| x.pid.cmp(&y.pid))
+            .then_with(|| x.fd.cmp(y.fd))
+    });
+
+    cadence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn io_data_pairs_requested_bytes_across_resumed_calls() {
+        let input = br##"477   00:09:56.000000 read(5<socket:[3578440]>,  <unfinished ...>
+477   00:09:56.000100 <... read resumed>"abc", 4096) = 3 <0.000100>"##;
+        let pid_data_map = build_syscall_data(input);
+        let calls = io_calls(&[477], &pid_data_map);
+
+        assert_eq!(calls[&477][0].requested, Some(4096));
+        assert!(calls[&477][0].is_short());
+    }
+
+    #[test]
+    fn io_data_short_writes_and_retries() {
+        let input = br##"477   00:09:56.000000 write(5<socket:[3578440]>, "abcdef", 6) = 4 <0.000010>
+477   00:09:56.000100 write(5<socket:[3578440]>, "ef", 2) = -1 EAGAIN (Resource temporarily unavailable) <0.000020>
+477   00:09:56.000200 write(5<socket:[3578440]>, "ef", 2) = 2 <0.000030>
+477   00:09:56.000300 write(5<socket:[3578440]>, "gh", 2) = 2 <0.000010>
+477   00:09:56.000400 read(6</var/log/app.log>, "", 4096) = 0 <0.000010>
+477   00:09:56.000500 write(7</dev/null>, "a", 1) = 1 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let short = short_io(&io_calls(&[477], &pid_data_map));
+
+        assert_eq!(short.len(), 1);
+        assert_eq!(short[0].fd, b"socket:[3578440]");
+        assert_eq!(short[0].calls, 4);
+        assert_eq!(short[0].short, 1);
+        assert_eq!(short[0].retries, 2);
+        assert!((short[0].retry_duration - 0.000050).abs() < 1e-7);
+    }
+
+    #[test]
+    fn io_byte_totals_by_file_and_pid() {
+        let input = br##"477   00:09:56.000000 read(5</etc/app.yml>, "abcdef", 4096) = 6 <0.000010>
+477   00:09:56.000100 write(6<TCP:[10.0.0.1:4000->10.0.0.2:80]>, "GET / HTTP/1.1\r\n", 16) = 16 <0.000020>
+477   00:09:56.000200 read(6<TCP:[10.0.0.1:4000->10.0.0.2:80]>, 0x7f, 4096) = -1 EAGAIN (Resource temporarily unavailable) <0.000010>
+477   00:09:56.000300 read(6<TCP:[10.0.0.1:4000->10.0.0.2:80]>, "HTTP/1.1 200 OK\r\n", 4096) = 17 <0.000010>
+495   00:09:56.000400 write(1, "done\n", 5) = 5 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let calls = io_calls(&[477, 495], &pid_data_map);
+
+        let files = file_byte_totals(&calls);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].fd, Some(&b"TCP:[10.0.0.1:4000->10.0.0.2:80]"[..]));
+        assert_eq!(
+            (files[0].calls, files[0].read, files[0].written),
+            (3, 17, 16)
+        );
+        assert_eq!(files[1].total(), 6);
+
+        let pids = pid_byte_totals(&calls);
+        assert_eq!(pids.len(), 2);
+        assert_eq!((pids[0].pid, pids[0].fd, pids[0].total()), (477, None, 39));
+        assert_eq!((pids[1].pid, pids[1].written), (495, 5));
+    }
+
+    #[test]
+    fn io_kind_totals_split_anon_inodes() {
+        let input = br##"9071  10:14:21.000000 read(8<anon_inode:[timerfd]>, "\1\0\0\0\0\0\0\0", 8) = 8 <0.000010>
+9071  10:14:21.100000 read(8<anon_inode:[timerfd]>, "\1\0\0\0\0\0\0\0", 8) = 8 <0.000010>
+9071  10:14:21.100100 write(5<anon_inode:[eventfd]>, "\1\0\0\0\0\0\0\0", 8) = 8 <0.000030>
+9071  10:14:21.100200 read(6<anon_inode:[signalfd]>, 0x7ffd2c0, 128) = -1 EAGAIN (Resource temporarily unavailable) <0.000005>
+9071  10:14:21.100300 write(3<socket:[61613]>, "ping", 4) = 4 <0.000100>
+9071  10:14:21.100400 read(9, "a", 1) = 1 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let totals = kind_totals(&io_calls(&[9071], &pid_data_map));
+
+        let kinds: Vec<_> = totals.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                FdKind::Socket,
+                FdKind::EventFd,
+                FdKind::TimerFd,
+                FdKind::AnonInode
+            ]
+        );
+        assert_eq!(totals[2].calls, 2);
+        assert_eq!(totals[2].bytes, 16);
+        assert_eq!(totals[3].bytes, 0);
+    }
+
+    #[test]
+    fn io_cadence_finds_steady_and_bursty_fds() {
+        let input = br##"477   00:09:56.000000 read(6</var/log/app.log>, "", 4096) = 0 <0.000010>
+477   00:09:57.000100 read(6</var/log/app.log>, "", 4096) = 0 <0.000010>
+477   00:09:57.999900 read(6</var/log/app.log>, "", 4096) = 0 <0.000010>
+477   00:09:59.000000 read(6</var/log/app.log>, "", 4096) = 0 <0.000010>
+477   00:09:56.000000 write(5<socket:[300]>, "a", 1) = 1 <0.000010>
+477   00:09:56.001000 write(5<socket:[300]>, "a", 1) = 1 <0.000010>
+477   00:09:56.002000 write(5<socket:[300]>, "a", 1) = 1 <0.000010>
+477   00:09:56.003000 write(5<socket:[300]>, "a", 1) = 1 <0.000010>
+477   00:09:58.000000 write(5<socket:[300]>, "a", 1) = 1 <0.000010>
+477   00:09:58.001000 write(5<socket:[300]>, "a", 1) = 1 <0.000010>
+477   00:09:58.002000 write(7</dev/null>, "a", 1) = 1 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let cadence = io_cadence(&io_calls(&[477], &pid_data_map));
+
+        assert_eq!(cadence.len(), 2);
+        assert_eq!(cadence[0].fd, b"socket:[300]");
+        assert_eq!(cadence[0].calls, 6);
+        assert!((cadence[0].median - 1.0).abs() < 1e-3);
+        assert_eq!(cadence[0].pattern(), "bursts");
+        assert_eq!(cadence[1].fd, b"/var/log/app.log");
+        assert!((cadence[1].min - 999.8).abs() < 1e-3);
+        assert!((cadence[1].max - 1000.1).abs() < 1e-3);
+        assert_eq!(cadence[1].pattern(), "steady");
+    }
+}
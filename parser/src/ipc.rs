@@ -0,0 +1,257 @@
+use crate::io_data;
+use crate::output;
+use crate::syscall_data::PidData;
+use crate::time::parse_timestamp_us;
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{prelude::*, stdout, Error};
+
+// Blocking calls shorter than this are ordinary pipe latency rather than a stall
+const MIN_STALL_US: i64 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PipeEnd {
+    Reader,
+    Writer,
+}
+
+impl PipeEnd {
+    fn peer(self) -> PipeEnd {
+        match self {
+            PipeEnd::Reader => PipeEnd::Writer,
+            PipeEnd::Writer => PipeEnd::Reader,
+        }
+    }
+}
+
+impl fmt::Display for PipeEnd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipeEnd::Reader => write!(f, "reader"),
+            PipeEnd::Writer => write!(f, "writer"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipeStall<'a> {
+    pub pipe: &'a [u8],
+    pub blocked_end: PipeEnd,
+    pub blocked_pid: Pid,
+    pub peer_pid: Pid,
+    pub blocked_us: i64,
+    pub stalls: usize,
+}
+
+impl<'a> fmt::Display for PipeStall<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "pipe {}: {} {} blocked {:.3}s waiting on {} {} ({} {})",
+            self.pipe.to_str_lossy(),
+            self.blocked_end,
+            output::pid_label(self.blocked_pid),
+            self.blocked_us as f64 / 1_000_000.0,
+            self.blocked_end.peer(),
+            output::pid_label(self.peer_pid),
+            self.stalls,
+            if self.stalls == 1 { "stall" } else { "stalls" }
+        )
+    }
+}
+
+// pipe, blocked end, blocked pid, peer pid
+type StallKey<'a> = (&'a [u8], PipeEnd, Pid, Pid);
+
+#[derive(Clone, Copy, Debug)]
+struct PipeCall {
+    end: PipeEnd,
+    pid: Pid,
+    start: i64,
+    finish: i64,
+    would_block: bool,
+}
+
+/// Finds intervals where one end of a pipe was blocked, either by 'EAGAIN' or
+/// a slow blocking call, while the traced PIDs on the other end made no calls
+/// on the pipe, and blames those PIDs for the stall
+pub fn pipe_stalls<'a>(pids: &[Pid], raw_data: &HashMap<Pid, PidData<'a>>) -> Vec<PipeStall<'a>> {
+    let mut pipes: HashMap<&[u8], Vec<PipeCall>> = HashMap::default();
+
+    for io_call in io_data::io_calls(pids, raw_data).values().flatten() {
+        // 477   00:09:56.954410 write(1<pipe:[3578440]>, "done\n", 5) = 5 <0.000010>
+        //                                     ^^^^^^^
+        let pipe = match io_call
+            .fd
+            .strip_prefix(b"pipe:[")
+            .and_then(|p| p.strip_suffix(b"]"))
+        {
+            Some(p) => p,
+            None => continue,
+        };
+
+        let end = match io_call.syscall {
+            b"read" => PipeEnd::Reader,
+            b"write" | b"writev" => PipeEnd::Writer,
+            _ => continue,
+        };
+
+        if let Some(start) = parse_timestamp_us(io_call.time) {
+            pipes.entry(pipe).or_default().push(PipeCall {
+                end,
+                pid: io_call.pid,
+                start,
+                finish: start + (io_call.duration as f64 * 1_000_000.0) as i64,
+                would_block: io_call.error == Some(b"EAGAIN"),
+            });
+        }
+    }
+
+    let mut blame: BTreeMap<StallKey, PipeStall> = BTreeMap::new();
+
+    for (pipe, calls) in pipes.iter_mut() {
+        calls.sort_by_key(|c| (c.end, c.pid, c.start));
+
+        for (end, pid, start, finish) in blocked_intervals(calls) {
+            let mut peers: Vec<_> = calls
+                .iter()
+                .filter(|c| c.end == end.peer() && c.pid != pid)
+                .map(|c| c.pid)
+                .collect();
+            peers.dedup();
+
+            for peer in peers {
+                let peer_active = calls.iter().any(|c| {
+                    c.end == end.peer() && c.pid == peer && c.start < finish && c.finish > start
+                });
+
+                if !peer_active {
+                    let stall = blame
+                        .entry((pipe, end, pid, peer))
+                        .or_insert_with(|| PipeStall {
+                            pipe,
+                            blocked_end: end,
+                            blocked_pid: pid,
+                            peer_pid: peer,
+                            blocked_us: 0,
+                            stalls: 0,
+                        });
+                    stall.blocked_us += finish - start;
+                    stall.stalls += 1;
+                }
+            }
+        }
+    }
+
+    let mut stalls: Vec<_> = blame.into_values().collect();
+    stalls.sort_by_key(|s| Reverse(s.blocked_us));
+
+    stalls
+}
+
+// Calls must be sorted by end, pid, and start time. A run of 'EAGAIN' results
+// is blocked until the next call that succeeds.
+fn blocked_intervals(calls: &[PipeCall]) -> Vec<(PipeEnd, Pid, i64, i64)> {
+    let mut intervals = Vec::new();
+    let mut would_block_since: Option<(PipeEnd, Pid, i64)> = None;
+
+    for call in calls {
+        if let Some((end, pid, _)) = would_block_since {
+            if end != call.end || pid != call.pid {
+                would_block_since = None;
+            }
+        }
+
+        if call.would_block {
+            would_block_since.get_or_insert((call.end, call.pid, call.start));
+            continue;
+        }
+
+        if let Some((end, pid, since)) = would_block_since.take() {
+            intervals.push((end, pid, since, call.start));
+        }
+
+        if call.finish - call.start >= MIN_STALL_US {
+            intervals.push((call.end, call.pid, call.start, call.finish));
+        }
+    }
+
+    intervals
+}
+
+pub fn print_ipc(pids: &[Pid], raw_data: &HashMap<Pid, PidData>) -> Result<(), Error> {
+    let stalls = pipe_stalls(pids, raw_data);
+
+    output::print_heading("\nPipe Stalls\n-----------\n")?;
+
+    if stalls.is_empty() {
+        output::print_note(
+            "  No stalls found on pipes with both ends traced, '-y' must be passed to strace to identify pipes\n",
+        )?;
+        return Ok(());
+    }
+
+    for stall in stalls {
+        writeln!(stdout(), "  {}", stall)?;
+    }
+    writeln!(stdout())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn ipc_blames_idle_reader() {
+        let input = br##"495   00:09:55.500000 read(0<pipe:[3578440]>, "a", 4096) = 1 <0.000010>
+477   00:09:56.000000 write(1<pipe:[3578440]>, "b", 1) = -1 EAGAIN (Resource temporarily unavailable) <0.000010>
+477   00:09:56.100000 write(1<pipe:[3578440]>, "b", 1) = -1 EAGAIN (Resource temporarily unavailable) <0.000010>
+477   00:09:57.200000 write(1<pipe:[3578440]>, "b", 1) = 1 <0.000010>
+495   00:09:57.300000 read(0<pipe:[3578440]>, "b", 4096) = 1 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let stalls = pipe_stalls(&[477, 495], &pid_data_map);
+
+        assert_eq!(stalls.len(), 1);
+        assert_eq!(
+            stalls[0].to_string(),
+            "pipe 3578440: writer 477 blocked 1.200s waiting on reader 495 (1 stall)"
+        );
+    }
+
+    #[test]
+    fn ipc_skips_stall_with_active_reader() {
+        let input = br##"477   00:09:56.000000 write(1<pipe:[3578440]>, "b", 1) = -1 EAGAIN (Resource temporarily unavailable) <0.000010>
+495   00:09:56.500000 read(0<pipe:[3578440]>, "a", 4096) = 4096 <0.000010>
+477   00:09:57.200000 write(1<pipe:[3578440]>, "b", 1) = 1 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        assert!(pipe_stalls(&[477, 495], &pid_data_map).is_empty());
+    }
+
+    #[test]
+    fn ipc_blames_idle_writer() {
+        let input = br##"477   00:09:56.000000 write(1<pipe:[3578440]>, "a", 1) = 1 <0.000010>
+495   00:09:56.000100 read(0<pipe:[3578440]>, "a", 4096) = 1 <2.000000>
+477   00:09:58.000200 write(1<pipe:[3578440]>, "a", 1) = 1 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let stalls = pipe_stalls(&[477, 495], &pid_data_map);
+
+        assert_eq!(stalls.len(), 1);
+        assert_eq!(stalls[0].blocked_end, PipeEnd::Reader);
+        assert_eq!(stalls[0].peer_pid, 477);
+        assert_eq!(stalls[0].blocked_us, 2_000_000);
+    }
+
+    #[test]
+    fn ipc_requires_both_ends_traced() {
+        let input = br##"477   00:09:56.000000 write(1<pipe:[3578440]>, "b", 1) = 1 <1.000000>"##;
+        let pid_data_map = build_syscall_data(input);
+        assert!(pipe_stalls(&[477], &pid_data_map).is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+use crate::output;
+
+use bstr::ByteSlice;
+use schemars::{generate::SchemaSettings, json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{Serialize, Serializer};
+use std::borrow::Cow;
+use std::io::{prelude::*, Error};
+
+/// The version of the JSON reports' layout, written in each one's metadata.
+/// Bump it when a field is renamed, removed, or changes meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Bytes from the trace, written as a string with any invalid UTF-8 replaced
+#[derive(Clone, Copy, Debug)]
+pub struct Lossy<'a>(pub &'a [u8]);
+
+impl Serialize for Lossy<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_str_lossy())
+    }
+}
+
+impl JsonSchema for Lossy<'_> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        "Lossy".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({ "type": "string" })
+    }
+}
+
+/// A timestamp from the trace, written as `output::timestamp` prints it
+#[derive(Clone, Copy, Debug)]
+pub struct Time<'a>(pub &'a [u8]);
+
+impl Serialize for Time<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&output::timestamp(self.0))
+    }
+}
+
+impl JsonSchema for Time<'_> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        "Time".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "description": "A timestamp as the trace has it, or an RFC 3339 datetime with '--absolute-time'",
+        })
+    }
+}
+
+/// `value` rounded to `places` decimals, as precise as the text reports print it
+pub fn rounded(value: impl Into<f64>, places: i32) -> f64 {
+    let scale = 10_f64.powi(places);
+    (value.into() * scale).round() / scale
+}
+
+// Written with every report, so a saved one can be told apart from others
+#[derive(Serialize, JsonSchema)]
+struct Metadata {
+    #[schemars(schema_with = "schema_version")]
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'static str>,
+}
+
+fn schema_version(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({ "type": "integer", "const": SCHEMA_VERSION })
+}
+
+// A report as `write_report` writes it, with its metadata
+#[derive(Serialize, JsonSchema)]
+#[schemars(inline)]
+pub(crate) struct Report<'a, T> {
+    metadata: Metadata,
+    #[serde(flatten)]
+    report: &'a T,
+}
+
+/// Writes `report` as a JSON object on one line, with a "metadata" field
+/// holding the '--title' given
+pub fn write_report<W: Write, T: Serialize>(out: &mut W, report: &T) -> Result<(), Error> {
+    let report = Report {
+        metadata: Metadata {
+            version: SCHEMA_VERSION,
+            title: output::title(),
+        },
+        report,
+    };
+    serde_json::to_writer(&mut *out, &report)?;
+    writeln!(out)
+}
+
+/// The JSON Schema of `T` as it's written, titled with `name`
+pub(crate) fn schema<T: JsonSchema>(name: &str) -> serde_json::Value {
+    let mut schema = SchemaSettings::draft2020_12()
+        .for_serialize()
+        .into_generator()
+        .into_root_schema_for::<T>();
+    schema.insert(
+        "title".to_string(),
+        format!("strace-parser {} report, version {}", name, SCHEMA_VERSION).into(),
+    );
+    schema.to_value()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_report_has_metadata() {
+        #[derive(Serialize)]
+        struct Counts<'a> {
+            syscall: Lossy<'a>,
+            total_ms: f64,
+        }
+
+        let mut out = Vec::new();
+        let counts = Counts {
+            syscall: Lossy(b"read\xff"),
+            total_ms: rounded(1.23456, 3),
+        };
+        write_report(&mut out, &counts).unwrap();
+        assert_eq!(
+            out.to_str_lossy(),
+            "{\"metadata\":{\"version\":1},\"syscall\":\"read\u{fffd}\",\"total_ms\":1.235}\n"
+        );
+    }
+}
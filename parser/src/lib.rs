@@ -1,21 +1,70 @@
-#![cfg_attr(feature = "nightly", feature(split_ascii_whitespace))]
-
 use self::pid_summary::PidSummary;
 use self::sort_by::{SortBy, SortEventsBy};
 
+pub mod amplification;
+pub mod baseline;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod clock;
+pub mod columns;
+pub mod concurrency;
+pub mod connections;
+pub mod console;
+pub mod count_summary;
+pub mod coverage;
 pub mod directories;
+pub mod drift;
+pub mod error_rate;
+pub mod errors;
+pub mod estimate;
+pub mod events;
 pub mod exec;
+pub mod extract;
+pub mod fd_lifecycle;
 pub mod file_data;
+pub mod file_kind;
+pub mod futex;
+pub mod health;
 pub mod histogram;
 pub mod io_data;
+pub mod ipc;
+pub mod json;
+pub mod marks;
+pub mod metrics;
+pub mod namespaces;
+pub mod net;
+pub mod open_fds;
+pub mod otlp;
+pub mod outliers;
+pub mod output;
 pub mod parser;
+pub mod path_aliases;
+pub mod path_calls;
+pub mod peers;
+pub mod permissions;
 pub mod pid_summary;
 pub mod pid_tree;
+pub mod poll;
+pub mod pseudo_files;
+pub mod schema;
+pub mod security;
 pub mod session_summary;
+pub mod signals;
+pub mod sleeps;
 pub mod sort_by;
+pub mod spikes;
+pub mod spill;
 pub mod syscall_data;
+pub mod syscall_groups;
 pub mod syscall_stats;
+pub mod table;
+pub mod throughput;
 pub mod time;
+pub mod timeline;
+pub mod tracer;
+pub mod users;
+pub mod wait_for;
+pub mod zombies;
 
 pub type Pid = i32;
 pub type HashMap<K, V> = std::collections::HashMap<K, V>;
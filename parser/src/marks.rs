@@ -0,0 +1,363 @@
+use crate::io_data;
+use crate::output;
+use crate::parser::{self, CallStatus, LineData};
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::time::Timestamp;
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use chrono::NaiveTime;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::io::Error;
+use std::str::FromStr;
+
+// Rows of the PID table after the row for all PIDs
+const PID_ROWS: usize = 25;
+
+/// A named span of the trace, such as the seconds a deploy took, that reports
+/// compare the rest of the trace against. Given as 'NAME=START-END', with
+/// wall clock times such as '00:09:48-00:09:52.5' or unix timestamps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mark {
+    pub name: String,
+    pub start: Timestamp,
+    pub end: Timestamp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Before,
+    During,
+    After,
+}
+
+impl Mark {
+    /// Which part of the trace `time` falls in. Wall clock marks are matched
+    /// against '-ttt' traces by the time of day in UTC, a unix timestamp mark
+    /// can't be placed in a '-tt' trace.
+    pub fn phase(&self, time: Timestamp) -> Option<Phase> {
+        let time = match (self.start.is_wall_clock(), time.is_wall_clock()) {
+            (true, false) => time.time_of_day(),
+            (false, true) => return None,
+            _ => time,
+        };
+
+        Some(if time < self.start {
+            Phase::Before
+        } else if time <= self.end {
+            Phase::During
+        } else {
+            Phase::After
+        })
+    }
+
+    // Seconds of the trace from `first` to `last` in each phase
+    fn spans(&self, first: Timestamp, last: Timestamp) -> [f64; 3] {
+        let (first, last) = match (self.start.is_wall_clock(), first.is_wall_clock()) {
+            (true, false) => (first.time_of_day(), last.time_of_day()),
+            _ => (first, last),
+        };
+        let secs = |from: Timestamp, to: Timestamp| {
+            (to.as_micros() - from.as_micros()).max(0) as f64 / 1_000_000.0
+        };
+
+        [
+            secs(first, self.start.min(last)),
+            secs(self.start.max(first), self.end.min(last)),
+            secs(self.end.max(first), last),
+        ]
+    }
+
+    fn heading(&self, report: &str) -> String {
+        format!(
+            "\n{} Before, During, and After '{}' ({} to {})\n-----------\n",
+            report, self.name, self.start, self.end
+        )
+    }
+}
+
+impl FromStr for Mark {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Invalid mark '{}', expected 'NAME=START-END'", s);
+
+        let (name, range) = s.split_once('=').ok_or_else(err)?;
+        let (start, end) = range.split_once('-').ok_or_else(err)?;
+        let (start, end) = match (parse_time(start.trim()), parse_time(end.trim())) {
+            (Some(start), Some(end)) if start.is_wall_clock() == end.is_wall_clock() => {
+                (start, end)
+            }
+            _ => return Err(err()),
+        };
+
+        if name.trim().is_empty() {
+            return Err(err());
+        }
+        if end < start {
+            return Err(format!("Mark '{}' ends before it starts", name.trim()));
+        }
+
+        Ok(Mark {
+            name: name.trim().to_string(),
+            start,
+            end,
+        })
+    }
+}
+
+// Wall clock times needn't give the microseconds strace prints
+fn parse_time(time: &str) -> Option<Timestamp> {
+    if let Ok(t) = NaiveTime::parse_from_str(time, "%H:%M:%S%.f") {
+        return Timestamp::parse(t.format("%H:%M:%S%.6f").to_string().as_bytes());
+    }
+    Timestamp::parse(time.as_bytes())
+}
+
+/// Calls, errors, time in ms, and bytes moved in one phase of a mark
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PhaseTotals {
+    pub calls: usize,
+    pub errors: usize,
+    pub time: f32,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+/// Each PID's calls before, during, and after `mark`, placed by the time they
+/// started, along with the seconds of the trace in each phase
+pub fn pid_phases(buffer: &[u8], mark: &Mark) -> (BTreeMap<Pid, [PhaseTotals; 3]>, [f64; 3]) {
+    let mut calls: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(|line| match parser::parse_line(line)? {
+            LineData::Syscall(raw_data) => Some((Timestamp::parse(raw_data.time)?, raw_data)),
+            _ => None,
+        })
+        .collect();
+    calls.par_sort_by_key(|(t, _)| *t);
+
+    let spans = match (calls.first(), calls.last()) {
+        (Some((first, _)), Some((last, _))) if mark.phase(*first).is_some() => {
+            mark.spans(*first, *last)
+        }
+        _ => return (BTreeMap::new(), [0.0; 3]),
+    };
+
+    let mut phases: BTreeMap<Pid, [PhaseTotals; 3]> = BTreeMap::new();
+    // The phase each PID's unfinished call started in
+    let mut started: HashMap<Pid, Phase> = HashMap::default();
+
+    for (time, raw_data) in calls {
+        let phase = match raw_data.call_status {
+            CallStatus::Started => {
+                if let Some(phase) = mark.phase(time) {
+                    started.insert(raw_data.pid, phase);
+                }
+                continue;
+            }
+            CallStatus::Complete => mark.phase(time),
+            CallStatus::Resumed => started.remove(&raw_data.pid),
+        };
+
+        if let Some(phase) = phase {
+            let totals = &mut phases.entry(raw_data.pid).or_default()[phase as usize];
+            totals.calls += 1;
+            totals.time += raw_data.duration.unwrap_or_default() * 1000.0;
+            if raw_data.error.is_some() {
+                totals.errors += 1;
+            }
+        }
+    }
+
+    (phases, spans)
+}
+
+/// Each file's reads and writes by `pids` before, during, and after `mark`
+pub fn file_phases<'a>(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData<'a>>,
+    mark: &Mark,
+) -> BTreeMap<&'a [u8], [PhaseTotals; 3]> {
+    let mut phases: BTreeMap<&[u8], [PhaseTotals; 3]> = BTreeMap::new();
+
+    for io_call in io_data::io_calls(pids, raw_data).values().flatten() {
+        let phase = match Timestamp::parse(io_call.time).and_then(|t| mark.phase(t)) {
+            Some(phase) => phase,
+            None => continue,
+        };
+
+        let totals = &mut phases.entry(io_call.fd).or_default()[phase as usize];
+        totals.calls += 1;
+        totals.time += io_call.duration * 1000.0;
+        match (io_call.error, io_call.is_read()) {
+            (Some(_), _) => totals.errors += 1,
+            (None, true) => totals.bytes_read += io_call.bytes.max(0) as u64,
+            (None, false) => totals.bytes_written += io_call.bytes.max(0) as u64,
+        }
+    }
+
+    phases
+}
+
+// A value per second of each phase, e.g. '12.0 -> 340.5 -> 15.2'
+fn rates(
+    phases: &[PhaseTotals; 3],
+    spans: &[f64; 3],
+    value: impl Fn(&PhaseTotals) -> f64,
+) -> String {
+    let rates: Vec<_> = phases
+        .iter()
+        .zip(spans)
+        .map(|(totals, span)| match *span > 0.0 {
+            true => format!("{:.1}", value(totals) / span),
+            false => String::from("-"),
+        })
+        .collect();
+    rates.join(" -> ")
+}
+
+/// Prints the rate of calls, errors, and time in syscalls of each PID before,
+/// during, and after each of `marks`, busiest during the mark first
+pub fn print_pid_marks(buffer: &[u8], marks: &[Mark], width: Option<usize>) -> Result<(), Error> {
+    for mark in marks {
+        output::print_heading(&mark.heading("Syscalls"))?;
+
+        let (phases, spans) = pid_phases(buffer, mark);
+        if spans[1] <= 0.0 {
+            output::print_note("  The mark doesn't overlap the trace\n")?;
+            continue;
+        }
+
+        let mut all = [PhaseTotals::default(); 3];
+        for pid_phases in phases.values() {
+            for (totals, pid_totals) in all.iter_mut().zip(pid_phases) {
+                totals.calls += pid_totals.calls;
+                totals.errors += pid_totals.errors;
+                totals.time += pid_totals.time;
+            }
+        }
+
+        let mut pids: Vec<_> = phases.into_iter().collect();
+        pids.sort_by_key(|(pid, p)| (std::cmp::Reverse(p[Phase::During as usize].calls), *pid));
+
+        let mut table = Table::new(vec![
+            Column::new("pid", 7, Align::Left, 0),
+            Column::new("calls/s", 24, Align::Right, 0),
+            Column::new("errors/s", 20, Align::Right, 2),
+            Column::new("syscall ms/s", 20, Align::Right, 1),
+        ]);
+
+        let rows = std::iter::once((String::from("all"), all)).chain(
+            pids.into_iter()
+                .take(PID_ROWS)
+                .map(|(pid, p)| (output::pid_label(pid), p)),
+        );
+        for (label, p) in rows {
+            table.add_row(vec![
+                label,
+                rates(&p, &spans, |t| t.calls as f64),
+                rates(&p, &spans, |t| t.errors as f64),
+                rates(&p, &spans, |t| t.time as f64),
+            ]);
+        }
+        table.print(width)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the rate of bytes read and written, and of time in I/O, for each
+/// file used by `pids` before, during, and after each of `marks`
+pub fn print_file_marks(
+    buffer: &[u8],
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+    marks: &[Mark],
+    width: Option<usize>,
+) -> Result<(), Error> {
+    for mark in marks {
+        output::print_heading(&mark.heading("I/O"))?;
+
+        // The phases' lengths are measured over the whole trace
+        let (_, spans) = pid_phases(buffer, mark);
+        let files = file_phases(pids, raw_data, mark);
+        if spans[1] <= 0.0 || files.is_empty() {
+            output::print_note("  No I/O found around the mark\n")?;
+            continue;
+        }
+
+        let mut files: Vec<_> = files.into_iter().collect();
+        files.sort_by_key(|(file, p)| {
+            let during = &p[Phase::During as usize];
+            (
+                std::cmp::Reverse(during.bytes_read + during.bytes_written),
+                *file,
+            )
+        });
+
+        let mut table = Table::new(vec![
+            Column::new("read B/s", 24, Align::Right, 0),
+            Column::new("written B/s", 24, Align::Right, 0),
+            Column::new("I/O ms/s", 20, Align::Right, 1),
+            Column::new("file name", 9, Align::Left, 0),
+        ]);
+        for (file, p) in files {
+            table.add_row(vec![
+                rates(&p, &spans, |t| t.bytes_read as f64),
+                rates(&p, &spans, |t| t.bytes_written as f64),
+                rates(&p, &spans, |t| t.time as f64),
+                file.to_str_lossy().to_string(),
+            ]);
+        }
+        table.print(width)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn marks_split_calls_by_phase() {
+        let input = br##"477   00:09:47.000000 write(1</tmp/out>, "ab", 2) = 2 <0.001000>
+477   00:09:48.500000 write(1</tmp/out>, "abcd", 4) = 4 <0.002000>
+478   00:09:49.000000 read(0</tmp/in>,  <unfinished ...>
+477   00:09:50.000000 stat("/tmp/missing", 0x7ffc6d3c2df0) = -1 ENOENT (No such file or directory) <0.000100>
+478   00:09:53.000000 <... read resumed>"abc", 10) = 3 <4.000000>
+477   00:09:54.000000 write(1</tmp/out>, "a", 1) = 1 <0.001000>"##;
+        let mark: Mark = "deploy=00:09:48-00:09:52".parse().unwrap();
+        assert_eq!(
+            mark.phase(Timestamp::parse(b"00:09:52.000000").unwrap()),
+            Some(Phase::During)
+        );
+
+        let (phases, spans) = pid_phases(input, &mark);
+        assert_eq!(spans, [1.0, 4.0, 2.0]);
+        assert_eq!(phases[&477][0].calls, 1);
+        assert_eq!(phases[&477][1].calls, 2);
+        assert_eq!(phases[&477][1].errors, 1);
+        assert_eq!(phases[&477][2].calls, 1);
+        // A call is placed by when it started
+        assert_eq!(phases[&478][1].calls, 1);
+        assert_eq!(phases[&478][1].time, 4000.0);
+
+        let pid_data_map = build_syscall_data(input);
+        let files = file_phases(&[477, 478], &pid_data_map, &mark);
+        assert_eq!(files[b"/tmp/out".as_ref()][1].bytes_written, 4);
+        assert_eq!(files[b"/tmp/in".as_ref()][1].bytes_read, 3);
+        assert_eq!(
+            rates(&files[b"/tmp/out".as_ref()], &spans, |t| t.bytes_written
+                as f64),
+            "2.0 -> 1.0 -> 0.5"
+        );
+
+        assert!("deploy=00:09:52-00:09:48".parse::<Mark>().is_err());
+        assert!("deploy".parse::<Mark>().is_err());
+        assert!("deploy=00:09:48-1588000000.0".parse::<Mark>().is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::syscall_data::PidData;
+
+use std::error;
+use std::fmt;
+
+/// A number computed for each PID and shown as an extra column of 'summary'
+/// and 'list-pids --columns'. Implement it to add a metric from the library,
+/// or write one as an expression with `ExprMetric`.
+pub trait PidMetric: Send + Sync {
+    /// The column's header
+    fn name(&self) -> &str;
+
+    /// The PID's value, or None if it has none, e.g. on dividing by zero
+    fn value(&self, pid_data: &PidData) -> Option<f64>;
+}
+
+/// A metric written as arithmetic on a PID's syscall totals, e.g.
+/// `futex_share = time(futex) / time * 100`. The totals are:
+///
+/// * `calls`, `calls(SYSCALL)` - the number of calls
+/// * `time`, `time(SYSCALL)` - the time in the calls, in ms
+/// * `errors`, `errors(SYSCALL)` - the number of calls that failed
+///
+/// Without a syscall they cover every syscall the PID made. Numbers,
+/// parentheses, and `+ - * /` combine them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExprMetric {
+    name: String,
+    expr: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Num(f64),
+    Total(Total, Option<String>),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Total {
+    Calls,
+    Time,
+    Errors,
+}
+
+impl ExprMetric {
+    /// Parses metric definitions of the form `name = expression`, one per
+    /// line. Blank lines and lines starting with `#` are ignored.
+    pub fn parse(config: &str) -> Result<Vec<ExprMetric>, ParseMetricError> {
+        let mut metrics = Vec::new();
+
+        for (idx, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let err = ParseMetricError { line: idx + 1 };
+
+            let (name, expr) = line.split_once('=').ok_or(err)?;
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(err);
+            }
+
+            let mut parser = ExprParser {
+                chars: expr.chars().filter(|c| !c.is_whitespace()).collect(),
+                pos: 0,
+            };
+            let expr = parser.expr().ok_or(err)?;
+            if parser.pos != parser.chars.len() {
+                return Err(err);
+            }
+
+            metrics.push(ExprMetric {
+                name: name.to_string(),
+                expr,
+            });
+        }
+
+        Ok(metrics)
+    }
+}
+
+impl PidMetric for ExprMetric {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn value(&self, pid_data: &PidData) -> Option<f64> {
+        eval(&self.expr, pid_data).filter(|v| v.is_finite())
+    }
+}
+
+fn eval(expr: &Expr, pid_data: &PidData) -> Option<f64> {
+    match expr {
+        Expr::Num(n) => Some(*n),
+        Expr::Total(total, syscall) => {
+            let data = pid_data
+                .syscall_data
+                .iter()
+                .filter(|(name, _)| syscall.as_ref().is_none_or(|s| s.as_bytes() == **name))
+                .map(|(_, data)| data);
+
+            // Folded from 0.0, as an empty float sum is -0.0
+            let value = match total {
+                Total::Calls => data
+                    .map(|d| (d.lengths.len() + d.fast_calls) as f64)
+                    .fold(0.0, |acc, v| acc + v),
+                Total::Time => data
+                    .map(|d| (d.lengths.iter().sum::<f32>() + d.fast_time) as f64 * 1000.0)
+                    .fold(0.0, |acc, v| acc + v),
+                Total::Errors => data
+                    .flat_map(|d| d.errors.values())
+                    .fold(0.0, |acc, e| acc + *e as f64),
+            };
+            Some(value)
+        }
+        Expr::Neg(expr) => eval(expr, pid_data).map(|v| -v),
+        Expr::Binary(lhs, op, rhs) => {
+            let (lhs, rhs) = (eval(lhs, pid_data)?, eval(rhs, pid_data)?);
+            match op {
+                '+' => Some(lhs + rhs),
+                '-' => Some(lhs - rhs),
+                '*' => Some(lhs * rhs),
+                _ if rhs == 0.0 => None,
+                _ => Some(lhs / rhs),
+            }
+        }
+    }
+}
+
+// A recursive descent parser, with '*' and '/' binding tighter than '+' and '-'
+struct ExprParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expr(&mut self) -> Option<Expr> {
+        let mut lhs = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.term()?));
+        }
+        Some(lhs)
+    }
+
+    fn term(&mut self) -> Option<Expr> {
+        let mut lhs = self.factor()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.pos += 1;
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.factor()?));
+        }
+        Some(lhs)
+    }
+
+    fn factor(&mut self) -> Option<Expr> {
+        if self.eat('-') {
+            return Some(Expr::Neg(Box::new(self.factor()?)));
+        }
+        if self.eat('(') {
+            let expr = self.expr()?;
+            return self.eat(')').then_some(expr);
+        }
+
+        match self.peek()? {
+            c if c.is_ascii_digit() || c == '.' => {
+                let num = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                num.parse().ok().map(Expr::Num)
+            }
+            c if c.is_ascii_alphabetic() => {
+                let total = match self.take_while(|c| c.is_ascii_alphabetic()).as_str() {
+                    "calls" => Total::Calls,
+                    "time" => Total::Time,
+                    "errors" => Total::Errors,
+                    _ => return None,
+                };
+
+                if !self.eat('(') {
+                    return Some(Expr::Total(total, None));
+                }
+                let syscall = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                if syscall.is_empty() || !self.eat(')') {
+                    return None;
+                }
+                Some(Expr::Total(total, Some(syscall)))
+            }
+            _ => None,
+        }
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(&pred) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParseMetricError {
+    line: usize,
+}
+
+impl fmt::Display for ParseMetricError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid metric on line {}, expected 'name = expression'",
+            self.line
+        )
+    }
+}
+
+impl error::Error for ParseMetricError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn metrics_evaluated_per_pid() {
+        let input = br##"477   00:09:56.000000 futex(0x7f2c, FUTEX_WAIT_PRIVATE, 0, NULL) = 0 <0.003000>
+477   00:09:56.100000 read(3</etc/hosts>, "a", 1) = 1 <0.001000>
+477   00:09:56.200000 stat("/tmp/missing", 0x7ffc6d3c2df0) = -1 ENOENT (No such file or directory) <0.000000>
+495   00:09:56.300000 read(3</etc/hosts>, "a", 1) = 1 <0.001000>"##;
+        let config = r##"# share of time waiting on locks
+futex_share = time(futex) / time * 100
+
+err_rate = errors / calls
+reads = -(calls(read)) + 2 * 3
+"##;
+        let pid_data_map = build_syscall_data(input);
+        let metrics = ExprMetric::parse(config).unwrap();
+        assert_eq!(metrics.len(), 3);
+        assert_eq!(metrics[0].name(), "futex_share");
+
+        let value = |idx: usize, pid| metrics[idx].value(&pid_data_map[&pid]).unwrap();
+        assert!((value(0, 477) - 75.0).abs() < 1e-3);
+        assert!((value(1, 477) - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(value(2, 477), 5.0);
+        assert_eq!(value(0, 495).to_string(), "0");
+
+        let idle = build_syscall_data(b"477   00:09:56.000000 +++ exited with 0 +++");
+        assert_eq!(metrics[1].value(&idle[&477]), None);
+
+        assert_eq!(
+            ExprMetric::parse("ok = calls\nbad = time(\n").unwrap_err(),
+            ParseMetricError { line: 2 }
+        );
+        assert!(ExprMetric::parse("bad = bytes").is_err());
+        assert!(ExprMetric::parse("bad = calls calls").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use bstr::ByteSlice;
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+
+// Flag names and their short names as printed by 'lsns'
+const FLAGS: [(&str, &str); 8] = [
+    ("CLONE_NEWCGROUP", "cgroup"),
+    ("CLONE_NEWIPC", "ipc"),
+    ("CLONE_NEWNS", "mnt"),
+    ("CLONE_NEWNET", "net"),
+    ("CLONE_NEWPID", "pid"),
+    ("CLONE_NEWTIME", "time"),
+    ("CLONE_NEWUSER", "user"),
+    ("CLONE_NEWUTS", "uts"),
+];
+
+/// The namespaces created by a 'clone', 'clone3', or 'unshare' call
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Namespaces(u8);
+
+impl Namespaces {
+    /// Reads the 'CLONE_NEW*' flags from a flags argument such as
+    /// 'flags=CLONE_NEWNS|CLONE_NEWPID|SIGCHLD,'
+    pub fn from_flags(flags: &[u8]) -> Namespaces {
+        let flags = flags.strip_prefix(b"flags=").unwrap_or(flags);
+        let mut namespaces = Namespaces::default();
+
+        for flag in flags.split_str("|") {
+            let flag = flag.trim_end_with(|c| !c.is_ascii_alphanumeric() && c != '_');
+            if let Some(idx) = FLAGS.iter().position(|(name, _)| name.as_bytes() == flag) {
+                namespaces.0 |= 1 << idx;
+            }
+        }
+
+        namespaces
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    // The flags as written to and read from a spill file
+    pub(crate) fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub(crate) fn from_bits(bits: u8) -> Namespaces {
+        Namespaces(bits)
+    }
+}
+
+impl BitOr for Namespaces {
+    type Output = Namespaces;
+
+    fn bitor(self, rhs: Namespaces) -> Namespaces {
+        Namespaces(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Namespaces {
+    fn bitor_assign(&mut self, rhs: Namespaces) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl fmt::Display for Namespaces {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<_> = FLAGS
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| self.0 & (1 << idx) != 0)
+            .map(|(_, (_, short))| *short)
+            .collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn namespaces_read_from_flags() {
+        let ns = Namespaces::from_flags(b"flags=CLONE_NEWNS|CLONE_NEWUTS|CLONE_NEWNET|SIGCHLD,");
+        assert_eq!(ns.to_string(), "mnt,net,uts");
+
+        assert!(Namespaces::from_flags(b"flags=CLONE_VM|CLONE_VFORK|SIGCHLD").is_empty());
+        assert_eq!(
+            (Namespaces::from_flags(b"CLONE_NEWPID}") | Namespaces::from_flags(b"CLONE_NEWUSER"))
+                .to_string(),
+            "pid,user"
+        );
+    }
+}
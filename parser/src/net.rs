@@ -0,0 +1,316 @@
+use crate::connections::endpoint;
+use crate::io_data;
+use crate::output;
+use crate::parser::{CallStatus, OtherFields};
+use crate::syscall_data::PidData;
+use crate::syscall_stats::FdKind;
+use crate::table::{Align, Column, Table};
+use crate::throughput::format_bytes;
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::io::Error;
+
+// Failures that only mean a non-blocking socket wasn't ready yet
+pub(crate) const NOT_READY: [&[u8]; 3] = [b"EAGAIN", b"EWOULDBLOCK", b"EINPROGRESS"];
+
+/// What one PID sent to and received from a peer, across all of its sockets
+/// connected to it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerTraffic {
+    pub pid: Pid,
+    /// 'ip:port', '[ipv6]:port', a UNIX socket path, or the socket as
+    /// printed by strace when the other end isn't known. Clients accepted by
+    /// a server are named by address alone, as their ports change with each
+    /// connection.
+    pub peer: String,
+    pub sent: u64,
+    pub received: u64,
+    /// Reads and writes on the sockets
+    pub calls: usize,
+    pub connects: usize,
+    pub accepts: usize,
+    /// Failed calls, other than those a non-blocking socket makes when it
+    /// isn't ready
+    pub errors: usize,
+    /// Time spent in reads, writes, and 'connect', in ms
+    pub time: f32,
+}
+
+impl PeerTraffic {
+    fn new(pid: Pid, peer: String) -> PeerTraffic {
+        PeerTraffic {
+            pid,
+            peer,
+            ..PeerTraffic::default()
+        }
+    }
+
+    fn add_error(&mut self, error: Option<&[u8]>) {
+        if error.is_some_and(|e| !NOT_READY.contains(&e)) {
+            self.errors += 1;
+        }
+    }
+}
+
+// The sockets of the whole trace that were connected, and the peer each
+// was connected to, and the sockets accepted by a server
+struct Sockets<'a> {
+    connected: HashMap<&'a [u8], String>,
+    accepted: HashSet<&'a [u8]>,
+}
+
+impl<'a> Sockets<'a> {
+    fn new(raw_data: &HashMap<Pid, PidData<'a>>) -> Sockets<'a> {
+        let mut connected = HashMap::default();
+        let mut accepted = HashSet::default();
+
+        for pid_data in raw_data.values() {
+            for event in &pid_data.socket_events {
+                match &event.other {
+                    Some(OtherFields::Accept { conn, .. }) => {
+                        accepted.insert(*conn);
+                    }
+                    Some(OtherFields::Connect {
+                        file: Some(f),
+                        addr: Some(addr),
+                    }) if event.syscall == b"connect"
+                        && event.error.is_none_or(|e| NOT_READY.contains(&e)) =>
+                    {
+                        connected.insert(*f, endpoint(addr));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Sockets {
+            connected,
+            accepted,
+        }
+    }
+
+    // The other end of `socket`, `None` for fds that aren't sockets
+    fn peer(&self, socket: &[u8]) -> Option<String> {
+        if FdKind::classify(socket) != FdKind::Socket {
+            return None;
+        }
+        if let Some(endpoint) = self.connected.get(socket) {
+            return Some(endpoint.clone());
+        }
+
+        // Traced with '-yy', e.g. 'TCP:[10.0.0.1:4000->10.0.0.3:80]' or
+        // 'UNIX:[3605141->3605140,"/run/app.socket"]'
+        let start = socket.find_byte(b'[').map_or(0, |idx| idx + 1);
+        let inner = socket[start..]
+            .strip_suffix(b"]")
+            .unwrap_or(&socket[start..]);
+        if socket.starts_with(b"UNIX:") {
+            if let Some(idx) = inner.find(b",\"") {
+                return Some(
+                    inner[idx + 2..]
+                        .trim_end_with(|c| c == '"')
+                        .to_str_lossy()
+                        .to_string(),
+                );
+            }
+        } else if let Some(idx) = inner.find(b"->") {
+            let remote = inner[idx + 2..].to_str_lossy();
+            return match remote.rsplit_once(':') {
+                Some((addr, _)) if self.accepted.contains(socket) => Some(addr.to_string()),
+                _ => Some(remote.to_string()),
+            };
+        }
+
+        Some(socket.to_str_lossy().to_string())
+    }
+}
+
+fn peer_entry(
+    peers: &mut BTreeMap<String, PeerTraffic>,
+    pid: Pid,
+    peer: String,
+) -> &mut PeerTraffic {
+    peers
+        .entry(peer.clone())
+        .or_insert_with(|| PeerTraffic::new(pid, peer))
+}
+
+/// Bytes each of `pids` sent to and received from each peer, with its
+/// connects, accepts, and errors, the peers it spent the most time on
+/// first. Only sockets strace named with '-y' can be matched to a peer.
+pub fn net_activity(pids: &[Pid], raw_data: &HashMap<Pid, PidData>) -> Vec<PeerTraffic> {
+    let sockets = Sockets::new(raw_data);
+    let io_calls = io_data::io_calls(pids, raw_data);
+
+    let mut traffic: Vec<_> = pids
+        .par_iter()
+        .flat_map_iter(|pid| {
+            let mut peers: BTreeMap<String, PeerTraffic> = BTreeMap::new();
+
+            for call in io_calls.get(pid).into_iter().flatten() {
+                let peer = match sockets.peer(call.fd) {
+                    Some(peer) => peer,
+                    None => continue,
+                };
+                let traffic = peer_entry(&mut peers, *pid, peer);
+                let bytes = call.bytes.max(0) as u64;
+                match call.is_read() {
+                    true => traffic.received += bytes,
+                    false => traffic.sent += bytes,
+                }
+                traffic.calls += 1;
+                traffic.time += call.duration * 1000.0;
+                traffic.add_error(call.error);
+            }
+
+            let mut socket_events = raw_data[pid].socket_events.clone();
+            socket_events.sort_unstable_by(|x, y| (x.time).cmp(y.time));
+
+            let mut pending_peer = None;
+            for event in &socket_events {
+                match (event.syscall, &event.other) {
+                    (b"accept" | b"accept4", Some(OtherFields::Accept { conn, .. })) => {
+                        if let Some(peer) = sockets.peer(conn) {
+                            peer_entry(&mut peers, *pid, peer).accepts += 1;
+                        }
+                    }
+                    (b"connect", other) => {
+                        let peer = match other {
+                            Some(OtherFields::Connect {
+                                addr: Some(addr), ..
+                            }) => Some(endpoint(addr)),
+                            Some(OtherFields::Connect { file: Some(f), .. }) => sockets.peer(f),
+                            _ => None,
+                        };
+                        let peer = match event.call_status {
+                            CallStatus::Started => {
+                                pending_peer = peer;
+                                continue;
+                            }
+                            CallStatus::Resumed => pending_peer.take(),
+                            CallStatus::Complete => peer,
+                        };
+                        if let Some(peer) = peer {
+                            let traffic = peer_entry(&mut peers, *pid, peer);
+                            traffic.connects += 1;
+                            traffic.time += event.duration.unwrap_or_default() * 1000.0;
+                            traffic.add_error(event.error);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            peers.into_values()
+        })
+        .collect();
+
+    traffic.sort_by(|x, y| {
+        y.time
+            .partial_cmp(&x.time)
+            .expect("Invalid comparison when sorting network time")
+            .then_with(|| (y.sent + y.received).cmp(&(x.sent + x.received)))
+            .then_with(|| (x.pid, &x.peer).cmp(&(y.pid, &y.peer)))
+    });
+
+    traffic
+}
+
+pub fn print_net_activity(
+    traffic: &[PeerTraffic],
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    if traffic.is_empty() {
+        output::print_note(
+            "No network activity found, '-y' must be passed to strace to match sockets",
+        )?;
+        return Ok(());
+    }
+
+    output::print_heading("\nNetwork Activity by Peer\n-----------\n")?;
+
+    let mut table = Table::new(vec![
+        Column::new("pid", 7, Align::Right, 0),
+        Column::new("time (ms)", 10, Align::Right, 1),
+        Column::new("sent", 10, Align::Right, 2),
+        Column::new("received", 10, Align::Right, 2),
+        Column::new("calls", 7, Align::Right, 5),
+        Column::new("connects", 8, Align::Right, 3),
+        Column::new("accepts", 7, Align::Right, 4),
+        Column::new("errors", 6, Align::Right, 3),
+        Column::new("peer", 30, Align::Left, 0),
+    ]);
+
+    for peer in traffic.iter().take(count) {
+        table.add_row(vec![
+            output::pid_label(peer.pid),
+            format!("{:.3}", peer.time),
+            format_bytes(peer.sent as f64),
+            format_bytes(peer.received as f64),
+            peer.calls.to_string(),
+            peer.connects.to_string(),
+            peer.accepts.to_string(),
+            peer.errors.to_string(),
+            peer.peer.clone(),
+        ]);
+    }
+    table.print(width)?;
+
+    let peers: HashSet<_> = traffic.iter().map(|t| t.peer.as_str()).collect();
+    output::print_note(&format!(
+        "\n  {} sent and {} received across {} {}",
+        format_bytes(traffic.iter().map(|t| t.sent).sum::<u64>() as f64),
+        format_bytes(traffic.iter().map(|t| t.received).sum::<u64>() as f64),
+        peers.len(),
+        if peers.len() == 1 { "peer" } else { "peers" }
+    ))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn net_activity_groups_sockets_by_peer() {
+        let input = br##"477   10:00:00.000000 connect(4<TCP:[61612]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = -1 EINPROGRESS (Operation now in progress) <0.000100>
+477   10:00:00.100000 sendto(4<TCP:[61612]>, "query", 5, MSG_NOSIGNAL, NULL, 0) = 5 <0.000010>
+477   10:00:00.200000 recvfrom(4<TCP:[61612]>, "rows", 4096, 0, NULL, NULL) = 4 <0.002000>
+477   10:00:00.300000 read(5<TCP:[10.0.0.1:40000->10.0.0.2:5432]>, "more rows", 4096) = 9 <0.001000>
+477   10:00:00.400000 read(5<TCP:[10.0.0.1:40000->10.0.0.2:5432]>, 0x7f, 4096) = -1 EAGAIN (Resource temporarily unavailable) <0.000010>
+477   10:00:00.500000 connect(6<UNIX:[7]>, {sa_family=AF_UNIX, sun_path="/run/app.sock"}, 110) = -1 ECONNREFUSED (Connection refused) <0.000010>
+477   10:00:00.600000 read(3</etc/hosts>, "127.0.0.1 localhost", 4096) = 19 <0.000010>
+823   10:00:01.000000 accept4(3<TCP:[0.0.0.0:80]>, NULL, NULL, SOCK_CLOEXEC) = 7<TCP:[10.0.0.1:80->10.0.0.5:51234]> <0.000010>
+823   10:00:01.100000 write(7<TCP:[10.0.0.1:80->10.0.0.5:51234]>, "HTTP/1.1 200 OK\r\n", 17) = 17 <0.000010>
+823   10:00:01.200000 accept4(3<TCP:[0.0.0.0:80]>, NULL, NULL, SOCK_CLOEXEC) = 8<TCP:[10.0.0.1:80->10.0.0.5:51240]> <0.000010>
+823   10:00:01.300000 read(8<UNIX:[9->10,"/run/app.sock"]>, "x", 1) = 1 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let traffic = net_activity(&[477, 823], &pid_data_map);
+
+        assert_eq!(traffic.len(), 4);
+        let db = &traffic[0];
+        assert_eq!((db.pid, db.peer.as_str()), (477, "10.0.0.2:5432"));
+        assert_eq!((db.sent, db.received, db.calls), (5, 13, 4));
+        assert_eq!((db.connects, db.errors), (1, 0));
+        assert_relative_eq!(db.time, 3.12, epsilon = 0.001);
+
+        let unix = |pid| {
+            traffic
+                .iter()
+                .find(|t| (t.pid, t.peer.as_str()) == (pid, "/run/app.sock"))
+                .unwrap()
+        };
+        assert_eq!((unix(477).connects, unix(477).errors), (1, 1));
+        assert_eq!(unix(823).received, 1);
+
+        let client = traffic.iter().find(|t| t.peer == "10.0.0.5").unwrap();
+        assert_eq!((client.accepts, client.sent), (2, 17));
+    }
+}
@@ -0,0 +1,375 @@
+use crate::output;
+use crate::parser::{CallStatus, OtherFields, RawData};
+use crate::syscall_data::PidData;
+use crate::syscall_stats::FdKind;
+use crate::table::{Align, Column, Table};
+use crate::{HashMap, HashSet, Pid};
+
+use std::collections::BTreeMap;
+use std::io::{prelude::*, stdout, Error};
+
+// stdin, stdout, and stderr are assumed to be open when the trace starts
+const STD_FDS: [i32; 3] = [0, 1, 2];
+
+/// The most fds a process held open at once. Threads share their process's fd
+/// table, so they are counted together under the thread group leader.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FdPeak<'a> {
+    pub pid: Pid,
+    pub threads: usize,
+    /// Fds open before the first traced call, including stdin, stdout, and
+    /// stderr and any closed without having been opened in the trace
+    pub inherited: usize,
+    pub peak: usize,
+    pub peak_time: Option<&'a [u8]>,
+    pub opened: usize,
+    /// Fds opened by the kind of file they refer to, when traced with '-y'
+    pub kinds: BTreeMap<FdKind, usize>,
+    pub closed: usize,
+}
+
+/// The high-water marks of `pids`, and of all their processes combined
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OpenFds<'a> {
+    pub pids: Vec<FdPeak<'a>>,
+    pub peak: usize,
+    pub peak_time: Option<&'a [u8]>,
+}
+
+// The changes in open fds of one thread group, in time order
+struct FdChanges<'a> {
+    start_time: &'a [u8],
+    end_time: Option<&'a [u8]>,
+    changes: Vec<(&'a [u8], i64)>,
+    inherited: usize,
+    opened: usize,
+    kinds: BTreeMap<FdKind, usize>,
+    closed: usize,
+}
+
+/// Replays the fds opened and closed by the processes of `pids` to find the
+/// most each held at once, and when. Fds that are closed without having been
+/// opened in the trace were open from the start. Fds closed on 'execve' or by
+/// 'close_range' are not seen, so counts after those calls may be high.
+pub fn open_fds<'a>(pids: &[Pid], raw_data: &HashMap<Pid, PidData<'a>>) -> OpenFds<'a> {
+    let groups = thread_groups(pids, raw_data);
+    let group_changes: Vec<_> = groups
+        .iter()
+        .map(|(pid, group)| (*pid, group.len() - 1, fd_changes(group, raw_data)))
+        .collect();
+
+    let mut peaks: Vec<_> = group_changes
+        .iter()
+        .map(|(pid, threads, changes)| {
+            let (peak, peak_time) = high_water_mark(changes.inherited, &changes.changes);
+            FdPeak {
+                pid: *pid,
+                threads: *threads,
+                inherited: changes.inherited,
+                peak,
+                peak_time: peak_time.or(Some(changes.start_time)),
+                opened: changes.opened,
+                kinds: changes.kinds.clone(),
+                closed: changes.closed,
+            }
+        })
+        .collect();
+    peaks.sort_by(|x, y| y.peak.cmp(&x.peak).then(x.pid.cmp(&y.pid)));
+
+    // Each process holds its inherited fds from its first call, and releases
+    // whatever is left open when it exits
+    let mut all_changes = Vec::new();
+    for (_, _, changes) in &group_changes {
+        all_changes.push((changes.start_time, changes.inherited as i64));
+        all_changes.extend(&changes.changes);
+        if let Some(end_time) = changes.end_time {
+            let left_open =
+                changes.inherited as i64 + changes.changes.iter().map(|(_, d)| d).sum::<i64>();
+            all_changes.push((end_time, -left_open));
+        }
+    }
+    all_changes.sort_by(|x, y| x.0.cmp(y.0));
+    let (peak, peak_time) = high_water_mark(0, &all_changes);
+
+    OpenFds {
+        pids: peaks,
+        peak,
+        peak_time,
+    }
+}
+
+// Each PID in `pids` is replaced by its thread group leader, mapped to the
+// leader followed by all of its threads
+pub(crate) fn thread_groups(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+) -> BTreeMap<Pid, Vec<Pid>> {
+    let creators: HashMap<Pid, Pid> = raw_data
+        .iter()
+        .flat_map(|(pid, pid_data)| pid_data.threads.iter().map(move |t| (*t, *pid)))
+        .collect();
+
+    let leader = |mut pid: Pid| {
+        // Bounded in case a malformed trace lists a thread as its own creator
+        for _ in 0..creators.len() {
+            match creators.get(&pid) {
+                Some(&creator) => pid = creator,
+                None => break,
+            }
+        }
+        pid
+    };
+
+    let leaders: HashSet<Pid> = pids.iter().map(|&pid| leader(pid)).collect();
+    let mut groups: BTreeMap<Pid, Vec<Pid>> = leaders.iter().map(|&pid| (pid, vec![pid])).collect();
+
+    let mut threads: Vec<_> = creators.keys().copied().collect();
+    threads.sort_unstable();
+    for thread in threads {
+        if let Some(group) = groups.get_mut(&leader(thread)) {
+            if raw_data.contains_key(&thread) {
+                group.push(thread);
+            }
+        }
+    }
+
+    groups.retain(|pid, _| raw_data.contains_key(pid));
+    groups
+}
+
+impl<'a> FdChanges<'a> {
+    // Returns the change in open fds. An fd that is already open was replaced,
+    // as 'dup2' does, or closed in a way that wasn't traced.
+    fn add(
+        &mut self,
+        open: &mut HashSet<i32>,
+        untracked: &mut usize,
+        fd: Option<i32>,
+        file: Option<&[u8]>,
+    ) -> i64 {
+        self.opened += 1;
+        if let Some(file) = file {
+            *self.kinds.entry(FdKind::classify(file)).or_default() += 1;
+        }
+        match fd {
+            Some(fd) if !open.insert(fd) => {
+                self.closed += 1;
+                0
+            }
+            Some(_) => 1,
+            None => {
+                *untracked += 1;
+                1
+            }
+        }
+    }
+}
+
+// The successful calls of a thread group that opened or closed fds, in time order
+pub(crate) fn fd_events<'r, 'a>(
+    group: &[Pid],
+    raw_data: &'r HashMap<Pid, PidData<'a>>,
+) -> Vec<&'r RawData<'a>> {
+    let mut events: Vec<&RawData> = group
+        .iter()
+        .flat_map(|pid| {
+            let pid_data = &raw_data[pid];
+            pid_data
+                .open_events
+                .iter()
+                .chain(pid_data.close_events.iter())
+                .chain(pid_data.dir_events.iter().filter(|e| e.syscall == b"creat"))
+        })
+        .filter(|e| e.call_status != CallStatus::Started && e.error.is_none())
+        .collect();
+    events.sort_by(|x, y| x.time.cmp(y.time));
+
+    events
+}
+
+fn fd_changes<'a>(group: &[Pid], raw_data: &HashMap<Pid, PidData<'a>>) -> FdChanges<'a> {
+    let events = fd_events(group, raw_data);
+
+    let mut open: HashSet<i32> = STD_FDS.iter().copied().collect();
+    let mut fd_changes = FdChanges {
+        start_time: group
+            .iter()
+            .map(|pid| raw_data[pid].start_time)
+            .min()
+            .unwrap_or_default(),
+        end_time: raw_data[&group[0]]
+            .exit
+            .and_then(|_| group.iter().map(|pid| raw_data[pid].end_time).max()),
+        changes: Vec::with_capacity(events.len()),
+        inherited: STD_FDS.len(),
+        opened: 0,
+        kinds: BTreeMap::new(),
+        closed: 0,
+    };
+    // Opens whose fd wasn't printed, e.g. without '-T' no return value is read
+    let mut untracked = 0;
+
+    for event in events {
+        // The file the fd refers to, for opens by path the one '-y' shows
+        let file = match event.other {
+            Some(OtherFields::Open { file, resolved, .. }) => Some(resolved.unwrap_or(file)),
+            _ => event.file(),
+        };
+
+        let delta = match event.other {
+            Some(OtherFields::Close { fd, .. }) => {
+                if !open.remove(&fd) {
+                    if untracked > 0 {
+                        untracked -= 1;
+                    } else {
+                        fd_changes.inherited += 1;
+                    }
+                }
+                fd_changes.closed += 1;
+                -1
+            }
+            Some(OtherFields::FdPair { fds, .. }) => fds
+                .iter()
+                .map(|fd| fd_changes.add(&mut open, &mut untracked, Some(*fd), file))
+                .sum(),
+            _ if event.syscall == b"close" => continue,
+            _ => fd_changes.add(
+                &mut open,
+                &mut untracked,
+                event.rtn_cd.filter(|fd| *fd >= 0),
+                file,
+            ),
+        };
+
+        if delta != 0 {
+            fd_changes.changes.push((event.time, delta));
+        }
+    }
+
+    fd_changes
+}
+
+// The most open at once, starting from `start`, and the time it was first reached
+fn high_water_mark<'a>(start: usize, changes: &[(&'a [u8], i64)]) -> (usize, Option<&'a [u8]>) {
+    let mut level = start as i64;
+    let mut peak = level;
+    let mut peak_time = None;
+
+    for &(time, delta) in changes {
+        level += delta;
+        if level > peak {
+            peak = level;
+            peak_time = Some(time);
+        }
+    }
+
+    (peak.max(0) as usize, peak_time)
+}
+
+pub fn print_open_fds(open_fds: &OpenFds, count: usize, width: Option<usize>) -> Result<(), Error> {
+    output::print_heading("\nPeak Open fds by Process\n-----------\n")?;
+
+    if open_fds.pids.is_empty() {
+        output::print_note("  No processes found\n")?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("pid", 7, Align::Right, 0),
+        Column::new("peak fds", 8, Align::Right, 0),
+        Column::new("at", 15, Align::Center, 1),
+        Column::new("inherited", 9, Align::Right, 3),
+        Column::new("opened", 8, Align::Right, 2),
+        Column::new("closed", 8, Align::Right, 2),
+        Column::new("threads", 7, Align::Right, 4),
+        Column::new("opened by kind", 14, Align::Left, 5),
+    ]);
+
+    for peak in open_fds.pids.iter().take(count) {
+        table.add_row(vec![
+            output::pid_label(peak.pid),
+            peak.peak.to_string(),
+            output::timestamp(peak.peak_time.unwrap_or(b"-")),
+            peak.inherited.to_string(),
+            peak.opened.to_string(),
+            peak.closed.to_string(),
+            peak.threads.to_string(),
+            peak.kinds
+                .iter()
+                .map(|(kind, ct)| format!("{} {}", kind, ct))
+                .collect::<Vec<_>>()
+                .join(", "),
+        ]);
+    }
+    table.print(width)?;
+
+    writeln!(
+        stdout(),
+        "\n  Most open across all processes: {} at {}\n",
+        open_fds.peak,
+        output::timestamp(open_fds.peak_time.unwrap_or(b"-"))
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn open_fds_tracks_peak_per_process() {
+        let input = br##"477   10:00:00.000000 openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = 3</etc/hosts> <0.000010>
+477   10:00:00.100000 socket(AF_INET, SOCK_STREAM, IPPROTO_TCP) = 4<TCP:[61612]> <0.000010>
+477   10:00:00.200000 pipe2([5<pipe:[700]>, 6<pipe:[700]>], O_CLOEXEC) = 0 <0.000010>
+477   10:00:00.300000 close(3</etc/hosts>) = 0 <0.000010>
+477   10:00:00.400000 close(9</var/log/app.log>) = 0 <0.000010>
+477   10:00:00.500000 dup2(4<TCP:[61612]>, 1) = 1<TCP:[61612]> <0.000010>
+477   10:00:00.600000 openat(AT_FDCWD, "/missing", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000010>
+477   10:00:00.700000 close(12) = -1 EBADF (Bad file descriptor) <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let open_fds = open_fds(&[477], &pid_data_map);
+
+        assert_eq!(open_fds.pids.len(), 1);
+        let peak = &open_fds.pids[0];
+        // stdin, stdout, stderr, and fd 9 were open before the trace
+        assert_eq!(peak.inherited, 4);
+        assert_eq!(peak.peak, 8);
+        assert_eq!(peak.peak_time, Some(b"10:00:00.200000".as_ref()));
+        assert_eq!(peak.opened, 5);
+        assert_eq!(peak.closed, 3);
+        assert_eq!(
+            peak.kinds.iter().collect::<Vec<_>>(),
+            vec![
+                (&FdKind::File, &1),
+                (&FdKind::Socket, &2),
+                (&FdKind::Pipe, &2),
+            ]
+        );
+        assert_eq!(open_fds.peak, 8);
+    }
+
+    #[test]
+    fn open_fds_counts_threads_with_their_process() {
+        let input = br##"477   10:00:00.000000 clone(child_stack=0x7f0, flags=CLONE_VM|CLONE_FS|CLONE_FILES|CLONE_SIGHAND|CLONE_THREAD|CLONE_SYSVSEM) = 478 <0.000010>
+477   10:00:00.100000 openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = 3</etc/hosts> <0.000010>
+478   10:00:00.200000 openat(AT_FDCWD, "/etc/passwd", O_RDONLY) = 4</etc/passwd> <0.000010>
+478   10:00:00.300000 close(3</etc/hosts>) = 0 <0.000010>
+477   10:00:00.400000 +++ exited with 0 +++
+480   10:00:00.500000 openat(AT_FDCWD, "/etc/group", O_RDONLY) = 3</etc/group> <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let open_fds = open_fds(&[478, 480], &pid_data_map);
+
+        assert_eq!(open_fds.pids.len(), 2);
+        assert_eq!(open_fds.pids[0].pid, 477);
+        assert_eq!(open_fds.pids[0].threads, 1);
+        assert_eq!(open_fds.pids[0].peak, 5);
+        assert_eq!(open_fds.pids[0].closed, 1);
+        assert_eq!(open_fds.pids[1].pid, 480);
+        assert_eq!(open_fds.pids[1].peak, 4);
+        // 477 exited before 480 opened its file
+        assert_eq!(open_fds.peak, 5);
+        assert_eq!(open_fds.peak_time, Some(b"10:00:00.200000".as_ref()));
+    }
+}
@@ -0,0 +1,300 @@
+use crate::events;
+use crate::Pid;
+
+use bstr::ByteSlice;
+use serde::Serialize;
+use std::io::{prelude::*, Error};
+
+/// Syscalls faster than this many ms are left out by default, so the spans
+/// show where a process waited rather than every call it made
+pub const DEFAULT_MIN_SPAN_MS: f32 = 10.0;
+
+// OTLP span kind and status codes
+const SPAN_KIND_INTERNAL: u8 = 1;
+const STATUS_CODE_ERROR: u8 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttrValue {
+    Str(String),
+    Int(i64),
+}
+
+/// A span in the trace sent to a collector, for a PID or one of its syscalls
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub span_id: u64,
+    pub parent_span_id: Option<u64>,
+    pub name: String,
+    /// Microseconds since the unix epoch
+    pub start_us: i64,
+    pub end_us: i64,
+    pub attributes: Vec<(&'static str, AttrValue)>,
+    /// Why the span failed, e.g. an errno
+    pub error: Option<String>,
+}
+
+impl Span {
+    fn to_json<'a>(&'a self, trace_id: &'a str) -> SpanJson<'a> {
+        SpanJson {
+            trace_id,
+            span_id: format!("{:016x}", self.span_id),
+            parent_span_id: self.parent_span_id.map(|id| format!("{:016x}", id)),
+            name: &self.name,
+            kind: SPAN_KIND_INTERNAL,
+            start_time_unix_nano: (self.start_us * 1000).to_string(),
+            end_time_unix_nano: (self.end_us * 1000).to_string(),
+            attributes: self
+                .attributes
+                .iter()
+                .map(|(key, value)| KeyValue {
+                    key,
+                    value: match value {
+                        AttrValue::Str(s) => AnyValue::StringValue(s),
+                        // 64 bit integers are strings in OTLP/JSON
+                        AttrValue::Int(i) => AnyValue::IntValue(i.to_string()),
+                    },
+                })
+                .collect(),
+            status: Status {
+                code: self.error.as_ref().map(|_| STATUS_CODE_ERROR),
+                message: self.error.as_deref(),
+            },
+        }
+    }
+}
+
+// The OTLP/JSON encoding of the protobuf messages, fields in camelCase
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportRequest<'a> {
+    resource_spans: [ResourceSpans<'a>; 1],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceSpans<'a> {
+    resource: Resource<'a>,
+    scope_spans: [ScopeSpans<'a>; 1],
+}
+
+#[derive(Serialize)]
+struct Resource<'a> {
+    attributes: Vec<KeyValue<'a>>,
+}
+
+#[derive(Serialize)]
+struct ScopeSpans<'a> {
+    scope: Scope,
+    spans: Vec<SpanJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct Scope {
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpanJson<'a> {
+    trace_id: &'a str,
+    span_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_span_id: Option<String>,
+    name: &'a str,
+    kind: u8,
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<KeyValue<'a>>,
+    status: Status<'a>,
+}
+
+#[derive(Serialize)]
+struct KeyValue<'a> {
+    key: &'a str,
+    value: AnyValue<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+enum AnyValue<'a> {
+    StringValue(&'a str),
+    IntValue(String),
+}
+
+#[derive(Serialize)]
+struct Status<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'a str>,
+}
+
+/// The span of a PID's lifetime, its syscall spans are numbered after it
+pub fn pid_span_id(pid: Pid) -> u64 {
+    (pid as u64) << 32
+}
+
+/// A span for each syscall of `pids` taking at least `min_ms`, as a child of
+/// its PID's span. `unix_us` converts the trace's timestamps to microseconds
+/// since the epoch, calls it can't convert are left out.
+pub fn syscall_spans<F>(buffer: &[u8], pids: &[Pid], min_ms: f32, unix_us: F) -> Vec<Span>
+where
+    F: Fn(&[u8]) -> Option<i64>,
+{
+    let mut spans = Vec::new();
+    let mut last_pid = None;
+    let mut seq = 0;
+
+    // Sorted by PID, then start time
+    for event in events::events(buffer, pids, 0) {
+        if event.duration < min_ms {
+            continue;
+        }
+        let start_us = match unix_us(event.time) {
+            Some(us) => us,
+            None => continue,
+        };
+        if last_pid != Some(event.pid) {
+            last_pid = Some(event.pid);
+            seq = 0;
+        }
+        seq += 1;
+
+        let mut attributes = vec![
+            ("process.pid", AttrValue::Int(i64::from(event.pid))),
+            (
+                "syscall.name",
+                AttrValue::Str(event.syscall.to_str_lossy().to_string()),
+            ),
+        ];
+        if let Some(path) = event.path {
+            attributes.push((
+                "syscall.path",
+                AttrValue::Str(path.to_str_lossy().to_string()),
+            ));
+        }
+        if let Some(errno) = event.errno {
+            attributes.push((
+                "syscall.errno",
+                AttrValue::Str(errno.to_str_lossy().to_string()),
+            ));
+        }
+
+        spans.push(Span {
+            span_id: pid_span_id(event.pid) | seq,
+            parent_span_id: Some(pid_span_id(event.pid)),
+            name: event.syscall.to_str_lossy().to_string(),
+            start_us,
+            end_us: start_us + (f64::from(event.duration) * 1000.0).round() as i64,
+            attributes,
+            error: event.errno.map(|e| e.to_str_lossy().to_string()),
+        });
+    }
+
+    spans
+}
+
+/// A trace id derived from the trace's contents, so exporting the same
+/// trace twice gives its spans the same ids
+pub fn trace_id(buffer: &[u8]) -> String {
+    // FNV-1a, run twice with different offsets for 128 bits
+    let hash = |mut h: u64| {
+        for &b in buffer.iter().take(1 << 16) {
+            h ^= u64::from(b);
+            h = h.wrapping_mul(0x100_0000_01b3);
+        }
+        h ^ buffer.len() as u64
+    };
+    format!(
+        "{:016x}{:016x}",
+        hash(0xcbf2_9ce4_8422_2325),
+        hash(0x8422_2325_cbf2_9ce4)
+    )
+}
+
+/// Writes `spans` as an OTLP/JSON export request, as read by OpenTelemetry
+/// collectors and by Jaeger and Tempo. A `title` names the service instance.
+pub fn write_otlp_json<W: Write>(
+    out: &mut W,
+    spans: &[Span],
+    trace_id: &str,
+    service_name: &str,
+    title: Option<&str>,
+) -> Result<(), Error> {
+    let mut attributes = vec![KeyValue {
+        key: "service.name",
+        value: AnyValue::StringValue(service_name),
+    }];
+    if let Some(title) = title {
+        attributes.push(KeyValue {
+            key: "service.instance.id",
+            value: AnyValue::StringValue(title),
+        });
+    }
+    let request = ExportRequest {
+        resource_spans: [ResourceSpans {
+            resource: Resource { attributes },
+            scope_spans: [ScopeSpans {
+                scope: Scope {
+                    name: "strace-parser",
+                },
+                spans: spans.iter().map(|span| span.to_json(trace_id)).collect(),
+            }],
+        }],
+    };
+    serde_json::to_writer(&mut *out, &request)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn otlp_spans_for_slow_calls() {
+        let input = br##"477   1600000000.000000 open("/etc/app.yml", O_RDONLY) = 3 <0.000010>
+477   1600000000.100000 connect(4<TCP:[1]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = -1 ETIMEDOUT (Connection timed out) <0.050000>"##;
+        let unix_us = |time: &[u8]| crate::time::parse_timestamp_us(time);
+        let spans = syscall_spans(input, &[477], DEFAULT_MIN_SPAN_MS, unix_us);
+
+        assert_eq!(spans.len(), 1);
+        let span = &spans[0];
+        assert_eq!(span.name, "connect");
+        assert_eq!(span.span_id, pid_span_id(477) | 1);
+        assert_eq!(span.parent_span_id, Some(pid_span_id(477)));
+        assert_eq!(span.start_us, 1_600_000_000_100_000);
+        assert_eq!(span.end_us - span.start_us, 50_000);
+        assert_eq!(span.error.as_deref(), Some("ETIMEDOUT"));
+
+        let mut out = Vec::new();
+        write_otlp_json(&mut out, &spans, &trace_id(input), "app", Some("canary")).unwrap();
+        let value: Value = serde_json::from_slice(&out).unwrap();
+        let first = |value: &Value, key: &str| match value.get(key) {
+            Some(Value::Array(items)) => items[0].clone(),
+            other => panic!("{} isn't an array: {:?}", key, other),
+        };
+        let resource_spans = first(&value, "resourceSpans");
+        assert_eq!(
+            resource_spans["resource"]["attributes"][1],
+            serde_json::json!({
+                "key": "service.instance.id",
+                "value": {"stringValue": "canary"},
+            })
+        );
+        let span = first(&first(&resource_spans, "scopeSpans"), "spans");
+        assert_eq!(
+            span.get("startTimeUnixNano").and_then(Value::as_str),
+            Some("1600000000100000000")
+        );
+        assert_eq!(
+            span.get("traceId").and_then(Value::as_str).unwrap().len(),
+            32
+        );
+        assert_eq!(
+            span.get("parentSpanId").and_then(Value::as_str),
+            Some("000001dd00000000")
+        );
+    }
+}
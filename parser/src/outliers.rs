@@ -0,0 +1,156 @@
+use crate::syscall_data::PidData;
+use crate::{HashMap, Pid};
+
+use std::cmp::Ordering;
+
+pub const DEFAULT_MAD_MULTIPLIER: f32 = 10.0;
+
+// Fewer calls than this are too few to say what is normal for a syscall
+const MIN_BASELINE_CALLS: usize = 10;
+// strace durations have microsecond resolution, so a MAD of zero only means
+// the calls were too fast to tell apart
+const MIN_MAD_SECS: f32 = 0.000_001;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Baseline {
+    pub median: f32,
+    pub mad: f32,
+}
+
+impl Baseline {
+    /// The median and median absolute deviation of `lengths`, in seconds
+    pub fn from_lengths(lengths: &[f32]) -> Option<Baseline> {
+        if lengths.len() < MIN_BASELINE_CALLS {
+            return None;
+        }
+
+        let mut values = lengths.to_vec();
+        let mid = median(&mut values);
+
+        for value in values.iter_mut() {
+            *value = (*value - mid).abs();
+        }
+        let mad = median(&mut values);
+
+        Some(Baseline { median: mid, mad })
+    }
+
+    pub fn is_outlier(&self, duration: f32, mad_multiplier: f32) -> bool {
+        duration > self.median + mad_multiplier * self.mad.max(MIN_MAD_SECS)
+    }
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    let len = values.len();
+    let cmp = |x: &f32, y: &f32| x.partial_cmp(y).unwrap_or(Ordering::Equal);
+
+    let (lower, upper, _) = values.select_nth_unstable_by(len / 2, cmp);
+    let upper = *upper;
+
+    if len % 2 == 1 {
+        upper
+    } else {
+        let lower = lower.iter().copied().fold(f32::MIN, f32::max);
+        (lower + upper) / 2.0
+    }
+}
+
+/// Session-wide baselines for each syscall, used to point out individual calls
+/// that were far slower than usual
+#[derive(Clone, Debug)]
+pub struct Baselines<'a> {
+    baselines: HashMap<&'a [u8], Baseline>,
+    mad_multiplier: f32,
+}
+
+impl<'a> Baselines<'a> {
+    pub fn new<I>(raw_data: &HashMap<Pid, PidData<'a>>, syscalls: I, mad_multiplier: f32) -> Self
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let mut baselines = HashMap::default();
+
+        for syscall in syscalls {
+            if baselines.contains_key(syscall) {
+                continue;
+            }
+
+            let lengths: Vec<_> = raw_data
+                .values()
+                .filter_map(|pid_data| pid_data.syscall_data.get(syscall))
+                .flat_map(|syscall_data| syscall_data.lengths.iter().copied())
+                .collect();
+
+            if let Some(baseline) = Baseline::from_lengths(&lengths) {
+                baselines.insert(syscall, baseline);
+            }
+        }
+
+        Baselines {
+            baselines,
+            mad_multiplier,
+        }
+    }
+
+    /// A note such as '22500x median 0.040' when a call of `duration` seconds
+    /// is an outlier for `syscall`, with the median in ms
+    pub fn annotate(&self, syscall: &[u8], duration: f32) -> Option<String> {
+        let baseline = self.baselines.get(syscall)?;
+
+        if !baseline.is_outlier(duration, self.mad_multiplier) {
+            return None;
+        }
+
+        let ratio = duration / baseline.median.max(MIN_MAD_SECS);
+        Some(format!(
+            "{:.0}x median {:.3}",
+            ratio,
+            baseline.median * 1000.0
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn outliers_baseline_median_and_mad() {
+        let lengths = [1.0, 1.0, 2.0, 2.0, 4.0, 6.0, 9.0, 2.0, 3.0, 3.0];
+        let baseline = Baseline::from_lengths(&lengths).unwrap();
+
+        assert_relative_eq!(baseline.median, 2.5);
+        assert_relative_eq!(baseline.mad, 1.0);
+        assert!(baseline.is_outlier(15.0, 10.0));
+        assert!(!baseline.is_outlier(9.0, 10.0));
+    }
+
+    #[test]
+    fn outliers_need_enough_calls() {
+        assert_eq!(Baseline::from_lengths(&[1.0, 2.0, 3.0]), None);
+    }
+
+    #[test]
+    fn outliers_annotate_slow_call() {
+        let mut input = String::new();
+        for i in 0..20 {
+            input += &format!(
+                "477   00:09:56.{:06} write(1</dev/null>, \"a\", 1) = 1 <0.000040>\n",
+                i
+            );
+        }
+        input += "477   00:09:57.000000 write(1</dev/null>, \"a\", 1) = 1 <0.900000>\n";
+
+        let pid_data_map = build_syscall_data(input.as_bytes());
+        let baselines = Baselines::new(&pid_data_map, vec![b"write".as_ref()], 10.0);
+
+        assert_eq!(
+            baselines.annotate(b"write", 0.9),
+            Some("22500x median 0.040".to_string())
+        );
+        assert_eq!(baselines.annotate(b"write", 0.000041), None);
+        assert_eq!(baselines.annotate(b"read", 0.9), None);
+    }
+}
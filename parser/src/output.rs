@@ -0,0 +1,107 @@
+use crate::time::AbsoluteTime;
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::io::{prelude::*, stdout, Error};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+// Set once from the command line before any report is printed
+static QUIET: AtomicBool = AtomicBool::new(false);
+static NO_HEADER: AtomicBool = AtomicBool::new(false);
+static PID_LABELS: OnceLock<HashMap<Pid, String>> = OnceLock::new();
+static COLUMN_PRIORITIES: OnceLock<HashMap<String, usize>> = OnceLock::new();
+static ABSOLUTE_TIME: OnceLock<AbsoluteTime> = OnceLock::new();
+static TITLE: OnceLock<String> = OnceLock::new();
+
+/// Suppress warnings, hints, and banners, leaving only the report
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Omit titles, column headers, and the preamble before tables, so each line
+/// printed is a row of data
+pub fn set_no_header(no_header: bool) {
+    NO_HEADER.store(no_header, Ordering::Relaxed);
+}
+
+pub fn headers() -> bool {
+    !NO_HEADER.load(Ordering::Relaxed)
+}
+
+/// Names to print next to PIDs, e.g. `823 (unicorn-worker-1)`. Only the
+/// first set of labels is kept.
+pub fn set_pid_labels(labels: HashMap<Pid, String>) {
+    let _result = PID_LABELS.set(labels);
+}
+
+/// The PID with its label, if one was given
+pub fn pid_label(pid: Pid) -> String {
+    match PID_LABELS.get().and_then(|labels| labels.get(&pid)) {
+        Some(label) => format!("{} ({})", pid, label),
+        None => pid.to_string(),
+    }
+}
+
+/// Priorities for dropping columns, by lowercase header, that replace those
+/// set by each table. Only the first set of priorities is kept.
+pub fn set_column_priorities(priorities: HashMap<String, usize>) {
+    let _result = COLUMN_PRIORITIES.set(priorities);
+}
+
+pub fn column_priority(header: &str) -> Option<usize> {
+    COLUMN_PRIORITIES
+        .get()
+        .and_then(|priorities| priorities.get(&header.to_lowercase()))
+        .copied()
+}
+
+/// The '--title' of the reports, written into the metadata of JSON output.
+/// Only the first title is kept.
+pub fn set_title(title: &str) {
+    let _result = TITLE.set(title.to_string());
+}
+
+pub fn title() -> Option<&'static str> {
+    TITLE.get().map(String::as_str)
+}
+
+/// Print timestamps as RFC 3339 datetimes rather than as strace printed them.
+/// Only the first setting is kept.
+pub fn set_absolute_time(absolute_time: AbsoluteTime) {
+    let _result = ABSOLUTE_TIME.set(absolute_time);
+}
+
+/// A timestamp from the trace, or a placeholder such as '-', to print
+pub fn timestamp(time: &[u8]) -> String {
+    ABSOLUTE_TIME
+        .get()
+        .and_then(|absolute| absolute.format(time))
+        .unwrap_or_else(|| time.to_str_lossy().to_string())
+}
+
+/// Prints a title or preamble line, unless headers are turned off
+pub fn print_heading(heading: &str) -> Result<(), Error> {
+    write_heading(&mut stdout(), heading)
+}
+
+pub fn write_heading<W: Write>(out: &mut W, heading: &str) -> Result<(), Error> {
+    if headers() {
+        writeln!(out, "{}", heading)?;
+    }
+
+    Ok(())
+}
+
+/// Prints a warning or hint, such as why nothing was found, unless quiet
+pub fn print_note(note: &str) -> Result<(), Error> {
+    if !quiet() {
+        writeln!(stdout(), "{}", note)?;
+    }
+
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use crate::namespaces::Namespaces;
 use crate::Pid;
 
 use bstr::ByteSlice;
@@ -34,13 +35,23 @@ impl<'a> fmt::Display for ExitType<'a> {
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct ExitData<'a> {
     pub pid: Pid,
+    pub time: &'a [u8],
     pub exit: ExitType<'a>,
 }
 
+/// A signal delivered to a PID, printed by strace as '--- SIGALRM {...} ---'
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SignalData<'a> {
+    pub pid: Pid,
+    pub time: &'a [u8],
+    pub signal: &'a [u8],
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum LineData<'a> {
     Syscall(RawData<'a>),
     Exit(ExitData<'a>),
+    Signal(SignalData<'a>),
 }
 
 impl<'a> LineData<'a> {
@@ -48,16 +59,112 @@ impl<'a> LineData<'a> {
         match self {
             LineData::Syscall(data) => data.pid,
             LineData::Exit(data) => data.pid,
+            LineData::Signal(data) => data.pid,
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum OtherFields<'a> {
+    Accept {
+        listener: Option<&'a [u8]>,
+        conn: &'a [u8],
+    },
+    Chmod {
+        path: &'a [u8],
+        mode: &'a [u8],
+    },
+    Chown {
+        path: &'a [u8],
+        owner: &'a [u8],
+        group: &'a [u8],
+    },
     Clone(ProcType),
-    Execve(Vec<&'a [u8]>),
+    // The file of the fd returned by 'dup2' or 'dup3', and the file of the fd
+    // it replaced, when traced with '-y'
+    Dup {
+        file: Option<&'a [u8]>,
+        replaced: Option<&'a [u8]>,
+    },
+    // The fd passed to 'close', and its file when traced with '-y'
+    Close {
+        fd: i32,
+        file: Option<&'a [u8]>,
+    },
+    // The socket's fd when traced with '-y', and the address connected or
+    // bound to as printed by strace, e.g. '{sa_family=AF_UNIX, sun_path="/run/app.sock"}'
+    Connect {
+        file: Option<&'a [u8]>,
+        addr: Option<&'a [u8]>,
+    },
+    // An 'epoll_ctl' operation such as 'EPOLL_CTL_ADD' on the epoll fd
+    // 'epfd', the fd it acts on and its file when traced with '-y', and the
+    // data 'epoll_wait' returns when the fd is ready
+    EpollCtl {
+        epfd: i32,
+        op: &'a [u8],
+        fd: i32,
+        file: Option<&'a [u8]>,
+        data: Option<&'a [u8]>,
+    },
+    // The epoll fd waited on, not printed on resumed lines, and the data of
+    // each ready event returned
+    EpollWait {
+        epfd: Option<i32>,
+        ready: Vec<&'a [u8]>,
+    },
+    // Raw argument tokens and the environment variable count
+    Execve(Vec<&'a [u8]>, Option<usize>),
+    // The two fds returned by 'pipe', 'pipe2', or 'socketpair', and the
+    // first one's file when traced with '-y'
+    FdPair {
+        fds: [i32; 2],
+        file: Option<&'a [u8]>,
+    },
     File(&'a [u8]),
-    Futex(&'a [u8]),
+    // The address of a private futex, and whether the call waits on it
+    Futex {
+        addr: &'a [u8],
+        wait: bool,
+    },
+    // The fd's file when traced with '-y', and the bytes asked to be read or written
+    Io {
+        file: Option<&'a [u8]>,
+        requested: Option<i32>,
+    },
+    // A 'clone' or 'clone3' with 'CLONE_NEW*' flags, or an 'unshare', which
+    // has no child
+    NewNamespaces {
+        proc_type: Option<ProcType>,
+        namespaces: Namespaces,
+    },
+    Open {
+        file: &'a [u8],
+        flags: &'a [u8],
+        // The file the returned fd refers to when traced with '-y', which is
+        // the target when `file` is a symlink
+        resolved: Option<&'a [u8]>,
+    },
+    // A read of a '/proc/<pid>/status' file, with the effective uid and the
+    // PID of the process tracing it when the read reached those lines
+    ProcStatus {
+        file: &'a [u8],
+        requested: Option<i32>,
+        euid: Option<u32>,
+        tracer_pid: Option<Pid>,
+    },
+    // A symlink, and the path it points to when 'readlink' succeeded
+    Readlink {
+        path: &'a [u8],
+        target: &'a [u8],
+    },
+    Rename {
+        from: &'a [u8],
+        to: &'a [u8],
+    },
+    // The effective uid set by 'setuid', 'setreuid', or 'setresuid'
+    Uid(u32),
+    Umask(&'a [u8]),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -77,13 +184,51 @@ impl<'a> RawData<'a> {
     pub fn file(&self) -> Option<&'a [u8]> {
         match self.other {
             Some(OtherFields::File(f)) => Some(f),
+            Some(OtherFields::Accept { conn, .. }) => Some(conn),
+            Some(OtherFields::Close { file, .. }) => file,
+            Some(OtherFields::Connect { file, .. }) => file,
+            Some(OtherFields::Dup { file, .. }) => file,
+            Some(OtherFields::FdPair { file, .. }) => file,
+            Some(OtherFields::Io { file, .. }) => file,
+            Some(OtherFields::Open { file, .. }) => Some(file),
+            Some(OtherFields::ProcStatus { file, .. }) => Some(file),
+            Some(OtherFields::Readlink { path, .. }) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The path or fd a call acted on, for renames the path renamed from
+    pub fn path(&self) -> Option<&'a [u8]> {
+        match self.other {
+            Some(OtherFields::Chmod { path, .. }) => Some(path),
+            Some(OtherFields::Chown { path, .. }) => Some(path),
+            Some(OtherFields::Rename { from, .. }) => Some(from),
+            _ => self.file(),
+        }
+    }
+
+    /// The bytes a read or write call asked for, which may be more than it returned
+    pub fn requested(&self) -> Option<i32> {
+        match self.other {
+            Some(OtherFields::Io { requested, .. }) => requested,
+            Some(OtherFields::ProcStatus { requested, .. }) => requested,
             _ => None,
         }
     }
 
+    /// Whether an 'open' or 'openat' call could create or modify the file
+    pub fn opened_for_write(&self) -> bool {
+        match self.other {
+            Some(OtherFields::Open { flags, .. }) => flags
+                .split_str("|")
+                .any(|f| matches!(f, b"O_WRONLY" | b"O_RDWR" | b"O_CREAT" | b"O_TRUNC")),
+            _ => false,
+        }
+    }
+
     pub fn execve(&self) -> Option<&[&'a [u8]]> {
         match &self.other {
-            Some(OtherFields::Execve(v)) => Some(v),
+            Some(OtherFields::Execve(v, _)) => Some(v),
             _ => None,
         }
     }
@@ -91,6 +236,14 @@ impl<'a> RawData<'a> {
     pub fn proc_type(&self) -> Option<ProcType> {
         match self.other {
             Some(OtherFields::Clone(p)) => Some(p),
+            Some(OtherFields::NewNamespaces { proc_type, .. }) => proc_type,
+            _ => None,
+        }
+    }
+
+    pub fn namespaces(&self) -> Option<Namespaces> {
+        match self.other {
+            Some(OtherFields::NewNamespaces { namespaces, .. }) => Some(namespaces),
             _ => None,
         }
     }
@@ -98,6 +251,577 @@ impl<'a> RawData<'a> {
 
 const CLONE_THREAD: &str = "CLONE_THREAD";
 
+// 17822 13:43:41.413034 clone(child_stack=NULL, flags=CLONE_NEWNS|CLONE_NEWPID|SIGCHLD <unfinished ...>
+//                                               ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+fn clone_fields<'a>(flags: &[u8]) -> OtherFields<'a> {
+    let proc_type = if flags.contains_str(CLONE_THREAD) {
+        ProcType::Thread
+    } else {
+        ProcType::Process
+    };
+
+    let namespaces = Namespaces::from_flags(flags);
+    if namespaces.is_empty() {
+        OtherFields::Clone(proc_type)
+    } else {
+        OtherFields::NewNamespaces {
+            proc_type: Some(proc_type),
+            namespaces,
+        }
+    }
+}
+
+// Splits a returned fd such as '7<socket:[61613]>' into its number and, when
+// strace was run with '-y', the file it refers to
+fn split_fd_token(token: &[u8]) -> (Option<i32>, Option<&[u8]>) {
+    let mut fd_split = token.splitn_str(2, "<");
+    let fd = fd_split
+        .next()
+        .and_then(|s| s.to_str().ok())
+        .and_then(|s| s.parse::<i32>().ok());
+    let file = fd_split.next().and_then(|s| s.strip_suffix(b">"));
+
+    (fd, file)
+}
+
+// The file of the fd a call returned, which unlike the last token keeps any
+// spaces in its name
+// 2104  10:01:12.118204 openat(AT_FDCWD, "/srv/a b.txt", O_RDONLY) = 3</srv/a b.txt> <0.000031>
+//                                                                     ^^^^^^^^^^^^
+fn returned_file(line: &[u8]) -> Option<&[u8]> {
+    let start = line.rfind(" = ")? + 3;
+    let end = start + line[start..].rfind(" <")?;
+    split_fd_token(&line[start..end]).1
+}
+
+// A failed 'readlink' leaves the buffer's address in place of the target
+fn readlink_fields<'a>(path: &'a [u8], target: Option<&'a [u8]>) -> OtherFields<'a> {
+    match target.map(trim_arg).and_then(quoted_arg) {
+        Some(target) => OtherFields::Readlink { path, target },
+        None => OtherFields::File(path),
+    }
+}
+
+// 17819 13:43:41.450318 read(22<pipe:[879334396]>,  <unfinished ...>
+//                               ^^^^^^^^^^^^^^^^
+fn fd_file(token: &[u8]) -> Option<&[u8]> {
+    token
+        .splitn_str(2, "<")
+        .nth(1)
+        .and_then(|s| s.get(..s.len().checked_sub(2)?))
+}
+
+// 9071  10:14:21.335300 epoll_wait(5<anon_inode:[eventpoll]>, [{EPOLLIN, {u32=7, u64=7}}], 1024, -1) = 1 <0.000011>
+//                                                           ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+fn epoll_ready(arg: &[u8]) -> Vec<&[u8]> {
+    let events = match arg.strip_prefix(b"[").and_then(|a| a.strip_suffix(b"]")) {
+        Some(events) => events,
+        None => return Vec::new(),
+    };
+
+    ArgSplit::new(events).filter_map(epoll_data).collect()
+}
+
+// Newer versions of strace name the fields of an event
+//   {EPOLLIN, {u32=7, u64=7}}
+//                        ^
+//   {events=EPOLLIN, data={u32=7, u64=7}}
+//                                  ^
+fn epoll_data(event: &[u8]) -> Option<&[u8]> {
+    let start = event.find("u64=").map(|idx| idx + 4).or_else(|| {
+        // {events=EPOLLIN, data=0x7f3c2c0008c0}
+        //                       ^^^^^^^^^^^^^^
+        event.find("data=").map(|idx| idx + 5)
+    })?;
+    let data = &event[start..];
+    let end = data.find_byteset(b",}").unwrap_or(data.len());
+    Some(data[..end].trim()).filter(|d| !d.is_empty())
+}
+
+// Strips the separator following an argument, e.g. '0755)' or '1000,'
+fn trim_arg(token: &[u8]) -> &[u8] {
+    token.trim_end_with(|c| c == ',' || c == ')')
+}
+
+// 17819 13:43:41.450311 pipe2([22<pipe:[879334396]>, 23<pipe:[879334396]>], O_CLOEXEC) = 0 <0.000022>
+//                             ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+fn fd_pair(arg: &[u8]) -> Option<OtherFields<'_>> {
+    let mut fds = arg
+        .strip_prefix(b"[")?
+        .strip_suffix(b"]")?
+        .split_str(",")
+        .map(|token| split_fd_token(token.trim()));
+    let (first, file) = fds.next()?;
+    let (second, _) = fds.next()?;
+
+    Some(OtherFields::FdPair {
+        fds: [first?, second?],
+        file,
+    })
+}
+
+// A path argument is either quoted, or an fd that strace has resolved with '-y'
+fn path_arg(token: &[u8]) -> Option<&[u8]> {
+    let token = trim_arg(token);
+    quoted_arg(token).or_else(|| split_fd_token(token).1)
+}
+
+fn quoted_arg(token: &[u8]) -> Option<&[u8]> {
+    token.strip_prefix(b"\"")?.strip_suffix(b"\"")
+}
+
+// The environment of an 'execve' is either summarized by strace, or printed
+// in full with '-v'
+//   0xc0001c2000 /* 22 vars */
+//   [/* 22 vars */]
+//   ["HOME=/root", "PATH=/usr/bin"]
+fn env_count(arg: &[u8]) -> Option<usize> {
+    if let Some(idx) = arg.find("/*") {
+        arg[idx + 2..]
+            .fields()
+            .next()
+            .and_then(|c| c.to_str().ok())
+            .and_then(|c| c.parse::<usize>().ok())
+    } else {
+        let vars = arg.strip_prefix(b"[")?.strip_suffix(b"]")?;
+        Some(ArgSplit::new(vars).count())
+    }
+}
+
+fn int_arg(arg: &[u8]) -> Option<i32> {
+    arg.to_str().ok().and_then(|a| a.parse::<i32>().ok())
+}
+
+// '-1' leaves the uid unchanged
+fn uid_arg(arg: &[u8]) -> Option<u32> {
+    arg.to_str().ok().and_then(|a| a.parse::<u32>().ok())
+}
+
+// '/proc/495/status', or '/proc/495/task/497/status' for a thread
+fn is_proc_status(file: &[u8]) -> bool {
+    file.starts_with(b"/proc/") && file.ends_with(b"/status")
+}
+
+// The effective uid from the contents of a status file, the second of its
+// real, effective, saved, and filesystem uids
+//   "Name:\tcat\nUmask:\t0022\n ... Uid:\t1000\t1000\t1000\t1000\nGid:\t..."
+fn status_euid(buf: &[u8]) -> Option<u32> {
+    let idx = buf.find(br"Uid:\t")?;
+    let euid = buf[idx + 6..].split_str(r"\t").nth(1)?;
+    let end = euid
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .unwrap_or(euid.len());
+
+    euid[..end].to_str().ok()?.parse::<u32>().ok()
+}
+
+// The PID of the process tracing the one a status file describes, a
+// debugger or strace itself, `None` when it isn't traced
+//   "... PPid:\t495\nTracerPid:\t7388\nUid:\t..."
+fn status_tracer_pid(buf: &[u8]) -> Option<Pid> {
+    let idx = buf.find(br"TracerPid:\t")?;
+    let digits = &buf[idx + 12..];
+    let end = digits
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+
+    digits[..end]
+        .to_str()
+        .ok()?
+        .parse::<Pid>()
+        .ok()
+        .filter(|&pid| pid != 0)
+}
+
+// The total of the buffers passed to a vectored call
+//   [{iov_base="GET / HTTP/1.1\r\n", iov_len=16}, {iov_base="\r\n", iov_len=2}]
+//   {msg_name=NULL, msg_namelen=0, msg_iov=[{iov_base="", iov_len=4096}], msg_iovlen=1, ...}
+fn iov_total(arg: &[u8]) -> Option<i32> {
+    let mut lens = arg
+        .find_iter("iov_len=")
+        .map(|idx| {
+            let digits = &arg[idx + 8..];
+            let end = digits
+                .find_not_byteset(b"0123456789")
+                .unwrap_or(digits.len());
+            int_arg(&digits[..end])
+        })
+        .peekable();
+
+    lens.peek()?;
+    lens.try_fold(0i32, |total, len| total.checked_add(len?))
+}
+
+fn is_vectored_io(syscall: &[u8]) -> bool {
+    matches!(
+        syscall,
+        b"preadv"
+            | b"preadv2"
+            | b"pwritev"
+            | b"pwritev2"
+            | b"readv"
+            | b"recvmsg"
+            | b"sendmsg"
+            | b"writev"
+    )
+}
+
+// 17819 13:43:40.149100 read(6</proc/sys/net/core/somaxconn>, "", 65531) = 0 <0.000013>
+//                                                                 ^^^^^
+// Reads print their buffer on return, so an unfinished read has no count
+fn requested_bytes(syscall: &[u8], buf: Option<&[u8]>, args: &mut ArgSplit) -> Option<i32> {
+    if is_vectored_io(syscall) {
+        buf.and_then(iov_total)
+    } else {
+        args.next().and_then(int_arg)
+    }
+}
+
+// 17819 13:43:40.149100 <... read resumed>"", 65531) = 0 <0.000013>
+//                                             ^^^^^
+fn resumed_requested_bytes(syscall: &[u8], args: &mut ArgSplit) -> Option<i32> {
+    match syscall {
+        b"pread64" | b"read" | b"recv" | b"recvfrom" => args.nth(1).and_then(int_arg),
+        b"preadv" | b"preadv2" | b"readv" | b"recvmsg" => args.next().and_then(iov_total),
+        _ => None,
+    }
+}
+
+/// Splits the arguments of a call at top level commas. Commas and spaces
+/// inside quoted strings, or the nested '()', '[]', and '{}' of structs and
+/// arrays printed by 'strace -v', do not split an argument. Stops at the ')'
+/// closing the call, or the end of the line for unfinished calls.
+#[derive(Clone)]
+struct ArgSplit<'a> {
+    region: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> ArgSplit<'a> {
+    // 'region' starts just after the '(' following the syscall name
+    fn new(region: &'a [u8]) -> ArgSplit<'a> {
+        ArgSplit {
+            region,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    // Bytes of the region consumed so far, including the last separator
+    fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for ArgSplit<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.done {
+            return None;
+        }
+
+        let rest = &self.region[self.offset..];
+        let mut depth = 0usize;
+        let mut idx = 0;
+
+        while let Some(&c) = rest.get(idx) {
+            match c {
+                b'"' => match string_end(&rest[idx + 1..]) {
+                    Some(len) => idx += len + 1,
+                    None => break,
+                },
+                b'(' | b'[' | b'{' => depth += 1,
+                b')' | b']' | b'}' if depth > 0 => depth -= 1,
+                b')' => {
+                    self.done = true;
+                    self.offset += idx + 1;
+                    return Some(rest[..idx].trim()).filter(|arg| !arg.is_empty());
+                }
+                b',' if depth == 0 => {
+                    self.offset += idx + 1;
+                    return Some(rest[..idx].trim());
+                }
+                _ => {}
+            }
+            idx += 1;
+        }
+
+        // 17819 13:43:41.450318 read(22<pipe:[879334396]>,  <unfinished ...>
+        //                                                   ^^^^^^^^^^^^^^^^
+        self.done = true;
+        self.offset = self.region.len();
+        let rest = rest.trim();
+        let arg = rest
+            .strip_suffix(b"<unfinished ...>")
+            .unwrap_or(rest)
+            .trim();
+        Some(arg).filter(|arg| !arg.is_empty())
+    }
+}
+
+// Offset of the '"' closing a string, 'rest' starts just after the opening quote.
+// Buffers printed with '-x' or '-xx' are mostly '\x48' escapes, which are
+// skipped whole rather than a byte at a time.
+fn string_end(rest: &[u8]) -> Option<usize> {
+    let mut idx = 0;
+
+    loop {
+        match rest.get(idx)? {
+            b'"' => return Some(idx),
+            b'\\' if rest.get(idx + 1) == Some(&b'x') => idx += 4,
+            b'\\' => idx += 2,
+            _ => idx += 1,
+        }
+    }
+}
+
+// 2104  10:01:12.118204 chown("/usr/local/bin/tool", 0, 0) = 0 <0.000031>
+//                                                    ^^  ^^
+fn permission_fields<'a, I>(syscall: &[u8], path: &'a [u8], args: &mut I) -> Option<OtherFields<'a>>
+where
+    I: Iterator<Item = &'a [u8]>,
+{
+    if syscall.contains_str("chmod") {
+        let mode = args.next().map(trim_arg)?;
+        Some(OtherFields::Chmod { path, mode })
+    } else {
+        let owner = args.next().map(trim_arg)?;
+        let group = args.next().map(trim_arg)?;
+        Some(OtherFields::Chown { path, owner, group })
+    }
+}
+
+/// The arguments printed on a line of a call. Started lines end with the last
+/// argument printed before '<unfinished ...>', and resumed lines only have
+/// the arguments printed after 'resumed>', so the two together hold them all.
+pub fn line_args(bytes: &[u8]) -> Vec<&[u8]> {
+    // 17819 13:43:39.897107 <... read resumed>"abc", 4096) = 3 <0.000016>
+    //                                          ^^^^^^^^^^^^
+    let region = match bytes.find("resumed>") {
+        Some(idx) => {
+            let rest = bytes[idx + 8..].trim_start();
+            // A ',' left over from the argument printed before the call was interrupted
+            rest.strip_prefix(b",").unwrap_or(rest)
+        }
+        // 17819 13:43:39.888658 read(3</etc/hosts>, "abc", 4096) = 3 <0.000019>
+        //                            ^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+        None => match bytes.find_byte(b'(') {
+            Some(idx) => &bytes[idx + 1..],
+            None => return Vec::new(),
+        },
+    };
+
+    ArgSplit::new(region).collect()
+}
+
+/// The value returned on a line of a call, without the name '-y' adds to
+/// a returned fd. Started lines have none.
+pub fn line_return(bytes: &[u8]) -> Option<&[u8]> {
+    // 17819 13:43:39.888967 open("/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache> <0.000014>
+    //                                                                      ^
+    let idx = bytes.rfind(" = ")?;
+    let token = bytes[idx + 3..].fields().next()?;
+
+    token.split_str("<").next()
+}
+
+/// The PID starting `line`, read without tokenizing the rest of it, to skip
+/// lines of other PIDs before parsing them
+pub(crate) fn line_pid(line: &[u8]) -> Option<Pid> {
+    let end = line
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .unwrap_or(line.len());
+    line[..end].to_str().ok()?.parse().ok()
+}
+
+/// The PID given to every line of a trace captured without '-f'
+pub const NO_PID: Pid = 0;
+
+/// Whether a trace was captured without '-f', so its lines start with the
+/// timestamp rather than a PID. Judged from the first line starting with
+/// either, lines the traced program printed are passed over.
+pub fn lacks_pids(buffer: &[u8]) -> bool {
+    buffer
+        .lines()
+        .find_map(|line| {
+            let mut tokens = line.fields();
+            let first = tokens.next()?;
+            if is_timestamp(first) {
+                Some(true)
+            } else if line_pid(first).is_some() && tokens.next().is_some_and(is_timestamp) {
+                Some(false)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(false)
+}
+
+/// Copies a trace captured without PIDs, starting each line that starts with
+/// a timestamp with `NO_PID`, so the trace parses as that of one process
+pub fn add_pid_column(buffer: &[u8]) -> Vec<u8> {
+    let prefix = format!("{} ", NO_PID);
+    let mut copy = Vec::with_capacity(buffer.len() + buffer.len() / 16);
+
+    for line in buffer.lines_with_terminator() {
+        if line.fields().next().is_some_and(is_timestamp) {
+            copy.extend_from_slice(prefix.as_bytes());
+        }
+        copy.extend_from_slice(line);
+    }
+
+    copy
+}
+
+/// Whether a call in the trace continues onto the lines after it, as when a
+/// log shipper or terminal wraps the long structs printed by 'strace -v'
+pub fn has_continued_lines(buffer: &[u8]) -> bool {
+    let mut prev: Option<&[u8]> = None;
+
+    for line in buffer.lines() {
+        if !is_trace_line(line) && prev.is_some_and(continues) {
+            return true;
+        }
+        prev = Some(line);
+    }
+
+    false
+}
+
+/// Copies a trace, joining each call left open at the end of its line with
+/// the lines continuing it, those that don't start with a PID and timestamp,
+/// so a struct wrapped onto several lines parses as one call
+pub fn join_continued_lines(buffer: &[u8]) -> Vec<u8> {
+    let mut copy = Vec::with_capacity(buffer.len());
+    let mut open = false;
+
+    for line in buffer.lines_with_terminator() {
+        let content = line.trim_end_with(|c| c == '\n' || c == '\r');
+        if is_trace_line(content) {
+            open = continues(content);
+        } else if open {
+            // The newline was added by the wrap rather than by strace
+            let len = copy.len();
+            copy.truncate(len - trailing_newline(&copy));
+            open = continues_after(&copy, content);
+        }
+        copy.extend_from_slice(line);
+    }
+
+    copy
+}
+
+// The bytes of the '\n' or '\r\n' ending `buffer`
+fn trailing_newline(buffer: &[u8]) -> usize {
+    match buffer {
+        [.., b'\r', b'\n'] => 2,
+        [.., b'\n'] => 1,
+        _ => 0,
+    }
+}
+
+// Whether the call `joined` is still open once `part` is appended, `joined`
+// ending with the trace line the call started on
+fn continues_after(joined: &[u8], part: &[u8]) -> bool {
+    let start = joined.rfind_byte(b'\n').map_or(0, |idx| idx + 1);
+    let line = [&joined[start..], part].concat();
+    continues(&line)
+}
+
+// Whether `line` starts with a PID and a timestamp as strace writes them
+fn is_trace_line(line: &[u8]) -> bool {
+    let mut tokens = line.fields();
+    tokens
+        .next()
+        .is_some_and(|pid| pid.to_str().is_ok_and(|p| p.parse::<Pid>().is_ok()))
+        && tokens.next().is_some_and(is_timestamp)
+}
+
+// Whether the call on `line` is cut off before its closing ')', inside a
+// string, struct, or array, rather than finished or '<unfinished ...>'
+//   2104  10:01:12.118204 rt_sigaction(SIGINT, {sa_handler=0x55d0c0a0e2d0, sa_mask=[INT
+//                                     ^
+fn continues(line: &[u8]) -> bool {
+    if line.trim_end().ends_with(b"<unfinished ...>") {
+        return false;
+    }
+    // The call is the third token, signals and exits such as
+    // '--- SIGCHLD {...} ---' and '+++ exited with 0 +++' have none
+    let call = match line.fields().nth(2) {
+        Some(call) => call,
+        None => return false,
+    };
+    let rest = &line[call.as_ptr() as usize - line.as_ptr() as usize..];
+    let region = if call.starts_with(b"<...") {
+        match rest.find("resumed>") {
+            Some(idx) => &rest[idx + 8..],
+            None => return false,
+        }
+    } else if call
+        .first()
+        .is_some_and(|c| c.is_ascii_alphabetic() || *c == b'_')
+    {
+        match rest.find_byte(b'(') {
+            Some(idx) => &rest[idx + 1..],
+            None => return false,
+        }
+    } else {
+        return false;
+    };
+
+    !closes_call(region)
+}
+
+// Whether the ')' closing the call is in `region`, which starts just after
+// the '(' following the syscall name, as for `ArgSplit`
+fn closes_call(region: &[u8]) -> bool {
+    let mut depth = 0usize;
+    let mut idx = 0;
+
+    while let Some(&c) = region.get(idx) {
+        match c {
+            b'"' => match string_end(&region[idx + 1..]) {
+                Some(len) => idx += len + 1,
+                None => return false,
+            },
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' | b'}' if depth > 0 => depth -= 1,
+            b')' => return true,
+            _ => {}
+        }
+        idx += 1;
+    }
+
+    false
+}
+
+/// Whether `token` is a time as printed by '-tt', '-ttt', or '-r', rather
+/// than a number in a line strace didn't write
+pub(crate) fn is_timestamp(token: &[u8]) -> bool {
+    token.first().is_some_and(u8::is_ascii_digit)
+        && token
+            .iter()
+            .all(|c| c.is_ascii_digit() || *c == b':' || *c == b'.')
+        && token.iter().any(|c| *c == b':' || *c == b'.')
+}
+
+/// Parses a single line of strace output.
+///
+/// For any input this will:
+/// * Not panic, a malformed or truncated line returns `None` or partial `RawData`
+/// * Return `None` unless the line starts with a PID and a timestamp, see `is_timestamp`
+/// * Return a syscall name beginning with an ASCII letter or '_', followed by '('
+///   unless the call is resumed
+/// * Borrow all returned byte slices from `bytes`
+///
+/// Arguments split onto a following line, such as a string containing a raw
+/// newline, are parsed as an unfinished call, and the following line is skipped.
+/// Join such lines first with `join_continued_lines`.
 pub fn parse_line<'a>(bytes: &'a [u8]) -> Option<LineData<'a>> {
     let mut tokens = bytes.fields_with(|c| c.is_ascii_whitespace());
     let mut rev_tokens = match bytes.last() {
@@ -117,13 +841,9 @@ pub fn parse_line<'a>(bytes: &'a [u8]) -> Option<LineData<'a>> {
 
     // 17819 13:43:39.888658 brk(NULL)         = 0x3213000 <0.000019>
     //       ^^^^^^^^^^^^^^^
-    let time = tokens.next().filter(|time_token| {
-        time_token
-            .chars()
-            .next()
-            .filter(|c| c.is_ascii_digit())
-            .is_some()
-    })?;
+    let time = tokens
+        .next()
+        .filter(|time_token| is_timestamp(time_token))?;
 
     // 17819 13:43:39.888658 brk(NULL)         = 0x3213000 <0.000019>
     //                                                     ^^^^^^^^^^
@@ -135,11 +855,11 @@ pub fn parse_line<'a>(bytes: &'a [u8]) -> Option<LineData<'a>> {
 
     // 17819 13:43:39.897107 <... rt_sigprocmask resumed>NULL, 8) = 0 <0.000016>
     //                       ^^^^
-    let call_status = if let Some(b'<') = syscall_token.get(0) {
+    let call_status = if let Some(b'<') = syscall_token.first() {
         CallStatus::Resumed
     // 17819 13:43:39.888658 brk(NULL)         = 0x3213000 <0.000019>
     //                                                     ^^^^^^^^^^
-    } else if let Some(b'<') = duration_token.get(0) {
+    } else if let Some(b'<') = duration_token.first() {
         CallStatus::Complete
     // 90718 13:48:58.423962 +++ exited with 0 +++
     //                       ^^^
@@ -158,15 +878,25 @@ pub fn parse_line<'a>(bytes: &'a [u8]) -> Option<LineData<'a>> {
                     .and_then(|s| s.parse::<i32>().ok())?;
                 Some(LineData::Exit(ExitData {
                     pid,
+                    time,
                     exit: ExitType::Exit(code),
                 }))
             }
             b"killed" => Some(LineData::Exit(ExitData {
                 pid,
+                time,
                 exit: ExitType::Signal(signal_code),
             })),
             _ => None,
         };
+    // 9071  10:14:21.500000 --- SIGALRM {si_signo=SIGALRM, si_code=SI_KERNEL} ---
+    //                       ^^^
+    } else if syscall_token == b"---" {
+        // 9071  10:14:21.500000 --- SIGALRM {si_signo=SIGALRM, si_code=SI_KERNEL} ---
+        //                           ^^^^^^^
+        // Group stops, '--- stopped by SIGTSTP ---', don't run a handler
+        let signal = tokens.next().filter(|t| t.starts_with(b"SIG"))?;
+        return Some(LineData::Signal(SignalData { pid, time, signal }));
     } else {
         CallStatus::Started
     };
@@ -187,31 +917,59 @@ pub fn parse_line<'a>(bytes: &'a [u8]) -> Option<LineData<'a>> {
             })?;
 
             match syscall {
-                b"clone" => {
-                    // 17819 13:43:39.897681 <... clone resumed>, parent_tid=[17822], tls=0x7f1c6f753700, child_tidptr=0x7f1c6f7539d0) = 17822 <0.000041>
-                    //                                  ^^^^^^^^^
+                // 17819 13:43:39.897681 <... clone resumed>, parent_tid=[17822], tls=0x7f1c6f753700, child_tidptr=0x7f1c6f7539d0) = 17822 <0.000041>
+                //                                  ^^^^^^^^^
+                b"clone"
                     if tokens
                         .next()
                         .map(|t| !t.ends_with_str(")"))
-                        .unwrap_or_default()
-                    {
-                        // 10738 01:58:22.788361 <... clone resumed> child_stack=0, flags=CLONE_VM|CLONE_VFORK|SIGCHLD) = 13442 <0.002381>
-                        //                                                          ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
-                        let flags = tokens.nth(1)?;
-                        if flags.contains_str(CLONE_THREAD) {
-                            other = Some(OtherFields::Clone(ProcType::Thread));
-                        } else if flags.starts_with_str("flags") {
-                            other = Some(OtherFields::Clone(ProcType::Process));
-                        }
+                        .unwrap_or_default() =>
+                {
+                    // 10738 01:58:22.788361 <... clone resumed> child_stack=0, flags=CLONE_VM|CLONE_VFORK|SIGCHLD) = 13442 <0.002381>
+                    //                                                          ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+                    let flags = tokens.nth(1)?;
+                    if flags.contains_str(CLONE_THREAD) || flags.starts_with_str("flags") {
+                        other = Some(clone_fields(flags));
                     }
                 }
                 b"fork" | b"vfork" => other = Some(OtherFields::Clone(ProcType::Process)),
+                // 9071  10:14:21.335300 <... epoll_wait resumed>[{EPOLLIN, {u32=7, u64=7}}], 1024, -1) = 1 <0.000011>
+                //                                               ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+                b"epoll_pwait" | b"epoll_pwait2" | b"epoll_wait" => {
+                    let mut args = ArgSplit::new(
+                        bytes
+                            .find("resumed>")
+                            .and_then(|idx| bytes.get(idx + 8..))
+                            .unwrap_or_default(),
+                    );
+                    other = Some(OtherFields::EpollWait {
+                        epfd: None,
+                        ready: args.next().map(epoll_ready).unwrap_or_default(),
+                    });
+                }
+                b"pread64" | b"preadv" | b"preadv2" | b"read" | b"readv" | b"recv"
+                | b"recvfrom" | b"recvmsg" => {
+                    let mut args = ArgSplit::new(
+                        bytes
+                            .find("resumed>")
+                            .and_then(|idx| bytes.get(idx + 8..))
+                            .unwrap_or_default(),
+                    );
+                    if let Some(requested) = resumed_requested_bytes(syscall, &mut args) {
+                        other = Some(OtherFields::Io {
+                            file: None,
+                            requested: Some(requested),
+                        });
+                    }
+                }
                 _ => {}
             }
         }
         CallStatus::Complete | CallStatus::Started => {
             // 17819 13:43:39.892101 sigaltstack(NULL, {ss_sp=NULL, ss_flags=SS_DISABLE, ss_size=0}) = 0 <0.000012>
             //                       ^^^^^^^^^^^ ^^^^^
+            // Lines the traced program printed don't have the '(' after a name
+            syscall_token.find_byte(b'(')?;
             let mut syscall_split = syscall_token.splitn_str(2, "(");
 
             // 17819 13:43:39.892101 sigaltstack(NULL, {ss_sp=NULL, ss_flags=SS_DISABLE, ss_size=0}) = 0 <0.000012>
@@ -224,60 +982,263 @@ pub fn parse_line<'a>(bytes: &'a [u8]) -> Option<LineData<'a>> {
                     .is_some()
             })?;
 
+            // 17819 13:43:39.892101 sigaltstack(NULL, {ss_sp=NULL, ss_flags=SS_DISABLE, ss_size=0}) = 0 <0.000012>
+            //                                   ^^^^  ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+            let mut args = ArgSplit::new(
+                bytes
+                    .find_byte(b'(')
+                    .and_then(|idx| bytes.get(idx + 1..))
+                    .unwrap_or_default(),
+            );
+
             match syscall {
                 b"open" => {
                     // 17819 13:43:39.888967 open("/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache> <0.000014>
-                    //                             ^^^^^^^^^^^^^^^^
-                    if let Some(f) = syscall_split.next().and_then(|f| f.get(1..f.len() - 2)) {
-                        other = Some(OtherFields::File(f));
+                    //                             ^^^^^^^^^^^^^^^^  ^^^^^^^^^^^^^^^^^^
+                    if let Some(file) = args.next().and_then(quoted_arg) {
+                        let flags = args.next().unwrap_or_default();
+                        other = Some(OtherFields::Open {
+                            file,
+                            flags,
+                            resolved: None,
+                        });
                     }
                 }
                 b"openat" => {
                     // 17819 13:43:40.146677 openat(AT_FDCWD, "config.toml", O_RDONLY|O_CLOEXEC) = 3</var/opt/gitlab/gitaly/config.toml> <0.000026>
-                    //                                         ^^^^^^^^^^^
-                    if let Some(f) = tokens.next().and_then(|f| f.get(1..f.len() - 2)) {
+                    //                                         ^^^^^^^^^^^  ^^^^^^^^^^^^^^^^^^
+                    if let Some(file) = args.nth(1).and_then(quoted_arg) {
+                        let flags = args.next().unwrap_or_default();
+                        other = Some(OtherFields::Open {
+                            file,
+                            flags,
+                            resolved: None,
+                        });
+                    }
+                }
+                // 2104  10:01:12.118204 unlink("/tmp/build.lock") = 0 <0.000031>
+                //                              ^^^^^^^^^^^^^^^^^
+                b"access" | b"chdir" | b"creat" | b"lstat" | b"mkdir" | b"rmdir" | b"stat"
+                | b"statfs" | b"truncate" | b"unlink" => {
+                    if let Some(f) = args.next().and_then(path_arg) {
                         other = Some(OtherFields::File(f));
                     }
                 }
+                // 2104  10:01:12.118204 unlinkat(AT_FDCWD, "/tmp/build.lock", 0) = 0 <0.000031>
+                //                                          ^^^^^^^^^^^^^^^^^
+                b"faccessat" | b"faccessat2" | b"fstatat64" | b"link" | b"mkdirat"
+                | b"newfstatat" | b"statx" | b"symlink" | b"unlinkat" => {
+                    if let Some(f) = args.nth(1).and_then(path_arg) {
+                        other = Some(OtherFields::File(f));
+                    }
+                }
+                // 2104  10:01:12.118204 readlink("/usr/bin/ruby", "ruby2.7", 4096) = 7 <0.000031>
+                //                                ^^^^^^^^^^^^^^^  ^^^^^^^^^
+                b"readlink" => {
+                    if let Some(path) = args.next().and_then(path_arg) {
+                        other = Some(readlink_fields(path, args.next()));
+                    }
+                }
+                // 2104  10:01:12.118204 readlinkat(AT_FDCWD, "/usr/bin/ruby", "ruby2.7", 4096) = 7 <0.000031>
+                //                                            ^^^^^^^^^^^^^^^  ^^^^^^^^^
+                b"readlinkat" => {
+                    if let Some(path) = args.nth(1).and_then(path_arg) {
+                        other = Some(readlink_fields(path, args.next()));
+                    }
+                }
+                // 2104  10:01:12.118204 linkat(AT_FDCWD, "/tmp/a", AT_FDCWD, "/tmp/b", 0) = 0 <0.000031>
+                //                                                            ^^^^^^^^
+                b"linkat" => {
+                    if let Some(f) = args.nth(3).and_then(path_arg) {
+                        other = Some(OtherFields::File(f));
+                    }
+                }
+                // 2104  10:01:12.118204 symlinkat("/tmp/a", AT_FDCWD, "/tmp/b") = 0 <0.000031>
+                //                                                     ^^^^^^^^
+                b"symlinkat" => {
+                    if let Some(f) = args.nth(2).and_then(path_arg) {
+                        other = Some(OtherFields::File(f));
+                    }
+                }
+                // 2104  10:01:12.118204 rename("/tmp/out.tmp", "/srv/out") = 0 <0.000031>
+                //                              ^^^^^^^^^^^^^^  ^^^^^^^^^^
+                b"rename" => {
+                    let from = args.next().and_then(path_arg);
+                    if let (Some(from), Some(to)) = (from, args.next().and_then(path_arg)) {
+                        other = Some(OtherFields::Rename { from, to });
+                    }
+                }
+                // 2104  10:01:12.118204 renameat2(AT_FDCWD, "/tmp/out.tmp", AT_FDCWD, "/srv/out", 0) = 0 <0.000031>
+                //                                           ^^^^^^^^^^^^^^            ^^^^^^^^^^
+                b"renameat" | b"renameat2" => {
+                    let from = args.nth(1).and_then(path_arg);
+                    if let (Some(from), Some(to)) = (from, args.nth(1).and_then(path_arg)) {
+                        other = Some(OtherFields::Rename { from, to });
+                    }
+                }
                 b"execve" => {
                     // 17840 13:43:41.449433 execve("/bin/ps", ["ps", "-o", "rss=", "-p", "17838"], 0xc0001c2000 /* 22 vars */ <unfinished ...>
-                    //                              ^^^^^^^^^^ ^^^^^^ ^^^^^ ^^^^^^^ ^^^^^ ^^^^^^^^^ ^^^^^^^^^^^^ ^^ ^^ ^^^^
-                    if let Some(t) = syscall_split.next() {
-                        let mut v = vec![t];
-                        tokens
-                            .by_ref()
-                            .take_while(|&s| s != b"[/*" && s != b"/*")
-                            .for_each(|arg| v.push(arg));
+                    //                              ^^^^^^^^^^ ^^^^^^ ^^^^^ ^^^^^^^ ^^^^^ ^^^^^^^^^
+                    let argv_end = args.nth(1).map(|_| args.offset());
+                    if let Some(end) = argv_end {
+                        let v = args.region[..end]
+                            .fields_with(|c| c.is_ascii_whitespace())
+                            .collect();
+
+                        // 17840 13:43:41.449433 execve("/bin/ps", ["ps", "-o", "rss=", "-p", "17838"], 0xc0001c2000 /* 22 vars */ <unfinished ...>
+                        //                                                                                                ^^
+                        let env_count = args.next().and_then(env_count);
 
-                        other = Some(OtherFields::Execve(v));
+                        other = Some(OtherFields::Execve(v, env_count));
                     }
                 }
                 b"futex" => {
                     // 17826 13:43:41.450300 futex(0xc00005ef48, FUTEX_WAKE_PRIVATE, 1 <unfinished ...>
                     //                             ^^^^^^^^^^^^
-                    if let Some(addr) = syscall_split.next().and_then(|a| a.get(..a.len() - 1)) {
+                    if let Some(addr) = syscall_split
+                        .next()
+                        .and_then(|a| a.get(..a.len().checked_sub(1)?))
+                    {
                         // 17826 13:43:41.450300 futex(0xc00005ef48, FUTEX_WAKE_PRIVATE, 1 <unfinished ...>
                         //                                           ^^^^^^^^^^^^^^^^^^^
-                        if tokens
-                            .next()
-                            .map(|t| t.contains_str("PRIVATE"))
-                            .unwrap_or_default()
-                        {
-                            other = Some(OtherFields::Futex(addr))
+                        if let Some(op) = tokens.next().filter(|t| t.contains_str("PRIVATE")) {
+                            let wait = op.contains_str("WAIT") || op.starts_with(b"FUTEX_LOCK_PI");
+                            other = Some(OtherFields::Futex { addr, wait })
                         }
                     }
                 }
+                b"bind" | b"connect" => {
+                    // 9080  10:14:21.000000 connect(5<socket:[300]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = 0 <0.000120>
+                    //                                 ^^^^^^^^^^^^  ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+                    let file = syscall_split.next().and_then(fd_file);
+                    let addr = args.nth(1).filter(|a| a.starts_with(b"{"));
+                    if file.is_some() || addr.is_some() {
+                        other = Some(OtherFields::Connect { file, addr });
+                    }
+                }
+                // 9071  10:14:21.335300 epoll_ctl(5<anon_inode:[eventpoll]>, EPOLL_CTL_ADD, 7<socket:[61613]>, {EPOLLIN, {u32=7, u64=7}}) = 0 <0.000011>
+                //                                 ^^^^^^^^^^^^^^^^^^^^^^^^  ^^^^^^^^^^^^^  ^^^^^^^^^^^^^^^^^^  ^^^^^^^^^^^^^^^^^^^^^^^^^^
+                b"epoll_ctl" => {
+                    let epfd = split_fd_token(args.next().unwrap_or_default()).0;
+                    let op = args.next();
+                    let (fd, file) = split_fd_token(args.next().unwrap_or_default());
+                    if let (Some(epfd), Some(op), Some(fd)) = (epfd, op, fd) {
+                        other = Some(OtherFields::EpollCtl {
+                            epfd,
+                            op,
+                            fd,
+                            file,
+                            data: args.next().and_then(epoll_data),
+                        });
+                    }
+                }
+                // 9071  10:14:21.335300 epoll_wait(5<anon_inode:[eventpoll]>,  <unfinished ...>
+                //                                  ^^^^^^^^^^^^^^^^^^^^^^^^
+                b"epoll_pwait" | b"epoll_pwait2" | b"epoll_wait" => {
+                    if let Some(epfd) = split_fd_token(args.next().unwrap_or_default()).0 {
+                        other = Some(OtherFields::EpollWait {
+                            epfd: Some(epfd),
+                            ready: args.next().map(epoll_ready).unwrap_or_default(),
+                        });
+                    }
+                }
+                // 9071  10:14:21.335300 close(7<socket:[61613]>) = 0 <0.000011>
+                //                             ^^^^^^^^^^^^^^^^^
+                b"close" => {
+                    if let (Some(fd), file) = split_fd_token(args.next().unwrap_or_default()) {
+                        other = Some(OtherFields::Close { fd, file });
+                    }
+                }
+                // 17819 13:43:41.450311 pipe2([22<pipe:[879334396]>, 23<pipe:[879334396]>], O_CLOEXEC) = 0 <0.000022>
+                //                             ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+                b"pipe" | b"pipe2" => {
+                    other = args.next().and_then(fd_pair);
+                }
+                // 9071  10:14:21.335300 socketpair(AF_UNIX, SOCK_STREAM, 0, [7<UNIX:[61613]>, 8<UNIX:[61614]>]) = 0 <0.000011>
+                //                                                              ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+                b"socketpair" => {
+                    other = args.nth(3).and_then(fd_pair);
+                }
+                // 477   10:00:00.200000 dup2(3</tmp/a>, 1</dev/pts/0>) = 1</tmp/a> <0.000010>
+                //                                       ^^^^^^^^^^^^^
+                b"dup2" | b"dup3" => {
+                    let replaced = split_fd_token(args.nth(1).unwrap_or_default()).1;
+                    if replaced.is_some() {
+                        other = Some(OtherFields::Dup {
+                            file: None,
+                            replaced,
+                        });
+                    }
+                }
+                b"accept" | b"accept4" | b"getdents" | b"getdents64" | b"shutdown" => {
+                    if let Some(f) = syscall_split.next().and_then(fd_file) {
+                        other = Some(OtherFields::File(f));
+                    }
+                }
                 b"pread64" | b"pwrite64" | b"preadv" | b"preadv2" | b"pwritev" | b"pwritev2"
-                | b"read" | b"recv" | b"recvfrom" | b"recvmsg" | b"send" | b"sendmsg"
-                | b"sendto" | b"write" | b"writev" => {
+                | b"read" | b"readv" | b"recv" | b"recvfrom" | b"recvmsg" | b"send"
+                | b"sendmsg" | b"sendto" | b"write" | b"writev" => {
                     // 17819 13:43:41.450318 read(22<pipe:[879334396]>,  <unfinished ...>
                     //                               ^^^^^^^^^^^^^^^^
-                    if let Some(f) = syscall_split.next().and_then(|s| {
-                        s.splitn_str(2, "<")
-                            .nth(1)
-                            .and_then(|s| s.get(..s.len() - 2))
-                    }) {
-                        other = Some(OtherFields::File(f));
+                    let file = syscall_split.next().and_then(fd_file);
+                    // The buffer is only scanned once, it can be most of a '-x' or '-xx' line
+                    let buf = args.nth(1);
+                    let requested = requested_bytes(syscall, buf, &mut args);
+                    let (euid, tracer_pid) = match (syscall, file, buf) {
+                        (b"pread64" | b"read", Some(f), Some(buf)) if is_proc_status(f) => {
+                            (status_euid(buf), status_tracer_pid(buf))
+                        }
+                        _ => (None, None),
+                    };
+
+                    match file {
+                        Some(file) if euid.is_some() || tracer_pid.is_some() => {
+                            other = Some(OtherFields::ProcStatus {
+                                file,
+                                requested,
+                                euid,
+                                tracer_pid,
+                            });
+                        }
+                        _ if file.is_some() || requested.is_some() => {
+                            other = Some(OtherFields::Io { file, requested });
+                        }
+                        _ => {}
+                    }
+                }
+                b"chmod" | b"chown" | b"fchmod" | b"fchown" | b"lchown" => {
+                    // 2104  10:01:12.118204 chmod("/usr/local/bin/tool", 0755) = 0 <0.000031>
+                    //                              ^^^^^^^^^^^^^^^^^^^^^
+                    if let Some(path) = args.next().and_then(path_arg) {
+                        other = permission_fields(syscall, path, &mut args);
+                    }
+                }
+                b"fchmodat" | b"fchownat" => {
+                    // 2104  10:01:12.118204 fchownat(AT_FDCWD, "/etc/tool.conf", 0, 0, 0) = 0 <0.000022>
+                    //                                          ^^^^^^^^^^^^^^^^
+                    if let Some(path) = args.nth(1).and_then(path_arg) {
+                        other = permission_fields(syscall, path, &mut args);
+                    }
+                }
+                b"setuid" | b"setuid32" => {
+                    // 3810  10:02:00.000100 setuid(1000) = 0 <0.000012>
+                    //                              ^^^^
+                    if let Some(euid) = args.next().and_then(uid_arg) {
+                        other = Some(OtherFields::Uid(euid));
+                    }
+                }
+                b"setresuid" | b"setresuid32" | b"setreuid" | b"setreuid32" => {
+                    // 3810  10:02:00.000100 setresuid(-1, 1000, -1) = 0 <0.000012>
+                    //                                     ^^^^
+                    if let Some(euid) = args.nth(1).and_then(uid_arg) {
+                        other = Some(OtherFields::Uid(euid));
+                    }
+                }
+                b"umask" => {
+                    // 2104  10:01:12.118204 umask(022) = 02 <0.000009>
+                    //                             ^^^
+                    if let Some(mask) = syscall_split.next().map(trim_arg) {
+                        other = Some(OtherFields::Umask(mask));
                     }
                 }
                 // Only set other when call is complete as new pid is not available on started
@@ -288,11 +1249,28 @@ pub fn parse_line<'a>(bytes: &'a [u8]) -> Option<LineData<'a>> {
                     // 17822 13:43:41.413034 clone(child_stack=NULL, flags=CLONE_VM|CLONE_VFORK|SIGCHLD <unfinished ...>
                     //                                               ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
                     if let Some(flags) = tokens.next() {
-                        if flags.contains_str(CLONE_THREAD) {
-                            other = Some(OtherFields::Clone(ProcType::Thread));
-                        } else {
-                            other = Some(OtherFields::Clone(ProcType::Process));
-                        }
+                        other = Some(clone_fields(flags));
+                    }
+                }
+                b"clone3" => {
+                    // 3301  10:02:00.000100 clone3({flags=CLONE_NEWPID|CLONE_NEWNET, exit_signal=SIGCHLD, stack=NULL, stack_size=0}, 88) = 3302 <0.000210>
+                    //                                ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
+                    if let Some(flags) = args.next().and_then(|arg| {
+                        let flags = &arg[arg.find("flags=")?..];
+                        flags.get(..flags.find_byteset(",}").unwrap_or(flags.len()))
+                    }) {
+                        other = Some(clone_fields(flags));
+                    }
+                }
+                b"unshare" => {
+                    // 3301  10:02:00.000100 unshare(CLONE_NEWNS|CLONE_NEWNET) = 0 <0.000051>
+                    //                               ^^^^^^^^^^^^^^^^^^^^^^^^
+                    let namespaces = Namespaces::from_flags(args.next().unwrap_or_default());
+                    if !namespaces.is_empty() {
+                        other = Some(OtherFields::NewNamespaces {
+                            proc_type: None,
+                            namespaces,
+                        });
                     }
                 }
                 _ => {}
@@ -302,7 +1280,7 @@ pub fn parse_line<'a>(bytes: &'a [u8]) -> Option<LineData<'a>> {
 
     // 17819 13:43:39.888658 brk(NULL)         = 0x3213000 <0.000019>
     //                                                     ^^^^^^^^^^
-    let duration = if let Some(b'<') = duration_token.get(0) {
+    let duration = if let Some(b'<') = duration_token.first() {
         duration_token
             .get(1..duration_token.len() - 1)
             .and_then(|s| s.to_str().ok())
@@ -322,7 +1300,7 @@ pub fn parse_line<'a>(bytes: &'a [u8]) -> Option<LineData<'a>> {
         while let Some(token) = end_tokens.next() {
             // 17826 13:43:40.155194 <... epoll_ctl resumed>) = -1 EPERM (Operation not permitted) <0.000029>
             //                                                     ^^^^^
-            if let Some(b'E') = token.get(0) {
+            if let Some(b'E') = token.first() {
                 error = Some(token);
             }
 
@@ -330,9 +1308,47 @@ pub fn parse_line<'a>(bytes: &'a [u8]) -> Option<LineData<'a>> {
             //                                                                          ^
             if end_tokens.peek().is_none() {
                 match syscall {
-                    b"clone" | b"fork" | b"vfork" | b"pread64" | b"pwrite64" | b"preadv"
-                    | b"preadv2" | b"pwritev" | b"pwritev2" | b"read" | b"recv" | b"recvfrom"
-                    | b"recvmsg" | b"send" | b"sendmsg" | b"sendto" | b"write" | b"writev" => {
+                    // 9071 10:14:21.335261 accept4(3<socket:[61612]>, NULL, NULL, SOCK_CLOEXEC) = 7<socket:[61613]> <0.000014>
+                    //                                                                          ^^^^^^^^^^^^^^^^^
+                    b"accept" | b"accept4" => {
+                        let (fd, conn) = split_fd_token(token);
+                        rtn_cd = fd;
+                        if let Some(conn) = conn {
+                            let listener = match other {
+                                Some(OtherFields::File(f)) => Some(f),
+                                _ => None,
+                            };
+                            other = Some(OtherFields::Accept { listener, conn });
+                        }
+                    }
+                    // 9071 10:14:21.335261 eventfd2(0, EFD_NONBLOCK|EFD_CLOEXEC) = 5<anon_inode:[eventfd]> <0.000014>
+                    //                                                             ^^^^^^^^^^^^^^^^^^^^^^^
+                    b"dup" | b"dup2" | b"dup3" | b"epoll_create" | b"epoll_create1"
+                    | b"eventfd" | b"eventfd2" | b"io_uring_setup" | b"memfd_create"
+                    | b"signalfd" | b"signalfd4" | b"socket" | b"timerfd_create" => {
+                        let (fd, file) = split_fd_token(token);
+                        rtn_cd = fd;
+                        match (&mut other, file) {
+                            (Some(OtherFields::Dup { file: dup_file, .. }), _) => *dup_file = file,
+                            (_, Some(f)) => other = Some(OtherFields::File(f)),
+                            _ => {}
+                        }
+                    }
+                    // 17819 13:43:39.888967 open("/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache> <0.000014>
+                    //                                                                         ^^^^^^^^^^^^^^^^^^^^^
+                    b"creat" | b"open" | b"openat" => {
+                        rtn_cd = split_fd_token(token).0;
+                        if let Some(OtherFields::Open { resolved, .. }) = &mut other {
+                            *resolved = returned_file(bytes);
+                        }
+                    }
+                    // 477   00:09:47.913636 <... wait4 resumed>[{WIFEXITED(s) && WEXITSTATUS(s) == 0}], 0, NULL) = 7383 <0.122712>
+                    //                                                                                              ^^^^
+                    b"clone" | b"clone3" | b"fork" | b"geteuid" | b"geteuid32" | b"vfork"
+                    | b"pread64" | b"pwrite64" | b"preadv" | b"preadv2" | b"pwritev"
+                    | b"pwritev2" | b"read" | b"readv" | b"recv" | b"recvfrom" | b"recvmsg"
+                    | b"send" | b"sendmsg" | b"sendto" | b"wait4" | b"waitid" | b"waitpid"
+                    | b"write" | b"writev" => {
                         rtn_cd = token.to_str().ok().and_then(|s| s.parse::<i32>().ok())
                     }
                     _ => {}
@@ -363,6 +1379,57 @@ mod tests {
         assert_eq!(parse_line(input), None);
     }
 
+    #[test]
+    fn traces_without_pids_get_one() {
+        let input = b"Starting server\n10:00:00.000100 open(\"/etc/app.yml\", O_RDONLY) = 3 <0.000010>\n10:00:00.000200 +++ exited with 0 +++\n";
+        assert!(lacks_pids(input));
+        assert!(!lacks_pids(
+            b"Starting server\n477 10:00:00.000100 close(3) = 0 <0.000010>\n"
+        ));
+
+        let copy = add_pid_column(input);
+        let lines: Vec<_> = copy.lines().collect();
+        assert_eq!(lines[0], b"Starting server");
+        assert_eq!(lines[2], b"0 10:00:00.000200 +++ exited with 0 +++");
+        match parse_line(lines[1]) {
+            Some(LineData::Syscall(raw_data)) => {
+                assert_eq!((raw_data.pid, raw_data.syscall), (NO_PID, b"open".as_ref()))
+            }
+            _ => panic!("line without a PID not parsed"),
+        }
+    }
+
+    #[test]
+    fn wrapped_calls_joined() {
+        // A 'strace -v' trace wrapped at a fixed width, inside a struct and a path
+        let input = b"2104  10:01:12.118204 statx(AT_FDCWD, \"/etc/hosts\", AT_STATX_SYNC_AS_STAT, STATX_ALL, {stx_mask=STATX_BASIC_STATS|STATX_MNT_ID, stx_attributes=0, stx_mode=S_IFREG|0644,\n stx_size=220, stx_blocks=8, stx_atime={tv_sec=1600000000, tv_n\nsec=0}}) = 0 <0.000031>\n2104  10:01:12.118304 openat(AT_FDCWD, \"/srv/My Docu\nments/a.txt\", O_RDONLY) = 3</srv/My Documents/a.txt> <0.000020>\n2104  10:01:12.118404 read(3</srv/My Documents/a.txt>,  <unfinished ...>\nServer started\n2104  10:01:12.118504 <... read resumed>\"abc\", 4096) = 3 <0.000031>\n";
+        assert!(has_continued_lines(input));
+
+        let joined = join_continued_lines(input);
+        let lines: Vec<_> = joined.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[3], b"Server started");
+        match parse_line(lines[0]) {
+            Some(LineData::Syscall(raw_data)) => {
+                assert_eq!(raw_data.syscall, b"statx");
+                assert_eq!(raw_data.duration, Some(0.000031));
+            }
+            _ => panic!("wrapped statx not parsed"),
+        }
+        match parse_line(lines[1]) {
+            Some(LineData::Syscall(RawData {
+                other: Some(OtherFields::Open { file, .. }),
+                ..
+            })) => assert_eq!(file, b"/srv/My Documents/a.txt"),
+            _ => panic!("wrapped openat not parsed"),
+        }
+
+        // Unfinished calls and output between lines aren't continuations
+        let input = b"2104  10:01:12.118404 read(3</etc/hosts>,  <unfinished ...>\nServer started\n2104  10:01:12.118504 <... read resumed>\"abc\", 4096) = 3 <0.000031>\n2104  10:01:12.118604 +++ killed by SIGKILL (core dumped) +++\n";
+        assert!(!has_continued_lines(input));
+        assert_eq!(join_continued_lines(input), input.as_ref());
+    }
+
     #[test]
     fn parser_returns_none_missing_pid() {
         let input = br##"11:29:49.112721 open("/dev/null", O_WRONLY|O_CREAT|O_TRUNC, 0666) = 3</dev/null> <0.000030>"##;
@@ -380,9 +1447,13 @@ mod tests {
                 syscall: b"open",
                 duration: Some(0.000030),
                 error: None,
-                rtn_cd: None,
+                rtn_cd: Some(3),
                 call_status: CallStatus::Complete,
-                other: Some(OtherFields::File(b"/dev/null")),
+                other: Some(OtherFields::Open {
+                    file: b"/dev/null",
+                    flags: b"O_WRONLY|O_CREAT|O_TRUNC",
+                    resolved: Some(b"/dev/null"),
+                }),
             }))
         );
     }
@@ -430,7 +1501,11 @@ mod tests {
                 error: None,
                 rtn_cd: None,
                 call_status: CallStatus::Complete,
-                other: Some(OtherFields::File(b"/dev/null")),
+                other: Some(OtherFields::Open {
+                    file: b"/dev/null",
+                    flags: b"O_WRONLY|O_CREAT|O_TRUNC",
+                    resolved: None,
+                }),
             }))
         );
     }
@@ -446,9 +1521,13 @@ mod tests {
                 syscall: b"open",
                 duration: Some(0.000030),
                 error: None,
-                rtn_cd: None,
+                rtn_cd: Some(3),
                 call_status: CallStatus::Complete,
-                other: Some(OtherFields::File(b"/dev/null")),
+                other: Some(OtherFields::Open {
+                    file: b"/dev/null",
+                    flags: b"O_WRONLY|O_CREAT|O_TRUNC",
+                    resolved: Some(b"/dev/null"),
+                }),
             }))
         );
     }
@@ -502,11 +1581,10 @@ mod tests {
                 error: None,
                 rtn_cd: None,
                 call_status: CallStatus::Complete,
-                other: Some(OtherFields::Execve(vec![
-                    b"\"/bin/sleep\",",
-                    b"[\"sleep\",",
-                    b"\"1\"],",
-                ])),
+                other: Some(OtherFields::Execve(
+                    vec![b"\"/bin/sleep\",", b"[\"sleep\",", b"\"1\"],",],
+                    Some(12)
+                )),
             }))
         );
     }
@@ -524,11 +1602,10 @@ mod tests {
                 error: None,
                 rtn_cd: None,
                 call_status: CallStatus::Started,
-                other: Some(OtherFields::Execve(vec![
-                    b"\"/bin/sleep\",",
-                    b"[\"sleep\",",
-                    b"\"1\"],",
-                ])),
+                other: Some(OtherFields::Execve(
+                    vec![b"\"/bin/sleep\",", b"[\"sleep\",", b"\"1\"],",],
+                    Some(12)
+                )),
             }))
         );
     }
@@ -547,7 +1624,10 @@ mod tests {
                 error: None,
                 rtn_cd: None,
                 call_status: CallStatus::Complete,
-                other: Some(OtherFields::Futex(b"0x535c890")),
+                other: Some(OtherFields::Futex {
+                    addr: b"0x535c890",
+                    wait: false
+                }),
             }))
         );
     }
@@ -565,7 +1645,10 @@ mod tests {
                 error: None,
                 rtn_cd: None,
                 call_status: CallStatus::Started,
-                other: Some(OtherFields::Futex(b"0x7ffa50080ff4"))
+                other: Some(OtherFields::Futex {
+                    addr: b"0x7ffa50080ff4",
+                    wait: true
+                })
             }))
         );
     }
@@ -758,6 +1841,7 @@ mod tests {
             parse_line(input),
             Some(LineData::Exit(ExitData {
                 pid: 13513,
+                time: b"01:58:50.823625",
                 exit: ExitType::Exit(0),
             }))
         );
@@ -770,6 +1854,7 @@ mod tests {
             parse_line(input),
             Some(LineData::Exit(ExitData {
                 pid: 13454,
+                time: b"01:58:23.149393",
                 exit: ExitType::Exit(128),
             }))
         );
@@ -782,8 +1867,710 @@ mod tests {
             parse_line(input),
             Some(LineData::Exit(ExitData {
                 pid: 13350,
+                time: b"01:58:19.443720",
                 exit: ExitType::Signal(b"SIGTERM"),
             }))
         );
     }
+
+    #[test]
+    fn parser_captures_signal_delivery() {
+        let input =
+            br##"9071  10:14:21.500000 --- SIGALRM {si_signo=SIGALRM, si_code=SI_KERNEL} ---"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Signal(SignalData {
+                pid: 9071,
+                time: b"10:14:21.500000",
+                signal: b"SIGALRM",
+            }))
+        );
+
+        let input = br##"9071  10:14:21.500000 --- stopped by SIGTSTP ---"##;
+        assert_eq!(parse_line(input), None);
+    }
+
+    #[test]
+    fn parser_captures_accept_listener_and_conn() {
+        let input = br##"9071  10:14:21.335261 accept4(3<socket:[61612]>, NULL, NULL, SOCK_CLOEXEC) = 7<socket:[61613]> <0.000014>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 9071,
+                time: b"10:14:21.335261",
+                syscall: b"accept4",
+                duration: Some(0.000014),
+                error: None,
+                rtn_cd: Some(7),
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::Accept {
+                    listener: Some(b"socket:[61612]"),
+                    conn: b"socket:[61613]",
+                }),
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_captures_resumed_accept_conn() {
+        let input = br##"9071  10:14:21.335261 <... accept resumed>{sa_family=AF_INET, sin_port=htons(51234), sin_addr=inet_addr("127.0.0.1")}, [16]) = 8<socket:[61620]> <2.000114>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 9071,
+                time: b"10:14:21.335261",
+                syscall: b"accept",
+                duration: Some(2.000114),
+                error: None,
+                rtn_cd: Some(8),
+                call_status: CallStatus::Resumed,
+                other: Some(OtherFields::Accept {
+                    listener: None,
+                    conn: b"socket:[61620]",
+                }),
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_captures_connect_file() {
+        let input = br##"9080  10:14:21.335261 connect(5<socket:[61700]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = -1 EINPROGRESS (Operation now in progress) <0.000051>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 9080,
+                time: b"10:14:21.335261",
+                syscall: b"connect",
+                duration: Some(0.000051),
+                error: Some(b"EINPROGRESS"),
+                rtn_cd: None,
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::Connect {
+                    file: Some(b"socket:[61700]"),
+                    addr: Some(
+                        b"{sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr(\"10.0.0.2\")}"
+                    ),
+                }),
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_captures_eventfd_file() {
+        let input = br##"9071  10:14:21.335261 eventfd2(0, EFD_NONBLOCK|EFD_CLOEXEC) = 5<anon_inode:[eventfd]> <0.000014>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 9071,
+                time: b"10:14:21.335261",
+                syscall: b"eventfd2",
+                duration: Some(0.000014),
+                error: None,
+                rtn_cd: Some(5),
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::File(b"anon_inode:[eventfd]")),
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_captures_dup2_replaced_file() {
+        let input =
+            br##"477   10:00:00.200000 dup2(3</tmp/a>, 1</dev/pts/0>) = 1</tmp/a> <0.000010>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 477,
+                time: b"10:00:00.200000",
+                syscall: b"dup2",
+                duration: Some(0.000010),
+                error: None,
+                rtn_cd: Some(1),
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::Dup {
+                    file: Some(b"/tmp/a"),
+                    replaced: Some(b"/dev/pts/0"),
+                }),
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_captures_socket_fd_without_file() {
+        let input = br##"9071  10:14:21.335261 socket(AF_INET, SOCK_STREAM|SOCK_CLOEXEC, IPPROTO_IP) = 6 <0.000020>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 9071,
+                time: b"10:14:21.335261",
+                syscall: b"socket",
+                duration: Some(0.000020),
+                error: None,
+                rtn_cd: Some(6),
+                call_status: CallStatus::Complete,
+                other: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_captures_pipe_file() {
+        let input = br##"17819 13:43:41.450311 pipe2([22<pipe:[879334396]>, 23<pipe:[879334396]>], O_CLOEXEC) = 0 <0.000022>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 17819,
+                time: b"13:43:41.450311",
+                syscall: b"pipe2",
+                duration: Some(0.000022),
+                error: None,
+                rtn_cd: None,
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::FdPair {
+                    fds: [22, 23],
+                    file: Some(b"pipe:[879334396]"),
+                }),
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_captures_clone_namespaces() {
+        let input = br##"3300  10:02:00.000100 clone3({flags=CLONE_NEWNS|CLONE_NEWPID, exit_signal=SIGCHLD, stack=NULL, stack_size=0}, 88) = 3301 <0.000210>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 3300,
+                time: b"10:02:00.000100",
+                syscall: b"clone3",
+                duration: Some(0.000210),
+                error: None,
+                rtn_cd: Some(3301),
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::NewNamespaces {
+                    proc_type: Some(ProcType::Process),
+                    namespaces: Namespaces::from_flags(b"CLONE_NEWNS|CLONE_NEWPID"),
+                }),
+            }))
+        );
+
+        let input = br##"3301  10:02:00.000400 unshare(CLONE_NEWNET) = 0 <0.000051>"##;
+        match parse_line(input) {
+            Some(LineData::Syscall(raw_data)) => {
+                assert_eq!(raw_data.proc_type(), None);
+                assert_eq!(raw_data.namespaces().unwrap().to_string(), "net");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parser_captures_closed_fds_and_pairs() {
+        let input = br##"9071  10:14:21.335300 close(7<socket:[61613]>) = 0 <0.000011>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 9071,
+                time: b"10:14:21.335300",
+                syscall: b"close",
+                duration: Some(0.000011),
+                error: None,
+                rtn_cd: None,
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::Close {
+                    fd: 7,
+                    file: Some(b"socket:[61613]"),
+                }),
+            }))
+        );
+
+        let input = br##"9071  10:14:21.335400 socketpair(AF_UNIX, SOCK_STREAM, 0, [8, 9]) = 0 <0.000011>"##;
+        match parse_line(input) {
+            Some(LineData::Syscall(raw_data)) => assert_eq!(
+                raw_data.other,
+                Some(OtherFields::FdPair {
+                    fds: [8, 9],
+                    file: None,
+                })
+            ),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parser_captures_io_requested_bytes() {
+        let input = br##"17819 13:43:40.149100 read(6</proc/sys/net/core/somaxconn>, "128\n", 65531) = 4 <0.000013>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 17819,
+                time: b"13:43:40.149100",
+                syscall: b"read",
+                duration: Some(0.000013),
+                error: None,
+                rtn_cd: Some(4),
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::Io {
+                    file: Some(b"/proc/sys/net/core/somaxconn"),
+                    requested: Some(65531),
+                }),
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_skips_hex_encoded_buffers() {
+        // '-xx' prints every byte escaped, '-x' only the non-printable ones
+        let hex = br##"477   10:00:00.000000 sendto(5<TCP:[1]>, "\x22\x2c\x29\x5c\x78", 5, MSG_NOSIGNAL, NULL, 0) = 5 <0.000010>"##;
+        let mixed =
+            br##"477   10:00:00.000000 write(1</dev/pts/0>, "a\"), \x00\\", 6) = 6 <0.000010>"##;
+        let truncated = br##"477   10:00:00.000000 read(3</etc/hosts>, "\x31\x32"..., 4096) = 4096 <0.000010>"##;
+
+        let requested = |input: &[u8]| match parse_line(input) {
+            Some(LineData::Syscall(raw_data)) => raw_data.requested(),
+            _ => None,
+        };
+        assert_eq!(requested(hex), Some(5));
+        assert_eq!(requested(mixed), Some(6));
+        assert_eq!(requested(truncated), Some(4096));
+    }
+
+    #[test]
+    fn parser_captures_io_requested_bytes_when_resumed() {
+        let started = br##"17819 13:43:41.450318 read(22<pipe:[879334396]>,  <unfinished ...>"##;
+        let resumed = br##"17819 13:43:41.450390 <... read resumed>"a, b", 4096) = 4 <0.000072>"##;
+        let unfinished_write = br##"17819 13:43:41.450318 write(1, "a, b", 4 <unfinished ...>"##;
+
+        let requested = |input: &[u8]| match parse_line(input) {
+            Some(LineData::Syscall(raw_data)) => raw_data.requested(),
+            _ => None,
+        };
+        assert_eq!(requested(started), None);
+        assert_eq!(requested(resumed), Some(4096));
+        assert_eq!(requested(unfinished_write), Some(4));
+    }
+
+    #[test]
+    fn parser_sums_vectored_io_requested_bytes() {
+        let writev = br##"9071  10:14:21.335261 writev(7<socket:[61613]>, [{iov_base="HTTP/1.1 200 OK\r\n", iov_len=17}, {iov_base="{}", iov_len=2}], 2) = 19 <0.000031>"##;
+        let recvmsg = br##"9071  10:14:21.335261 recvmsg(3<socket:[61612]>, {msg_name=NULL, msg_namelen=0, msg_iov=[{iov_base="ok", iov_len=2048}], msg_iovlen=1, msg_controllen=0, msg_flags=0}, 0) = 2 <0.000010>"##;
+
+        let requested = |input: &[u8]| match parse_line(input) {
+            Some(LineData::Syscall(raw_data)) => raw_data.requested(),
+            _ => None,
+        };
+        assert_eq!(requested(writev), Some(19));
+        assert_eq!(requested(recvmsg), Some(2048));
+    }
+
+    #[test]
+    fn parser_captures_connect_address_without_fd_path() {
+        let input = br##"9080  10:14:21.000000 connect(5, {sa_family=AF_UNIX, sun_path="/run/app.sock"}, 110) = -1 ENOENT (No such file or directory) <0.000020>"##;
+        match parse_line(input) {
+            Some(LineData::Syscall(raw_data)) => assert_eq!(
+                raw_data.other,
+                Some(OtherFields::Connect {
+                    file: None,
+                    addr: Some(b"{sa_family=AF_UNIX, sun_path=\"/run/app.sock\"}"),
+                })
+            ),
+            _ => panic!("connect not parsed"),
+        }
+    }
+
+    #[test]
+    fn parser_captures_uid_changes_and_status_reads() {
+        let input = br##"3810  10:02:00.000100 setresuid(-1, 1000, -1) = 0 <0.000012>"##;
+        match parse_line(input) {
+            Some(LineData::Syscall(raw_data)) => {
+                assert_eq!(raw_data.other, Some(OtherFields::Uid(1000)))
+            }
+            _ => panic!("setresuid not parsed"),
+        }
+
+        let input = br##"3812  10:02:00.000400 read(3</proc/3813/status>, "Name:\tnginx\nUid:\t0\t33\t33\t33\n", 1024) = 29 <0.000020>"##;
+        match parse_line(input) {
+            Some(LineData::Syscall(raw_data)) => assert_eq!(
+                raw_data.other,
+                Some(OtherFields::ProcStatus {
+                    file: b"/proc/3813/status",
+                    requested: Some(1024),
+                    euid: Some(33),
+                    tracer_pid: None,
+                })
+            ),
+            _ => panic!("status read not parsed"),
+        }
+
+        // strace's default '-s 32' cuts the read off before the 'Uid:' line
+        let input = br##"823   00:09:51.247900 read(44</proc/823/status>, "Name:\tbundle\nPPid:\t495\nTracerPid:\t7388\n"..., 1024) = 1024 <0.000020>"##;
+        match parse_line(input) {
+            Some(LineData::Syscall(raw_data)) => assert_eq!(
+                raw_data.other,
+                Some(OtherFields::ProcStatus {
+                    file: b"/proc/823/status",
+                    requested: Some(1024),
+                    euid: None,
+                    tracer_pid: Some(7388),
+                })
+            ),
+            _ => panic!("truncated status read not parsed"),
+        }
+    }
+
+    #[test]
+    fn parser_captures_chmod_path_and_mode() {
+        let input = br##"2104  10:01:12.118204 chmod("/usr/local/bin/tool", 0755) = 0 <0.000031>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 2104,
+                time: b"10:01:12.118204",
+                syscall: b"chmod",
+                duration: Some(0.000031),
+                error: None,
+                rtn_cd: None,
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::Chmod {
+                    path: b"/usr/local/bin/tool",
+                    mode: b"0755",
+                }),
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_captures_fchownat_path_and_owner() {
+        let input = br##"2104  10:01:12.118204 fchownat(AT_FDCWD, "/etc/tool.conf", 0, -1, 0) = -1 EPERM (Operation not permitted) <0.000022>"##;
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 2104,
+                time: b"10:01:12.118204",
+                syscall: b"fchownat",
+                duration: Some(0.000022),
+                error: Some(b"EPERM"),
+                rtn_cd: None,
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::Chown {
+                    path: b"/etc/tool.conf",
+                    owner: b"0",
+                    group: b"-1",
+                }),
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_captures_fchmod_fd_path() {
+        let input =
+            br##"2104  10:01:12.118204 fchmod(3</var/lib/tool/state>, 0600) = 0 <0.000011>"##;
+        assert_eq!(
+            parse_line(input).and_then(|l| match l {
+                LineData::Syscall(d) => d.other,
+                _ => None,
+            }),
+            Some(OtherFields::Chmod {
+                path: b"/var/lib/tool/state",
+                mode: b"0600",
+            })
+        );
+    }
+
+    #[test]
+    fn parser_captures_umask() {
+        let input = br##"2104  10:01:12.118204 umask(022) = 02 <0.000009>"##;
+        assert_eq!(
+            parse_line(input).and_then(|l| match l {
+                LineData::Syscall(d) => d.other,
+                _ => None,
+            }),
+            Some(OtherFields::Umask(b"022"))
+        );
+    }
+
+    #[test]
+    fn parser_captures_rename_paths() {
+        let input = br##"2104  10:01:12.118204 renameat2(AT_FDCWD, "/tmp/out.tmp", AT_FDCWD, "/srv/out", RENAME_NOREPLACE) = 0 <0.000031>"##;
+        assert_eq!(
+            parse_line(input).and_then(|l| match l {
+                LineData::Syscall(d) => d.other,
+                _ => None,
+            }),
+            Some(OtherFields::Rename {
+                from: b"/tmp/out.tmp",
+                to: b"/srv/out",
+            })
+        );
+    }
+
+    #[test]
+    fn parser_captures_unlinkat_path() {
+        let input =
+            br##"2104  10:01:12.118204 unlinkat(AT_FDCWD, "/tmp/build.lock", 0) = 0 <0.000031>"##;
+        assert_eq!(
+            parse_line(input).and_then(|l| match l {
+                LineData::Syscall(d) => d.other,
+                _ => None,
+            }),
+            Some(OtherFields::File(b"/tmp/build.lock"))
+        );
+    }
+
+    #[test]
+    fn parser_captures_stat_paths() {
+        let other = |input: &'static [u8]| {
+            parse_line(input).and_then(|l| match l {
+                LineData::Syscall(d) => d.other,
+                _ => None,
+            })
+        };
+
+        assert_eq!(
+            other(br##"567   00:09:47.836504 access("/etc/ld.so.nohwcap", F_OK) = -1 ENOENT (No such file or directory) <0.000020>"##),
+            Some(OtherFields::File(b"/etc/ld.so.nohwcap"))
+        );
+        assert_eq!(
+            other(br##"567   00:09:47.836604 newfstatat(AT_FDCWD, "/etc/gitlab/gitlab.rb", 0x7ffd4c3b8d70, 0) = -1 EACCES (Permission denied) <0.000011>"##),
+            Some(OtherFields::File(b"/etc/gitlab/gitlab.rb"))
+        );
+    }
+
+    #[test]
+    fn parser_captures_epoll_events() {
+        let other = |input: &'static [u8]| {
+            parse_line(input).and_then(|l| match l {
+                LineData::Syscall(d) => d.other,
+                _ => None,
+            })
+        };
+
+        assert_eq!(
+            other(br##"9071  10:14:21.335300 epoll_ctl(5<anon_inode:[eventpoll]>, EPOLL_CTL_ADD, 7<socket:[61613]>, {EPOLLIN, {u32=7, u64=7}}) = 0 <0.000011>"##),
+            Some(OtherFields::EpollCtl {
+                epfd: 5,
+                op: b"EPOLL_CTL_ADD",
+                fd: 7,
+                file: Some(b"socket:[61613]"),
+                data: Some(b"7"),
+            })
+        );
+        assert_eq!(
+            other(br##"9071  10:14:21.335400 epoll_pwait(5, [{events=EPOLLIN, data={u32=7, u64=7}}, {events=EPOLLOUT, data={u32=2864541888, u64=139896186373312}}], 128, -1, NULL, 8) = 2 <0.000011>"##),
+            Some(OtherFields::EpollWait {
+                epfd: Some(5),
+                ready: vec![b"7", b"139896186373312"],
+            })
+        );
+        assert_eq!(
+            other(br##"9071  10:14:21.335500 <... epoll_wait resumed>[{EPOLLIN, {u32=8, u64=8}}], 1024, -1) = 1 <0.500000>"##),
+            Some(OtherFields::EpollWait {
+                epfd: None,
+                ready: vec![b"8"],
+            })
+        );
+    }
+
+    #[test]
+    fn parser_open_flags_for_write() {
+        let input = br##"16747 11:29:49.112721 openat(AT_FDCWD, "/var/log/app.log", O_WRONLY|O_APPEND|O_CLOEXEC) = 3</var/log/app.log> <0.000030>"##;
+        match parse_line(input) {
+            Some(LineData::Syscall(d)) => assert!(d.opened_for_write()),
+            _ => panic!("Failed to parse openat"),
+        }
+
+        let input = br##"17819 13:43:39.888967 open("/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache> <0.000014>"##;
+        match parse_line(input) {
+            Some(LineData::Syscall(d)) => assert!(!d.opened_for_write()),
+            _ => panic!("Failed to parse open"),
+        }
+    }
+
+    #[test]
+    fn parser_handles_crlf_line_endings() {
+        let input = b"17819 13:43:39.888967 open(\"/etc/ld.so.cache\", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache> <0.000014>\r";
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Syscall(RawData {
+                pid: 17819,
+                time: b"13:43:39.888967",
+                syscall: b"open",
+                duration: Some(0.000014),
+                error: None,
+                rtn_cd: Some(3),
+                call_status: CallStatus::Complete,
+                other: Some(OtherFields::Open {
+                    file: b"/etc/ld.so.cache",
+                    flags: b"O_RDONLY|O_CLOEXEC",
+                    resolved: Some(b"/etc/ld.so.cache"),
+                }),
+            }))
+        );
+
+        let input = b"90718 13:48:58.423962 +++ exited with 0 +++\r";
+        assert_eq!(
+            parse_line(input),
+            Some(LineData::Exit(ExitData {
+                pid: 90718,
+                time: b"13:48:58.423962",
+                exit: ExitType::Exit(0),
+            }))
+        );
+    }
+
+    #[test]
+    fn parser_arg_split_skips_nested_commas() {
+        let region = br##"SIGINT, {sa_handler=0x55d0c0a0e2d0, sa_mask=[INT QUIT], sa_flags=SA_RESTORER|SA_RESTART, sa_restorer=0x7f1c6f8a0520}, NULL, 8) = 0 <0.000012>"##;
+        let args: Vec<_> = ArgSplit::new(region).collect();
+
+        assert_eq!(
+            args,
+            vec![
+                b"SIGINT".as_ref(),
+                b"{sa_handler=0x55d0c0a0e2d0, sa_mask=[INT QUIT], sa_flags=SA_RESTORER|SA_RESTART, sa_restorer=0x7f1c6f8a0520}",
+                b"NULL",
+                b"8",
+            ]
+        );
+    }
+
+    #[test]
+    fn parser_arg_split_skips_quoted_commas() {
+        let region = br##""a, \"b)\"", 5 <unfinished ...>"##;
+        let args: Vec<_> = ArgSplit::new(region).collect();
+
+        assert_eq!(args, vec![br##""a, \"b)\"""##.as_ref(), b"5"]);
+    }
+
+    #[test]
+    fn parser_captures_path_with_spaces() {
+        let input = br##"2104  10:01:12.118204 openat(AT_FDCWD, "/srv/My Documents/a, b.txt", O_RDONLY) = 3</srv/My Documents/a, b.txt> <0.000031>"##;
+        assert_eq!(
+            parse_line(input).and_then(|l| match l {
+                LineData::Syscall(raw_data) => raw_data.other,
+                _ => None,
+            }),
+            Some(OtherFields::Open {
+                file: b"/srv/My Documents/a, b.txt",
+                flags: b"O_RDONLY",
+                resolved: Some(b"/srv/My Documents/a, b.txt"),
+            })
+        );
+    }
+
+    #[test]
+    fn parser_captures_readlink_target() {
+        let input = br##"2104  10:01:12.118204 readlinkat(AT_FDCWD, "/usr/bin/ruby", "ruby2.7", 4096) = 7 <0.000031>"##;
+        assert_eq!(
+            parse_line(input).and_then(|l| match l {
+                LineData::Syscall(raw_data) => raw_data.other,
+                _ => None,
+            }),
+            Some(OtherFields::Readlink {
+                path: b"/usr/bin/ruby",
+                target: b"ruby2.7",
+            })
+        );
+
+        let input = br##"2104  10:01:12.118204 readlink("/etc/hosts", 0x7ffd4c3b8d70, 4096) = -1 EINVAL (Invalid argument) <0.000011>"##;
+        assert_eq!(
+            parse_line(input).and_then(|l| match l {
+                LineData::Syscall(raw_data) => raw_data.other,
+                _ => None,
+            }),
+            Some(OtherFields::File(b"/etc/hosts"))
+        );
+    }
+
+    #[test]
+    fn parser_captures_verbose_execve_env() {
+        let input = br##"13656 10:53:02.442246 execve("/bin/sleep", ["sleep", "1"], ["HOME=/root", "PATH=/usr/bin:/bin", "LS_COLORS=rs=0:di=01;34"]) = 0 <0.000229>"##;
+        assert_eq!(
+            parse_line(input).and_then(|l| match l {
+                LineData::Syscall(raw_data) => raw_data.other,
+                _ => None,
+            }),
+            Some(OtherFields::Execve(
+                vec![b"\"/bin/sleep\",", b"[\"sleep\",", b"\"1\"],"],
+                Some(3)
+            ))
+        );
+    }
+
+    #[test]
+    fn parser_splits_line_args() {
+        let complete =
+            br##"477   10:00:00.000000 read(3</etc/hosts>, "a, b)", 4096) = 5 <0.000010>"##;
+        assert_eq!(
+            line_args(complete),
+            vec![b"3</etc/hosts>".as_ref(), b"\"a, b)\"", b"4096"]
+        );
+
+        let started = br##"477   10:00:00.000000 read(5<socket:[1]>,  <unfinished ...>"##;
+        let resumed = br##"477   10:00:00.100000 <... read resumed>"abc", 4096) = 3 <0.100000>"##;
+        assert_eq!(line_args(started), vec![b"5<socket:[1]>".as_ref()]);
+        assert_eq!(line_args(resumed), vec![b"\"abc\"".as_ref(), b"4096"]);
+
+        let resumed = br##"477   10:00:00.100000 <... clone resumed>, parent_tid=[478], tls=0x7f) = 478 <0.000041>"##;
+        assert_eq!(
+            line_args(resumed),
+            vec![b"parent_tid=[478]".as_ref(), b"tls=0x7f"]
+        );
+        assert!(line_args(b"477   10:00:00.000000 getpid() = 477 <0.000010>").is_empty());
+    }
+
+    #[test]
+    fn parser_finds_line_return() {
+        let input = br##"17819 13:43:39.888967 open("/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache> <0.000014>"##;
+        assert_eq!(line_return(input), Some(b"3".as_ref()));
+
+        let input = br##"477   00:09:47.913636 getdents(221</proc/495/fd>, /* 224 entries */, 32768) = 5376 <0.000143>"##;
+        assert_eq!(line_return(input), Some(b"5376".as_ref()));
+
+        let input = br##"477   00:09:47.913636 brk(NULL) = 0x3213000 <0.000019>"##;
+        assert_eq!(line_return(input), Some(b"0x3213000".as_ref()));
+
+        assert_eq!(
+            line_return(b"477   10:00:00.000000 read(5<socket:[1]>,  <unfinished ...>"),
+            None
+        );
+    }
+
+    #[test]
+    fn parser_truncated_lines_do_not_panic() {
+        let lines: &[&[u8]] = &[
+            br##"17819 13:43:39.888967 open("/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache> <0.000014>"##,
+            br##"17819 13:43:40.146677 openat(AT_FDCWD, "config.toml", O_RDONLY|O_CLOEXEC) = 3</var/opt/gitlab/gitaly/config.toml> <0.000026>"##,
+            br##"17826 13:43:41.450300 futex(0xc00005ef48, FUTEX_WAKE_PRIVATE, 1 <unfinished ...>"##,
+            br##"17819 13:43:41.450318 read(22<pipe:[879334396]>,  <unfinished ...>"##,
+            br##"17840 13:43:41.449433 execve("/bin/ps", ["ps", "-o", "rss=", "-p", "17838"], 0xc0001c2000 /* 22 vars */ <unfinished ...>"##,
+            br##"10738 01:58:22.788361 <... clone resumed> child_stack=0, flags=CLONE_VM|CLONE_VFORK|SIGCHLD) = 13442 <0.002381>"##,
+            br##"13449 01:58:23.198334 +++ killed by SIGTERM +++"##,
+            br##"17840 13:43:41.449433 execve("/bin/ps", ["ps", "-o", "rss="], ["HOME=/root", "PATH=/usr/bin"]) = 0 <0.000229>"##,
+            br##"2104  10:01:12.118204 rename("/tmp/a \"b\", c)", "/srv/out") = 0 <0.000031>"##,
+        ];
+
+        for line in lines {
+            for end in 0..=line.len() {
+                parse_line(&line[..end]);
+            }
+        }
+    }
+
+    #[test]
+    fn parser_handles_string_split_by_newline() {
+        let input = b"477   00:09:56.954410 write(1</dev/pts/0>, \"first line\nsecond line\", 23) = 23 <0.000010>";
+        let mut lines = input.split(|c| *c == b'\n');
+
+        assert!(matches!(
+            lines.next().and_then(parse_line),
+            Some(LineData::Syscall(RawData {
+                pid: 477,
+                syscall: b"write",
+                call_status: CallStatus::Started,
+                ..
+            }))
+        ));
+        assert_eq!(lines.next().and_then(parse_line), None);
+    }
 }
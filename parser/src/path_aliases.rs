@@ -0,0 +1,228 @@
+use crate::parser::OtherFields;
+use crate::syscall_data::PidData;
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::borrow::Cow;
+
+// Links may point to other links, the kernel gives up after as many
+const MAX_LINK_HOPS: usize = 40;
+
+/// Other names files and directories were reached by, such as symlinks,
+/// mapped to the path they refer to
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PathAliases {
+    links: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl PathAliases {
+    /// Aliases seen in the trace: symlinks read with 'readlink', and files
+    /// opened by a name other than the one '-y' shows for the fd returned.
+    /// Links under '/proc' differ between processes, so are left out, as
+    /// are relative names, which depend on the process's working directory.
+    pub fn from_trace(raw_data: &HashMap<Pid, PidData>) -> PathAliases {
+        let mut aliases = PathAliases::default();
+
+        for pid_data in raw_data.values() {
+            let events = pid_data.open_events.iter().chain(&pid_data.link_events);
+            for event in events.filter(|e| e.error.is_none()) {
+                let (path, target) = match event.other {
+                    Some(OtherFields::Readlink { path, target }) => (path, target),
+                    Some(OtherFields::Open {
+                        file,
+                        resolved: Some(resolved),
+                        ..
+                    }) if !resolved.ends_with(b" (deleted)") => (file, resolved),
+                    _ => continue,
+                };
+
+                if path.starts_with(b"/") && !path.starts_with(b"/proc/") {
+                    aliases.insert(path, target);
+                }
+            }
+        }
+
+        aliases
+    }
+
+    /// Parses aliases of the form 'ALIAS -> TARGET', one per line, as 'ls -l'
+    /// prints symlinks. A relative target is taken from the alias's
+    /// directory. Blank lines and anything after a '#' are ignored.
+    pub fn parse<T: AsRef<[u8]> + ?Sized>(text: &T) -> Result<PathAliases, String> {
+        let mut aliases = PathAliases::default();
+
+        for (idx, line) in text.as_ref().lines().enumerate() {
+            let line = match line.find_byte(b'#') {
+                Some(pos) => &line[..pos],
+                None => line,
+            }
+            .trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            match line.find(" -> ") {
+                Some(pos) if line[..pos].trim().starts_with(b"/") => {
+                    aliases.insert(line[..pos].trim(), line[pos + 4..].trim())
+                }
+                _ => {
+                    return Err(format!(
+                        "Invalid path alias '{}' on line {}",
+                        line.to_str_lossy(),
+                        idx + 1
+                    ))
+                }
+            }
+        }
+
+        Ok(aliases)
+    }
+
+    /// Adds the aliases in `other`, replacing any for the same path
+    pub fn extend(&mut self, other: PathAliases) {
+        self.links.extend(other.links);
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    fn insert(&mut self, path: &[u8], target: &[u8]) {
+        let path = normalize(path);
+        let target = match target.first() {
+            Some(b'/') => normalize(target),
+            // Targets such as 'socket:[61613]' aren't paths
+            _ if target.contains_str(":[") => return,
+            _ => {
+                let dir = path.rfind_byte(b'/').map_or(&b""[..], |idx| &path[..idx]);
+                normalize(&[dir, b"/", target].concat())
+            }
+        };
+
+        if path != target {
+            self.links.insert(path, target);
+        }
+    }
+
+    /// The path that `path` refers to, following aliases of it or of any of
+    /// its parent directories
+    pub fn resolve<'a>(&self, path: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut resolved = Cow::Borrowed(path);
+        if self.links.is_empty() {
+            return resolved;
+        }
+
+        for _ in 0..MAX_LINK_HOPS {
+            match self.longest_alias(&resolved) {
+                Some((len, target)) => {
+                    resolved = Cow::Owned([target, &resolved[len..]].concat());
+                }
+                None => break,
+            }
+        }
+
+        resolved
+    }
+
+    // The longest alias that is `path` or one of its parent directories,
+    // with its length
+    fn longest_alias(&self, path: &[u8]) -> Option<(usize, &[u8])> {
+        let mut end = path.len();
+        loop {
+            if let Some(target) = self.links.get(&path[..end]) {
+                return Some((end, target));
+            }
+            end = match path[..end].rfind_byte(b'/') {
+                Some(idx) if idx > 0 => idx,
+                _ => return None,
+            };
+        }
+    }
+}
+
+// Drops empty and '.' components and applies '..' ones, which won't match
+// the kernel when '..' follows a symlink, but is the best the trace allows
+fn normalize(path: &[u8]) -> Vec<u8> {
+    let mut parts: Vec<&[u8]> = Vec::new();
+    for part in path.split_str("/") {
+        match part {
+            b"" | b"." => {}
+            b".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    let mut normalized = Vec::with_capacity(path.len());
+    for part in parts {
+        normalized.push(b'/');
+        normalized.extend_from_slice(part);
+    }
+    if normalized.is_empty() {
+        normalized.push(b'/');
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn path_aliases_learned_from_trace() {
+        let input = br##"1093  00:09:48.963261 open("/var/log/gitlab/gitlab-rails/sidekiq.log", O_RDONLY|O_NONBLOCK) = 33</var/log/gitlab/sidekiq/current> <0.000024>
+1093  00:09:48.963300 readlink("/opt/app/current", "releases/42", 4096) = 11 <0.000010>
+1093  00:09:48.963310 readlink("/proc/self/exe", "/usr/bin/ruby", 4096) = 13 <0.000010>
+1093  00:09:48.963320 openat(AT_FDCWD, "config.toml", O_RDONLY) = 3</var/opt/gitlab/gitaly/config.toml> <0.000026>"##;
+        let pid_data_map = build_syscall_data(input);
+        let aliases = PathAliases::from_trace(&pid_data_map);
+
+        assert_eq!(aliases.len(), 2);
+        assert_eq!(
+            aliases.resolve(b"/var/log/gitlab/gitlab-rails/sidekiq.log"),
+            b"/var/log/gitlab/sidekiq/current".as_ref()
+        );
+        assert_eq!(
+            aliases.resolve(b"/opt/app/current/config/app.yml"),
+            b"/opt/app/releases/42/config/app.yml".as_ref()
+        );
+        assert_eq!(
+            aliases.resolve(b"/opt/app/currently"),
+            b"/opt/app/currently".as_ref()
+        );
+        assert_eq!(
+            aliases.resolve(b"/proc/self/exe"),
+            b"/proc/self/exe".as_ref()
+        );
+    }
+
+    #[test]
+    fn path_aliases_parsed_and_chained() {
+        let mut aliases = PathAliases::parse(
+            "# data moved to the new volume\n/srv/data -> /mnt/vol1/data\n/mnt/vol1 -> ../mnt/vol2\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            aliases.resolve(b"/srv/data/db.sqlite"),
+            b"/mnt/vol2/data/db.sqlite".as_ref()
+        );
+
+        aliases.extend(PathAliases::parse("/a -> /b\n/b -> /a\n").unwrap());
+        assert!(aliases.resolve(b"/a/file").ends_with(b"/file"));
+
+        assert_eq!(
+            PathAliases::parse("/srv/data /mnt/vol1/data\n"),
+            Err(String::from(
+                "Invalid path alias '/srv/data /mnt/vol1/data' on line 1"
+            ))
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use crate::io_data;
+use crate::output;
+use crate::parser::{self, CallStatus, LineData, OtherFields, RawData};
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::time::parse_timestamp_us;
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::io::Error;
+
+// Calls and time in ms of each syscall made on a path
+type SyscallTotals<'a> = HashMap<&'a [u8], (usize, f32)>;
+
+/// The calls made on one path, by syscall
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PathCalls<'a> {
+    pub path: &'a [u8],
+    pub calls: usize,
+    /// Time in all calls on the path, in ms
+    pub duration: f32,
+    /// Each syscall's calls and time in ms, the most time first
+    pub syscalls: Vec<(&'a [u8], usize, f32)>,
+}
+
+impl<'a> PathCalls<'a> {
+    /// The share of the path's time spent in each syscall, e.g.
+    /// 'read 62%, fstat 21%, openat 17%'
+    pub fn breakdown(&self) -> String {
+        let shares: Vec<_> = self
+            .syscalls
+            .iter()
+            .map(|(syscall, calls, duration)| {
+                let pct = match self.duration > 0.0 {
+                    true => duration / self.duration * 100.0,
+                    false => *calls as f32 / self.calls as f32 * 100.0,
+                };
+                format!("{} {:.0}%", syscall.to_str_lossy(), pct)
+            })
+            .collect();
+
+        shares.join(", ")
+    }
+}
+
+/// Every call by `pids` on one of `paths`, whether named by the path or by a
+/// fd strace printed with '-y', totaled by path and syscall with the most time
+/// first. Unfinished calls are joined with their resumed line.
+pub fn path_calls<'a>(
+    buffer: &'a [u8],
+    pids: &[Pid],
+    paths: &HashSet<&[u8]>,
+) -> Vec<PathCalls<'a>> {
+    let pids: HashSet<Pid> = pids.iter().copied().collect();
+    let start = buffer.as_ptr() as usize;
+
+    let mut calls: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(|line| match parser::parse_line(line)? {
+            LineData::Syscall(raw_data) if pids.contains(&raw_data.pid) => {
+                let time = parse_timestamp_us(raw_data.time)?;
+                // Lines with the same timestamp are kept in the order they were printed
+                let offset = line.as_ptr() as usize - start;
+                let path = call_path(line, &raw_data);
+                Some(((raw_data.pid, time, offset), raw_data, path))
+            }
+            _ => None,
+        })
+        .collect();
+    calls.par_sort_unstable_by_key(|(key, _, _)| *key);
+
+    let mut totals: HashMap<&[u8], SyscallTotals> = HashMap::default();
+    // The path of each PID's unfinished call, waiting for its resumed line
+    let mut started: HashMap<Pid, &[u8]> = HashMap::default();
+
+    for ((pid, _, _), raw_data, path) in calls {
+        let path = match raw_data.call_status {
+            CallStatus::Complete => path,
+            CallStatus::Started => {
+                if let Some(path) = path {
+                    started.insert(pid, path);
+                }
+                continue;
+            }
+            CallStatus::Resumed => started.remove(&pid).or(path),
+        };
+
+        if let Some(path) = path.filter(|p| paths.contains(p)) {
+            let entry = totals
+                .entry(path)
+                .or_default()
+                .entry(raw_data.syscall)
+                .or_default();
+            entry.0 += 1;
+            entry.1 += raw_data.duration.unwrap_or_default() * 1000.0;
+        }
+    }
+
+    let mut path_calls: Vec<_> = totals
+        .into_iter()
+        .map(|(path, syscalls)| {
+            let mut syscalls: Vec<_> = syscalls
+                .into_iter()
+                .map(|(syscall, (calls, duration))| (syscall, calls, duration))
+                .collect();
+            syscalls.sort_by(|x, y| y.2.total_cmp(&x.2).then_with(|| x.0.cmp(y.0)));
+
+            PathCalls {
+                path,
+                calls: syscalls.iter().map(|s| s.1).sum(),
+                duration: syscalls.iter().map(|s| s.2).sum(),
+                syscalls,
+            }
+        })
+        .collect();
+    path_calls.sort_by(|x, y| {
+        y.duration
+            .total_cmp(&x.duration)
+            .then_with(|| x.path.cmp(y.path))
+    });
+
+    path_calls
+}
+
+// The file a call acted on, from a path argument, the file an open returned,
+// or the name '-y' gives a fd passed first
+pub(crate) fn call_path<'a>(line: &'a [u8], raw_data: &RawData<'a>) -> Option<&'a [u8]> {
+    if let Some(OtherFields::Open {
+        resolved: Some(resolved),
+        ..
+    }) = raw_data.other
+    {
+        return Some(resolved);
+    }
+
+    raw_data.path().or_else(|| {
+        // 17819 13:43:39.889240 fstat(3</etc/ld.so.cache>, {st_mode=S_IFREG|0644, st_size=69296, ...}) = 0 <0.000010>
+        //                             ^^^^^^^^^^^^^^^^^^^
+        let first = *parser::line_args(line).first()?;
+        let mut fd_split = first.splitn_str(2, "<");
+        let fd = fd_split.next()?;
+        if fd.is_empty() || !fd.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        fd_split.next()?.strip_suffix(b">")
+    })
+}
+
+/// Prints which syscalls the time on each path read or written by `pids` went
+/// to, including calls other than I/O such as 'fstat' and 'openat'
+pub fn print_path_calls(
+    buffer: &[u8],
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let io_calls = io_data::io_calls(pids, raw_data);
+    let paths: HashSet<&[u8]> = io_calls
+        .values()
+        .flatten()
+        .map(|io_call| io_call.fd)
+        .filter(|fd| !fd.is_empty())
+        .collect();
+    let path_calls = path_calls(buffer, pids, &paths);
+
+    output::print_heading("\nI/O Paths by Syscall\n")?;
+    if path_calls.is_empty() {
+        output::print_note(
+            "  No named files were read or written, run strace with '-y' to name fds\n",
+        )?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("calls", 8, Align::Right, 1),
+        Column::new("dur (ms)", 10, Align::Right, 0),
+        Column::new("syscalls (% of time)", 20, Align::Left, 0),
+        Column::new("file name", 9, Align::Left, 0),
+    ]);
+
+    for path in path_calls.iter() {
+        table.add_row(vec![
+            path.calls.to_string(),
+            format!("{:.3}", path.duration),
+            path.breakdown(),
+            path.path.to_str_lossy().to_string(),
+        ]);
+    }
+    table.print(width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_calls_split_time_by_syscall() {
+        let input = br##"477   10:00:00.000000 openat(AT_FDCWD, "app.log", O_RDONLY) = 3</var/log/app.log> <0.001000>
+477   10:00:00.100000 fstat(3</var/log/app.log>, {st_mode=S_IFREG|0644, st_size=20, ...}) = 0 <0.001000>
+477   10:00:00.200000 read(3</var/log/app.log>,  <unfinished ...>
+478   10:00:00.250000 stat("/etc/hosts", {st_mode=S_IFREG|0644, st_size=174, ...}) = 0 <0.000500>
+477   10:00:00.300000 <... read resumed>"0123456789", 10) = 10 <0.002000>
+477   10:00:00.400000 close(3</var/log/app.log>) = 0 <0.000010>"##;
+
+        let paths: HashSet<&[u8]> = [b"/var/log/app.log".as_ref()].iter().copied().collect();
+        let path_calls = path_calls(input, &[477, 478], &paths);
+
+        assert_eq!(path_calls.len(), 1);
+        assert_eq!(path_calls[0].calls, 4);
+        assert!((path_calls[0].duration - 4.01).abs() < 1e-3);
+        assert_eq!(path_calls[0].syscalls[0].0, b"read");
+        assert_eq!(
+            path_calls[0].breakdown(),
+            "read 50%, fstat 25%, openat 25%, close 0%"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use crate::pid_summary::PidSummary;
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+// A pool needs this many PIDs for the others to show what's usual
+const MIN_PEERS: usize = 4;
+
+// Standard deviations from the peers' mean for a metric to be flagged
+pub const Z_THRESHOLD: f64 = 3.0;
+
+// A flagged metric must also be at least this many times the peers' mean,
+// so tight pools don't flag small differences
+const MIN_RATIO: f64 = 2.0;
+
+// And exceed the peers' mean by at least this much, so syscalls that took
+// almost no time aren't flagged
+const MIN_EXCESS_MS: f64 = 10.0;
+const MIN_EXCESS_CALLS: f64 = 100.0;
+
+/// A PID whose metric stands out from the rest of its worker pool
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerOutlier {
+    pub pid: Pid,
+    pub program: String,
+    /// The other PIDs in the pool
+    pub peers: usize,
+    pub metric: String,
+    pub value: f64,
+    pub peer_mean: f64,
+    /// Standard deviations above the peers' mean, `None` when the peers
+    /// all had the same value
+    pub z_score: Option<f64>,
+}
+
+impl PeerOutlier {
+    pub fn ratio(&self) -> Option<f64> {
+        Some(self.value / self.peer_mean).filter(|r| r.is_finite())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Unit {
+    Ms,
+    Calls,
+}
+
+/// Compares each PID with the others in its worker pool, the PIDs sharing a
+/// parent, program, and whether they're threads, flagging metrics far above
+/// the rest of the pool. Each PID is compared with the mean and standard
+/// deviation of its peers, leaving itself out, so one misbehaving worker
+/// among a few doesn't hide itself by raising the mean.
+///
+/// The metrics are active time, user time, syscall count, and time spent in
+/// each syscall. `program` gives the program each PID runs.
+pub fn peer_outliers<F>(pid_summaries: &HashMap<Pid, PidSummary>, program: F) -> Vec<PeerOutlier>
+where
+    F: Fn(Pid) -> Option<String>,
+{
+    let mut pools: BTreeMap<(Option<Pid>, String, bool), Vec<Pid>> = BTreeMap::new();
+    for (&pid, pid_summary) in pid_summaries {
+        let program = match program(pid) {
+            Some(program) => program,
+            None => continue,
+        };
+        let is_thread = pid_summary
+            .parent_pid
+            .and_then(|parent| pid_summaries.get(&parent))
+            .is_some_and(|parent| parent.threads.contains(&pid));
+        pools
+            .entry((pid_summary.parent_pid, program, is_thread))
+            .or_default()
+            .push(pid);
+    }
+
+    let mut outliers = Vec::new();
+    for ((_, program, _), pids) in pools.iter().filter(|(_, p)| p.len() >= MIN_PEERS) {
+        let metrics: Vec<_> = pids
+            .iter()
+            .map(|pid| (*pid, metrics(&pid_summaries[pid])))
+            .collect();
+        let mut names: Vec<_> = metrics
+            .iter()
+            .flat_map(|(_, m)| m.keys().cloned())
+            .collect();
+        names.sort();
+        names.dedup();
+
+        for (name, unit) in names {
+            let values: Vec<f64> = metrics
+                .iter()
+                .map(|(_, m)| m.get(&(name.clone(), unit)).copied().unwrap_or_default())
+                .collect();
+            let sum: f64 = values.iter().sum();
+
+            for (idx, (pid, _)) in metrics.iter().enumerate() {
+                let value = values[idx];
+                let others = (values.len() - 1) as f64;
+                let mean = (sum - value) / others;
+                let variance = values
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| *i != idx)
+                    .map(|(_, v)| (v - mean).powi(2))
+                    .sum::<f64>()
+                    / others;
+                let sd = variance.sqrt();
+                let z_score = Some((value - mean) / sd).filter(|z| z.is_finite());
+
+                let min_excess = match unit {
+                    Unit::Ms => MIN_EXCESS_MS,
+                    Unit::Calls => MIN_EXCESS_CALLS,
+                };
+                if z_score.is_none_or(|z| z >= Z_THRESHOLD)
+                    && value >= mean * MIN_RATIO
+                    && value - mean >= min_excess
+                {
+                    outliers.push(PeerOutlier {
+                        pid: *pid,
+                        program: program.clone(),
+                        peers: pids.len() - 1,
+                        metric: name.clone(),
+                        value,
+                        peer_mean: mean,
+                        z_score,
+                    });
+                }
+            }
+        }
+    }
+
+    // Metrics with no spread among the peers are the most striking
+    outliers.sort_by(|x, y| {
+        let x_z = x.z_score.unwrap_or(f64::INFINITY);
+        let y_z = y.z_score.unwrap_or(f64::INFINITY);
+        y_z.partial_cmp(&x_z)
+            .unwrap_or(Ordering::Equal)
+            .then(x.pid.cmp(&y.pid))
+    });
+
+    outliers
+}
+
+fn metrics(pid_summary: &PidSummary) -> HashMap<(String, Unit), f64> {
+    let mut metrics = HashMap::default();
+    metrics.insert(
+        (String::from("active (ms)"), Unit::Ms),
+        f64::from(pid_summary.system_active_time),
+    );
+    metrics.insert(
+        (String::from("user (ms)"), Unit::Ms),
+        f64::from(pid_summary.user_time),
+    );
+    metrics.insert(
+        (String::from("syscalls"), Unit::Calls),
+        f64::from(pid_summary.syscall_count),
+    );
+
+    // Stats split by fd kind are added back together
+    for stats in &pid_summary.syscall_stats {
+        *metrics
+            .entry((format!("{} (ms)", stats.name.to_str_lossy()), Unit::Ms))
+            .or_default() += f64::from(stats.total);
+    }
+
+    metrics
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::session_summary::SessionSummary;
+    use crate::syscall_data::build_syscall_data;
+    use crate::syscall_stats::build_syscall_stats;
+
+    #[test]
+    fn peer_outliers_flag_one_slow_worker() {
+        let input = br##"100   10:00:00.000000 execve("/usr/bin/ruby", ["ruby", "unicorn"], 0x7ffc0 /* 10 vars */) = 0 <0.000100>
+100   10:00:00.001000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f0) = 101 <0.000050>
+100   10:00:00.002000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f0) = 102 <0.000050>
+100   10:00:00.003000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f0) = 103 <0.000050>
+100   10:00:00.004000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f0) = 104 <0.000050>
+100   10:00:00.005000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f0) = 105 <0.000050>
+101   10:00:00.010000 futex(0x7f00, FUTEX_WAIT_PRIVATE, 0, NULL) = 0 <0.010000>
+102   10:00:00.010000 futex(0x7f00, FUTEX_WAIT_PRIVATE, 0, NULL) = 0 <0.012000>
+103   10:00:00.010000 futex(0x7f00, FUTEX_WAIT_PRIVATE, 0, NULL) = 0 <0.500000>
+104   10:00:00.010000 futex(0x7f00, FUTEX_WAIT_PRIVATE, 0, NULL) = 0 <0.009000>
+105   10:00:00.010000 futex(0x7f00, FUTEX_WAIT_PRIVATE, 0, NULL) = 0 <0.011000>"##;
+        let pid_data_map = build_syscall_data(input);
+        let syscall_stats = build_syscall_stats(&pid_data_map);
+        let summary = SessionSummary::from_syscall_stats(&syscall_stats, &pid_data_map);
+
+        let outliers = summary.peer_outliers();
+        assert_eq!(outliers.len(), 1);
+        let outlier = &outliers[0];
+        assert_eq!((outlier.pid, outlier.program.as_str()), (103, "ruby"));
+        assert_eq!((outlier.metric.as_str(), outlier.peers), ("futex (ms)", 4));
+        assert!((outlier.peer_mean - 10.5).abs() < 0.01);
+        assert!(outlier.z_score.unwrap() > 100.0);
+        assert!((outlier.ratio().unwrap() - 47.6).abs() < 0.1);
+    }
+}
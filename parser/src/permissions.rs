@@ -0,0 +1,184 @@
+use crate::output;
+use crate::parser::{CallStatus, OtherFields};
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::fmt;
+use std::io::{prelude::*, stdout, Error};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Change<'a> {
+    Mode(&'a [u8]),
+    Owner { owner: &'a [u8], group: &'a [u8] },
+    Umask(&'a [u8]),
+}
+
+impl<'a> fmt::Display for Change<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // An id of -1 leaves the owner or group unchanged
+        let id = |id: &'a [u8]| {
+            if id == b"-1" {
+                "-".into()
+            } else {
+                id.to_str_lossy()
+            }
+        };
+
+        match self {
+            Change::Mode(mode) => write!(f, "mode {}", mode.to_str_lossy()),
+            Change::Owner { owner, group } => write!(f, "owner {}:{}", id(owner), id(group)),
+            Change::Umask(mask) => write!(f, "umask {}", mask.to_str_lossy()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PermissionChange<'a> {
+    pub pid: Pid,
+    pub time: &'a [u8],
+    pub syscall: &'a [u8],
+    pub path: Option<&'a [u8]>,
+    pub change: Change<'a>,
+    pub error: Option<&'a [u8]>,
+}
+
+impl<'a> fmt::Display for PermissionChange<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "  {: >7}    {: ^15}    {: <8}    {: <16}    {: ^15}    {: <30}",
+            self.pid,
+            output::timestamp(self.time),
+            self.syscall.to_str_lossy(),
+            self.change.to_string(),
+            self.error.unwrap_or(b"-").to_str_lossy(),
+            self.path.unwrap_or(b"-").to_str_lossy()
+        )
+    }
+}
+
+/// Mode, owner, and umask changes made by `pids`, in time order. The outcome of
+/// an unfinished call is taken from the line where it resumed.
+pub fn permission_changes<'a>(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData<'a>>,
+) -> Vec<PermissionChange<'a>> {
+    let mut changes = Vec::new();
+
+    for pid in pids {
+        let mut events = raw_data[pid].permission_events.clone();
+        events.sort_by(|x, y| (x.time).cmp(y.time));
+
+        let mut pending: Option<usize> = None;
+        for event in &events {
+            let (path, change) = match event.other {
+                Some(OtherFields::Chmod { path, mode }) => (Some(path), Change::Mode(mode)),
+                Some(OtherFields::Chown { path, owner, group }) => {
+                    (Some(path), Change::Owner { owner, group })
+                }
+                Some(OtherFields::Umask(mask)) => (None, Change::Umask(mask)),
+                _ => {
+                    if let (CallStatus::Resumed, Some(idx)) = (event.call_status, pending.take()) {
+                        let started: &mut PermissionChange = &mut changes[idx];
+                        if started.syscall == event.syscall {
+                            started.error = event.error;
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            if event.call_status == CallStatus::Started {
+                pending = Some(changes.len());
+            }
+
+            changes.push(PermissionChange {
+                pid: *pid,
+                time: event.time,
+                syscall: event.syscall,
+                path,
+                change,
+                error: event.error,
+            });
+        }
+    }
+
+    changes.sort_by(|x, y| (x.time).cmp(y.time).then_with(|| (x.pid).cmp(&y.pid)));
+
+    changes
+}
+
+pub fn print_permissions(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let changes = permission_changes(pids, raw_data);
+
+    output::print_heading("\nPermission Changes\n-----------\n")?;
+
+    if changes.is_empty() {
+        output::print_note("  No mode, owner, or umask changes found\n")?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("pid", 7, Align::Right, 0),
+        Column::new("time", 15, Align::Center, 3),
+        Column::new("syscall", 8, Align::Left, 2),
+        Column::new("change", 16, Align::Left, 0),
+        Column::new("error", 15, Align::Center, 1),
+        Column::new("path", 4, Align::Left, 0),
+    ]);
+
+    for change in changes {
+        table.add_row(vec![
+            output::pid_label(change.pid),
+            output::timestamp(change.time),
+            change.syscall.to_str_lossy().to_string(),
+            change.change.to_string(),
+            change.error.unwrap_or(b"-").to_str_lossy().to_string(),
+            change.path.unwrap_or(b"-").to_str_lossy().to_string(),
+        ]);
+    }
+    table.print(width)?;
+    writeln!(stdout())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn permissions_lists_changes_in_time_order() {
+        let input = br##"2105  10:01:12.200000 fchownat(AT_FDCWD, "/etc/tool.conf", 0, -1, 0) = 0 <0.000022>
+2104  10:01:12.100000 umask(022) = 02 <0.000009>
+2104  10:01:12.118204 chmod("/usr/local/bin/tool", 0755) = 0 <0.000031>"##;
+        let pid_data_map = build_syscall_data(input);
+        let changes = permission_changes(&[2104, 2105], &pid_data_map);
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].change.to_string(), "umask 022");
+        assert_eq!(changes[1].path, Some(b"/usr/local/bin/tool".as_ref()));
+        assert_eq!(changes[1].change.to_string(), "mode 0755");
+        assert_eq!(changes[2].pid, 2105);
+        assert_eq!(changes[2].change.to_string(), "owner 0:-");
+    }
+
+    #[test]
+    fn permissions_resumed_call_sets_error() {
+        let input = br##"2104  10:01:12.118204 chown("/opt/tool", 1000, 1000 <unfinished ...>
+2104  10:01:12.118304 <... chown resumed>) = -1 EPERM (Operation not permitted) <0.000100>"##;
+        let pid_data_map = build_syscall_data(input);
+        let changes = permission_changes(&[2104], &pid_data_map);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].change.to_string(), "owner 1000:1000");
+        assert_eq!(changes[0].error, Some(b"EPERM".as_ref()));
+    }
+}
@@ -1,18 +1,21 @@
 use crate::exec::Execs;
+use crate::json::{rounded, Lossy, Time};
+use crate::namespaces::Namespaces;
+use crate::output;
 use crate::parser::ExitType;
 use crate::syscall_data::PidData;
-use crate::syscall_stats::SyscallStats;
-use crate::time::parse_unix_timestamp;
+use crate::syscall_stats::{SyscallStats, SyscallStatsJson};
+use crate::table::{Align, Column, Table};
+use crate::time::elapsed_ms;
 use crate::HashSet;
 use crate::Pid;
 
-use bstr::ByteSlice;
-use chrono::NaiveTime;
 use lazy_static::lazy_static;
 use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::Serialize;
 use std::collections::BTreeSet;
-use std::fmt;
-use std::io::{prelude::*, stdout, Error};
+use std::io::{prelude::*, Error};
 
 lazy_static! {
     static ref WAIT_SYSCALLS: HashSet<&'static [u8]> = {
@@ -41,15 +44,53 @@ pub struct PidSummary<'a> {
     pub system_wait_time: f32,
     pub user_time: f32,
     pub total_time: f32,
+    pub elapsed_time: f32,
     pub start_time: &'a [u8],
     pub end_time: &'a [u8],
     pub syscall_stats: Vec<SyscallStats<'a>>,
+    pub grouped_stats: Option<Vec<SyscallStats<'a>>>,
     pub pvt_futex: HashSet<&'a [u8]>,
     pub parent_pid: Option<Pid>,
+    /// Start time of the parent's call that created this PID
+    pub clone_time: Option<&'a [u8]>,
     pub threads: BTreeSet<Pid>,
     pub child_pids: BTreeSet<Pid>,
     pub execve: Option<Execs>,
     pub exit: Option<ExitType<'a>>,
+    pub euid: Option<u32>,
+    /// Namespaces created by the 'clone' that started this PID
+    pub namespaces: Namespaces,
+    /// Namespaces this PID moved itself into with 'unshare'
+    pub unshared: Namespaces,
+}
+
+/// A PID's totals, relatives, programs, and exit as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub struct PidSummaryJson<'a> {
+    pid: Pid,
+    parent: Option<Pid>,
+    syscalls: i32,
+    errors: i32,
+    active_ms: f64,
+    wait_ms: f64,
+    user_ms: f64,
+    total_ms: f64,
+    elapsed_ms: f64,
+    start_time: Time<'a>,
+    end_time: Time<'a>,
+    children: &'a BTreeSet<Pid>,
+    threads: &'a BTreeSet<Pid>,
+    execs: Vec<ExecJson<'a>>,
+    exit_code: Option<i32>,
+    signal: Option<Lossy<'a>>,
+    euid: Option<u32>,
+}
+
+/// A program run by a PID as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub struct ExecJson<'a> {
+    time: &'a str,
+    cmd: &'a str,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -58,33 +99,37 @@ pub enum PrintAmt {
     Some(usize),
 }
 
-impl<'a> fmt::Display for PidSummary<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl<'a> PidSummary<'a> {
+    /// Writes the PID's totals and times, then a row for each syscall fit to `width`
+    pub fn write<W: Write>(&self, out: &mut W, width: Option<usize>) -> Result<(), Error> {
         writeln!(
-            f,
+            out,
             "  {} syscalls, active time: {:.3}ms, user time: {:.3}ms, total time: {:.3}ms",
             self.syscall_count, self.system_active_time, self.user_time, self.total_time
         )?;
         writeln!(
-            f,
-            "  start time: {}    end time: {}\n",
-            self.start_time.to_str_lossy(),
-            self.end_time.to_str_lossy()
+            out,
+            "  start time: {}    end time: {}    elapsed: {:.3}ms\n",
+            output::timestamp(self.start_time),
+            output::timestamp(self.end_time),
+            self.elapsed_time
         )?;
-        writeln!(
-            f,
-            "  {: <17}    {: >8}    {: >10}    {: >10}    {: >10}    {: >10}    {: >4}",
-            "syscall", "count", "total (ms)", "max (ms)", "avg (ms)", " min (ms)", "errors"
-        )?;
-        writeln!(
-            f,
-            "  -----------------    --------    ----------    ----------    ----------    ----------    --------"
-        )?;
-        for s in &self.syscall_stats {
-            writeln!(f, "  {}", s)?;
+
+        let mut table = Table::new(vec![
+            Column::new("syscall", 17, Align::Left, 0),
+            Column::new("count", 8, Align::Right, 0),
+            Column::new("total (ms)", 10, Align::Right, 0),
+            Column::new("max (ms)", 10, Align::Right, 2),
+            Column::new("avg (ms)", 10, Align::Right, 1),
+            Column::new("min (ms)", 10, Align::Right, 3),
+            Column::new("errors", 8, Align::Left, 0),
+        ]);
+        let rows = self.grouped_stats.as_ref().unwrap_or(&self.syscall_stats);
+        for s in rows {
+            table.add_row(s.cells());
         }
 
-        Ok(())
+        table.write(out, width)
     }
 }
 
@@ -112,15 +157,14 @@ impl<'a> From<(&[SyscallStats<'a>], &PidData<'a>)> for PidSummary<'a> {
         let start_time = pid_data.start_time;
         let end_time = pid_data.end_time;
 
+        // Time between the first and last lines for the PID
+        let elapsed_time = elapsed_ms(start_time, end_time).unwrap_or(0.0);
         let total_time =
-            PidSummary::calc_total_time(start_time, end_time, system_active_time, system_wait_time);
+            PidSummary::calc_total_time(elapsed_time, system_active_time, system_wait_time);
 
         let user_time = total_time - system_active_time - system_wait_time;
 
-        let execve = match &pid_data.execve {
-            Some(e) => Some(Execs::new(e.clone())),
-            None => None,
-        };
+        let execve = pid_data.execve.as_ref().map(|e| Execs::new(e.clone()));
 
         PidSummary {
             syscall_count,
@@ -128,32 +172,124 @@ impl<'a> From<(&[SyscallStats<'a>], &PidData<'a>)> for PidSummary<'a> {
             system_wait_time,
             user_time,
             total_time,
+            elapsed_time,
             start_time,
             end_time,
             syscall_stats: syscall_stats.to_vec(),
+            grouped_stats: None,
             pvt_futex: pid_data.pvt_futex.clone(),
             parent_pid: None, // parent is calculated later on
+            clone_time: None,
             threads: pid_data.threads.iter().cloned().collect(),
             child_pids: pid_data.child_pids.iter().cloned().collect(),
             execve,
             exit: pid_data.exit,
+            euid: None,
+            namespaces: Namespaces::default(), // set with the parent's clone later on
+            unshared: pid_data.unshared,
         }
     }
 }
 
 impl<'a> PidSummary<'a> {
-    pub fn print_related_pids(&self, print_amt: PrintAmt) -> Result<(), Error> {
+    pub fn error_count(&self) -> i32 {
+        self.syscall_stats.iter().map(|s| s.error_count()).sum()
+    }
+
+    /// The PID's totals, relatives, programs, and exit as written to JSON,
+    /// for reports to flatten into records of their own
+    pub fn to_json(&self, pid: Pid) -> PidSummaryJson<'_> {
+        let (exit_code, signal) = self.exit_json();
+
+        PidSummaryJson {
+            pid,
+            parent: self.parent_pid,
+            syscalls: self.syscall_count,
+            errors: self.error_count(),
+            active_ms: rounded(self.system_active_time, 3),
+            wait_ms: rounded(self.system_wait_time, 3),
+            user_ms: rounded(self.user_time, 3),
+            total_ms: rounded(self.total_time, 3),
+            elapsed_ms: rounded(self.elapsed_time, 3),
+            start_time: Time(self.start_time),
+            end_time: Time(self.end_time),
+            children: &self.child_pids,
+            threads: &self.threads,
+            execs: self.execs_json(),
+            exit_code,
+            signal,
+            euid: self.euid,
+        }
+    }
+
+    /// The programs the PID ran as written to JSON
+    pub fn execs_json(&self) -> Vec<ExecJson<'_>> {
+        self.execve
+            .iter()
+            .flat_map(|e| e.iter())
+            .map(|(cmd, time)| ExecJson { time, cmd })
+            .collect()
+    }
+
+    /// The exit code and the signal the PID exited with, either or neither
+    pub fn exit_json(&self) -> (Option<i32>, Option<Lossy<'a>>) {
+        match self.exit {
+            Some(ExitType::Exit(code)) => (Some(code), None),
+            Some(ExitType::Signal(sig)) => (None, Some(Lossy(sig))),
+            None => (None, None),
+        }
+    }
+
+    /// The rows of the PID's syscall table as written to JSON, grouped when
+    /// syscalls were grouped
+    pub fn syscall_stats_json(&self) -> Vec<SyscallStatsJson<'_>> {
+        self.grouped_stats
+            .as_ref()
+            .unwrap_or(&self.syscall_stats)
+            .iter()
+            .map(SyscallStats::to_json)
+            .collect()
+    }
+
+    /// Where the PID started a sandbox, e.g. 'new ns: mnt,pid; unshared: net'
+    pub fn namespace_note(&self) -> Option<String> {
+        let mut notes = Vec::new();
+        if !self.namespaces.is_empty() {
+            notes.push(format!("new ns: {}", self.namespaces));
+        }
+        if !self.unshared.is_empty() {
+            notes.push(format!("unshared: {}", self.unshared));
+        }
+
+        if notes.is_empty() {
+            None
+        } else {
+            Some(notes.join("; "))
+        }
+    }
+
+    pub fn write_related_pids<W: Write>(
+        &self,
+        out: &mut W,
+        print_amt: PrintAmt,
+    ) -> Result<(), Error> {
         if let Some(p) = self.parent_pid {
-            writeln!(stdout(), "  Parent PID:  {}", p)?;
+            writeln!(out, "  Parent PID:  {}", output::pid_label(p))?;
         }
 
-        PidSummary::print_pids(self.threads.iter().cloned(), "Threads", print_amt)?;
-        PidSummary::print_pids(self.child_pids.iter().cloned(), "Child PIDs", print_amt)?;
+        PidSummary::write_pids(out, self.threads.iter().cloned(), "Threads", print_amt)?;
+        PidSummary::write_pids(
+            out,
+            self.child_pids.iter().cloned(),
+            "Child PIDs",
+            print_amt,
+        )?;
 
         Ok(())
     }
 
-    fn print_pids(
+    fn write_pids<W: Write>(
+        out: &mut W,
         pids: impl ExactSizeIterator<Item = Pid>,
         name: &str,
         print_amt: PrintAmt,
@@ -166,51 +302,39 @@ impl<'a> PidSummary<'a> {
                 PrintAmt::Some(c) => c,
             };
 
-            write!(stdout(), "  {}:  ", name)?;
+            write!(out, "  {}:  ", name)?;
             if pids.len() > print_ct {
                 for (i, p) in pids.enumerate().take(print_ct) {
                     if i % 10 == 0 && i != 0 {
-                        write!(stdout(), "\n               ")?;
+                        write!(out, "\n               ")?;
                     }
                     if i != print_ct - 1 {
-                        write!(stdout(), "{}, ", p)?;
+                        write!(out, "{}, ", output::pid_label(p))?;
                     } else {
-                        write!(stdout(), "{} ", p)?;
+                        write!(out, "{} ", output::pid_label(p))?;
                     }
                 }
-                writeln!(stdout(), "and {} more...", len - print_ct)?;
+                writeln!(out, "and {} more...", len - print_ct)?;
             } else {
                 let mut pid_iter = pids.enumerate().peekable();
                 while let Some((i, n)) = pid_iter.next() {
                     if i % 10 == 0 && i != 0 {
-                        write!(stdout(), "\n               ")?;
+                        write!(out, "\n               ")?;
                     }
                     if pid_iter.peek().is_some() {
-                        write!(stdout(), "{}, ", n)?;
+                        write!(out, "{}, ", output::pid_label(n))?;
                     } else {
-                        write!(stdout(), "{}", n)?;
+                        write!(out, "{}", output::pid_label(n))?;
                     }
                 }
-                writeln!(stdout())?;
+                writeln!(out)?;
             }
         }
 
         Ok(())
     }
 
-    fn calc_total_time(start: &[u8], end: &[u8], active_time: f32, wait_time: f32) -> f32 {
-        let st = NaiveTime::parse_from_str(start.to_str_lossy().as_ref(), "%H:%M:%S%.6f");
-        let et = NaiveTime::parse_from_str(end.to_str_lossy().as_ref(), "%H:%M:%S%.6f");
-
-        let timestamp_time = if let (Some(s), Some(e)) = (st.ok(), et.ok()) {
-            (e - s).num_microseconds().unwrap() as f32 / 1000.0
-        } else if let (Some(s), Some(e)) = (parse_unix_timestamp(start), parse_unix_timestamp(end))
-        {
-            (e - s).num_microseconds().unwrap() as f32 / 1000.0
-        } else {
-            0.0
-        };
-
+    fn calc_total_time(timestamp_time: f32, active_time: f32, wait_time: f32) -> f32 {
         // In some cases a syscall begun before strace may report
         // a run time greater than the timestamp span of the trace
         // In this case we just use the timestamp span
@@ -1,7 +1,13 @@
-use crate::pid_summary::PidSummary;
+use crate::json::{self, Lossy, Time};
+use crate::output;
+use crate::pid_summary::{ExecJson, PidSummary};
+use crate::table::{Align, Column, Table};
+use crate::time::Timestamp;
 use crate::HashMap;
 use crate::Pid;
 
+use schemars::JsonSchema;
+use serde::Serialize;
 use std::collections::HashSet;
 use std::io;
 use std::io::prelude::*;
@@ -35,6 +41,198 @@ impl TreePrint {
     }
 }
 
+/// Syscall activity of a PID in the tree, on its own and with everything
+/// printed beneath it
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rollup {
+    /// Active syscall time, excluding calls that wait, in ms
+    pub self_time: f32,
+    pub self_calls: i64,
+    pub time: f32,
+    pub calls: i64,
+}
+
+/// The `Rollup` of each PID printed in the tree starting at `roots`. The walk
+/// matches `print_tree`, so a PID's totals are those of the lines under it.
+pub fn rollups(roots: &[Pid], pid_summaries: &HashMap<Pid, PidSummary>) -> HashMap<Pid, Rollup> {
+    let mut rollups = HashMap::default();
+    let mut done = HashSet::new();
+    for &pid in roots {
+        rollup(pid, FanOut::All, pid_summaries, &mut done, &mut rollups);
+    }
+
+    rollups
+}
+
+fn rollup(
+    pid: Pid,
+    fan_out: FanOut,
+    pid_summaries: &HashMap<Pid, PidSummary>,
+    done: &mut HashSet<Pid>,
+    rollups: &mut HashMap<Pid, Rollup>,
+) -> Option<Rollup> {
+    if !done.insert(pid) {
+        return None;
+    }
+    let pid_summary = pid_summaries.get(&pid)?;
+
+    let mut totals = Rollup {
+        self_time: pid_summary.system_active_time,
+        self_calls: i64::from(pid_summary.syscall_count),
+        time: pid_summary.system_active_time,
+        calls: i64::from(pid_summary.syscall_count),
+    };
+
+    // Threads are only printed under the PID that isn't itself shown as one
+    let mut below: Vec<_> = match fan_out {
+        FanOut::All => pid_summary
+            .threads
+            .iter()
+            .map(|&thread| (thread, FanOut::NoThreads))
+            .collect(),
+        FanOut::NoThreads => Vec::new(),
+    };
+    below.extend(
+        pid_summary
+            .child_pids
+            .difference(&pid_summary.threads)
+            .map(|&child| (child, FanOut::All)),
+    );
+
+    for (below, fan_out) in below {
+        if let Some(below) = rollup(below, fan_out, pid_summaries, done, rollups) {
+            totals.time += below.time;
+            totals.calls += below.calls;
+        }
+    }
+
+    rollups.insert(pid, totals);
+    Some(totals)
+}
+
+/// The columns printed before each PID in the tree
+#[derive(Clone, Debug, Default)]
+pub struct TreeColumns {
+    pub rollups: Option<HashMap<Pid, Rollup>>,
+    pub timeline: Option<Timeline>,
+}
+
+impl TreeColumns {
+    // The columns before the tree, followed by the tree itself under 'pid'
+    fn table(&self) -> Table {
+        let mut columns = Vec::new();
+        if self.rollups.is_some() {
+            columns.push(Column::new("self actv (ms)", 14, Align::Right, 0));
+            columns.push(Column::new("incl actv (ms)", 14, Align::Right, 0));
+            columns.push(Column::new("self calls", 10, Align::Right, 0));
+            columns.push(Column::new("incl calls", 10, Align::Right, 0));
+        }
+        if let Some(timeline) = &self.timeline {
+            let lifetime = format!("lifetime over {:.3}s", timeline.span_us as f64 / 1e6);
+            columns.push(Column::new(lifetime, TIMELINE_WIDTH, Align::Left, 0));
+        }
+        columns.push(Column::new("pid", 7, Align::Left, 0));
+
+        Table::new(columns)
+    }
+
+    /// Prints the column headings shown before the tree
+    pub fn print_header(&self) -> Result<(), io::Error> {
+        if !output::headers() || (self.rollups.is_none() && self.timeline.is_none()) {
+            return Ok(());
+        }
+
+        self.table().print(None)
+    }
+
+    fn prefix(&self, pid: Pid, pid_summary: &PidSummary) -> String {
+        let mut cells = Vec::new();
+        if let Some(rollups) = &self.rollups {
+            let r = rollups.get(&pid).copied().unwrap_or_default();
+            cells.push(format!("{:.3}", r.self_time));
+            cells.push(format!("{:.3}", r.time));
+            cells.push(r.self_calls.to_string());
+            cells.push(r.calls.to_string());
+        }
+        if let Some(timeline) = &self.timeline {
+            cells.push(timeline.bar(pid_summary.start_time, pid_summary.end_time));
+        }
+        if cells.is_empty() {
+            return String::new();
+        }
+        cells.push(String::new());
+
+        let mut table = self.table();
+        table.set_headers(true);
+        let pid_start = table.render(None)[0].chars().count() - "pid".len();
+        table.set_headers(false);
+        table.add_row(cells);
+        let row = table.render(None).remove(0);
+
+        format!("{: <1$}", row, pid_start)
+    }
+}
+
+/// Characters in a timeline bar
+pub const TIMELINE_WIDTH: usize = 40;
+
+// Blocks one to seven eighths of a character wide
+const EIGHTHS: [char; 7] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// The span from the first PID's start to the last PID's end, against which
+/// each PID's lifetime is drawn as a bar
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timeline {
+    start: Timestamp,
+    span_us: i64,
+}
+
+impl Timeline {
+    pub fn new(pid_summaries: &HashMap<Pid, PidSummary>) -> Option<Timeline> {
+        let start = pid_summaries
+            .values()
+            .filter_map(|s| Timestamp::parse(s.start_time))
+            .min()?;
+        let end = pid_summaries
+            .values()
+            .filter_map(|s| Timestamp::parse(s.end_time))
+            .max()?;
+        let span_us = end.since(start)?.num_microseconds()?;
+
+        Some(Timeline {
+            start,
+            span_us: span_us.max(1),
+        })
+    }
+
+    /// A bar `TIMELINE_WIDTH` characters wide, blank up to where the PID
+    /// started and filled until it ended, to an eighth of a character
+    pub fn bar(&self, start: &[u8], end: &[u8]) -> String {
+        let eighths = |time: &[u8]| {
+            let offset = Timestamp::parse(time)?
+                .since(self.start)?
+                .num_microseconds()?
+                .clamp(0, self.span_us);
+            Some((offset as i128 * TIMELINE_WIDTH as i128 * 8 / self.span_us as i128) as usize)
+        };
+        let (start, end) = match (eighths(start), eighths(end)) {
+            (Some(start), Some(end)) => (start / 8, end.max(start + 1)),
+            _ => return " ".repeat(TIMELINE_WIDTH),
+        };
+        let end = end.min(TIMELINE_WIDTH * 8);
+
+        let mut bar = " ".repeat(start);
+        bar.extend(std::iter::repeat_n('█', end / 8 - start));
+        if end % 8 > 0 {
+            bar.push(EIGHTHS[end % 8 - 1]);
+        }
+        let len = bar.chars().count();
+        bar.extend(std::iter::repeat_n(' ', TIMELINE_WIDTH - len));
+
+        bar
+    }
+}
+
 const ELL: &str = "  └";
 const EMPTY: &str = "   ";
 const HORIZ: &str = "─";
@@ -47,6 +245,7 @@ pub fn print_tree(
     done: &mut Vec<Pid>,
     filled_cols: &mut HashSet<usize>,
     truncate: bool,
+    columns: &TreeColumns,
     print_info: TreePrint,
 ) -> Result<(), io::Error> {
     use PidPosition::*;
@@ -57,7 +256,7 @@ pub fn print_tree(
     done.push(pid);
 
     if let Some(pid_summary) = pid_summaries.get(&pid) {
-        let mut header = String::new();
+        let mut header = columns.prefix(pid, pid_summary);
         for i in 1..print_info.indent {
             if filled_cols.contains(&i) {
                 header += VERT;
@@ -73,18 +272,18 @@ pub fn print_tree(
         };
 
         match print_info.fan_out {
-            FanOut::All => line += &pid.to_string(),
-            FanOut::NoThreads => line += &format!("{{{}}}", pid),
+            FanOut::All => line += &output::pid_label(pid),
+            FanOut::NoThreads => line += &format!("{{{}}}", output::pid_label(pid)),
         }
 
         let exec = pid_summary
             .execve
             .as_ref()
             .and_then(|e| e.cmds().last())
-            .map(|last| match (truncate, last.len() > 50) {
-                (true, true) => format!("{}...", &last[..50]),
-                (true, false) => last.clone(),
-                (false, _) => last.clone(),
+            .map(|last| match (truncate, last.char_indices().nth(50)) {
+                // Cut on a char boundary, names needn't be ASCII
+                (true, Some((end, _))) => format!("{}...", &last[..end]),
+                _ => last.clone(),
             });
 
         match (print_info.fan_out, pid_summary.exit, exec) {
@@ -103,6 +302,10 @@ pub fn print_tree(
             _ => {}
         }
 
+        if let Some(note) = pid_summary.namespace_note() {
+            line += format!(" [{}]", note).as_str();
+        }
+
         writeln!(io::stdout(), "{}", line)?;
 
         match print_info.position {
@@ -134,6 +337,7 @@ pub fn print_tree(
                     done,
                     filled_cols,
                     truncate,
+                    columns,
                     TreePrint::new(FanOut::NoThreads, print_info.indent + 1, last_thread),
                 )?;
             }
@@ -155,6 +359,7 @@ pub fn print_tree(
                 done,
                 filled_cols,
                 truncate,
+                columns,
                 TreePrint::new(FanOut::All, print_info.indent + 1, last_child),
             )?;
         }
@@ -162,3 +367,187 @@ pub fn print_tree(
 
     Ok(())
 }
+
+/// Writes the tree of `pids` as one JSON object, for tools building their own
+/// view of it. It has a record for each PID with its parent, whether it's a
+/// thread, when it was cloned, started, and ended, the programs it executed,
+/// and how it exited, and an edge from each parent to each of its children and
+/// threads with the time of the call that created it.
+pub fn write_tree_json<W: Write>(
+    out: &mut W,
+    pids: &[Pid],
+    pid_summaries: &HashMap<Pid, PidSummary>,
+) -> Result<(), io::Error> {
+    let is_thread = |pid: Pid, parent: Option<Pid>| {
+        parent
+            .and_then(|p| pid_summaries.get(&p))
+            .is_some_and(|p| p.threads.contains(&pid))
+    };
+
+    let mut tree = TreeReport {
+        pids: Vec::new(),
+        edges: Vec::new(),
+    };
+    for &pid in pids {
+        let pid_summary = match pid_summaries.get(&pid) {
+            Some(p) => p,
+            None => continue,
+        };
+
+        let (exit_code, signal) = pid_summary.exit_json();
+        tree.pids.push(TreePidJson {
+            pid,
+            parent: pid_summary.parent_pid,
+            thread: is_thread(pid, pid_summary.parent_pid),
+            clone_time: pid_summary.clone_time.map(Time),
+            start_time: Time(pid_summary.start_time),
+            end_time: Time(pid_summary.end_time),
+            execs: pid_summary.execs_json(),
+            exit_code,
+            signal,
+        });
+
+        for &child in &pid_summary.child_pids {
+            let clone_time = pid_summaries.get(&child).and_then(|c| c.clone_time);
+            tree.edges.push(TreeEdgeJson {
+                parent: pid,
+                child,
+                thread: pid_summary.threads.contains(&child),
+                time: clone_time.map(Time),
+            });
+        }
+    }
+
+    json::write_report(out, &tree)
+}
+
+// A PID of the tree as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct TreePidJson<'a> {
+    pid: Pid,
+    parent: Option<Pid>,
+    thread: bool,
+    clone_time: Option<Time<'a>>,
+    start_time: Time<'a>,
+    end_time: Time<'a>,
+    execs: Vec<ExecJson<'a>>,
+    exit_code: Option<i32>,
+    signal: Option<Lossy<'a>>,
+}
+
+// An edge from a parent to a child or thread as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct TreeEdgeJson<'a> {
+    parent: Pid,
+    child: Pid,
+    thread: bool,
+    time: Option<Time<'a>>,
+}
+
+// The tree as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct TreeReport<'a> {
+    pids: Vec<TreePidJson<'a>>,
+    edges: Vec<TreeEdgeJson<'a>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_summary::SessionSummary;
+    use crate::syscall_data::build_syscall_data;
+    use crate::syscall_stats::build_syscall_stats;
+    use serde_json::Value;
+
+    #[test]
+    fn pid_tree_writes_json() {
+        let input = br##"100   10:00:00.000000 execve("/usr/bin/ruby", ["ruby", "unicorn"], 0x7ffc0 /* 10 vars */) = 0 <0.000100>
+100   10:00:00.001000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f0) = 101 <0.000050>
+100   10:00:00.002000 clone(child_stack=0x7f1, flags=CLONE_VM|CLONE_FS|CLONE_FILES|CLONE_SIGHAND|CLONE_THREAD|CLONE_SYSVSEM|CLONE_SETTLS|CLONE_PARENT_SETTID|CLONE_CHILD_CLEARTID, parent_tid=[102], tls=0x7f2, child_tidptr=0x7f3) = 102 <0.000050>
+101   10:00:00.010000 write(1, "a", 1) = 1 <0.000010>
+101   10:00:00.020000 exit_group(3) = ?
+101   10:00:00.020100 +++ exited with 3 +++
+102   10:00:00.010000 futex(0x7f00, FUTEX_WAIT_PRIVATE, 0, NULL) = 0 <0.010000>"##;
+        let pid_data_map = build_syscall_data(input);
+        let syscall_stats = build_syscall_stats(&pid_data_map);
+        let summary = SessionSummary::from_syscall_stats(&syscall_stats, &pid_data_map);
+
+        let mut out = Vec::new();
+        summary.write_pid_tree_json(&mut out).unwrap();
+        let tree = serde_json::from_slice::<Value>(&out).unwrap();
+
+        let pids = match tree.get("pids") {
+            Some(Value::Array(pids)) => pids,
+            _ => panic!("no pids"),
+        };
+        assert_eq!(pids.len(), 3);
+        let record = |pid: f64| {
+            pids.iter()
+                .find(|p| p.get("pid").and_then(Value::as_f64) == Some(pid))
+                .unwrap()
+        };
+        let root = record(100.0);
+        assert_eq!(root.get("parent"), Some(&Value::Null));
+        assert_eq!(
+            root.get("execs").and_then(|e| match e {
+                Value::Array(execs) => execs[0].get("cmd").and_then(Value::as_str),
+                _ => None,
+            }),
+            Some("/usr/bin/ruby unicorn")
+        );
+        assert_eq!(
+            record(101.0).get("exit_code").and_then(Value::as_f64),
+            Some(3.0)
+        );
+        assert_eq!(record(101.0).get("thread"), Some(&Value::Bool(false)));
+        assert_eq!(record(102.0).get("thread"), Some(&Value::Bool(true)));
+        assert_eq!(
+            record(102.0).get("clone_time").and_then(Value::as_str),
+            Some("10:00:00.002000")
+        );
+
+        let edges = match tree.get("edges") {
+            Some(Value::Array(edges)) => edges,
+            _ => panic!("no edges"),
+        };
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0].get("child").and_then(Value::as_f64), Some(101.0));
+        assert_eq!(
+            edges[0].get("time").and_then(Value::as_str),
+            Some("10:00:00.001000")
+        );
+    }
+
+    #[test]
+    fn pid_tree_columns_line_up_with_header() {
+        let mut rollups = HashMap::default();
+        rollups.insert(
+            100,
+            Rollup {
+                self_time: 7.654,
+                self_calls: 385,
+                time: 17.502,
+                calls: 877,
+            },
+        );
+        let columns = TreeColumns {
+            rollups: Some(rollups),
+            timeline: None,
+        };
+        let mut table = columns.table();
+        table.set_headers(true);
+        let header = table.render(None).remove(0);
+
+        let pid_data_map = build_syscall_data(b"100   10:00:00.000000 brk(NULL) = 0x1 <0.000001>");
+        let pid_summary = PidSummary::from((&[][..], &pid_data_map[&100]));
+        let prefix = columns.prefix(100, &pid_summary);
+        assert_eq!(
+            header,
+            "  self actv (ms)    incl actv (ms)    self calls    incl calls    pid"
+        );
+        assert_eq!(
+            prefix,
+            "           7.654            17.502           385           877    "
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use crate::output;
+use crate::parser::{CallStatus, OtherFields, RawData};
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use std::collections::BTreeSet;
+use std::io::Error;
+
+/// Ready events returned by 'epoll_wait' for one fd registered with 'epoll_ctl'
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpollSource<'a> {
+    pub epfd: i32,
+    /// The registered fd, not known if it was added before the trace started
+    pub fd: Option<i32>,
+    pub file: Option<&'a [u8]>,
+    /// The data the fd was registered with, which 'epoll_wait' returns
+    pub data: &'a [u8],
+    pub wakeups: usize,
+    pub pids: BTreeSet<Pid>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EpollWaits {
+    pub calls: usize,
+    /// Calls that returned without any ready events
+    pub empty: usize,
+}
+
+// Threads share their process's epoll fds, so registrations are keyed by the
+// process rather than the PID making the call
+type Registration<'a> = (Pid, i32, &'a [u8]);
+
+/// Ready events returned to `pids` by 'epoll_wait', 'epoll_pwait', and
+/// 'epoll_pwait2', attributed to the fd registered with the data returned,
+/// with the fds that woke the most waits first. Registrations made by any
+/// thread of the same process are used.
+pub fn epoll_sources<'a>(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData<'a>>,
+) -> (Vec<EpollSource<'a>>, EpollWaits) {
+    let pids: HashSet<Pid> = pids.iter().copied().collect();
+    let processes = thread_processes(raw_data);
+    let process = |pid: Pid| processes.get(&pid).copied().unwrap_or(pid);
+
+    let mut events: Vec<&RawData> = raw_data
+        .values()
+        .flat_map(|pid_data| pid_data.poll_events.iter())
+        .collect();
+    events.sort_by(|x, y| (x.time).cmp(y.time));
+
+    let mut registered: HashMap<Registration, (i32, Option<&[u8]>)> = HashMap::default();
+    let mut sources: HashMap<(i32, &[u8]), EpollSource> = HashMap::default();
+    let mut pending: HashMap<Pid, i32> = HashMap::default();
+    let mut waits = EpollWaits::default();
+
+    for event in events {
+        let (epfd, ready) = match (&event.other, event.call_status) {
+            (Some(OtherFields::EpollCtl { .. }), _) if event.error.is_some() => continue,
+            (
+                Some(OtherFields::EpollCtl {
+                    epfd,
+                    op,
+                    fd,
+                    file,
+                    data,
+                }),
+                _,
+            ) => {
+                let proc = process(event.pid);
+                match (*op, data) {
+                    (b"EPOLL_CTL_ADD" | b"EPOLL_CTL_MOD", Some(data)) => {
+                        registered.retain(|(p, e, _), (f, _)| (*p, *e, *f) != (proc, *epfd, *fd));
+                        registered.insert((proc, *epfd, data), (*fd, *file));
+                    }
+                    (b"EPOLL_CTL_DEL", _) => {
+                        registered.retain(|(p, e, _), (f, _)| (*p, *e, *f) != (proc, *epfd, *fd));
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            (
+                Some(OtherFields::EpollWait {
+                    epfd: Some(epfd), ..
+                }),
+                CallStatus::Started,
+            ) => {
+                pending.insert(event.pid, *epfd);
+                continue;
+            }
+            (Some(OtherFields::EpollWait { epfd, ready }), _) => {
+                let epfd = match epfd {
+                    Some(epfd) => *epfd,
+                    None => match pending.remove(&event.pid) {
+                        Some(epfd) => epfd,
+                        None => continue,
+                    },
+                };
+                (epfd, ready)
+            }
+            _ => continue,
+        };
+
+        if !pids.contains(&event.pid) {
+            continue;
+        }
+
+        waits.calls += 1;
+        if ready.is_empty() {
+            waits.empty += 1;
+        }
+
+        let proc = process(event.pid);
+        for data in ready {
+            let (fd, file) = match registered.get(&(proc, epfd, *data)) {
+                Some((fd, file)) => (Some(*fd), *file),
+                None => (None, None),
+            };
+            let source = sources.entry((epfd, data)).or_insert_with(|| EpollSource {
+                epfd,
+                fd,
+                file,
+                data,
+                wakeups: 0,
+                pids: BTreeSet::new(),
+            });
+            // The fd may have been registered since an earlier wakeup
+            if source.fd.is_none() {
+                source.fd = fd;
+                source.file = file;
+            }
+            source.wakeups += 1;
+            source.pids.insert(event.pid);
+        }
+    }
+
+    let mut sources: Vec<_> = sources.into_values().collect();
+    sources.sort_by(|x, y| {
+        (y.wakeups)
+            .cmp(&x.wakeups)
+            .then_with(|| x.epfd.cmp(&y.epfd))
+            .then_with(|| x.fd.is_none().cmp(&y.fd.is_none()))
+            .then_with(|| x.fd.cmp(&y.fd))
+            .then_with(|| x.data.cmp(y.data))
+    });
+
+    (sources, waits)
+}
+
+// Each thread's process, following threads started by other threads
+fn thread_processes(raw_data: &HashMap<Pid, PidData>) -> HashMap<Pid, Pid> {
+    let parents: HashMap<Pid, Pid> = raw_data
+        .iter()
+        .flat_map(|(pid, pid_data)| pid_data.threads.iter().map(move |t| (*t, *pid)))
+        .collect();
+
+    parents
+        .keys()
+        .map(|&thread| {
+            let mut process = thread;
+            let mut seen = HashSet::default();
+            while let Some(&parent) = parents.get(&process) {
+                if !seen.insert(process) {
+                    break;
+                }
+                process = parent;
+            }
+            (thread, process)
+        })
+        .collect()
+}
+
+pub fn print_epoll_sources(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let (sources, waits) = epoll_sources(pids, raw_data);
+
+    output::print_heading("\nepoll Wakeups by Registered fd\n-----------\n")?;
+
+    if waits.calls == 0 {
+        output::print_note("  No epoll_wait calls found\n")?;
+        return Ok(());
+    }
+
+    let total: usize = sources.iter().map(|s| s.wakeups).sum();
+    let mut table = Table::new(vec![
+        Column::new("wakeups", 8, Align::Right, 0),
+        Column::new("% wakeups", 9, Align::Right, 2),
+        Column::new("pids", 5, Align::Right, 3),
+        Column::new("epfd", 4, Align::Right, 1),
+        Column::new("fd", 4, Align::Right, 0),
+        Column::new("data", 6, Align::Left, 4),
+        Column::new("file", 4, Align::Left, 0),
+    ]);
+
+    for source in sources.iter().take(count) {
+        table.add_row(vec![
+            source.wakeups.to_string(),
+            format!("{:.2}%", source.wakeups as f64 / total as f64 * 100.0),
+            source.pids.len().to_string(),
+            source.epfd.to_string(),
+            source
+                .fd
+                .map_or_else(|| String::from("?"), |fd| fd.to_string()),
+            source.data.to_str_lossy().to_string(),
+            source.file.unwrap_or(b"-").to_str_lossy().to_string(),
+        ]);
+    }
+    table.print(width)?;
+
+    output::print_note(&format!(
+        "\n  {} epoll_wait calls, {} returned no ready fds",
+        waits.calls, waits.empty
+    ))?;
+
+    if sources.iter().any(|s| s.fd.is_none()) {
+        output::print_note(
+            "  fds shown as '?' were registered before the trace started, their data is often the fd itself",
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn epoll_wakeups_attributed_to_registered_fds() {
+        let input = br##"9071  10:14:21.000000 epoll_ctl(5<anon_inode:[eventpoll]>, EPOLL_CTL_ADD, 7<socket:[200]>, {EPOLLIN, {u32=7, u64=7}}) = 0 <0.000010>
+9071  10:14:21.000100 epoll_ctl(5<anon_inode:[eventpoll]>, EPOLL_CTL_ADD, 8<pipe:[300]>, {EPOLLIN, {u32=1, u64=1}}) = 0 <0.000010>
+9071  10:14:21.000200 clone(child_stack=0x7f0d7cbfef70, flags=CLONE_VM|CLONE_FS|CLONE_FILES|CLONE_SIGHAND|CLONE_THREAD|CLONE_SYSVSEM|CLONE_SETTLS|CLONE_PARENT_SETTID|CLONE_CHILD_CLEARTID, parent_tidptr=0x7f0d7cbff9d0, tls=0x7f0d7cbff700, child_tidptr=0x7f0d7cbff9d0) = 9072 <0.000050>
+9072  10:14:21.001000 epoll_wait(5<anon_inode:[eventpoll]>,  <unfinished ...>
+9072  10:14:21.101000 <... epoll_wait resumed>[{EPOLLIN, {u32=7, u64=7}}, {EPOLLIN, {u32=1, u64=1}}], 1024, -1) = 2 <0.100000>
+9072  10:14:21.102000 epoll_wait(5<anon_inode:[eventpoll]>, [{EPOLLIN, {u32=7, u64=7}}], 1024, -1) = 1 <0.000500>
+9071  10:14:21.103000 epoll_ctl(5<anon_inode:[eventpoll]>, EPOLL_CTL_DEL, 7<socket:[200]>, NULL) = 0 <0.000010>
+9072  10:14:21.104000 epoll_wait(5<anon_inode:[eventpoll]>, [{EPOLLIN, {u32=9, u64=9}}], 1024, 0) = 1 <0.000010>
+9072  10:14:21.105000 epoll_wait(5<anon_inode:[eventpoll]>, [], 1024, 0) = 0 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let (sources, waits) = epoll_sources(&[9071, 9072], &pid_data_map);
+
+        assert_eq!(waits, EpollWaits { calls: 4, empty: 1 });
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[0].fd, Some(7));
+        assert_eq!(sources[0].file, Some(b"socket:[200]".as_ref()));
+        assert_eq!(sources[0].wakeups, 2);
+        assert_eq!(sources[1].file, Some(b"pipe:[300]".as_ref()));
+        assert_eq!(sources[2].fd, None);
+        assert_eq!(sources[2].data, b"9");
+
+        let (sources, _) = epoll_sources(&[9071], &pid_data_map);
+        assert!(sources.is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::file_data::FileData;
+use crate::file_kind::FileKind;
+use crate::io_data;
+use crate::output;
+use crate::parser::OtherFields;
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::time::parse_timestamp_us;
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::collections::BTreeSet;
+use std::io::Error;
+
+// A process opening its own /proc entries this often is polling itself
+const SELF_POLL_OPENS: usize = 10;
+
+/// Accesses to a path under /proc or /sys
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PseudoFile<'a> {
+    /// The path as opened, so '/proc/self/status' is shared by every PID
+    /// reading its own status
+    pub path: &'a [u8],
+    pub opens: usize,
+    /// Reads and writes on fds of the path
+    pub io_calls: usize,
+    pub bytes: i64,
+    pub errors: usize,
+    /// Time in opens and I/O, in ms
+    pub duration: f32,
+    pub pids: BTreeSet<Pid>,
+    /// Whether a PID accessed its own entries under /proc
+    pub self_inspection: bool,
+    first_open: Option<i64>,
+    last_open: Option<i64>,
+}
+
+impl<'a> PseudoFile<'a> {
+    /// Opens per second between the first and last, when opened more than once
+    pub fn opens_per_sec(&self) -> Option<f64> {
+        match (self.first_open, self.last_open) {
+            (Some(first), Some(last)) if self.opens > 1 && last > first => {
+                Some((self.opens - 1) as f64 / ((last - first) as f64 / 1_000_000.0))
+            }
+            _ => None,
+        }
+    }
+
+    /// A PID re-reading its own /proc entries, such as '/proc/self/status'
+    /// on every request, rather than keeping what it learned
+    pub fn is_self_poll(&self) -> bool {
+        self.self_inspection && self.opens >= SELF_POLL_OPENS
+    }
+}
+
+/// Accesses by `pids` to paths under /proc and /sys, from `files` opened and
+/// the I/O on their fds, with the most time spent first. I/O on fds opened
+/// through a link such as '/proc/self' is counted under the path opened.
+pub fn pseudo_files<'a>(
+    files: &[&FileData<'a>],
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData<'a>>,
+) -> Vec<PseudoFile<'a>> {
+    // '-y' shows '/proc/self/status' opened by PID 823 as '/proc/823/status',
+    // and '/proc/net/unix' as '/proc/823/net/unix'
+    let mut opened_as: HashMap<&[u8], &[u8]> = HashMap::default();
+    for pid in pids {
+        for event in raw_data[pid].open_events.iter() {
+            if let Some(OtherFields::Open {
+                file,
+                resolved: Some(resolved),
+                ..
+            }) = event.other
+            {
+                if file.starts_with(b"/proc/") && file != resolved {
+                    opened_as.insert(resolved, file);
+                }
+            }
+        }
+    }
+
+    let mut paths: HashMap<&[u8], PseudoFile> = HashMap::default();
+
+    for file in files {
+        if FileKind::classify(file.file) != FileKind::Pseudo {
+            continue;
+        }
+
+        let entry = path_entry(&mut paths, file.file, file.pid);
+        entry.opens += 1;
+        entry.duration += file.duration;
+        if file.error.is_some() {
+            entry.errors += 1;
+        }
+        if let Some(time) = parse_timestamp_us(file.time) {
+            entry.first_open = Some(entry.first_open.map_or(time, |t| t.min(time)));
+            entry.last_open = Some(entry.last_open.map_or(time, |t| t.max(time)));
+        }
+    }
+
+    for io_call in io_data::io_calls(pids, raw_data).values().flatten() {
+        let path = opened_as.get(io_call.fd).copied().unwrap_or(io_call.fd);
+        if FileKind::classify(path) != FileKind::Pseudo {
+            continue;
+        }
+
+        let entry = path_entry(&mut paths, path, io_call.pid);
+        entry.io_calls += 1;
+        entry.duration += io_call.duration * 1000.0;
+        match io_call.error {
+            Some(_) => entry.errors += 1,
+            None => entry.bytes += i64::from(io_call.bytes.max(0)),
+        }
+    }
+
+    let mut paths: Vec<_> = paths.into_values().collect();
+    paths.sort_by(|x, y| {
+        (y.duration)
+            .total_cmp(&x.duration)
+            .then_with(|| x.path.cmp(y.path))
+    });
+
+    paths
+}
+
+fn path_entry<'a, 'b>(
+    paths: &'b mut HashMap<&'a [u8], PseudoFile<'a>>,
+    path: &'a [u8],
+    pid: Pid,
+) -> &'b mut PseudoFile<'a> {
+    let entry = paths.entry(path).or_insert_with(|| PseudoFile {
+        path,
+        ..PseudoFile::default()
+    });
+    entry.pids.insert(pid);
+    entry.self_inspection |= is_self_path(path, pid);
+    entry
+}
+
+// '/proc/self/...', '/proc/thread-self/...', or the PID's own '/proc/<pid>/...'
+fn is_self_path(path: &[u8], pid: Pid) -> bool {
+    let rest = match path.strip_prefix(b"/proc/") {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let dir = rest.split_str("/").next().unwrap_or_default();
+
+    dir == b"self" || dir == b"thread-self" || dir == pid.to_string().as_bytes()
+}
+
+pub fn print_pseudo_files(
+    files: &[&FileData],
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let paths = pseudo_files(files, pids, raw_data);
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    output::print_heading("\n/proc and /sys Accesses\n")?;
+    let mut table = Table::new(vec![
+        Column::new("opens", 7, Align::Right, 0),
+        Column::new("reads/writes", 12, Align::Right, 2),
+        Column::new("bytes", 10, Align::Right, 4),
+        Column::new("dur (ms)", 10, Align::Right, 0),
+        Column::new("errors", 6, Align::Right, 3),
+        Column::new("pids", 5, Align::Right, 5),
+        Column::new("opens/s", 8, Align::Right, 1),
+        Column::new("flag", 9, Align::Left, 1),
+        Column::new("path", 4, Align::Left, 0),
+    ]);
+
+    for path in paths.iter() {
+        table.add_row(vec![
+            path.opens.to_string(),
+            path.io_calls.to_string(),
+            path.bytes.to_string(),
+            format!("{:.3}", path.duration),
+            path.errors.to_string(),
+            path.pids.len().to_string(),
+            path.opens_per_sec()
+                .map_or_else(|| String::from("-"), |rate| format!("{:.2}", rate)),
+            if path.is_self_poll() { "self-poll" } else { "" }.to_string(),
+            path.path.to_str_lossy().to_string(),
+        ]);
+    }
+    table.print(width)?;
+
+    if paths.iter().any(PseudoFile::is_self_poll) {
+        output::print_note(&format!(
+            "\n  'self-poll' paths are a PID's own /proc entries opened {} or more times, the result could be kept between uses\n",
+            SELF_POLL_OPENS
+        ))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_data::{files_opened, SortFilesBy};
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn pseudo_files_counts_self_polling() {
+        let mut input = Vec::new();
+        for sec in 0..10 {
+            input.extend_from_slice(
+                format!(
+                    r##"823   10:00:0{sec}.000000 open("/proc/self/status", O_RDONLY|O_CLOEXEC) = 44</proc/823/status> <0.000020>
+823   10:00:0{sec}.000100 read(44</proc/823/status>, "Name:\tbundle\n", 8192) = 13 <0.000030>
+823   10:00:0{sec}.000200 read(44</proc/823/status>, "", 8179) = 0 <0.000010>
+"##,
+                    sec = sec
+                )
+                .as_bytes(),
+            );
+        }
+        input.extend_from_slice(
+            br##"495   10:00:05.000000 open("/sys/fs/cgroup/memory.max", O_RDONLY) = -1 ENOENT (No such file or directory) <0.000050>
+495   10:00:05.100000 open("/etc/hosts", O_RDONLY) = 3</etc/hosts> <0.000050>"##,
+        );
+
+        let pid_data_map = build_syscall_data(&input);
+        let pids = [495, 823];
+        let opened = files_opened(&pids, &pid_data_map, SortFilesBy::Time);
+        let files: Vec<_> = opened.values().flatten().collect();
+        let paths = pseudo_files(&files, &pids, &pid_data_map);
+
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].path, b"/proc/self/status");
+        assert_eq!(paths[0].opens, 10);
+        assert_eq!(paths[0].io_calls, 20);
+        assert_eq!(paths[0].bytes, 130);
+        assert!((paths[0].duration - 0.6).abs() < 1e-4);
+        assert!((paths[0].opens_per_sec().unwrap() - 1.0).abs() < 1e-9);
+        assert!(paths[0].is_self_poll());
+
+        assert_eq!(paths[1].path, b"/sys/fs/cgroup/memory.max");
+        assert_eq!(paths[1].errors, 1);
+        assert_eq!(paths[1].opens_per_sec(), None);
+        assert!(!paths[1].is_self_poll());
+    }
+}
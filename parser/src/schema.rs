@@ -0,0 +1,155 @@
+use crate::count_summary::CountSummaryReport;
+use crate::error_rate::ErrorRateReport;
+use crate::events::EventJson;
+use crate::json::{self, Report};
+use crate::pid_tree::TreeReport;
+use crate::session_summary::{
+    DirectoriesReport, ExecListReport, FilesReport, IoReport, OpenJson, PidDetailsReport,
+    PidListReport, SharedFileJson, SummaryReport, UserSummaryReport,
+};
+use crate::throughput::ThroughputReport;
+
+use schemars::JsonSchema;
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+/// The reports written as JSON with '--format json', plus 'events'
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JsonReport {
+    Directories,
+    ErrorRate,
+    Events,
+    Exec,
+    Files,
+    Io,
+    ListPids,
+    Pid,
+    Summary,
+    Throughput,
+    Tree,
+}
+
+// Only the schemas of the reports below are used, each variant is one of the
+// objects the subcommand can write
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+enum SummarySchema {
+    Pids(Report<'static, SummaryReport<'static>>),
+    Users(Report<'static, UserSummaryReport>),
+    Counts(Report<'static, CountSummaryReport<'static>>),
+}
+
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+enum FilesSchema {
+    Opens(Report<'static, FilesReport<OpenJson<'static>>>),
+    Shared(Report<'static, FilesReport<SharedFileJson<'static>>>),
+}
+
+impl FromStr for JsonReport {
+    type Err = ParseReportError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "directories" => Ok(JsonReport::Directories),
+            "error-rate" => Ok(JsonReport::ErrorRate),
+            "events" => Ok(JsonReport::Events),
+            "exec" => Ok(JsonReport::Exec),
+            "files" => Ok(JsonReport::Files),
+            "io" => Ok(JsonReport::Io),
+            "list-pids" => Ok(JsonReport::ListPids),
+            "pid" => Ok(JsonReport::Pid),
+            "summary" => Ok(JsonReport::Summary),
+            "throughput" => Ok(JsonReport::Throughput),
+            "tree" => Ok(JsonReport::Tree),
+            _ => Err(ParseReportError),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ParseReportError;
+
+impl fmt::Display for ParseReportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No JSON report with that name")
+    }
+}
+
+impl error::Error for ParseReportError {}
+
+impl JsonReport {
+    /// The subcommand writing the report
+    pub fn name(self) -> &'static str {
+        match self {
+            JsonReport::Directories => "directories",
+            JsonReport::ErrorRate => "error-rate",
+            JsonReport::Events => "events",
+            JsonReport::Exec => "exec",
+            JsonReport::Files => "files",
+            JsonReport::Io => "io",
+            JsonReport::ListPids => "list-pids",
+            JsonReport::Pid => "pid",
+            JsonReport::Summary => "summary",
+            JsonReport::Throughput => "throughput",
+            JsonReport::Tree => "tree",
+        }
+    }
+
+    /// The JSON Schema (draft 2020-12) of the report, pretty printed. The
+    /// schema of 'events' is of each line it writes.
+    pub fn schema(self) -> String {
+        let name = self.name();
+        let schema = match self {
+            JsonReport::Directories => json::schema::<Report<DirectoriesReport>>(name),
+            JsonReport::ErrorRate => json::schema::<Report<ErrorRateReport>>(name),
+            JsonReport::Events => json::schema::<EventJson>(name),
+            JsonReport::Exec => json::schema::<Report<ExecListReport>>(name),
+            JsonReport::Files => json::schema::<FilesSchema>(name),
+            JsonReport::Io => json::schema::<Report<IoReport>>(name),
+            JsonReport::ListPids => json::schema::<Report<PidListReport>>(name),
+            JsonReport::Pid => json::schema::<Report<PidDetailsReport>>(name),
+            JsonReport::Summary => json::schema::<SummarySchema>(name),
+            JsonReport::Throughput => json::schema::<Report<ThroughputReport>>(name),
+            JsonReport::Tree => json::schema::<Report<TreeReport>>(name),
+        };
+        serde_json::to_string_pretty(&schema).expect("Schema is always valid JSON")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn schema_has_metadata_version() {
+        let schema: Value = serde_json::from_str(&JsonReport::Tree.schema()).unwrap();
+        assert_eq!(schema["title"], "strace-parser tree report, version 1");
+
+        let metadata = &schema["properties"]["metadata"];
+        let metadata = match metadata["$ref"].as_str() {
+            Some(path) => &schema["$defs"][path.trim_start_matches("#/$defs/")],
+            None => metadata,
+        };
+        assert_eq!(
+            metadata["properties"]["version"]["const"],
+            json::SCHEMA_VERSION
+        );
+        assert_eq!(metadata["required"], serde_json::json!(["version"]));
+    }
+
+    #[test]
+    fn summary_schema_has_each_report() {
+        let schema: Value = serde_json::from_str(&JsonReport::Summary.schema()).unwrap();
+        let reports = schema["anyOf"].as_array().unwrap();
+        assert_eq!(reports.len(), 3);
+        for (report, field) in reports.iter().zip(&["pids", "users", "syscalls"]) {
+            assert!(report["properties"].get(*field).is_some(), "{}", report);
+            assert!(report["properties"].get("metadata").is_some(), "{}", report);
+        }
+    }
+}
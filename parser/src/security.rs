@@ -0,0 +1,348 @@
+use crate::connections;
+use crate::net::NOT_READY;
+use crate::output;
+use crate::parser::{self, CallStatus, LineData, OtherFields, RawData};
+use crate::path_calls;
+use crate::table::{Align, Column, Table};
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, prelude::*, Error};
+use std::net::{IpAddr, SocketAddr};
+
+// Trace lines printed under each finding with '--lines'
+const MAX_LINES: usize = 10;
+
+// Files holding credentials or keys, matched exactly
+const SENSITIVE_FILES: &[&[u8]] = &[
+    b"/etc/gshadow",
+    b"/etc/master.passwd",
+    b"/etc/shadow",
+    b"/etc/shadow-",
+    b"/etc/sudoers",
+    b"/proc/kcore",
+];
+
+// Directories holding credentials or keys, matched anywhere in the path so
+// every user's home is covered
+const SENSITIVE_DIRS: &[&[u8]] = &[
+    b"/.aws/",
+    b"/.gnupg/",
+    b"/.ssh/",
+    b"/etc/ssl/private/",
+    b"/etc/sudoers.d/",
+];
+
+// World-writable directories binaries are dropped into
+const TMP_DIRS: &[&[u8]] = &[b"/dev/shm/", b"/tmp/", b"/var/tmp/"];
+
+/// The kinds of activity worth a look when investigating an incident
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FindingKind {
+    /// A call on a file holding credentials or keys, or another process's memory
+    SensitivePath,
+    /// 'execve' of a binary in a world-writable temporary directory
+    TmpExec,
+    /// 'connect' to an address outside the private, loopback, and link-local ranges
+    PublicConnect,
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FindingKind::SensitivePath => "sensitive path",
+            FindingKind::TmpExec => "exec from tmp",
+            FindingKind::PublicConnect => "public connect",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The calls one PID made that were flagged for the same target
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding<'a> {
+    pub kind: FindingKind,
+    pub pid: Pid,
+    /// The path, binary, or 'ip:port' flagged
+    pub target: String,
+    pub first_time: &'a [u8],
+    pub calls: usize,
+    pub failed: usize,
+    pub syscalls: BTreeMap<&'a [u8], usize>,
+    /// The trace lines of the calls, resumed lines included
+    pub lines: Vec<&'a [u8]>,
+}
+
+/// Reads of sensitive files, 'execve' from temporary directories, and
+/// connects to public addresses made by `pids`, ordered by kind and then by
+/// when each was first seen
+pub fn findings<'a>(buffer: &'a [u8], pids: &[Pid]) -> Vec<Finding<'a>> {
+    let pids: HashSet<Pid> = pids.iter().copied().collect();
+
+    let mut calls: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(|line| match parser::parse_line(line)? {
+            LineData::Syscall(raw_data) if pids.contains(&raw_data.pid) => Some((line, raw_data)),
+            _ => None,
+        })
+        .collect();
+    calls.par_sort_by(|(_, x), (_, y)| x.time.cmp(y.time));
+
+    let mut findings: Vec<Finding> = Vec::new();
+    let mut found: HashMap<(FindingKind, Pid, String), usize> = HashMap::default();
+    // The finding for each PID's unfinished call, whose result is on the resumed line
+    let mut started: HashMap<Pid, usize> = HashMap::default();
+
+    for (line, raw_data) in calls {
+        let idx = match raw_data.call_status {
+            CallStatus::Resumed => match started.remove(&raw_data.pid) {
+                Some(idx) => idx,
+                None => continue,
+            },
+            _ => {
+                let (kind, target) = match flag(line, &raw_data) {
+                    Some(flagged) => flagged,
+                    None => continue,
+                };
+                let idx = *found
+                    .entry((kind, raw_data.pid, target.clone()))
+                    .or_insert_with(|| {
+                        findings.push(Finding {
+                            kind,
+                            pid: raw_data.pid,
+                            target,
+                            first_time: raw_data.time,
+                            calls: 0,
+                            failed: 0,
+                            syscalls: BTreeMap::new(),
+                            lines: Vec::new(),
+                        });
+                        findings.len() - 1
+                    });
+
+                let finding = &mut findings[idx];
+                finding.calls += 1;
+                *finding.syscalls.entry(raw_data.syscall).or_default() += 1;
+                if raw_data.call_status == CallStatus::Started {
+                    started.insert(raw_data.pid, idx);
+                }
+                idx
+            }
+        };
+
+        let finding = &mut findings[idx];
+        finding.lines.push(line);
+        // A non-blocking connect's EINPROGRESS is how it starts, not a failure
+        if raw_data.error.is_some_and(|e| !NOT_READY.contains(&e)) {
+            finding.failed += 1;
+        }
+    }
+
+    findings.sort_by(|x, y| {
+        x.kind
+            .cmp(&y.kind)
+            .then_with(|| x.first_time.cmp(y.first_time))
+            .then_with(|| x.pid.cmp(&y.pid))
+    });
+
+    findings
+}
+
+// The kind of finding a started or complete call is, and its target
+fn flag(line: &[u8], raw_data: &RawData) -> Option<(FindingKind, String)> {
+    match raw_data.syscall {
+        // 477   00:09:56.000000 execve("/tmp/x", ["/tmp/x"], 0x7ffc6d3c2df0 /* 20 vars */) = 0 <0.000300>
+        //                              ^^^^^^
+        b"execve" | b"execveat" => {
+            let args = parser::line_args(line);
+            let arg = match raw_data.syscall {
+                b"execve" => args.first(),
+                _ => args.get(1),
+            };
+            let binary = arg?.trim_with(|c| c == '"');
+            TMP_DIRS
+                .iter()
+                .any(|dir| binary.starts_with(dir))
+                .then(|| (FindingKind::TmpExec, binary.to_str_lossy().to_string()))
+        }
+        b"connect" => {
+            let addr = match raw_data.other {
+                Some(OtherFields::Connect {
+                    addr: Some(addr), ..
+                }) => addr,
+                _ => return None,
+            };
+            let endpoint = connections::endpoint(addr);
+            let socket_addr: SocketAddr = endpoint.parse().ok()?;
+            is_public(socket_addr.ip()).then_some((FindingKind::PublicConnect, endpoint))
+        }
+        _ => {
+            let path = path_calls::call_path(line, raw_data)?;
+            is_sensitive(path)
+                .then(|| (FindingKind::SensitivePath, path.to_str_lossy().to_string()))
+        }
+    }
+}
+
+/// Whether `path` holds credentials or keys, such as '/etc/shadow' or a file
+/// in '~/.ssh', or is the memory of a process in '/proc'
+pub fn is_sensitive(path: &[u8]) -> bool {
+    if SENSITIVE_FILES.contains(&path) || SENSITIVE_DIRS.iter().any(|dir| path.contains_str(dir)) {
+        return true;
+    }
+
+    // /proc/1234/mem, /proc/self/mem, and /proc/1234/task/1240/mem
+    path.starts_with(b"/proc/") && path.ends_with(b"/mem")
+}
+
+/// Whether `ip` is outside the RFC 1918 private ranges and the loopback,
+/// link-local, and unspecified addresses, with the IPv6 equivalents
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified())
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+            let first = ip.segments()[0];
+            // Unique local fc00::/7 and link-local fe80::/10
+            let local = (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80;
+            !(local || ip.is_loopback() || ip.is_unspecified())
+        }
+    }
+}
+
+/// Prints the findings for `pids`, followed by the trace lines behind each
+/// when `lines` is set
+pub fn print_security(
+    buffer: &[u8],
+    pids: &[Pid],
+    lines: bool,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let findings = findings(buffer, pids);
+
+    output::print_heading("\nSecurity Findings\n-----------\n")?;
+    if findings.is_empty() {
+        output::print_note(
+            "  No reads of sensitive files, execs from temporary directories, or connects to public addresses found\n",
+        )?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("finding", 14, Align::Left, 0),
+        Column::new("pid", 7, Align::Right, 0),
+        Column::new("first seen", 15, Align::Left, 2),
+        Column::new("calls", 6, Align::Right, 1),
+        Column::new("failed", 6, Align::Right, 1),
+        Column::new("syscalls", 20, Align::Left, 3),
+        Column::new("target", 6, Align::Left, 0),
+    ]);
+
+    for finding in &findings {
+        let syscalls: Vec<_> = finding
+            .syscalls
+            .iter()
+            .map(|(syscall, ct)| match ct {
+                1 => syscall.to_str_lossy().to_string(),
+                _ => format!("{} x{}", syscall.to_str_lossy(), ct),
+            })
+            .collect();
+
+        table.add_row(vec![
+            finding.kind.to_string(),
+            output::pid_label(finding.pid),
+            output::timestamp(finding.first_time),
+            finding.calls.to_string(),
+            finding.failed.to_string(),
+            syscalls.join(", "),
+            finding.target.clone(),
+        ]);
+    }
+    table.print(width)?;
+
+    if !lines {
+        return Ok(());
+    }
+
+    output::print_heading("\nSupporting Events\n-----------\n")?;
+    let mut out = io::stdout();
+    for (idx, finding) in findings.iter().enumerate() {
+        if idx > 0 {
+            writeln!(out)?;
+        }
+        writeln!(
+            out,
+            "  {} {} (PID {})",
+            finding.kind,
+            finding.target,
+            output::pid_label(finding.pid)
+        )?;
+        for line in finding.lines.iter().take(MAX_LINES) {
+            writeln!(out, "    {}", line.to_str_lossy())?;
+        }
+        if finding.lines.len() > MAX_LINES {
+            writeln!(out, "    ... {} more", finding.lines.len() - MAX_LINES)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn security_flags_paths_execs_and_connects() {
+        let input = br##"477   00:09:56.000000 openat(AT_FDCWD, "/etc/shadow", O_RDONLY|O_CLOEXEC) = 3</etc/shadow> <0.000010>
+477   00:09:56.100000 read(3</etc/shadow>, "root:*:17000:0:99999:7:::\n", 4096) = 26 <0.000010>
+477   00:09:56.200000 openat(AT_FDCWD, "/etc/passwd", O_RDONLY|O_CLOEXEC) = 4</etc/passwd> <0.000010>
+478   00:09:56.300000 openat(AT_FDCWD, "/home/app/.ssh/id_rsa", O_RDONLY) = -1 EACCES (Permission denied) <0.000010>
+478   00:09:56.400000 open("/proc/1/mem", O_RDONLY <unfinished ...>
+478   00:09:56.500000 <... open resumed>) = -1 EPERM (Operation not permitted) <0.000010>
+479   00:09:56.600000 execve("/tmp/.x/miner", ["/tmp/.x/miner"], 0x7ffc6d3c2df0 /* 20 vars */) = 0 <0.000300>
+479   00:09:56.700000 execve("/usr/bin/tmp/ls", ["ls"], 0x7ffc6d3c2df0 /* 20 vars */) = 0 <0.000300>
+479   00:09:56.800000 connect(5<socket:[300]>, {sa_family=AF_INET, sin_port=htons(4444), sin_addr=inet_addr("203.0.113.9")}, 16) = 0 <0.000100>
+479   00:09:56.900000 connect(6<socket:[301]>, {sa_family=AF_INET, sin_port=htons(5432), sin_addr=inet_addr("10.0.0.2")}, 16) = 0 <0.000100>
+479   00:09:56.850000 connect(8<socket:[303]>, {sa_family=AF_INET, sin_port=htons(4444), sin_addr=inet_addr("203.0.113.9")}, 16) = -1 EINPROGRESS (Operation now in progress) <0.000050>
+479   00:09:57.000000 connect(7<socket:[302]>, {sa_family=AF_UNIX, sun_path="/var/run/nscd/socket"}, 110) = 0 <0.000100>
+480   00:09:57.100000 stat("/etc/shadow", {st_mode=S_IFREG|0640, st_size=1200, ...}) = 0 <0.000010>"##;
+        let found = findings(input, &[477, 478, 479]);
+
+        let summary: Vec<_> = found
+            .iter()
+            .map(|f| (f.kind, f.pid, f.target.as_str(), f.calls, f.failed))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (FindingKind::SensitivePath, 477, "/etc/shadow", 2, 0),
+                (
+                    FindingKind::SensitivePath,
+                    478,
+                    "/home/app/.ssh/id_rsa",
+                    1,
+                    1
+                ),
+                (FindingKind::SensitivePath, 478, "/proc/1/mem", 1, 1),
+                (FindingKind::TmpExec, 479, "/tmp/.x/miner", 1, 0),
+                (FindingKind::PublicConnect, 479, "203.0.113.9:4444", 2, 0),
+            ]
+        );
+        assert_eq!(found[2].lines.len(), 2);
+        assert_eq!(found[0].syscalls.len(), 2);
+
+        assert!(!is_public("172.20.1.1".parse().unwrap()));
+        assert!(is_public("172.32.1.1".parse().unwrap()));
+        assert!(!is_public("fd12::1".parse().unwrap()));
+        assert!(!is_public("::ffff:192.168.1.1".parse().unwrap()));
+        assert!(is_public("2001:db8::1".parse().unwrap()));
+    }
+}
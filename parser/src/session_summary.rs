@@ -1,16 +1,46 @@
-use crate::exec::Execs;
-use crate::pid_summary::PrintAmt;
+use crate::columns::PidColumn;
+use crate::directories::DirectoryData;
+use crate::events::CallRun;
+use crate::exec;
+use crate::file_data::{FileData, SharedFile};
+use crate::file_kind::{self, FileKind};
+use crate::health::{Health, Signals};
+use crate::io_data::IoCall;
+use crate::json::{rounded, Lossy, Time};
+use crate::marks::Mark;
+use crate::metrics::PidMetric;
+use crate::otlp::{self, AttrValue, Span};
+use crate::outliers::{self, Baselines};
+use crate::output;
+use crate::parser::ExitType;
+use crate::path_aliases::PathAliases;
+use crate::peers::{self, PeerOutlier};
+use crate::pid_summary::{PidSummaryJson, PrintAmt};
 use crate::syscall_data::PidData;
-use crate::syscall_stats::SyscallStats;
-use crate::{directories, file_data, file_data::SortFilesBy, io_data, pid_tree};
+use crate::syscall_groups::{GroupMode, SyscallGroups};
+use crate::syscall_stats::{SyscallStats, SyscallStatsJson};
+use crate::table::{Align, Column, Table};
+use crate::tracer;
+use crate::users::{self, UserSummary};
+use crate::{
+    directories, file_data, file_data::SortFilesBy, io_data, json, open_fds, pid_tree,
+    pseudo_files, time,
+};
 use crate::{HashMap, HashSet, Pid, PidSummary, SortBy, SortEventsBy};
 
 use bstr::ByteSlice;
 use chrono::Duration;
 use petgraph::prelude::*;
 use rayon::prelude::*;
-use std::collections::BTreeSet;
-use std::io::{prelude::*, stdout, Error};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{prelude::*, stdout, BufWriter, Error};
+use std::path::{Path, PathBuf};
 
 static PRINT_COUNT: usize = 10;
 
@@ -20,6 +50,16 @@ pub struct SessionSummary<'a> {
     all_time: f32,
     all_active_time: f32,
     all_user_time: f32,
+    // Terminal width that tables are fit to, if known
+    width: Option<usize>,
+    // Calls slower than their syscall's median by this many MADs are flagged
+    outlier_mads: f32,
+    // Files opened by an alias are shown and totaled under the path it refers to
+    path_aliases: PathAliases,
+    // Extra columns of 'summary' and 'list-pids', each metric's name and value by PID
+    metrics: Vec<(String, HashMap<Pid, Option<f64>>)>,
+    // Named time ranges that 'summary' and 'io' compare the rest of the trace to
+    marks: Vec<Mark>,
 }
 
 impl<'a> SessionSummary<'a> {
@@ -27,7 +67,10 @@ impl<'a> SessionSummary<'a> {
         session_stats: &HashMap<Pid, Vec<SyscallStats<'a>>>,
         pid_data: &'a HashMap<Pid, PidData<'a>>,
     ) -> SessionSummary<'a> {
-        let mut summary = SessionSummary::default();
+        let mut summary = SessionSummary {
+            outlier_mads: outliers::DEFAULT_MAD_MULTIPLIER,
+            ..SessionSummary::default()
+        };
 
         for (pid, syscall_stats) in session_stats {
             summary.pid_summaries.insert(
@@ -58,6 +101,9 @@ impl<'a> SessionSummary<'a> {
 
         summary.populate_children();
         summary.populate_threads();
+        summary.populate_uids(pid_data);
+        summary.populate_namespaces(pid_data);
+        summary.populate_clone_times(pid_data);
 
         summary
     }
@@ -91,8 +137,8 @@ impl<'a> SessionSummary<'a> {
                 let (mut existing, mut forked): (Vec<_>, Vec<_>) = sorted_summaries
                     .into_iter()
                     .partition(|(_, s)| s.parent_pid.is_none());
-                existing.sort_by(|(pid_x, _), (pid_y, _)| pid_x.cmp(&pid_y));
-                forked.sort_by(|(_, x), (_, y)| (x.start_time).cmp(&y.start_time));
+                existing.sort_by_key(|(pid_x, _)| *pid_x);
+                forked.sort_by(|(_, x), (_, y)| (x.start_time).cmp(y.start_time));
                 existing.extend(forked);
 
                 sorted_summaries = existing
@@ -261,7 +307,7 @@ impl<'a> SessionSummary<'a> {
 
             while let Some(relative) = dfs.next(&addr_graph) {
                 for &pid in pids.iter() {
-                    let entry = thread_map.entry(pid).or_insert_with(HashSet::new);
+                    let entry = thread_map.entry(pid).or_default();
                     entry.extend(pids.iter());
 
                     if let Some(more_pids) = addr_map.get(&relative) {
@@ -288,11 +334,103 @@ impl<'a> SessionSummary<'a> {
         }
     }
 
+    fn populate_uids(&mut self, pid_data: &HashMap<Pid, PidData<'a>>) {
+        let events = users::uid_events(pid_data);
+        if events.is_empty() {
+            return;
+        }
+
+        let euids = users::effective_uids(&self.pid_summaries, &events);
+        for (pid, pid_summary) in self.pid_summaries.iter_mut() {
+            pid_summary.euid = euids.get(pid).copied();
+        }
+    }
+
+    fn populate_namespaces(&mut self, pid_data: &HashMap<Pid, PidData<'a>>) {
+        for (child_pid, namespaces) in pid_data.values().flat_map(|d| &d.child_namespaces) {
+            if let Some(child) = self.pid_summaries.get_mut(child_pid) {
+                child.namespaces |= *namespaces;
+            }
+        }
+    }
+
+    fn populate_clone_times(&mut self, pid_data: &HashMap<Pid, PidData<'a>>) {
+        for (child_pid, time) in pid_data.values().flat_map(|d| &d.child_clone_times) {
+            if let Some(child) = self.pid_summaries.get_mut(child_pid) {
+                child.clone_time = Some(time);
+            }
+        }
+    }
+
+    pub fn group_syscalls(&mut self, groups: &SyscallGroups<'a>, mode: GroupMode) {
+        for pid_summary in self.pid_summaries.values_mut() {
+            pid_summary.grouped_stats = Some(groups.group_stats(&pid_summary.syscall_stats, mode));
+        }
+    }
+
+    pub fn width(&self) -> Option<usize> {
+        self.width
+    }
+
+    pub fn set_width(&mut self, width: Option<usize>) {
+        self.width = width;
+    }
+
+    pub fn set_outlier_mads(&mut self, outlier_mads: f32) {
+        self.outlier_mads = outlier_mads;
+    }
+
+    pub fn set_path_aliases(&mut self, path_aliases: PathAliases) {
+        self.path_aliases = path_aliases;
+    }
+
+    pub fn marks(&self) -> &[Mark] {
+        &self.marks
+    }
+
+    pub fn set_marks(&mut self, marks: Vec<Mark>) {
+        self.marks = marks;
+    }
+
+    /// Computes `metric` for every PID, to be shown as an extra column of
+    /// 'summary' and 'list-pids --columns'
+    pub fn add_metric(&mut self, metric: &dyn PidMetric, pid_data: &HashMap<Pid, PidData<'a>>) {
+        let values = self
+            .pid_summaries
+            .keys()
+            .map(|pid| (*pid, pid_data.get(pid).and_then(|data| metric.value(data))))
+            .collect();
+        self.metrics.push((metric.name().to_string(), values));
+    }
+
+    // Adds a column per metric, dropped to fit the terminal before the others
+    fn add_metric_columns(&self, columns: &mut Vec<Column>) {
+        let priority = columns.iter().map(|c| c.priority).max().unwrap_or_default();
+        for (idx, (name, _)) in self.metrics.iter().enumerate() {
+            columns.push(Column::new(
+                name.clone(),
+                10,
+                Align::Right,
+                priority + idx + 1,
+            ));
+        }
+    }
+
+    fn add_metric_cells(&self, pid: Pid, row: &mut Vec<String>) {
+        for (_, values) in &self.metrics {
+            let cell = match values.get(&pid).copied().flatten() {
+                Some(value) => format!("{:.3}", value),
+                None => "-".to_string(),
+            };
+            row.push(cell);
+        }
+    }
+
     pub fn related_pids(&self, pids: &[Pid]) -> Vec<Pid> {
         let mut related_pids = BTreeSet::new();
 
         for pid in pids {
-            if let Some(pid_summary) = self.pid_summaries.get(&pid) {
+            if let Some(pid_summary) = self.pid_summaries.get(pid) {
                 related_pids.insert(*pid);
                 if let Some(parent) = pid_summary.parent_pid {
                     related_pids.insert(parent);
@@ -323,15 +461,52 @@ impl<'a> SessionSummary<'a> {
         let (valid_pids, invalid_pids): (BTreeSet<Pid>, BTreeSet<Pid>) = pids
             .iter()
             .copied()
-            .partition(|p| self.pid_summaries.get(p).is_some());
+            .partition(|p| self.pid_summaries.contains_key(p));
 
         for pid in invalid_pids {
-            writeln!(stdout(), "No data found for PID {}", pid)?;
+            output::print_note(&format!("No data found for PID {}", output::pid_label(pid)))?;
         }
 
         Ok(valid_pids.into_iter().collect::<Vec<_>>())
     }
 
+    /// Time during which at least one PID in the session was traced
+    pub fn traced_time(&self) -> Option<Duration> {
+        time::traced_time(
+            self.pid_summaries
+                .values()
+                .map(|summary| (summary.start_time, summary.end_time)),
+        )
+    }
+
+    /// Scores the whole session on its error rate, time spent waiting, fd
+    /// pressure, and process churn
+    pub fn health(&self, raw_data: &HashMap<Pid, PidData<'a>>) -> Health {
+        let mut signals = Signals::default();
+        for summary in self.pid_summaries.values() {
+            signals.calls += i64::from(summary.syscall_count);
+            signals.errors += i64::from(summary.error_count());
+            signals.wait_time += f64::from(summary.system_wait_time);
+            signals.active_time += f64::from(summary.system_active_time);
+            signals.processes += summary.child_pids.difference(&summary.threads).count();
+        }
+
+        let pids: Vec<_> = self.pid_summaries.keys().copied().collect();
+        signals.fd_peak = open_fds::open_fds(&pids, raw_data)
+            .pids
+            .iter()
+            .max_by_key(|fds| (fds.peak, Reverse(fds.pid)))
+            .map(|fds| (fds.pid, fds.peak));
+
+        signals.traced_secs = self.traced_time().map_or(0.0, |t| {
+            t.num_microseconds().unwrap_or_default() as f64 / 1_000_000.0
+        });
+
+        let mut health = Health::from_signals(&signals);
+        health.caveats = tracer::interference(raw_data).messages(&pids);
+        health
+    }
+
     pub fn print_summary(
         &self,
         elapsed_time: Option<Duration>,
@@ -342,44 +517,235 @@ impl<'a> SessionSummary<'a> {
             count = self.pid_summaries.len()
         }
 
-        writeln!(
-            stdout(),
+        output::print_heading(&format!(
             "\nTop {} PIDs by {}\n-----------\n",
-            count,
-            sort_by
-        )?;
-
-        writeln!(
-            stdout(),
-            "  {: <7}    {: >10}    {: >10}    {: >10}    {: >10}    {: >9}    {: >9}    {: >9}",
-            "pid",
-            "actv (ms)",
-            "wait (ms)",
-            "user (ms)",
-            "total (ms)",
-            "% of actv",
-            "syscalls",
-            "children"
-        )?;
-        writeln!(
-            stdout(),
-            "  -------    ----------    ----------    ----------    ----------    ---------    ---------    ---------"
-        )?;
+            count, sort_by
+        ))?;
+
+        let mut columns = vec![
+            Column::new("pid", 7, Align::Left, 0),
+            Column::new("actv (ms)", 10, Align::Right, 0),
+            Column::new("wait (ms)", 10, Align::Right, 2),
+            Column::new("user (ms)", 10, Align::Right, 3),
+            Column::new("total (ms)", 10, Align::Right, 1),
+            Column::new("% of actv", 9, Align::Right, 4),
+            Column::new("syscalls", 9, Align::Right, 5),
+            Column::new("children", 9, Align::Right, 6),
+            Column::new("elapsed (ms)", 12, Align::Right, 7),
+        ];
+        self.add_metric_columns(&mut columns);
+        let mut table = Table::new(columns);
 
         for (pid, pid_summary) in self.to_sorted(sort_by).iter().take(count) {
-            writeln!(
-                stdout(),
-                "  {: <7}    {: >10.3}    {: >10.3}    {: >10.3}    {: >10.3}    {: >8.2}%    {: >9}    {: >9}",
-                pid,
-                pid_summary.system_active_time,
-                pid_summary.system_wait_time,
-                pid_summary.user_time,
-                pid_summary.total_time,
-                pid_summary.system_active_time / self.all_active_time * 100.0,
-                pid_summary.syscall_count,
-                pid_summary.child_pids.len(),
+            let mut row = vec![
+                output::pid_label(*pid),
+                format!("{:.3}", pid_summary.system_active_time),
+                format!("{:.3}", pid_summary.system_wait_time),
+                format!("{:.3}", pid_summary.user_time),
+                format!("{:.3}", pid_summary.total_time),
+                format!(
+                    "{:.2}%",
+                    pid_summary.system_active_time / self.all_active_time * 100.0
+                ),
+                pid_summary.syscall_count.to_string(),
+                pid_summary.child_pids.len().to_string(),
+                format!("{:.3}", pid_summary.elapsed_time),
+            ];
+            self.add_metric_cells(*pid, &mut row);
+            table.add_row(row);
+        }
+        table.print(self.width)?;
+
+        self.print_totals(elapsed_time)
+    }
+
+    /// PIDs with metrics far above the rest of their worker pool, see
+    /// `peers::peer_outliers`
+    pub fn peer_outliers(&self) -> Vec<PeerOutlier> {
+        peers::peer_outliers(&self.pid_summaries, |pid| self.program_name(pid))
+    }
+
+    /// A span for each of `pids` from its first syscall to its last, as a
+    /// child of its parent's span when the parent is one of `pids`, and of
+    /// `parent_span_id` otherwise. See `otlp::syscall_spans` for `unix_us`.
+    pub fn pid_spans<F>(&self, pids: &[Pid], parent_span_id: Option<u64>, unix_us: F) -> Vec<Span>
+    where
+        F: Fn(&[u8]) -> Option<i64>,
+    {
+        let exported: HashSet<Pid> = pids.iter().copied().collect();
+        let mut spans = Vec::new();
+
+        for pid in pids {
+            let pid_summary = match self.pid_summaries.get(pid) {
+                Some(pid_summary) => pid_summary,
+                None => continue,
+            };
+            let (start_us, end_us) = match (
+                unix_us(pid_summary.start_time),
+                unix_us(pid_summary.end_time),
+            ) {
+                (Some(start), Some(end)) => (start, end),
+                _ => continue,
+            };
+
+            let mut attributes = vec![("process.pid", AttrValue::Int(i64::from(*pid)))];
+            if let Some(parent) = pid_summary.parent_pid {
+                attributes.push(("process.parent_pid", AttrValue::Int(i64::from(parent))));
+            }
+            if let Some(cmd) = pid_summary.execve.as_ref().and_then(|e| e.cmds().last()) {
+                attributes.push(("process.command_line", AttrValue::Str(cmd.clone())));
+            }
+            let error = match &pid_summary.exit {
+                Some(ExitType::Exit(code)) => {
+                    attributes.push(("process.exit_code", AttrValue::Int(i64::from(*code))));
+                    Some(format!("exited with {}", code)).filter(|_| *code != 0)
+                }
+                Some(ExitType::Signal(signal)) => {
+                    Some(format!("killed by {}", signal.to_str_lossy()))
+                }
+                None => None,
+            };
+
+            spans.push(Span {
+                span_id: otlp::pid_span_id(*pid),
+                parent_span_id: match pid_summary.parent_pid {
+                    Some(parent) if exported.contains(&parent) => Some(otlp::pid_span_id(parent)),
+                    _ => parent_span_id,
+                },
+                name: self.program_name(*pid).map_or_else(
+                    || format!("pid {}", pid),
+                    |program| format!("{} {}", program, pid),
+                ),
+                start_us,
+                end_us,
+                attributes,
+                error,
+            });
+        }
+
+        spans
+    }
+
+    pub fn print_peer_outliers(&self) -> Result<(), Error> {
+        let outliers = self.peer_outliers();
+        if outliers.is_empty() {
+            return Ok(());
+        }
+
+        output::print_heading("\nPIDs Unlike Their Peers\n-----------\n")?;
+        let mut table = Table::new(vec![
+            Column::new("pid", 7, Align::Left, 0),
+            Column::new("program", 10, Align::Left, 3),
+            Column::new("peers", 5, Align::Right, 4),
+            Column::new("metric", 20, Align::Left, 0),
+            Column::new("value", 10, Align::Right, 0),
+            Column::new("peer avg", 10, Align::Right, 1),
+            Column::new("vs peers", 8, Align::Right, 2),
+            Column::new("z-score", 7, Align::Right, 5),
+        ]);
+        for outlier in &outliers {
+            table.add_row(vec![
+                output::pid_label(outlier.pid),
+                outlier.program.clone(),
+                outlier.peers.to_string(),
+                outlier.metric.clone(),
+                format!("{:.3}", outlier.value),
+                format!("{:.3}", outlier.peer_mean),
+                outlier
+                    .ratio()
+                    .map_or_else(|| String::from("-"), |r| format!("{:.1}x", r)),
+                outlier
+                    .z_score
+                    .map_or_else(|| String::from("-"), |z| format!("{:.1}", z)),
+            ]);
+        }
+        table.print(self.width)?;
+
+        output::print_note(&format!(
+            "\n  Peers share a parent and program, flagged at {} standard deviations and twice the peers' average",
+            peers::Z_THRESHOLD
+        ))
+    }
+
+    pub fn user_summaries(&self) -> Vec<UserSummary> {
+        users::user_summaries(&self.pid_summaries)
+    }
+
+    fn sorted_user_summaries(&self, sort_by: SortBy) -> Vec<UserSummary> {
+        let mut user_summaries = self.user_summaries();
+        match sort_by {
+            SortBy::ActiveTime => user_summaries.sort_by(|x, y| {
+                (y.system_active_time)
+                    .partial_cmp(&x.system_active_time)
+                    .unwrap()
+            }),
+            SortBy::ChildPids => user_summaries.sort_by_key(|u| Reverse(u.pids)),
+            SortBy::SyscallCount => user_summaries.sort_by_key(|u| Reverse(u.syscall_count)),
+            SortBy::TotalTime => {
+                user_summaries.sort_by(|x, y| (y.total_time).partial_cmp(&x.total_time).unwrap())
+            }
+            SortBy::UserTime => {
+                user_summaries.sort_by(|x, y| (y.user_time).partial_cmp(&x.user_time).unwrap())
+            }
+            // Already in uid order
+            _ => {}
+        }
+
+        user_summaries
+    }
+
+    pub fn print_user_summary(
+        &self,
+        elapsed_time: Option<Duration>,
+        count: usize,
+        sort_by: SortBy,
+    ) -> Result<(), Error> {
+        let user_summaries = self.sorted_user_summaries(sort_by);
+        let count = count.min(user_summaries.len());
+        output::print_heading(&format!(
+            "\nTop {} Users by {}\n-----------\n",
+            count, sort_by
+        ))?;
+
+        let mut table = Table::new(vec![
+            Column::new("uid", 9, Align::Left, 0),
+            Column::new("pids", 7, Align::Right, 5),
+            Column::new("actv (ms)", 10, Align::Right, 0),
+            Column::new("wait (ms)", 10, Align::Right, 2),
+            Column::new("user (ms)", 10, Align::Right, 3),
+            Column::new("total (ms)", 10, Align::Right, 1),
+            Column::new("% of actv", 9, Align::Right, 4),
+            Column::new("syscalls", 9, Align::Right, 6),
+        ]);
+
+        for user in user_summaries.iter().take(count) {
+            table.add_row(vec![
+                user.euid
+                    .map_or_else(|| "unknown".to_string(), |uid| uid.to_string()),
+                user.pids.to_string(),
+                format!("{:.3}", user.system_active_time),
+                format!("{:.3}", user.system_wait_time),
+                format!("{:.3}", user.user_time),
+                format!("{:.3}", user.total_time),
+                format!(
+                    "{:.2}%",
+                    user.system_active_time / self.all_active_time * 100.0
+                ),
+                user.syscall_count.to_string(),
+            ]);
+        }
+        table.print(self.width)?;
+
+        if user_summaries.iter().all(|u| u.euid.is_none()) {
+            output::print_note(
+                "\n  No uids found, trace with '-e trace=%creds' or read '/proc/<pid>/status' to see them",
             )?;
         }
+
+        self.print_totals(elapsed_time)
+    }
+
+    fn print_totals(&self, elapsed_time: Option<Duration>) -> Result<(), Error> {
         writeln!(stdout(), "\nPIDs   {}", self.pid_summaries.len())?;
         if let Some(real_time) = elapsed_time {
             writeln!(
@@ -388,6 +754,14 @@ impl<'a> SessionSummary<'a> {
                 SessionSummary::format_duration(real_time.num_milliseconds()),
             )?;
         }
+        // Differs from the real time when no traced PID was running for a period
+        if let Some(traced) = self.traced_time() {
+            writeln!(
+                stdout(),
+                "traced {}",
+                SessionSummary::format_duration(traced.num_milliseconds()),
+            )?;
+        }
         writeln!(
             stdout(),
             "user   {}",
@@ -407,16 +781,15 @@ impl<'a> SessionSummary<'a> {
             count = self.pid_summaries.len()
         }
 
-        writeln!(
-            stdout(),
+        output::print_heading(&format!(
             "\nDetails of Top {} PIDs by {}\n-----------\n",
-            count,
-            sort_by
-        )?;
+            count, sort_by
+        ))?;
 
         for (pid, pid_summary) in self.to_sorted(sort_by).iter().take(count) {
-            writeln!(stdout(), "PID {}\n", pid)?;
-            writeln!(stdout(), "{}  ---------------", pid_summary)?;
+            writeln!(stdout(), "PID {}\n", output::pid_label(*pid))?;
+            pid_summary.write(&mut stdout(), self.width)?;
+            writeln!(stdout(), "  ---------------")?;
 
             match (&pid_summary.execve, pid_summary.exit) {
                 (Some(exec), Some(exit)) => {
@@ -446,7 +819,7 @@ impl<'a> SessionSummary<'a> {
                 }
             }
 
-            pid_summary.print_related_pids(PrintAmt::Some(PRINT_COUNT))?;
+            pid_summary.write_related_pids(&mut stdout(), PrintAmt::Some(PRINT_COUNT))?;
 
             writeln!(stdout(), "\n")?;
         }
@@ -454,297 +827,1261 @@ impl<'a> SessionSummary<'a> {
         Ok(())
     }
 
+    pub fn print_pid_table(
+        &self,
+        mut count: usize,
+        sort_by: SortBy,
+        columns: &[PidColumn],
+    ) -> Result<(), Error> {
+        if count > self.pid_summaries.len() {
+            count = self.pid_summaries.len()
+        }
+
+        output::print_heading(&format!(
+            "\nTop {} PIDs by {}\n-----------\n",
+            count, sort_by
+        ))?;
+
+        // Columns are dropped to fit the terminal in the reverse of the order they were requested
+        let mut table_columns = vec![Column::new("pid", 7, Align::Left, 0)];
+        for (idx, column) in columns.iter().enumerate() {
+            let align = match column {
+                PidColumn::Exec => Align::Left,
+                _ => Align::Right,
+            };
+            table_columns.push(Column::new(column.header(), column.width(), align, idx + 1));
+        }
+        self.add_metric_columns(&mut table_columns);
+        let mut table = Table::new(table_columns);
+
+        for (pid, pid_summary) in self.to_sorted(sort_by).iter().take(count) {
+            let mut row = vec![output::pid_label(*pid)];
+            for column in columns {
+                let cell = match column {
+                    PidColumn::Children => pid_summary.child_pids.len().to_string(),
+                    PidColumn::Threads => pid_summary.threads.len().to_string(),
+                    PidColumn::Exec => pid_summary
+                        .execve
+                        .as_ref()
+                        .and_then(|e| e.cmds().last())
+                        .map(|c| c.chars().take(column.width()).collect::<String>())
+                        .unwrap_or_else(|| "-".to_string()),
+                    PidColumn::FirstActivity => output::timestamp(pid_summary.start_time),
+                    PidColumn::LastActivity => output::timestamp(pid_summary.end_time),
+                    PidColumn::Elapsed => format!("{:.3}", pid_summary.elapsed_time),
+                    PidColumn::Errors => pid_summary.error_count().to_string(),
+                    PidColumn::Uid => pid_summary
+                        .euid
+                        .map_or_else(|| "-".to_string(), |uid| uid.to_string()),
+                };
+                row.push(cell);
+            }
+            self.add_metric_cells(*pid, &mut row);
+            table.add_row(row);
+        }
+        table.print(self.width)?;
+
+        writeln!(stdout())?;
+
+        Ok(())
+    }
+
     pub fn print_pid_details(
         &self,
         pids: &[Pid],
         raw_data: &HashMap<Pid, PidData<'a>>,
+        calls: &HashMap<Pid, Vec<CallRun>>,
     ) -> Result<(), Error> {
-        let file_times = file_data::files_opened(&pids, raw_data, SortFilesBy::Duration);
+        let file_times = file_data::files_opened(pids, raw_data, SortFilesBy::Duration);
 
         for pid in pids {
-            if let Some(pid_summary) = self.pid_summaries.get(&pid) {
-                writeln!(stdout(), "\nPID {}\n", pid)?;
-                writeln!(stdout(), "{}  ---------------\n", pid_summary)?;
+            self.write_pid_details(
+                &mut stdout(),
+                *pid,
+                &file_times,
+                raw_data,
+                calls,
+                self.width,
+            )?;
+        }
 
-                if let Some(exec) = &pid_summary.execve {
-                    writeln!(stdout(), "{}", exec)?;
+        Ok(())
+    }
+
+    /// A short summary of where each of `pids` spent its time, busiest first:
+    /// wall, syscall, and wait time, and the syscalls, files, and errno that
+    /// account for the most of it
+    pub fn print_pid_budgets(
+        &self,
+        pids: &[Pid],
+        raw_data: &HashMap<Pid, PidData<'a>>,
+        count: usize,
+    ) -> Result<(), Error> {
+        let mut pids: Vec<_> = pids
+            .iter()
+            .filter_map(|pid| Some((*pid, self.pid_summaries.get(pid)?)))
+            .collect();
+        pids.sort_by(|(x_pid, x), (y_pid, y)| {
+            (y.system_active_time)
+                .total_cmp(&x.system_active_time)
+                .then_with(|| x_pid.cmp(y_pid))
+        });
+        pids.truncate(count);
+
+        let budget_pids: Vec<_> = pids.iter().map(|(pid, _)| *pid).collect();
+        let file_times = file_times(&budget_pids, raw_data, &self.path_aliases);
+
+        for (pid, pid_summary) in pids {
+            let heading = match self.program_name(pid) {
+                Some(name) => format!("\nPID {} - {}\n", output::pid_label(pid), name),
+                None => format!("\nPID {}\n", output::pid_label(pid)),
+            };
+            output::print_heading(&heading)?;
+
+            let wall = pid_summary.elapsed_time;
+            let of_wall = |ms: f32| {
+                if wall > 0.0 {
+                    format!("{:>6.2}% of wall", ms / wall * 100.0)
+                } else {
+                    String::new()
                 }
-                if let Some(exit) = pid_summary.exit {
-                    writeln!(stdout(), "  Exit: {}", exit)?;
+            };
+            writeln!(stdout(), "  wall time     {:>12.3} ms", wall)?;
+            writeln!(
+                stdout(),
+                "  syscall time  {:>12.3} ms  {}, {} calls",
+                pid_summary.system_active_time,
+                of_wall(pid_summary.system_active_time),
+                pid_summary.syscall_count
+            )?;
+            writeln!(
+                stdout(),
+                "  wait time     {:>12.3} ms  {}",
+                pid_summary.system_wait_time,
+                of_wall(pid_summary.system_wait_time)
+            )?;
+            writeln!(
+                stdout(),
+                "  user time     {:>12.3} ms  {}",
+                pid_summary.user_time,
+                of_wall(pid_summary.user_time)
+            )?;
+
+            let top_syscalls: Vec<_> = pid_summary
+                .syscall_stats
+                .iter()
+                .take(3)
+                .map(|s| format!("{} {:.3} ms", s.label(), s.total))
+                .collect();
+            writeln!(stdout(), "  top syscalls  {}", list_or_none(&top_syscalls))?;
+
+            let top_files: Vec<_> = file_times
+                .get(&pid)
+                .into_iter()
+                .flatten()
+                .take(3)
+                .map(|(file, ms)| format!("{} {:.3} ms", file.to_str_lossy(), ms))
+                .collect();
+            writeln!(stdout(), "  top files     {}", list_or_none(&top_files))?;
+
+            let top_errno = raw_data
+                .get(&pid)
+                .and_then(top_errno)
+                .map(|(errno, ct, syscall)| {
+                    format!(
+                        "{} x{}, most from {}",
+                        errno.to_str_lossy(),
+                        ct,
+                        syscall.to_str_lossy()
+                    )
+                });
+            writeln!(
+                stdout(),
+                "  top errno     {}",
+                top_errno.unwrap_or_else(|| String::from("-"))
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the details of each of `pids` to its own file in `dir`, named
+    /// 'pid_<PID>_<PROGRAM>.txt', and returns the paths written
+    pub fn write_pid_reports(
+        &self,
+        pids: &[Pid],
+        raw_data: &HashMap<Pid, PidData<'a>>,
+        calls: &HashMap<Pid, Vec<CallRun>>,
+        dir: &Path,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let file_times = file_data::files_opened(pids, raw_data, SortFilesBy::Duration);
+        fs::create_dir_all(dir)?;
+
+        let mut paths = Vec::new();
+        for pid in pids.iter().filter(|p| self.pid_summaries.contains_key(p)) {
+            let file_name = match self.program_name(*pid) {
+                Some(program) => format!("pid_{}_{}.txt", pid, program),
+                None => format!("pid_{}.txt", pid),
+            };
+            let path = dir.join(file_name);
+
+            let mut out = BufWriter::new(File::create(&path)?);
+            // Files are never fit to a terminal
+            self.write_pid_details(&mut out, *pid, &file_times, raw_data, calls, None)?;
+            out.flush()?;
+
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+
+    // The last program executed by the PID, or its closest ancestor for threads
+    // and forks that never exec'd, made safe to use in a file name
+    fn program_name(&self, pid: Pid) -> Option<String> {
+        let mut curr = Some(pid);
+        let mut visited = HashSet::new();
+
+        while let Some(p) = curr.filter(|p| visited.insert(*p)) {
+            let pid_summary = self.pid_summaries.get(&p)?;
+            let cmd = pid_summary
+                .execve
+                .as_ref()
+                .and_then(|e| e.cmds().last())
+                .and_then(|cmd| cmd.split_whitespace().next());
+
+            if let Some(cmd) = cmd {
+                let name: String = cmd
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or(cmd)
+                    .chars()
+                    .take(32)
+                    .map(|c| {
+                        if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                return Some(name).filter(|n| !n.is_empty());
+            }
+
+            curr = pid_summary.parent_pid;
+        }
+
+        None
+    }
+
+    fn write_pid_details<W: Write>(
+        &self,
+        out: &mut W,
+        pid: Pid,
+        file_times: &BTreeMap<Pid, Vec<FileData<'a>>>,
+        raw_data: &HashMap<Pid, PidData<'a>>,
+        calls: &HashMap<Pid, Vec<CallRun>>,
+        width: Option<usize>,
+    ) -> Result<(), Error> {
+        let pid_summary = match self.pid_summaries.get(&pid) {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        writeln!(out, "\nPID {}\n", output::pid_label(pid))?;
+        pid_summary.write(out, width)?;
+        writeln!(out, "  ---------------\n")?;
+
+        if let Some(exec) = &pid_summary.execve {
+            writeln!(out, "{}", exec)?;
+        }
+        if let Some(exit) = pid_summary.exit {
+            writeln!(out, "  Exit: {}", exit)?;
+        }
+        if let Some(euid) = pid_summary.euid {
+            writeln!(out, "  Effective uid: {}", euid)?;
+        }
+        let namespace_note = pid_summary.namespace_note();
+        if let Some(note) = &namespace_note {
+            writeln!(out, "  Namespaces: {}", note)?;
+        }
+        if pid_summary.execve.is_some()
+            || pid_summary.exit.is_some()
+            || pid_summary.euid.is_some()
+            || namespace_note.is_some()
+        {
+            writeln!(out)?;
+        }
+
+        pid_summary.write_related_pids(out, PrintAmt::All)?;
+
+        if let Some(pid_files) = file_times.get(&pid) {
+            if !pid_files.is_empty() {
+                if pid_summary.parent_pid.is_some() || !pid_summary.child_pids.is_empty() {
+                    writeln!(out)?;
                 }
-                if pid_summary.execve.is_some() || pid_summary.exit.is_some() {
-                    writeln!(stdout())?;
+                output::write_heading(
+                    out,
+                    &format!(
+                        "  Slowest file open times for PID {}:\n",
+                        output::pid_label(pid)
+                    ),
+                )?;
+
+                let slowest = &pid_files[..pid_files.len().min(10)];
+                let baselines = Baselines::new(
+                    raw_data,
+                    slowest.iter().map(|f| f.syscall),
+                    self.outlier_mads,
+                );
+
+                let mut table = Table::new(vec![
+                    Column::new("dur (ms)", 10, Align::Right, 0),
+                    Column::new("timestamp", 15, Align::Center, 4),
+                    Column::new("syscall", 14, Align::Left, 3),
+                    Column::new("error", 15, Align::Center, 2),
+                    Column::new("outlier", 20, Align::Left, 1),
+                    Column::new("file name", 9, Align::Left, 0),
+                ]);
+                for file in slowest {
+                    table.add_row(vec![
+                        format!("{:.3}", file.duration),
+                        output::timestamp(file.time),
+                        file.syscall.to_str_lossy().to_string(),
+                        file.error.unwrap_or(b"-").to_str_lossy().to_string(),
+                        baselines
+                            .annotate(file.syscall, file.duration / 1000.0)
+                            .unwrap_or_default(),
+                        self.path_aliases
+                            .resolve(file.file)
+                            .to_str_lossy()
+                            .to_string(),
+                    ]);
                 }
+                table.write(out, width)?;
+            }
+        }
 
-                pid_summary.print_related_pids(PrintAmt::All)?;
+        if let Some(runs) = calls.get(&pid).filter(|runs| !runs.is_empty()) {
+            writeln!(out)?;
+            output::write_heading(
+                out,
+                &format!("  Calls by PID {}:\n", output::pid_label(pid)),
+            )?;
 
-                if let Some(pid_files) = file_times.get(&pid) {
-                    if !pid_files.is_empty() {
-                        if pid_summary.parent_pid.is_some() || !pid_summary.child_pids.is_empty() {
-                            writeln!(stdout())?;
-                        }
-                        writeln!(stdout(), "  Slowest file open times for PID {}:\n", pid)?;
-                        writeln!(
-                            stdout(),
-                            "  {:>10}    {: ^15}    {: ^15}    {: <30}",
-                            "dur (ms)",
-                            "timestamp",
-                            "error",
-                            "file name"
-                        )?;
-                        writeln!(
-                            stdout(),
-                            "  ----------    ---------------    ---------------    ---------"
-                        )?;
-
-                        for file in pid_files.iter().take(10) {
-                            writeln!(stdout(), "  {}", file)?;
-                        }
-                    }
+            let mut table = Table::new(vec![
+                Column::new("timestamp", 15, Align::Center, 1),
+                Column::new("dur (ms)", 10, Align::Right, 0),
+                Column::new("call", 4, Align::Left, 0),
+            ]);
+            for run in runs {
+                let mut call = format!(
+                    "{}({})",
+                    run.syscall.to_str_lossy(),
+                    run.path
+                        .as_ref()
+                        .map(|path| format!("\"{}\"", path))
+                        .unwrap_or_default()
+                );
+                if let Some(errno) = run.errno {
+                    call.push_str(&format!(" = {}", errno.to_str_lossy()));
                 }
-                writeln!(stdout())?;
+                if run.count > 1 {
+                    call.push_str(&format!(" \u{d7} {}", run.count));
+                }
+                table.add_row(vec![
+                    output::timestamp(run.time),
+                    format!("{:.3}", run.duration),
+                    call,
+                ]);
             }
+            table.write(out, width)?;
         }
+        writeln!(out)?;
 
         Ok(())
     }
 
     pub fn print_exec_list(&self, pids_to_print: &[Pid]) -> Result<(), Error> {
-        writeln!(stdout(), "\nPrograms Executed\n")?;
-        writeln!(
-            stdout(),
-            "  {: <6}    {: >10}    {: <16}    {: <}",
-            "pid",
-            "exit",
-            "time",
-            "program",
-        )?;
-        writeln!(stdout(), "  ------    ----------    ---------------     -------")?;
+        output::print_heading("\nPrograms Executed\n")?;
+        let mut table = Table::new(vec![
+            Column::new("pid", 6, Align::Left, 0),
+            Column::new("exit", 10, Align::Right, 1),
+            Column::new("time", 15, Align::Left, 2),
+            Column::new("program", 7, Align::Left, 0),
+        ]);
 
         for pid in pids_to_print.iter() {
-            if let Some(pid_summary) = self.pid_summaries.get(&pid) {
+            if let Some(pid_summary) = self.pid_summaries.get(pid) {
                 if let Some(exec) = &pid_summary.execve {
                     for (cmd, time) in exec.iter() {
-                        writeln!(
-                            stdout(),
-                            "  {: <6}    {: >10}    {: <16}    {: <}",
-                            pid,
-                            pid_summary
-                                .exit
-                                .map(|e| e.to_string())
-                                .unwrap_or("n/a".to_string()),
-                            time,
-                            Execs::replace_newlines(cmd, 35)
-                        )?;
+                        let exit = pid_summary
+                            .exit
+                            .map(|e| e.to_string())
+                            .unwrap_or("n/a".to_string());
+                        add_cmd_rows(
+                            &mut table,
+                            vec![output::pid_label(*pid), exit, time.to_string()],
+                            cmd,
+                        );
                     }
                 }
             }
         }
+        table.print(self.width)?;
         writeln!(stdout())?;
 
         Ok(())
     }
 
+    pub fn print_exec_anomalies(&self, pids_to_print: &[Pid]) -> Result<(), Error> {
+        let execs: Vec<_> = pids_to_print
+            .iter()
+            .filter_map(|pid| {
+                self.pid_summaries
+                    .get(pid)
+                    .and_then(|p| p.execve.as_ref())
+                    .map(|e| (*pid, e))
+            })
+            .collect();
+
+        let anomalies = exec::exec_anomalies(&execs);
+
+        if anomalies.is_empty() {
+            output::print_note("\nNo anomalous programs executed\n")?;
+            return Ok(());
+        }
+
+        output::print_heading("\nAnomalous Programs Executed\n")?;
+        let mut table = Table::new(vec![
+            Column::new("pid", 6, Align::Left, 0),
+            Column::new("time", 15, Align::Left, 2),
+            Column::new("runs", 6, Align::Right, 3),
+            Column::new("differs by", 20, Align::Left, 1),
+            Column::new("program", 7, Align::Left, 0),
+        ]);
+
+        for anomaly in anomalies {
+            add_cmd_rows(
+                &mut table,
+                vec![
+                    output::pid_label(anomaly.pid),
+                    anomaly.time.to_string(),
+                    anomaly.runs.to_string(),
+                    anomaly.reason(),
+                ],
+                anomaly.cmd,
+            );
+        }
+        table.print(self.width)?;
+        writeln!(stdout())?;
+
+        Ok(())
+    }
+
+    /// Prints each file opened by `pids_to_print`, or with `coalesce_pids`
+    /// each path once with the PIDs that opened it
     pub fn print_opened_files(
         &self,
         pids_to_print: &[Pid],
         raw_data: &HashMap<Pid, PidData<'a>>,
         sort_by: SortEventsBy,
+        coalesce_pids: bool,
     ) -> Result<(), Error> {
-        let open_calls = file_data::files_opened(&pids_to_print, raw_data, SortFilesBy::Time);
+        let open_calls = file_data::files_opened(pids_to_print, raw_data, SortFilesBy::Time);
+        if coalesce_pids {
+            return self.print_shared_files(pids_to_print, raw_data, &open_calls, sort_by);
+        }
 
-        writeln!(stdout(), "\nFiles Opened")?;
-        writeln!(
-            stdout(),
-            "\n  {: >7}    {: >10}    {: ^15}    {: ^15}    {: <30}",
-            "pid",
-            "dur (ms)",
-            "timestamp",
-            "error",
-            "file name"
-        )?;
-        writeln!(
-            stdout(),
-            "  -------    ----------    ---------------    ---------------    ---------"
-        )?;
+        output::print_heading("\nFiles Opened\n")?;
+        let mut table = Table::new(vec![
+            Column::new("pid", 7, Align::Right, 0),
+            Column::new("dur (ms)", 10, Align::Right, 1),
+            Column::new("timestamp", 15, Align::Center, 4),
+            Column::new("syscall", 14, Align::Left, 5),
+            Column::new("error", 15, Align::Center, 3),
+            Column::new("outlier", 20, Align::Left, 2),
+            Column::new("kind", 6, Align::Left, 3),
+            Column::new("file name", 9, Align::Left, 0),
+        ]);
+
+        let open_events = sorted_open_events(pids_to_print, &open_calls, sort_by);
+        let baselines = Baselines::new(
+            raw_data,
+            open_events.iter().map(|e| e.syscall),
+            self.outlier_mads,
+        );
+
+        for event in open_events.iter() {
+            let file = self.path_aliases.resolve(event.file);
+            table.add_row(vec![
+                output::pid_label(event.pid),
+                format!("{:.3}", event.duration),
+                output::timestamp(event.time),
+                event.syscall.to_str_lossy().to_string(),
+                event.error.unwrap_or(b"-").to_str_lossy().to_string(),
+                baselines
+                    .annotate(event.syscall, event.duration / 1000.0)
+                    .unwrap_or_default(),
+                FileKind::classify(&file).to_string(),
+                file.to_str_lossy().to_string(),
+            ]);
+        }
+        table.print(self.width)?;
+
+        file_kind::print_kind_totals(&open_events, &self.path_aliases, self.width)?;
+        pseudo_files::print_pseudo_files(&open_events, pids_to_print, raw_data, self.width)?;
+
+        writeln!(stdout())?;
+
+        Ok(())
+    }
 
-        let mut open_events: Vec<_> = pids_to_print
+    fn print_shared_files(
+        &self,
+        pids_to_print: &[Pid],
+        raw_data: &HashMap<Pid, PidData<'a>>,
+        open_calls: &BTreeMap<Pid, Vec<FileData<'a>>>,
+        sort_by: SortEventsBy,
+    ) -> Result<(), Error> {
+        let open_events: Vec<_> = pids_to_print
             .iter()
             .filter_map(|pid| open_calls.get(pid))
             .flatten()
             .collect();
+        let files = self.sorted_shared_files(&open_events, sort_by);
+
+        output::print_heading("\nFiles Opened by Path\n")?;
+        let mut table = Table::new(vec![
+            Column::new("pids", 6, Align::Right, 0),
+            Column::new("opens", 7, Align::Right, 0),
+            Column::new("errors", 6, Align::Right, 2),
+            Column::new("dur (ms)", 10, Align::Right, 1),
+            Column::new("mean/pid (ms)", 13, Align::Right, 3),
+            Column::new("max/pid (ms)", 12, Align::Right, 1),
+            Column::new("first time", 15, Align::Center, 4),
+            Column::new("kind", 6, Align::Left, 3),
+            Column::new("file name", 9, Align::Left, 0),
+        ]);
+
+        for file in files.iter() {
+            table.add_row(vec![
+                file.pids().to_string(),
+                file.opens.to_string(),
+                file.errors.to_string(),
+                format!("{:.3}", file.duration),
+                format!("{:.3}", file.mean_per_pid()),
+                format!("{:.3}", file.max_per_pid()),
+                output::timestamp(file.first_time),
+                FileKind::classify(&file.file).to_string(),
+                file.file.to_str_lossy().to_string(),
+            ]);
+        }
+        table.print(self.width)?;
+
+        file_kind::print_kind_totals(&open_events, &self.path_aliases, self.width)?;
+        pseudo_files::print_pseudo_files(&open_events, pids_to_print, raw_data, self.width)?;
+
+        writeln!(stdout())?;
+
+        Ok(())
+    }
+
+    fn sorted_shared_files<'b>(
+        &self,
+        open_events: &[&'b FileData<'a>],
+        sort_by: SortEventsBy,
+    ) -> Vec<SharedFile<'b>> {
+        let mut files = file_data::shared_files(open_events, &self.path_aliases);
 
         match sort_by {
             SortEventsBy::Duration => {
-                open_events.par_sort_by(|x, y| {
-                    (y.duration)
-                        .partial_cmp(&x.duration)
-                        .expect("Invalid comparison on io durations")
-                });
+                files.par_sort_by(|x, y| (y.duration).total_cmp(&x.duration));
             }
             SortEventsBy::Pid => {
-                open_events.par_sort_by(|x, y| (x.pid).cmp(&y.pid));
+                files.par_sort_by(|x, y| (y.pids()).cmp(&x.pids()));
             }
-            SortEventsBy::Time | SortEventsBy::Count => {
-                open_events.par_sort_by(|x, y| (x.time).cmp(y.time));
+            SortEventsBy::Count => {
+                files.par_sort_by(|x, y| (y.opens).cmp(&x.opens));
             }
+            SortEventsBy::Time | SortEventsBy::Bytes => {} // Files are already in the order first opened
         }
 
-        for event in open_events {
-            writeln!(stdout(), "  {: >7}    {}", event.pid, event,)?;
+        files
+    }
+
+    pub fn print_opened_directories(
+        &self,
+        pids_to_print: &[Pid],
+        raw_data: &HashMap<Pid, PidData<'a>>,
+        sort_by: SortEventsBy,
+    ) -> Result<(), Error> {
+        let open_calls = directories::directories_opened(pids_to_print, raw_data);
+
+        output::print_heading("\nDirectories accessed for files\n")?;
+        let mut table = Table::new(vec![
+            Column::new("pid", 7, Align::Right, 0),
+            Column::new("dur (ms)", 10, Align::Right, 1),
+            Column::new("first time", 15, Align::Center, 5),
+            Column::new("last time", 15, Align::Center, 6),
+            Column::new("calls", 10, Align::Right, 2),
+            Column::new("reads", 8, Align::Right, 3),
+            Column::new("read (ms)", 10, Align::Right, 4),
+            Column::new("writes", 8, Align::Right, 3),
+            Column::new("write (ms)", 10, Align::Right, 4),
+            Column::new("directory name", 14, Align::Left, 0),
+        ]);
+
+        let open_events = sorted_directories(pids_to_print, &open_calls, sort_by);
+        for (fullpath, dir) in open_events {
+            table.add_row(vec![
+                output::pid_label(dir.pid),
+                format!("{:.3}", dir.duration),
+                output::timestamp(dir.start_time),
+                output::timestamp(dir.end_time),
+                dir.ct.to_string(),
+                dir.read_ct.to_string(),
+                format!("{:.3}", dir.read_duration),
+                dir.write_ct.to_string(),
+                format!("{:.3}", dir.write_duration),
+                fullpath.to_str_lossy().to_string(),
+            ]);
         }
+        table.print(self.width)?;
 
         writeln!(stdout())?;
 
         Ok(())
     }
 
-    pub fn print_opened_directories(
+    pub fn print_io(
+        &self,
+        pids_to_print: &[Pid],
+        raw_data: &HashMap<Pid, PidData<'a>>,
+        sort_by: SortEventsBy,
+    ) -> Result<(), Error> {
+        let io_calls = io_data::io_calls(pids_to_print, raw_data);
+
+        output::print_heading("\nI/O Performed\n")?;
+        let mut table = Table::new(vec![
+            Column::new("pid", 7, Align::Right, 0),
+            Column::new("dur (ms)", 10, Align::Right, 1),
+            Column::new("timestamp", 15, Align::Center, 5),
+            Column::new("syscall", 8, Align::Left, 2),
+            Column::new("bytes", 8, Align::Right, 4),
+            Column::new("short I/O", 9, Align::Right, 7),
+            Column::new("error", 15, Align::Center, 6),
+            Column::new("outlier", 20, Align::Left, 3),
+            Column::new("file name", 9, Align::Left, 0),
+        ]);
+
+        let io_events = sorted_io_events(pids_to_print, &io_calls, sort_by);
+
+        let baselines = Baselines::new(
+            raw_data,
+            io_events.iter().map(|e| e.syscall),
+            self.outlier_mads,
+        );
+
+        for event in io_events {
+            table.add_row(vec![
+                output::pid_label(event.pid),
+                format!("{:.3}", event.duration * 1000.0),
+                output::timestamp(event.time),
+                event.syscall.to_str_lossy().to_string(),
+                event.bytes.max(0).to_string(),
+                match event.requested {
+                    Some(requested) if event.is_short() => format!("{}/{}", event.bytes, requested),
+                    _ => String::from("-"),
+                },
+                event.error.unwrap_or(b"-").to_str_lossy().to_string(),
+                baselines
+                    .annotate(event.syscall, event.duration)
+                    .unwrap_or_default(),
+                event.fd.to_str_lossy().to_string(),
+            ]);
+        }
+        table.print(self.width)?;
+
+        let kind_totals = io_data::kind_totals(&io_calls);
+        if !kind_totals.is_empty() {
+            output::print_heading("\nI/O by fd Kind\n")?;
+            let mut table = Table::new(vec![
+                Column::new("kind", 8, Align::Left, 0),
+                Column::new("calls", 8, Align::Right, 0),
+                Column::new("bytes", 10, Align::Right, 1),
+                Column::new("dur (ms)", 10, Align::Right, 0),
+            ]);
+            for total in kind_totals {
+                table.add_row(vec![
+                    total.kind.to_string(),
+                    total.calls.to_string(),
+                    total.bytes.to_string(),
+                    format!("{:.3}", total.duration * 1000.0),
+                ]);
+            }
+            table.print(self.width)?;
+        }
+
+        let file_bytes = io_data::file_byte_totals(&io_calls);
+        if !file_bytes.is_empty() {
+            output::print_heading("\nBytes by File\n")?;
+            let mut table = Table::new(vec![
+                Column::new("pid", 7, Align::Right, 0),
+                Column::new("calls", 8, Align::Right, 2),
+                Column::new("read", 10, Align::Right, 1),
+                Column::new("written", 10, Align::Right, 1),
+                Column::new("file name", 9, Align::Left, 0),
+            ]);
+            for total in file_bytes {
+                table.add_row(vec![
+                    output::pid_label(total.pid),
+                    total.calls.to_string(),
+                    total.read.to_string(),
+                    total.written.to_string(),
+                    total.fd.unwrap_or_default().to_str_lossy().to_string(),
+                ]);
+            }
+            table.print(self.width)?;
+        }
+
+        let pid_bytes = io_data::pid_byte_totals(&io_calls);
+        if !pid_bytes.is_empty() {
+            output::print_heading("\nBytes by PID\n")?;
+            let mut table = Table::new(vec![
+                Column::new("pid", 7, Align::Right, 0),
+                Column::new("calls", 8, Align::Right, 2),
+                Column::new("read", 10, Align::Right, 1),
+                Column::new("written", 10, Align::Right, 1),
+                Column::new("total", 10, Align::Right, 0),
+            ]);
+            for total in pid_bytes {
+                table.add_row(vec![
+                    output::pid_label(total.pid),
+                    total.calls.to_string(),
+                    total.read.to_string(),
+                    total.written.to_string(),
+                    total.total().to_string(),
+                ]);
+            }
+            table.print(self.width)?;
+        }
+
+        let mut short_io = io_data::short_io(&io_calls);
+        if !short_io.is_empty() {
+            short_io.sort_by(|x, y| (y.short, x.pid).cmp(&(x.short, y.pid)));
+
+            output::print_heading("\nShort I/O by File\n")?;
+            let mut table = Table::new(vec![
+                Column::new("pid", 7, Align::Right, 0),
+                Column::new("calls", 8, Align::Right, 2),
+                Column::new("short", 8, Align::Right, 0),
+                Column::new("retries", 8, Align::Right, 1),
+                Column::new("retry (ms)", 10, Align::Right, 1),
+                Column::new("file name", 9, Align::Left, 0),
+            ]);
+            for fd in short_io {
+                table.add_row(vec![
+                    output::pid_label(fd.pid),
+                    fd.calls.to_string(),
+                    fd.short.to_string(),
+                    fd.retries.to_string(),
+                    format!("{:.3}", fd.retry_duration * 1000.0),
+                    fd.fd.to_str_lossy().to_string(),
+                ]);
+            }
+            table.print(self.width)?;
+        }
+
+        writeln!(stdout())?;
+
+        Ok(())
+    }
+
+    /// The spacing of I/O calls on each fd, to tell reads paced on a timer
+    /// from ones made in bursts
+    pub fn print_io_cadence(
+        &self,
+        pids_to_print: &[Pid],
+        raw_data: &HashMap<Pid, PidData<'a>>,
+    ) -> Result<(), Error> {
+        let cadence = io_data::io_cadence(&io_data::io_calls(pids_to_print, raw_data));
+
+        output::print_heading("\nI/O Cadence by File\n")?;
+        if cadence.is_empty() {
+            output::print_note("  No fds named with '-y' had 3 or more I/O calls\n")?;
+            return Ok(());
+        }
+
+        let mut table = Table::new(vec![
+            Column::new("pid", 7, Align::Right, 0),
+            Column::new("calls", 8, Align::Right, 0),
+            Column::new("min gap (ms)", 12, Align::Right, 3),
+            Column::new("median (ms)", 11, Align::Right, 2),
+            Column::new("avg (ms)", 10, Align::Right, 1),
+            Column::new("max (ms)", 10, Align::Right, 1),
+            Column::new("pattern", 7, Align::Left, 0),
+            Column::new("file name", 9, Align::Left, 0),
+        ]);
+        for fd in cadence {
+            table.add_row(vec![
+                output::pid_label(fd.pid),
+                fd.calls.to_string(),
+                format!("{:.3}", fd.min),
+                format!("{:.3}", fd.median),
+                format!("{:.3}", fd.avg),
+                format!("{:.3}", fd.max),
+                fd.pattern().to_string(),
+                fd.fd.to_str_lossy().to_string(),
+            ]);
+        }
+        table.print(self.width)?;
+
+        output::print_note(
+            "\n  'steady' gaps vary less than 20% from the average, 'bursts' have a median gap under a quarter of it\n",
+        )
+    }
+
+    /// Prints the process tree, with each PID's own and inclusive syscall
+    /// time and counts first when `inclusive` is set, and a bar showing when
+    /// it was alive in the trace when `timeline` is set
+    pub fn print_pid_tree(
+        &self,
+        truncate: bool,
+        inclusive: bool,
+        timeline: bool,
+    ) -> Result<(), Error> {
+        let pids: Vec<_> = self
+            .to_sorted(SortBy::StartTime)
+            .iter()
+            .map(|(p, _)| p)
+            .cloned()
+            .collect();
+
+        let columns = pid_tree::TreeColumns {
+            rollups: match inclusive {
+                true => Some(pid_tree::rollups(&pids, &self.pid_summaries)),
+                false => None,
+            },
+            timeline: match timeline {
+                true => pid_tree::Timeline::new(&self.pid_summaries),
+                false => None,
+            },
+        };
+        columns.print_header()?;
+        let mut done = Vec::new();
+        let mut filled_cols = HashSet::new();
+
+        let mut pid_iter = pids.iter().peekable();
+        while let Some(&pid) = pid_iter.next() {
+            let position = match pid_iter.peek().is_some() {
+                true => pid_tree::PidPosition::NotLast,
+                false => pid_tree::PidPosition::Last,
+            };
+
+            pid_tree::print_tree(
+                pid,
+                &self.pid_summaries,
+                &mut done,
+                &mut filled_cols,
+                truncate,
+                &columns,
+                pid_tree::TreePrint::new(pid_tree::FanOut::All, 0, position),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the tree as JSON rather than drawing it, PIDs in the order
+    /// they're drawn
+    pub fn write_pid_tree_json<W: Write>(&self, out: &mut W) -> Result<(), Error> {
+        let pids: Vec<_> = self
+            .to_sorted(SortBy::StartTime)
+            .iter()
+            .map(|(p, _)| *p)
+            .collect();
+
+        pid_tree::write_tree_json(out, &pids, &self.pid_summaries)
+    }
+
+    /// Writes the 'summary' table as one JSON object: the top `count` PIDs
+    /// by `sort_by`, and the totals for the trace
+    pub fn write_summary_json<W: Write>(
+        &self,
+        out: &mut W,
+        elapsed_time: Option<Duration>,
+        count: usize,
+        sort_by: SortBy,
+    ) -> Result<(), Error> {
+        let sorted = self.to_sorted(sort_by);
+        let pids = sorted
+            .iter()
+            .take(count)
+            .map(|(pid, pid_summary)| SummaryPidJson {
+                pid: pid_summary.to_json(*pid),
+                active_pct: rounded(self.active_pct(pid_summary.system_active_time), 2),
+                metrics: self.metrics_json(*pid),
+            })
+            .collect();
+
+        json::write_report(
+            out,
+            &SummaryReport {
+                pids,
+                totals: self.totals_json(elapsed_time),
+            },
+        )
+    }
+
+    /// As `write_summary_json`, with a row for each effective uid rather than each PID
+    pub fn write_user_summary_json<W: Write>(
+        &self,
+        out: &mut W,
+        elapsed_time: Option<Duration>,
+        count: usize,
+        sort_by: SortBy,
+    ) -> Result<(), Error> {
+        let users = self
+            .sorted_user_summaries(sort_by)
+            .iter()
+            .take(count)
+            .map(|user| UserJson {
+                euid: user.euid,
+                pids: user.pids,
+                syscalls: user.syscall_count,
+                active_ms: rounded(user.system_active_time, 3),
+                wait_ms: rounded(user.system_wait_time, 3),
+                user_ms: rounded(user.user_time, 3),
+                total_ms: rounded(user.total_time, 3),
+                active_pct: rounded(self.active_pct(user.system_active_time), 2),
+            })
+            .collect();
+
+        json::write_report(
+            out,
+            &UserSummaryReport {
+                users,
+                totals: self.totals_json(elapsed_time),
+            },
+        )
+    }
+
+    /// Writes the top `count` PIDs by `sort_by` as JSON, each with its syscall table
+    pub fn write_pid_list_json<W: Write>(
+        &self,
+        out: &mut W,
+        count: usize,
+        sort_by: SortBy,
+    ) -> Result<(), Error> {
+        let sorted = self.to_sorted(sort_by);
+        let pids = sorted
+            .iter()
+            .take(count)
+            .map(|(pid, pid_summary)| PidListJson {
+                pid: pid_summary.to_json(*pid),
+                metrics: self.metrics_json(*pid),
+                syscall_stats: pid_summary.syscall_stats_json(),
+            })
+            .collect();
+
+        json::write_report(out, &PidListReport { pids })
+    }
+
+    /// Writes what 'pid' prints for each of `pids` as JSON: the PID's totals,
+    /// syscall table, slowest opens, and calls
+    pub fn write_pid_details_json<W: Write>(
+        &self,
+        out: &mut W,
+        pids: &[Pid],
+        raw_data: &HashMap<Pid, PidData<'a>>,
+        calls: &HashMap<Pid, Vec<CallRun>>,
+    ) -> Result<(), Error> {
+        let file_times = file_data::files_opened(pids, raw_data, SortFilesBy::Duration);
+
+        let records = pids
+            .iter()
+            .filter_map(|pid| self.pid_summaries.get(pid).map(|p| (*pid, p)))
+            .map(|(pid, pid_summary)| {
+                let slowest = file_times
+                    .get(&pid)
+                    .map_or(&[][..], |f| &f[..f.len().min(10)]);
+                let baselines = Baselines::new(
+                    raw_data,
+                    slowest.iter().map(|f| f.syscall),
+                    self.outlier_mads,
+                );
+
+                PidDetailsJson {
+                    pid: pid_summary.to_json(pid),
+                    namespaces: pid_summary.namespace_note(),
+                    syscall_stats: pid_summary.syscall_stats_json(),
+                    slowest_opens: slowest
+                        .iter()
+                        .map(|file| self.open_json(file, &baselines))
+                        .collect(),
+                    calls: calls
+                        .get(&pid)
+                        .into_iter()
+                        .flatten()
+                        .map(|run| CallRunJson {
+                            time: Time(run.time),
+                            duration_ms: rounded(run.duration, 3),
+                            syscall: Lossy(run.syscall),
+                            path: run.path.as_deref(),
+                            errno: run.errno.map(Lossy),
+                            count: run.count,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        json::write_report(out, &PidDetailsReport { pids: records })
+    }
+
+    /// Writes each program run by `pids_to_print` as JSON, in the order of `pids_to_print`
+    pub fn write_exec_list_json<W: Write>(
+        &self,
+        out: &mut W,
+        pids_to_print: &[Pid],
+    ) -> Result<(), Error> {
+        let mut execs = Vec::new();
+        for pid in pids_to_print {
+            let pid_summary = match self.pid_summaries.get(pid) {
+                Some(p) => p,
+                None => continue,
+            };
+            for (cmd, time) in pid_summary.execve.iter().flat_map(|e| e.iter()) {
+                execs.push(ExecListJson {
+                    pid: *pid,
+                    time,
+                    cmd,
+                    exit: pid_summary.exit.map(|e| e.to_string()),
+                });
+            }
+        }
+
+        json::write_report(out, &ExecListReport { execs })
+    }
+
+    /// Writes the files opened by `pids_to_print` as JSON, or with
+    /// `coalesce_pids` each path once with the PIDs that opened it
+    pub fn write_opened_files_json<W: Write>(
+        &self,
+        out: &mut W,
+        pids_to_print: &[Pid],
+        raw_data: &HashMap<Pid, PidData<'a>>,
+        sort_by: SortEventsBy,
+        coalesce_pids: bool,
+    ) -> Result<(), Error> {
+        let open_calls = file_data::files_opened(pids_to_print, raw_data, SortFilesBy::Time);
+
+        if coalesce_pids {
+            let open_events: Vec<_> = pids_to_print
+                .iter()
+                .filter_map(|pid| open_calls.get(pid))
+                .flatten()
+                .collect();
+            let shared_files = self.sorted_shared_files(&open_events, sort_by);
+            let files = shared_files
+                .iter()
+                .map(|file| SharedFileJson {
+                    path: Lossy(&file.file),
+                    kind: FileKind::classify(&file.file).to_string(),
+                    pids: file.pid_durations.keys().copied().collect(),
+                    opens: file.opens,
+                    errors: file.errors,
+                    duration_ms: rounded(file.duration, 3),
+                    mean_per_pid_ms: rounded(file.mean_per_pid(), 3),
+                    max_per_pid_ms: rounded(file.max_per_pid(), 3),
+                    first_time: Time(file.first_time),
+                })
+                .collect();
+
+            json::write_report(out, &FilesReport { files })
+        } else {
+            let open_events = sorted_open_events(pids_to_print, &open_calls, sort_by);
+            let baselines = Baselines::new(
+                raw_data,
+                open_events.iter().map(|e| e.syscall),
+                self.outlier_mads,
+            );
+            let files = open_events
+                .iter()
+                .map(|event| self.open_json(event, &baselines))
+                .collect();
+
+            json::write_report(out, &FilesReport { files })
+        }
+    }
+
+    /// Writes the directories `pids_to_print` worked in as JSON
+    pub fn write_opened_directories_json<W: Write>(
         &self,
+        out: &mut W,
         pids_to_print: &[Pid],
         raw_data: &HashMap<Pid, PidData<'a>>,
         sort_by: SortEventsBy,
     ) -> Result<(), Error> {
-        let open_calls = directories::directories_opened(&pids_to_print, raw_data);
-
-        writeln!(stdout(), "\nDirectories accessed for files")?;
-        writeln!(
-            stdout(),
-            "\n  {: >7}    {: >10}    {: ^15}    {: ^15}    {: >10}    {: <30}",
-            "pid",
-            "dur (ms)",
-            "first time",
-            "last time",
-            "open ct",
-            "directory name"
-        )?;
-        writeln!(
-            stdout(),
-            "  -------    ----------    ---------------    ---------------    ----------    --------------"
-        )?;
+        let open_calls = directories::directories_opened(pids_to_print, raw_data);
 
-        let mut open_events: Vec<_> = pids_to_print
+        let sorted = sorted_directories(pids_to_print, &open_calls, sort_by);
+        let directories = sorted
             .iter()
-            .filter_map(|pid| open_calls.get(pid))
-            .flatten()
+            .map(|(fullpath, dir)| DirectoryJson {
+                pid: dir.pid,
+                path: Lossy(fullpath),
+                duration_ms: rounded(dir.duration, 3),
+                first_time: Time(dir.start_time),
+                last_time: Time(dir.end_time),
+                calls: dir.ct,
+                reads: dir.read_ct,
+                read_ms: rounded(dir.read_duration, 3),
+                writes: dir.write_ct,
+                write_ms: rounded(dir.write_duration, 3),
+            })
             .collect();
 
-        match sort_by {
-            SortEventsBy::Count => {
-                open_events.par_sort_by(|(_, x), (_, y)| (y.ct).cmp(&x.ct));
-            }
-            SortEventsBy::Duration => {
-                open_events.par_sort_by(|(_, x), (_, y)| {
-                    (y.duration)
-                        .partial_cmp(&x.duration)
-                        .expect("Invalid comparison on directory durations")
-                });
-            }
-            SortEventsBy::Pid => {} // Events are already sorted by pid, no action needed
-            SortEventsBy::Time => {
-                open_events.par_sort_by(|(_, x), (_, y)| (x.start_time).cmp(y.start_time));
-            }
-        }
-
-        for (fullpath, dir) in open_events {
-            writeln!(
-                stdout(),
-                "  {: >7}    {}    {: <30}",
-                dir.pid,
-                dir,
-                fullpath.to_str_lossy(),
-            )?;
-        }
-
-        writeln!(stdout())?;
-
-        Ok(())
+        json::write_report(out, &DirectoriesReport { directories })
     }
 
-    pub fn print_io(
+    /// Writes the I/O calls of `pids_to_print` as JSON, with the totals by
+    /// kind of fd and the fds that saw short reads or writes
+    pub fn write_io_json<W: Write>(
         &self,
+        out: &mut W,
         pids_to_print: &[Pid],
         raw_data: &HashMap<Pid, PidData<'a>>,
         sort_by: SortEventsBy,
     ) -> Result<(), Error> {
         let io_calls = io_data::io_calls(pids_to_print, raw_data);
+        let io_events = sorted_io_events(pids_to_print, &io_calls, sort_by);
+        let baselines = Baselines::new(
+            raw_data,
+            io_events.iter().map(|e| e.syscall),
+            self.outlier_mads,
+        );
 
-        writeln!(stdout(), "\nI/O Performed")?;
-        writeln!(
-            stdout(),
-            "\n  {: >7}    {: >10}    {: ^15}    {: <8}    {: >8}    {: ^15}     {: <30}",
-            "pid",
-            "dur (ms)",
-            "timestamp",
-            "syscall",
-            "bytes",
-            "error",
-            "file name"
-        )?;
-        writeln!(
-            stdout(),
-            "  -------    ----------    ---------------    --------    --------    ---------------     ---------"
-        )?;
+        let io = io_events
+            .iter()
+            .map(|event| IoCallJson {
+                pid: event.pid,
+                time: Time(event.time),
+                duration_ms: rounded(event.duration * 1000.0, 3),
+                syscall: Lossy(event.syscall),
+                bytes: event.bytes.max(0),
+                requested: event.requested,
+                error: event.error.map(Lossy),
+                outlier: baselines.annotate(event.syscall, event.duration),
+                file: Lossy(event.fd),
+            })
+            .collect();
 
-        let mut io_events: Vec<_> = pids_to_print
+        let kinds = io_data::kind_totals(&io_calls)
             .iter()
-            .filter_map(|pid| io_calls.get(pid))
-            .flatten()
+            .map(|total| KindTotalJson {
+                kind: total.kind.to_string(),
+                calls: total.calls,
+                bytes: total.bytes,
+                duration_ms: rounded(total.duration * 1000.0, 3),
+            })
             .collect();
 
-        match sort_by {
-            SortEventsBy::Duration => {
-                io_events.par_sort_by(|x, y| {
-                    (y.duration)
-                        .partial_cmp(&x.duration)
-                        .expect("Invalid comparison on io durations")
-                });
-            }
-            SortEventsBy::Pid => {
-                io_events.par_sort_by(|x, y| (x.pid).cmp(&y.pid));
-            }
-            SortEventsBy::Time | SortEventsBy::Count => {
-                io_events.par_sort_by(|x, y| (x.time).cmp(y.time));
-            }
-        }
+        let mut short_io = io_data::short_io(&io_calls);
+        short_io.sort_by(|x, y| (y.short, x.pid).cmp(&(x.short, y.pid)));
+        let short_io = short_io
+            .iter()
+            .map(|fd| ShortIoJson {
+                pid: fd.pid,
+                file: Lossy(fd.fd),
+                calls: fd.calls,
+                short: fd.short,
+                retries: fd.retries,
+                retry_ms: rounded(fd.retry_duration * 1000.0, 3),
+            })
+            .collect();
 
-        for event in io_events {
-            writeln!(stdout(), "{}", event)?;
-        }
+        let bytes = |total: &io_data::ByteTotal<'a>| ByteTotalJson {
+            pid: total.pid,
+            file: total.fd.map(Lossy),
+            calls: total.calls,
+            read: total.read,
+            written: total.written,
+        };
+        let file_bytes = io_data::file_byte_totals(&io_calls)
+            .iter()
+            .map(bytes)
+            .collect();
+        let pid_bytes = io_data::pid_byte_totals(&io_calls)
+            .iter()
+            .map(bytes)
+            .collect();
 
-        writeln!(stdout())?;
+        json::write_report(
+            out,
+            &IoReport {
+                io,
+                kinds,
+                short_io,
+                file_bytes,
+                pid_bytes,
+            },
+        )
+    }
 
-        Ok(())
+    // A file open as JSON, with its path resolved through any aliases
+    fn open_json<'b>(&self, file: &FileData<'b>, baselines: &Baselines) -> OpenJson<'b> {
+        let path = self.path_aliases.resolve(file.file);
+        OpenJson {
+            pid: file.pid,
+            time: Time(file.time),
+            duration_ms: rounded(file.duration, 3),
+            syscall: Lossy(file.syscall),
+            error: file.error.map(Lossy),
+            outlier: baselines.annotate(file.syscall, file.duration / 1000.0),
+            kind: FileKind::classify(&path).to_string(),
+            path: path.to_str_lossy().to_string(),
+        }
     }
 
-    pub fn print_pid_tree(&self, truncate: bool) -> Result<(), Error> {
-        let pids: Vec<_> = self
-            .to_sorted(SortBy::StartTime)
+    // Percent of the trace's active time
+    fn active_pct(&self, active_time: f32) -> f32 {
+        if self.all_active_time > 0.0 {
+            active_time / self.all_active_time * 100.0
+        } else {
+            0.0
+        }
+    }
+
+    // The values of '--metric' for `pid` as JSON, `None` without any
+    fn metrics_json(&self, pid: Pid) -> Option<MetricsJson<'_>> {
+        if self.metrics.is_empty() {
+            return None;
+        }
+        let values = self
+            .metrics
             .iter()
-            .map(|(p, _)| p)
-            .cloned()
+            .map(|(name, values)| {
+                let value = values.get(&pid).copied().flatten();
+                (
+                    name.as_str(),
+                    value.filter(|v| v.is_finite()).map(|v| rounded(v, 3)),
+                )
+            })
             .collect();
-        let mut done = Vec::new();
-        let mut filled_cols = HashSet::new();
-
-        let mut pid_iter = pids.iter().peekable();
-        while let Some(&pid) = pid_iter.next() {
-            let position = match pid_iter.peek().is_some() {
-                true => pid_tree::PidPosition::NotLast,
-                false => pid_tree::PidPosition::Last,
-            };
+        Some(MetricsJson(values))
+    }
 
-            pid_tree::print_tree(
-                pid,
-                &self.pid_summaries,
-                &mut done,
-                &mut filled_cols,
-                truncate,
-                pid_tree::TreePrint::new(pid_tree::FanOut::All, 0, position),
-            )?;
+    // The totals 'summary' prints below its table, as JSON
+    fn totals_json(&self, elapsed_time: Option<Duration>) -> TotalsJson {
+        TotalsJson {
+            pid_count: self.pid_summaries.len(),
+            real_ms: elapsed_time.map(|d| d.num_milliseconds()),
+            traced_ms: self.traced_time().map(|d| d.num_milliseconds()),
+            user_ms: rounded(self.all_user_time, 3),
+            sys_ms: rounded(self.all_active_time, 3),
         }
-
-        Ok(())
     }
 
     pub fn pids(&self) -> Vec<Pid> {
@@ -762,11 +2099,433 @@ impl<'a> SessionSummary<'a> {
     }
 }
 
+// A file and the ms spent on it
+type FileTime<'a> = (Cow<'a, [u8]>, f32);
+
+// Time in ms spent opening, reading, and writing each file, slowest first,
+// with files opened by an alias totaled under the path it refers to
+fn file_times<'a>(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData<'a>>,
+    aliases: &PathAliases,
+) -> HashMap<Pid, Vec<FileTime<'a>>> {
+    let mut file_times: HashMap<Pid, HashMap<Cow<[u8]>, f32>> = HashMap::default();
+
+    for (pid, files) in file_data::files_opened(pids, raw_data, SortFilesBy::Duration) {
+        let times = file_times.entry(pid).or_default();
+        for file in files {
+            *times.entry(aliases.resolve(file.file)).or_default() += file.duration;
+        }
+    }
+    for (pid, calls) in io_data::io_calls(pids, raw_data) {
+        let times = file_times.entry(pid).or_default();
+        // Without '-y' the fd's file isn't known
+        for call in calls.iter().filter(|c| !c.fd.starts_with(b"Unavailable:")) {
+            *times.entry(Cow::Borrowed(call.fd)).or_default() += call.duration * 1000.0;
+        }
+    }
+
+    file_times
+        .into_iter()
+        .map(|(pid, times)| {
+            let mut times: Vec<_> = times.into_iter().collect();
+            times.sort_by(|x, y| (y.1).total_cmp(&x.1).then_with(|| x.0.cmp(&y.0)));
+            (pid, times)
+        })
+        .collect()
+}
+
+// A row of `cells` ending in the command, with each further line of the
+// command on a row of its own under it
+fn add_cmd_rows(table: &mut Table, mut cells: Vec<String>, cmd: &str) {
+    let blank = cells.len();
+    let mut lines = cmd.split(r#"\n"#);
+    cells.push(lines.next().unwrap_or_default().to_string());
+    table.add_row(cells);
+
+    for line in lines {
+        let mut row = vec![String::new(); blank];
+        row.push(line.to_string());
+        table.add_row(row);
+    }
+}
+
+fn list_or_none(items: &[String]) -> String {
+    if items.is_empty() {
+        String::from("-")
+    } else {
+        items.join(", ")
+    }
+}
+
+// The most frequent errno, its count, and the syscall returning it most often
+fn top_errno<'a>(pid_data: &PidData<'a>) -> Option<(&'a [u8], i32, &'a [u8])> {
+    let mut errnos: BTreeMap<&[u8], (i32, &[u8], i32)> = BTreeMap::new();
+
+    for (syscall, data) in &pid_data.syscall_data {
+        for (errno, ct) in &data.errors {
+            let (total, top_syscall, top_ct) = errnos.entry(errno).or_insert((0, syscall, 0));
+            *total += ct;
+            if (*ct, Reverse(*syscall)) > (*top_ct, Reverse(*top_syscall)) {
+                *top_syscall = syscall;
+                *top_ct = *ct;
+            }
+        }
+    }
+
+    errnos
+        .into_iter()
+        .max_by(|x, y| (x.1 .0).cmp(&y.1 .0).then_with(|| y.0.cmp(x.0)))
+        .map(|(errno, (total, syscall, _))| (errno, total, syscall))
+}
+
+// The opens of `pids` sorted for the 'files' report
+fn sorted_open_events<'a, 'b>(
+    pids: &[Pid],
+    open_calls: &'b BTreeMap<Pid, Vec<FileData<'a>>>,
+    sort_by: SortEventsBy,
+) -> Vec<&'b FileData<'a>> {
+    let mut open_events: Vec<_> = pids
+        .iter()
+        .filter_map(|pid| open_calls.get(pid))
+        .flatten()
+        .collect();
+
+    match sort_by {
+        SortEventsBy::Duration => {
+            open_events.par_sort_by(|x, y| {
+                (y.duration)
+                    .partial_cmp(&x.duration)
+                    .expect("Invalid comparison on io durations")
+            });
+        }
+        SortEventsBy::Pid => {
+            open_events.par_sort_by(|x, y| (x.pid).cmp(&y.pid));
+        }
+        SortEventsBy::Time | SortEventsBy::Count | SortEventsBy::Bytes => {
+            open_events.par_sort_by(|x, y| (x.time).cmp(y.time));
+        }
+    }
+
+    open_events
+}
+
+// The directories of `pids` sorted for the 'directories' report
+fn sorted_directories<'a, 'b>(
+    pids: &[Pid],
+    open_calls: &'b HashMap<Pid, BTreeMap<&'a [u8], DirectoryData<'a>>>,
+    sort_by: SortEventsBy,
+) -> Vec<(&'b &'a [u8], &'b DirectoryData<'a>)> {
+    let mut open_events: Vec<_> = pids
+        .iter()
+        .filter_map(|pid| open_calls.get(pid))
+        .flatten()
+        .collect();
+
+    match sort_by {
+        SortEventsBy::Count => {
+            open_events.par_sort_by(|(_, x), (_, y)| (y.ct).cmp(&x.ct));
+        }
+        SortEventsBy::Duration => {
+            open_events.par_sort_by(|(_, x), (_, y)| {
+                (y.duration)
+                    .partial_cmp(&x.duration)
+                    .expect("Invalid comparison on directory durations")
+            });
+        }
+        SortEventsBy::Pid => {} // Events are already sorted by pid, no action needed
+        SortEventsBy::Time | SortEventsBy::Bytes => {
+            open_events.par_sort_by(|(_, x), (_, y)| (x.start_time).cmp(y.start_time));
+        }
+    }
+
+    open_events
+}
+
+// The I/O calls of `pids` sorted for the 'io' report
+fn sorted_io_events<'a, 'b>(
+    pids: &[Pid],
+    io_calls: &'b BTreeMap<Pid, Vec<IoCall<'a>>>,
+    sort_by: SortEventsBy,
+) -> Vec<&'b IoCall<'a>> {
+    let mut io_events: Vec<_> = pids
+        .iter()
+        .filter_map(|pid| io_calls.get(pid))
+        .flatten()
+        .collect();
+
+    match sort_by {
+        SortEventsBy::Duration => {
+            io_events.par_sort_by(|x, y| {
+                (y.duration)
+                    .partial_cmp(&x.duration)
+                    .expect("Invalid comparison on io durations")
+            });
+        }
+        SortEventsBy::Pid => {
+            io_events.par_sort_by(|x, y| (x.pid).cmp(&y.pid));
+        }
+        SortEventsBy::Bytes => {
+            io_events.par_sort_by(|x, y| (y.bytes.max(0), x.time).cmp(&(x.bytes.max(0), y.time)));
+        }
+        SortEventsBy::Time | SortEventsBy::Count => {
+            io_events.par_sort_by(|x, y| (x.time).cmp(y.time));
+        }
+    }
+
+    io_events
+}
+
+// A file open as written to JSON by 'files' and 'pid'
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct OpenJson<'a> {
+    pid: Pid,
+    time: Time<'a>,
+    duration_ms: f64,
+    syscall: Lossy<'a>,
+    error: Option<Lossy<'a>>,
+    outlier: Option<String>,
+    kind: String,
+    path: String,
+}
+
+// The values of '--metric' for a PID, written as an object in the order the
+// metrics were given
+struct MetricsJson<'a>(Vec<(&'a str, Option<f64>)>);
+
+impl Serialize for MetricsJson<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl JsonSchema for MetricsJson<'_> {
+    fn schema_name() -> Cow<'static, str> {
+        "MetricsJson".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "object",
+            "additionalProperties": { "type": ["number", "null"] },
+        })
+    }
+}
+
+// The trace's totals, written after the rows of 'summary'
+#[derive(Serialize, JsonSchema)]
+struct TotalsJson {
+    pid_count: usize,
+    real_ms: Option<i64>,
+    traced_ms: Option<i64>,
+    user_ms: f64,
+    sys_ms: f64,
+}
+
+// A PID's row of 'summary' as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct SummaryPidJson<'a> {
+    #[serde(flatten)]
+    pid: PidSummaryJson<'a>,
+    active_pct: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<MetricsJson<'a>>,
+}
+
+// 'summary' as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct SummaryReport<'a> {
+    pids: Vec<SummaryPidJson<'a>>,
+    #[serde(flatten)]
+    totals: TotalsJson,
+}
+
+// A user's row of 'summary --by-user' as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct UserJson {
+    euid: Option<u32>,
+    pids: usize,
+    syscalls: i32,
+    active_ms: f64,
+    wait_ms: f64,
+    user_ms: f64,
+    total_ms: f64,
+    active_pct: f64,
+}
+
+// 'summary --by-user' as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct UserSummaryReport {
+    users: Vec<UserJson>,
+    #[serde(flatten)]
+    totals: TotalsJson,
+}
+
+// A PID of 'list-pids' as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct PidListJson<'a> {
+    #[serde(flatten)]
+    pid: PidSummaryJson<'a>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metrics: Option<MetricsJson<'a>>,
+    syscall_stats: Vec<SyscallStatsJson<'a>>,
+}
+
+// 'list-pids' as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct PidListReport<'a> {
+    pids: Vec<PidListJson<'a>>,
+}
+
+// A run of calls in 'pid' as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct CallRunJson<'a> {
+    time: Time<'a>,
+    duration_ms: f64,
+    syscall: Lossy<'a>,
+    path: Option<&'a str>,
+    errno: Option<Lossy<'a>>,
+    count: usize,
+}
+
+// A PID of 'pid' as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct PidDetailsJson<'a> {
+    #[serde(flatten)]
+    pid: PidSummaryJson<'a>,
+    namespaces: Option<String>,
+    syscall_stats: Vec<SyscallStatsJson<'a>>,
+    slowest_opens: Vec<OpenJson<'a>>,
+    calls: Vec<CallRunJson<'a>>,
+}
+
+// 'pid' as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct PidDetailsReport<'a> {
+    pids: Vec<PidDetailsJson<'a>>,
+}
+
+// A program run in 'exec' as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct ExecListJson<'a> {
+    pid: Pid,
+    time: &'a str,
+    cmd: &'a str,
+    exit: Option<String>,
+}
+
+// 'exec' as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct ExecListReport<'a> {
+    execs: Vec<ExecListJson<'a>>,
+}
+
+// A path opened by several PIDs in 'files --coalesce-pids' as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct SharedFileJson<'a> {
+    path: Lossy<'a>,
+    kind: String,
+    pids: Vec<Pid>,
+    opens: usize,
+    errors: usize,
+    duration_ms: f64,
+    mean_per_pid_ms: f64,
+    max_per_pid_ms: f64,
+    first_time: Time<'a>,
+}
+
+// 'files' as written to JSON, a row per open or per path
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct FilesReport<T> {
+    files: Vec<T>,
+}
+
+// A directory of 'directories' as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct DirectoryJson<'a> {
+    pid: Pid,
+    path: Lossy<'a>,
+    duration_ms: f64,
+    first_time: Time<'a>,
+    last_time: Time<'a>,
+    calls: usize,
+    reads: usize,
+    read_ms: f64,
+    writes: usize,
+    write_ms: f64,
+}
+
+// 'directories' as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct DirectoriesReport<'a> {
+    directories: Vec<DirectoryJson<'a>>,
+}
+
+// An I/O call of 'io' as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct IoCallJson<'a> {
+    pid: Pid,
+    time: Time<'a>,
+    duration_ms: f64,
+    syscall: Lossy<'a>,
+    bytes: i32,
+    requested: Option<i32>,
+    error: Option<Lossy<'a>>,
+    outlier: Option<String>,
+    file: Lossy<'a>,
+}
+
+// The I/O totals for a kind of fd as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct KindTotalJson {
+    kind: String,
+    calls: usize,
+    bytes: i64,
+    duration_ms: f64,
+}
+
+// An fd that saw short reads or writes as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct ShortIoJson<'a> {
+    pid: Pid,
+    file: Lossy<'a>,
+    calls: usize,
+    short: usize,
+    retries: usize,
+    retry_ms: f64,
+}
+
+// The bytes moved by a PID, or by a PID through one file, as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct ByteTotalJson<'a> {
+    pid: Pid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<Lossy<'a>>,
+    calls: usize,
+    read: u64,
+    written: u64,
+}
+
+// 'io' as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct IoReport<'a> {
+    io: Vec<IoCallJson<'a>>,
+    kinds: Vec<KindTotalJson>,
+    short_io: Vec<ShortIoJson<'a>>,
+    file_bytes: Vec<ByteTotalJson<'a>>,
+    pid_bytes: Vec<ByteTotalJson<'a>>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::syscall_data::*;
     use crate::syscall_stats::*;
+    use serde_json::Value;
 
     #[test]
     fn pid_summary_count_correct() {
@@ -781,6 +2540,18 @@ mod tests {
         assert_eq!(summary.pid_summaries[&566].syscall_count, 5);
     }
 
+    #[test]
+    fn pid_summary_error_count_correct() {
+        let input = br##"566   00:09:48.145068 <... restart_syscall resumed> ) = -1 ETIMEDOUT (Connection timed out) <1.000000>
+566   00:09:48.145114 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>
+566   00:09:48.145264 stat("/tmp/missing", 0x7ffc6d3c2df0) = -1 ENOENT (No such file or directory) <1.000000>
+566   00:09:48.145929 stat("/tmp/missing", 0x7ffc6d3c2df0) = -1 ENOENT (No such file or directory) <1.000000>"##;
+        let pid_data_map = build_syscall_data(input);
+        let syscall_stats = build_syscall_stats(&pid_data_map);
+        let summary = SessionSummary::from_syscall_stats(&syscall_stats, &pid_data_map);
+        assert_eq!(summary.pid_summaries[&566].error_count(), 3);
+    }
+
     #[test]
     fn pid_summary_active_time_correct() {
         let input = br##"566   00:09:48.145068 <... restart_syscall resumed> ) = -1 ETIMEDOUT (Connection timed out) <1.000000>
@@ -1067,4 +2838,200 @@ mod tests {
             .collect();
         assert_eq!(sorted, &[9746, 32766, 26124, 412]);
     }
+
+    #[test]
+    fn pid_summary_program_name_from_exec_or_parent() {
+        let input = br##"12668 15:57:56.205465 execve("/opt/gitlab/embedded/bin/git-upload-pack", ["git-upload-pack", "/var/opt/gitlab/repo.git"], 0x1ae3c08 /* 15 vars */) = 0 <0.000176>
+12668 15:57:56.300000 clone(child_stack=0, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7fe5648a69d0) = 12670 <0.000100>
+12670 15:57:56.400000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+12671 15:57:56.500000 execve("/tmp/run$1", ["/tmp/run$1", "-v"], 0x1ae3c08 /* 15 vars */) = 0 <0.000176>
+12672 15:57:56.600000 write(1</dev/null>, "a", 1) = 1 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let syscall_stats = build_syscall_stats(&pid_data_map);
+        let summary = SessionSummary::from_syscall_stats(&syscall_stats, &pid_data_map);
+
+        assert_eq!(
+            summary.program_name(12668).as_deref(),
+            Some("git-upload-pack")
+        );
+        assert_eq!(
+            summary.program_name(12670).as_deref(),
+            Some("git-upload-pack")
+        );
+        assert_eq!(summary.program_name(12671).as_deref(), Some("run_1"));
+        assert_eq!(summary.program_name(12672), None);
+    }
+
+    #[test]
+    fn pid_budget_top_files_and_errno() {
+        let input = br##"477   00:09:56.000000 openat(AT_FDCWD, "/etc/hosts", O_RDONLY) = 3</etc/hosts> <0.000100>
+477   00:09:56.100000 read(3</etc/hosts>, "a", 1) = 1 <0.000200>
+477   00:09:56.200000 read(4<socket:[300]>,  <unfinished ...>
+477   00:09:56.400000 <... read resumed>"", 10) = 0 <0.200000>
+477   00:09:56.500000 stat("/a", 0x7ffd4c3b8d70) = -1 ENOENT (No such file or directory) <0.000010>
+477   00:09:56.600000 stat("/b", 0x7ffd4c3b8d70) = -1 ENOENT (No such file or directory) <0.000010>
+477   00:09:56.700000 access("/c", F_OK) = -1 ENOENT (No such file or directory) <0.000010>
+477   00:09:56.800000 unlink("/d") = -1 EACCES (Permission denied) <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+
+        let times = &file_times(&[477], &pid_data_map, &PathAliases::default())[&477];
+        assert_eq!(times[0].0, b"socket:[300]".as_ref());
+        assert_eq!(times[1].0, b"/etc/hosts".as_ref());
+        assert!((times[1].1 - 0.3).abs() < 1e-4);
+
+        assert_eq!(
+            top_errno(&pid_data_map[&477]),
+            Some((&b"ENOENT"[..], 3, &b"stat"[..]))
+        );
+    }
+
+    #[test]
+    fn pid_budget_files_merged_by_alias() {
+        let input = br##"477   00:09:56.000000 openat(AT_FDCWD, "/var/log/app.log", O_RDONLY) = 3</var/log/app/current> <0.000100>
+477   00:09:56.100000 read(3</var/log/app/current>, "a", 1) = 1 <0.000200>"##;
+        let pid_data_map = build_syscall_data(input);
+
+        let times = &file_times(&[477], &pid_data_map, &PathAliases::default())[&477];
+        assert_eq!(times.len(), 2);
+
+        let aliases = PathAliases::from_trace(&pid_data_map);
+        let times = &file_times(&[477], &pid_data_map, &aliases)[&477];
+        assert_eq!(times.len(), 1);
+        assert_eq!(times[0].0, b"/var/log/app/current".as_ref());
+        assert!((times[0].1 - 0.3).abs() < 1e-4);
+    }
+
+    #[test]
+    fn pid_tree_rolls_up_descendants() {
+        let input = br##"477   00:09:56.000000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f3ac1c75a10) = 600 <0.001000>
+477   00:09:56.100000 clone(child_stack=0x7f3ac1c74fb0, flags=CLONE_VM|CLONE_FS|CLONE_FILES|CLONE_SIGHAND|CLONE_THREAD|CLONE_SYSVSEM, parent_tid=[601], tls=0x7f3ac1c75700, child_tidptr=0x7f3ac1c759d0) = 601 <0.001000>
+600   00:09:56.200000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f3ac1c75a10) = 700 <0.002000>
+601   00:09:56.300000 stat("/etc/hosts", {st_mode=S_IFREG|0644, st_size=174, ...}) = 0 <0.004000>
+700   00:09:56.400000 stat("/etc/hosts", {st_mode=S_IFREG|0644, st_size=174, ...}) = 0 <0.008000>
+700   00:09:56.500000 wait4(-1, [{WIFEXITED(s) && WEXITSTATUS(s) == 0}], 0, NULL) = 701 <1.000000>"##;
+        let pid_data_map = build_syscall_data(input);
+        let syscall_stats = build_syscall_stats(&pid_data_map);
+        let summary = SessionSummary::from_syscall_stats(&syscall_stats, &pid_data_map);
+        let rollups = pid_tree::rollups(&[477, 600, 601, 700], &summary.pid_summaries);
+
+        assert_eq!(rollups[&477].self_calls, 2);
+        assert!((rollups[&477].self_time - 2.0).abs() < 1e-3);
+        assert_eq!(rollups[&477].calls, 6);
+        assert!((rollups[&477].time - 16.0).abs() < 1e-3);
+        assert_eq!(rollups[&601].calls, 1);
+        assert_eq!(rollups[&600].calls, 3);
+        // Waiting in 'wait4' is left out of active time
+        assert!((rollups[&600].time - 10.0).abs() < 1e-3);
+        assert!((rollups[&700].self_time - 8.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn pid_tree_timeline_bars() {
+        let input = br##"477   00:09:50.000000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f3ac1c75a10) = 600 <0.001000>
+600   00:09:51.000000 stat("/etc/hosts", {st_mode=S_IFREG|0644, st_size=174, ...}) = 0 <0.001000>
+600   00:09:52.030000 stat("/etc/hosts", {st_mode=S_IFREG|0644, st_size=174, ...}) = 0 <0.001000>
+477   00:09:54.000000 stat("/etc/hosts", {st_mode=S_IFREG|0644, st_size=174, ...}) = 0 <0.001000>"##;
+        let pid_data_map = build_syscall_data(input);
+        let syscall_stats = build_syscall_stats(&pid_data_map);
+        let summary = SessionSummary::from_syscall_stats(&syscall_stats, &pid_data_map);
+        let timeline = pid_tree::Timeline::new(&summary.pid_summaries).unwrap();
+
+        let bar = |pid: Pid| {
+            let s = &summary.pid_summaries[&pid];
+            timeline.bar(s.start_time, s.end_time)
+        };
+        assert_eq!(bar(477), "█".repeat(pid_tree::TIMELINE_WIDTH));
+        // 1s to 2.03s of 4s is cells 10 to 20.3
+        assert_eq!(
+            bar(600),
+            format!("{}{}▎{}", " ".repeat(10), "█".repeat(10), " ".repeat(19))
+        );
+        assert_eq!(
+            timeline.bar(b"junk", b"00:09:51.000000").chars().count(),
+            40
+        );
+    }
+
+    #[test]
+    fn json_reports_parse_back() {
+        let input = br##"477   00:09:50.000000 execve("/usr/bin/ruby", ["ruby", "app.rb"], 0x7ffc0 /* 10 vars */) = 0 <0.000100>
+477   00:09:50.100000 openat(AT_FDCWD, "/etc/app/config.yml", O_RDONLY) = 3</etc/app/config.yml> <0.000200>
+477   00:09:50.200000 read(3</etc/app/config.yml>, "production:\n", 4096) = 12 <0.000300>
+477   00:09:50.300000 stat("/etc/missing", 0x7ffc6d3c2df0) = -1 ENOENT (No such file or directory) <0.000050>
+477   00:09:50.400000 +++ exited with 0 +++"##;
+        let pid_data_map = build_syscall_data(input);
+        let syscall_stats = build_syscall_stats(&pid_data_map);
+        let summary = SessionSummary::from_syscall_stats(&syscall_stats, &pid_data_map);
+        let parse = |out: Vec<u8>| serde_json::from_slice::<Value>(&out).unwrap();
+        let first = |value: &Value, key: &str| match value.get(key) {
+            Some(Value::Array(records)) => records[0].clone(),
+            _ => panic!("no {} in {:?}", key, value),
+        };
+        let num = |value: &Value, key: &str| value.get(key).and_then(Value::as_f64);
+
+        let mut out = Vec::new();
+        summary
+            .write_summary_json(&mut out, None, 10, SortBy::ActiveTime)
+            .unwrap();
+        let report = parse(out);
+        let pid = first(&report, "pids");
+        assert_eq!(num(&pid, "pid"), Some(477.0));
+        assert_eq!(num(&pid, "syscalls"), Some(4.0));
+        assert_eq!(num(&pid, "errors"), Some(1.0));
+        assert_eq!(num(&pid, "exit_code"), Some(0.0));
+        assert_eq!(num(&report, "pid_count"), Some(1.0));
+        assert_eq!(report.get("real_ms"), Some(&Value::Null));
+
+        let mut out = Vec::new();
+        summary
+            .write_pid_list_json(&mut out, 10, SortBy::ActiveTime)
+            .unwrap();
+        let stats = first(&first(&parse(out), "pids"), "syscall_stats");
+        assert!(stats.get("syscall").and_then(Value::as_str).is_some());
+
+        let mut out = Vec::new();
+        summary
+            .write_pid_details_json(&mut out, &[477], &pid_data_map, &HashMap::default())
+            .unwrap();
+        let open = first(&first(&parse(out), "pids"), "slowest_opens");
+        assert_eq!(
+            open.get("path").and_then(Value::as_str),
+            Some("/etc/app/config.yml")
+        );
+
+        let mut out = Vec::new();
+        summary
+            .write_io_json(&mut out, &[477], &pid_data_map, SortEventsBy::Time)
+            .unwrap();
+        let value = parse(out);
+        let io = first(&value, "io");
+        assert_eq!(num(&io, "bytes"), Some(12.0));
+        assert_eq!(num(&io, "requested"), Some(4096.0));
+        assert_eq!(num(&first(&value, "file_bytes"), "read"), Some(12.0));
+        assert_eq!(num(&first(&value, "pid_bytes"), "written"), Some(0.0));
+
+        let mut out = Vec::new();
+        summary
+            .write_opened_files_json(&mut out, &[477], &pid_data_map, SortEventsBy::Time, true)
+            .unwrap();
+        let file = first(&parse(out), "files");
+        assert_eq!(num(&file, "opens"), Some(1.0));
+
+        let mut out = Vec::new();
+        summary
+            .write_opened_directories_json(&mut out, &[477], &pid_data_map, SortEventsBy::Pid)
+            .unwrap();
+        assert!(matches!(
+            parse(out).get("directories"),
+            Some(Value::Array(_))
+        ));
+
+        let mut out = Vec::new();
+        summary.write_exec_list_json(&mut out, &[477]).unwrap();
+        let exec = first(&parse(out), "execs");
+        assert_eq!(
+            exec.get("cmd").and_then(Value::as_str),
+            Some("/usr/bin/ruby app.rb")
+        );
+    }
 }
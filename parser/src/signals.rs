@@ -0,0 +1,239 @@
+use crate::output;
+use crate::parser::{self, CallStatus, LineData};
+use crate::table::{Align, Column, Table};
+use crate::time::parse_timestamp_us;
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::io::Error;
+
+/// Signals delivered to a PID, and the syscalls made by its handlers
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HandlerCalls<'a> {
+    pub pid: Pid,
+    pub signals: BTreeMap<&'a [u8], usize>,
+    /// Deliveries whose handler returned with 'rt_sigreturn'
+    pub returned: usize,
+    /// Syscalls made between delivery and 'rt_sigreturn', including it
+    pub calls: usize,
+    /// Time in the handlers' syscalls, in ms
+    pub time: f32,
+    /// Time in all of the PID's syscalls, in ms
+    pub pid_time: f32,
+    pub syscalls: HashMap<&'a [u8], usize>,
+}
+
+impl<'a> HandlerCalls<'a> {
+    pub fn delivered(&self) -> usize {
+        self.signals.values().sum()
+    }
+}
+
+enum Event<'a> {
+    Signal(&'a [u8]),
+    Call {
+        syscall: &'a [u8],
+        status: CallStatus,
+        duration: f32,
+    },
+}
+
+// The syscalls and durations of a handler still running
+type HandlerRun<'a> = Vec<(&'a [u8], f32)>;
+
+/// For each of `pids` that was sent a signal, the syscalls made from when
+/// the signal was delivered until its handler returned with 'rt_sigreturn',
+/// with the PIDs spending the most time in handlers first. Signals without a
+/// handler never see 'rt_sigreturn', so the calls that follow them are not
+/// counted unless a later signal's handler is running.
+pub fn signal_handlers<'a>(buffer: &'a [u8], pids: &[Pid]) -> Vec<HandlerCalls<'a>> {
+    let pids: HashSet<Pid> = pids.iter().copied().collect();
+    let start = buffer.as_ptr() as usize;
+
+    let mut events: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(|line| {
+            let line_data = parser::parse_line(line)?;
+            let pid = line_data.pid();
+            if !pids.contains(&pid) {
+                return None;
+            }
+
+            let (time, event) = match line_data {
+                LineData::Signal(signal) => (signal.time, Event::Signal(signal.signal)),
+                LineData::Syscall(raw_data) => (
+                    raw_data.time,
+                    Event::Call {
+                        syscall: raw_data.syscall,
+                        status: raw_data.call_status,
+                        duration: raw_data.duration.unwrap_or_default() * 1000.0,
+                    },
+                ),
+                LineData::Exit(_) => return None,
+            };
+
+            // Lines with the same timestamp are kept in the order they were printed
+            let offset = line.as_ptr() as usize - start;
+            Some(((pid, parse_timestamp_us(time)?, offset), event))
+        })
+        .collect();
+    events.par_sort_unstable_by_key(|(key, _)| *key);
+
+    let mut handlers: HashMap<Pid, HandlerCalls> = HashMap::default();
+    // Each PID's handlers still running, innermost last, with the calls they made
+    let mut running: HashMap<Pid, Vec<HandlerRun>> = HashMap::default();
+
+    for ((pid, _, _), event) in events {
+        let pid_handlers = handlers.entry(pid).or_insert_with(|| HandlerCalls {
+            pid,
+            ..HandlerCalls::default()
+        });
+        let stack = running.entry(pid).or_default();
+
+        let (syscall, status, duration) = match event {
+            Event::Signal(signal) => {
+                *pid_handlers.signals.entry(signal).or_default() += 1;
+                stack.push(Vec::new());
+                continue;
+            }
+            Event::Call {
+                syscall,
+                status,
+                duration,
+            } => (syscall, status, duration),
+        };
+
+        // Started lines are counted when the call resumes
+        if !matches!(status, CallStatus::Started) {
+            pid_handlers.pid_time += duration;
+        }
+
+        let calls = match stack.last_mut() {
+            Some(calls) => calls,
+            None => continue,
+        };
+
+        match (syscall, status) {
+            (b"rt_sigreturn" | b"sigreturn", CallStatus::Resumed) => {}
+            (b"rt_sigreturn" | b"sigreturn", _) => {
+                calls.push((syscall, duration));
+                pid_handlers.returned += 1;
+                for (syscall, duration) in stack.pop().unwrap_or_default() {
+                    pid_handlers.calls += 1;
+                    pid_handlers.time += duration;
+                    *pid_handlers.syscalls.entry(syscall).or_default() += 1;
+                }
+            }
+            (_, CallStatus::Started) => {}
+            _ => calls.push((syscall, duration)),
+        }
+    }
+
+    let mut handlers: Vec<_> = handlers
+        .into_values()
+        .filter(|h| !h.signals.is_empty())
+        .collect();
+    handlers.sort_by(|x, y| {
+        (y.time)
+            .total_cmp(&x.time)
+            .then_with(|| y.calls.cmp(&x.calls))
+            .then_with(|| x.pid.cmp(&y.pid))
+    });
+
+    handlers
+}
+
+pub fn print_signal_handlers(
+    buffer: &[u8],
+    pids: &[Pid],
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let handlers = signal_handlers(buffer, pids);
+
+    output::print_heading("\nSyscalls Made in Signal Handlers\n-----------\n")?;
+
+    if handlers.is_empty() {
+        output::print_note("  No signals delivered\n")?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("pid", 7, Align::Left, 0),
+        Column::new("delivered", 9, Align::Right, 2),
+        Column::new("returned", 8, Align::Right, 3),
+        Column::new("calls", 8, Align::Right, 0),
+        Column::new("time (ms)", 10, Align::Right, 0),
+        Column::new("% of time", 9, Align::Right, 1),
+        Column::new("signals", 7, Align::Left, 4),
+        Column::new("syscalls", 8, Align::Left, 5),
+    ]);
+
+    for handler in handlers.iter().take(count) {
+        let mut syscalls: Vec<_> = handler.syscalls.iter().collect();
+        syscalls.sort_by(|(x_name, x_ct), (y_name, y_ct)| y_ct.cmp(x_ct).then(x_name.cmp(y_name)));
+
+        let pct = if handler.pid_time > 0.0 {
+            format!("{:.2}%", handler.time / handler.pid_time * 100.0)
+        } else {
+            String::from("-")
+        };
+
+        table.add_row(vec![
+            output::pid_label(handler.pid),
+            handler.delivered().to_string(),
+            handler.returned.to_string(),
+            handler.calls.to_string(),
+            format!("{:.3}", handler.time),
+            pct,
+            counted_names(handler.signals.iter().map(|(name, ct)| (*name, *ct))),
+            counted_names(syscalls.into_iter().take(3).map(|(name, ct)| (*name, *ct))),
+        ]);
+    }
+    table.print(width)?;
+
+    output::print_note(
+        "\n  Signals with no handler are never followed by 'rt_sigreturn', so are not matched to calls\n",
+    )
+}
+
+fn counted_names<'a>(names: impl Iterator<Item = (&'a [u8], usize)>) -> String {
+    names
+        .map(|(name, ct)| format!("{} x{}", name.to_str_lossy(), ct))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handler_calls_bracketed_by_sigreturn() {
+        let input = br##"9071  10:14:21.000000 read(0</dev/null>, "", 10) = 0 <0.000100>
+9071  10:14:21.100000 --- SIGCHLD {si_signo=SIGCHLD, si_code=CLD_EXITED, si_pid=9075, si_uid=998, si_status=0, si_utime=0, si_stime=0} ---
+9071  10:14:21.200000 read(0</dev/null>, "", 10) = 0 <0.000100>
+9071  10:14:21.300000 --- SIGALRM {si_signo=SIGALRM, si_code=SI_KERNEL} ---
+9071  10:14:21.300000 write(2</dev/pts/0>, "tick\n", 5 <unfinished ...>
+9072  10:14:21.300100 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <0.000010>
+9071  10:14:21.300300 <... write resumed>) = 5 <0.000300>
+9071  10:14:21.300400 getpid() = 9071 <0.000100>
+9071  10:14:21.300500 rt_sigreturn({mask=[]}) = 0 <0.000100>
+9071  10:14:21.400000 getpid() = 9071 <0.000500>"##;
+        let handlers = signal_handlers(input, &[9071, 9072]);
+
+        assert_eq!(handlers.len(), 1);
+        assert_eq!(handlers[0].pid, 9071);
+        assert_eq!(handlers[0].delivered(), 2);
+        assert_eq!(handlers[0].returned, 1);
+        assert_eq!(handlers[0].calls, 3);
+        assert!((handlers[0].time - 0.5).abs() < 1e-4);
+        assert!((handlers[0].pid_time - 1.2).abs() < 1e-4);
+        assert_eq!(handlers[0].syscalls[b"write".as_ref()], 1);
+        assert!(!handlers[0].syscalls.contains_key(b"read".as_ref()));
+
+        assert!(signal_handlers(input, &[9072]).is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+use crate::output;
+use crate::parser::{self, CallStatus, LineData, RawData};
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::time::{elapsed_ms, parse_timestamp_us};
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::io::Error;
+
+// Calls that put the PID to sleep for a time it asks for
+const SLEEP_SYSCALLS: &[&[u8]] = &[b"clock_nanosleep", b"nanosleep"];
+
+/// How a PID slept over the trace: with 'nanosleep' and 'clock_nanosleep',
+/// and the timers it armed with 'timerfd_settime'
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SleepSummary {
+    pub pid: Pid,
+    pub sleeps: usize,
+    /// Time asleep, in ms
+    pub asleep: f64,
+    /// Time between the PID's first and last lines, in ms
+    pub elapsed: f64,
+    /// The sleep asked for most often, in ms
+    pub usual_request: Option<f64>,
+    /// Sleeps that ran to the time asked for, which oversleep is measured on
+    pub completed: usize,
+    /// Time slept past what was asked for, in ms
+    pub oversleep: f64,
+    pub max_oversleep: f64,
+    /// Sleeps cut short, e.g. by a signal
+    pub interrupted: usize,
+    pub timers_set: usize,
+    /// The interval asked for most often when arming a timer, in ms
+    pub usual_interval: Option<f64>,
+}
+
+impl SleepSummary {
+    /// Sleeps per second of the PID's time in the trace
+    pub fn per_sec(&self) -> Option<f64> {
+        Some(self.sleeps as f64 / self.elapsed * 1000.0).filter(|r| r.is_finite())
+    }
+
+    pub fn asleep_pct(&self) -> Option<f64> {
+        Some(self.asleep / self.elapsed * 100.0).filter(|p| p.is_finite())
+    }
+
+    pub fn avg_oversleep(&self) -> Option<f64> {
+        Some(self.oversleep / self.completed as f64).filter(|o| o.is_finite())
+    }
+}
+
+// One call, joined with its resumed line
+struct SleepCall {
+    pid: Pid,
+    timer: bool,
+    /// Seconds asked for, unknown for 'TIMER_ABSTIME' sleeps to a set time
+    requested: Option<f64>,
+    duration: Option<f64>,
+    interrupted: bool,
+}
+
+/// Sums up the sleeps and timers of each of `pids`, the longest asleep first
+pub fn sleep_summaries(
+    buffer: &[u8],
+    pids: &[Pid],
+    pid_data: &HashMap<Pid, PidData>,
+) -> Vec<SleepSummary> {
+    let pid_set: HashSet<Pid> = pids.iter().copied().collect();
+
+    let mut lines: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter(|line| parser::line_pid(line).is_some_and(|pid| pid_set.contains(&pid)))
+        .filter_map(|line| match parser::parse_line(line)? {
+            LineData::Syscall(raw_data) if is_sleep(&raw_data, line) => {
+                Some((parse_timestamp_us(raw_data.time)?, line, raw_data))
+            }
+            _ => None,
+        })
+        .collect();
+    lines.par_sort_by_key(|(time, _, _)| *time);
+
+    let mut calls = Vec::new();
+    let mut started: HashMap<Pid, (&[u8], RawData)> = HashMap::default();
+    for (_, line, raw_data) in lines {
+        match raw_data.call_status {
+            CallStatus::Started => {
+                started.insert(raw_data.pid, (line, raw_data));
+            }
+            CallStatus::Complete => calls.push(sleep_call(line, &raw_data, &raw_data)),
+            CallStatus::Resumed => {
+                if let Some((first_line, first)) = started.remove(&raw_data.pid) {
+                    calls.push(sleep_call(first_line, &first, &raw_data));
+                }
+            }
+        }
+    }
+
+    let mut by_pid: HashMap<Pid, Vec<SleepCall>> = HashMap::default();
+    for call in calls {
+        by_pid.entry(call.pid).or_default().push(call);
+    }
+
+    let mut summaries: Vec<_> = by_pid
+        .into_iter()
+        .map(|(pid, calls)| {
+            let elapsed = pid_data
+                .get(&pid)
+                .and_then(|d| elapsed_ms(d.start_time, d.end_time))
+                .map_or(0.0, f64::from);
+            summarize(pid, &calls, elapsed)
+        })
+        .collect();
+    summaries.sort_by(|x, y| y.asleep.total_cmp(&x.asleep).then(x.pid.cmp(&y.pid)));
+
+    summaries
+}
+
+fn is_sleep(raw_data: &RawData, line: &[u8]) -> bool {
+    SLEEP_SYSCALLS.contains(&raw_data.syscall)
+        || raw_data.syscall == b"timerfd_settime"
+        // 1093  00:09:47.789807 restart_syscall(<... resuming interrupted nanosleep ...> <unfinished ...>
+        || (raw_data.syscall == b"restart_syscall" && line.contains_str("nanosleep"))
+}
+
+// 'first' holds the call's arguments, 'last' its result
+fn sleep_call(line: &[u8], first: &RawData, last: &RawData) -> SleepCall {
+    let args = parser::line_args(line);
+    let timer = first.syscall == b"timerfd_settime";
+    let requested = match first.syscall {
+        b"nanosleep" => args.first().and_then(|a| timespec_secs(a)),
+        b"clock_nanosleep" if !args.get(1).is_some_and(|f| f.contains_str("TIMER_ABSTIME")) => {
+            args.get(2).and_then(|a| timespec_secs(a))
+        }
+        // 7390  10:00:00.000000 timerfd_settime(5, 0, {it_interval={tv_sec=1, tv_nsec=0}, it_value={tv_sec=1, tv_nsec=0}}, NULL) = 0
+        b"timerfd_settime" => args.get(2).and_then(|a| {
+            let interval = a.find("it_interval=").map(|idx| &a[idx + 12..])?;
+            let end = interval.find_byte(b'}')?;
+            timespec_secs(&interval[..=end])
+        }),
+        _ => None,
+    };
+
+    SleepCall {
+        pid: first.pid,
+        timer,
+        requested,
+        duration: last.duration.map(f64::from),
+        interrupted: last.error.is_some(),
+    }
+}
+
+// Seconds in a timespec printed as '{1, 500000000}' or
+// '{tv_sec=1, tv_nsec=500000000}'
+fn timespec_secs(arg: &[u8]) -> Option<f64> {
+    let inner = arg.trim().strip_prefix(b"{")?.strip_suffix(b"}")?;
+    let mut fields = inner.split_str(",").map(|f| {
+        let f = f.trim();
+        let value = f.rfind_byte(b'=').map_or(f, |idx| &f[idx + 1..]);
+        value.to_str().ok()?.parse::<i64>().ok()
+    });
+    let secs = fields.next()??;
+    let nsecs = fields.next()??;
+
+    Some(secs as f64 + nsecs as f64 / 1e9)
+}
+
+fn summarize(pid: Pid, calls: &[SleepCall], elapsed: f64) -> SleepSummary {
+    let mut summary = SleepSummary {
+        pid,
+        elapsed,
+        ..SleepSummary::default()
+    };
+    let mut requests: HashMap<i64, usize> = HashMap::default();
+    let mut intervals: HashMap<i64, usize> = HashMap::default();
+
+    for call in calls {
+        if call.timer {
+            summary.timers_set += 1;
+            if let Some(interval) = call.requested.filter(|i| *i > 0.0) {
+                *intervals.entry((interval * 1e6) as i64).or_default() += 1;
+            }
+            continue;
+        }
+
+        summary.sleeps += 1;
+        summary.asleep += call.duration.unwrap_or_default() * 1000.0;
+        if let Some(requested) = call.requested {
+            *requests.entry((requested * 1e6) as i64).or_default() += 1;
+        }
+        if call.interrupted {
+            summary.interrupted += 1;
+            continue;
+        }
+        if let (Some(requested), Some(duration)) = (call.requested, call.duration) {
+            let oversleep = ((duration - requested) * 1000.0).max(0.0);
+            summary.completed += 1;
+            summary.oversleep += oversleep;
+            summary.max_oversleep = summary.max_oversleep.max(oversleep);
+        }
+    }
+
+    let usual = |counts: HashMap<i64, usize>| {
+        counts
+            .into_iter()
+            .max_by_key(|&(us, count)| (count, Reverse(us)))
+            .map(|(us, _)| us as f64 / 1000.0)
+    };
+    summary.usual_request = usual(requests);
+    summary.usual_interval = usual(intervals);
+
+    summary
+}
+
+pub fn print_sleeps(
+    buffer: &[u8],
+    pids: &[Pid],
+    pid_data: &HashMap<Pid, PidData>,
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let summaries = sleep_summaries(buffer, pids, pid_data);
+
+    output::print_heading("\nSleeps and Timers\n-----------\n")?;
+    if summaries.is_empty() {
+        output::print_note(
+            "  No PID called 'nanosleep' or 'clock_nanosleep', or set a timer with 'timerfd_settime'\n",
+        )?;
+        return Ok(());
+    }
+
+    let ms = |ms: Option<f64>| ms.map_or_else(|| "-".to_string(), |ms| format!("{:.3}", ms));
+    let mut table = Table::new(vec![
+        Column::new("pid", 7, Align::Left, 0),
+        Column::new("sleeps", 8, Align::Right, 0),
+        Column::new("per sec", 8, Align::Right, 3),
+        Column::new("asleep (ms)", 12, Align::Right, 0),
+        Column::new("% of time", 9, Align::Right, 2),
+        Column::new("usual (ms)", 10, Align::Right, 1),
+        Column::new("avg over (ms)", 13, Align::Right, 1),
+        Column::new("max over (ms)", 13, Align::Right, 2),
+        Column::new("cut short", 9, Align::Right, 3),
+        Column::new("timers", 6, Align::Right, 4),
+        Column::new("interval (ms)", 13, Align::Right, 4),
+    ]);
+    for summary in summaries.iter().take(count) {
+        table.add_row(vec![
+            output::pid_label(summary.pid),
+            summary.sleeps.to_string(),
+            summary
+                .per_sec()
+                .map_or_else(|| "-".to_string(), |r| format!("{:.2}", r)),
+            format!("{:.3}", summary.asleep),
+            summary
+                .asleep_pct()
+                .map_or_else(|| "-".to_string(), |p| format!("{:.2}%", p)),
+            ms(summary.usual_request),
+            ms(summary.avg_oversleep()),
+            ms(Some(summary.max_oversleep).filter(|_| summary.completed > 0)),
+            summary.interrupted.to_string(),
+            summary.timers_set.to_string(),
+            ms(summary.usual_interval),
+        ]);
+    }
+    table.print(width)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn sleeps_measure_oversleep() {
+        let input = br##"7390  10:00:00.000000 nanosleep({1, 0},  <unfinished ...>
+7391  10:00:00.000000 timerfd_settime(5, 0, {it_interval={tv_sec=0, tv_nsec=250000000}, it_value={tv_sec=0, tv_nsec=250000000}}, NULL) = 0 <0.000010>
+7390  10:00:01.002000 <... nanosleep resumed> NULL) = 0 <1.002000>
+7390  10:00:01.003000 nanosleep({tv_sec=1, tv_nsec=0}, NULL) = 0 <1.010000>
+7390  10:00:02.013000 clock_nanosleep(CLOCK_MONOTONIC, 0, {tv_sec=0, tv_nsec=500000000}, 0x7ffc) = ? ERESTART_RESTARTBLOCK (Interrupted by signal) <0.100000>
+7390  10:00:02.113000 clock_nanosleep(CLOCK_REALTIME, TIMER_ABSTIME, {tv_sec=1600000000, tv_nsec=0}, NULL) = 0 <0.300000>
+7390  10:00:03.000000 write(1, "a", 1) = 1 <0.000010>"##;
+        let pid_data = build_syscall_data(input);
+        let summaries = sleep_summaries(input, &[7390, 7391], &pid_data);
+
+        assert_eq!(summaries.len(), 2);
+        let sleeper = &summaries[0];
+        assert_eq!(sleeper.pid, 7390);
+        assert_eq!(sleeper.sleeps, 4);
+        assert!((sleeper.asleep - 2412.0).abs() < 0.01);
+        assert_eq!(sleeper.usual_request, Some(1000.0));
+        assert_eq!((sleeper.completed, sleeper.interrupted), (2, 1));
+        assert!((sleeper.avg_oversleep().unwrap() - 6.0).abs() < 0.01);
+        assert!((sleeper.max_oversleep - 10.0).abs() < 0.01);
+        assert!((sleeper.per_sec().unwrap() - 4.0 / 3.0).abs() < 0.01);
+
+        let timer = &summaries[1];
+        assert_eq!((timer.pid, timer.sleeps, timer.timers_set), (7391, 0, 1));
+        assert_eq!(timer.usual_interval, Some(250.0));
+    }
+}
@@ -45,6 +45,7 @@ impl FromStr for SortBy {
 
 #[derive(Clone, Copy)]
 pub enum SortEventsBy {
+    Bytes,
     Count,
     Duration,
     Pid,
@@ -54,6 +55,7 @@ pub enum SortEventsBy {
 impl fmt::Display for SortEventsBy {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
+            SortEventsBy::Bytes => write!(f, "Bytes"),
             SortEventsBy::Count => write!(f, "Count"),
             SortEventsBy::Duration => write!(f, "Duration"),
             SortEventsBy::Pid => write!(f, "PID #"),
@@ -67,6 +69,7 @@ impl FromStr for SortEventsBy {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
+            "bytes" => Ok(SortEventsBy::Bytes),
             "count" => Ok(SortEventsBy::Count),
             "duration" => Ok(SortEventsBy::Duration),
             "pid" => Ok(SortEventsBy::Pid),
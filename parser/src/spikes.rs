@@ -0,0 +1,205 @@
+use crate::output;
+use crate::parser::{self, CallStatus, LineData};
+use crate::table::{Align, Column, Table};
+use crate::time::{format_timestamp_us, parse_timestamp_us};
+use crate::HashMap;
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::io::Error;
+
+const US_PER_SEC: i64 = 1_000_000;
+
+/// The second of the trace in which a syscall was called most often
+#[derive(Clone, Debug, PartialEq)]
+pub struct BusiestSecond<'a> {
+    pub syscall: &'a [u8],
+    pub calls: usize,
+    /// Time in the syscall over the whole trace, in ms
+    pub total: f64,
+    /// Start of the second, as from `parse_timestamp_us`
+    pub second_us: i64,
+    pub second_calls: usize,
+    /// Time in calls started during the second, in ms
+    pub second_total: f64,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Totals {
+    calls: usize,
+    total_us: i64,
+}
+
+impl Totals {
+    fn add(&mut self, other: Totals) {
+        self.calls += other.calls;
+        self.total_us += other.total_us;
+    }
+}
+
+type SecondTotals<'a> = HashMap<(&'a [u8], i64), Totals>;
+
+/// For the `count` syscalls with the most time in them, the second each was
+/// called most often, breaking ties by time in the calls. A timestamp from
+/// the trace is returned to format the seconds.
+pub fn busiest_seconds(buffer: &[u8], count: usize) -> (Vec<BusiestSecond<'_>>, Option<&[u8]>) {
+    let (seconds, first_time) = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(parser::parse_line)
+        .filter_map(|line| match line {
+            LineData::Syscall(raw_data) => Some(raw_data),
+            _ => None,
+        })
+        .fold(
+            || (SecondTotals::default(), None),
+            |(mut seconds, first_time), raw_data| {
+                let time = match parse_timestamp_us(raw_data.time) {
+                    Some(time) => time,
+                    None => return (seconds, first_time),
+                };
+                let duration = raw_data
+                    .duration
+                    .map_or(0, |d| (d as f64 * US_PER_SEC as f64).round() as i64);
+
+                // Calls are counted in the second they started
+                let start = match raw_data.call_status {
+                    CallStatus::Complete => time,
+                    CallStatus::Resumed => time - duration,
+                    CallStatus::Started => return (seconds, first_time.or(Some(raw_data.time))),
+                };
+
+                seconds
+                    .entry((raw_data.syscall, start.div_euclid(US_PER_SEC)))
+                    .or_default()
+                    .add(Totals {
+                        calls: 1,
+                        total_us: duration,
+                    });
+                (seconds, first_time.or(Some(raw_data.time)))
+            },
+        )
+        .reduce(
+            || (SecondTotals::default(), None),
+            |(mut seconds, first_time), (other, other_time)| {
+                for (key, totals) in other {
+                    seconds.entry(key).or_default().add(totals);
+                }
+                (seconds, first_time.or(other_time))
+            },
+        );
+
+    let mut syscalls: HashMap<&[u8], (Totals, i64, Totals)> = HashMap::default();
+    for ((syscall, second), totals) in seconds {
+        let (all, busiest_sec, busiest) =
+            syscalls
+                .entry(syscall)
+                .or_insert((Totals::default(), second, totals));
+        all.add(totals);
+
+        let busier = (totals.calls, totals.total_us, -second)
+            > (busiest.calls, busiest.total_us, -*busiest_sec);
+        if busier {
+            *busiest_sec = second;
+            *busiest = totals;
+        }
+    }
+
+    let mut busiest: Vec<_> = syscalls
+        .into_iter()
+        .map(|(syscall, (all, second, totals))| BusiestSecond {
+            syscall,
+            calls: all.calls,
+            total: all.total_us as f64 / 1000.0,
+            second_us: second * US_PER_SEC,
+            second_calls: totals.calls,
+            second_total: totals.total_us as f64 / 1000.0,
+        })
+        .collect();
+    busiest.sort_by(|x, y| {
+        (y.total)
+            .total_cmp(&x.total)
+            .then_with(|| (x.syscall).cmp(y.syscall))
+    });
+    busiest.truncate(count);
+
+    (busiest, first_time)
+}
+
+pub fn print_busiest_seconds(
+    buffer: &[u8],
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let (busiest, first_time) = busiest_seconds(buffer, count);
+    let time_layout = match first_time {
+        Some(t) if !busiest.is_empty() => t,
+        _ => return Ok(()),
+    };
+
+    output::print_heading(&format!(
+        "\nBusiest Second of the Top {} Syscalls\n-----------\n",
+        busiest.len()
+    ))?;
+
+    let mut table = Table::new(vec![
+        Column::new("syscall", 17, Align::Left, 0),
+        Column::new("calls", 8, Align::Right, 3),
+        Column::new("total (ms)", 10, Align::Right, 2),
+        Column::new("busiest second", 15, Align::Left, 0),
+        Column::new("calls in sec", 12, Align::Right, 0),
+        Column::new("ms in sec", 10, Align::Right, 1),
+        Column::new("% of calls", 10, Align::Right, 4),
+    ]);
+
+    for second in &busiest {
+        let timestamp =
+            output::timestamp(format_timestamp_us(second.second_us, time_layout).as_bytes());
+        table.add_row(vec![
+            second.syscall.to_str_lossy().to_string(),
+            second.calls.to_string(),
+            format!("{:.3}", second.total),
+            // Whole seconds, the fraction is always zero
+            timestamp.replacen(".000000", "", 1),
+            second.second_calls.to_string(),
+            format!("{:.3}", second.second_total),
+            format!(
+                "{:.2}%",
+                second.second_calls as f64 / second.calls as f64 * 100.0
+            ),
+        ]);
+    }
+
+    table.print(width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn busiest_second_found_per_syscall() {
+        let input = br##"477   00:09:46.100000 stat("/a", 0x7ffd4c3b8d70) = -1 ENOENT (No such file or directory) <0.000100>
+477   00:09:47.100000 stat("/b", 0x7ffd4c3b8d70) = -1 ENOENT (No such file or directory) <0.000100>
+477   00:09:47.200000 stat("/c", 0x7ffd4c3b8d70) = -1 ENOENT (No such file or directory) <0.000100>
+495   00:09:47.300000 read(0</dev/null>,  <unfinished ...>
+477   00:09:47.900000 stat("/d", 0x7ffd4c3b8d70) = -1 ENOENT (No such file or directory) <0.000100>
+495   00:09:48.500000 <... read resumed>"", 10) = 0 <1.200000>
+495   00:09:48.600000 read(0</dev/null>, "", 10) = 0 <0.000010>"##;
+        let (busiest, first_time) = busiest_seconds(input, 5);
+
+        assert_eq!(first_time, Some(b"00:09:46.100000".as_ref()));
+        assert_eq!(busiest.len(), 2);
+        assert_eq!(busiest[0].syscall, b"read");
+        assert_eq!(busiest[0].calls, 2);
+        assert_eq!(
+            format_timestamp_us(busiest[0].second_us, b"00:09:46.100000"),
+            "00:09:47.000000"
+        );
+        assert_eq!(busiest[1].syscall, b"stat");
+        assert_eq!(busiest[1].second_calls, 3);
+        assert!((busiest[1].second_total - 0.3).abs() < 1e-6);
+
+        let (busiest, _) = busiest_seconds(input, 1);
+        assert_eq!(busiest.len(), 1);
+    }
+}
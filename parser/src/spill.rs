@@ -0,0 +1,431 @@
+use crate::namespaces::Namespaces;
+use crate::parser::{self, ExitType, LineData, RawData};
+use crate::syscall_data::{PidData, RawExec, SyscallData};
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::convert::TryFrom;
+use std::io::{prelude::*, BufReader, BufWriter, Error, ErrorKind, SeekFrom};
+
+/// Parts of the per-PID data written to a file while the trace is read, so
+/// that only the part being built is held in memory, and read back to be
+/// merged once the whole trace has been read.
+///
+/// The data borrows from the trace, so a part holds offsets into `buffer`
+/// rather than copies of its text, and each call is written as the offset of
+/// its line, which is parsed again as the part is read.
+pub struct Spill<'a, S> {
+    buffer: &'a [u8],
+    file: S,
+    parts: usize,
+}
+
+impl<'a, S: Read + Write + Seek> Spill<'a, S> {
+    /// Spills data parsed from `buffer` to `file`, which should be empty
+    pub fn new(buffer: &'a [u8], file: S) -> Spill<'a, S> {
+        Spill {
+            buffer,
+            file,
+            parts: 0,
+        }
+    }
+
+    /// The number of parts written
+    pub fn parts(&self) -> usize {
+        self.parts
+    }
+
+    /// Writes `data_map` after the parts already written
+    pub fn write_part(&mut self, data_map: &HashMap<Pid, PidData<'a>>) -> Result<(), Error> {
+        self.file.seek(SeekFrom::End(0))?;
+        let mut encoder = Encoder {
+            buffer: self.buffer,
+            out: BufWriter::new(&mut self.file),
+        };
+
+        encoder.len(data_map.len())?;
+        for (pid, pid_data) in data_map {
+            encoder.i32(*pid)?;
+            encoder.pid_data(pid_data)?;
+        }
+        encoder.out.flush()?;
+        self.parts += 1;
+
+        Ok(())
+    }
+
+    /// Reads each part back in the order they were written, handing them to `merge`
+    pub fn read_parts<F>(&mut self, mut merge: F) -> Result<(), Error>
+    where
+        F: FnMut(HashMap<Pid, PidData<'a>>),
+    {
+        self.file.seek(SeekFrom::Start(0))?;
+        let mut decoder = Decoder {
+            buffer: self.buffer,
+            input: BufReader::new(&mut self.file),
+        };
+
+        for _ in 0..self.parts {
+            let pid_ct = decoder.len()?;
+            let mut data_map = HashMap::with_capacity(pid_ct);
+            for _ in 0..pid_ct {
+                let pid = decoder.i32()?;
+                data_map.insert(pid, decoder.pid_data()?);
+            }
+            merge(data_map);
+        }
+
+        Ok(())
+    }
+}
+
+struct Encoder<'a, W> {
+    buffer: &'a [u8],
+    out: W,
+}
+
+impl<'a, W: Write> Encoder<'a, W> {
+    fn u8(&mut self, value: u8) -> Result<(), Error> {
+        self.out.write_all(&[value])
+    }
+
+    fn i32(&mut self, value: i32) -> Result<(), Error> {
+        self.out.write_all(&value.to_le_bytes())
+    }
+
+    fn f32(&mut self, value: f32) -> Result<(), Error> {
+        self.out.write_all(&value.to_le_bytes())
+    }
+
+    fn len(&mut self, value: usize) -> Result<(), Error> {
+        self.out.write_all(&(value as u64).to_le_bytes())
+    }
+
+    fn offset(&mut self, slice: &[u8]) -> Result<usize, Error> {
+        let offset = (slice.as_ptr() as usize).wrapping_sub(self.buffer.as_ptr() as usize);
+        if offset
+            .checked_add(slice.len())
+            .is_none_or(|end| end > self.buffer.len())
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "can't spill data that wasn't read from the trace",
+            ));
+        }
+
+        Ok(offset)
+    }
+
+    fn slice(&mut self, slice: &[u8]) -> Result<(), Error> {
+        let offset = self.offset(slice)?;
+        self.len(offset)?;
+        self.len(slice.len())
+    }
+
+    fn opt_slice(&mut self, slice: Option<&[u8]>) -> Result<(), Error> {
+        match slice {
+            Some(slice) => {
+                self.u8(1)?;
+                self.slice(slice)
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn f32s(&mut self, values: &[f32]) -> Result<(), Error> {
+        self.len(values.len())?;
+        values.iter().try_for_each(|value| self.f32(*value))
+    }
+
+    fn pids(&mut self, pids: &[Pid]) -> Result<(), Error> {
+        self.len(pids.len())?;
+        pids.iter().try_for_each(|pid| self.i32(*pid))
+    }
+
+    // A call is written as the start of its line, which is parsed again when read
+    fn events(&mut self, events: &[RawData]) -> Result<(), Error> {
+        self.len(events.len())?;
+        for event in events {
+            let offset = self.offset(event.time)?;
+            let line_start = self.buffer[..offset]
+                .rfind_byte(b'\n')
+                .map_or(0, |idx| idx + 1);
+            self.len(line_start)?;
+        }
+
+        Ok(())
+    }
+
+    // Only times within the trace are written, a PID without calls keeps
+    // the placeholders `PidData::new` starts it with
+    fn time(&mut self, time: &[u8]) -> Result<(), Error> {
+        match self.offset(time) {
+            Ok(_) => self.opt_slice(Some(time)),
+            Err(_) => self.opt_slice(None),
+        }
+    }
+
+    fn syscall_data(&mut self, data: &SyscallData) -> Result<(), Error> {
+        self.f32s(&data.lengths)?;
+        self.len(data.fast_calls)?;
+        self.f32(data.fast_time)?;
+        self.len(data.errors.len())?;
+        for (error, count) in &data.errors {
+            self.slice(error)?;
+            self.i32(*count)?;
+        }
+        self.len(data.error_lengths.len())?;
+        for (error, lengths) in &data.error_lengths {
+            self.slice(error)?;
+            self.f32s(lengths)?;
+        }
+        self.opt_slice(data.first_time)?;
+        self.opt_slice(data.last_time)
+    }
+
+    fn pid_data(&mut self, pid_data: &PidData) -> Result<(), Error> {
+        self.len(pid_data.syscall_data.len())?;
+        for (syscall, data) in &pid_data.syscall_data {
+            self.slice(syscall)?;
+            self.syscall_data(data)?;
+        }
+        self.time(pid_data.start_time)?;
+        self.time(pid_data.end_time)?;
+
+        self.len(pid_data.pvt_futex.len())?;
+        for addr in &pid_data.pvt_futex {
+            self.slice(addr)?;
+        }
+        self.events(&pid_data.split_clones)?;
+        self.pids(&pid_data.threads)?;
+        self.pids(&pid_data.child_pids)?;
+        self.len(pid_data.child_namespaces.len())?;
+        for (pid, namespaces) in &pid_data.child_namespaces {
+            self.i32(*pid)?;
+            self.u8(namespaces.bits())?;
+        }
+        self.len(pid_data.child_clone_times.len())?;
+        for (pid, time) in &pid_data.child_clone_times {
+            self.i32(*pid)?;
+            self.slice(time)?;
+        }
+        self.u8(pid_data.unshared.bits())?;
+
+        for events in [
+            &pid_data.open_events,
+            &pid_data.close_events,
+            &pid_data.io_events,
+            &pid_data.socket_events,
+            &pid_data.permission_events,
+            &pid_data.dir_events,
+            &pid_data.uid_events,
+            &pid_data.futex_events,
+            &pid_data.poll_events,
+            &pid_data.link_events,
+            &pid_data.error_events,
+        ] {
+            self.events(events)?;
+        }
+
+        match &pid_data.execve {
+            Some(execs) => {
+                self.u8(1)?;
+                self.len(execs.len())?;
+                for exec in execs {
+                    self.len(exec.exec.len())?;
+                    exec.exec.iter().try_for_each(|arg| self.slice(arg))?;
+                    match exec.env_count {
+                        Some(count) => {
+                            self.u8(1)?;
+                            self.len(count)?;
+                        }
+                        None => self.u8(0)?,
+                    }
+                    self.slice(exec.time)?;
+                }
+            }
+            None => self.u8(0)?,
+        }
+
+        match pid_data.exit {
+            Some(ExitType::Exit(code)) => {
+                self.u8(1)?;
+                self.i32(code)
+            }
+            Some(ExitType::Signal(signal)) => {
+                self.u8(2)?;
+                self.slice(signal)
+            }
+            None => self.u8(0),
+        }
+    }
+}
+
+struct Decoder<'a, R> {
+    buffer: &'a [u8],
+    input: R,
+}
+
+impl<'a, R: Read> Decoder<'a, R> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut bytes = [0; N];
+        self.input.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn flag(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(self.bytes()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_le_bytes(self.bytes()?))
+    }
+
+    fn len(&mut self) -> Result<usize, Error> {
+        usize::try_from(u64::from_le_bytes(self.bytes()?)).map_err(|_| invalid())
+    }
+
+    fn slice(&mut self) -> Result<&'a [u8], Error> {
+        let offset = self.len()?;
+        let len = self.len()?;
+        let end = offset.checked_add(len).ok_or_else(invalid)?;
+        self.buffer.get(offset..end).ok_or_else(invalid)
+    }
+
+    fn opt_slice(&mut self) -> Result<Option<&'a [u8]>, Error> {
+        match self.flag()? {
+            true => self.slice().map(Some),
+            false => Ok(None),
+        }
+    }
+
+    fn f32s(&mut self) -> Result<Vec<f32>, Error> {
+        let len = self.len()?;
+        (0..len).map(|_| self.f32()).collect()
+    }
+
+    fn pids(&mut self) -> Result<Vec<Pid>, Error> {
+        let len = self.len()?;
+        (0..len).map(|_| self.i32()).collect()
+    }
+
+    fn events(&mut self) -> Result<Vec<RawData<'a>>, Error> {
+        let len = self.len()?;
+        (0..len)
+            .map(|_| {
+                let start = self.len()?;
+                let rest = self.buffer.get(start..).ok_or_else(invalid)?;
+                let line = rest.find_byte(b'\n').map_or(rest, |end| &rest[..end]);
+                match parser::parse_line(line) {
+                    Some(LineData::Syscall(raw_data)) => Ok(raw_data),
+                    _ => Err(invalid()),
+                }
+            })
+            .collect()
+    }
+
+    fn syscall_data(&mut self) -> Result<SyscallData<'a>, Error> {
+        let mut data = SyscallData::new();
+        data.lengths = self.f32s()?;
+        data.fast_calls = self.len()?;
+        data.fast_time = self.f32()?;
+        for _ in 0..self.len()? {
+            let error = self.slice()?;
+            data.errors.insert(error, self.i32()?);
+        }
+        for _ in 0..self.len()? {
+            let error = self.slice()?;
+            data.error_lengths.insert(error, self.f32s()?);
+        }
+        data.first_time = self.opt_slice()?;
+        data.last_time = self.opt_slice()?;
+
+        Ok(data)
+    }
+
+    fn pid_data(&mut self) -> Result<PidData<'a>, Error> {
+        let syscall_ct = self.len()?;
+        let mut pid_data = PidData::with_capacity(syscall_ct);
+        for _ in 0..syscall_ct {
+            let syscall = self.slice()?;
+            pid_data.syscall_data.insert(syscall, self.syscall_data()?);
+        }
+        if let Some(time) = self.opt_slice()? {
+            pid_data.start_time = time;
+        }
+        if let Some(time) = self.opt_slice()? {
+            pid_data.end_time = time;
+        }
+
+        for _ in 0..self.len()? {
+            pid_data.pvt_futex.insert(self.slice()?);
+        }
+        pid_data.split_clones = self.events()?;
+        pid_data.threads = self.pids()?;
+        pid_data.child_pids = self.pids()?;
+        for _ in 0..self.len()? {
+            let pid = self.i32()?;
+            let namespaces = Namespaces::from_bits(self.u8()?);
+            pid_data.child_namespaces.push((pid, namespaces));
+        }
+        for _ in 0..self.len()? {
+            let pid = self.i32()?;
+            pid_data.child_clone_times.push((pid, self.slice()?));
+        }
+        pid_data.unshared = Namespaces::from_bits(self.u8()?);
+
+        pid_data.open_events = self.events()?;
+        pid_data.close_events = self.events()?;
+        pid_data.io_events = self.events()?;
+        pid_data.socket_events = self.events()?;
+        pid_data.permission_events = self.events()?;
+        pid_data.dir_events = self.events()?;
+        pid_data.uid_events = self.events()?;
+        pid_data.futex_events = self.events()?;
+        pid_data.poll_events = self.events()?;
+        pid_data.link_events = self.events()?;
+        pid_data.error_events = self.events()?;
+
+        if self.flag()? {
+            let exec_ct = self.len()?;
+            let mut execs = Vec::with_capacity(exec_ct);
+            for _ in 0..exec_ct {
+                let arg_ct = self.len()?;
+                let exec = (0..arg_ct)
+                    .map(|_| self.slice())
+                    .collect::<Result<_, _>>()?;
+                let env_count = match self.flag()? {
+                    true => Some(self.len()?),
+                    false => None,
+                };
+                execs.push(RawExec::new(exec, env_count, self.slice()?));
+            }
+            pid_data.execve = Some(execs);
+        }
+
+        pid_data.exit = match self.u8()? {
+            0 => None,
+            1 => Some(ExitType::Exit(self.i32()?)),
+            2 => Some(ExitType::Signal(self.slice()?)),
+            _ => return Err(invalid()),
+        };
+
+        Ok(pid_data)
+    }
+}
+
+fn invalid() -> Error {
+    Error::new(ErrorKind::InvalidData, "spill file is corrupt")
+}
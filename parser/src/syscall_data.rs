@@ -1,27 +1,71 @@
+use crate::namespaces::Namespaces;
 use crate::parser;
-use crate::parser::{ExitType, LineData, OtherFields, ProcType, RawData};
+use crate::parser::{CallStatus, ExitType, LineData, OtherFields, ProcType, RawData};
+use crate::spill::Spill;
+use crate::time::Timestamp;
 use crate::Pid;
 use crate::{HashMap, HashSet};
 
+use bstr::ByteSlice;
 use rayon::prelude::*;
+use std::collections::hash_map::Entry;
 use std::convert::TryFrom;
+use std::io::{Error, Read, Seek, Write};
+
+// Smaller traces build quickly enough that sampling them costs more than it saves
+const MIN_ESTIMATE_BYTES: usize = 4 * 1024 * 1024;
+// Number of evenly spaced places in the trace sampled to estimate map sizes
+const SAMPLE_CT: usize = 8;
+const SAMPLE_LINES: usize = 256;
+// Pieces of the trace handed to each thread while aggregating. Each piece is
+// built into maps of its own, so a few per thread keep the threads busy while
+// the per-PID vecs grow long enough between merges to rarely reallocate
+const CHUNKS_PER_THREAD: usize = 4;
+const MIN_CHUNK_BYTES: usize = 256 * 1024;
+
+/// Size of the windows `build_syscall_data_streaming` reads the trace in by
+/// default, large enough to keep every thread busy
+pub const DEFAULT_STREAM_WINDOW: usize = 64 * 1024 * 1024;
 
 #[derive(Clone, Default, Debug)]
 pub struct SyscallData<'a> {
     pub lengths: Vec<f32>,
+    /// Calls faster than the floor given when building, counted here rather
+    /// than kept in `lengths`
+    pub fast_calls: usize,
+    /// Total time in seconds of the calls in `fast_calls`
+    pub fast_time: f32,
     pub errors: HashMap<&'a [u8], Pid>,
+    pub error_lengths: HashMap<&'a [u8], Vec<f32>>,
+    /// Times of the syscall's first and last lines, as printed in the trace
+    pub first_time: Option<&'a [u8]>,
+    pub last_time: Option<&'a [u8]>,
 }
 
 impl<'a> SyscallData<'a> {
     pub fn new() -> SyscallData<'a> {
         SyscallData {
             lengths: Vec::new(),
+            fast_calls: 0,
+            fast_time: 0.0,
             errors: HashMap::default(),
+            error_lengths: HashMap::default(),
+            first_time: None,
+            last_time: None,
+        }
+    }
+
+    fn add_time(&mut self, time: &'a [u8]) {
+        if self.first_time.is_none_or(|first| time < first) {
+            self.first_time = Some(time);
+        }
+        if self.last_time.is_none_or(|last| time > last) {
+            self.last_time = Some(time);
         }
     }
 }
 
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Debug)]
 pub struct PidData<'a> {
     pub syscall_data: HashMap<&'a [u8], SyscallData<'a>>,
     pub start_time: &'a [u8],
@@ -30,57 +74,171 @@ pub struct PidData<'a> {
     pub split_clones: Vec<RawData<'a>>,
     pub threads: Vec<Pid>,
     pub child_pids: Vec<Pid>,
+    /// Children created in new namespaces, and the namespaces created
+    pub child_namespaces: Vec<(Pid, Namespaces)>,
+    /// Start times of the calls that created each child
+    pub child_clone_times: Vec<(Pid, &'a [u8])>,
+    /// Namespaces this PID moved itself into with 'unshare'
+    pub unshared: Namespaces,
     pub open_events: Vec<RawData<'a>>,
+    pub close_events: Vec<RawData<'a>>,
     pub io_events: Vec<RawData<'a>>,
+    pub socket_events: Vec<RawData<'a>>,
+    pub permission_events: Vec<RawData<'a>>,
+    pub dir_events: Vec<RawData<'a>>,
+    pub uid_events: Vec<RawData<'a>>,
+    pub futex_events: Vec<RawData<'a>>,
+    /// 'epoll_ctl' registrations and the 'epoll_wait' calls they wake
+    pub poll_events: Vec<RawData<'a>>,
+    /// 'readlink' calls, which name the targets of symlinks
+    pub link_events: Vec<RawData<'a>>,
+    /// Calls that failed, and unfinished calls with a path that may fail on resuming
+    pub error_events: Vec<RawData<'a>>,
     pub execve: Option<Vec<RawExec<'a>>>,
     pub exit: Option<ExitType<'a>>,
 }
 
 impl<'a> PidData<'a> {
     pub fn new() -> PidData<'a> {
+        PidData::with_capacity(0)
+    }
+
+    /// Space is reserved for `syscalls` distinct syscalls
+    pub fn with_capacity(syscalls: usize) -> PidData<'a> {
         PidData {
-            syscall_data: HashMap::default(),
+            syscall_data: HashMap::with_capacity(syscalls),
             start_time: b"zzzzz", // greater than any valid time str
             end_time: b"00000",   // less than any valid time str
             pvt_futex: HashSet::new(),
             split_clones: Vec::new(),
             threads: Vec::new(),
             child_pids: Vec::new(),
+            child_namespaces: Vec::new(),
+            child_clone_times: Vec::new(),
+            unshared: Namespaces::default(),
             open_events: Vec::new(),
+            close_events: Vec::new(),
             io_events: Vec::new(),
+            socket_events: Vec::new(),
+            permission_events: Vec::new(),
+            dir_events: Vec::new(),
+            uid_events: Vec::new(),
+            futex_events: Vec::new(),
+            poll_events: Vec::new(),
+            link_events: Vec::new(),
+            error_events: Vec::new(),
             execve: None,
             exit: None,
         }
     }
 
     fn coalesce_split_clones(&mut self) {
-        self.split_clones.sort_by(|a, b| a.time.cmp(&b.time));
+        self.split_clones.sort_by(|a, b| a.time.cmp(b.time));
 
-        let mut pairs = self.split_clones.chunks_exact(2);
+        let split_clones = std::mem::take(&mut self.split_clones);
+        let mut pairs = split_clones.chunks_exact(2);
         while let Some([start, end]) = pairs.next() {
-            match (&start.other, &end.rtn_cd) {
-                (Some(OtherFields::Clone(ProcType::Process)), Some(child_pid)) => {
-                    self.child_pids.push(*child_pid);
-                }
-                (Some(OtherFields::Clone(ProcType::Thread)), Some(thread_pid)) => {
-                    self.threads.push(*thread_pid);
-                    self.child_pids.push(*thread_pid);
-                }
-                _ => {}
+            if let Some(child_pid) = end.rtn_cd {
+                self.add_child(child_pid, start);
+            }
+        }
+        self.split_clones = split_clones;
+    }
+
+    // 'clone_data' is the line holding the call's flags, which may be the
+    // start of a call split across lines
+    fn add_child(&mut self, child_pid: Pid, clone_data: &RawData<'a>) {
+        match clone_data.proc_type() {
+            Some(ProcType::Process) => self.child_pids.push(child_pid),
+            Some(ProcType::Thread) => {
+                self.threads.push(child_pid);
+                self.child_pids.push(child_pid);
             }
+            None => return,
+        }
+        self.child_clone_times.push((child_pid, clone_data.time));
+
+        if let Some(namespaces) = clone_data.namespaces() {
+            self.child_namespaces.push((child_pid, namespaces));
         }
     }
+
+    /// A rough count of the bytes allocated for this PID's data, not
+    /// including the trace its slices borrow from
+    pub fn heap_bytes(&self) -> usize {
+        use std::mem::size_of;
+
+        let syscalls: usize = self
+            .syscall_data
+            .values()
+            .map(|data| {
+                size_of::<(&[u8], SyscallData)>()
+                    + data.lengths.capacity() * size_of::<f32>()
+                    + data.errors.capacity() * size_of::<(&[u8], Pid)>()
+                    + data
+                        .error_lengths
+                        .values()
+                        .map(|l| size_of::<(&[u8], Vec<f32>)>() + l.capacity() * size_of::<f32>())
+                        .sum::<usize>()
+            })
+            .sum();
+
+        let events: usize = [
+            &self.split_clones,
+            &self.open_events,
+            &self.close_events,
+            &self.io_events,
+            &self.socket_events,
+            &self.permission_events,
+            &self.dir_events,
+            &self.uid_events,
+            &self.futex_events,
+            &self.poll_events,
+            &self.link_events,
+            &self.error_events,
+        ]
+        .iter()
+        .map(|events| events.capacity() * size_of::<RawData>())
+        .sum();
+
+        let execs: usize = self
+            .execve
+            .iter()
+            .flatten()
+            .map(|e| size_of::<RawExec>() + e.exec.capacity() * size_of::<&[u8]>())
+            .sum();
+
+        size_of::<PidData>()
+            + syscalls
+            + events
+            + execs
+            + self.pvt_futex.capacity() * size_of::<&[u8]>()
+            + (self.threads.capacity() + self.child_pids.capacity()) * size_of::<Pid>()
+            + self.child_namespaces.capacity() * size_of::<(Pid, Namespaces)>()
+            + self.child_clone_times.capacity() * size_of::<(Pid, &[u8])>()
+    }
+}
+
+impl<'a> Default for PidData<'a> {
+    fn default() -> PidData<'a> {
+        PidData::new()
+    }
 }
 
 #[derive(Clone, Default, Debug)]
 pub struct RawExec<'a> {
     pub exec: Vec<&'a [u8]>,
+    pub env_count: Option<usize>,
     pub time: &'a [u8],
 }
 
 impl<'a> RawExec<'a> {
-    pub fn new(exec: Vec<&'a [u8]>, time: &'a [u8]) -> RawExec<'a> {
-        RawExec { exec, time }
+    pub fn new(exec: Vec<&'a [u8]>, env_count: Option<usize>, time: &'a [u8]) -> RawExec<'a> {
+        RawExec {
+            exec,
+            env_count,
+            time,
+        }
     }
 }
 
@@ -89,27 +247,109 @@ impl<'a> TryFrom<RawData<'a>> for RawExec<'a> {
 
     fn try_from(data: RawData<'a>) -> Result<Self, Self::Error> {
         let t = data.time;
-        if let Some(OtherFields::Execve(v)) = data.other {
-            Ok(RawExec::new(v, t))
+        if let Some(OtherFields::Execve(v, env_count)) = data.other {
+            Ok(RawExec::new(v, env_count, t))
         } else {
             Err("No exec")
         }
     }
 }
 
-pub fn build_syscall_data<'a>(buffer: &'a [u8]) -> HashMap<Pid, PidData<'a>> {
-    let mut data_map = buffer
-        .par_split(|c| *c == b'\n')
-        .fold(HashMap::default, |mut pid_data_map, line| {
-            if let Some(raw_data) = parser::parse_line(line) {
-                add_syscall_data(&mut pid_data_map, raw_data);
+/// A guess at how many PIDs a trace holds and how many distinct syscalls each
+/// one makes, used to size maps up front rather than growing them line by line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SizeHint {
+    pub pids: usize,
+    pub syscalls_per_pid: usize,
+}
+
+impl SizeHint {
+    /// Parses a few hundred lines from evenly spaced places in `buffer`. PIDs
+    /// that only appear elsewhere are missed, so the result is a lower bound.
+    /// Traces under 4 MiB are not sampled and get the default hint.
+    pub fn estimate(buffer: &[u8]) -> SizeHint {
+        if buffer.len() < MIN_ESTIMATE_BYTES {
+            return SizeHint::default();
+        }
+        SizeHint::sample(buffer)
+    }
+
+    fn sample(buffer: &[u8]) -> SizeHint {
+        let mut syscalls: HashMap<Pid, HashSet<&[u8]>> = HashMap::default();
+
+        for idx in 0..SAMPLE_CT {
+            let offset = buffer.len() / SAMPLE_CT * idx;
+            // Skip the partial line the offset landed in
+            let start = match offset {
+                0 => 0,
+                _ => match buffer[offset..].find_byte(b'\n') {
+                    Some(pos) => offset + pos + 1,
+                    None => break,
+                },
+            };
+
+            for line in buffer[start..].lines().take(SAMPLE_LINES) {
+                match parser::parse_line(line) {
+                    Some(LineData::Syscall(raw_data)) => {
+                        syscalls
+                            .entry(raw_data.pid)
+                            .or_default()
+                            .insert(raw_data.syscall);
+                    }
+                    Some(line_data) => {
+                        syscalls.entry(line_data.pid()).or_default();
+                    }
+                    None => {}
+                }
             }
-            pid_data_map
-        })
-        .reduce(HashMap::default, |mut pid_data_map, temp_map| {
-            coalesce_pid_data(&mut pid_data_map, temp_map);
-            pid_data_map
-        });
+        }
+
+        SizeHint {
+            pids: syscalls.len(),
+            syscalls_per_pid: syscalls.values().map(|s| s.len()).max().unwrap_or_default(),
+        }
+    }
+}
+
+pub fn build_syscall_data<'a>(buffer: &'a [u8]) -> HashMap<Pid, PidData<'a>> {
+    build_syscall_data_with_hint(buffer, SizeHint::estimate(buffer))
+}
+
+/// As `build_syscall_data`, with maps sized from `hint`. A default hint
+/// leaves them to grow as lines are added.
+pub fn build_syscall_data_with_hint(buffer: &[u8], hint: SizeHint) -> HashMap<Pid, PidData<'_>> {
+    build(buffer, hint, 0.0, None)
+}
+
+/// As `build_syscall_data`, but the durations of calls faster than `floor`
+/// seconds are only added to their syscall's `fast_calls` and `fast_time`
+/// rather than kept one by one, nor are fast 'futex' calls kept as events.
+/// Traces dominated by calls like 'clock_gettime' take far less memory, while
+/// call counts and total times are unchanged.
+pub fn build_syscall_data_with_floor(buffer: &[u8], floor: f32) -> HashMap<Pid, PidData<'_>> {
+    build(buffer, SizeHint::estimate(buffer), floor, None)
+}
+
+/// As `build_syscall_data_with_floor`, keeping only the lines of `pids`. A
+/// line's PID is read before the rest of it is parsed, so looking into a few
+/// PIDs of a large trace takes a fraction of the time. Other PIDs are left
+/// out entirely, including the parents of `pids`.
+pub fn build_syscall_data_for_pids<'a>(
+    buffer: &'a [u8],
+    pids: &HashSet<Pid>,
+    floor: f32,
+) -> HashMap<Pid, PidData<'a>> {
+    // The sample would be of every PID's lines
+    build(buffer, SizeHint::default(), floor, Some(pids))
+}
+
+fn build<'a>(
+    buffer: &'a [u8],
+    hint: SizeHint,
+    floor: f32,
+    pids: Option<&HashSet<Pid>>,
+) -> HashMap<Pid, PidData<'a>> {
+    let mut data_map = aggregate(buffer, hint, floor, pids);
 
     data_map.par_iter_mut().for_each(|(_, pid_data)| {
         pid_data.coalesce_split_clones();
@@ -118,25 +358,386 @@ pub fn build_syscall_data<'a>(buffer: &'a [u8]) -> HashMap<Pid, PidData<'a>> {
     data_map
 }
 
-fn add_syscall_data<'a>(pid_data_map: &mut HashMap<Pid, PidData<'a>>, line_data: LineData<'a>) {
+/// As `build_syscall_data_with_floor`, reading the trace from start to end a
+/// window of about `window_bytes` at a time, for traces larger than memory.
+/// Each window is aggregated by every thread and merged into the result
+/// before the next is read, then passed to `window_done`, e.g. to drop its
+/// pages from a memory map. Only the current window and the lines the result
+/// refers to need to be in memory, at some cost in speed as threads wait for
+/// each other at the end of every window.
+pub fn build_syscall_data_streaming<'a, F>(
+    buffer: &'a [u8],
+    window_bytes: usize,
+    floor: f32,
+    mut window_done: F,
+) -> HashMap<Pid, PidData<'a>>
+where
+    F: FnMut(&'a [u8]),
+{
+    let hint = SizeHint::estimate(buffer);
+    let mut data_map: HashMap<Pid, PidData> = HashMap::default();
+
+    let window_ct = buffer.len() / window_bytes.max(1) + 1;
+    for window in line_chunks(buffer, window_ct, window_bytes) {
+        let window_map = aggregate(window, hint, floor, None);
+        coalesce_pid_data(&mut data_map, window_map);
+        window_done(window);
+    }
+
+    data_map.par_iter_mut().for_each(|(_, pid_data)| {
+        pid_data.coalesce_split_clones();
+    });
+
+    data_map
+}
+
+/// As `build_syscall_data_streaming`, keeping the per-PID data held in
+/// memory while the trace is read to about `max_bytes`. Windows are sized to
+/// a fraction of `max_bytes`, and once the data merged from them grows past
+/// half of it, it's written to `spill` as a part and dropped. The parts are
+/// read back and merged in order once the whole trace has been read, so the
+/// reports still need the memory of the full result, but the trace and the
+/// maps each thread builds are never held alongside it.
+pub fn build_syscall_data_capped<'a, S, F>(
+    buffer: &'a [u8],
+    max_bytes: usize,
+    floor: f32,
+    spill: S,
+    window_done: F,
+) -> Result<HashMap<Pid, PidData<'a>>, Error>
+where
+    S: Read + Write + Seek,
+    F: FnMut(&'a [u8]),
+{
+    let window_bytes = (max_bytes / 4).clamp(MIN_CHUNK_BYTES, DEFAULT_STREAM_WINDOW);
+    build_capped(buffer, max_bytes, window_bytes, floor, spill, window_done)
+}
+
+fn build_capped<'a, S, F>(
+    buffer: &'a [u8],
+    max_bytes: usize,
+    window_bytes: usize,
+    floor: f32,
+    spill: S,
+    mut window_done: F,
+) -> Result<HashMap<Pid, PidData<'a>>, Error>
+where
+    S: Read + Write + Seek,
+    F: FnMut(&'a [u8]),
+{
+    let hint = SizeHint::estimate(buffer);
+    let mut spill = Spill::new(buffer, spill);
+    let mut data_map: HashMap<Pid, PidData> = HashMap::default();
+
+    let window_ct = buffer.len() / window_bytes.max(1) + 1;
+    for window in line_chunks(buffer, window_ct, window_bytes) {
+        let window_map = aggregate(window, hint, floor, None);
+        coalesce_pid_data(&mut data_map, window_map);
+        window_done(window);
+
+        if data_map.values().map(PidData::heap_bytes).sum::<usize>() > max_bytes / 2 {
+            spill.write_part(&data_map)?;
+            data_map = HashMap::default();
+        }
+    }
+
+    if spill.parts() > 0 {
+        let last = std::mem::take(&mut data_map);
+        spill.read_parts(|part| coalesce_pid_data(&mut data_map, part))?;
+        coalesce_pid_data(&mut data_map, last);
+    }
+
+    data_map.par_iter_mut().for_each(|(_, pid_data)| {
+        pid_data.coalesce_split_clones();
+    });
+
+    Ok(data_map)
+}
+
+/// Parses a size in bytes, with an optional 'K', 'M', or 'G' suffix for
+/// powers of 1024, e.g. '4G'
+pub fn parse_size(size: &str) -> Option<usize> {
+    let size = size.trim();
+    let (digits, multiplier) = match size.char_indices().last()? {
+        (idx, 'k' | 'K') => (&size[..idx], 1 << 10),
+        (idx, 'm' | 'M') => (&size[..idx], 1 << 20),
+        (idx, 'g' | 'G') => (&size[..idx], 1 << 30),
+        _ => (size, 1),
+    };
+
+    digits.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+/// Lines of `buffer` that look like strace output but don't start with a PID
+/// and a timestamp, such as ones cut off partway when the trace was copied.
+/// Arguments continued onto the next line by a raw newline are counted too,
+/// as the call's details after the newline are lost. Lines that aren't strace
+/// output at all are left to `skipped_lines`.
+pub fn unparsed_lines(buffer: &[u8]) -> usize {
+    skipped_lines(buffer).unparsed
+}
+
+/// Lines of a trace left out of the reports
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SkippedLines {
+    /// Lines of strace output that couldn't be parsed, see `unparsed_lines`
+    pub unparsed: usize,
+    /// Lines that aren't strace output, e.g. the traced program's own output
+    /// in a trace captured with `strace ... 2>&1 | tee`
+    pub noise: usize,
+}
+
+/// Counts the lines of `buffer` that aren't complete strace output, telling
+/// lines strace wrote but that were damaged apart from lines it never wrote.
+/// Neither is parsed, but only the first leaves the reports incomplete.
+pub fn skipped_lines(buffer: &[u8]) -> SkippedLines {
+    buffer
+        .par_split(|c| *c == b'\n')
+        .fold(SkippedLines::default, |mut skipped, line| {
+            match classify_line(line) {
+                LineKind::Unparsed => skipped.unparsed += 1,
+                LineKind::Noise => skipped.noise += 1,
+                LineKind::Trace | LineKind::Blank => {}
+            }
+            skipped
+        })
+        .reduce(SkippedLines::default, |x, y| SkippedLines {
+            unparsed: x.unparsed + y.unparsed,
+            noise: x.noise + y.noise,
+        })
+}
+
+/// Whether `line` is neither strace output nor blank, such as a line printed
+/// by the traced program
+pub fn is_noise(line: &[u8]) -> bool {
+    classify_line(line) == LineKind::Noise
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LineKind {
+    Trace,
+    Unparsed,
+    Noise,
+    Blank,
+}
+
+fn classify_line(line: &[u8]) -> LineKind {
+    let mut tokens = line.fields();
+    let pid = match tokens.next() {
+        Some(pid) => pid,
+        None => return LineKind::Blank,
+    };
+
+    let is_pid = pid.to_str().is_ok_and(|p| p.parse::<Pid>().is_ok());
+    if is_pid && tokens.next().is_some_and(parser::is_timestamp) {
+        return LineKind::Trace;
+    }
+
+    // Pieces of calls and exits, and strace's own messages
+    let strace_output = line.trim_start().starts_with(b"strace: ")
+        || [
+            &b") = "[..],
+            b"<unfinished ...>",
+            b" resumed>",
+            b"+++ ",
+            b"--- SIG",
+        ]
+        .iter()
+        .any(|s| line.find(s).is_some());
+    if strace_output {
+        LineKind::Unparsed
+    } else {
+        LineKind::Noise
+    }
+}
+
+/// A PID left out of the reports because its data can't be right
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorruptPid {
+    pub pid: Pid,
+    pub reason: String,
+}
+
+/// Removes PIDs whose data breaks what the reports rely on: a call with a
+/// negative or non-numeric duration, e.g. estimated across a corrupted
+/// timestamp, or timestamps in more than one format. One such PID would
+/// otherwise distort the totals for the whole trace, or stop reports that
+/// sort by time altogether.
+pub fn quarantine_corrupt_pids(pid_data_map: &mut HashMap<Pid, PidData>) -> Vec<CorruptPid> {
+    let mut corrupt: Vec<_> = pid_data_map
+        .par_iter()
+        .filter_map(|(&pid, pid_data)| {
+            corrupt_reason(pid_data).map(|reason| CorruptPid { pid, reason })
+        })
+        .collect();
+    corrupt.sort_by_key(|c| c.pid);
+
+    for c in &corrupt {
+        pid_data_map.remove(&c.pid);
+    }
+
+    corrupt
+}
+
+fn corrupt_reason(pid_data: &PidData) -> Option<String> {
+    let bad = |d: f32| !d.is_finite() || d < 0.0;
+
+    let mut syscalls: Vec<_> = pid_data.syscall_data.iter().collect();
+    syscalls.sort_by_key(|(name, _)| *name);
+    for (name, data) in syscalls {
+        let duration = data
+            .lengths
+            .iter()
+            .chain(data.error_lengths.values().flatten())
+            .chain(Some(&data.fast_time))
+            .find(|d| bad(**d));
+        if let Some(duration) = duration {
+            return Some(format!(
+                "'{}' has a duration of {}s",
+                name.to_str_lossy(),
+                duration
+            ));
+        }
+    }
+
+    match (
+        Timestamp::parse(pid_data.start_time),
+        Timestamp::parse(pid_data.end_time),
+    ) {
+        (Some(start), Some(end)) if start.is_wall_clock() != end.is_wall_clock() => Some(format!(
+            "timestamps '{}' and '{}' are in different formats",
+            pid_data.start_time.to_str_lossy(),
+            pid_data.end_time.to_str_lossy()
+        )),
+        _ => None,
+    }
+}
+
+/// Parses a duration in seconds, with an optional 'ns', 'us', 'ms', or 's'
+/// suffix, e.g. '5us'
+pub fn parse_duration(duration: &str) -> Option<f32> {
+    let duration = duration.trim();
+    let (digits, multiplier) = if let Some(digits) = duration.strip_suffix("ns") {
+        (digits, 1e-9)
+    } else if let Some(digits) = duration.strip_suffix("us") {
+        (digits, 1e-6)
+    } else if let Some(digits) = duration.strip_suffix("ms") {
+        (digits, 1e-3)
+    } else {
+        (duration.strip_suffix('s').unwrap_or(duration), 1.0)
+    };
+
+    match digits.trim().parse::<f64>() {
+        Ok(d) if d.is_finite() && d >= 0.0 => Some((d * multiplier) as f32),
+        _ => None,
+    }
+}
+
+fn aggregate<'a>(
+    buffer: &'a [u8],
+    hint: SizeHint,
+    floor: f32,
+    pids: Option<&HashSet<Pid>>,
+) -> HashMap<Pid, PidData<'a>> {
+    let chunk_ct = rayon::current_num_threads() * CHUNKS_PER_THREAD;
+    line_chunks(buffer, chunk_ct, MIN_CHUNK_BYTES)
+        .into_par_iter()
+        .map(|chunk| {
+            let mut pid_data_map = HashMap::with_capacity(hint.pids);
+            for line in chunk.split(|c| *c == b'\n') {
+                if pids.is_some_and(|pids| {
+                    !parser::line_pid(line).is_some_and(|pid| pids.contains(&pid))
+                }) {
+                    continue;
+                }
+                if let Some(raw_data) = parser::parse_line(line) {
+                    add_syscall_data(&mut pid_data_map, raw_data, hint, floor);
+                }
+            }
+            pid_data_map
+        })
+        .reduce(HashMap::default, |pid_data_map, temp_map| {
+            merge_chunks(pid_data_map, temp_map, hint)
+        })
+}
+
+// Merges the data of a later chunk into that of an earlier one. Each PID's
+// events must stay in the order they were read, so the later chunk is always
+// appended to the earlier one, whichever holds more PIDs.
+fn merge_chunks<'a>(
+    mut pid_data_map: HashMap<Pid, PidData<'a>>,
+    temp_map: HashMap<Pid, PidData<'a>>,
+    hint: SizeHint,
+) -> HashMap<Pid, PidData<'a>> {
+    pid_data_map.reserve(hint.pids.saturating_sub(pid_data_map.len()));
+    coalesce_pid_data(&mut pid_data_map, temp_map);
+    pid_data_map
+}
+
+// Splits `buffer` after a newline into about `count` pieces of at least
+// `min_bytes`, the last taking whatever is left
+fn line_chunks(buffer: &[u8], count: usize, min_bytes: usize) -> Vec<&[u8]> {
+    let chunk_len = (buffer.len() / count.max(1)).max(min_bytes).max(1);
+    let mut chunks = Vec::with_capacity(count);
+    let mut rest = buffer;
+
+    while !rest.is_empty() {
+        let end = match rest.get(chunk_len..).and_then(|r| r.find_byte(b'\n')) {
+            Some(idx) => chunk_len + idx + 1,
+            None => rest.len(),
+        };
+        let (chunk, remaining) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = remaining;
+    }
+
+    chunks
+}
+
+fn add_syscall_data<'a>(
+    pid_data_map: &mut HashMap<Pid, PidData<'a>>,
+    line_data: LineData<'a>,
+    hint: SizeHint,
+    floor: f32,
+) {
     let pid_entry = pid_data_map
         .entry(line_data.pid())
-        .or_insert_with(PidData::new);
+        .or_insert_with(|| PidData::with_capacity(hint.syscalls_per_pid));
 
     match line_data {
         LineData::Syscall(raw_data) => {
-            let syscall_entry = pid_entry
-                .syscall_data
-                .entry(raw_data.syscall)
-                .or_insert_with(SyscallData::new);
+            let syscall_entry = pid_entry.syscall_data.entry(raw_data.syscall).or_default();
+            syscall_entry.add_time(raw_data.time);
 
-            if let Some(duration) = raw_data.duration {
-                syscall_entry.lengths.push(duration);
+            let fast = raw_data.duration.is_some_and(|d| d < floor);
+            match raw_data.duration {
+                Some(duration) if fast => {
+                    syscall_entry.fast_calls += 1;
+                    syscall_entry.fast_time += duration;
+                }
+                Some(duration) => syscall_entry.lengths.push(duration),
+                None => {}
             }
 
             if let Some(error) = raw_data.error {
                 let error_entry = syscall_entry.errors.entry(error).or_insert(0);
                 *error_entry += 1;
+
+                if let Some(duration) = raw_data.duration {
+                    syscall_entry
+                        .error_lengths
+                        .entry(error)
+                        .or_default()
+                        .push(duration);
+                }
+            }
+
+            match raw_data.call_status {
+                _ if raw_data.error.is_some() => pid_entry.error_events.push(raw_data.clone()),
+                CallStatus::Started if raw_data.path().is_some() => {
+                    pid_entry.error_events.push(raw_data.clone())
+                }
+                _ => {}
             }
 
             if raw_data.time < pid_entry.start_time {
@@ -148,19 +749,20 @@ fn add_syscall_data<'a>(pid_data_map: &mut HashMap<Pid, PidData<'a>>, line_data:
             }
 
             match raw_data.syscall {
-                b"clone" | b"fork" | b"vfork" => match (raw_data.rtn_cd, &raw_data.other) {
-                    (Some(child_pid), Some(OtherFields::Clone(ProcType::Process))) => {
-                        pid_entry.child_pids.push(child_pid as Pid)
-                    }
-                    (Some(child_pid), Some(OtherFields::Clone(ProcType::Thread))) => {
-                        pid_entry.threads.push(child_pid as Pid);
-                        pid_entry.child_pids.push(child_pid as Pid);
+                b"clone" | b"clone3" | b"fork" | b"vfork" => {
+                    match (raw_data.rtn_cd, raw_data.proc_type()) {
+                        (Some(child_pid), Some(_)) => pid_entry.add_child(child_pid, &raw_data),
+                        (None, Some(_)) | (Some(_), None) => {
+                            pid_entry.split_clones.push(raw_data);
+                        }
+                        _ => {}
                     }
-                    (None, Some(_)) | (Some(_), None) => {
-                        pid_entry.split_clones.push(raw_data);
+                }
+                b"unshare" if raw_data.error.is_none() => {
+                    if let Some(namespaces) = raw_data.namespaces() {
+                        pid_entry.unshared |= namespaces;
                     }
-                    _ => {}
-                },
+                }
                 b"execve" => {
                     if let Ok(e) = RawExec::try_from(raw_data) {
                         if let Some(execs) = &mut pid_entry.execve {
@@ -171,24 +773,64 @@ fn add_syscall_data<'a>(pid_data_map: &mut HashMap<Pid, PidData<'a>>, line_data:
                     }
                 }
                 b"futex" => {
-                    if let Some(OtherFields::Futex(addr)) = raw_data.other {
+                    if let Some(OtherFields::Futex { addr, .. }) = raw_data.other {
                         pid_entry.pvt_futex.insert(addr);
                     }
+                    if !fast {
+                        pid_entry.futex_events.push(raw_data);
+                    }
                 }
-                b"open" | b"openat" => {
+                b"dup" | b"dup2" | b"dup3" | b"epoll_create" | b"epoll_create1" | b"eventfd"
+                | b"eventfd2" | b"io_uring_setup" | b"memfd_create" | b"open" | b"openat"
+                | b"pipe" | b"pipe2" | b"signalfd" | b"signalfd4" | b"socket" | b"socketpair"
+                | b"timerfd_create" => {
                     pid_entry.open_events.push(raw_data);
                 }
+                b"close" => {
+                    pid_entry.close_events.push(raw_data);
+                }
+                b"epoll_ctl" | b"epoll_pwait" | b"epoll_pwait2" | b"epoll_wait" => {
+                    pid_entry.poll_events.push(raw_data);
+                }
+                b"readlink" | b"readlinkat" => {
+                    pid_entry.link_events.push(raw_data);
+                }
                 b"pread64" | b"pwrite64" | b"preadv" | b"preadv2" | b"pwritev" | b"pwritev2"
-                | b"read" | b"recv" | b"recvfrom" | b"recvmsg" | b"send" | b"sendmsg"
-                | b"sendto" | b"write" | b"writev" => {
+                | b"read" | b"readv" | b"recv" | b"recvfrom" | b"recvmsg" | b"send"
+                | b"sendmsg" | b"sendto" | b"write" | b"writev" => {
+                    if let Some(OtherFields::ProcStatus { .. }) = raw_data.other {
+                        pid_entry.uid_events.push(raw_data.clone());
+                    }
                     pid_entry.io_events.push(raw_data);
                 }
+                b"accept" | b"accept4" => {
+                    pid_entry.open_events.push(raw_data.clone());
+                    pid_entry.socket_events.push(raw_data);
+                }
+                b"bind" | b"connect" | b"shutdown" => {
+                    pid_entry.socket_events.push(raw_data);
+                }
+                b"creat" | b"getdents" | b"getdents64" | b"link" | b"linkat" | b"mkdir"
+                | b"mkdirat" | b"rename" | b"renameat" | b"renameat2" | b"rmdir" | b"symlink"
+                | b"symlinkat" | b"unlink" | b"unlinkat" => {
+                    pid_entry.dir_events.push(raw_data);
+                }
+                b"chmod" | b"chown" | b"fchmod" | b"fchmodat" | b"fchown" | b"fchownat"
+                | b"lchown" | b"umask" => {
+                    pid_entry.permission_events.push(raw_data);
+                }
+                b"geteuid" | b"geteuid32" | b"setresuid" | b"setresuid32" | b"setreuid"
+                | b"setreuid32" | b"setuid" | b"setuid32" => {
+                    pid_entry.uid_events.push(raw_data);
+                }
                 _ => {}
             }
         }
         LineData::Exit(exit_data) => {
             pid_entry.exit = Some(exit_data.exit);
         }
+        // Handlers are only matched to the calls they make in the 'signals' report
+        LineData::Signal(_) => {}
     }
 }
 
@@ -197,22 +839,39 @@ fn coalesce_pid_data<'a>(
     temp_map: HashMap<Pid, PidData<'a>>,
 ) {
     for (pid, temp_pid_data) in temp_map.into_iter() {
-        let pid_entry = pid_data_map.entry(pid).or_insert_with(PidData::new);
+        // Nothing to merge, so keep the already built maps and vecs as is
+        let pid_entry = match pid_data_map.entry(pid) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                entry.insert(temp_pid_data);
+                continue;
+            }
+        };
 
         for (syscall, temp_syscall_data) in temp_pid_data.syscall_data {
-            let syscall_entry = pid_entry
-                .syscall_data
-                .entry(syscall)
-                .or_insert_with(SyscallData::new);
+            let syscall_entry = pid_entry.syscall_data.entry(syscall).or_default();
 
-            syscall_entry
-                .lengths
-                .extend(temp_syscall_data.lengths.into_iter());
+            append(&mut syscall_entry.lengths, temp_syscall_data.lengths);
+            syscall_entry.fast_calls += temp_syscall_data.fast_calls;
+            for time in [temp_syscall_data.first_time, temp_syscall_data.last_time]
+                .iter()
+                .flatten()
+            {
+                syscall_entry.add_time(time);
+            }
+            syscall_entry.fast_time += temp_syscall_data.fast_time;
 
             for (error, count) in temp_syscall_data.errors.iter() {
                 let error_entry = syscall_entry.errors.entry(error).or_insert(0);
                 *error_entry += count;
             }
+
+            for (error, lengths) in temp_syscall_data.error_lengths {
+                append(
+                    syscall_entry.error_lengths.entry(error).or_default(),
+                    lengths,
+                );
+            }
         }
 
         if temp_pid_data.start_time < pid_entry.start_time {
@@ -225,15 +884,48 @@ fn coalesce_pid_data<'a>(
 
         pid_entry.pvt_futex.extend(temp_pid_data.pvt_futex);
 
-        pid_entry.split_clones.extend(temp_pid_data.split_clones);
+        append(&mut pid_entry.split_clones, temp_pid_data.split_clones);
+
+        append(&mut pid_entry.threads, temp_pid_data.threads);
+
+        append(&mut pid_entry.child_pids, temp_pid_data.child_pids);
+
+        append(
+            &mut pid_entry.child_namespaces,
+            temp_pid_data.child_namespaces,
+        );
+
+        append(
+            &mut pid_entry.child_clone_times,
+            temp_pid_data.child_clone_times,
+        );
+
+        pid_entry.unshared |= temp_pid_data.unshared;
+
+        append(&mut pid_entry.open_events, temp_pid_data.open_events);
+
+        append(&mut pid_entry.close_events, temp_pid_data.close_events);
+
+        append(&mut pid_entry.io_events, temp_pid_data.io_events);
+
+        append(&mut pid_entry.socket_events, temp_pid_data.socket_events);
+
+        append(
+            &mut pid_entry.permission_events,
+            temp_pid_data.permission_events,
+        );
+
+        append(&mut pid_entry.dir_events, temp_pid_data.dir_events);
 
-        pid_entry.threads.extend(temp_pid_data.threads);
+        append(&mut pid_entry.uid_events, temp_pid_data.uid_events);
 
-        pid_entry.child_pids.extend(temp_pid_data.child_pids);
+        append(&mut pid_entry.futex_events, temp_pid_data.futex_events);
 
-        pid_entry.open_events.extend(temp_pid_data.open_events);
+        append(&mut pid_entry.poll_events, temp_pid_data.poll_events);
 
-        pid_entry.io_events.extend(temp_pid_data.io_events);
+        append(&mut pid_entry.link_events, temp_pid_data.link_events);
+
+        append(&mut pid_entry.error_events, temp_pid_data.error_events);
 
         match (pid_entry.execve.as_mut(), temp_pid_data.execve) {
             (Some(pid_exec), Some(temp_exec)) => {
@@ -251,10 +943,39 @@ fn coalesce_pid_data<'a>(
     }
 }
 
+// Moves `temp` onto the end of `vec`. An empty `vec` takes `temp` whole, so
+// a PID's events seen by only one thread are kept where they were built
+// rather than copied into a fresh allocation.
+fn append<T>(vec: &mut Vec<T>, mut temp: Vec<T>) {
+    if vec.is_empty() {
+        *vec = temp;
+    } else {
+        vec.append(&mut temp);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn merged_chunks_keep_each_pids_events_in_order() {
+        let earlier =
+            br##"477   00:09:56.000000 write(5<socket:[3578440]>, "ab", 2) = 2 <0.000010>"##;
+        let later = br##"477   00:09:56.000000 write(5<socket:[3578440]>, "cde", 3) = 3 <0.000010>
+495   00:09:56.000100 read(3</etc/hosts>, "", 4096) = 0 <0.000010>
+496   00:09:56.000200 read(3</etc/hosts>, "", 4096) = 0 <0.000010>"##;
+        // The later chunk holds more PIDs, which used to put its events first
+        let merged = merge_chunks(
+            build_syscall_data(earlier),
+            build_syscall_data(later),
+            SizeHint::default(),
+        );
+
+        let written: Vec<_> = merged[&477].io_events.iter().map(|e| e.rtn_cd).collect();
+        assert_eq!(written, vec![Some(2), Some(3)]);
+    }
+
     #[test]
     fn syscall_data_captures_lengths() {
         let input = br##"567   00:09:47.836504 open("/proc/self/fd", O_RDONLY|O_NONBLOCK|O_DIRECTORY|O_CLOEXEC) = 221</proc/495/fd> <0.000027>
@@ -267,6 +988,86 @@ mod tests {
         );
     }
 
+    #[test]
+    fn syscall_data_quarantines_corrupt_pids() {
+        let input = br##"477   00:09:56.954410 read(3</etc/hosts>, "a", 1) = 1 <0.000010>
+495   00:09:56.954448 write(1</dev/pts/0>, "a", 1) = 1 <-5.000000>
+566   00:09:56.954500 write(1</dev/pts/0>, "a", 1) = 1 <NaN>
+567   00:09:56.954500 read(3</etc/hosts>, "a", 1) = 1 <0.000010>
+567   1581897596.954600 read(3</etc/hosts>, "a", 1) = 1 <0.000010>"##;
+        let mut pid_data_map = build_syscall_data(input);
+        let corrupt = quarantine_corrupt_pids(&mut pid_data_map);
+
+        let pids: Vec<_> = corrupt.iter().map(|c| c.pid).collect();
+        assert_eq!(pids, vec![495, 566, 567]);
+        assert_eq!(corrupt[0].reason, "'write' has a duration of -5s");
+        assert!(corrupt[2].reason.contains("different formats"));
+        assert_eq!(pid_data_map.keys().collect::<Vec<_>>(), vec![&477]);
+    }
+
+    #[test]
+    fn syscall_data_prefilters_pids() {
+        let input = br##"477   00:09:56.954410 read(3</etc/hosts>, "a", 1) = 1 <0.000010>
+4770  00:09:56.954448 write(1</dev/pts/0>, "a", 1) = 1 <0.000010>
+495   00:09:56.954500 write(1</dev/pts/0>, "a", 1) = 1 <0.000010>
+477   00:09:56.954600 read(3</etc/hosts>, "a", 1) = 1 <0.000010>"##;
+        let pids: HashSet<Pid> = [477].iter().copied().collect();
+        let pid_data_map = build_syscall_data_for_pids(input, &pids, 0.0);
+
+        assert_eq!(pid_data_map.keys().collect::<Vec<_>>(), vec![&477]);
+        assert_eq!(
+            pid_data_map[&477].syscall_data[b"read".as_ref()].lengths,
+            build_syscall_data(input)[&477].syscall_data[b"read".as_ref()].lengths
+        );
+    }
+
+    #[test]
+    fn syscall_data_counts_unparsed_lines() {
+        let input = b"477   00:09:56.954410 read(3</etc/hosts>, \"a\", 1) = 1 <0.000010>\r
+\r
+477   00:09:56.954448 write(1</dev/pts/0>, \"a\\nb\", 3) = 3 <0.000010>
+56.954500 close(3</etc/hosts>) = 0 <0.000010>
+477   close(3</etc/hosts>) = 0 <0.000010>
+strace: Process 478 attached
+";
+        assert_eq!(unparsed_lines(input), 3);
+        assert_eq!(unparsed_lines(b""), 0);
+    }
+
+    #[test]
+    fn syscall_data_skips_program_output() {
+        let input = br##"Starting server on port 8080
+477   00:09:56.954410 write(1</dev/pts/0>, "Starting server on port 8080\n", 29) = 29 <0.000010>
+404 20 bytes
+  [INFO] 12 workers ready
+477   00:09:56.954448 close(3</etc/hosts>) = 0 <0.000010>
+477   00:09:56.954500 close(4</etc/hosts>
+Traceback (most recent call last):
+"##;
+        assert_eq!(
+            skipped_lines(input),
+            SkippedLines {
+                unparsed: 0,
+                noise: 4,
+            }
+        );
+        assert!(is_noise(b"404 20 bytes"));
+        assert!(!is_noise(b""));
+        assert!(!is_noise(
+            b"00:09:56.954500 close(3</etc/hosts>) = 0 <0.000010>"
+        ));
+        assert!(!is_noise(b"<... read resumed>\"\", 10) = 0 <0.000010>"));
+
+        let pid_data_map = build_syscall_data(input);
+        assert_eq!(pid_data_map.len(), 1);
+        assert_eq!(
+            pid_data_map[&477].syscall_data[b"close".as_ref()]
+                .lengths
+                .len(),
+            1
+        );
+    }
+
     #[test]
     fn syscall_data_captures_errors() {
         let input = br##"823   00:09:51.247794 ioctl(44</proc/823/status>, TCGETS, 0x7ffc6d3d2d10) = -1 ENOTTY (Inappropriate ioctl for device) <0.000010>
@@ -282,6 +1083,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn syscall_data_captures_fd_origins() {
+        let input = br##"9071  10:14:21.335261 eventfd2(0, EFD_NONBLOCK|EFD_CLOEXEC) = 5<anon_inode:[eventfd]> <0.000014>
+9071  10:14:21.335301 accept4(3<socket:[61612]>,  <unfinished ...>
+9071  10:14:21.335401 <... accept4 resumed>NULL, NULL, SOCK_CLOEXEC) = 7<socket:[61613]> <0.000094>
+9071  10:14:21.335501 timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK|TFD_CLOEXEC) = 8<anon_inode:[timerfd]> <0.000011>"##;
+        let pid_data_map = build_syscall_data(input);
+        let open_events: Vec<_> = pid_data_map[&9071]
+            .open_events
+            .iter()
+            .map(|e| e.syscall)
+            .collect();
+        assert_eq!(open_events.len(), 4);
+        assert!(open_events.contains(&b"timerfd_create".as_ref()));
+
+        let files = crate::file_data::files_opened(
+            &[9071],
+            &pid_data_map,
+            crate::file_data::SortFilesBy::Time,
+        );
+        let file_names: Vec<_> = files[&9071].iter().map(|f| f.file).collect();
+        assert_eq!(
+            file_names,
+            vec![
+                b"anon_inode:[eventfd]".as_ref(),
+                b"socket:[61613]",
+                b"anon_inode:[timerfd]"
+            ]
+        );
+    }
+
     #[test]
     fn syscall_data_captures_thread() {
         let input = br##"28898 21:16:52.387464 clone(child_stack=0x7f03e0beeff0, flags=CLONE_VM|CLONE_FS|CLONE_FILES|CLONE_SIGHAND|CLONE_THREAD|CLONE_SYSVSEM|CLONE_SETTLS|CLONE_PARENT_SETTID|CLONE_CHILD_CLEARTID, parent_tidptr=0x7f03e0bef9d0, tls=0x7f03e0bef700, child_tidptr=0x7f03e0bef9d0) = 28899 <0.000081>"##;
@@ -385,4 +1217,182 @@ mod tests {
         let pid_data_map = build_syscall_data(input);
         assert_eq!(b"12:00:00.000000", pid_data_map[&13656].end_time,);
     }
+
+    #[test]
+    fn size_hint_estimates_pids_and_syscalls() {
+        let input = br##"477   00:09:56.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   00:09:56.100000 read(0</dev/null>, "", 10) = 0 <0.000010>
+477   00:09:56.200000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+495   00:09:56.300000 read(0</dev/null>, "", 10) = 0 <0.000010>
+567   00:09:56.400000 +++ exited with 0 +++"##;
+        assert_eq!(SizeHint::estimate(input), SizeHint::default());
+        assert_eq!(
+            SizeHint::sample(input),
+            SizeHint {
+                pids: 3,
+                syscalls_per_pid: 2
+            }
+        );
+        assert_eq!(SizeHint::sample(b""), SizeHint::default());
+    }
+
+    #[test]
+    fn syscall_data_captures_child_namespaces() {
+        let input = br##"3301  10:02:00.000400 unshare(CLONE_NEWCGROUP) = 0 <0.000051>
+3301  10:02:00.000600 clone(child_stack=NULL, flags=CLONE_NEWUSER|CLONE_NEWNS|SIGCHLD <unfinished ...>
+3302  10:02:00.000700 execve("/usr/bin/true", ["true"], 0x7ffd /* 10 vars */) = 0 <0.000300>
+3301  10:02:00.000800 <... clone resumed>, child_tidptr=0x7f0) = 3302 <0.000190>
+3301  10:02:00.000900 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|SIGCHLD, child_tidptr=0x7f0) = 3303 <0.000100>"##;
+        let pid_data_map = build_syscall_data(input);
+        let pid_data = &pid_data_map[&3301];
+
+        assert_eq!(pid_data.child_pids, vec![3303, 3302]);
+        assert_eq!(
+            pid_data.child_namespaces,
+            vec![(3302, Namespaces::from_flags(b"CLONE_NEWNS|CLONE_NEWUSER"))]
+        );
+        assert_eq!(pid_data.unshared.to_string(), "cgroup");
+    }
+
+    #[test]
+    fn size_hint_does_not_change_results() {
+        let input = br##"477   00:09:56.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+495   00:09:56.300000 read(0</dev/null>, "", 10) = -1 EAGAIN (Resource temporarily unavailable) <0.000010>
+477   00:09:56.400000 +++ exited with 0 +++"##;
+        let hint = SizeHint {
+            pids: 64,
+            syscalls_per_pid: 16,
+        };
+        let sized = build_syscall_data_with_hint(input, hint);
+        let unsized_map = build_syscall_data_with_hint(input, SizeHint::default());
+
+        assert_eq!(sized.len(), unsized_map.len());
+        for (pid, pid_data) in &sized {
+            let other = &unsized_map[pid];
+            assert_eq!(pid_data.start_time, other.start_time);
+            assert_eq!(pid_data.syscall_data.len(), other.syscall_data.len());
+            assert_eq!(pid_data.exit.is_some(), other.exit.is_some());
+        }
+        assert!(sized[&477].syscall_data.capacity() >= 16);
+    }
+
+    #[test]
+    fn streaming_build_matches_full_build() {
+        let input = br##"477   00:09:56.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+495   00:09:56.100000 read(0</dev/null>,  <unfinished ...>
+477   00:09:56.200000 write(1</dev/null>, "b", 1) = 1 <0.000020>
+495   00:09:56.300000 <... read resumed>"", 10) = -1 EAGAIN (Resource temporarily unavailable) <0.200000>
+477   00:09:56.400000 +++ exited with 0 +++"##;
+        let full = build_syscall_data(input);
+        let mut windows = Vec::new();
+        // Small enough that every line is its own window
+        let streamed = build_syscall_data_streaming(input, 1, 0.0, |w| windows.push(w));
+
+        assert_eq!(windows.len(), 5);
+        assert_eq!(windows.concat(), input.to_vec());
+        assert_eq!(full.len(), streamed.len());
+        for (pid, pid_data) in &full {
+            let other = &streamed[pid];
+            assert_eq!(pid_data.start_time, other.start_time);
+            assert_eq!(pid_data.end_time, other.end_time);
+            assert_eq!(pid_data.exit.is_some(), other.exit.is_some());
+            assert_eq!(pid_data.error_events.len(), other.error_events.len());
+            for (syscall, data) in &pid_data.syscall_data {
+                assert_eq!(data.lengths, other.syscall_data[syscall].lengths);
+                assert_eq!(data.errors, other.syscall_data[syscall].errors);
+            }
+        }
+    }
+
+    #[test]
+    fn line_chunks_end_after_newlines() {
+        let input = b"477 a\n477 bb\n495 ccc\n495 d";
+        let chunks = line_chunks(input, 4, 1);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks.concat(), input.to_vec());
+        assert_eq!(chunks[0], b"477 a\n477 bb\n");
+        assert!(chunks[1].ends_with(b"\n"));
+
+        assert_eq!(line_chunks(input, 4, 1024), vec![&input[..]]);
+        assert!(line_chunks(b"", 4, 1).is_empty());
+    }
+
+    #[test]
+    fn fast_calls_counted_not_kept() {
+        let input = br##"477   00:09:56.000000 clock_gettime(CLOCK_MONOTONIC, {tv_sec=1, tv_nsec=0}) = 0 <0.000001>
+477   00:09:56.100000 clock_gettime(CLOCK_MONOTONIC, {tv_sec=1, tv_nsec=1}) = 0 <0.000002>
+477   00:09:56.200000 clock_gettime(CLOCK_MONOTONIC, {tv_sec=1, tv_nsec=2}) = 0 <0.000100>
+477   00:09:56.300000 futex(0x7f2c, FUTEX_WAIT_PRIVATE, 0, NULL) = 0 <0.000003>"##;
+        let floor = parse_duration("5us").unwrap();
+        let pid_data_map = build_syscall_data_with_floor(input, floor);
+        let clock = &pid_data_map[&477].syscall_data[b"clock_gettime".as_ref()];
+
+        assert_eq!(clock.lengths, vec![0.0001]);
+        assert_eq!(clock.fast_calls, 2);
+        assert!((clock.fast_time - 0.000003).abs() < 1e-9);
+        assert!(pid_data_map[&477].futex_events.is_empty());
+
+        let stats = crate::syscall_stats::build_syscall_stats(&pid_data_map);
+        let clock = stats[&477]
+            .iter()
+            .find(|s| s.name == b"clock_gettime")
+            .unwrap();
+        assert_eq!(clock.count, 3);
+        assert!((clock.total - 0.103).abs() < 1e-6);
+
+        assert_eq!(parse_duration("1ms"), Some(0.001));
+        assert_eq!(parse_duration("0.5"), Some(0.5));
+        assert_eq!(parse_duration("ms"), None);
+        assert_eq!(parse_duration("-1us"), None);
+    }
+
+    #[test]
+    fn capped_build_matches_full_build() {
+        let input = br##"477   00:09:56.000000 write(1</dev/null>, "a", 1) = 1 <0.000010>
+477   00:09:56.050000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f3e1a0f1a10) = 495 <0.000100>
+495   00:09:56.100000 read(0</dev/null>,  <unfinished ...>
+477   00:09:56.200000 write(1</dev/null>, "b", 1) = 1 <0.000020>
+495   00:09:56.300000 <... read resumed>"", 10) = -1 EAGAIN (Resource temporarily unavailable) <0.200000>
+495   00:09:56.400000 execve("/bin/sleep", ["sleep", "1"], 0x7ffd /* 3 vars */) = 0 <0.000300>
+495   00:09:56.500000 open("/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache> <0.000030>
+495   00:09:56.600000 +++ killed by SIGKILL +++
+477   00:09:56.700000 +++ exited with 0 +++"##;
+        let full = build_syscall_data(input);
+        // Every line is a window of its own, and every window is spilled
+        let mut spill = std::io::Cursor::new(Vec::new());
+        let capped = build_capped(input, 1, 1, 0.0, &mut spill, |_| {}).unwrap();
+        assert!(!spill.into_inner().is_empty());
+
+        assert_eq!(full.len(), capped.len());
+        for (pid, pid_data) in &full {
+            let other = &capped[pid];
+            assert_eq!(pid_data.start_time, other.start_time);
+            assert_eq!(pid_data.end_time, other.end_time);
+            assert_eq!(pid_data.exit, other.exit);
+            assert_eq!(pid_data.child_pids, other.child_pids);
+            assert_eq!(pid_data.io_events, other.io_events);
+            assert_eq!(pid_data.open_events, other.open_events);
+            assert_eq!(pid_data.error_events, other.error_events);
+            assert_eq!(
+                pid_data.execve.as_ref().map(|e| e[0].exec.clone()),
+                other.execve.as_ref().map(|e| e[0].exec.clone())
+            );
+            for (syscall, data) in &pid_data.syscall_data {
+                let other_data = &other.syscall_data[syscall];
+                assert_eq!(data.lengths, other_data.lengths);
+                assert_eq!(data.errors, other_data.errors);
+                assert_eq!(data.first_time, other_data.first_time);
+            }
+        }
+        assert!(capped[&495].heap_bytes() > 0);
+    }
+
+    #[test]
+    fn size_parsed_with_suffix() {
+        assert_eq!(parse_size("4G"), Some(4 << 30));
+        assert_eq!(parse_size("512m"), Some(512 << 20));
+        assert_eq!(parse_size("65536"), Some(65536));
+        assert_eq!(parse_size("G"), None);
+        assert_eq!(parse_size("4T"), None);
+    }
 }
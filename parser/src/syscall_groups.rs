@@ -0,0 +1,172 @@
+use crate::syscall_stats::SyscallStats;
+use crate::HashSet;
+
+use std::error;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GroupMode {
+    Alongside,
+    Instead,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SyscallGroups<'a> {
+    groups: Vec<(&'a [u8], HashSet<&'a [u8]>)>,
+}
+
+impl<'a> SyscallGroups<'a> {
+    /// Parses group definitions of the form `name = [syscall, syscall, ...]`,
+    /// one per line. Blank lines and lines starting with `#` are ignored.
+    pub fn parse(config: &'a str) -> Result<SyscallGroups<'a>, ParseGroupError> {
+        let mut groups = Vec::new();
+
+        for (idx, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let err = ParseGroupError { line: idx + 1 };
+
+            let (name, members) = line.split_once('=').ok_or(err)?;
+            let name = name.trim();
+            let members = members
+                .trim()
+                .strip_prefix('[')
+                .and_then(|m| m.strip_suffix(']'))
+                .ok_or(err)?;
+
+            let members: HashSet<_> = members
+                .split(',')
+                .map(|m| m.trim())
+                .filter(|m| !m.is_empty())
+                .map(|m| m.as_bytes())
+                .collect();
+
+            if name.is_empty() || members.is_empty() {
+                return Err(err);
+            }
+
+            groups.push((name.as_bytes(), members));
+        }
+
+        Ok(SyscallGroups { groups })
+    }
+
+    /// Each group's name and member syscalls
+    pub fn iter(&self) -> impl Iterator<Item = (&'a [u8], &HashSet<&'a [u8]>)> {
+        self.groups.iter().map(|(name, members)| (*name, members))
+    }
+
+    pub fn is_grouped(&self, syscall: &[u8]) -> bool {
+        self.groups
+            .iter()
+            .any(|(_, members)| members.contains(syscall))
+    }
+
+    /// Builds the rows to display for a PID, adding one row per group that
+    /// has at least one member present in `stats`.
+    pub fn group_stats(
+        &self,
+        stats: &[SyscallStats<'a>],
+        mode: GroupMode,
+    ) -> Vec<SyscallStats<'a>> {
+        let mut rows: Vec<_> = match mode {
+            GroupMode::Alongside => stats.to_vec(),
+            GroupMode::Instead => stats
+                .iter()
+                .filter(|s| !self.is_grouped(s.name))
+                .cloned()
+                .collect(),
+        };
+
+        for (name, members) in &self.groups {
+            let member_stats: Vec<_> = stats.iter().filter(|s| members.contains(s.name)).collect();
+
+            if !member_stats.is_empty() {
+                rows.push(SyscallStats::combine(name, &member_stats));
+            }
+        }
+
+        rows.sort_by(|x, y| {
+            (y.total)
+                .partial_cmp(&x.total)
+                .expect("Invalid comparison when sorting grouped stats")
+        });
+
+        rows
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParseGroupError {
+    line: usize,
+}
+
+impl fmt::Display for ParseGroupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid syscall group on line {}, expected 'name = [syscall, ...]'",
+            self.line
+        )
+    }
+}
+
+impl error::Error for ParseGroupError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+    use crate::syscall_stats::build_syscall_stats;
+
+    static CONFIG: &str = r##"# waiting on something else
+io_wait = [poll, ppoll, select, epoll_wait]
+
+locking = [futex, flock]
+"##;
+
+    #[test]
+    fn groups_parsed() {
+        let groups = SyscallGroups::parse(CONFIG).unwrap();
+        assert_eq!(groups.groups.len(), 2);
+        assert_eq!(groups.groups[0].0, b"io_wait");
+        assert!(groups.groups[0].1.contains(&b"epoll_wait"[..]));
+        assert_eq!(groups.groups[1].1.len(), 2);
+    }
+
+    #[test]
+    fn groups_invalid_line_reported() {
+        let config = "io_wait = [poll]\nlocking = futex\n";
+        assert_eq!(
+            SyscallGroups::parse(config).unwrap_err(),
+            ParseGroupError { line: 2 }
+        );
+        assert!(SyscallGroups::parse("= [poll]").is_err());
+        assert!(SyscallGroups::parse("io_wait = []").is_err());
+    }
+
+    #[test]
+    fn groups_combine_member_stats() {
+        let input = br##"566   00:09:47.836504 poll([{fd=4, events=POLLIN}], 1, 0) = 0 (Timeout) <0.000010>
+566   00:09:47.836604 select(5, [4], NULL, NULL, {tv_sec=0, tv_usec=0}) = 0 (Timeout) <0.000030>
+566   00:09:47.836704 futex(0x7f8d1c2a9d50, FUTEX_WAKE_PRIVATE, 1) = -1 EAGAIN (Resource temporarily unavailable) <0.000005>
+566   00:09:47.836804 read(4, "a", 1) = 1 <0.000001>"##;
+        let pid_data_map = build_syscall_data(input);
+        let syscall_stats = build_syscall_stats(&pid_data_map);
+        let groups = SyscallGroups::parse(CONFIG).unwrap();
+
+        let rows = groups.group_stats(&syscall_stats[&566], GroupMode::Alongside);
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0].name, b"io_wait");
+        assert_eq!(rows[0].count, 2);
+        assert_eq!(rows[0].error_count(), 0);
+
+        let rows = groups.group_stats(&syscall_stats[&566], GroupMode::Instead);
+        let names: Vec<_> = rows.iter().map(|r| r.name).collect();
+        assert_eq!(names, vec![&b"io_wait"[..], b"locking", b"read"]);
+        assert_eq!(rows[1].error_count(), 1);
+    }
+}
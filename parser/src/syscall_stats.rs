@@ -1,15 +1,95 @@
+use crate::json::{rounded, Lossy};
+use crate::parser::{CallStatus, RawData};
 use crate::syscall_data::PidData;
 use crate::HashMap;
 use crate::Pid;
 
 use bstr::ByteSlice;
 use rayon::prelude::*;
+use schemars::JsonSchema;
+use serde::Serialize;
 use std::collections::BTreeMap;
 use std::fmt;
 
+/// What an fd refers to, from the name strace gives it with '-y'
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FdKind {
+    File,
+    Device,
+    Socket,
+    Pipe,
+    EventPoll,
+    TimerFd,
+    EventFd,
+    IoUring,
+    /// Any other anonymous inode, such as a 'signalfd' or 'inotify' fd
+    AnonInode,
+}
+
+// Anonymous inodes strace names e.g. 'anon_inode:[eventfd]', by the name in
+// brackets. Ones not listed are left as `FdKind::AnonInode`.
+const ANON_INODE_KINDS: [(&[u8], FdKind); 4] = [
+    (b"eventpoll", FdKind::EventPoll),
+    (b"timerfd", FdKind::TimerFd),
+    (b"eventfd", FdKind::EventFd),
+    (b"io_uring", FdKind::IoUring),
+];
+
+impl FdKind {
+    pub fn classify(file: &[u8]) -> FdKind {
+        const SOCKET_PREFIXES: [&[u8]; 8] = [
+            b"socket:",
+            b"TCP:",
+            b"TCPv6:",
+            b"UDP:",
+            b"UDPv6:",
+            b"UNIX:",
+            b"NETLINK:",
+            b"UDPLITE:",
+        ];
+
+        if SOCKET_PREFIXES.iter().any(|p| file.starts_with(p)) {
+            FdKind::Socket
+        } else if file.starts_with(b"pipe:") {
+            FdKind::Pipe
+        } else if let Some(anon) = file.strip_prefix(b"anon_inode:") {
+            let name = anon
+                .trim_start_with(|c| c == '[')
+                .trim_end_with(|c| c == ']');
+            ANON_INODE_KINDS
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map_or(FdKind::AnonInode, |(_, kind)| *kind)
+        } else if file.starts_with(b"/dev/") {
+            FdKind::Device
+        } else {
+            FdKind::File
+        }
+    }
+}
+
+impl fmt::Display for FdKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            FdKind::File => "file",
+            FdKind::Device => "dev",
+            FdKind::Socket => "socket",
+            FdKind::Pipe => "pipe",
+            FdKind::EventPoll => "epoll",
+            FdKind::TimerFd => "timerfd",
+            FdKind::EventFd => "eventfd",
+            FdKind::IoUring => "io_uring",
+            FdKind::AnonInode => "anon",
+        };
+        f.pad(label)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SyscallStats<'a> {
     pub name: &'a [u8],
+    /// Set when the syscall's calls are split by the kind of fd they acted on
+    pub fd_kind: Option<FdKind>,
     pub count: i32,
     pub total: f32,
     max: f32,
@@ -18,39 +98,154 @@ pub struct SyscallStats<'a> {
     errors: HashMap<&'a [u8], i32>,
 }
 
-impl<'a> fmt::Display for SyscallStats<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+/// A row of a syscall table as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub struct SyscallStatsJson<'a> {
+    syscall: Lossy<'a>,
+    fd_kind: Option<String>,
+    count: i32,
+    total_ms: f64,
+    max_ms: f64,
+    avg_ms: f64,
+    min_ms: f64,
+    errors: BTreeMap<String, i32>,
+}
+
+impl<'a> SyscallStats<'a> {
+    fn from_lengths(
+        name: &'a [u8],
+        lengths: &[f32],
+        errors: HashMap<&'a [u8], i32>,
+    ) -> SyscallStats<'a> {
+        let total = lengths.par_iter().sum::<f32>() * 1000.0;
+        let max = *lengths
+            .par_iter()
+            .max_by(|x, y| {
+                x.partial_cmp(y)
+                    .expect("Invalid comparison when finding max length")
+            })
+            .unwrap_or(&(0.0))
+            * 1000.0;
+        let min = *lengths
+            .par_iter()
+            .min_by(|x, y| {
+                x.partial_cmp(y)
+                    .expect("Invalid comparison when finding min length")
+            })
+            .unwrap_or(&(0.0))
+            * 1000.0;
+        let avg = if !lengths.is_empty() {
+            total / lengths.len() as f32
+        } else {
+            0.0
+        };
+
+        SyscallStats {
+            name,
+            fd_kind: None,
+            count: lengths.len() as i32,
+            total,
+            max,
+            avg,
+            min,
+            errors,
+        }
+    }
+
+    // Calls under '--ignore-faster-than' count toward the totals, but as
+    // their durations weren't kept they leave the max and min as they are
+    fn add_fast_calls(&mut self, calls: usize, time: f32) {
+        if calls == 0 {
+            return;
+        }
+        self.count += calls as i32;
+        self.total += time * 1000.0;
+        self.avg = self.total / self.count as f32;
+    }
+
+    /// The syscall's name, followed by the kind of fd when split by it, e.g. 'read(socket)'
+    pub fn label(&self) -> String {
+        match self.fd_kind {
+            Some(kind) => format!("{}({})", self.name.to_str_lossy(), kind),
+            None => self.name.to_str_lossy().to_string(),
+        }
+    }
+
+    pub fn error_count(&self) -> i32 {
+        self.errors.values().sum()
+    }
+
+    /// The row's cells in a PID's syscall table, errors by errno in name order
+    pub fn cells(&self) -> Vec<String> {
+        let mut cells = vec![self.label()];
         if self.count > 0 {
-            write!(
-                f,
-                "{0: <17}    {1: >8}    {2: >10.3}    {3: >10.3}    {4: >10.3}    {5: >10.3}    ",
-                self.name.to_str_lossy(),
-                self.count,
-                self.total,
-                self.max,
-                self.avg,
-                self.min
-            )?;
+            cells.push(self.count.to_string());
+            cells.extend(
+                [self.total, self.max, self.avg, self.min]
+                    .iter()
+                    .map(|ms| format!("{:.3}", ms)),
+            );
         } else {
-            write!(
-                f,
-                "{0: <17}    {1: >8}    {2: >10}    {3: >10}    {4: >10}    {5: >10}    ",
-                self.name.to_str_lossy(),
-                "1",
-                "n/a",
-                "n/a",
-                "n/a",
-                "n/a"
-            )?;
+            cells.push("1".to_string());
+            cells.extend(std::iter::repeat_n("n/a".to_string(), 4));
         }
 
         let sorted_errs: BTreeMap<_, _> = self.errors.iter().collect();
+        let errors: Vec<_> = sorted_errs
+            .iter()
+            .map(|(err, count)| format!("{}: {}", err.to_str_lossy(), count))
+            .collect();
+        cells.push(errors.join("   "));
 
-        for (err, count) in sorted_errs.iter() {
-            write!(f, "{}: {}   ", err.to_str_lossy(), count)?;
+        cells
+    }
+
+    /// The row as written to JSON, errors by errno in name order
+    pub fn to_json(&self) -> SyscallStatsJson<'_> {
+        SyscallStatsJson {
+            syscall: Lossy(self.name),
+            fd_kind: self.fd_kind.map(|k| k.to_string()),
+            count: self.count,
+            total_ms: rounded(self.total, 3),
+            max_ms: rounded(self.max, 3),
+            avg_ms: rounded(self.avg, 3),
+            min_ms: rounded(self.min, 3),
+            errors: self
+                .errors
+                .iter()
+                .map(|(errno, count)| (errno.to_str_lossy().to_string(), *count))
+                .collect(),
         }
+    }
 
-        Ok(())
+    /// Merges the stats of several syscalls into a single row under `name`
+    pub fn combine(name: &'a [u8], stats: &[&SyscallStats<'a>]) -> SyscallStats<'a> {
+        let count = stats.iter().map(|s| s.count).sum();
+        let total = stats.iter().map(|s| s.total).sum();
+        let max = stats.iter().map(|s| s.max).fold(0.0, f32::max);
+        let min = stats
+            .iter()
+            .filter(|s| s.count > 0)
+            .map(|s| s.min)
+            .reduce(f32::min)
+            .unwrap_or(0.0);
+        let avg = if count > 0 { total / count as f32 } else { 0.0 };
+
+        let mut errors = HashMap::default();
+        for (err, err_count) in stats.iter().flat_map(|s| s.errors.iter()) {
+            *errors.entry(*err).or_insert(0) += err_count;
+        }
+
+        SyscallStats {
+            name,
+            fd_kind: None,
+            count,
+            total,
+            max,
+            avg,
+            min,
+            errors,
+        }
     }
 }
 
@@ -64,41 +259,10 @@ pub fn build_syscall_stats<'a>(
             .syscall_data
             .par_iter()
             .map(|(syscall, raw_data)| {
-                let total = raw_data.lengths.par_iter().sum::<f32>() * 1000.0;
-                let max = *raw_data
-                    .lengths
-                    .par_iter()
-                    .max_by(|x, y| {
-                        x.partial_cmp(y)
-                            .expect("Invalid comparison when finding max length")
-                    })
-                    .unwrap_or(&(0.0))
-                    * 1000.0;
-                let min = *raw_data
-                    .lengths
-                    .par_iter()
-                    .min_by(|x, y| {
-                        x.partial_cmp(y)
-                            .expect("Invalid comparison when finding min length")
-                    })
-                    .unwrap_or(&(0.0))
-                    * 1000.0;
-                let avg = if !raw_data.lengths.is_empty() {
-                    total / raw_data.lengths.len() as f32
-                } else {
-                    0.0
-                };
-                let errors = raw_data.errors.clone();
-
-                SyscallStats {
-                    name: syscall,
-                    count: raw_data.lengths.len() as i32,
-                    total,
-                    max,
-                    avg,
-                    min,
-                    errors,
-                }
+                let mut stats =
+                    SyscallStats::from_lengths(syscall, &raw_data.lengths, raw_data.errors.clone());
+                stats.add_fast_calls(raw_data.fast_calls, raw_data.fast_time);
+                stats
             })
             .collect();
 
@@ -114,6 +278,92 @@ pub fn build_syscall_stats<'a>(
     syscall_stats
 }
 
+/// Splits each PID's rows for calls on an fd into one row per kind of fd,
+/// e.g. 'read(file)' and 'read(socket)'. The fd's kind is only known when
+/// strace was run with '-y', calls without it stay in a row of their own.
+pub fn split_by_fd_kind<'a>(
+    stats: HashMap<Pid, Vec<SyscallStats<'a>>>,
+    data: &HashMap<Pid, PidData<'a>>,
+) -> HashMap<Pid, Vec<SyscallStats<'a>>> {
+    stats
+        .into_iter()
+        .map(|(pid, pid_stats)| match data.get(&pid) {
+            Some(pid_data) => (pid, split_pid_stats(pid_stats, pid_data)),
+            None => (pid, pid_stats),
+        })
+        .collect()
+}
+
+// Per syscall and kind of fd, the durations and errors of the calls
+type KindCalls<'a> = HashMap<(&'a [u8], Option<FdKind>), (Vec<f32>, HashMap<&'a [u8], i32>)>;
+
+fn split_pid_stats<'a>(
+    pid_stats: Vec<SyscallStats<'a>>,
+    pid_data: &PidData<'a>,
+) -> Vec<SyscallStats<'a>> {
+    let mut events: Vec<&RawData> = pid_data
+        .io_events
+        .iter()
+        .chain(pid_data.close_events.iter())
+        .collect();
+    events.sort_by(|x, y| (x.time).cmp(y.time));
+
+    let mut calls: KindCalls = HashMap::default();
+    let mut started: HashMap<&[u8], Option<&[u8]>> = HashMap::default();
+    for event in events {
+        let file = match event.call_status {
+            CallStatus::Started => {
+                started.insert(event.syscall, event.file());
+                continue;
+            }
+            CallStatus::Resumed => started.remove(event.syscall).flatten(),
+            CallStatus::Complete => event.file(),
+        };
+
+        let duration = match event.duration {
+            Some(duration) => duration,
+            None => continue,
+        };
+
+        let (lengths, errors) = calls
+            .entry((event.syscall, file.map(FdKind::classify)))
+            .or_default();
+        lengths.push(duration);
+        if let Some(error) = event.error {
+            *errors.entry(error).or_insert(0) += 1;
+        }
+    }
+
+    let mut rows = Vec::with_capacity(pid_stats.len());
+    for stats in pid_stats {
+        let split = calls
+            .keys()
+            .any(|(syscall, kind)| *syscall == stats.name && kind.is_some());
+        if !split {
+            rows.push(stats);
+            continue;
+        }
+
+        let mut kinds: Vec<_> = calls
+            .iter()
+            .filter(|((syscall, _), _)| *syscall == stats.name)
+            .collect();
+        kinds.sort_by_key(|((_, kind), _)| *kind);
+        for ((syscall, kind), (lengths, errors)) in kinds {
+            let mut kind_stats = SyscallStats::from_lengths(syscall, lengths, errors.clone());
+            kind_stats.fd_kind = *kind;
+            rows.push(kind_stats);
+        }
+    }
+
+    rows.sort_by(|x, y| {
+        (y.total)
+            .partial_cmp(&x.total)
+            .expect("Invalid comparison when sorting split stats")
+    });
+    rows
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -186,4 +436,47 @@ mod tests {
         let syscall_stats = &pid_stats[&477];
         assert_eq!(syscall_stats[0].errors[b"ECHILD".as_ref()], 1);
     }
+
+    #[test]
+    fn syscall_stats_split_by_fd_kind() {
+        let input = br##"477   00:09:56.954410 read(3</etc/hosts>, "a", 1) = 1 <0.000010>
+477   00:09:56.954448 read(4<socket:[3578440]>,  <unfinished ...>
+477   00:09:56.954488 <... read resumed>"", 10) = -1 EAGAIN (Resource temporarily unavailable) <0.000100>
+477   00:09:56.954525 read(5<pipe:[3578441]>, "b", 1) = 1 <0.000020>
+477   00:09:56.954600 read(6, "c", 1) = 1 <0.000030>
+477   00:09:56.954700 read(3</etc/hosts>, "", 1) = 0 <0.000005>
+477   00:09:56.954800 fcntl(3</etc/hosts>, F_GETFD) = 0 <0.000001>"##;
+        let pid_data_map = build_syscall_data(input);
+        let pid_stats = split_by_fd_kind(build_syscall_stats(&pid_data_map), &pid_data_map);
+        let labels: Vec<_> = pid_stats[&477].iter().map(|s| s.label()).collect();
+
+        assert_eq!(
+            labels,
+            vec!["read(socket)", "read", "read(pipe)", "read(file)", "fcntl"]
+        );
+        assert_eq!(pid_stats[&477][0].errors[b"EAGAIN".as_ref()], 1);
+        assert_eq!(pid_stats[&477][3].count, 2);
+        assert_eq!(
+            FdKind::classify(b"TCP:[10.0.0.1:80->10.0.0.2:5432]"),
+            FdKind::Socket
+        );
+        assert_eq!(FdKind::classify(b"/dev/null"), FdKind::Device);
+    }
+
+    #[test]
+    fn fd_kind_classifies_anon_inodes() {
+        assert_eq!(
+            FdKind::classify(b"anon_inode:[eventpoll]"),
+            FdKind::EventPoll
+        );
+        assert_eq!(FdKind::classify(b"anon_inode:[timerfd]"), FdKind::TimerFd);
+        assert_eq!(FdKind::classify(b"anon_inode:[eventfd]"), FdKind::EventFd);
+        assert_eq!(FdKind::classify(b"anon_inode:[io_uring]"), FdKind::IoUring);
+        assert_eq!(
+            FdKind::classify(b"anon_inode:[signalfd]"),
+            FdKind::AnonInode
+        );
+        assert_eq!(FdKind::classify(b"anon_inode:inotify"), FdKind::AnonInode);
+        assert_eq!(FdKind::EventPoll.to_string(), "epoll");
+    }
 }
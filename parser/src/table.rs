@@ -0,0 +1,380 @@
+use crate::output;
+
+use std::borrow::Cow;
+use std::io::{prelude::*, stdout, Error};
+use std::str::FromStr;
+
+const INDENT: &str = "  ";
+const GAP: &str = "    ";
+const ELLIPSIS: &str = "...";
+// Characters of a trailing column kept before dropping other columns to make room
+const MIN_ABBREVIATED: usize = 30;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub header: Cow<'static, str>,
+    pub width: usize,
+    pub align: Align,
+    // Columns with the highest priority value are dropped first, 0 is never dropped
+    pub priority: usize,
+}
+
+impl Column {
+    pub fn new(
+        header: impl Into<Cow<'static, str>>,
+        width: usize,
+        align: Align,
+        priority: usize,
+    ) -> Column {
+        let header = header.into();
+        Column {
+            width: width.max(header.chars().count()),
+            header,
+            align,
+            priority,
+        }
+    }
+}
+
+/// A '--column-priority' setting, e.g. 'avg (ms)=0' to never drop a column
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnPriority {
+    pub header: String,
+    pub priority: usize,
+}
+
+impl FromStr for ColumnPriority {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || String::from("expected 'COLUMN=PRIORITY', where PRIORITY is a non-negative integer");
+        let (header, priority) = s.rsplit_once('=').ok_or_else(invalid)?;
+        let header = header.trim();
+        if header.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(ColumnPriority {
+            header: header.to_lowercase(),
+            priority: priority.trim().parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A table that drops its lowest priority columns, and then abbreviates a
+/// trailing left-aligned column, to fit within a terminal width rather than
+/// wrapping
+#[derive(Clone, Debug)]
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+    headers: bool,
+}
+
+impl Table {
+    /// A table of `columns`, with the priorities set by '--column-priority'
+    /// in place of their own
+    pub fn new(mut columns: Vec<Column>) -> Table {
+        for column in &mut columns {
+            if let Some(priority) = output::column_priority(&column.header) {
+                column.priority = priority;
+            }
+        }
+
+        Table {
+            columns,
+            rows: Vec::new(),
+            headers: output::headers(),
+        }
+    }
+
+    /// Whether the column headers and divider are rendered, from '--no-header' by default
+    pub fn set_headers(&mut self, headers: bool) {
+        self.headers = headers;
+    }
+
+    pub fn add_row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    pub fn print(&self, width: Option<usize>) -> Result<(), Error> {
+        self.write(&mut stdout(), width)
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W, width: Option<usize>) -> Result<(), Error> {
+        for line in self.render(width) {
+            writeln!(out, "{}", line)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn render(&self, width: Option<usize>) -> Vec<String> {
+        let visible = self.visible_columns(width);
+        let last_width = self.last_column_width(&visible, width);
+        let widths: Vec<_> = visible.iter().map(|&i| self.content_width(i)).collect();
+
+        let mut lines = Vec::with_capacity(self.rows.len() + 2);
+
+        if self.headers {
+            let headers: Vec<_> = visible
+                .iter()
+                .map(|&i| self.columns[i].header.as_ref())
+                .collect();
+            lines.push(self.render_line(&visible, &widths, &headers, last_width));
+
+            let dividers: Vec<_> = visible
+                .iter()
+                .enumerate()
+                .map(|(pos, &i)| {
+                    let col_width = match last_width {
+                        Some(w) if pos == visible.len() - 1 => w,
+                        // A long trailing column runs on past its divider
+                        _ if pos == visible.len() - 1 => self.columns[i].width,
+                        _ => widths[pos],
+                    };
+                    "-".repeat(col_width)
+                })
+                .collect();
+            lines.push(format!("{}{}", INDENT, dividers.join(GAP)));
+        }
+
+        for row in &self.rows {
+            let cells: Vec<_> = visible
+                .iter()
+                .map(|&i| row.get(i).map(String::as_str).unwrap_or_default())
+                .collect();
+            lines.push(self.render_line(&visible, &widths, &cells, last_width));
+        }
+
+        lines
+    }
+
+    fn render_line(
+        &self,
+        visible: &[usize],
+        widths: &[usize],
+        cells: &[&str],
+        last_width: Option<usize>,
+    ) -> String {
+        let mut line = String::from(INDENT);
+
+        for (pos, (&i, cell)) in visible.iter().zip(cells).enumerate() {
+            let column = &self.columns[i];
+            if pos > 0 {
+                line += GAP;
+            }
+
+            let is_last = pos == visible.len() - 1;
+            let cell = match last_width {
+                Some(w) if is_last => abbreviate(cell, w),
+                _ => cell.to_string(),
+            };
+            let width = if is_last { column.width } else { widths[pos] };
+
+            match column.align {
+                Align::Left => line += &format!("{: <1$}", cell, width),
+                Align::Center => line += &format!("{: ^1$}", cell, width),
+                Align::Right => line += &format!("{: >1$}", cell, width),
+            }
+        }
+
+        line.trim_end().to_string()
+    }
+
+    // Columns widen to fit their longest cell, e.g. long paths or labelled PIDs
+    fn content_width(&self, idx: usize) -> usize {
+        self.rows
+            .iter()
+            .filter_map(|r| r.get(idx))
+            .map(|c| c.chars().count())
+            .max()
+            .unwrap_or_default()
+            .max(self.columns[idx].width)
+    }
+
+    fn line_width(&self, visible: &[usize], abbreviate_last: bool) -> usize {
+        let cols: usize = visible
+            .iter()
+            .enumerate()
+            .map(|(pos, &i)| {
+                let width = self.content_width(i);
+                let column = &self.columns[i];
+                if abbreviate_last && pos == visible.len() - 1 && column.align == Align::Left {
+                    width.min(MIN_ABBREVIATED.max(column.width))
+                } else {
+                    width
+                }
+            })
+            .sum();
+
+        INDENT.len() + cols + GAP.len() * visible.len().saturating_sub(1)
+    }
+
+    fn visible_columns(&self, width: Option<usize>) -> Vec<usize> {
+        let mut visible: Vec<_> = (0..self.columns.len()).collect();
+
+        let width = match width {
+            Some(w) => w,
+            None => return visible,
+        };
+
+        while self.line_width(&visible, true) > width {
+            let drop = visible
+                .iter()
+                .enumerate()
+                .filter(|(_, &i)| self.columns[i].priority > 0)
+                .max_by_key(|(pos, &i)| (self.columns[i].priority, *pos))
+                .map(|(pos, _)| pos);
+
+            match drop {
+                Some(pos) => {
+                    visible.remove(pos);
+                }
+                None => break,
+            }
+        }
+
+        visible
+    }
+
+    // Space left for a trailing left-aligned column once the others are laid out
+    fn last_column_width(&self, visible: &[usize], width: Option<usize>) -> Option<usize> {
+        let width = width?;
+        let (&last, rest) = visible.split_last()?;
+        let column = &self.columns[last];
+
+        if column.align != Align::Left || self.line_width(visible, false) <= width {
+            return None;
+        }
+
+        let used = INDENT.len()
+            + rest.iter().map(|&i| self.content_width(i)).sum::<usize>()
+            + GAP.len() * rest.len();
+
+        Some(width.saturating_sub(used).max(column.width))
+    }
+}
+
+// Keeps the end of the text, which is the most specific part of a path
+fn abbreviate(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len <= width || width <= ELLIPSIS.len() {
+        return text.to_string();
+    }
+
+    let keep = width - ELLIPSIS.len();
+    let tail: String = text.chars().skip(len - keep).collect();
+    format!("{}{}", ELLIPSIS, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_table() -> Table {
+        let mut table = Table::new(vec![
+            Column::new("pid", 7, Align::Right, 0),
+            Column::new("dur (ms)", 10, Align::Right, 1),
+            Column::new("error", 15, Align::Center, 2),
+            Column::new("file name", 9, Align::Left, 0),
+        ]);
+        table.add_row(vec![
+            "123".to_string(),
+            "1.000".to_string(),
+            "ENOENT".to_string(),
+            "/usr/lib/x86_64-linux-gnu/libc.so.6".to_string(),
+        ]);
+        table
+    }
+
+    #[test]
+    fn table_keeps_all_columns_without_width() {
+        let lines = test_table().render(None);
+
+        assert_eq!(
+            lines[0],
+            "      pid      dur (ms)         error         file name"
+        );
+        assert_eq!(
+            lines[2],
+            "      123         1.000        ENOENT         /usr/lib/x86_64-linux-gnu/libc.so.6"
+        );
+    }
+
+    #[test]
+    fn table_drops_lowest_priority_column() {
+        let lines = test_table().render(Some(70));
+
+        assert_eq!(lines[0], "      pid      dur (ms)    file name");
+        assert_eq!(
+            lines[2],
+            "      123         1.000    /usr/lib/x86_64-linux-gnu/libc.so.6"
+        );
+    }
+
+    #[test]
+    fn table_abbreviates_last_column() {
+        let lines = test_table().render(Some(30));
+
+        assert_eq!(lines[0], "      pid    file name");
+        assert_eq!(lines[1], "  -------    -----------------");
+        assert_eq!(lines[2], "      123    ...-gnu/libc.so.6");
+    }
+
+    #[test]
+    fn table_widens_columns_to_fit() {
+        let mut table = test_table();
+        table.add_row(vec![
+            "823 (unicorn-worker-1)".to_string(),
+            "2.000".to_string(),
+            "-".to_string(),
+            "/tmp/a".to_string(),
+        ]);
+        let lines = table.render(None);
+
+        assert_eq!(
+            lines[0],
+            "                     pid      dur (ms)         error         file name"
+        );
+        assert_eq!(
+            lines[3],
+            "  823 (unicorn-worker-1)         2.000           -           /tmp/a"
+        );
+    }
+
+    #[test]
+    fn table_omits_headers() {
+        let mut table = test_table();
+        table.set_headers(false);
+        let lines = table.render(None);
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            lines[0],
+            "      123         1.000        ENOENT         /usr/lib/x86_64-linux-gnu/libc.so.6"
+        );
+    }
+
+    #[test]
+    fn column_priority_parses() {
+        assert_eq!(
+            "Avg (ms)=0".parse(),
+            Ok(ColumnPriority {
+                header: "avg (ms)".to_string(),
+                priority: 0,
+            })
+        );
+        assert!("errors".parse::<ColumnPriority>().is_err());
+        assert!("=1".parse::<ColumnPriority>().is_err());
+        assert!("errors=-1".parse::<ColumnPriority>().is_err());
+    }
+}
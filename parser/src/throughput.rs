@@ -0,0 +1,268 @@
+use crate::concurrency::sparkline;
+use crate::io_data::{self, IoCall};
+use crate::json::{self, Lossy};
+use crate::output;
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::time::{format_timestamp_us, parse_timestamp_us};
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::io::{prelude::*, stdout, Error};
+
+// Longest sparkline printed in the table, longer series are merged to fit
+const SPARK_WIDTH: usize = 40;
+
+/// Bytes read from and written to one file or socket over the trace
+#[derive(Clone, Debug, PartialEq)]
+pub struct TargetSeries<'a> {
+    pub target: &'a [u8],
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    /// Bytes moved in each interval, in both directions
+    pub bytes: Vec<u64>,
+    /// Start times of the first and last calls moving bytes, in µs
+    pub first_us: i64,
+    pub last_us: i64,
+}
+
+impl<'a> TargetSeries<'a> {
+    pub fn total(&self) -> u64 {
+        self.read_bytes + self.write_bytes
+    }
+}
+
+/// Throughput of the busiest targets, each split into the same intervals
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Throughput<'a> {
+    pub start_us: i64,
+    pub interval_us: i64,
+    pub targets: Vec<TargetSeries<'a>>,
+    /// A timestamp from the trace to format interval starts like
+    pub time_layout: Option<&'a [u8]>,
+}
+
+/// Splits the I/O of `pids` into intervals of `interval_us` and returns the
+/// bytes moved per interval for the `count` targets that moved the most.
+/// Calls are counted in the interval they started in, and calls on the same
+/// file or socket from different PIDs are combined.
+pub fn throughput<'a>(
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData<'a>>,
+    interval_us: i64,
+    count: usize,
+) -> Throughput<'a> {
+    let io_calls = io_data::io_calls(pids, raw_data);
+    let calls: Vec<(i64, &IoCall)> = io_calls
+        .values()
+        .flatten()
+        .filter(|call| call.error.is_none() && call.bytes > 0)
+        .filter_map(|call| Some((parse_timestamp_us(call.time)?, call)))
+        .collect();
+
+    let (first, last) = match (
+        calls.iter().map(|(t, _)| *t).min(),
+        calls.iter().map(|(t, _)| *t).max(),
+    ) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Throughput::default(),
+    };
+    let interval_ct = ((last - first) / interval_us + 1) as usize;
+
+    let mut targets: HashMap<&[u8], TargetSeries> = HashMap::default();
+    for (time, call) in &calls {
+        let series = targets.entry(call.fd).or_insert_with(|| TargetSeries {
+            target: call.fd,
+            read_bytes: 0,
+            write_bytes: 0,
+            bytes: vec![0; interval_ct],
+            first_us: *time,
+            last_us: *time,
+        });
+        series.first_us = series.first_us.min(*time);
+        series.last_us = series.last_us.max(*time);
+
+        let bytes = call.bytes as u64;
+        if call.is_read() {
+            series.read_bytes += bytes;
+        } else {
+            series.write_bytes += bytes;
+        }
+        series.bytes[((time - first) / interval_us) as usize] += bytes;
+    }
+
+    let mut targets: Vec<_> = targets.into_values().collect();
+    targets.sort_by(|x, y| y.total().cmp(&x.total()).then(x.target.cmp(y.target)));
+    targets.truncate(count);
+
+    Throughput {
+        start_us: first,
+        interval_us,
+        targets,
+        time_layout: calls.first().map(|(_, call)| call.time),
+    }
+}
+
+pub fn print_throughput(throughput: &Throughput, width: Option<usize>) -> Result<(), Error> {
+    output::print_heading("\nThroughput by File or Socket\n-----------\n")?;
+
+    let time_layout = match throughput.time_layout {
+        Some(t) if !throughput.targets.is_empty() => t,
+        _ => {
+            output::print_note("  No timestamped reads or writes found\n")?;
+            return Ok(());
+        }
+    };
+
+    let interval_ct = throughput.targets[0].bytes.len();
+    let interval_secs = throughput.interval_us as f64 / 1_000_000.0;
+    output::print_heading(&format!(
+        "  {} intervals of {:.3}s starting at {}\n",
+        interval_ct,
+        interval_secs,
+        output::timestamp(format_timestamp_us(throughput.start_us, time_layout).as_bytes())
+    ))?;
+
+    let mut table = Table::new(vec![
+        Column::new("read", 10, Align::Right, 3),
+        Column::new("written", 10, Align::Right, 2),
+        Column::new("peak /s", 10, Align::Right, 1),
+        Column::new("over time", SPARK_WIDTH.min(interval_ct), Align::Left, 4),
+        Column::new("file name", 9, Align::Left, 0),
+    ]);
+
+    for series in &throughput.targets {
+        let peak = series.bytes.iter().copied().max().unwrap_or_default();
+        let values: Vec<_> = series.bytes.iter().map(|&b| b as usize).collect();
+        table.add_row(vec![
+            format_bytes(series.read_bytes as f64),
+            format_bytes(series.write_bytes as f64),
+            format_bytes(peak as f64 / interval_secs),
+            sparkline(&values, SPARK_WIDTH),
+            series.target.to_str_lossy().to_string(),
+        ]);
+    }
+    table.print(width)?;
+
+    writeln!(stdout())?;
+
+    Ok(())
+}
+
+/// Prints the series as a single JSON object, with bytes per interval for each target
+pub fn print_throughput_json(throughput: &Throughput) -> Result<(), Error> {
+    let time = |us: i64| {
+        throughput
+            .time_layout
+            .map(|layout| output::timestamp(format_timestamp_us(us, layout).as_bytes()))
+    };
+
+    let targets = throughput
+        .targets
+        .iter()
+        .map(|series| TargetJson {
+            target: Lossy(series.target),
+            read_bytes: series.read_bytes,
+            write_bytes: series.write_bytes,
+            first: time(series.first_us),
+            last: time(series.last_us),
+            bytes: &series.bytes,
+        })
+        .collect();
+
+    json::write_report(
+        &mut stdout(),
+        &ThroughputReport {
+            start: time(throughput.start_us),
+            interval_ms: throughput.interval_us as f64 / 1000.0,
+            targets,
+        },
+    )
+}
+
+pub(crate) fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024.0 {
+        return format!("{:.0} B", bytes);
+    }
+
+    let mut value = bytes / 1024.0;
+    let mut unit = UNITS[0];
+    for next in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+// A target's series as written to JSON
+#[derive(Serialize, JsonSchema)]
+struct TargetJson<'a> {
+    target: Lossy<'a>,
+    read_bytes: u64,
+    write_bytes: u64,
+    first: Option<String>,
+    last: Option<String>,
+    bytes: &'a [u64],
+}
+
+// 'throughput' as written to JSON
+#[derive(Serialize, JsonSchema)]
+pub(crate) struct ThroughputReport<'a> {
+    start: Option<String>,
+    interval_ms: f64,
+    targets: Vec<TargetJson<'a>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn throughput_buckets_bytes_by_target() {
+        let input = br##"477   10:00:00.000000 write(3</var/log/app.log>, "aaaa", 4) = 4 <0.000010>
+495   10:00:00.500000 write(3</var/log/app.log>, "aa", 2) = 2 <0.000010>
+477   10:00:01.200000 read(4</etc/hosts>, "a", 4096) = 1 <0.000010>
+477   10:00:02.100000 write(3</var/log/app.log>, "aaaaaaaa", 8) = 8 <0.000010>
+477   10:00:02.200000 write(3</var/log/app.log>, "a", 1) = -1 EAGAIN (Resource temporarily unavailable) <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let series = throughput(&[477, 495], &pid_data_map, 1_000_000, 5);
+
+        assert_eq!(series.targets.len(), 2);
+        assert_eq!(series.targets[0].target, b"/var/log/app.log");
+        assert_eq!(series.targets[0].write_bytes, 14);
+        assert_eq!(series.targets[0].bytes, vec![6, 0, 8]);
+        assert_eq!(
+            (series.targets[0].first_us, series.targets[0].last_us),
+            (series.start_us, series.start_us + 2_100_000)
+        );
+        assert_eq!(series.targets[1].target, b"/etc/hosts");
+        assert_eq!(series.targets[1].read_bytes, 1);
+        assert_eq!(series.targets[1].bytes, vec![0, 1, 0]);
+    }
+
+    #[test]
+    fn throughput_keeps_top_targets() {
+        let input = br##"477   10:00:00.000000 write(3</var/log/app.log>, "aaaa", 4) = 4 <0.000010>
+477   10:00:00.100000 write(5</tmp/small>, "a", 1) = 1 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let series = throughput(&[477], &pid_data_map, 1_000_000, 1);
+
+        assert_eq!(series.targets.len(), 1);
+        assert_eq!(series.targets[0].target, b"/var/log/app.log");
+    }
+
+    #[test]
+    fn throughput_formats_bytes() {
+        assert_eq!(format_bytes(512.0), "512 B");
+        assert_eq!(format_bytes(1536.0), "1.5 KiB");
+        assert_eq!(format_bytes(3.0 * 1024.0 * 1024.0), "3.0 MiB");
+    }
+}
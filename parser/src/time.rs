@@ -1,63 +1,564 @@
 use bstr::ByteSlice;
-use chrono::{Duration, NaiveDateTime, NaiveTime};
+use chrono::{
+    DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone,
+};
+use std::fmt;
+
+const US_PER_DAY: i64 = 86_400_000_000;
 
 pub fn parse_elapsed_real_time(buffer: &[u8]) -> Option<Duration> {
-    let start_token = buffer
-        .lines()
-        .next()
-        .and_then(|line| line.fields().skip(1).next());
+    let start_token = buffer.lines().next().and_then(|line| line.fields().nth(1));
 
     let end_token = {
-        // Skip the first newline which is the last character in the file
-        if let Some(last_line_idx) = buffer.rfind_iter("\n").skip(1).next() {
-            let last_line = &buffer[last_line_idx..];
-            last_line.fields().skip(1).next()
+        // Trim trailing LF or CRLF newlines so the last line is found
+        // whether or not the file ends with one
+        let trimmed = buffer.trim_end();
+        let last_line = match trimmed.rfind_byte(b'\n') {
+            Some(idx) => &trimmed[idx + 1..],
+            None => trimmed,
+        };
+        last_line.fields().nth(1)
+    };
+
+    let start = Timestamp::parse(start_token?)?;
+    let end = Timestamp::parse(end_token?)?;
+    if start.is_wall_clock() == end.is_wall_clock() {
+        end.since(start)
+    } else {
+        end.time_of_day().since(start.time_of_day())
+    }
+}
+
+/// Time during which at least one traced PID was active, from the union of
+/// each PID's first and last timestamps. Unlike the real time this excludes
+/// periods when no traced PID was running, e.g. between short lived processes.
+pub fn traced_time<'a, I>(spans: I) -> Option<Duration>
+where
+    I: IntoIterator<Item = (&'a [u8], &'a [u8])>,
+{
+    let mut spans: Vec<_> = spans
+        .into_iter()
+        .filter_map(|(start, end)| Some((parse_timestamp_us(start)?, parse_timestamp_us(end)?)))
+        .collect();
+    spans.sort_unstable();
+
+    let mut total = 0;
+    let mut current: Option<(i64, i64)> = None;
+
+    for (start, end) in spans {
+        current = match current {
+            Some((cur_start, cur_end)) if start <= cur_end => Some((cur_start, cur_end.max(end))),
+            Some((cur_start, cur_end)) => {
+                total += cur_end - cur_start;
+                Some((start, end))
+            }
+            None => Some((start, end)),
+        };
+    }
+
+    let (cur_start, cur_end) = current?;
+    Some(Duration::microseconds(total + cur_end - cur_start))
+}
+
+// strace prints '-tt' timestamps in a fixed 'HH:MM:SS.ffffff' layout, so
+// we can read the digits in place instead of going through chrono's format
+// string machinery, which is a measurable fraction of the parse time on
+// large traces. Anything not in the expected layout falls back to chrono.
+fn parse_wall_clock_time(time_bytes: &[u8]) -> Option<NaiveTime> {
+    parse_fixed_wall_clock_time(time_bytes).or_else(|| {
+        time_bytes
+            .to_str()
+            .ok()
+            .and_then(|t| NaiveTime::parse_from_str(t, "%H:%M:%S%.6f").ok())
+    })
+}
+
+fn parse_fixed_wall_clock_time(time_bytes: &[u8]) -> Option<NaiveTime> {
+    // 13:43:39.888658, or 13:43:39 from '-t'
+    //   ^  ^  ^
+    let usecs = match time_bytes.len() {
+        15 if time_bytes[8] == b'.' => parse_digits(&time_bytes[9..15])?,
+        8 => 0,
+        _ => return None,
+    };
+    if time_bytes[2] != b':' || time_bytes[5] != b':' {
+        return None;
+    }
+
+    let hours = parse_digits(&time_bytes[0..2])?;
+    let mins = parse_digits(&time_bytes[3..5])?;
+    let secs = parse_digits(&time_bytes[6..8])?;
+
+    NaiveTime::from_hms_micro_opt(hours, mins, secs, usecs)
+}
+
+// '-ttt' timestamps are as fixed, seconds since the epoch and six digits of
+// microseconds, so they skip both chrono and the fallback to
+// `parse_unix_timestamp`'s string handling
+fn parse_fixed_unix_timestamp_us(time_bytes: &[u8]) -> Option<i64> {
+    // 1546409294.931558
+    //           ^
+    let dot = time_bytes.len().checked_sub(7)?;
+    // Up to 12 digits of seconds fit in an i64 of microseconds and in chrono's range
+    if dot == 0 || dot > 12 || time_bytes[dot] != b'.' {
+        return None;
+    }
+    let secs = time_bytes[..dot].iter().try_fold(0i64, |acc, &d| {
+        if d.is_ascii_digit() {
+            Some(acc * 10 + i64::from(d - b'0'))
         } else {
             None
         }
-    };
+    })?;
+    let usecs = parse_digits(&time_bytes[dot + 1..])?;
+
+    Some(secs * 1_000_000 + i64::from(usecs))
+}
+
+fn parse_digits(digits: &[u8]) -> Option<u32> {
+    digits.iter().try_fold(0u32, |acc, &d| {
+        if d.is_ascii_digit() {
+            Some(acc * 10 + u32::from(d - b'0'))
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether `time_bytes` is a '-t' time of day, to the second only
+pub fn is_whole_seconds(time_bytes: &[u8]) -> bool {
+    time_bytes.contains(&b':') && !time_bytes.contains(&b'.')
+}
+
+/// Milliseconds from `start` to `end`, where both are either wall clock
+/// times or unix timestamps
+pub fn elapsed_ms(start: &[u8], end: &[u8]) -> Option<f32> {
+    let elapsed = Timestamp::parse(end)?.since(Timestamp::parse(start)?)?;
+    elapsed.num_microseconds().map(|us| us as f32 / 1000.0)
+}
+
+/// Microseconds since midnight for wall clock times, or since the epoch for
+/// unix timestamps. Only comparable between times in the same format.
+pub fn parse_timestamp_us(time_bytes: &[u8]) -> Option<i64> {
+    Timestamp::parse(time_bytes).map(Timestamp::as_micros)
+}
+
+/// Formats microseconds from `parse_timestamp_us` in the same layout as
+/// `like`, a timestamp from the same trace
+pub fn format_timestamp_us(us: i64, like: &[u8]) -> String {
+    let wall_clock = Timestamp::parse(like).is_some_and(Timestamp::is_wall_clock);
+    Timestamp { us, wall_clock }.to_string()
+}
+
+/// A timestamp from a trace as microseconds since the unix epoch. '-tt' wall
+/// clock times have no date, so they are placed on the epoch's first day and
+/// can only be compared with unix timestamps from '-ttt' after converting one
+/// to the other's format with `on_date` or `time_of_day`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    us: i64,
+    wall_clock: bool,
+}
+
+impl Timestamp {
+    /// Parses a '-t' or '-tt' wall clock time or a '-ttt' unix timestamp
+    pub fn parse(time_bytes: &[u8]) -> Option<Timestamp> {
+        if let Some(us) = parse_fixed_unix_timestamp_us(time_bytes) {
+            Some(Timestamp {
+                us,
+                wall_clock: false,
+            })
+        } else if let Some(t) = parse_wall_clock_time(time_bytes) {
+            let us = (t - NaiveTime::MIN).num_microseconds()?;
+            Some(Timestamp {
+                us,
+                wall_clock: true,
+            })
+        } else {
+            let us = parse_unix_timestamp(time_bytes)?
+                .and_utc()
+                .timestamp_micros();
+            Some(Timestamp {
+                us,
+                wall_clock: false,
+            })
+        }
+    }
+
+    pub fn as_micros(self) -> i64 {
+        self.us
+    }
+
+    pub fn is_wall_clock(self) -> bool {
+        self.wall_clock
+    }
+
+    /// A wall clock time as a unix timestamp on `date`, in UTC. Unix
+    /// timestamps are returned unchanged.
+    pub fn on_date(self, date: NaiveDate) -> Timestamp {
+        if !self.wall_clock {
+            return self;
+        }
+        let midnight = date.and_time(NaiveTime::MIN).and_utc().timestamp_micros();
+        Timestamp {
+            us: midnight + self.us,
+            wall_clock: false,
+        }
+    }
+
+    /// A unix timestamp as the wall clock time of day, in UTC. Wall clock
+    /// times are returned unchanged.
+    pub fn time_of_day(self) -> Timestamp {
+        Timestamp {
+            us: self.us.rem_euclid(US_PER_DAY),
+            wall_clock: true,
+        }
+    }
+
+    /// Time from `earlier` to this timestamp, if both are in the same format
+    pub fn since(self, earlier: Timestamp) -> Option<Duration> {
+        if self.wall_clock == earlier.wall_clock {
+            Some(Duration::microseconds(self.us - earlier.us))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.wall_clock {
+            let time = NaiveTime::MIN + Duration::microseconds(self.us);
+            write!(f, "{}", time.format("%H:%M:%S%.6f"))
+        } else {
+            write!(
+                f,
+                "{}.{:06}",
+                self.us.div_euclid(1_000_000),
+                self.us.rem_euclid(1_000_000)
+            )
+        }
+    }
+}
+
+/// Converts a trace's timestamps to RFC 3339 datetimes, to line them up with
+/// other logs. '-tt' wall clock times are read in `offset` on `date`, the day
+/// the trace started, and times earlier than the trace's first are taken to be
+/// on the day after, as a trace running past midnight wraps back to 00:00.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AbsoluteTime {
+    date: Option<NaiveDate>,
+    offset: FixedOffset,
+    first: Option<Timestamp>,
+}
+
+impl AbsoluteTime {
+    /// Converts times on `date`, if given, in `offset`, where `first` is the
+    /// trace's first timestamp
+    pub fn new(date: Option<NaiveDate>, offset: FixedOffset, first: &[u8]) -> AbsoluteTime {
+        AbsoluteTime {
+            date,
+            offset,
+            first: Timestamp::parse(first),
+        }
+    }
+
+    /// Parses a date of the form 'YYYY-MM-DD', optionally followed by the UTC
+    /// offset of the traced host's clock, e.g. '2020-09-13+02:00'
+    pub fn parse_date(date: &str) -> Result<(NaiveDate, FixedOffset), String> {
+        let invalid = || {
+            format!(
+                "Invalid date '{}', expected e.g. '2020-09-13' or '2020-09-13+02:00'",
+                date
+            )
+        };
+        let (day, offset) = match date.get(10..) {
+            Some("") | Some("Z") => (&date[..10], FixedOffset::east_opt(0)),
+            Some(offset) => (&date[..10], parse_offset(offset)),
+            None => return Err(invalid()),
+        };
+
+        let day = NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(|_| invalid())?;
+        Ok((day, offset.ok_or_else(invalid)?))
+    }
+
+    /// Whether `time` needs a date to be converted
+    pub fn needs_date(time: &[u8]) -> bool {
+        Timestamp::parse(time).is_some_and(Timestamp::is_wall_clock)
+    }
+
+    /// `time` as e.g. '2020-09-13T10:20:00.500000Z', or `None` if it isn't a
+    /// timestamp, or is a wall clock time and no date was given
+    pub fn format(&self, time: &[u8]) -> Option<String> {
+        let datetime = self.datetime(time)?;
+        Some(datetime.to_rfc3339_opts(SecondsFormat::Micros, true))
+    }
+
+    /// `time` as microseconds since the unix epoch, `None` as for `format`
+    pub fn unix_micros(&self, time: &[u8]) -> Option<i64> {
+        self.datetime(time)
+            .map(|datetime| datetime.timestamp_micros())
+    }
 
-    match (start_token, end_token) {
-        (Some(start), Some(end)) => {
-            let start_time;
-            if let Ok(t) = NaiveTime::parse_from_str(start.to_str_lossy().as_ref(), "%H:%M:%S%.6f")
+    fn datetime(&self, time: &[u8]) -> Option<DateTime<FixedOffset>> {
+        let time = Timestamp::parse(time)?;
+        let datetime = if time.wall_clock {
+            let mut us = time.us;
+            if self
+                .first
+                .is_some_and(|first| first.wall_clock && us < first.us)
             {
-                start_time = Some(t);
-            } else if let Some(t) = parse_unix_timestamp(start) {
-                start_time = Some(t.time());
-            } else {
-                start_time = None;
+                us += US_PER_DAY;
             }
+            let local = self.date?.and_time(NaiveTime::MIN) + Duration::microseconds(us);
+            self.offset.from_local_datetime(&local).single()?
+        } else {
+            DateTime::from_timestamp_micros(time.us)?.with_timezone(&self.offset)
+        };
 
-            let end_time;
-            if let Ok(t) = NaiveTime::parse_from_str(end.to_str_lossy().as_ref(), "%H:%M:%S%.6f") {
-                end_time = Some(t);
-            } else if let Some(t) = parse_unix_timestamp(end) {
-                end_time = Some(t.time());
-            } else {
-                end_time = None;
-            }
-            match (start_time, end_time) {
-                (Some(starting), Some(ending)) => Some(ending - starting),
-                _ => None,
-            }
-        }
-        _ => None,
+        Some(datetime)
+    }
+}
+
+// '+HH:MM' or '-HH:MM'
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let (sign, hh_mm) = match offset.split_at_checked(1)? {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let (hours, mins) = hh_mm.split_once(':')?;
+    if hours.len() != 2 || mins.len() != 2 {
+        return None;
     }
+    let secs = hours.parse::<i32>().ok()? * 3600 + mins.parse::<i32>().ok()? * 60;
+    FixedOffset::east_opt(sign * secs)
 }
 
-pub fn parse_unix_timestamp(time_bytes: &[u8]) -> Option<NaiveDateTime> {
+fn parse_unix_timestamp(time_bytes: &[u8]) -> Option<NaiveDateTime> {
     let time = time_bytes.to_str_lossy().to_string();
     let mut split_iter = time.split('.');
 
-    let secs = match split_iter.next().and_then(|s| s.parse::<i64>().ok()) {
-        Some(s) => s,
-        None => return None,
-    };
+    let secs = split_iter.next().and_then(|s| s.parse::<i64>().ok())?;
     let nanosecs = match split_iter.next().and_then(|n| n.parse::<u32>().ok()) {
         Some(n) => n * 1000, // strace provides usecs, convert to nsecs
         None => return None,
     };
 
-    NaiveDateTime::from_timestamp_opt(secs, nanosecs)
+    DateTime::from_timestamp(secs, nanosecs).map(|dt| dt.naive_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chrono_parse(time: &str) -> Option<NaiveTime> {
+        NaiveTime::parse_from_str(time, "%H:%M:%S%.6f").ok()
+    }
+
+    #[test]
+    fn elapsed_real_time_handles_line_endings() {
+        let lf = b"1 10:00:00.000000 brk(NULL) = 0x1 <0.000001>\n2 10:00:01.500000 brk(NULL) = 0x1 <0.000001>\n";
+        let crlf = b"1 10:00:00.000000 brk(NULL) = 0x1 <0.000001>\r\n2 10:00:01.500000 brk(NULL) = 0x1 <0.000001>\r\n";
+        let no_trailing = b"1 10:00:00.000000 brk(NULL) = 0x1 <0.000001>\n2 10:00:01.500000 brk(NULL) = 0x1 <0.000001>";
+
+        for input in [&lf[..], &crlf[..], &no_trailing[..]] {
+            assert_eq!(
+                parse_elapsed_real_time(input),
+                Some(Duration::milliseconds(1500))
+            );
+        }
+    }
+
+    #[test]
+    fn timestamp_us_parsed() {
+        assert_eq!(parse_timestamp_us(b"00:00:01.000002"), Some(1_000_002));
+        assert_eq!(
+            parse_timestamp_us(b"1554236049.123456"),
+            Some(1_554_236_049_123_456)
+        );
+        assert_eq!(parse_timestamp_us(b"garbage"), None);
+
+        // '-t' times are to the second
+        assert_eq!(parse_timestamp_us(b"00:00:01"), Some(1_000_000));
+        assert_eq!(parse_timestamp_us(b"24:00:00"), None);
+        assert!(is_whole_seconds(b"00:00:01"));
+        assert!(!is_whole_seconds(b"00:00:01.000002"));
+        assert!(!is_whole_seconds(b"1554236049.123456"));
+    }
+
+    #[test]
+    fn timestamps_converted_between_formats() {
+        let wall = Timestamp::parse(b"10:00:00.000000").unwrap();
+        let unix = Timestamp::parse(b"1599992400.500000").unwrap();
+
+        assert_eq!(unix.since(wall), None);
+        assert_eq!(
+            unix.time_of_day().since(wall),
+            Some(Duration::milliseconds(20 * 60 * 1000 + 500))
+        );
+
+        let date = NaiveDate::from_ymd_opt(2020, 9, 13).unwrap();
+        assert_eq!(
+            unix.since(wall.on_date(date)),
+            Some(Duration::milliseconds(20 * 60 * 1000 + 500))
+        );
+        assert_eq!(unix.on_date(date), unix);
+        assert_eq!(wall.on_date(date).to_string(), "1599991200.000000");
+        assert_eq!(unix.time_of_day().to_string(), "10:20:00.500000");
+
+        let mixed = b"1 10:00:00.000000 brk(NULL) = 0x1 <0.000001>\n2 1599992400.500000 brk(NULL) = 0x1 <0.000001>\n";
+        assert_eq!(
+            parse_elapsed_real_time(mixed),
+            Some(Duration::milliseconds(20 * 60 * 1000 + 500))
+        );
+    }
+
+    #[test]
+    fn absolute_time_formatted_as_rfc3339() {
+        let (date, utc) = AbsoluteTime::parse_date("2020-09-13").unwrap();
+        let absolute = AbsoluteTime::new(Some(date), utc, b"23:59:59.500000");
+
+        assert_eq!(
+            absolute.format(b"23:59:59.750000").as_deref(),
+            Some("2020-09-13T23:59:59.750000Z")
+        );
+        // Past midnight the wall clock wraps around
+        assert_eq!(
+            absolute.format(b"00:00:01.000000").as_deref(),
+            Some("2020-09-14T00:00:01.000000Z")
+        );
+        assert_eq!(
+            absolute.format(b"1599992400.500000").as_deref(),
+            Some("2020-09-13T10:20:00.500000Z")
+        );
+        assert_eq!(absolute.format(b"-"), None);
+
+        let (date, offset) = AbsoluteTime::parse_date("2020-09-13+02:00").unwrap();
+        let absolute = AbsoluteTime::new(Some(date), offset, b"10:00:00.000000");
+        assert_eq!(
+            absolute.format(b"12:20:00.500000").as_deref(),
+            Some("2020-09-13T12:20:00.500000+02:00")
+        );
+        assert_eq!(
+            absolute.format(b"1599992400.500000").as_deref(),
+            Some("2020-09-13T12:20:00.500000+02:00")
+        );
+
+        let no_date = AbsoluteTime::new(None, utc, b"10:00:00.000000");
+        assert_eq!(no_date.format(b"10:00:00.000000"), None);
+        assert!(AbsoluteTime::needs_date(b"10:00:00.000000"));
+        assert!(!AbsoluteTime::needs_date(b"1599992400.500000"));
+
+        assert!(AbsoluteTime::parse_date("2020-09-13 02:00").is_err());
+        assert!(AbsoluteTime::parse_date("2020-9-13").is_err());
+        assert!(AbsoluteTime::parse_date("13/09/2020").is_err());
+    }
+
+    #[test]
+    fn traced_time_excludes_gaps() {
+        let spans: [(&[u8], &[u8]); 3] = [
+            (b"10:00:00.000000", b"10:00:02.000000"),
+            (b"10:00:01.000000", b"10:00:03.000000"),
+            (b"10:00:10.000000", b"10:00:10.500000"),
+        ];
+        assert_eq!(traced_time(spans), Some(Duration::milliseconds(3500)));
+        assert_eq!(traced_time(Vec::new()), None);
+    }
+
+    #[test]
+    fn timestamp_us_formatted_like_trace() {
+        assert_eq!(
+            format_timestamp_us(36_587_000_002, b"10:09:47.836504"),
+            "10:09:47.000002"
+        );
+        assert_eq!(
+            format_timestamp_us(1_600_000_000_000_002, b"1600000000.123456"),
+            "1600000000.000002"
+        );
+    }
+
+    #[test]
+    fn wall_clock_time_matches_chrono() {
+        for time in &[
+            "00:00:00.000000",
+            "00:09:47.789648",
+            "13:43:39.888658",
+            "23:59:59.999999",
+            "09:07:12.000001",
+        ] {
+            assert_eq!(
+                chrono_parse(time),
+                parse_wall_clock_time(time.as_bytes()),
+                "{}",
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn wall_clock_time_rejects_invalid_like_chrono() {
+        for time in &[
+            "",
+            "24:00:00.000000",
+            "12:60:00.000000",
+            "1a:00:00.000000",
+            "12-00-00.000000",
+            "1546409294.931558",
+        ] {
+            assert_eq!(
+                chrono_parse(time),
+                parse_wall_clock_time(time.as_bytes()),
+                "{}",
+                time
+            );
+        }
+    }
+
+    #[test]
+    fn unix_timestamp_matches_fallback() {
+        let fallback = |time: &str| {
+            parse_unix_timestamp(time.as_bytes()).map(|t| t.and_utc().timestamp_micros())
+        };
+        for time in &[
+            "0.000000",
+            "1546409294.931558",
+            "1600000000.000001",
+            "999999999999.999999",
+        ] {
+            assert_eq!(
+                parse_fixed_unix_timestamp_us(time.as_bytes()),
+                fallback(time),
+                "{}",
+                time
+            );
+        }
+        for time in &[
+            "",
+            ".000000",
+            "1546409294.93155",
+            "15464a9294.931558",
+            "13:43:39.888658",
+        ] {
+            assert_eq!(
+                parse_fixed_unix_timestamp_us(time.as_bytes()),
+                None,
+                "{}",
+                time
+            );
+        }
+        // Other layouts still parse through the fallback
+        assert_eq!(
+            Timestamp::parse(b"1546409294.9315").map(Timestamp::as_micros),
+            fallback("1546409294.9315")
+        );
+    }
+
+    #[test]
+    fn wall_clock_time_falls_back_to_chrono() {
+        assert_eq!(
+            chrono_parse("12:00:60.000000"),
+            parse_wall_clock_time(b"12:00:60.000000")
+        );
+    }
 }
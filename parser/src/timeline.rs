@@ -0,0 +1,167 @@
+use crate::events::{self, Event};
+use crate::Pid;
+
+use chrono::Duration;
+use std::iter::FusedIterator;
+
+/// A PID's calls in the order they started, to be walked over windows of time
+#[derive(Clone, Debug, PartialEq)]
+pub struct PidTimeline<'a> {
+    pub pid: Pid,
+    events: Vec<Event<'a>>,
+}
+
+impl<'a> PidTimeline<'a> {
+    pub fn events(&self) -> &[Event<'a>] {
+        &self.events
+    }
+
+    /// The PID's calls split into back-to-back windows of `duration`, from the
+    /// start of its first call to that of its last. Each window borrows the
+    /// calls started in it, windows without any are still yielded so gaps in
+    /// the PID's activity are seen, and the windows can be combined into
+    /// sliding ones by their stats. A `duration` under a microsecond yields
+    /// nothing.
+    pub fn windows(&self, duration: Duration) -> Windows<'_, 'a> {
+        let duration_us = duration.num_microseconds().unwrap_or(i64::MAX);
+        Windows {
+            events: if duration_us > 0 { &self.events } else { &[] },
+            start_us: self.events.first().map_or(0, Event::start_us),
+            duration_us,
+        }
+    }
+}
+
+/// Totals for the calls of a window
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WindowStats {
+    pub calls: usize,
+    pub errors: usize,
+    /// Time in the calls, in ms
+    pub time: f32,
+    /// The longest call, in ms
+    pub max: f32,
+}
+
+impl WindowStats {
+    fn new(events: &[Event]) -> WindowStats {
+        events
+            .iter()
+            .fold(WindowStats::default(), |mut stats, event| {
+                stats.calls += event.count;
+                stats.errors += usize::from(event.errno.is_some());
+                stats.time += event.duration;
+                stats.max = stats.max.max(event.duration);
+                stats
+            })
+    }
+}
+
+/// The calls a PID started in `[start_us, end_us)`, in microseconds as given
+/// by `time::parse_timestamp_us`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Window<'t, 'a> {
+    pub start_us: i64,
+    pub end_us: i64,
+    pub events: &'t [Event<'a>],
+    pub stats: WindowStats,
+}
+
+/// The windows of a `PidTimeline`, see `PidTimeline::windows`
+#[derive(Clone, Debug)]
+pub struct Windows<'t, 'a> {
+    events: &'t [Event<'a>],
+    start_us: i64,
+    duration_us: i64,
+}
+
+impl<'t, 'a> Iterator for Windows<'t, 'a> {
+    type Item = Window<'t, 'a>;
+
+    fn next(&mut self) -> Option<Window<'t, 'a>> {
+        if self.events.is_empty() {
+            return None;
+        }
+
+        let end_us = self.start_us.saturating_add(self.duration_us);
+        let len = self
+            .events
+            .iter()
+            .position(|event| event.start_us() >= end_us)
+            .unwrap_or(self.events.len());
+        let (events, rest) = self.events.split_at(len);
+
+        let window = Window {
+            start_us: self.start_us,
+            end_us,
+            events,
+            stats: WindowStats::new(events),
+        };
+        self.events = rest;
+        self.start_us = end_us;
+
+        Some(window)
+    }
+}
+
+impl FusedIterator for Windows<'_, '_> {}
+
+/// A timeline for each of `pids` that made a call, ordered by PID
+pub fn timelines<'a>(buffer: &'a [u8], pids: &[Pid]) -> Vec<PidTimeline<'a>> {
+    let mut events = events::events(buffer, pids, 0);
+    // Sorted by start time, which is kept within each PID
+    events.sort_by_key(|event| event.pid);
+
+    let mut timelines: Vec<PidTimeline> = Vec::new();
+    for event in events {
+        match timelines.last_mut() {
+            Some(timeline) if timeline.pid == event.pid => timeline.events.push(event),
+            _ => timelines.push(PidTimeline {
+                pid: event.pid,
+                events: vec![event],
+            }),
+        }
+    }
+
+    timelines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeline_windows_borrow_calls_and_keep_gaps() {
+        let input = br##"477   00:09:56.000000 stat("/a", 0x7ffd4c3b8d70) = 0 <0.000100>
+495   00:09:56.100000 stat("/b", 0x7ffd4c3b8d70) = 0 <0.000100>
+477   00:09:56.400000 stat("/c", 0x7ffd4c3b8d70) = -1 ENOENT (No such file or directory) <0.000300>
+477   00:09:57.700000 read(0</dev/null>,  <unfinished ...>
+477   00:09:58.900000 <... read resumed>"", 10) = 0 <1.200000>
+477   00:09:59.100000 stat("/d", 0x7ffd4c3b8d70) = 0 <0.000100>"##;
+        let timelines = timelines(input, &[477, 495]);
+        assert_eq!(timelines.len(), 2);
+        let timeline = &timelines[0];
+        assert_eq!((timeline.pid, timeline.events().len()), (477, 4));
+
+        let windows: Vec<_> = timeline.windows(Duration::seconds(1)).collect();
+        assert_eq!(windows.len(), 4);
+        assert_eq!(windows[0].events.len(), 2);
+        assert_eq!(
+            windows[0].stats,
+            WindowStats {
+                calls: 2,
+                errors: 1,
+                time: 0.4,
+                max: 0.3,
+            }
+        );
+        // The read is placed where it started
+        assert_eq!(windows[1].events[0].syscall, b"read");
+        assert!((windows[1].stats.max - 1200.0).abs() < 0.01);
+        assert!(windows[2].events.is_empty());
+        assert_eq!(windows[2].end_us - windows[2].start_us, 1_000_000);
+        assert_eq!(windows[3].events[0].time, b"00:09:59.100000");
+
+        assert_eq!(timeline.windows(Duration::zero()).count(), 0);
+    }
+}
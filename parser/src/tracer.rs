@@ -0,0 +1,188 @@
+use crate::parser::{CallStatus, OtherFields};
+use crate::syscall_data::PidData;
+use crate::users::status_pid;
+use crate::{HashMap, Pid};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Reads of a '/proc/<pid>/status' file listing a tracer, by one PID of the
+/// process the file describes
+#[derive(Clone, Debug, PartialEq)]
+pub struct TracerCheck<'a> {
+    /// The PID that read the file
+    pub pid: Pid,
+    /// The PID the file describes
+    pub target: Pid,
+    pub tracer: Pid,
+    pub reads: usize,
+    pub first_time: &'a [u8],
+    /// Whether the file describes the reader or a thread of its process, so
+    /// the program could tell it was being traced
+    pub own: bool,
+}
+
+/// Signs in the trace that the traced programs noticed the tracer, or that
+/// something other than strace was tracing them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Interference<'a> {
+    pub checks: Vec<TracerCheck<'a>>,
+    /// PIDs that called 'ptrace' themselves, with their calls and failures.
+    /// 'PTRACE_TRACEME' fails under strace, a common check for a debugger.
+    pub ptrace_calls: Vec<(Pid, usize, usize)>,
+}
+
+impl<'a> Interference<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty() && self.ptrace_calls.is_empty()
+    }
+
+    /// The tracer PIDs the status files listed, the most often seen first.
+    /// strace traces every process it follows itself, so more than one means
+    /// a debugger or another tracer was attached too.
+    pub fn tracers(&self) -> Vec<Pid> {
+        let mut reads: BTreeMap<Pid, usize> = BTreeMap::new();
+        for check in &self.checks {
+            *reads.entry(check.tracer).or_default() += check.reads;
+        }
+
+        let mut tracers: Vec<_> = reads.into_iter().collect();
+        tracers.sort_by(|x, y| y.1.cmp(&x.1).then(x.0.cmp(&y.0)));
+        tracers.into_iter().map(|(pid, _)| pid).collect()
+    }
+
+    /// One line for each sign of interference
+    pub fn messages(&self, traced: &[Pid]) -> Vec<String> {
+        let mut messages = Vec::new();
+
+        for check in self.checks.iter().filter(|c| c.own) {
+            messages.push(format!(
+                "PID {} read TracerPid {} from its own status {} {}, it can tell it's being traced",
+                check.pid,
+                check.tracer,
+                check.reads,
+                if check.reads == 1 { "time" } else { "times" }
+            ));
+        }
+
+        let tracers = self.tracers();
+        if tracers.len() > 1 {
+            let pids: Vec<_> = tracers.iter().map(|pid| pid.to_string()).collect();
+            messages.push(format!(
+                "status files list {} tracers ({}), a debugger or another tracer was attached too",
+                tracers.len(),
+                pids.join(", ")
+            ));
+        }
+        let traced_tracers: BTreeSet<_> = self
+            .checks
+            .iter()
+            .filter(|c| traced.contains(&c.tracer))
+            .map(|c| (c.tracer, c.target))
+            .collect();
+        for (tracer, target) in traced_tracers {
+            messages.push(format!(
+                "PID {} in the trace was tracing PID {}",
+                tracer, target
+            ));
+        }
+
+        for &(pid, calls, failed) in &self.ptrace_calls {
+            messages.push(format!(
+                "PID {} called 'ptrace' {} {} ({} failed), it traces other processes or checks for a debugger",
+                pid,
+                calls,
+                if calls == 1 { "time" } else { "times" },
+                failed
+            ));
+        }
+
+        messages
+    }
+}
+
+/// Finds reads of status files that list a tracer, and 'ptrace' calls, by
+/// the PIDs in `raw_data`
+pub fn interference<'a>(raw_data: &HashMap<Pid, PidData<'a>>) -> Interference<'a> {
+    // The threads of each PID's process, with itself
+    let mut process: HashMap<Pid, Pid> = HashMap::default();
+    for (&pid, pid_data) in raw_data {
+        for &thread in &pid_data.threads {
+            process.insert(thread, pid);
+        }
+    }
+    let process_of = |pid: Pid| process.get(&pid).copied().unwrap_or(pid);
+
+    let mut checks: BTreeMap<(Pid, Pid, Pid), TracerCheck> = BTreeMap::new();
+    let mut ptrace_calls = Vec::new();
+    for (&pid, pid_data) in raw_data {
+        for raw_data in &pid_data.uid_events {
+            let (file, tracer) = match raw_data.other {
+                Some(OtherFields::ProcStatus {
+                    file,
+                    tracer_pid: Some(tracer),
+                    ..
+                }) if raw_data.call_status == CallStatus::Complete => (file, tracer),
+                _ => continue,
+            };
+            let target = status_pid(file).unwrap_or(pid);
+
+            let check = checks
+                .entry((pid, target, tracer))
+                .or_insert_with(|| TracerCheck {
+                    pid,
+                    target,
+                    tracer,
+                    reads: 0,
+                    first_time: raw_data.time,
+                    own: process_of(target) == process_of(pid),
+                });
+            check.reads += 1;
+            check.first_time = check.first_time.min(raw_data.time);
+        }
+
+        if let Some(ptrace) = pid_data.syscall_data.get(b"ptrace".as_ref()) {
+            let calls = ptrace.lengths.len() + ptrace.fast_calls;
+            let failed = ptrace.errors.values().map(|&ct| ct as usize).sum();
+            ptrace_calls.push((pid, calls, failed));
+        }
+    }
+    ptrace_calls.sort_unstable();
+
+    Interference {
+        checks: checks.into_values().collect(),
+        ptrace_calls,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn interference_finds_own_status_checks_and_ptrace() {
+        let input = br##"823   00:09:51.247900 read(44</proc/823/status>, "Name:\tbundle\nPPid:\t495\nTracerPid:\t7388\nUid:\t998\t998\t998\t998\n", 1024) = 61 <0.000020>
+823   00:09:51.253678 read(44</proc/823/status>, "Name:\tbundle\nPPid:\t495\nTracerPid:\t7388\nUid:\t998\t998\t998\t998\n", 1024) = 61 <0.000020>
+495   00:09:51.300000 read(5</proc/823/status>, "Name:\tbundle\nPPid:\t495\nTracerPid:\t7388\nUid:\t998\t998\t998\t998\n", 1024) = 61 <0.000020>
+495   00:09:51.310000 read(5</proc/901/status>, "Name:\tgdb\nPPid:\t1\nTracerPid:\t0\nUid:\t0\t0\t0\t0\n", 1024) = 44 <0.000020>
+901   00:09:51.400000 ptrace(PTRACE_TRACEME) = -1 EPERM (Operation not permitted) <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let interference = interference(&pid_data_map);
+
+        assert_eq!(interference.checks.len(), 2);
+        let own = &interference.checks[1];
+        assert_eq!((own.pid, own.target, own.tracer), (823, 823, 7388));
+        assert_eq!((own.reads, own.own), (2, true));
+        assert_eq!(own.first_time, b"00:09:51.247900");
+        assert!(!interference.checks[0].own);
+        assert_eq!(interference.tracers(), vec![7388]);
+        assert_eq!(interference.ptrace_calls, vec![(901, 1, 1)]);
+
+        let messages = interference.messages(&[495, 823, 901]);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages[0],
+            "PID 823 read TracerPid 7388 from its own status 2 times, it can tell it's being traced"
+        );
+    }
+}
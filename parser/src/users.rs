@@ -0,0 +1,193 @@
+use crate::parser::{CallStatus, OtherFields, RawData};
+use crate::pid_summary::PidSummary;
+use crate::syscall_data::PidData;
+use crate::{HashMap, Pid};
+
+use bstr::ByteSlice;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+/// An effective uid a PID was seen with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UidEvent<'a> {
+    pub time: &'a [u8],
+    pub euid: u32,
+    /// Set by the call rather than observed, so the uid before it is unknown
+    pub changed: bool,
+}
+
+/// The uids each PID was seen with from 'setuid' and friends, 'geteuid',
+/// and reads of '/proc/<pid>/status', in time order. A status file read by
+/// one PID may describe another.
+pub fn uid_events<'a>(raw_data: &HashMap<Pid, PidData<'a>>) -> HashMap<Pid, Vec<UidEvent<'a>>> {
+    let mut events: HashMap<Pid, Vec<UidEvent>> = HashMap::default();
+
+    for (&pid, pid_data) in raw_data {
+        for (target, event) in pid_data.uid_events.iter().filter_map(|e| uid_event(pid, e)) {
+            events.entry(target).or_default().push(event);
+        }
+    }
+
+    for pid_events in events.values_mut() {
+        pid_events.sort_by(|x, y| (x.time).cmp(y.time));
+    }
+
+    events
+}
+
+fn uid_event<'a>(pid: Pid, raw_data: &RawData<'a>) -> Option<(Pid, UidEvent<'a>)> {
+    // Unfinished calls are split across lines, and the arguments and result can't be paired
+    if raw_data.error.is_some() || raw_data.call_status != CallStatus::Complete {
+        return None;
+    }
+
+    let (target, euid, changed) = match (raw_data.syscall, &raw_data.other) {
+        (_, Some(OtherFields::Uid(euid))) => (pid, *euid, true),
+        (_, Some(OtherFields::ProcStatus { file, euid, .. })) => {
+            (status_pid(file).unwrap_or(pid), (*euid)?, false)
+        }
+        (b"geteuid" | b"geteuid32", _) => (pid, u32::try_from(raw_data.rtn_cd?).ok()?, false),
+        _ => return None,
+    };
+
+    Some((
+        target,
+        UidEvent {
+            time: raw_data.time,
+            euid,
+            changed,
+        },
+    ))
+}
+
+/// The PID a status file describes, 495 for '/proc/495/status', or 497 for
+/// '/proc/495/task/497/status'
+pub fn status_pid(file: &[u8]) -> Option<Pid> {
+    file.strip_prefix(b"/proc/")?
+        .strip_suffix(b"/status")?
+        .rsplit_str("/")
+        .next()?
+        .to_str()
+        .ok()?
+        .parse::<Pid>()
+        .ok()
+}
+
+/// The effective uid of each PID at its last activity. A PID that wasn't seen
+/// with a uid before then takes its parent's at the time it was forked.
+pub fn effective_uids(
+    pid_summaries: &HashMap<Pid, PidSummary>,
+    events: &HashMap<Pid, Vec<UidEvent>>,
+) -> HashMap<Pid, u32> {
+    pid_summaries
+        .iter()
+        .filter_map(|(&pid, summary)| {
+            let uid = uid_at(pid, summary.end_time, pid_summaries, events, 0)?;
+            Some((pid, uid))
+        })
+        .collect()
+}
+
+fn uid_at(
+    pid: Pid,
+    time: &[u8],
+    pid_summaries: &HashMap<Pid, PidSummary>,
+    events: &HashMap<Pid, Vec<UidEvent>>,
+    depth: usize,
+) -> Option<u32> {
+    // Guards against a cycle of parents from a trace with reused PIDs
+    if depth > pid_summaries.len() {
+        return None;
+    }
+
+    let pid_events = events.get(&pid).map(Vec::as_slice).unwrap_or_default();
+    if let Some(event) = pid_events.iter().rev().find(|e| e.time <= time) {
+        return Some(event.euid);
+    }
+
+    let summary = pid_summaries.get(&pid);
+    summary
+        .and_then(|s| Some((s.parent_pid?, s.start_time)))
+        .and_then(|(parent, start)| uid_at(parent, start, pid_summaries, events, depth + 1))
+        // Nothing changed the uid before it was first observed
+        .or_else(|| pid_events.first().filter(|e| !e.changed).map(|e| e.euid))
+}
+
+/// Totals for the PIDs running as one effective uid
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserSummary {
+    /// `None` for PIDs whose uid is unknown
+    pub euid: Option<u32>,
+    pub pids: usize,
+    pub syscall_count: i32,
+    pub system_active_time: f32,
+    pub system_wait_time: f32,
+    pub user_time: f32,
+    pub total_time: f32,
+}
+
+pub fn user_summaries(pid_summaries: &HashMap<Pid, PidSummary>) -> Vec<UserSummary> {
+    let mut users: BTreeMap<Option<u32>, UserSummary> = BTreeMap::new();
+
+    for pid_summary in pid_summaries.values() {
+        let user = users
+            .entry(pid_summary.euid)
+            .or_insert_with(|| UserSummary {
+                euid: pid_summary.euid,
+                ..UserSummary::default()
+            });
+
+        user.pids += 1;
+        user.syscall_count += pid_summary.syscall_count;
+        user.system_active_time += pid_summary.system_active_time;
+        user.system_wait_time += pid_summary.system_wait_time;
+        user.user_time += pid_summary.user_time;
+        user.total_time += pid_summary.total_time;
+    }
+
+    users.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_summary::SessionSummary;
+    use crate::syscall_data::build_syscall_data;
+    use crate::syscall_stats::build_syscall_stats;
+
+    #[test]
+    fn users_collects_uid_events() {
+        let input = br##"3810  10:02:00.000100 setresuid(-1, 1000, -1) = 0 <0.000012>
+3810  10:02:00.000200 setuid(0) = -1 EPERM (Operation not permitted) <0.000012>
+3811  10:02:00.000300 geteuid() = 33 <0.000002>
+3812  10:02:00.000400 read(3</proc/3813/status>, "Name:\tnginx\nUmask:\t0022\nState:\tS (sleeping)\nUid:\t0\t33\t33\t33\nGid:\t0\t33\t33\t33\n", 1024) = 80 <0.000020>"##;
+        let pid_data_map = build_syscall_data(input);
+        let events = uid_events(&pid_data_map);
+
+        assert_eq!(events[&3810].len(), 1);
+        assert_eq!(events[&3810][0].euid, 1000);
+        assert!(events[&3810][0].changed);
+        assert_eq!(events[&3811][0].euid, 33);
+        assert_eq!(events[&3813][0].euid, 33);
+        assert!(!events.contains_key(&3812));
+    }
+
+    #[test]
+    fn users_children_inherit_uid_at_fork() {
+        let input = br##"3810  10:02:00.000100 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f0) = 3811 <0.000100>
+3811  10:02:00.000200 geteuid() = 0 <0.000002>
+3810  10:02:00.000300 setuid(1000) = 0 <0.000012>
+3810  10:02:00.000400 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f0) = 3812 <0.000100>
+3812  10:02:00.000500 write(1</dev/null>, "a", 1) = 1 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let syscall_stats = build_syscall_stats(&pid_data_map);
+        let summary = SessionSummary::from_syscall_stats(&syscall_stats, &pid_data_map);
+        let users = summary.user_summaries();
+
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[0].euid, Some(0));
+        assert_eq!(users[0].pids, 1);
+        assert_eq!(users[1].euid, Some(1000));
+        assert_eq!(users[1].pids, 2);
+    }
+}
@@ -0,0 +1,423 @@
+use crate::output;
+use crate::parser::{self, CallStatus, LineData, OtherFields, RawData};
+use crate::table::{Align, Column, Table};
+use crate::time::parse_timestamp_us;
+use crate::{HashMap, HashSet, Pid};
+
+use bstr::ByteSlice;
+use rayon::prelude::*;
+use std::fmt;
+use std::io::Error;
+
+// Calls shorter than this are ordinary latency rather than blocking
+const MIN_WAIT_US: i64 = 1000;
+// PIDs followed from a chain's first waiter
+const MAX_DEPTH: usize = 8;
+
+/// How one PID came to be waiting on another
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WaitKind {
+    /// A 'wait4' or 'waitpid' that returned when the child exited
+    Child,
+    /// A wait on a private futex ended by the other PID's wake
+    Futex,
+    /// A read of an empty pipe ended by the other PID's write, or a write
+    /// to a full one ended by its read
+    Pipe,
+}
+
+impl fmt::Display for WaitKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WaitKind::Child => write!(f, "child"),
+            WaitKind::Futex => write!(f, "futex"),
+            WaitKind::Pipe => write!(f, "pipe"),
+        }
+    }
+}
+
+/// A call in which `waiter` was blocked until `holder` acted, with its
+/// start and end in microseconds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WaitEdge<'a> {
+    pub waiter: Pid,
+    pub holder: Pid,
+    pub kind: WaitKind,
+    pub syscall: &'a [u8],
+    pub start: i64,
+    pub end: i64,
+}
+
+impl<'a> WaitEdge<'a> {
+    pub fn waited_us(&self) -> i64 {
+        self.end - self.start
+    }
+}
+
+/// What the last PID in a chain was doing while the PID before it waited
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChainEnd<'a> {
+    /// Blocked in a call not attributed to another PID, such as 'nanosleep',
+    /// for this many microseconds
+    Syscall(&'a [u8], i64),
+    /// Making only short calls, or none
+    Running,
+}
+
+/// PIDs each blocked on the next, starting from the longest wait
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaitChain<'a> {
+    pub edges: Vec<WaitEdge<'a>>,
+    pub end: ChainEnd<'a>,
+}
+
+impl<'a> fmt::Display for WaitChain<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, edge) in self.edges.iter().enumerate() {
+            if idx == 0 {
+                write!(f, "{}", output::pid_label(edge.waiter))?;
+            } else {
+                write!(f, " which")?;
+            }
+            write!(
+                f,
+                " waited {} on {} ({})",
+                secs(edge.waited_us()),
+                output::pid_label(edge.holder),
+                edge.syscall.to_str_lossy()
+            )?;
+        }
+
+        match self.end {
+            ChainEnd::Syscall(syscall, us) => {
+                write!(
+                    f,
+                    " which waited {} in {}",
+                    secs(us),
+                    syscall.to_str_lossy()
+                )
+            }
+            ChainEnd::Running => write!(f, " which was running"),
+        }
+    }
+}
+
+fn secs(us: i64) -> String {
+    format!("{:.3}s", us as f64 / 1_000_000.0)
+}
+
+// A call with its result, joined from its started and resumed lines
+#[derive(Clone, Copy, Debug)]
+struct Call<'a> {
+    pid: Pid,
+    syscall: &'a [u8],
+    start: i64,
+    end: i64,
+    rtn: Option<i32>,
+    failed: bool,
+    futex: Option<(&'a [u8], bool)>,
+    pipe: Option<&'a [u8]>,
+}
+
+impl<'a> Call<'a> {
+    fn new(start: i64, first: &RawData<'a>, last: &RawData<'a>) -> Call<'a> {
+        let end = match last.duration {
+            Some(duration) => start + (duration as f64 * 1_000_000.0) as i64,
+            None => parse_timestamp_us(last.time).unwrap_or(start),
+        };
+        let futex = match first.other {
+            Some(OtherFields::Futex { addr, wait }) => Some((addr, wait)),
+            _ => None,
+        };
+        let pipe = match first.syscall {
+            b"read" | b"write" | b"writev" => first.file().filter(|f| f.starts_with(b"pipe:[")),
+            _ => None,
+        };
+
+        Call {
+            pid: first.pid,
+            syscall: first.syscall,
+            start,
+            end,
+            rtn: last.rtn_cd,
+            failed: last.error.is_some(),
+            futex,
+            pipe,
+        }
+    }
+
+    fn is_long(&self) -> bool {
+        self.end - self.start >= MIN_WAIT_US
+    }
+}
+
+// The calls that can end another PID's wait
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Release<'a> {
+    FutexWake(&'a [u8]),
+    PipeWrite(&'a [u8]),
+    PipeRead(&'a [u8]),
+}
+
+type LongCalls<'a> = HashMap<Pid, Vec<Call<'a>>>;
+
+/// The waits that another PID ended, and each PID's long calls that no other
+/// PID ended
+fn wait_edges(buffer: &[u8]) -> (Vec<WaitEdge<'_>>, LongCalls<'_>) {
+    let mut lines: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(|line| match parser::parse_line(line)? {
+            LineData::Syscall(raw_data) => Some((parse_timestamp_us(raw_data.time)?, raw_data)),
+            _ => None,
+        })
+        .collect();
+    lines.par_sort_by_key(|(time, _)| *time);
+
+    let mut calls = Vec::new();
+    let mut started: HashMap<Pid, (i64, RawData)> = HashMap::default();
+    for (time, raw_data) in lines {
+        match raw_data.call_status {
+            CallStatus::Started => {
+                started.insert(raw_data.pid, (time, raw_data));
+            }
+            CallStatus::Complete => calls.push(Call::new(time, &raw_data, &raw_data)),
+            CallStatus::Resumed => {
+                if let Some((start, first)) = started.remove(&raw_data.pid) {
+                    calls.push(Call::new(start, &first, &raw_data));
+                }
+            }
+        }
+    }
+
+    let mut releases: HashMap<Release, Vec<(i64, Pid)>> = HashMap::default();
+    for call in calls.iter().filter(|c| !c.failed) {
+        let release = match (call.futex, call.pipe, call.syscall) {
+            (Some((_, true)), ..) => continue,
+            // A wake that found no waiter ended no wait
+            (Some((addr, false)), ..) if call.rtn != Some(0) => Release::FutexWake(addr),
+            (_, Some(pipe), b"read") => Release::PipeRead(pipe),
+            (_, Some(pipe), _) => Release::PipeWrite(pipe),
+            _ => continue,
+        };
+        releases
+            .entry(release)
+            .or_default()
+            .push((call.start, call.pid));
+    }
+    // Calls were joined in the order they finished
+    for times in releases.values_mut() {
+        times.sort_unstable();
+    }
+
+    let mut edges = Vec::new();
+    let mut long_calls: HashMap<Pid, Vec<Call>> = HashMap::default();
+    for call in calls.into_iter().filter(|c| c.is_long() && !c.failed) {
+        let holder = match (call.futex, call.pipe, call.syscall) {
+            (_, _, b"wait4" | b"waitpid") => call
+                .rtn
+                .filter(|&child| child > 0)
+                .map(|child| (child as Pid, WaitKind::Child)),
+            (Some((addr, true)), ..) => releaser(&releases, Release::FutexWake(addr), &call)
+                .map(|pid| (pid, WaitKind::Futex)),
+            (_, Some(pipe), b"read") => releaser(&releases, Release::PipeWrite(pipe), &call)
+                .map(|pid| (pid, WaitKind::Pipe)),
+            (_, Some(pipe), _) => {
+                releaser(&releases, Release::PipeRead(pipe), &call).map(|pid| (pid, WaitKind::Pipe))
+            }
+            _ => None,
+        };
+
+        match holder {
+            Some((holder, kind)) if holder != call.pid => edges.push(WaitEdge {
+                waiter: call.pid,
+                holder,
+                kind,
+                syscall: call.syscall,
+                start: call.start,
+                end: call.end,
+            }),
+            _ => long_calls.entry(call.pid).or_default().push(call),
+        }
+    }
+    (edges, long_calls)
+}
+
+// The PID whose call ended `wait`, the last to act during it
+fn releaser(
+    releases: &HashMap<Release, Vec<(i64, Pid)>>,
+    release: Release,
+    wait: &Call,
+) -> Option<Pid> {
+    let times = releases.get(&release)?;
+    let before_end = times.partition_point(|(time, _)| *time <= wait.end);
+
+    times[..before_end]
+        .iter()
+        .rev()
+        .take_while(|(time, _)| *time >= wait.start)
+        .find(|(_, pid)| *pid != wait.pid)
+        .map(|(_, pid)| *pid)
+}
+
+fn overlap(start: i64, end: i64, other_start: i64, other_end: i64) -> i64 {
+    end.min(other_end) - start.max(other_start)
+}
+
+/// The chains of PIDs blocked on each other that began with a wait by one
+/// of `pids`, the longest first. A chain is followed through whatever the
+/// PID waited on was itself waiting on at the time, and ends with the long
+/// call it was blocked in, if any. Chains already part of a longer one are
+/// left out.
+pub fn wait_chains<'a>(buffer: &'a [u8], pids: &[Pid]) -> Vec<WaitChain<'a>> {
+    let pids: HashSet<Pid> = pids.iter().copied().collect();
+    let (edges, long_calls) = wait_edges(buffer);
+
+    // Chains are followed through any PID, not only those asked for
+    let mut by_waiter: HashMap<Pid, Vec<&WaitEdge>> = HashMap::default();
+    for edge in &edges {
+        by_waiter.entry(edge.waiter).or_default().push(edge);
+    }
+
+    let mut chains: Vec<WaitChain> = edges
+        .iter()
+        .filter(|edge| pids.contains(&edge.waiter))
+        .map(|edge| {
+            let mut chain = vec![*edge];
+            let mut seen: HashSet<Pid> = [edge.waiter, edge.holder].iter().copied().collect();
+            let mut end = ChainEnd::Running;
+
+            while chain.len() < MAX_DEPTH {
+                let last = chain[chain.len() - 1];
+                let next = by_waiter.get(&last.holder).and_then(|edges| {
+                    edges
+                        .iter()
+                        .filter(|e| overlap(e.start, e.end, last.start, last.end) > 0)
+                        .max_by_key(|e| overlap(e.start, e.end, last.start, last.end))
+                });
+
+                match next {
+                    Some(next) if seen.insert(next.holder) => chain.push(**next),
+                    Some(_) => break,
+                    None => {
+                        let call = long_calls.get(&last.holder).and_then(|calls| {
+                            calls
+                                .iter()
+                                .filter(|c| overlap(c.start, c.end, last.start, last.end) > 0)
+                                .max_by_key(|c| overlap(c.start, c.end, last.start, last.end))
+                        });
+                        if let Some(call) = call {
+                            end = ChainEnd::Syscall(call.syscall, call.end - call.start);
+                        }
+                        break;
+                    }
+                }
+            }
+
+            WaitChain { edges: chain, end }
+        })
+        .collect();
+
+    chains.sort_by(|x, y| {
+        y.edges[0]
+            .waited_us()
+            .cmp(&x.edges[0].waited_us())
+            .then_with(|| y.edges.len().cmp(&x.edges.len()))
+            .then_with(|| x.edges[0].start.cmp(&y.edges[0].start))
+            .then_with(|| x.edges[0].waiter.cmp(&y.edges[0].waiter))
+    });
+
+    let continued: HashSet<(Pid, i64)> = chains
+        .iter()
+        .flat_map(|c| c.edges.iter().skip(1))
+        .map(|e| (e.waiter, e.start))
+        .collect();
+    chains.retain(|c| !continued.contains(&(c.edges[0].waiter, c.edges[0].start)));
+
+    chains
+}
+
+/// Prints the `count` longest chains of PIDs blocked on each other that
+/// began with a wait by one of `pids`
+pub fn print_wait_chains(
+    buffer: &[u8],
+    pids: &[Pid],
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let chains = wait_chains(buffer, pids);
+
+    output::print_heading("\nLongest Blocking Chains\n-----------\n")?;
+    if chains.is_empty() {
+        output::print_note(
+            "  No PID was found waiting on another, via 'wait4', a private futex, or a pipe\n",
+        )?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("waited (ms)", 11, Align::Right, 0),
+        Column::new("via", 5, Align::Left, 1),
+        Column::new("depth", 5, Align::Right, 2),
+        Column::new("chain", 5, Align::Left, 0),
+    ]);
+    for chain in chains.iter().take(count) {
+        let first = &chain.edges[0];
+        table.add_row(vec![
+            format!("{:.3}", first.waited_us() as f64 / 1000.0),
+            first.kind.to_string(),
+            chain.edges.len().to_string(),
+            chain.to_string(),
+        ]);
+    }
+    table.print(width)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_chains_follow_blocked_pids() {
+        let input = br##"477   00:09:47.000000 wait4(-1,  <unfinished ...>
+7390  00:09:47.100000 read(3<pipe:[500]>,  <unfinished ...>
+7391  00:09:47.200000 futex(0x7f2c, FUTEX_WAIT_PRIVATE, 0, NULL <unfinished ...>
+7392  00:09:47.300000 nanosleep({1, 0},  <unfinished ...>
+7392  00:09:48.300000 <... nanosleep resumed> NULL) = 0 <1.000000>
+7392  00:09:48.310000 futex(0x7f2c, FUTEX_WAKE_PRIVATE, 1) = 1 <0.000010>
+7391  00:09:48.320000 <... futex resumed> ) = 0 <1.120000>
+7391  00:09:48.330000 write(4<pipe:[500]>, "x", 1) = 1 <0.000010>
+7390  00:09:48.340000 <... read resumed> "x", 1) = 1 <1.240000>
+477   00:09:48.400000 <... wait4 resumed> [{WIFEXITED(s) && WEXITSTATUS(s) == 0}], 0, NULL) = 7390 <1.400000>
+477   00:09:48.500000 wait4(-1, 0x7ffe09dbae50, WNOHANG, NULL) = -1 ECHILD (No child processes) <0.000013>"##;
+        let chains = wait_chains(input, &[477, 7390, 7391, 7392]);
+
+        assert_eq!(chains.len(), 1);
+        let chain = &chains[0];
+        let hops: Vec<_> = chain
+            .edges
+            .iter()
+            .map(|e| (e.waiter, e.holder, e.kind))
+            .collect();
+        assert_eq!(
+            hops,
+            vec![
+                (477, 7390, WaitKind::Child),
+                (7390, 7391, WaitKind::Pipe),
+                (7391, 7392, WaitKind::Futex),
+            ]
+        );
+        assert_eq!(chain.end, ChainEnd::Syscall(b"nanosleep", 1_000_000));
+        assert_eq!(
+            chain.to_string(),
+            "477 waited 1.400s on 7390 (wait4) which waited 1.240s on 7391 (read) \
+             which waited 1.120s on 7392 (futex) which waited 1.000s in nanosleep"
+        );
+
+        // The waiter must be one of the PIDs asked for
+        let chains = wait_chains(input, &[7391]);
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].edges[0].waiter, 7391);
+    }
+}
@@ -0,0 +1,262 @@
+use crate::output;
+use crate::parser::{self, CallStatus, LineData};
+use crate::syscall_data::PidData;
+use crate::table::{Align, Column, Table};
+use crate::time::parse_timestamp_us;
+use crate::{HashMap, HashSet, Pid};
+
+use rayon::prelude::*;
+use std::io::Error;
+
+/// How long a parent's children stayed zombies, from exiting to being reaped
+/// by a 'wait' call
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParentReaping {
+    pub pid: Pid,
+    /// Children seen exiting
+    pub exited: usize,
+    /// Time from each reaped child's exit until its 'wait' returned, in ms,
+    /// shortest first
+    pub latencies: Vec<f32>,
+    /// The most children exited but not yet reaped at once
+    pub max_unreaped: usize,
+    /// Children still unreaped when the trace ends
+    pub unreaped: Vec<Pid>,
+}
+
+impl ParentReaping {
+    pub fn min(&self) -> f32 {
+        self.latencies.first().copied().unwrap_or_default()
+    }
+
+    pub fn median(&self) -> f32 {
+        match self.latencies.len() {
+            0 => 0.0,
+            len if len.is_multiple_of(2) => {
+                (self.latencies[len / 2 - 1] + self.latencies[len / 2]) / 2.0
+            }
+            len => self.latencies[len / 2],
+        }
+    }
+
+    pub fn avg(&self) -> f32 {
+        match self.latencies.len() {
+            0 => 0.0,
+            len => self.latencies.iter().sum::<f32>() / len as f32,
+        }
+    }
+
+    pub fn max(&self) -> f32 {
+        self.latencies.last().copied().unwrap_or_default()
+    }
+}
+
+enum Event {
+    Exited(Pid),
+    Reaped(Pid),
+}
+
+/// For each of `pids` with child processes that exited, how long the children
+/// waited to be reaped and the most left as zombies at once, with the slowest
+/// parents first. A child's exit is taken from its '+++ exited' or '+++ killed'
+/// line, or its 'exit_group' call when the trace has no such line.
+pub fn reaping(
+    buffer: &[u8],
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+) -> Vec<ParentReaping> {
+    let mut parents: HashMap<Pid, Pid> = HashMap::default();
+    for pid in pids {
+        if let Some(pid_data) = raw_data.get(pid) {
+            let threads: HashSet<_> = pid_data.threads.iter().collect();
+            for child in pid_data.child_pids.iter().filter(|c| !threads.contains(c)) {
+                parents.insert(*child, *pid);
+            }
+        }
+    }
+
+    let mut events: Vec<_> = buffer
+        .par_split(|c| *c == b'\n')
+        .filter_map(|line| {
+            let (time, event) = match parser::parse_line(line)? {
+                LineData::Exit(exit) => (exit.time, Event::Exited(exit.pid)),
+                LineData::Syscall(raw_data) => match raw_data.syscall {
+                    b"exit_group" if raw_data.call_status != CallStatus::Resumed => {
+                        (raw_data.time, Event::Exited(raw_data.pid))
+                    }
+                    b"wait4" | b"waitid" | b"waitpid"
+                        if raw_data.call_status != CallStatus::Started =>
+                    {
+                        (
+                            raw_data.time,
+                            Event::Reaped(raw_data.rtn_cd.filter(|p| *p > 0)?),
+                        )
+                    }
+                    _ => return None,
+                },
+                LineData::Signal(_) => return None,
+            };
+
+            let child = match event {
+                Event::Exited(pid) | Event::Reaped(pid) => pid,
+            };
+            if !parents.contains_key(&child) {
+                return None;
+            }
+
+            Some((parse_timestamp_us(time)?, event))
+        })
+        .collect();
+    events.par_sort_by_key(|(time, _)| *time);
+
+    let mut reaping: HashMap<Pid, ParentReaping> = HashMap::default();
+    // When each child became a zombie
+    let mut exited: HashMap<Pid, i64> = HashMap::default();
+    let mut unreaped: HashMap<Pid, usize> = HashMap::default();
+
+    for (time, event) in events {
+        match event {
+            Event::Exited(child) => {
+                // The '+++ exited' line following 'exit_group' replaces its time
+                if exited.insert(child, time).is_some() {
+                    continue;
+                }
+                let parent = parents[&child];
+                let entry = reaping.entry(parent).or_insert_with(|| ParentReaping {
+                    pid: parent,
+                    ..ParentReaping::default()
+                });
+                entry.exited += 1;
+
+                let count = unreaped.entry(parent).or_default();
+                *count += 1;
+                entry.max_unreaped = entry.max_unreaped.max(*count);
+            }
+            Event::Reaped(child) => {
+                let exit_time = match exited.remove(&child) {
+                    Some(exit_time) => exit_time,
+                    None => continue,
+                };
+                let parent = parents[&child];
+                if let Some(entry) = reaping.get_mut(&parent) {
+                    entry.latencies.push((time - exit_time) as f32 / 1000.0);
+                }
+                if let Some(count) = unreaped.get_mut(&parent) {
+                    *count = count.saturating_sub(1);
+                }
+            }
+        }
+    }
+
+    for child in exited.keys() {
+        if let Some(entry) = reaping.get_mut(&parents[child]) {
+            entry.unreaped.push(*child);
+        }
+    }
+
+    let mut reaping: Vec<_> = reaping.into_values().collect();
+    for parent in reaping.iter_mut() {
+        parent.latencies.sort_by(|x, y| x.total_cmp(y));
+        parent.unreaped.sort_unstable();
+    }
+    reaping.sort_by(|x, y| {
+        y.max()
+            .total_cmp(&x.max())
+            .then_with(|| y.max_unreaped.cmp(&x.max_unreaped))
+            .then_with(|| x.pid.cmp(&y.pid))
+    });
+
+    reaping
+}
+
+pub fn print_reaping(
+    buffer: &[u8],
+    pids: &[Pid],
+    raw_data: &HashMap<Pid, PidData>,
+    count: usize,
+    width: Option<usize>,
+) -> Result<(), Error> {
+    let reaping = reaping(buffer, pids, raw_data);
+
+    output::print_heading("\nTime Children Spent as Zombies Before Being Reaped\n-----------\n")?;
+
+    if reaping.is_empty() {
+        output::print_note("  No zombie processes found, no child processes exited\n")?;
+        return Ok(());
+    }
+
+    let mut table = Table::new(vec![
+        Column::new("pid", 7, Align::Left, 0),
+        Column::new("exited", 7, Align::Right, 1),
+        Column::new("reaped", 7, Align::Right, 1),
+        Column::new("min (ms)", 10, Align::Right, 3),
+        Column::new("median (ms)", 11, Align::Right, 2),
+        Column::new("avg (ms)", 10, Align::Right, 3),
+        Column::new("max (ms)", 10, Align::Right, 0),
+        Column::new("max zombies", 11, Align::Right, 0),
+        Column::new("unreaped at end", 15, Align::Left, 4),
+    ]);
+
+    for parent in reaping.iter().take(count) {
+        let unreaped: Vec<_> = parent
+            .unreaped
+            .iter()
+            .map(|pid| output::pid_label(*pid))
+            .collect();
+
+        table.add_row(vec![
+            output::pid_label(parent.pid),
+            parent.exited.to_string(),
+            parent.latencies.len().to_string(),
+            format!("{:.3}", parent.min()),
+            format!("{:.3}", parent.median()),
+            format!("{:.3}", parent.avg()),
+            format!("{:.3}", parent.max()),
+            parent.max_unreaped.to_string(),
+            unreaped.join(", "),
+        ]);
+    }
+    table.print(width)?;
+
+    if reaping.iter().any(|parent| !parent.unreaped.is_empty()) {
+        output::print_note(
+            "\n  Children of a parent ignoring SIGCHLD are reaped by the kernel, so are never seen being reaped\n",
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syscall_data::build_syscall_data;
+
+    #[test]
+    fn zombies_measure_reap_latency() {
+        let input = br##"477   10:00:00.000000 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f3ac1c75a10) = 600 <0.000100>
+477   10:00:00.000200 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f3ac1c75a10) = 601 <0.000100>
+477   10:00:00.000300 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f3ac1c75a10) = 602 <0.000100>
+477   10:00:00.000400 clone(child_stack=0x7f3ac1c74fb0, flags=CLONE_VM|CLONE_FS|CLONE_FILES|CLONE_SIGHAND|CLONE_THREAD|CLONE_SYSVSEM, parent_tid=[603], tls=0x7f3ac1c75700, child_tidptr=0x7f3ac1c759d0) = 603 <0.000100>
+600   10:00:01.000000 exit_group(0)           = ?
+600   10:00:01.000100 +++ exited with 0 +++
+601   10:00:01.500000 +++ killed by SIGKILL +++
+603   10:00:01.600000 +++ exited with 0 +++
+477   10:00:02.000000 wait4(-1,  <unfinished ...>
+477   10:00:02.000100 <... wait4 resumed>[{WIFEXITED(s) && WEXITSTATUS(s) == 0}], 0, NULL) = 600 <0.000100>
+477   10:00:02.700000 wait4(-1, [{WIFSIGNALED(s) && WTERMSIG(s) == SIGKILL}], 0, NULL) = 601 <0.000100>
+602   10:00:03.000000 exit_group(1)           = ?
+477   10:00:03.000100 wait4(-1, 0x7ffe09dbae50, WNOHANG, NULL) = 0 <0.000010>"##;
+        let pid_data_map = build_syscall_data(input);
+        let reaping = reaping(input, &[477, 600, 601, 602, 603], &pid_data_map);
+
+        assert_eq!(reaping.len(), 1);
+        assert_eq!(reaping[0].pid, 477);
+        assert_eq!(reaping[0].exited, 3);
+        assert_eq!(reaping[0].latencies.len(), 2);
+        assert!((reaping[0].min() - 1000.0).abs() < 1e-3);
+        assert!((reaping[0].max() - 1200.0).abs() < 1e-3);
+        assert_eq!(reaping[0].max_unreaped, 2);
+        assert_eq!(reaping[0].unreaped, vec![602]);
+    }
+}
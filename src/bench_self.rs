@@ -0,0 +1,134 @@
+use bstr::ByteSlice;
+use parser::output;
+use parser::session_summary::SessionSummary;
+use parser::syscall_data;
+use parser::syscall_stats;
+use std::io::{stdout, Error, Write};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug)]
+struct PhaseTimes {
+    name: &'static str,
+    durations: Vec<Duration>,
+}
+
+impl PhaseTimes {
+    fn new(name: &'static str) -> PhaseTimes {
+        PhaseTimes {
+            name,
+            durations: Vec::new(),
+        }
+    }
+
+    fn mean(&self) -> Duration {
+        if self.durations.is_empty() {
+            return Duration::default();
+        }
+
+        self.durations.iter().sum::<Duration>() / self.durations.len() as u32
+    }
+
+    fn min(&self) -> Duration {
+        self.durations.iter().min().copied().unwrap_or_default()
+    }
+}
+
+/// Times the parse and aggregation phases over `bytes`, `iterations` times each
+pub fn run(bytes: &[u8], iterations: usize) -> Result<(), Error> {
+    let line_ct = bytes.lines().count();
+
+    let mut parse = PhaseTimes::new("parse");
+    let mut stats = PhaseTimes::new("syscall stats");
+    let mut summary = PhaseTimes::new("session summary");
+
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let syscall_data = syscall_data::build_syscall_data(bytes);
+        parse.durations.push(start.elapsed());
+
+        let start = Instant::now();
+        let syscall_stats = syscall_stats::build_syscall_stats(&syscall_data);
+        stats.durations.push(start.elapsed());
+
+        let start = Instant::now();
+        let _session_summary = SessionSummary::from_syscall_stats(&syscall_stats, &syscall_data);
+        summary.durations.push(start.elapsed());
+    }
+
+    // One per core unless set with '--threads' or '--single-thread'
+    let threads = rayon::current_num_threads();
+
+    if output::headers() {
+        writeln!(stdout(), "\nSelf Benchmark\n-----------\n")?;
+        writeln!(
+            stdout(),
+            "  input: {:.1} MB, {} lines, {} iterations, {} threads\n",
+            bytes.len() as f64 / 1_000_000.0,
+            line_ct,
+            iterations,
+            threads
+        )?;
+        writeln!(
+            stdout(),
+            "  {: <15}    {: >10}    {: >10}    {: >10}    {: >12}",
+            "phase",
+            "mean (ms)",
+            "min (ms)",
+            "MB/s",
+            "lines/s"
+        )?;
+        writeln!(
+            stdout(),
+            "  ---------------    ----------    ----------    ----------    ------------"
+        )?;
+    }
+
+    for phase in &[parse, stats, summary] {
+        let mean_secs = phase.mean().as_secs_f64();
+        let (mb_per_sec, lines_per_sec) = if mean_secs > 0.0 {
+            (
+                bytes.len() as f64 / 1_000_000.0 / mean_secs,
+                line_ct as f64 / mean_secs,
+            )
+        } else {
+            (0.0, 0.0)
+        };
+
+        writeln!(
+            stdout(),
+            "  {: <15}    {: >10.3}    {: >10.3}    {: >10.1}    {: >12.0}",
+            phase.name,
+            mean_secs * 1000.0,
+            phase.min().as_secs_f64() * 1000.0,
+            mb_per_sec,
+            lines_per_sec
+        )?;
+    }
+    writeln!(stdout())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_times_mean_and_min() {
+        let mut phase = PhaseTimes::new("parse");
+        phase.durations.push(Duration::from_millis(30));
+        phase.durations.push(Duration::from_millis(10));
+        phase.durations.push(Duration::from_millis(20));
+
+        assert_eq!(phase.mean(), Duration::from_millis(20));
+        assert_eq!(phase.min(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn phase_times_empty() {
+        let phase = PhaseTimes::new("parse");
+
+        assert_eq!(phase.mean(), Duration::default());
+        assert_eq!(phase.min(), Duration::default());
+    }
+}
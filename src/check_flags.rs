@@ -1,8 +1,9 @@
-use chrono::NaiveTime;
+use parser::output;
 use parser::time;
 use parser::Pid;
 use std::io::{prelude::*, stdout, Error};
 
+// A missing '-T' isn't checked here, durations are estimated for those traces
 pub fn correct_strace_flags(line: &str) -> Result<bool, Error> {
     let mut tokens = line.split_whitespace();
 
@@ -19,21 +20,18 @@ pub fn correct_strace_flags(line: &str) -> Result<bool, Error> {
         })
         .unwrap_or_default();
 
-    let iso_time_ok =
-        NaiveTime::parse_from_str(time_str, "%H:%M:%S%.6f").is_ok() && time_str.contains('.');
-    let unix_time_ok = time_str.chars().next().filter(|c| *c != '0').is_some()
-        && time::parse_unix_timestamp(time_str.as_bytes()).is_some();
-    let time = iso_time_ok || unix_time_ok;
+    // '-t' and '-tt' print the time of day, '-ttt' the time since the epoch,
+    // and '-r' the time since the previous line, which starts with a zero
+    let time = time::Timestamp::parse(time_str.as_bytes())
+        .is_some_and(|t| t.is_wall_clock() || !time_str.starts_with('0'));
 
-    let duration = tokens.next_back().filter(|s| s.ends_with('>')).is_some();
-
-    if pid && time && duration {
+    if pid && time {
         Ok(true)
     } else {
         write!(
             stdout(),
-            "  Error: strace command must include '-f', '-T' and '-tt' OR '-ttt'\
-             \n    '-yyy' is also recommended to obtain all file names in 'io'\
+            "  Error: strace command must include '-f' and '-tt' OR '-ttt'\
+             \n    '-T' is also recommended for exact call durations, and '-yyy' to obtain all file names in 'io'\
              \n\n  The following required flag(s) were missing when strace was run: "
         )?;
 
@@ -45,16 +43,21 @@ pub fn correct_strace_flags(line: &str) -> Result<bool, Error> {
             write!(stdout(), "[-tt OR -ttt] ")?;
         }
 
-        if !duration {
-            write!(stdout(), "-T ")?;
-        }
-
         writeln!(stdout())?;
 
         Ok(false)
     }
 }
 
+/// Notes that a trace was run with '-t', so times between calls are only
+/// known to the second
+pub fn print_seconds_note() -> Result<(), Error> {
+    output::print_note(
+        "  Note: this trace was run with '-t', so timestamps are to the second\n  \
+         Elapsed times, intervals, and durations estimated without '-T' are approximate, run strace with '-tt' to measure them",
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -63,66 +66,72 @@ mod tests {
     fn missing_pid_caught() {
         let input =
             r###"00:09:48.145114 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), false);
+        assert!(!correct_strace_flags(input).unwrap());
     }
 
     #[test]
     fn present_pid_found() {
         let input = r###"123 00:09:48.145114 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), true);
+        assert!(correct_strace_flags(input).unwrap());
     }
     #[test]
     fn missing_time_wo_pid_caught() {
         let input = r###"futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), false);
+        assert!(!correct_strace_flags(input).unwrap());
     }
 
     #[test]
     fn missing_time_w_pid_caught() {
         let input = r###"123 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), false);
+        assert!(!correct_strace_flags(input).unwrap());
     }
 
     #[test]
     fn present_time_found() {
         let input = r###"123 00:09:48.145114 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), true);
+        assert!(correct_strace_flags(input).unwrap());
     }
 
     #[test]
-    fn short_time_rejected() {
+    fn seconds_time_found() {
         let input =
             r###"123 00:09:48 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), false);
+        assert!(correct_strace_flags(input).unwrap());
+    }
+
+    #[test]
+    fn truncated_time_rejected() {
+        let input = r###"123 00:09 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>"###;
+        assert!(!correct_strace_flags(input).unwrap());
     }
 
     #[test]
     fn unix_time_found() {
         let input = r###"123 1546409294.931558 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), true);
+        assert!(correct_strace_flags(input).unwrap());
     }
 
     #[test]
     fn relative_time_rejected() {
         let input =
             r###"123 0.000615 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), false);
+        assert!(!correct_strace_flags(input).unwrap());
     }
 
     #[test]
-    fn missing_execution_caught() {
+    fn missing_execution_allowed() {
         let input = r###"123 00:09:48.145114 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), false);
+        assert!(correct_strace_flags(input).unwrap());
     }
     #[test]
     fn present_execution_found() {
         let input = r###"123 00:09:48.145114 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <1.000000>"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), true);
+        assert!(correct_strace_flags(input).unwrap());
     }
 
     #[test]
     fn present_execution_unfinished_found() {
         let input = r###"123 00:09:48.145114 futex(0x7f5efea4bd28, FUTEX_WAKE_PRIVATE, 1) = 0 <unfinished ...>"###;
-        assert_eq!(correct_strace_flags(&input).unwrap(), true);
+        assert!(correct_strace_flags(input).unwrap());
     }
 }
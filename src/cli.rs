@@ -1,4 +1,6 @@
-use clap::{App, AppSettings, Arg, SubCommand};
+use clap::{App, AppSettings, Arg, ArgGroup, SubCommand};
+use parser::histogram::QuantizeValue;
+use parser::time::AbsoluteTime;
 use parser::Pid;
 
 pub fn cli_args() -> App<'static, 'static> {
@@ -6,6 +8,11 @@ pub fn cli_args() -> App<'static, 'static> {
         .version(clap::crate_version!())
         .author(clap::crate_authors!())
         .about("Summarizes raw strace output")
+        .after_help("Run 'strace-parser run -- <COMMAND>' to trace a command with the flags the parser needs and report on it, \
+            or 'strace-parser report <EXTRACT>' to report on a trace written by 'extract'. \
+            Run 'strace-parser --schema <REPORT>' to print the JSON Schema of a report's '--format json' output\n\n\
+            STRACE_PARSER_SORT, STRACE_PARSER_COUNT, and STRACE_PARSER_FORMAT set defaults for '--sort', '--count', and '--format', \
+            as 'VALUE' for any subcommand or 'REPORT=VALUE' for one, e.g. 'STRACE_PARSER_SORT=files=time,total_time'")
         .setting(AppSettings::SubcommandRequired)
         .setting(AppSettings::InferSubcommands)
         .setting(AppSettings::VersionlessSubcommands)
@@ -17,269 +24,1804 @@ pub fn cli_args() -> App<'static, 'static> {
                 .takes_value(true)
                 .number_of_values(1),
         )
-        .subcommand(SubCommand::with_name("exec")
-            .about("List programs executed")
+        .arg(
+            Arg::with_name("absolute_time")
+                .long("absolute-time")
+                .help("Print timestamps as RFC 3339 datetimes, e.g. '2020-09-13T10:20:00.500000Z', traces run with '-tt' also need '--date'"),
+        )
+        .arg(
+            Arg::with_name("by_fd_kind")
+                .long("by-fd-kind")
+                .help("Split the syscall rows of 'list-pids' and 'pid' by the kind of fd acted on, e.g. 'read(socket)', needs '-y'"),
+        )
+        .arg(
+            Arg::with_name("column_priority")
+                .long("column-priority")
+                .help("Order in which a table column is dropped to fit the width, e.g. 'avg (ms)=0' to always keep it or 'max (ms)=99' to drop it first, replacing the report's own priority")
+                .takes_value(true)
+                .value_name("COLUMN=PRIORITY")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(validate_column_priority),
+        )
+        .arg(
+            Arg::with_name("date")
+                .long("date")
+                .help("Day a '-tt' trace started on, optionally with the UTC offset of its clock, e.g. '2020-09-13+02:00'")
+                .takes_value(true)
+                .value_name("DATE")
+                .number_of_values(1)
+                .requires("absolute_time")
+                .validator(validate_date),
+        )
+        .arg(
+            Arg::with_name("fix_clock")
+                .long("fix-clock")
+                .help("Shift timestamps after the clock goes back, e.g. from an NTP step, so they keep increasing"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .help("Print reports as tables or as JSON, one object per subcommand. JSON is written by summary, list-pids, pid, io, files, directories, exec, error-rate, throughput, and tree [default: text]")
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(&["json", "text"]),
+        )
+        .arg(
+            Arg::with_name("groups")
+                .long("groups")
+                .help("File defining syscall groups, one 'name = [syscall, ...]' per line")
+                .takes_value(true)
+                .value_name("FILE")
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("groups_only")
+                .long("groups-only")
+                .help("Show group rows instead of their member syscalls")
+                .requires("groups"),
+        )
+        .arg(
+            Arg::with_name("ignore_errors")
+                .long("ignore-errors")
+                .help("File of 'SYSCALL PATH ERRNO' errors to leave out of every report, one per line, '*' matches anything")
+                .takes_value(true)
+                .value_name("FILE")
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("ignore_faster_than")
+                .long("ignore-faster-than")
+                .help("Count calls faster than <DURATION>, e.g. '5us', without keeping their durations, to save memory. They're left out of percentiles, histograms, and futex waits")
+                .takes_value(true)
+                .value_name("DURATION")
+                .number_of_values(1)
+                .validator(validate_duration),
+        )
+        .arg(
+            Arg::with_name("mark")
+                .long("mark")
+                .help("Compare the trace before, during, and after <NAME=START-END> in 'summary' and 'io', e.g. 'deploy=00:09:48-00:09:52'")
+                .takes_value(true)
+                .value_name("NAME=START-END")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(validate_mark),
+        )
+        .arg(
+            Arg::with_name("max_memory")
+                .long("max-memory")
+                .help("Keep the per-PID data held while reading the trace near <SIZE>, e.g. '4G', spilling the rest to a temporary file. Slower than parsing it at once")
+                .takes_value(true)
+                .value_name("SIZE")
+                .number_of_values(1)
+                .conflicts_with_all(&["prefilter", "stream"])
+                .validator(validate_size),
+        )
+        .arg(
+            Arg::with_name("metrics")
+                .long("metrics")
+                .help("File of 'NAME = EXPRESSION' metrics computed per PID and added as columns of 'summary' and 'list-pids --columns'")
+                .takes_value(true)
+                .value_name("FILE")
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("no_header")
+                .long("no-header")
+                .help("Omit titles, column headers, and preambles so every line is a row of data"),
+        )
+        .arg(
+            Arg::with_name("outlier_mads")
+                .long("outlier-mads")
+                .help("Flag calls slower than their syscall's median by more than <K> median absolute deviations [default: 10]")
+                .takes_value(true)
+                .value_name("K")
+                .number_of_values(1)
+                .validator(validate_outlier_mads),
+        )
+        .arg(
+            Arg::with_name("path_aliases")
+                .long("path-aliases")
+                .help("File of 'ALIAS -> TARGET' paths, one per line, so files opened by an alias are shown under its target")
+                .takes_value(true)
+                .value_name("FILE")
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("prefilter")
+                .long("prefilter")
+                .help("Parse only the lines of the PIDs given to the subcommand, skipping the rest of the trace. Their parents, siblings, and the trace's totals are left out"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .help("Suppress warnings, hints, and banners"),
+        )
+        .arg(
+            Arg::with_name("resolve_links")
+                .long("resolve-links")
+                .help("Show files opened through a symlink under its target, from the links seen in the trace"),
+        )
+        .arg(
+            Arg::with_name("label")
+                .long("label")
+                .help("Name to print next to a PID, e.g. '823=unicorn-worker-1'")
+                .takes_value(true)
+                .value_name("PID=NAME")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(validate_label),
+        )
+        .arg(
+            Arg::with_name("label_file")
+                .long("label-file")
+                .help("File of PID=NAME labels, one per line, '#' starts a comment")
+                .takes_value(true)
+                .value_name("FILE")
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("single_thread")
+                .long("single-thread")
+                .help("Parse and report on one thread, for reproducible timings or to leave a shared host's other cores alone")
+                .conflicts_with("pool_threads"),
+        )
+        .arg(
+            Arg::with_name("no_pid")
+                .long("no-pid")
+                .help("Read a trace of one process captured without '-f', whose lines have no PID. Detected from the first line unless given, the process is PID 0"),
+        )
+        .arg(
+            Arg::with_name("stream")
+                .long("stream")
+                .help("Read the trace 64 MiB at a time, releasing each part once parsed, for traces larger than memory. Slower than parsing it at once")
+                .conflicts_with("prefilter"),
+        )
+        .arg(
+            Arg::with_name("pool_threads")
+                .long("threads")
+                .help("Parse and report on at most <N> threads, defaults to one per core")
+                .takes_value(true)
+                .value_name("N")
+                .number_of_values(1)
+                .validator(validate_threads),
+        )
+        .arg(
+            Arg::with_name("title")
+                .long("title")
+                .help("Title to print at the top of the report")
+                .takes_value(true)
+                .value_name("TITLE")
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("width")
+                .long("width")
+                .help("Fit tables to <WIDTH> columns, defaults to the terminal width")
+                .takes_value(true)
+                .value_name("WIDTH")
+                .number_of_values(1)
+                .validator(validate_width),
+        )
+        .subcommand(SubCommand::with_name("baseline")
+            .about("Save a profile of <INPUT>, or check <INPUT> against a saved one")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("save")
+                .about("Write per-syscall counts, times, and errors, and the files opened to <PROFILE>")
+                .arg(
+                    Arg::with_name("PROFILE")
+                        .help("JSON file to write the profile to")
+                        .required(true)
+                        .takes_value(true),
+                ))
+            .subcommand(SubCommand::with_name("check")
+                .about("Report metrics that changed from <PROFILE> by more than the tolerance")
+                .arg(
+                    Arg::with_name("PROFILE")
+                        .help("JSON file written by 'baseline save'")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("tolerance")
+                        .long("tolerance")
+                        .help("Change from the baseline to allow before reporting it [default: 20%]")
+                        .takes_value(true)
+                        .value_name("PCT")
+                        .validator(validate_tolerance),
+                )))
+        .subcommand(SubCommand::with_name("bench-self")
+            .about("Time the parse and aggregation phases over <INPUT> and print their throughput")
+            .arg(
+                Arg::with_name("iterations")
+                    .short("n")
+                    .long("iterations")
+                    .help("The number of times to run each phase")
+                    .takes_value(true)
+                    .value_name("N")
+                    .validator(validate_count),
+            ))
+        .subcommand(SubCommand::with_name("budget")
+            .about("A short summary per PID of wall, syscall, and wait time, with its top syscalls, files, and errno")
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of PIDs to print, busiest first [default: 25]")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .validator(validate_count),
+            )
             .arg(
                 Arg::with_name("pid")
                     .short("p")
                     .long("pid")
-                    .help("PID(s) to analyze")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
                     .takes_value(true)
                     .value_name("PIDS")
                     .multiple(true)
                     .validator(validate_pid),
             )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
             .arg(
                 Arg::with_name("related")
                     .short("r")
                     .long("related")
                     .help("Include parent and child PIDs of <PIDS> in results")
-                    .requires("pid"),
-            ).arg(
+                    .requires("pids"),
+            )
+            .arg(
                 Arg::with_name("threads")
                     .short("t")
                     .long("threads")
                     .help("Include sibling threads of <PIDS> in results")
-                    .requires("pid"),
+                    .requires("pids"),
             ))
-        .subcommand(SubCommand::with_name("files")
-            .about("List files opened")
+        .subcommand(SubCommand::with_name("concurrency")
+            .about("Number of PIDs making syscalls and blocked in slow syscalls over each interval of the trace")
+            .arg(
+                Arg::with_name("interval")
+                    .short("i")
+                    .long("interval")
+                    .help("Length of each interval in milliseconds [default: 1000]")
+                    .takes_value(true)
+                    .value_name("MS")
+                    .validator(validate_interval),
+            )
             .arg(
                 Arg::with_name("pid")
                     .short("p")
                     .long("pid")
-                    .help("PID(s) to analyze")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
                     .takes_value(true)
                     .value_name("PIDS")
                     .multiple(true)
                     .validator(validate_pid),
             )
-            .arg( Arg::with_name("related")
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
                     .short("r")
                     .long("related")
                     .help("Include parent and child PIDs of <PIDS> in results")
-                    .requires("pid"),
-            )
-            .arg(
-                Arg::with_name("sort_by")
-                    .short("s")
-                    .long("sort")
-                    .help("Field to sort results by")
-                    .takes_value(true)
-                    .value_name("SORT_BY")
-                    .possible_values(&[
-                        "duration",
-                        "pid",
-                        "time",
-                    ]),
+                    .requires("pids"),
             ).arg(
                 Arg::with_name("threads")
                     .short("t")
                     .long("threads")
                     .help("Include sibling threads of <PIDS> in results")
-                    .requires("pid"),
-        ))
-        .subcommand(SubCommand::with_name("directories")
-            .about("List total duration of 'open' and 'openat' calls performed in a directory and its children")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("connections")
+            .about("Distribution of per-connection service times from 'accept'/'connect' to the final write or 'shutdown', by listening socket")
             .arg(
                 Arg::with_name("pid")
                     .short("p")
                     .long("pid")
-                    .help("PID(s) to analyze")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
                     .takes_value(true)
                     .value_name("PIDS")
                     .multiple(true)
                     .validator(validate_pid),
             )
-            .arg( Arg::with_name("related")
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
                     .short("r")
                     .long("related")
                     .help("Include parent and child PIDs of <PIDS> in results")
-                    .requires("pid"),
-            )
-            .arg(
-                Arg::with_name("sort_by")
-                    .short("s")
-                    .long("sort")
-                    .help("Field to sort results by")
-                    .takes_value(true)
-                    .value_name("SORT_BY")
-                    .possible_values(&[
-                        "count",
-                        "duration",
-                        "pid",
-                        "time",
-                    ]),
+                    .requires("pids"),
             ).arg(
                 Arg::with_name("threads")
                     .short("t")
                     .long("threads")
                     .help("Include sibling threads of <PIDS> in results")
-                    .requires("pid"),
-        ))
-        .subcommand(SubCommand::with_name("io")
-            .about("Show details of I/O syscalls: read, recv, recvfrom, recvmsg, send, sendmsg, sendto, and write")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("console")
+            .about("What each PID wrote to stdout, stderr, terminals, log files, and /dev/null, recovered from the trace")
+            .arg(
+                Arg::with_name("raw")
+                    .long("raw")
+                    .help("Print only the bytes written to stdout and stderr, in the order written"),
+            )
             .arg(
                 Arg::with_name("pid")
                     .short("p")
                     .long("pid")
-                    .help("PID(s) to analyze")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
                     .takes_value(true)
                     .value_name("PIDS")
                     .multiple(true)
                     .validator(validate_pid),
-            ).arg( Arg::with_name("related")
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
                     .short("r")
                     .long("related")
                     .help("Include parent and child PIDs of <PIDS> in results")
-                  .requires("pid"),
-            ).arg(
-                Arg::with_name("sort_by")
-                    .short("s")
-                    .long("sort")
-                    .help("Field to sort results by")
-                    .takes_value(true)
-                    .value_name("SORT_BY")
-                    .possible_values(&[
-                        "duration",
-                        "pid",
-                        "time",
-                    ]),
+                    .requires("pids"),
             ).arg(
                 Arg::with_name("threads")
                     .short("t")
                     .long("threads")
                     .help("Include sibling threads of <PIDS> in results")
-                    .requires("pid"),
+                    .requires("pids"),
             ))
-        .subcommand(SubCommand::with_name("list-pids")
-            .about("List of PIDs and their syscall stats")
+        .subcommand(SubCommand::with_name("drift")
+            .about("Compare each PID's syscall mix in the first and last part of the trace to find when behavior changed")
             .arg(
                 Arg::with_name("count")
                     .short("c")
                     .long("count")
-                    .help("The number of PIDs to print")
+                    .help("The number of PIDs to print, most changed first [default: 25]")
                     .takes_value(true)
                     .value_name("COUNT")
-                    .default_value_if("detail", None, "5")
                     .validator(validate_count),
             )
             .arg(
-                Arg::with_name("sort_by")
-                    .short("s")
-                    .long("sort")
-                    .help("Field to sort results by")
+                Arg::with_name("percent")
+                    .long("percent")
+                    .help("Length of the first and last windows as a percentage of the trace's time [default: 20%]")
                     .takes_value(true)
-                    .value_name("SORT_BY")
-                    .possible_values(&[
-                        "active_time",
-                        "children",
-                        "pid",
-                        "syscalls",
-                        "total_time",
-                        "user_time",
-                    ]),
-            ))
-        .subcommand(SubCommand::with_name("pid")
-            .about("Details of PID(s) including syscalls stats, exec'd process, and slowest 'open' calls")
+                    .value_name("PCT")
+                    .validator(validate_window_pct),
+            )
             .arg(
                 Arg::with_name("pid")
-                    .help("PID(s) to analyze")
-                    .required(true)
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
                     .takes_value(true)
                     .value_name("PIDS")
                     .multiple(true)
                     .validator(validate_pid),
             )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
             .arg(
                 Arg::with_name("related")
                     .short("r")
                     .long("related")
                     .help("Include parent and child PIDs of <PIDS> in results")
-                    .requires("pid"),
-            ).arg(
+                    .requires("pids"),
+            )
+            .arg(
                 Arg::with_name("threads")
                     .short("t")
                     .long("threads")
                     .help("Include sibling threads of <PIDS> in results")
-                    .requires("pid"),
+                    .requires("pids"),
             ))
-        .subcommand(SubCommand::with_name("quantize")
-            .about("Prints a log\u{2082} scale histogram of the quantized execution times in \u{03BC}secs for <SYSCALL>")
+        .subcommand(SubCommand::with_name("error-rate")
+            .about("Share of syscalls that failed over each interval of the trace, for all PIDs and for <PIDS>")
             .arg(
-                Arg::with_name("syscall")
-                    .help("Syscall to analyze")
-                    .required(true)
-                    .value_name("SYSCALL")
+                Arg::with_name("format")
+                    .long("format")
+                    .help("Print the error rate of each interval as a table, JSON, or CSV [default: text]")
                     .takes_value(true)
-                    .number_of_values(1),
+                    .value_name("FORMAT")
+                    .possible_values(&["csv", "json", "text"]),
+            )
+            .arg(
+                Arg::with_name("interval")
+                    .short("i")
+                    .long("interval")
+                    .help("Length of each interval in milliseconds [default: 1000]")
+                    .takes_value(true)
+                    .value_name("MS")
+                    .validator(validate_interval),
             )
             .arg(
                 Arg::with_name("pid")
                     .short("p")
                     .long("pid")
-                    .help("PID(s) to analyze")
+                    .help("PID(s) to give their own error rate, '-' reads them from stdin")
                     .takes_value(true)
                     .value_name("PIDS")
                     .multiple(true)
                     .validator(validate_pid),
             )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to give their own error rate, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
             .arg(
                 Arg::with_name("related")
                     .short("r")
                     .long("related")
                     .help("Include parent and child PIDs of <PIDS> in results")
-                    .requires("pid"),
-            ).arg(
+                    .requires("pids"),
+            )
+            .arg(
                 Arg::with_name("threads")
                     .short("t")
                     .long("threads")
                     .help("Include sibling threads of <PIDS> in results")
-                    .requires("pid"),
+                    .requires("pids"),
             ))
-        .subcommand(SubCommand::with_name("summary")
-            .about("Overview of PIDs in session")
+        .subcommand(SubCommand::with_name("errors")
+            .about("Most frequent errors by syscall, path, and errno")
             .arg(
                 Arg::with_name("count")
                     .short("c")
                     .long("count")
-                    .help("The number of PIDs to print")
+                    .help("The number of errors to print [default: 25]")
                     .takes_value(true)
                     .value_name("COUNT")
-                    .default_value_if("detail", None, "5")
                     .validator(validate_count),
             )
             .arg(
-                Arg::with_name("sort_by")
-                    .short("s")
-                    .long("sort")
-                    .help("Field to sort results by")
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
                     .takes_value(true)
-                    .value_name("SORT_BY")
-                    .possible_values(&[
-                        "active_time",
-                        "children",
-                        "pid",
-                        "syscalls",
-                        "total_time",
-                        "user_time",
-                    ]),
-            ))
-        .subcommand(SubCommand::with_name("tree")
-            .about("pstree-style view of traced processes")
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
             .arg(
-                Arg::with_name("truncate")
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            )
+            .arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("events")
+            .about("Write every call as a JSON object per line, for loading into other tools")
+            .arg(
+                Arg::with_name("dedup_window")
+                    .long("dedup-window")
+                    .help("Collapse calls matching one of the PID's last <N> records by syscall, path, and errno into that record")
+                    .takes_value(true)
+                    .value_name("N")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            )
+            .arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("exec")
+            .about("List programs executed")
+            .arg(
+                Arg::with_name("anomalies")
+                    .long("anomalies")
+                    .help("Only list runs whose arguments or environment size differ from other runs of the same program"),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("extract")
+            .about("Write a compact copy of <INPUT> to report on elsewhere, with the data of reads and writes cut short")
+            .arg(
+                Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .help("File to write the extract to [default: stdout]")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .arg(
+                Arg::with_name("string_limit")
+                    .short("s")
+                    .long("string-limit")
+                    .help("Bytes of data to keep from each read, write, send, and receive [default: 32]")
+                    .takes_value(true)
+                    .value_name("BYTES")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to keep, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to keep, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"])))
+        .subcommand(SubCommand::with_name("fd")
+            .about("Each fd's lifetime, what it referred to, and fds never closed")
+            .arg(
+                Arg::with_name("leaks")
+                    .long("leaks")
+                    .help("List only the fds never closed"),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("fd-peak")
+            .about("Most fds each process held open at once, and when")
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of processes to print [default: 25]")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("futex")
+            .about("Time spent waiting on each private futex")
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of futexes to print [default: 25]")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("lock_names")
+                    .long("lock-names")
+                    .help("File mapping futex addresses to lock names, 'ADDR NAME' or 'nm' output, one per line")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("files")
+            .about("List files opened and other fds created")
+            .arg(
+                Arg::with_name("coalesce_pids")
+                    .long("coalesce-pids")
+                    .help("Show each path once with the number of PIDs that opened it and their mean and max time"),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg( Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            )
+            .arg(
+                Arg::with_name("sort_by")
+                    .short("s")
+                    .long("sort")
+                    .help("Field to sort results by")
+                    .takes_value(true)
+                    .value_name("SORT_BY")
+                    .possible_values(&[
+                        "duration",
+                        "pid",
+                        "time",
+                    ]),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+        ))
+        .subcommand(SubCommand::with_name("directories")
+            .about("List total duration of calls that read or write a directory and its children")
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg( Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            )
+            .arg(
+                Arg::with_name("sort_by")
+                    .short("s")
+                    .long("sort")
+                    .help("Field to sort results by")
+                    .takes_value(true)
+                    .value_name("SORT_BY")
+                    .possible_values(&[
+                        "count",
+                        "duration",
+                        "pid",
+                        "time",
+                    ]),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+        ))
+        .subcommand(SubCommand::with_name("io")
+            .about("Show details of I/O syscalls: read, recv, recvfrom, recvmsg, send, sendmsg, sendto, and write")
+            .arg(
+                Arg::with_name("amplification")
+                    .long("amplification")
+                    .help("Follow the calls with how many times over each file was read, from its size in 'stat' and 'fstat' calls")
+                    .conflicts_with("cadence"),
+            )
+            .arg(
+                Arg::with_name("by_path")
+                    .long("by-path")
+                    .help("Follow the calls with each file's time split by the syscalls made on it, including 'fstat' and 'openat'")
+                    .conflicts_with("cadence"),
+            )
+            .arg(
+                Arg::with_name("cadence")
+                    .long("cadence")
+                    .help("Print the min, median, average, and max time between calls on each fd instead of each call"),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg( Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                  .requires("pids"),
+            ).arg(
+                Arg::with_name("sort_by")
+                    .short("s")
+                    .long("sort")
+                    .help("Field to sort results by")
+                    .takes_value(true)
+                    .value_name("SORT_BY")
+                    .possible_values(&[
+                        "bytes",
+                        "duration",
+                        "pid",
+                        "time",
+                    ]),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("ipc")
+            .about("Show pipes where a blocked reader or writer was waiting on an idle PID at the other end")
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("list-pids")
+            .about("List of PIDs and their syscall stats")
+            .arg(
+                Arg::with_name("columns")
+                    .long("columns")
+                    .help("Print a table of the selected columns instead of full PID details")
+                    .takes_value(true)
+                    .value_name("COLUMNS")
+                    .multiple(true)
+                    .use_delimiter(true)
+                    .possible_values(&[
+                        "children",
+                        "elapsed",
+                        "errors",
+                        "exec",
+                        "first",
+                        "last",
+                        "threads",
+                        "uid",
+                    ]),
+            )
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of PIDs to print")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .default_value_if("detail", None, "5")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("sort_by")
+                    .short("s")
+                    .long("sort")
+                    .help("Field to sort results by")
+                    .takes_value(true)
+                    .value_name("SORT_BY")
+                    .possible_values(&[
+                        "active_time",
+                        "children",
+                        "pid",
+                        "syscalls",
+                        "total_time",
+                        "user_time",
+                    ]),
+            ))
+        .subcommand(SubCommand::with_name("net")
+            .about("Bytes each PID sent to and received from each peer, with its connects, accepts, and errors")
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of peers to print [default: 25]")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("otlp")
+            .about("Export each PID and its slow syscalls as OpenTelemetry spans, as OTLP/JSON or sent to a collector")
+            .arg(
+                Arg::with_name("endpoint")
+                    .long("endpoint")
+                    .help("Send the spans to the collector at <URL>, e.g. 'http://localhost:4318' or 'https://collector.example.com', rather than printing them")
+                    .takes_value(true)
+                    .value_name("URL")
+                    .number_of_values(1)
+                    .conflicts_with("output"),
+            )
+            .arg(
+                Arg::with_name("min_duration")
+                    .long("min-duration")
+                    .help("Leave out syscalls faster than <DURATION>, e.g. '500us' [default: 10ms]")
+                    .takes_value(true)
+                    .value_name("DURATION")
+                    .number_of_values(1)
+                    .validator(validate_duration),
+            )
+            .arg(
+                Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .help("File to write the spans to [default: stdout]")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .arg(
+                Arg::with_name("parent_span_id")
+                    .long("parent-span-id")
+                    .help("Span of the distributed trace to attach the PIDs to, as 16 hex digits, needs '--trace-id'")
+                    .takes_value(true)
+                    .value_name("ID")
+                    .number_of_values(1)
+                    .requires("trace_id")
+                    .validator(validate_span_id),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to export, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to export, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            )
+            .arg(
+                Arg::with_name("service_name")
+                    .long("service-name")
+                    .help("The 'service.name' to export the spans under [default: strace]")
+                    .takes_value(true)
+                    .value_name("NAME")
+                    .number_of_values(1),
+            )
+            .arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            )
+            .arg(
+                Arg::with_name("trace_id")
+                    .long("trace-id")
+                    .help("Trace to add the spans to, as 32 hex digits, such as that of the request being traced [default: derived from <INPUT>]")
+                    .takes_value(true)
+                    .value_name("ID")
+                    .number_of_values(1)
+                    .validator(validate_trace_id),
+            ))
+        .subcommand(SubCommand::with_name("permissions")
+            .about("List mode, owner, and umask changes made with chmod, chown, and umask")
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("poll")
+            .about("Which fds registered with epoll_ctl woke each epoll_wait")
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of fds to print [default: 25]")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("pid")
+            .about("Details of PID(s) including syscalls stats, exec'd process, and slowest 'open' calls")
+            .arg(
+                Arg::with_name("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .required_unless("pid_file")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ).arg(
+                Arg::with_name("expand")
+                    .long("expand")
+                    .help("List every call rather than collapsing runs of calls that differ only in numbers in their path"),
+            ).arg(
+                Arg::with_name("each")
+                    .long("each")
+                    .help("Write each PID's details to its own file in <DIR> instead of printing them")
+                    .requires("output_dir"),
+            ).arg(
+                Arg::with_name("output_dir")
+                    .long("output-dir")
+                    .help("Directory to write reports to with --each, created if missing")
+                    .takes_value(true)
+                    .value_name("DIR")
+                    .number_of_values(1)
+                    .requires("each"),
+            ))
+        .subcommand(SubCommand::with_name("quantize")
+            .about("Prints a log\u{2082} scale histogram of the quantized execution times in \u{03BC}secs, return values, or an argument of <SYSCALL>")
+            .arg(
+                Arg::with_name("syscall")
+                    .help("Syscall to analyze")
+                    .required(true)
+                    .value_name("SYSCALL")
+                    .takes_value(true)
+                    .number_of_values(1),
+            )
+            .arg(
+                Arg::with_name("by_error")
+                    .long("by-error")
+                    .help("Print separate histograms for successful calls and each errno returned"),
+            )
+            .arg(
+                Arg::with_name("value")
+                    .long("value")
+                    .help("What to histogram: 'duration', 'retval', or 'arg:N' for the Nth argument, counting from 1")
+                    .takes_value(true)
+                    .value_name("VALUE")
+                    .number_of_values(1)
+                    .validator(validate_quantize_value),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("repl")
+            .about("Loads <INPUT> once, then runs subcommands entered one per line until 'quit'"))
+        .subcommand(SubCommand::with_name("security")
+            .about("Reads of sensitive files, execs from temporary directories, and connects to public addresses")
+            .arg(
+                Arg::with_name("lines")
+                    .short("l")
+                    .long("lines")
+                    .help("Print the trace lines behind each finding"),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("signals")
+            .about("Signals delivered to each PID and the syscalls made by their handlers before 'rt_sigreturn'")
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of PIDs to print, most time in handlers first [default: 25]")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            )
+            .arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("sleeps")
+            .about("Time each PID spent in 'nanosleep' and 'clock_nanosleep', how far past the time asked for they slept, and timers set with 'timerfd_settime'")
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of PIDs to print, longest asleep first [default: 25]")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            )
+            .arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("summary")
+            .about("Overview of PIDs in session")
+            .arg(
+                Arg::with_name("by_user")
+                    .long("by-user")
+                    .help("Combine PIDs running as the same effective uid into one row"),
+            )
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of PIDs to print")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .default_value_if("detail", None, "5")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("sort_by")
+                    .short("s")
+                    .long("sort")
+                    .help("Field to sort results by")
+                    .takes_value(true)
+                    .value_name("SORT_BY")
+                    .possible_values(&[
+                        "active_time",
+                        "children",
+                        "pid",
+                        "syscalls",
+                        "total_time",
+                        "user_time",
+                    ]),
+            ))
+        .subcommand(SubCommand::with_name("throughput")
+            .about("Bytes read and written over each interval of the trace for the busiest files and sockets")
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of files and sockets to print [default: 10]")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("format")
+                    .long("format")
+                    .help("Print the bytes moved in each interval as a table or as JSON [default: text]")
+                    .takes_value(true)
+                    .value_name("FORMAT")
+                    .possible_values(&["json", "text"])
+                    .conflicts_with("json"),
+            )
+            .arg(
+                Arg::with_name("interval")
+                    .short("i")
+                    .long("interval")
+                    .help("Length of each interval in milliseconds [default: 1000]")
+                    .takes_value(true)
+                    .value_name("MS")
+                    .validator(validate_interval),
+            )
+            .arg(
+                Arg::with_name("json")
+                    .long("json")
+                    .help("Print the bytes moved in each interval as JSON, short for '--format json'"),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("tree")
+            .about("pstree-style view of traced processes")
+            .arg(
+                Arg::with_name("truncate")
                     .short("t")
                     .long("truncate")
                     .help("Truncate commands to 50 characters")
-                    ))
+                    )
+            .arg(
+                Arg::with_name("inclusive")
+                    .short("i")
+                    .long("inclusive")
+                    .help("Print each PID's own active time and syscalls, and the totals including every thread and child below it"),
+            )
+            .arg(
+                Arg::with_name("timeline")
+                    .short("l")
+                    .long("timeline")
+                    .help("Draw a bar showing when each PID was alive between the start and end of the trace"),
+            )
+            .arg(
+                Arg::with_name("format")
+                    .long("format")
+                    .help("Draw the tree as text or write it as JSON with its parent/child edges [default: text]")
+                    .takes_value(true)
+                    .value_name("FORMAT")
+                    .possible_values(&["json", "text"])
+                    .conflicts_with_all(&["truncate", "inclusive", "timeline"]),
+            ))
+        .subcommand(SubCommand::with_name("wait-for")
+            .about("Chains of PIDs blocked on each other through 'wait4', futexes, and pipes, the longest waits first")
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of chains to print [default: 25]")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            ).arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+        .subcommand(SubCommand::with_name("zombies")
+            .about("How long exited children waited to be reaped by their parent, and the most left as zombies at once")
+            .arg(
+                Arg::with_name("count")
+                    .short("c")
+                    .long("count")
+                    .help("The number of parent PIDs to print, slowest to reap first [default: 25]")
+                    .takes_value(true)
+                    .value_name("COUNT")
+                    .validator(validate_count),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .short("p")
+                    .long("pid")
+                    .help("Parent PID(s) to analyze, '-' reads them from stdin")
+                    .takes_value(true)
+                    .value_name("PIDS")
+                    .multiple(true)
+                    .validator(validate_pid),
+            )
+            .arg(
+                Arg::with_name("pid_file")
+                    .long("pid-file")
+                    .help("File listing parent PID(s) to analyze, one per line, '#' starts a comment")
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .number_of_values(1),
+            )
+            .group(ArgGroup::with_name("pids").args(&["pid", "pid_file"]))
+            .arg(
+                Arg::with_name("related")
+                    .short("r")
+                    .long("related")
+                    .help("Include parent and child PIDs of <PIDS> in results")
+                    .requires("pids"),
+            )
+            .arg(
+                Arg::with_name("threads")
+                    .short("t")
+                    .long("threads")
+                    .help("Include sibling threads of <PIDS> in results")
+                    .requires("pids"),
+            ))
+}
+
+/// Arguments of 'strace-parser --schema <REPORT>', which needs no trace file
+/// or subcommand
+pub fn schema_args() -> App<'static, 'static> {
+    App::new("strace-parser --schema")
+        .about("Prints the JSON Schema of what <REPORT> writes with '--format json'")
+        .setting(AppSettings::ColoredHelp)
+        .arg(
+            Arg::with_name("schema")
+                .long("schema")
+                .help("Report to print the schema of, 'events' has the schema of each line")
+                .required(true)
+                .takes_value(true)
+                .value_name("REPORT")
+                .possible_values(&[
+                    "directories",
+                    "error-rate",
+                    "events",
+                    "exec",
+                    "files",
+                    "io",
+                    "list-pids",
+                    "pid",
+                    "summary",
+                    "throughput",
+                    "tree",
+                ]),
+        )
+}
+
+/// Arguments of 'strace-parser run' and 'strace-parser report', which take
+/// no trace file as <INPUT>. They're parsed only when the command line isn't
+/// a trace and its subcommands, so a trace named 'run' or 'report' can still
+/// be given as <INPUT>.
+pub fn command_args() -> App<'static, 'static> {
+    App::new("strace parser")
+        .setting(AppSettings::SubcommandRequired)
+        .setting(AppSettings::VersionlessSubcommands)
+        .setting(AppSettings::ColoredHelp)
+        .subcommand(run_args())
+        .subcommand(report_args())
+}
+
+fn report_args() -> App<'static, 'static> {
+    SubCommand::with_name("report")
+        .about("Reports on <EXTRACT>, a trace written by 'extract', with the options and subcommands given as for <INPUT>")
+        .setting(AppSettings::TrailingVarArg)
+        .setting(AppSettings::AllowLeadingHyphen)
+        .setting(AppSettings::ColoredHelp)
+        .arg(
+            Arg::with_name("EXTRACT")
+                .help("Extract to report on, then any options and subcommands [default: summary]")
+                .required(true)
+                .multiple(true)
+                .value_name("EXTRACT"),
+        )
+}
+
+fn run_args() -> App<'static, 'static> {
+    SubCommand::with_name("run")
+        .about("Runs <COMMAND> under strace with the flags the parser needs, then reports on the trace once it exits")
+        .setting(AppSettings::ColoredHelp)
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .help("Keep the trace in <FILE> rather than a temporary file removed afterwards")
+                .takes_value(true)
+                .value_name("FILE")
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("report")
+                .short("r")
+                .long("report")
+                .help("Subcommand and options to report with, e.g. 'files --count 10', several run in turn, e.g. 'summary files io' [default: summary]")
+                .takes_value(true)
+                .value_name("SUBCOMMAND")
+                .number_of_values(1)
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::with_name("strace")
+                .long("strace")
+                .help("Path of the strace binary [default: strace]")
+                .takes_value(true)
+                .value_name("PATH")
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("COMMAND")
+                .help("Command to trace, and its arguments")
+                .required(true)
+                .multiple(true)
+                .last(true),
+        )
 }
 
 fn validate_pid(p: String) -> Result<(), String> {
-    if p.parse::<Pid>().is_ok() {
+    if p == "-" || p.parse::<Pid>().is_ok() {
         return Ok(());
     }
-    Err(String::from("PID must be an integer"))
+    Err(String::from("PID must be an integer or '-'"))
+}
+
+fn validate_label(l: String) -> Result<(), String> {
+    match l.split_once('=') {
+        Some((pid, name)) if pid.trim().parse::<Pid>().is_ok() && !name.trim().is_empty() => Ok(()),
+        _ => Err(String::from("Label must be of the form PID=NAME")),
+    }
+}
+
+fn validate_date(d: String) -> Result<(), String> {
+    AbsoluteTime::parse_date(&d).map(|_| ())
+}
+
+fn validate_quantize_value(v: String) -> Result<(), String> {
+    v.parse::<QuantizeValue>().map(|_| ())
 }
 
 fn validate_count(c: String) -> Result<(), String> {
@@ -288,3 +1830,137 @@ fn validate_count(c: String) -> Result<(), String> {
     }
     Err(String::from("COUNT must be a non-negative integer"))
 }
+
+fn validate_tolerance(t: String) -> Result<(), String> {
+    match t.trim_end_matches('%').parse::<f64>() {
+        Ok(t) if t >= 0.0 => Ok(()),
+        _ => Err(String::from(
+            "PCT must be a non-negative percentage, e.g. '20%'",
+        )),
+    }
+}
+
+fn validate_window_pct(p: String) -> Result<(), String> {
+    match p.trim_end_matches('%').parse::<f64>() {
+        Ok(p) if p > 0.0 && p <= 50.0 => Ok(()),
+        _ => Err(String::from(
+            "PCT must be a percentage above 0 and at most 50, e.g. '20%'",
+        )),
+    }
+}
+
+fn validate_interval(i: String) -> Result<(), String> {
+    match i.parse::<i64>() {
+        Ok(i) if i > 0 => Ok(()),
+        _ => Err(String::from("MS must be a positive integer")),
+    }
+}
+
+fn validate_threads(n: String) -> Result<(), String> {
+    match n.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(String::from("N must be a positive integer")),
+    }
+}
+
+fn validate_width(w: String) -> Result<(), String> {
+    if w.parse::<usize>().is_ok() {
+        return Ok(());
+    }
+    Err(String::from("WIDTH must be a non-negative integer"))
+}
+
+fn validate_column_priority(c: String) -> Result<(), String> {
+    c.parse::<parser::table::ColumnPriority>().map(|_| ())
+}
+
+fn validate_size(s: String) -> Result<(), String> {
+    match parser::syscall_data::parse_size(&s) {
+        Some(s) if s > 0 => Ok(()),
+        _ => Err(String::from(
+            "SIZE must be a positive number of bytes, with an optional K, M, or G suffix",
+        )),
+    }
+}
+
+fn validate_duration(d: String) -> Result<(), String> {
+    match parser::syscall_data::parse_duration(&d) {
+        Some(_) => Ok(()),
+        None => Err(String::from(
+            "DURATION must be a non-negative number of seconds, with an optional ns, us, ms, or s suffix",
+        )),
+    }
+}
+
+fn validate_trace_id(id: String) -> Result<(), String> {
+    match id.len() == 32 && id.bytes().all(|c| c.is_ascii_hexdigit()) {
+        true => Ok(()),
+        false => Err(String::from("ID must be 32 hex digits")),
+    }
+}
+
+fn validate_span_id(id: String) -> Result<(), String> {
+    match id.len() == 16 && id.bytes().all(|c| c.is_ascii_hexdigit()) {
+        true => Ok(()),
+        false => Err(String::from("ID must be 16 hex digits")),
+    }
+}
+
+fn validate_mark(m: String) -> Result<(), String> {
+    m.parse::<parser::marks::Mark>().map(|_| ())
+}
+
+fn validate_outlier_mads(k: String) -> Result<(), String> {
+    match k.parse::<f32>() {
+        Ok(k) if k >= 0.0 => Ok(()),
+        _ => Err(String::from("K must be a non-negative number")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ErrorKind;
+
+    #[test]
+    fn stream_conflicts_with_prefilter_and_max_memory() {
+        for flags in &[&["--prefilter"][..], &["--max-memory", "1G"][..]] {
+            let mut cmd_line = vec!["strace-parser", "trace.log", "--stream"];
+            cmd_line.extend_from_slice(flags);
+            cmd_line.extend_from_slice(&["summary"]);
+            let err = cli_args().get_matches_from_safe(cmd_line).unwrap_err();
+            assert_eq!(err.kind, ErrorKind::ArgumentConflict);
+        }
+
+        assert!(cli_args()
+            .get_matches_from_safe(vec!["strace-parser", "trace.log", "--stream", "summary"])
+            .is_ok());
+    }
+
+    #[test]
+    fn run_needs_double_dash() {
+        let err = command_args()
+            .get_matches_from_safe(vec!["strace-parser", "run", "ls"])
+            .unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnknownArgument);
+
+        let matches = command_args()
+            .get_matches_from_safe(vec!["strace-parser", "run", "-r", "exec", "--", "ls", "-l"])
+            .unwrap();
+        let run = matches.subcommand_matches("run").unwrap();
+        assert_eq!(
+            run.values_of("COMMAND").unwrap().collect::<Vec<_>>(),
+            ["ls", "-l"]
+        );
+    }
+
+    #[test]
+    fn trace_named_run_is_input() {
+        for name in &["run", "report"] {
+            let matches = cli_args()
+                .get_matches_from_safe(vec!["strace-parser", name, "summary"])
+                .unwrap();
+            assert_eq!(matches.value_of("INPUT"), Some(*name));
+        }
+    }
+}
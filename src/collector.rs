@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+// Collectors listen for OTLP over HTTP on this path and port
+const TRACES_PATH: &str = "/v1/traces";
+const OTLP_PORT: u16 = 4318;
+const POST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends an OTLP/JSON export request to a collector's 'http[s]://HOST[:PORT]'
+/// endpoint, on '/v1/traces' unless the URL has a path
+pub fn post_spans(endpoint: &str, body: &[u8]) -> Result<(), String> {
+    let url = traces_url(endpoint)?;
+    let agent = ureq::AgentBuilder::new().timeout(POST_TIMEOUT).build();
+
+    match agent
+        .post(&url)
+        .set("Content-Type", "application/json")
+        .send_bytes(body)
+    {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, response)) => Err(format!(
+            "{} didn't accept the spans: {} {}",
+            url,
+            code,
+            response.status_text()
+        )),
+        // ureq's errors start with the URL
+        Err(e) => Err(format!("Failed to send spans to {}", e)),
+    }
+}
+
+// The endpoint with the scheme, port, and path of OTLP over HTTP filled in
+// where it left them out
+fn traces_url(endpoint: &str) -> Result<String, String> {
+    let (scheme, rest) = match endpoint.split_once("://") {
+        Some((scheme, rest)) if scheme == "http" || scheme == "https" => (scheme, rest),
+        Some((scheme, _)) => {
+            return Err(format!(
+                "'{}' uses {}, send the spans over http or https instead",
+                endpoint, scheme
+            ))
+        }
+        None => ("http", endpoint),
+    };
+
+    let (host, path) = match rest.find('/') {
+        Some(idx) if idx + 1 < rest.len() => (&rest[..idx], &rest[idx..]),
+        Some(idx) => (&rest[..idx], TRACES_PATH),
+        None => (rest, TRACES_PATH),
+    };
+    if host.is_empty() {
+        return Err(format!("'{}' has no host", endpoint));
+    }
+    let host = match host.rsplit_once(':') {
+        Some((_, port)) if !port.contains(']') => host.to_string(),
+        _ => format!("{}:{}", host, OTLP_PORT),
+    };
+
+    Ok(format!("{}://{}{}", scheme, host, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traces_url_default_port_and_path() {
+        assert_eq!(
+            traces_url("http://collector").unwrap(),
+            "http://collector:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("localhost:4000/").unwrap(),
+            "http://localhost:4000/v1/traces"
+        );
+        assert_eq!(
+            traces_url("https://10.0.0.1:9000/otlp/v1/traces").unwrap(),
+            "https://10.0.0.1:9000/otlp/v1/traces"
+        );
+        assert_eq!(
+            traces_url("https://[::1]").unwrap(),
+            "https://[::1]:4318/v1/traces"
+        );
+        assert!(traces_url("grpc://collector").is_err());
+        assert!(traces_url("http:///v1/traces").is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::cli;
+use clap::ErrorKind;
+use std::env;
+use std::ffi::OsString;
+
+// Environment variables giving defaults for subcommand options, with the
+// options that set the same thing and the flag the default is passed as
+const ENV_DEFAULTS: [(&str, &[&str], &str); 3] = [
+    ("STRACE_PARSER_SORT", &["sort_by"], "--sort"),
+    ("STRACE_PARSER_COUNT", &["count"], "--count"),
+    ("STRACE_PARSER_FORMAT", &["format", "json"], "--format"),
+];
+
+// Reports written as JSON by the global '--format', which STRACE_PARSER_FORMAT
+// sets for them rather than an option of their own
+const GLOBAL_FORMAT_REPORTS: [&str; 7] = [
+    "directories",
+    "exec",
+    "files",
+    "io",
+    "list-pids",
+    "pid",
+    "summary",
+];
+
+/// Fills in the options of `cmd_line`'s subcommand left unset from the
+/// STRACE_PARSER_SORT, STRACE_PARSER_COUNT, and STRACE_PARSER_FORMAT
+/// environment variables.
+///
+/// Each holds a comma separated list of 'REPORT=VALUE' entries for one
+/// subcommand and at most one bare 'VALUE' for any subcommand that accepts it,
+/// e.g. 'STRACE_PARSER_SORT=files=time,total_time'. A flag on the command line
+/// takes precedence over the subcommand's entry, which takes precedence over
+/// the bare value. A bare value the subcommand's option doesn't accept is
+/// skipped with a warning on stderr. For the reports written by the global
+/// '--format', STRACE_PARSER_FORMAT sets it before <INPUT> instead.
+pub fn with_env_defaults(cmd_line: Vec<OsString>) -> Result<Vec<OsString>, clap::Error> {
+    let (cmd_line, warnings) = apply_defaults(cmd_line, |var| env::var(var).ok())?;
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    Ok(cmd_line)
+}
+
+// The command line with the defaults filled in, and the bare values skipped
+// as invalid for the subcommand
+fn apply_defaults<F>(
+    mut cmd_line: Vec<OsString>,
+    lookup: F,
+) -> Result<(Vec<OsString>, Vec<String>), clap::Error>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut warnings = Vec::new();
+    let matches = cli::cli_args().get_matches_from_safe(&cmd_line)?;
+    let (report, args) = match matches.subcommand() {
+        (report, Some(args)) => (report, args),
+        _ => return Ok((cmd_line, warnings)),
+    };
+
+    for &(var, options, flag) in &ENV_DEFAULTS {
+        let setting = match lookup(var) {
+            Some(setting) => setting,
+            None => continue,
+        };
+        let global = flag == "--format" && GLOBAL_FORMAT_REPORTS.contains(&report);
+        if options.iter().any(|opt| args.occurrences_of(opt) > 0)
+            || (global && matches.occurrences_of("format") > 0)
+        {
+            continue;
+        }
+        let (value, named) = match env_value(&setting, report) {
+            Some(value) => value,
+            None => continue,
+        };
+
+        let mut with_default = cmd_line.clone();
+        let default = OsString::from(format!("{}={}", flag, value));
+        if global {
+            with_default.insert(1, default);
+        } else {
+            with_default.push(default);
+        }
+        match cli::cli_args().get_matches_from_safe(&with_default) {
+            Ok(_) => cmd_line = with_default,
+            // A bare value only applies to the subcommands with the option
+            Err(e) if !named && e.kind == ErrorKind::UnknownArgument => {}
+            Err(_) if !named => warnings.push(format!(
+                "ignoring '{}' from {}, it isn't a valid '{}' for '{}'",
+                value, var, flag, report
+            )),
+            Err(e) if e.kind == ErrorKind::UnknownArgument => {
+                return Err(clap::Error::with_description(
+                    &format!("'{}' has no '{}' option, set by {}", report, flag, var),
+                    ErrorKind::UnknownArgument,
+                ))
+            }
+            Err(e) => {
+                return Err(clap::Error::with_description(
+                    &format!(
+                        "'{}' from {} isn't a valid '{}' for '{}'",
+                        value, var, flag, report
+                    ),
+                    e.kind,
+                ))
+            }
+        }
+    }
+
+    Ok((cmd_line, warnings))
+}
+
+// The value `setting` gives `report`, and whether it was named
+fn env_value<'a>(setting: &'a str, report: &str) -> Option<(&'a str, bool)> {
+    let mut bare = None;
+    for entry in setting.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.split_once('=') {
+            Some((name, value)) if name.trim() == report => return Some((value.trim(), true)),
+            Some(_) => {}
+            None => bare = Some((entry, false)),
+        }
+    }
+    bare
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults_with_warnings(
+        line: &str,
+        vars: &[(&str, &str)],
+    ) -> Result<(String, Vec<String>), clap::Error> {
+        let cmd_line = line.split_whitespace().map(OsString::from).collect();
+        let (cmd_line, warnings) = apply_defaults(cmd_line, |var| {
+            vars.iter()
+                .find(|(name, _)| *name == var)
+                .map(|(_, value)| value.to_string())
+        })?;
+
+        let words: Vec<_> = cmd_line.iter().map(|w| w.to_string_lossy()).collect();
+        Ok((words.join(" "), warnings))
+    }
+
+    fn defaults(line: &str, vars: &[(&str, &str)]) -> Result<String, clap::Error> {
+        defaults_with_warnings(line, vars).map(|(line, _)| line)
+    }
+
+    #[test]
+    fn env_defaults_fill_unset_options() {
+        let vars = [
+            ("STRACE_PARSER_SORT", "files=time, total_time"),
+            ("STRACE_PARSER_COUNT", "50,throughput=5"),
+            ("STRACE_PARSER_FORMAT", "json"),
+        ];
+        assert_eq!(
+            defaults("strace-parser trace.log summary", &vars).unwrap(),
+            "strace-parser --format=json trace.log summary --sort=total_time --count=50"
+        );
+        assert_eq!(
+            defaults("strace-parser trace.log files", &vars).unwrap(),
+            "strace-parser --format=json trace.log files --sort=time"
+        );
+        assert_eq!(
+            defaults("strace-parser trace.log throughput", &vars).unwrap(),
+            "strace-parser trace.log throughput --count=5 --format=json"
+        );
+
+        // The reports of the global '--format' get it before <INPUT>
+        assert_eq!(
+            defaults(
+                "strace-parser trace.log pid 823",
+                &[("STRACE_PARSER_FORMAT", "summary=text,json")]
+            )
+            .unwrap(),
+            "strace-parser --format=json trace.log pid 823"
+        );
+        assert_eq!(
+            defaults(
+                "strace-parser trace.log summary",
+                &[("STRACE_PARSER_FORMAT", "summary=json")]
+            )
+            .unwrap(),
+            "strace-parser --format=json trace.log summary"
+        );
+        assert_eq!(
+            defaults(
+                "strace-parser trace.log --format text files",
+                &[("STRACE_PARSER_FORMAT", "json")]
+            )
+            .unwrap(),
+            "strace-parser trace.log --format text files"
+        );
+        let (line, warnings) = defaults_with_warnings(
+            "strace-parser trace.log summary",
+            &[("STRACE_PARSER_FORMAT", "csv")],
+        )
+        .unwrap();
+        assert_eq!(line, "strace-parser trace.log summary");
+        assert_eq!(warnings.len(), 1);
+
+        // Flags win, including ones that set the option another way
+        assert_eq!(
+            defaults("strace-parser trace.log summ -c 3 -s pid", &vars).unwrap(),
+            "strace-parser --format=json trace.log summ -c 3 -s pid"
+        );
+        assert_eq!(
+            defaults("strace-parser trace.log throughput --json -c 1", &vars).unwrap(),
+            "strace-parser trace.log throughput --json -c 1"
+        );
+
+        // Entries naming a subcommand must be valid for it
+        let err = defaults(
+            "strace-parser trace.log files",
+            &[("STRACE_PARSER_SORT", "files=bogus")],
+        )
+        .unwrap_err();
+        assert!(err.message.contains("STRACE_PARSER_SORT"));
+        assert!(defaults(
+            "strace-parser trace.log tree",
+            &[("STRACE_PARSER_COUNT", "tree=5")]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn env_defaults_warn_on_invalid_bare_values() {
+        let vars = [
+            ("STRACE_PARSER_SORT", "total_time"),
+            ("STRACE_PARSER_COUNT", "lots"),
+        ];
+        let (line, warnings) =
+            defaults_with_warnings("strace-parser trace.log files", &vars).unwrap();
+        assert_eq!(line, "strace-parser trace.log files");
+        assert_eq!(
+            warnings,
+            vec!["ignoring 'total_time' from STRACE_PARSER_SORT, it isn't a valid '--sort' for 'files'"]
+        );
+
+        let (_, warnings) =
+            defaults_with_warnings("strace-parser trace.log summary", &vars).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("'lots' from STRACE_PARSER_COUNT"));
+
+        // Subcommands without the option are skipped quietly
+        let (_, warnings) = defaults_with_warnings(
+            "strace-parser trace.log tree",
+            &[("STRACE_PARSER_COUNT", "5")],
+        )
+        .unwrap();
+        assert!(warnings.is_empty());
+    }
+}
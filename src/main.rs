@@ -1,112 +1,1024 @@
 use bstr::ByteSlice;
-use clap::ArgMatches;
-use memmap::MmapOptions;
-use parser::histogram;
+use chrono::{Duration, FixedOffset};
+use clap::{ArgMatches, ErrorKind};
+#[cfg(unix)]
+use memmap2::{Advice, UncheckedAdvice};
+use memmap2::{Mmap, MmapOptions};
+use parser::amplification;
+use parser::baseline::{self, Profile};
+use parser::clock;
+use parser::columns::PidColumn;
+use parser::concurrency;
+use parser::connections;
+use parser::console;
+use parser::count_summary::CountSummary;
+use parser::coverage::Coverage;
+use parser::drift;
+use parser::error_rate;
+use parser::errors::{self, IgnoredErrors};
+use parser::estimate;
+use parser::events;
+use parser::extract;
+use parser::fd_lifecycle;
+use parser::futex;
+use parser::health;
+use parser::histogram::{self, QuantizeValue};
+use parser::ipc;
+use parser::marks::{self, Mark};
+use parser::metrics::ExprMetric;
+use parser::net;
+use parser::open_fds;
+use parser::otlp;
+use parser::output;
+use parser::path_aliases::PathAliases;
+use parser::path_calls;
+use parser::permissions;
+use parser::poll;
+use parser::schema::JsonReport;
+use parser::security;
 use parser::session_summary::SessionSummary;
+use parser::signals;
+use parser::sleeps;
 use parser::sort_by::{SortBy, SortEventsBy};
-use parser::syscall_data;
+use parser::spikes;
+use parser::syscall_data::{self, PidData};
+use parser::syscall_groups::{GroupMode, SyscallGroups};
 use parser::syscall_stats;
+use parser::table::ColumnPriority;
+use parser::throughput;
 use parser::time;
-use parser::HashSet;
+use parser::wait_for;
+use parser::zombies;
 use parser::Pid;
+use parser::{HashMap, HashSet};
 use std::error::Error;
-use std::fs::File;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{stdin, stdout, BufWriter, IsTerminal, Read, Write};
+use std::path::Path;
+use terminal_size::{terminal_size, Width};
 
+mod bench_self;
 mod check_flags;
 mod cli;
+mod collector;
+mod env_defaults;
+mod pid_list;
+mod pipeline;
+mod repl;
+mod run;
 
+#[cfg(all(feature = "jemalloc", not(target_env = "msvc")))]
 #[global_allocator]
 static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
+// Exit statuses, documented in the README for scripts to branch on
+const EXIT_OK: i32 = 0;
+const EXIT_ERROR: i32 = 1;
+const EXIT_PARTIAL: i32 = 2;
+const EXIT_INVALID_TRACE: i32 = 3;
+const EXIT_CHECK_FAILED: i32 = 4;
+
+/// An error to exit with a status other than `EXIT_ERROR`
+#[derive(Debug)]
+struct ExitError {
+    code: i32,
+    message: String,
+}
+
+impl ExitError {
+    fn new(code: i32, message: impl Into<String>) -> ExitError {
+        ExitError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ExitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ExitError {}
+
 #[derive(Clone, Copy, Debug)]
 enum SubCmd {
+    Baseline,
+    BenchSelf,
+    Budget,
+    Concurrency,
+    Connections,
+    Console,
     Details,
+    Drift,
+    ErrorRate,
+    Errors,
+    Events,
     Exec,
+    Extract,
+    Fd,
+    FdPeak,
     Files,
+    Futex,
     Directories,
     Io,
+    Ipc,
     List,
+    Net,
+    Otlp,
+    Permissions,
+    Poll,
     Quantize,
+    Repl,
+    Security,
+    Signals,
+    Sleeps,
     Summary,
+    Throughput,
     Tree,
+    WaitFor,
+    Zombies,
 }
 
 fn main() {
-    let app_matches = cli::cli_args().get_matches();
+    let args: Vec<_> = std::env::args_os().collect();
 
-    if let Err(e) = execute(app_matches) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match cli::schema_args().get_matches_from_safe(&args) {
+        Ok(matches) => {
+            let report = matches.value_of("schema").unwrap_or_default();
+            if let Ok(report) = report.parse::<JsonReport>() {
+                println!("{}", report.schema());
+            }
+            return;
+        }
+        // Any other command line is a trace and its subcommands
+        Err(e) if matches!(e.kind, ErrorKind::InvalidValue | ErrorKind::EmptyValue) => e.exit(),
+        Err(_) => {}
+    }
+
+    // A command line reading as a trace and its subcommands is taken as one,
+    // even if the trace is named 'run' or 'report'
+    let result = match pipeline::split(&args) {
+        Ok(cmd_lines) => execute(parse_cmd_lines(cmd_lines)),
+        Err(e) => match run::command(&args) {
+            Some(Ok(matches)) => match matches.subcommand() {
+                ("run", Some(run_args)) => run::run(run_args, execute),
+                (_, Some(report_args)) => match pipeline::split(&run::report_args(report_args)) {
+                    Ok(cmd_lines) => execute(parse_cmd_lines(cmd_lines)),
+                    Err(e) => e.exit(),
+                },
+                _ => unreachable!(),
+            },
+            Some(Err(command_err)) => command_err.exit(),
+            None => e.exit(),
+        },
+    };
+    let code = match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e);
+            e.downcast_ref::<ExitError>().map_or(EXIT_ERROR, |e| e.code)
+        }
+    };
+    if code != EXIT_OK {
+        std::process::exit(code);
     }
 }
 
-fn execute(app_matches: ArgMatches) -> Result<(), Box<dyn Error>> {
+fn parse_cmd_lines(cmd_lines: Vec<Vec<OsString>>) -> Vec<ArgMatches<'static>> {
+    cmd_lines
+        .into_iter()
+        .map(|cmd_line| cli::cli_args().get_matches_from(cmd_line))
+        .collect()
+}
+
+// Runs the subcommand of each of `sections` in turn over one parse of the
+// trace, they share <INPUT> and the options given before the first. Returns
+// the status to exit with, `EXIT_PARTIAL` if some lines of the trace couldn't
+// be parsed or PIDs with corrupt data were left out.
+fn execute(sections: Vec<ArgMatches>) -> Result<i32, Box<dyn Error>> {
+    let app_matches = sections.first().ok_or("Missing subcommand")?;
+    let subcmds: Vec<_> = sections.iter().map(parse_subcmd).collect();
+    if subcmds.len() > 1 {
+        let alone = subcmds.iter().zip(&sections).find(|((subcmd, _), _)| {
+            matches!(subcmd, SubCmd::BenchSelf | SubCmd::Extract | SubCmd::Repl)
+        });
+        if let Some((_, section)) = alone {
+            return Err(format!(
+                "Error: '{}' can't be combined with other subcommands",
+                section.subcommand_name().unwrap_or_default()
+            )
+            .into());
+        }
+    }
+
     let file_name = app_matches.value_of("INPUT").ok_or("Missing filename")?;
+    let threads = match app_matches.value_of("pool_threads") {
+        Some(n) => Some(n.parse::<usize>()?),
+        None if app_matches.is_present("single_thread") => Some(1),
+        None => None,
+    };
+    if let Some(threads) = threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+    let group_config = app_matches
+        .value_of("groups")
+        .map(fs::read_to_string)
+        .transpose()?;
+
     let file = File::open(file_name)?;
     let mmap = unsafe { MmapOptions::new().map(&file) }?;
-    let bytes = mmap.as_ref();
+    let with_pids;
+    let joined;
+    let corrected;
+    let estimated;
+    let mut bytes = mmap.as_ref();
 
     if bytes.is_empty() {
-        eprintln!("Error: {} is empty", file_name);
-        std::process::exit(1);
+        return Err(
+            ExitError::new(EXIT_INVALID_TRACE, format!("Error: {} is empty", file_name)).into(),
+        );
     }
 
-    match check_flags::correct_strace_flags(
-        bytes.lines().next().unwrap_or_default().to_str().unwrap(),
-    ) {
+    output::set_quiet(app_matches.is_present("quiet"));
+    output::set_no_header(app_matches.is_present("no_header"));
+    output::set_pid_labels(pid_labels(app_matches)?);
+    output::set_column_priorities(column_priorities(app_matches));
+    if let Some(title) = app_matches.value_of("title") {
+        output::set_title(title);
+    }
+    if app_matches.is_present("absolute_time") {
+        output::set_absolute_time(absolute_time(app_matches, bytes)?);
+    }
+
+    let (subcmd, args) = subcmds[0];
+    let json = app_matches.value_of("format") == Some("json");
+    if json {
+        sections.iter().try_for_each(check_json_args)?;
+    }
+    // Every line 'events' writes is a JSON object, and 'console --raw' writes
+    // only the program's output, so notes and titles are left out. As are
+    // they with '--format json', which writes a JSON object per subcommand.
+    if json
+        || subcmds.iter().any(|(subcmd, args)| {
+            matches!(subcmd, SubCmd::Events)
+                || (matches!(subcmd, SubCmd::Console) && args.is_present("raw"))
+                || (matches!(subcmd, SubCmd::Throughput)
+                    && (args.is_present("json") || args.value_of("format") == Some("json")))
+                || (matches!(subcmd, SubCmd::Tree) && args.value_of("format") == Some("json"))
+                || (matches!(subcmd, SubCmd::Otlp)
+                    && !args.is_present("endpoint")
+                    && !args.is_present("output"))
+                || (matches!(subcmd, SubCmd::ErrorRate)
+                    && matches!(args.value_of("format"), Some("csv") | Some("json")))
+        })
+    {
+        output::set_quiet(true);
+        output::set_no_header(true);
+    }
+
+    if let Some(mut counts) = CountSummary::parse(bytes) {
+        if let Some(config) = &group_config {
+            counts.group_syscalls(&SyscallGroups::parse(config)?, group_mode(app_matches));
+        }
+        // Every subcommand is checked before any prints
+        let full_trace = subcmds
+            .iter()
+            .zip(&sections)
+            .find(|((subcmd, _), _)| !matches!(subcmd, SubCmd::Baseline | SubCmd::Summary));
+        if let Some((_, section)) = full_trace {
+            return Err(format!(
+                "Error: '{}' needs a full trace, <INPUT> only has the syscall totals printed by 'strace -c'. \
+                    Only 'summary' and 'baseline' work with these",
+                section.subcommand_name().unwrap_or_default()
+            )
+            .into());
+        }
+        for &(subcmd, args) in &subcmds {
+            match subcmd {
+                SubCmd::Baseline => {
+                    // ignore result as we expect failures when piping to head
+                    let _result = run_baseline(
+                        args,
+                        &Profile::from_counts(&counts),
+                        table_width(app_matches)?,
+                    )?;
+                }
+                _ => print_count_summary(args, app_matches, &counts)?,
+            }
+        }
+        return Ok(EXIT_OK);
+    }
+
+    // A trace of one process run without '-f' has no PIDs, a copy with a
+    // PID on each line is parsed instead
+    if app_matches.is_present("no_pid") || parser::parser::lacks_pids(bytes) {
+        with_pids = parser::parser::add_pid_column(bytes);
+        bytes = with_pids.as_ref();
+    }
+
+    // Calls wrapped onto several lines, e.g. by a log shipper, are joined back
+    if parser::parser::has_continued_lines(bytes) {
+        joined = parser::parser::join_continued_lines(bytes);
+        bytes = joined.as_ref();
+    }
+
+    // Output the traced program printed before strace's first line doesn't
+    // show how strace was run
+    let first_line = bytes
+        .lines()
+        .find(|line| !line.trim().is_empty() && !syscall_data::is_noise(line))
+        .unwrap_or_default();
+    match check_flags::correct_strace_flags(&first_line.to_str_lossy()) {
         Ok(true) => {}
-        _ => std::process::exit(0),
+        _ => return Ok(EXIT_INVALID_TRACE),
     }
+    let whole_seconds = first_line
+        .fields()
+        .nth(1)
+        .is_some_and(time::is_whole_seconds);
 
-    let syscall_data = syscall_data::build_syscall_data(bytes);
-    let syscall_stats = syscall_stats::build_syscall_stats(&syscall_data);
-    let session_summary = SessionSummary::from_syscall_stats(&syscall_stats, &syscall_data);
-    let elapsed_time = time::parse_elapsed_real_time(bytes);
+    if let SubCmd::BenchSelf = subcmd {
+        let iterations = match args.value_of("iterations") {
+            Some(ct) => ct.parse::<usize>()?.max(1),
+            None => 5,
+        };
 
-    let (subcmd, args) = parse_subcmd(&app_matches);
+        // ignore result as we expect failures when piping to head
+        let _result = bench_self::run(bytes, iterations);
+        return Ok(EXIT_OK);
+    }
 
+    // The extract is a copy of the trace as captured, so it's written before
+    // clock jumps are fixed or durations estimated
+    if let SubCmd::Extract = subcmd {
+        let pids: Option<HashSet<Pid>> =
+            requested_pids(args)?.map(|pids| pids.into_iter().collect());
+        let string_limit = match args.value_of("string_limit") {
+            Some(bytes) => bytes.parse::<usize>()?,
+            None => extract::DEFAULT_STRING_LIMIT,
+        };
+
+        match args.value_of_os("output") {
+            Some(path) => {
+                let mut out = BufWriter::new(File::create(path)?);
+                let stats = extract::write_extract(&mut out, bytes, pids.as_ref(), string_limit)?;
+                out.flush()?;
+                writeln!(stdout(), "Wrote {}, {}", path.to_string_lossy(), stats)?;
+            }
+            None => {
+                let mut out = BufWriter::new(stdout().lock());
+                // ignore result as we expect failures when piping to head
+                let _result = extract::write_extract(&mut out, bytes, pids.as_ref(), string_limit)
+                    .and_then(|_| out.flush());
+            }
+        }
+        return Ok(EXIT_OK);
+    }
+
+    let clock_jumps = clock::find_clock_jumps(bytes);
+    let fix_clock = app_matches.is_present("fix_clock") && !clock_jumps.is_empty();
+    if fix_clock {
+        // Copy-on-write, the trace file itself is left unchanged
+        let mut copy = unsafe { MmapOptions::new().map_copy(&file) }?;
+        clock::correct_clock_jumps(&mut copy, &clock_jumps);
+        corrected = copy;
+        bytes = corrected.as_ref();
+    }
+
+    let estimate_durations = !estimate::has_durations(bytes);
+    if estimate_durations {
+        estimated = estimate::add_estimated_durations(bytes);
+        bytes = estimated.as_ref();
+    }
+
+    let floor = app_matches
+        .value_of("ignore_faster_than")
+        .and_then(syscall_data::parse_duration);
+    let prefilter = match app_matches.is_present("prefilter") {
+        true => prefilter_pids(&subcmds)?,
+        false => None,
+    };
+    if app_matches.is_present("prefilter") && prefilter.is_none() {
+        output::print_note(
+            "  Warning: '--prefilter' needs PIDs given to every subcommand, without '-', '--related', or '--threads', parsing the whole trace\n",
+        )?;
+    }
+    let max_memory = app_matches
+        .value_of("max_memory")
+        .and_then(syscall_data::parse_size);
+    let stream = app_matches.is_present("stream");
+    #[cfg(unix)]
+    if stream || max_memory.is_some() {
+        mmap.advise(Advice::Sequential)?;
+    }
+    // clap rejects '--stream' and '--max-memory' with each other and with
+    // '--prefilter', so it never has to pick between them
+    let mut syscall_data = match (prefilter, max_memory, floor) {
+        _ if stream => syscall_data::build_syscall_data_streaming(
+            bytes,
+            syscall_data::DEFAULT_STREAM_WINDOW,
+            floor.unwrap_or_default(),
+            |window| release_pages(&mmap, window),
+        ),
+        (_, Some(max_bytes), _) => syscall_data::build_syscall_data_capped(
+            bytes,
+            max_bytes,
+            floor.unwrap_or_default(),
+            tempfile::tempfile()?,
+            |window| release_pages(&mmap, window),
+        )?,
+        (Some(pids), None, _) => {
+            syscall_data::build_syscall_data_for_pids(bytes, &pids, floor.unwrap_or_default())
+        }
+        (None, None, Some(floor)) => syscall_data::build_syscall_data_with_floor(bytes, floor),
+        (None, None, None) => syscall_data::build_syscall_data(bytes),
+    };
+    let skipped = syscall_data::skipped_lines(bytes);
+    let unparsed = skipped.unparsed;
+    if skipped.noise > 0 {
+        output::print_note(&format!(
+            "  Skipped {} {} that {} strace output, such as the traced program's own output\n",
+            skipped.noise,
+            if skipped.noise == 1 { "line" } else { "lines" },
+            if skipped.noise == 1 {
+                "isn't"
+            } else {
+                "aren't"
+            }
+        ))?;
+    }
+    if unparsed > 0 {
+        output::print_note(&format!(
+            "  Warning: {} {} of the trace couldn't be parsed, results may be incomplete\n",
+            unparsed,
+            if unparsed == 1 { "line" } else { "lines" }
+        ))?;
+    }
+    let corrupt = syscall_data::quarantine_corrupt_pids(&mut syscall_data);
+    if !corrupt.is_empty() {
+        let mut note = format!(
+            "  Warning: {} left out as {} data can't be right, results may be incomplete",
+            if corrupt.len() == 1 {
+                "1 PID was".to_string()
+            } else {
+                format!("{} PIDs were", corrupt.len())
+            },
+            if corrupt.len() == 1 { "its" } else { "their" }
+        );
+        for c in &corrupt {
+            note.push_str(&format!("\n    {}: {}", output::pid_label(c.pid), c.reason));
+        }
+        note.push('\n');
+        output::print_note(&note)?;
+    }
+    if let Some(path) = app_matches.value_of("ignore_errors") {
+        let ignored = IgnoredErrors::parse(&fs::read(path)?)?;
+        errors::remove_ignored_errors(&mut syscall_data, &ignored);
+    }
+    let mut syscall_stats = syscall_stats::build_syscall_stats(&syscall_data);
+    if app_matches.is_present("by_fd_kind") {
+        syscall_stats = syscall_stats::split_by_fd_kind(syscall_stats, &syscall_data);
+    }
+    let coverage = Coverage::from_syscall_stats(&syscall_stats);
+    let mut session_summary = SessionSummary::from_syscall_stats(&syscall_stats, &syscall_data);
+    if let Some(config) = &group_config {
+        let groups = SyscallGroups::parse(config)?;
+        session_summary.group_syscalls(&groups, group_mode(app_matches));
+    }
+    session_summary.set_width(table_width(app_matches)?);
+    if let Some(k) = app_matches.value_of("outlier_mads") {
+        session_summary.set_outlier_mads(k.parse::<f32>()?);
+    }
+    if app_matches.is_present("resolve_links") || app_matches.is_present("path_aliases") {
+        let mut path_aliases = PathAliases::default();
+        if app_matches.is_present("resolve_links") {
+            path_aliases = PathAliases::from_trace(&syscall_data);
+        }
+        if let Some(path) = app_matches.value_of("path_aliases") {
+            path_aliases.extend(PathAliases::parse(&fs::read(path)?)?);
+        }
+        session_summary.set_path_aliases(path_aliases);
+    }
+    if let Some(marks) = app_matches.values_of("mark") {
+        session_summary.set_marks(marks.map(str::parse).collect::<Result<Vec<Mark>, _>>()?);
+    }
+    if let Some(path) = app_matches.value_of("metrics") {
+        for metric in ExprMetric::parse(&fs::read_to_string(path)?)? {
+            session_summary.add_metric(&metric, &syscall_data);
+        }
+    }
+    let elapsed_time = time::parse_elapsed_real_time(bytes);
+
+    if let Some(title) = app_matches.value_of("title") {
+        // ignore result as we expect failures when piping to head
+        let _result = print_title(title);
+    }
+    if !clock_jumps.is_empty() {
+        // ignore result as we expect failures when piping to head
+        let _result = clock::print_clock_jumps(&clock_jumps, fix_clock);
+    }
     // ignore result as we expect failures when piping to head
-    let _result = match subcmd {
+    let _result = coverage.print_coverage();
+    if estimate_durations && subcmds.iter().any(|&(subcmd, _)| uses_durations(subcmd)) {
+        // ignore result as we expect failures when piping to head
+        let _result = estimate::print_estimate_note();
+    }
+    if whole_seconds {
+        // ignore result as we expect failures when piping to head
+        let _result = check_flags::print_seconds_note();
+    }
+    // Replaying fds for the score is skipped when the line would not be shown
+    if !output::quiet() {
+        // ignore result as we expect failures when piping to head
+        let _result = health::print_verdict(&session_summary.health(&syscall_data));
+    }
+
+    if let SubCmd::Repl = subcmd {
+        return repl::run(stdin().lock(), stdin().is_terminal(), |words| {
+            run_repl_command(
+                file_name,
+                words,
+                bytes,
+                &syscall_data,
+                &session_summary,
+                &coverage,
+                elapsed_time,
+            )
+        })
+        .map(|_| EXIT_OK)
+        .map_err(|e| e.into());
+    }
+
+    for section in &sections {
+        // ignore result as we expect failures when piping to head
+        let _result = run_subcmd(
+            section,
+            bytes,
+            &syscall_data,
+            &session_summary,
+            &coverage,
+            elapsed_time,
+        )?;
+    }
+
+    Ok(if unparsed > 0 || !corrupt.is_empty() {
+        EXIT_PARTIAL
+    } else {
+        EXIT_OK
+    })
+}
+
+// The outer error is from invalid arguments, the inner from writing output.
+// `section` is the command line of one subcommand.
+fn run_subcmd(
+    section: &ArgMatches,
+    bytes: &[u8],
+    syscall_data: &HashMap<Pid, PidData>,
+    session_summary: &SessionSummary,
+    coverage: &Coverage,
+    elapsed_time: Option<Duration>,
+) -> Result<Result<(), std::io::Error>, Box<dyn Error>> {
+    let (subcmd, args) = parse_subcmd(section);
+    let json = section.value_of("format") == Some("json");
+    if json {
+        check_json_args(section)?;
+    }
+    let (report, needs) = report_needs(subcmd);
+    match coverage.check_report(report, needs) {
+        Ok(true) => {}
+        skipped => return Ok(skipped.map(|_| ())),
+    }
+
+    let result = match subcmd {
+        // Run before any data is aggregated, or read their own commands
+        SubCmd::BenchSelf | SubCmd::Extract | SubCmd::Repl => Ok(()),
+        SubCmd::Baseline => {
+            let traced_secs = session_summary.traced_time().map_or(0.0, |t| {
+                t.num_microseconds().unwrap_or_default() as f64 / 1_000_000.0
+            });
+            let stats = syscall_stats::build_syscall_stats(syscall_data);
+            let current = Profile::from_trace(&stats, syscall_data, traced_secs);
+            run_baseline(args, &current, session_summary.width())?
+        }
+        SubCmd::Budget => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            session_summary.print_pid_budgets(&pids_to_print, syscall_data, count)
+        }
+        SubCmd::Concurrency => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let interval_ms = match args.value_of("interval") {
+                Some(ms) => ms.parse::<i64>()?,
+                None => 1000,
+            };
+            concurrency::print_concurrency(
+                bytes,
+                &pids_to_print,
+                interval_ms * 1000,
+                session_summary.width(),
+            )
+        }
+        SubCmd::Connections => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            connections::print_connections(&pids_to_print, syscall_data, session_summary.width())
+        }
+        SubCmd::Console => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            match args.is_present("raw") {
+                true => console::write_raw_console(&mut stdout(), bytes, &pids_to_print),
+                false => console::print_console(bytes, &pids_to_print),
+            }
+        }
         SubCmd::Details => {
-            let pids_to_print = select_pids(args, &session_summary)?;
-            session_summary.print_pid_details(&pids_to_print, &syscall_data)
+            let pids_to_print = select_pids(args, session_summary)?;
+            let calls = events::call_runs(bytes, &pids_to_print, args.is_present("expand"));
+            match args.value_of("output_dir") {
+                Some(dir) if args.is_present("each") => {
+                    let paths = session_summary.write_pid_reports(
+                        &pids_to_print,
+                        syscall_data,
+                        &calls,
+                        Path::new(dir),
+                    )?;
+                    paths
+                        .iter()
+                        .try_for_each(|path| writeln!(stdout(), "Wrote {}", path.display()))
+                }
+                _ if json => session_summary.write_pid_details_json(
+                    &mut stdout(),
+                    &pids_to_print,
+                    syscall_data,
+                    &calls,
+                ),
+                _ => session_summary.print_pid_details(&pids_to_print, syscall_data, &calls),
+            }
         }
         SubCmd::Io => {
-            let pids_to_print = select_pids(&args, &session_summary)?;
+            let pids_to_print = select_pids(args, session_summary)?;
             let sort_by = args
                 .value_of("sort_by")
                 .unwrap_or_default()
                 .parse::<SortEventsBy>()
                 .unwrap_or(SortEventsBy::Time);
-            session_summary.print_io(&pids_to_print, &syscall_data, sort_by)
+            if json {
+                return Ok(session_summary.write_io_json(
+                    &mut stdout(),
+                    &pids_to_print,
+                    syscall_data,
+                    sort_by,
+                ));
+            }
+            if args.is_present("cadence") {
+                session_summary.print_io_cadence(&pids_to_print, syscall_data)
+            } else if args.is_present("by_path") {
+                session_summary
+                    .print_io(&pids_to_print, syscall_data, sort_by)
+                    .and_then(|_| {
+                        path_calls::print_path_calls(
+                            bytes,
+                            &pids_to_print,
+                            syscall_data,
+                            session_summary.width(),
+                        )
+                    })
+            } else {
+                session_summary.print_io(&pids_to_print, syscall_data, sort_by)
+            }
+            .and_then(|_| match args.is_present("amplification") {
+                true => amplification::print_amplification(
+                    bytes,
+                    &pids_to_print,
+                    syscall_data,
+                    session_summary.width(),
+                ),
+                false => Ok(()),
+            })
+            .and_then(|_| {
+                marks::print_file_marks(
+                    bytes,
+                    &pids_to_print,
+                    syscall_data,
+                    session_summary.marks(),
+                    session_summary.width(),
+                )
+            })
+        }
+        SubCmd::Ipc => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            ipc::print_ipc(&pids_to_print, syscall_data)
+        }
+        SubCmd::Net => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            let traffic = net::net_activity(&pids_to_print, syscall_data);
+            net::print_net_activity(&traffic, count, session_summary.width())
+        }
+        SubCmd::Otlp => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let first_time = bytes
+                .lines()
+                .next()
+                .and_then(|line| line.fields().nth(1))
+                .unwrap_or_default();
+            if section.value_of("date").is_none() && time::AbsoluteTime::needs_date(first_time) {
+                return Err("Error: 'otlp' needs '--absolute-time --date <DATE>' for traces run with '-tt', which have no date".into());
+            }
+            let clock = absolute_time(section, bytes)?;
+            let unix_us = |time: &[u8]| clock.unix_micros(time);
+
+            let min_ms = args
+                .value_of("min_duration")
+                .and_then(syscall_data::parse_duration)
+                .map_or(otlp::DEFAULT_MIN_SPAN_MS, |secs| secs * 1000.0);
+            let parent_span_id = args
+                .value_of("parent_span_id")
+                .map(|id| u64::from_str_radix(id, 16))
+                .transpose()?;
+            let mut spans = session_summary.pid_spans(&pids_to_print, parent_span_id, unix_us);
+            spans.extend(otlp::syscall_spans(bytes, &pids_to_print, min_ms, unix_us));
+
+            let trace_id = args
+                .value_of("trace_id")
+                .map_or_else(|| otlp::trace_id(bytes), str::to_lowercase);
+            let service_name = args.value_of("service_name").unwrap_or("strace");
+            match (args.value_of("endpoint"), args.value_of_os("output")) {
+                (Some(endpoint), _) => {
+                    let mut body = Vec::new();
+                    otlp::write_otlp_json(
+                        &mut body,
+                        &spans,
+                        &trace_id,
+                        service_name,
+                        output::title(),
+                    )?;
+                    collector::post_spans(endpoint, &body)?;
+                    writeln!(stdout(), "Sent {} spans to {}", spans.len(), endpoint)
+                }
+                (None, Some(path)) => {
+                    let mut out = BufWriter::new(File::create(path)?);
+                    otlp::write_otlp_json(
+                        &mut out,
+                        &spans,
+                        &trace_id,
+                        service_name,
+                        output::title(),
+                    )?;
+                    out.flush()?;
+                    writeln!(
+                        stdout(),
+                        "Wrote {} spans to {}",
+                        spans.len(),
+                        path.to_string_lossy()
+                    )
+                }
+                (None, None) => otlp::write_otlp_json(
+                    &mut stdout(),
+                    &spans,
+                    &trace_id,
+                    service_name,
+                    output::title(),
+                ),
+            }
+        }
+        SubCmd::Permissions => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            permissions::print_permissions(&pids_to_print, syscall_data, session_summary.width())
+        }
+        SubCmd::Poll => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            poll::print_epoll_sources(&pids_to_print, syscall_data, count, session_summary.width())
+        }
+        SubCmd::Security => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let lines = args.is_present("lines");
+            security::print_security(bytes, &pids_to_print, lines, session_summary.width())
+        }
+        SubCmd::Signals => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            signals::print_signal_handlers(bytes, &pids_to_print, count, session_summary.width())
+        }
+        SubCmd::Sleeps => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            sleeps::print_sleeps(
+                bytes,
+                &pids_to_print,
+                syscall_data,
+                count,
+                session_summary.width(),
+            )
+        }
+        SubCmd::Drift => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            let pct = match args.value_of("percent") {
+                Some(pct) => pct.trim_end_matches('%').parse::<f64>()?,
+                None => 20.0,
+            };
+            drift::print_drift(bytes, &pids_to_print, pct, count, session_summary.width())
+        }
+        SubCmd::WaitFor => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            wait_for::print_wait_chains(bytes, &pids_to_print, count, session_summary.width())
+        }
+        SubCmd::Zombies => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            zombies::print_reaping(
+                bytes,
+                &pids_to_print,
+                syscall_data,
+                count,
+                session_summary.width(),
+            )
+        }
+        SubCmd::Events => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let dedup_window = match args.value_of("dedup_window") {
+                Some(window) => window.parse::<usize>()?,
+                None => 0,
+            };
+            events::print_events(bytes, &pids_to_print, dedup_window)
+        }
+        SubCmd::ErrorRate => {
+            // Every PID is in the overall rate, only the PIDs asked for get their own
+            let pids_to_print = match args.is_present("pids") {
+                true => select_pids(args, session_summary)?,
+                false => Vec::new(),
+            };
+            let interval_ms = match args.value_of("interval") {
+                Some(ms) => ms.parse::<i64>()?,
+                None => 1000,
+            };
+            let series = error_rate::error_rate(bytes, &pids_to_print, interval_ms * 1000);
+            match args.value_of("format") {
+                Some("csv") => error_rate::print_error_rate_csv(&series),
+                Some("json") => error_rate::print_error_rate_json(&series),
+                None if json => error_rate::print_error_rate_json(&series),
+                _ => error_rate::print_error_rate(&series, session_summary.width()),
+            }
+        }
+        SubCmd::Errors => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            errors::print_error_patterns(
+                &pids_to_print,
+                syscall_data,
+                count,
+                session_summary.width(),
+            )
+        }
+        SubCmd::Fd => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let lifecycle = fd_lifecycle::fd_lifecycle(&pids_to_print, syscall_data);
+            fd_lifecycle::print_fd_lifecycle(
+                &lifecycle,
+                args.is_present("leaks"),
+                session_summary.width(),
+            )
+        }
+        SubCmd::FdPeak => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            let open_fds = open_fds::open_fds(&pids_to_print, syscall_data);
+            open_fds::print_open_fds(&open_fds, count, session_summary.width())
+        }
+        SubCmd::Futex => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 25,
+            };
+            let lock_names = match args.value_of("lock_names") {
+                Some(path) => futex::parse_lock_names(&fs::read_to_string(path)?)?,
+                None => futex::LockNames::default(),
+            };
+            let contention = futex::futex_contention(&pids_to_print, syscall_data);
+            futex::print_futex_contention(&contention, &lock_names, count, session_summary.width())
         }
         SubCmd::Files => {
-            let pids_to_print = select_pids(&args, &session_summary)?;
+            let pids_to_print = select_pids(args, session_summary)?;
             let sort_by = args
                 .value_of("sort_by")
                 .unwrap_or_default()
                 .parse::<SortEventsBy>()
                 .unwrap_or(SortEventsBy::Time);
-            session_summary.print_opened_files(&pids_to_print, &syscall_data, sort_by)
+            let coalesce_pids = args.is_present("coalesce_pids");
+            if json {
+                session_summary.write_opened_files_json(
+                    &mut stdout(),
+                    &pids_to_print,
+                    syscall_data,
+                    sort_by,
+                    coalesce_pids,
+                )
+            } else {
+                session_summary.print_opened_files(
+                    &pids_to_print,
+                    syscall_data,
+                    sort_by,
+                    coalesce_pids,
+                )
+            }
         }
         SubCmd::Directories => {
-            let pids_to_print = select_pids(&args, &session_summary)?;
+            let pids_to_print = select_pids(args, session_summary)?;
             let sort_by = args
                 .value_of("sort_by")
                 .unwrap_or_default()
                 .parse::<SortEventsBy>()
                 .unwrap_or(SortEventsBy::Time);
-            session_summary.print_opened_directories(&pids_to_print, &syscall_data, sort_by)
+            if json {
+                session_summary.write_opened_directories_json(
+                    &mut stdout(),
+                    &pids_to_print,
+                    syscall_data,
+                    sort_by,
+                )
+            } else {
+                session_summary.print_opened_directories(&pids_to_print, syscall_data, sort_by)
+            }
         }
         SubCmd::Exec => {
-            let mut pids_to_print = select_pids(&args, &session_summary)?;
+            let mut pids_to_print = select_pids(args, session_summary)?;
             pids_to_print.sort();
-            session_summary.print_exec_list(&pids_to_print)
+            if json {
+                session_summary.write_exec_list_json(&mut stdout(), &pids_to_print)
+            } else if args.is_present("anomalies") {
+                session_summary.print_exec_anomalies(&pids_to_print)
+            } else {
+                session_summary.print_exec_list(&pids_to_print)
+            }
         }
         SubCmd::Quantize => {
-            let pids_to_print = select_pids(&args, &session_summary)?;
+            let pids_to_print = select_pids(args, session_summary)?;
             let syscall = args.value_of("syscall").unwrap_or_default();
-            histogram::print_histogram(syscall.as_bytes(), &pids_to_print, &syscall_data)
+            let value = match args.value_of("value") {
+                Some(value) => value.parse::<QuantizeValue>()?,
+                None => QuantizeValue::Duration,
+            };
+            match value {
+                QuantizeValue::Duration if args.is_present("by_error") => {
+                    histogram::print_outcome_histograms(
+                        syscall.as_bytes(),
+                        &pids_to_print,
+                        syscall_data,
+                    )
+                }
+                QuantizeValue::Duration => {
+                    histogram::print_histogram(syscall.as_bytes(), &pids_to_print, syscall_data)
+                }
+                _ if args.is_present("by_error") => {
+                    return Err("'--by-error' only applies to '--value duration', \
+                        failed calls have no return value to histogram"
+                        .into());
+                }
+                _ => histogram::print_value_histogram(
+                    bytes,
+                    syscall.as_bytes(),
+                    &pids_to_print,
+                    value,
+                ),
+            }
         }
         SubCmd::List => {
             let count_to_print = if let Some(count) = args.value_of("count") {
@@ -120,7 +1032,17 @@ fn execute(app_matches: ArgMatches) -> Result<(), Box<dyn Error>> {
                 .unwrap_or_default()
                 .parse::<SortBy>()
                 .unwrap_or(SortBy::ActiveTime);
-            session_summary.print_pid_list(count_to_print, sort_by)
+
+            if json {
+                session_summary.write_pid_list_json(&mut stdout(), count_to_print, sort_by)
+            } else if let Some(column_strs) = args.values_of("columns") {
+                let columns = column_strs
+                    .map(|c| c.parse::<PidColumn>())
+                    .collect::<Result<Vec<_>, _>>()?;
+                session_summary.print_pid_table(count_to_print, sort_by, &columns)
+            } else {
+                session_summary.print_pid_list(count_to_print, sort_by)
+            }
         }
         SubCmd::Summary => {
             let count_to_print = if let Some(count) = args.value_of("count") {
@@ -134,44 +1056,509 @@ fn execute(app_matches: ArgMatches) -> Result<(), Box<dyn Error>> {
                 .unwrap_or_default()
                 .parse::<SortBy>()
                 .unwrap_or(SortBy::ActiveTime);
-            session_summary.print_summary(elapsed_time, count_to_print, sort_by)
+            if json && args.is_present("by_user") {
+                return Ok(session_summary.write_user_summary_json(
+                    &mut stdout(),
+                    elapsed_time,
+                    count_to_print,
+                    sort_by,
+                ));
+            } else if json {
+                return Ok(session_summary.write_summary_json(
+                    &mut stdout(),
+                    elapsed_time,
+                    count_to_print,
+                    sort_by,
+                ));
+            }
+            let result = if args.is_present("by_user") {
+                session_summary.print_user_summary(elapsed_time, count_to_print, sort_by)
+            } else {
+                session_summary
+                    .print_summary(elapsed_time, count_to_print, sort_by)
+                    .and_then(|_| session_summary.print_peer_outliers())
+                    .and_then(|_| error_rate::print_error_trend(bytes, 1_000_000))
+                    .and_then(|_| {
+                        concurrency::print_in_flight(
+                            bytes,
+                            &session_summary.pids(),
+                            TOP_SYSCALLS,
+                            session_summary.width(),
+                        )
+                    })
+            };
+            result
+                .and_then(|_| {
+                    spikes::print_busiest_seconds(bytes, TOP_SYSCALLS, session_summary.width())
+                })
+                .and_then(|_| {
+                    marks::print_pid_marks(bytes, session_summary.marks(), session_summary.width())
+                })
+        }
+        SubCmd::Throughput => {
+            let pids_to_print = select_pids(args, session_summary)?;
+            let count = match args.value_of("count") {
+                Some(count) => count.parse::<usize>()?,
+                None => 10,
+            };
+            let interval_ms = match args.value_of("interval") {
+                Some(ms) => ms.parse::<i64>()?,
+                None => 1000,
+            };
+            let series =
+                throughput::throughput(&pids_to_print, syscall_data, interval_ms * 1000, count);
+            if args.is_present("json") || args.value_of("format").map_or(json, |f| f == "json") {
+                throughput::print_throughput_json(&series)
+            } else {
+                throughput::print_throughput(&series, session_summary.width())
+            }
+        }
+        SubCmd::Tree if args.value_of("format").map_or(json, |f| f == "json") => {
+            session_summary.write_pid_tree_json(&mut stdout())
         }
         SubCmd::Tree => {
             let truncate = args.is_present("truncate");
-            session_summary.print_pid_tree(truncate)
+            let inclusive = args.is_present("inclusive");
+            let timeline = args.is_present("timeline");
+            session_summary.print_pid_tree(truncate, inclusive, timeline)
         }
     };
 
+    Ok(result)
+}
+
+// Parses a line from the repl as if its words followed '<INPUT>' on the
+// command line, then prints errors rather than exiting
+fn run_repl_command(
+    file_name: &str,
+    words: &[&str],
+    bytes: &[u8],
+    syscall_data: &HashMap<Pid, PidData>,
+    session_summary: &SessionSummary,
+    coverage: &Coverage,
+    elapsed_time: Option<Duration>,
+) {
+    let mut cmd_line = vec!["strace-parser", file_name];
+    cmd_line.extend_from_slice(words);
+
+    let cmd_line: Vec<OsString> = cmd_line.into_iter().map(OsString::from).collect();
+    let sections = match pipeline::split(&cmd_line) {
+        Ok(cmd_lines) => cmd_lines
+            .into_iter()
+            .map(|cmd_line| cli::cli_args().get_matches_from_safe(cmd_line))
+            .collect::<Result<Vec<_>, _>>(),
+        Err(e) => Err(e),
+    };
+    let sections = match sections {
+        Ok(sections) => sections,
+        Err(e) => match e.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => {
+                let _result = writeln!(stdout(), "{}", e.message);
+                return;
+            }
+            _ => {
+                eprintln!("{}", e.message);
+                return;
+            }
+        },
+    };
+
+    let alone = sections.iter().find(|matches| {
+        matches!(
+            parse_subcmd(matches).0,
+            SubCmd::BenchSelf | SubCmd::Extract | SubCmd::Repl
+        )
+    });
+    if let Some(matches) = alone {
+        eprintln!(
+            "'{}' can't be run from the repl",
+            matches.subcommand_name().unwrap_or_default()
+        );
+        return;
+    }
+
+    for section in &sections {
+        if let Err(e) = run_subcmd(
+            section,
+            bytes,
+            syscall_data,
+            session_summary,
+            coverage,
+            elapsed_time,
+        ) {
+            eprintln!("{}", e);
+            return;
+        }
+    }
+}
+
+// Only the totals printed by 'strace -c' are available, without any calls or PIDs
+fn print_count_summary(
+    args: &ArgMatches,
+    app_matches: &ArgMatches,
+    counts: &CountSummary,
+) -> Result<(), Box<dyn Error>> {
+    let count_to_print = match args.value_of("count") {
+        Some(count) => count.parse::<usize>()?,
+        None => 25,
+    };
+    let width = table_width(app_matches)?;
+
+    // ignore result as we expect failures when piping to head
+    let _result = if app_matches.value_of("format") == Some("json") {
+        counts.write_json(&mut stdout(), count_to_print)
+    } else {
+        app_matches
+            .value_of("title")
+            .map_or(Ok(()), print_title)
+            .and_then(|_| counts.print(count_to_print, width))
+    };
+
     Ok(())
 }
 
+// 'baseline save' and 'baseline check' with the profile of <INPUT>
+fn run_baseline(
+    args: &ArgMatches,
+    current: &Profile,
+    width: Option<usize>,
+) -> Result<Result<(), std::io::Error>, Box<dyn Error>> {
+    let result = match args.subcommand() {
+        ("save", Some(save_args)) => {
+            let path = save_args.value_of("PROFILE").unwrap_or_default();
+            fs::write(path, current.to_json())?;
+            writeln!(stdout(), "Wrote {}", path)
+        }
+        ("check", Some(check_args)) => {
+            let path = check_args.value_of("PROFILE").unwrap_or_default();
+            let saved = Profile::from_json(&fs::read_to_string(path)?)?;
+            let tolerance = match check_args.value_of("tolerance") {
+                Some(pct) => pct.trim_end_matches('%').parse::<f64>()? / 100.0,
+                None => 0.2,
+            };
+            let comparison = baseline::compare(&saved, current, tolerance);
+            let result = baseline::print_comparison(&saved, current, &comparison, tolerance, width);
+            if !comparison.is_empty() {
+                return Err(
+                    ExitError::new(EXIT_CHECK_FAILED, "Trace differs from the baseline").into(),
+                );
+            }
+            result
+        }
+        _ => unreachable!(),
+    };
+
+    Ok(result)
+}
+
+// Drops the pages of a part of the trace already parsed from memory. The map
+// is read only, so any touched again are read back from the file. Parts of a
+// corrected or estimated copy of the trace are left alone.
+fn release_pages(mmap: &Mmap, window: &[u8]) {
+    let offset = (window.as_ptr() as usize).wrapping_sub(mmap.as_ptr() as usize);
+    if offset
+        .checked_add(window.len())
+        .is_none_or(|end| end > mmap.len())
+    {
+        return;
+    }
+    #[cfg(unix)]
+    // SAFETY: the pages are of a read only map of a file that isn't written to
+    let _result =
+        unsafe { mmap.unchecked_advise_range(UncheckedAdvice::DontNeed, offset, window.len()) };
+}
+
+// Converts times on the day given by '--date', or only unix timestamps without it
+fn absolute_time(
+    app_matches: &ArgMatches,
+    bytes: &[u8],
+) -> Result<time::AbsoluteTime, Box<dyn Error>> {
+    let first_time = bytes
+        .lines()
+        .next()
+        .and_then(|line| line.fields().nth(1))
+        .unwrap_or_default();
+
+    let (date, offset) = match app_matches.value_of("date") {
+        Some(date) => {
+            let (date, offset) = time::AbsoluteTime::parse_date(date)?;
+            (Some(date), offset)
+        }
+        None if time::AbsoluteTime::needs_date(first_time) => {
+            let msg =
+                "'--absolute-time' needs '--date' for traces run with '-tt', which have no date";
+            return Err(msg.into());
+        }
+        None => (
+            None,
+            FixedOffset::east_opt(0).expect("UTC is a valid offset"),
+        ),
+    };
+
+    Ok(time::AbsoluteTime::new(date, offset, first_time))
+}
+
+// Labels given with '--label' take precedence over those in '--label-file'
+fn pid_labels(app_matches: &ArgMatches) -> Result<HashMap<Pid, String>, Box<dyn Error>> {
+    let mut labels = HashMap::default();
+
+    if let Some(path) = app_matches.value_of("label_file") {
+        labels.extend(pid_list::parse_labels(&fs::read_to_string(path)?)?);
+    }
+    if let Some(values) = app_matches.values_of("label") {
+        labels.extend(values.filter_map(pid_list::parse_label));
+    }
+
+    Ok(labels)
+}
+
+// Validated as the command line was parsed, a later setting for the same column wins
+fn column_priorities(app_matches: &ArgMatches) -> HashMap<String, usize> {
+    app_matches
+        .values_of("column_priority")
+        .into_iter()
+        .flatten()
+        .filter_map(|value| value.parse::<ColumnPriority>().ok())
+        .map(|c| (c.header, c.priority))
+        .collect()
+}
+
+fn group_mode(app_matches: &ArgMatches) -> GroupMode {
+    if app_matches.is_present("groups_only") {
+        GroupMode::Instead
+    } else {
+        GroupMode::Alongside
+    }
+}
+
+fn print_title(title: &str) -> Result<(), std::io::Error> {
+    output::print_heading(&format!(
+        "\n{}\n{}",
+        title,
+        "=".repeat(title.chars().count())
+    ))
+}
+
+// Tables are only fit to the terminal when writing to one, piped output is left at full width
+fn table_width(app_matches: &ArgMatches) -> Result<Option<usize>, Box<dyn Error>> {
+    match app_matches.value_of("width") {
+        Some(width) => Ok(Some(width.parse::<usize>()?)),
+        None => Ok(terminal_size().map(|(Width(w), _)| w as usize)),
+    }
+}
+
 fn parse_subcmd<'a>(app_matches: &'a ArgMatches<'a>) -> (SubCmd, &'a ArgMatches<'a>) {
     match app_matches.subcommand() {
+        ("baseline", Some(args)) => (SubCmd::Baseline, args),
+        ("bench-self", Some(args)) => (SubCmd::BenchSelf, args),
+        ("budget", Some(args)) => (SubCmd::Budget, args),
+        ("concurrency", Some(args)) => (SubCmd::Concurrency, args),
+        ("connections", Some(args)) => (SubCmd::Connections, args),
+        ("console", Some(args)) => (SubCmd::Console, args),
         ("pid", Some(args)) => (SubCmd::Details, args),
+        ("drift", Some(args)) => (SubCmd::Drift, args),
+        ("events", Some(args)) => (SubCmd::Events, args),
         ("exec", Some(args)) => (SubCmd::Exec, args),
+        ("extract", Some(args)) => (SubCmd::Extract, args),
+        ("error-rate", Some(args)) => (SubCmd::ErrorRate, args),
+        ("errors", Some(args)) => (SubCmd::Errors, args),
+        ("fd", Some(args)) => (SubCmd::Fd, args),
+        ("fd-peak", Some(args)) => (SubCmd::FdPeak, args),
         ("files", Some(args)) => (SubCmd::Files, args),
+        ("futex", Some(args)) => (SubCmd::Futex, args),
         ("directories", Some(args)) => (SubCmd::Directories, args),
         ("io", Some(args)) => (SubCmd::Io, args),
+        ("ipc", Some(args)) => (SubCmd::Ipc, args),
+        ("net", Some(args)) => (SubCmd::Net, args),
+        ("otlp", Some(args)) => (SubCmd::Otlp, args),
+        ("permissions", Some(args)) => (SubCmd::Permissions, args),
+        ("poll", Some(args)) => (SubCmd::Poll, args),
         ("quantize", Some(args)) => (SubCmd::Quantize, args),
+        ("repl", Some(args)) => (SubCmd::Repl, args),
+        ("security", Some(args)) => (SubCmd::Security, args),
+        ("signals", Some(args)) => (SubCmd::Signals, args),
+        ("sleeps", Some(args)) => (SubCmd::Sleeps, args),
         ("list-pids", Some(args)) => (SubCmd::List, args),
         ("summary", Some(args)) => (SubCmd::Summary, args),
+        ("throughput", Some(args)) => (SubCmd::Throughput, args),
         ("tree", Some(args)) => (SubCmd::Tree, args),
+        ("wait-for", Some(args)) => (SubCmd::WaitFor, args),
+        ("zombies", Some(args)) => (SubCmd::Zombies, args),
         _ => unreachable!(),
     }
 }
 
+// Syscalls shown with their busiest second below the summary
+const TOP_SYSCALLS: usize = 10;
+
+const IO_SYSCALLS: &[&[u8]] = &[
+    b"pread64",
+    b"preadv",
+    b"pwrite64",
+    b"pwritev",
+    b"read",
+    b"readv",
+    b"recv",
+    b"recvfrom",
+    b"recvmsg",
+    b"send",
+    b"sendmsg",
+    b"sendto",
+    b"write",
+    b"writev",
+];
+
+// '--format json' is only for the reports that write JSON, and not with the
+// options adding sections that don't
+fn check_json_args(section: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let (subcmd, args) = parse_subcmd(section);
+    let text_only: &[&str] = match subcmd {
+        SubCmd::Directories | SubCmd::Files | SubCmd::List | SubCmd::Otlp | SubCmd::Summary => &[],
+        // Only ever written as JSON lines
+        SubCmd::Events => &[],
+        SubCmd::Details => &["output_dir"],
+        SubCmd::Exec => &["anomalies"],
+        SubCmd::Io => &["amplification", "by_path", "cadence"],
+        // Their own '--format' takes precedence
+        SubCmd::ErrorRate | SubCmd::Throughput => &[],
+        SubCmd::Tree if args.is_present("format") => &[],
+        SubCmd::Tree => &["inclusive", "timeline", "truncate"],
+        _ => {
+            return Err(format!(
+                "Error: '{}' has no JSON output, run it without '--format json'",
+                section.subcommand_name().unwrap_or_default()
+            )
+            .into())
+        }
+    };
+
+    match text_only.iter().find(|arg| args.is_present(arg)) {
+        Some(arg) => Err(format!(
+            "Error: '--{}' has no JSON output, run '{}' without it or without '--format json'",
+            arg.replace('_', "-"),
+            section.subcommand_name().unwrap_or_default()
+        )
+        .into()),
+        None => Ok(()),
+    }
+}
+
+// The syscalls a report is built from, in a trace filtered to other syscalls it would be empty
+fn report_needs(subcmd: SubCmd) -> (&'static str, &'static [&'static [u8]]) {
+    match subcmd {
+        SubCmd::Connections => ("connections", &[b"accept", b"accept4", b"connect"]),
+        SubCmd::Console => ("console", &[b"pwrite64", b"write", b"writev"]),
+        SubCmd::Directories => (
+            "directories",
+            &[
+                b"getdents",
+                b"getdents64",
+                b"mkdir",
+                b"mkdirat",
+                b"open",
+                b"openat",
+            ],
+        ),
+        SubCmd::Exec => ("exec", &[b"execve", b"execveat"]),
+        SubCmd::Fd => ("fd", &[b"close", b"open", b"openat", b"socket"]),
+        SubCmd::FdPeak => ("fd-peak", &[b"close", b"open", b"openat", b"socket"]),
+        SubCmd::Files => ("files", &[b"creat", b"open", b"openat", b"openat2"]),
+        SubCmd::Futex => ("futex", &[b"futex"]),
+        SubCmd::Io => ("io", IO_SYSCALLS),
+        SubCmd::Ipc => ("ipc", &[b"read", b"write", b"writev"]),
+        SubCmd::Net => (
+            "net",
+            &[
+                b"accept",
+                b"accept4",
+                b"connect",
+                b"read",
+                b"recv",
+                b"recvfrom",
+                b"recvmsg",
+                b"send",
+                b"sendmsg",
+                b"sendto",
+                b"write",
+                b"writev",
+            ],
+        ),
+        SubCmd::Permissions => (
+            "permissions",
+            &[
+                b"chmod",
+                b"chown",
+                b"fchmod",
+                b"fchmodat",
+                b"fchown",
+                b"fchownat",
+                b"lchown",
+                b"umask",
+            ],
+        ),
+        SubCmd::Poll => ("poll", &[b"epoll_pwait", b"epoll_pwait2", b"epoll_wait"]),
+        SubCmd::Signals => ("signals", &[b"rt_sigreturn", b"sigreturn"]),
+        SubCmd::Sleeps => (
+            "sleeps",
+            &[
+                b"clock_nanosleep",
+                b"nanosleep",
+                b"restart_syscall",
+                b"timerfd_settime",
+            ],
+        ),
+        SubCmd::Throughput => ("throughput", IO_SYSCALLS),
+        SubCmd::Tree => ("tree", &[b"clone", b"clone3", b"execve", b"fork", b"vfork"]),
+        SubCmd::WaitFor => (
+            "wait-for",
+            &[b"futex", b"read", b"wait4", b"waitpid", b"write", b"writev"],
+        ),
+        SubCmd::Zombies => ("zombies", &[b"wait4", b"waitid", b"waitpid"]),
+        // Built from whichever syscalls were traced
+        SubCmd::Baseline
+        | SubCmd::BenchSelf
+        | SubCmd::Budget
+        | SubCmd::Concurrency
+        | SubCmd::Details
+        | SubCmd::Drift
+        | SubCmd::ErrorRate
+        | SubCmd::Errors
+        | SubCmd::Events
+        | SubCmd::Extract
+        | SubCmd::List
+        | SubCmd::Otlp
+        | SubCmd::Quantize
+        | SubCmd::Repl
+        | SubCmd::Security
+        | SubCmd::Summary => ("", &[]),
+    }
+}
+
+// Reports showing call durations or times derived from them, which are only
+// estimates for traces run without '-T'
+fn uses_durations(subcmd: SubCmd) -> bool {
+    !matches!(
+        subcmd,
+        SubCmd::Console
+            | SubCmd::ErrorRate
+            | SubCmd::Errors
+            | SubCmd::Exec
+            | SubCmd::Fd
+            | SubCmd::FdPeak
+            | SubCmd::Files
+            | SubCmd::Permissions
+            | SubCmd::Poll
+            | SubCmd::Security
+            | SubCmd::Tree
+            | SubCmd::Zombies
+    )
+}
+
 fn select_pids(
     args: &ArgMatches,
     session_summary: &SessionSummary,
 ) -> Result<Vec<Pid>, Box<dyn Error>> {
-    if args.value_of("pid").is_some() {
-        let pid_strs: HashSet<_> = args.values_of("pid").ok_or("No pids entered")?.collect();
-
-        let pids: Vec<_> = pid_strs
-            .into_iter()
-            .filter_map(|p| p.parse::<Pid>().ok())
-            .collect();
-
+    if let Some(pids) = requested_pids(args)? {
         let checked_pids = session_summary.validate_pids(&pids)?;
 
         if args.is_present("related") {
@@ -187,3 +1574,52 @@ fn select_pids(
         Ok(session_summary.pids())
     }
 }
+
+// Every PID the subcommands asked for, if each of them asked for PIDs by
+// number or from a file. PIDs from stdin can only be read once, when the
+// subcommand runs, and '--related' and '--threads' need the PIDs around them.
+fn prefilter_pids(
+    subcmds: &[(SubCmd, &ArgMatches)],
+) -> Result<Option<HashSet<Pid>>, Box<dyn Error>> {
+    let mut pids = HashSet::default();
+    for (_, args) in subcmds {
+        let from_stdin = args
+            .values_of("pid")
+            .is_some_and(|mut p| p.any(|p| p == "-"));
+        if from_stdin || args.is_present("related") || args.is_present("threads") {
+            return Ok(None);
+        }
+        match requested_pids(args)? {
+            Some(requested) => pids.extend(requested),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(pids))
+}
+
+// PIDs from '--pid-file', or '--pid', where '-' reads a list from stdin
+fn requested_pids(args: &ArgMatches) -> Result<Option<Vec<Pid>>, Box<dyn Error>> {
+    if let Some(path) = args.value_of("pid_file") {
+        let text = fs::read_to_string(path)?;
+        return Ok(Some(pid_list::parse(&text)?));
+    }
+
+    let pid_strs: HashSet<_> = match args.values_of("pid") {
+        Some(values) => values.collect(),
+        None => return Ok(None),
+    };
+
+    let mut pids = Vec::new();
+    for pid_str in pid_strs {
+        if pid_str == "-" {
+            let mut text = String::new();
+            stdin().read_to_string(&mut text)?;
+            pids.extend(pid_list::parse(&text)?);
+        } else if let Ok(pid) = pid_str.parse::<Pid>() {
+            pids.push(pid);
+        }
+    }
+
+    Ok(Some(pids))
+}
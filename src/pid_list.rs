@@ -0,0 +1,98 @@
+use parser::Pid;
+
+/// Parses a list of PIDs, one per line. Blank lines and anything after a '#'
+/// are ignored.
+pub fn parse(text: &str) -> Result<Vec<Pid>, String> {
+    let mut pids = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(pos) => &line[..pos],
+            None => line,
+        }
+        .trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match line.parse::<Pid>() {
+            Ok(pid) => pids.push(pid),
+            Err(_) => return Err(format!("Invalid PID '{}' on line {}", line, idx + 1)),
+        }
+    }
+
+    Ok(pids)
+}
+
+/// Parses a single 'PID=NAME' label
+pub fn parse_label(text: &str) -> Option<(Pid, String)> {
+    let (pid, name) = text.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    Some((pid.trim().parse::<Pid>().ok()?, name.to_string()))
+}
+
+/// Parses labels of the form 'PID=NAME', one per line. Blank lines and
+/// anything after a '#' are ignored.
+pub fn parse_labels(text: &str) -> Result<Vec<(Pid, String)>, String> {
+    let mut labels = Vec::new();
+
+    for (idx, line) in text.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(pos) => &line[..pos],
+            None => line,
+        }
+        .trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        match parse_label(line) {
+            Some(label) => labels.push(label),
+            None => return Err(format!("Invalid label '{}' on line {}", line, idx + 1)),
+        }
+    }
+
+    Ok(labels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pid_list_skips_comments_and_blank_lines() {
+        let input = "# candidates from runbook\n477\n\n  495  \n567 # sidekiq\n";
+        assert_eq!(parse(input), Ok(vec![477, 495, 567]));
+    }
+
+    #[test]
+    fn pid_list_parses_labels() {
+        let input = "# gitlab web\n823=unicorn-worker-1\n477 = wrapper # parent\n";
+        assert_eq!(
+            parse_labels(input),
+            Ok(vec![
+                (823, String::from("unicorn-worker-1")),
+                (477, String::from("wrapper"))
+            ])
+        );
+        assert_eq!(
+            parse_labels("823\n"),
+            Err(String::from("Invalid label '823' on line 1"))
+        );
+    }
+
+    #[test]
+    fn pid_list_reports_invalid_line() {
+        let input = "477\nsidekiq\n";
+        assert_eq!(
+            parse(input),
+            Err(String::from("Invalid PID 'sidekiq' on line 2"))
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use crate::cli;
+use crate::env_defaults;
+use clap::ErrorKind;
+use std::ffi::OsString;
+
+/// Splits a command line naming several subcommands, such as
+/// '<INPUT> summary files --sort time io -p 823', into one command line per
+/// subcommand, each with <INPUT> and the options given before the first.
+///
+/// A word naming a subcommand starts a new one when the words before it form
+/// a complete command line, otherwise it's taken as the value of an option.
+/// Options left unset are filled in from the environment, see
+/// `env_defaults::with_env_defaults`.
+pub fn split(args: &[OsString]) -> Result<Vec<Vec<OsString>>, clap::Error> {
+    let starts: Vec<usize> = args
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, arg)| arg.to_str().is_some_and(is_subcommand))
+        .map(|(idx, _)| idx)
+        .collect();
+
+    // Without a second subcommand clap reports any errors as usual
+    let (first, rest) = match starts.split_first() {
+        Some((&first, rest)) if !rest.is_empty() => (first, rest),
+        _ => {
+            return Ok(vec![env_defaults::with_env_defaults(args.to_vec())?]);
+        }
+    };
+    let prefix = &args[..first];
+
+    let mut cmd_lines = Vec::new();
+    let mut start = first;
+    // The first error is the one to report, later ones come from the words
+    // merged into a command line because of it
+    let mut first_err = None;
+    for &end in rest {
+        let cmd_line = [prefix, &args[start..end]].concat();
+        match cli::cli_args().get_matches_from_safe(&cmd_line) {
+            Ok(_) => {
+                cmd_lines.push(cmd_line);
+                start = end;
+            }
+            Err(e) => {
+                first_err.get_or_insert(e);
+            }
+        }
+    }
+
+    let cmd_line = [prefix, &args[start..]].concat();
+    match cli::cli_args().get_matches_from_safe(&cmd_line) {
+        Ok(_) => {
+            cmd_lines.push(cmd_line);
+            cmd_lines
+                .into_iter()
+                .map(env_defaults::with_env_defaults)
+                .collect()
+        }
+        Err(e) => Err(first_err.unwrap_or(e)),
+    }
+}
+
+// Asking for a word's help only succeeds for subcommands, and flags, which
+// show the main help
+fn is_subcommand(word: &str) -> bool {
+    if word.starts_with('-') {
+        return false;
+    }
+
+    let probe = ["strace-parser", "INPUT", word, "--help"];
+    match cli::cli_args().get_matches_from_safe(probe) {
+        Err(e) => e.kind == ErrorKind::HelpDisplayed,
+        Ok(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_words(line: &str) -> Result<Vec<String>, clap::Error> {
+        let args: Vec<OsString> = line.split_whitespace().map(OsString::from).collect();
+        let cmd_lines = split(&args)?;
+
+        Ok(cmd_lines
+            .iter()
+            .map(|cmd_line| {
+                let words: Vec<_> = cmd_line.iter().map(|w| w.to_string_lossy()).collect();
+                words.join(" ")
+            })
+            .collect())
+    }
+
+    #[test]
+    fn pipeline_splits_at_subcommands() {
+        assert_eq!(
+            split_words("strace-parser -q trace.log summary files --sort time io -p 823").unwrap(),
+            vec![
+                "strace-parser -q trace.log summary",
+                "strace-parser -q trace.log files --sort time",
+                "strace-parser -q trace.log io -p 823",
+            ]
+        );
+        assert_eq!(
+            split_words("strace-parser trace.log tree").unwrap(),
+            vec!["strace-parser trace.log tree"]
+        );
+
+        // The first mistake is reported, not the subcommand it left unparsed
+        let err = split_words("strace-parser trace.log summary --bogus io").unwrap_err();
+        assert!(err.message.contains("--bogus"));
+        assert!(split_words("strace-parser trace.log summary bogus").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use parser::output;
+use std::io::{prelude::*, stdout, Error};
+
+/// Reads commands from `input` until it ends or 'quit' is entered, passing
+/// the words of each to `run_cmd`. A prompt is printed when `interactive`.
+pub fn run<R, F>(mut input: R, interactive: bool, mut run_cmd: F) -> Result<(), Error>
+where
+    R: BufRead,
+    F: FnMut(&[&str]),
+{
+    if interactive && !output::quiet() {
+        writeln!(
+            stdout(),
+            "Enter a subcommand such as 'summary' or 'pid 823', 'help' to list them, or 'quit' to exit"
+        )?;
+    }
+
+    let mut line = String::new();
+    loop {
+        if interactive {
+            write!(stdout(), "> ")?;
+            stdout().flush()?;
+        }
+
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["quit"] | ["exit"] => break,
+            // The repl is already reading stdin
+            _ if words.contains(&"-") => {
+                eprintln!("PIDs can't be read from stdin in the repl, list them or use --pid-file")
+            }
+            _ => run_cmd(&words),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repl_runs_commands_until_quit() {
+        let input = "summary -c 5\n\n  pid 823  \nquit\nfiles\n";
+        let mut cmds = Vec::new();
+
+        run(input.as_bytes(), false, |words| cmds.push(words.join(" "))).unwrap();
+
+        assert_eq!(cmds, vec!["summary -c 5", "pid 823"]);
+    }
+
+    #[test]
+    fn repl_skips_pids_from_stdin() {
+        let input = "pid -\nio -p 823";
+        let mut cmds = Vec::new();
+
+        run(input.as_bytes(), false, |words| cmds.push(words.join(" "))).unwrap();
+
+        assert_eq!(cmds, vec!["io -p 823"]);
+    }
+}
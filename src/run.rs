@@ -0,0 +1,140 @@
+use crate::cli;
+use crate::pipeline;
+use clap::ArgMatches;
+use parser::output;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+// The flags the parser relies on, see the README
+const STRACE_FLAGS: &[&str] = &["-fttTyyy", "-s", "1024"];
+
+/// The matches of 'strace-parser run ...' or 'strace-parser report ...', or
+/// `None` if `args` don't start with either. Only used once `args` failed to
+/// parse as a trace and its subcommands.
+pub fn command(args: &[OsString]) -> Option<Result<ArgMatches<'static>, clap::Error>> {
+    match args.get(1) {
+        Some(arg) if arg == "run" || arg == "report" => {
+            Some(cli::command_args().get_matches_from_safe(args))
+        }
+        _ => None,
+    }
+}
+
+/// The command line for 'strace-parser report', the words after 'report' with
+/// 'summary' as the subcommand when none is given
+pub fn report_args(matches: &ArgMatches) -> Vec<OsString> {
+    let mut cmd_line = vec![OsString::from("strace-parser")];
+    cmd_line.extend(
+        matches
+            .values_of_os("EXTRACT")
+            .unwrap_or_default()
+            .map(OsString::from),
+    );
+    if let Err(e) = cli::cli_args().get_matches_from_safe(&cmd_line) {
+        if e.kind == clap::ErrorKind::MissingSubcommand {
+            cmd_line.push("summary".into());
+        }
+    }
+
+    cmd_line
+}
+
+/// Traces the command given in `matches` until it exits, then reports on the
+/// trace with `execute`. Returns the report's exit status.
+pub fn run<F>(matches: &ArgMatches, execute: F) -> Result<i32, Box<dyn Error>>
+where
+    F: FnOnce(Vec<ArgMatches>) -> Result<i32, Box<dyn Error>>,
+{
+    // strace opens the trace file by name, so a temporary one is created with
+    // a random name first rather than at a path another user could guess and
+    // put a symlink at. It's removed when dropped.
+    let temp_file;
+    let trace_file = match matches.value_of_os("output") {
+        Some(path) => PathBuf::from(path),
+        None => {
+            temp_file = tempfile::Builder::new()
+                .prefix("strace-parser-")
+                .suffix(".trace")
+                .tempfile()?;
+            temp_file.path().to_path_buf()
+        }
+    };
+    let strace = matches
+        .value_of_os("strace")
+        .unwrap_or_else(|| "strace".as_ref());
+    let command: Vec<_> = matches
+        .values_of_os("COMMAND")
+        .unwrap_or_default()
+        .collect();
+
+    let status = match trace(strace, &trace_file, &command) {
+        Ok(status) => status,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(format!(
+                "Error: couldn't run '{}', is strace installed?",
+                strace.to_string_lossy()
+            )
+            .into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut cmd_line = vec![OsString::from("strace-parser"), trace_file.clone().into()];
+    cmd_line.extend(
+        matches
+            .value_of("report")
+            .unwrap_or("summary")
+            .split_whitespace()
+            .map(OsString::from),
+    );
+
+    let result = match pipeline::split(&cmd_line) {
+        Ok(cmd_lines) => execute(
+            cmd_lines
+                .into_iter()
+                .map(|cmd_line| cli::cli_args().get_matches_from(cmd_line))
+                .collect(),
+        ),
+        Err(e) => Err(e.message.into()),
+    };
+
+    if !status.success() {
+        let command = command
+            .iter()
+            .map(|word| word.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let exit = match status.code() {
+            Some(code) => format!("status {}", code),
+            None => status.to_string(),
+        };
+        output::print_note(&format!("\n  Note: '{}' exited with {}\n", command, exit))?;
+    }
+
+    result
+}
+
+// Interrupting the command, e.g. with ctrl-c to stop a server, ends the trace
+// but should still produce a report, so the parser ignores SIGINT meanwhile
+fn trace(strace: &OsStr, trace_file: &Path, command: &[&OsStr]) -> Result<ExitStatus, io::Error> {
+    let mut child = Command::new(strace)
+        .args(STRACE_FLAGS)
+        .arg("-o")
+        .arg(trace_file)
+        .arg("--")
+        .args(command)
+        .spawn()?;
+
+    #[cfg(unix)]
+    let previous = unsafe { libc::signal(libc::SIGINT, libc::SIG_IGN) };
+    let status = child.wait();
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGINT, previous);
+    }
+
+    status
+}